@@ -0,0 +1,250 @@
+//! Parallel decompression of BGZF files (`bgzip`, as written by htslib and
+//! some log shippers). BGZF is a series of small gzip members, each giving its
+//! own compressed size in a header field, so the members can be cut apart
+//! without inflating them and inflated on several threads.
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Read},
+    thread,
+};
+
+use crossbeam_channel::{bounded, Receiver};
+use flate2::read::MultiGzDecoder;
+
+/// Blocks inflated together by one thread: up to 4 MiB of compressed data
+/// (a block is at most 64 KiB either side).
+const JOB_BLOCKS: usize = 64;
+
+/// Fixed part of a gzip header, up to and including XLEN.
+const HEADER: usize = 12;
+
+type Chunk = (u64, io::Result<Vec<u8>>);
+
+/// True if `head` starts with a BGZF block header.
+pub(crate) fn is_bgzf(head: &[u8]) -> bool {
+    if head.len() < HEADER || !is_extra_header(head) {
+        return false;
+    }
+    let xlen = u16::from_le_bytes([head[10], head[11]]) as usize;
+    head.get(HEADER..HEADER + xlen)
+        .and_then(block_size)
+        .is_some()
+}
+
+/// Gzip magic, deflate, and the FEXTRA flag set.
+fn is_extra_header(h: &[u8]) -> bool {
+    h[..3] == [0x1F, 0x8B, 8] && h[3] & 4 != 0
+}
+
+/// The whole block's size from its `BC` extra subfield.
+fn block_size(mut extra: &[u8]) -> Option<usize> {
+    while extra.len() >= 4 {
+        let len = u16::from_le_bytes([extra[2], extra[3]]) as usize;
+        let data = extra.get(4..4 + len)?;
+        if extra[..2] == *b"BC" && len == 2 {
+            return Some(u16::from_le_bytes([data[0], data[1]]) as usize + 1);
+        }
+        extra = &extra[4 + len..];
+    }
+    None
+}
+
+/// Reads up to `JOB_BLOCKS` whole blocks; empty at EOF.
+fn read_blocks(r: &mut impl Read, offset: &mut u64) -> io::Result<Vec<u8>> {
+    let mut job = Vec::new();
+    for _ in 0..JOB_BLOCKS {
+        let start = job.len();
+        job.resize(start + HEADER, 0);
+        let n = read_full(r, &mut job[start..])?;
+        if n == 0 {
+            job.truncate(start);
+            break;
+        }
+        let invalid = |what: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{what} at byte {offset} of a BGZF file"),
+            )
+        };
+        if n < HEADER || !is_extra_header(&job[start..]) {
+            return Err(invalid("truncated or non-BGZF block"));
+        }
+
+        let xlen = u16::from_le_bytes([job[start + 10], job[start + 11]]) as usize;
+        job.resize(start + HEADER + xlen, 0);
+        r.read_exact(&mut job[start + HEADER..])?;
+        let size = block_size(&job[start + HEADER..])
+            .filter(|&size| size > HEADER + xlen)
+            .ok_or_else(|| invalid("block without a BC size field"))?;
+
+        job.resize(start + size, 0);
+        r.read_exact(&mut job[start + HEADER + xlen..])
+            .map_err(|_| invalid("truncated block"))?;
+        *offset += size as u64;
+    }
+    Ok(job)
+}
+
+/// `read` until `buf` is full or EOF; the number of bytes read.
+fn read_full(r: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match r.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(k) => n += k,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(n)
+}
+
+/// Decompressed BGZF, inflated on `threads` threads and handed out in order.
+///
+/// One thread cuts the input into jobs of whole blocks; the others inflate
+/// them (checking each block's CRC). Threads stop once the reader is dropped.
+pub(crate) struct ParallelBgzf {
+    chunks: Receiver<Chunk>,
+    /// Chunks that arrived ahead of `next`.
+    early: BTreeMap<u64, io::Result<Vec<u8>>>,
+    next: u64,
+    current: Vec<u8>,
+    pos: usize,
+}
+
+impl ParallelBgzf {
+    pub(crate) fn new<R: Read + Send + 'static>(mut input: R, threads: usize) -> Self {
+        let threads = threads.max(1);
+        let (tx_jobs, rx_jobs) = bounded::<Chunk>(threads * 2);
+        let (tx_chunks, rx_chunks) = bounded::<Chunk>(threads * 2);
+
+        thread::spawn(move || {
+            let mut offset = 0;
+            for seq in 0.. {
+                let job = read_blocks(&mut input, &mut offset);
+                if job.as_ref().is_ok_and(Vec::is_empty) {
+                    break;
+                }
+                let failed = job.is_err();
+                if tx_jobs.send((seq, job)).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        for _ in 0..threads {
+            let (rx, tx) = (rx_jobs.clone(), tx_chunks.clone());
+            thread::spawn(move || {
+                for (seq, job) in rx {
+                    let chunk = job.and_then(|data| {
+                        let mut out = Vec::with_capacity(JOB_BLOCKS << 16);
+                        MultiGzDecoder::new(&data[..]).read_to_end(&mut out)?;
+                        Ok(out)
+                    });
+                    if tx.send((seq, chunk)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+
+        Self {
+            chunks: rx_chunks,
+            early: BTreeMap::new(),
+            next: 0,
+            current: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// The next chunk in order, or none at the end.
+    fn next_chunk(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            if let Some(chunk) = self.early.remove(&self.next) {
+                self.next += 1;
+                return chunk.map(Some);
+            }
+            match self.chunks.recv() {
+                Ok((seq, chunk)) => {
+                    self.early.insert(seq, chunk);
+                }
+                Err(_) if self.early.is_empty() => return Ok(None),
+                Err(_) => return Err(io::Error::other("BGZF block lost by a worker")),
+            }
+        }
+    }
+}
+
+impl Read for ParallelBgzf {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.current.len() {
+            match self.next_chunk()? {
+                Some(chunk) => (self.current, self.pos) = (chunk, 0),
+                None => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.current.len() - self.pos);
+        buf[..n].copy_from_slice(&self.current[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use flate2::{write::DeflateEncoder, Compression, Crc};
+    use std::io::Write;
+
+    /// `data` as BGZF, in blocks of `block` uncompressed bytes, with the
+    /// empty end-of-file block.
+    pub(crate) fn bgzip(data: &[u8], block: usize) -> Vec<u8> {
+        let mut out = Vec::new();
+        for chunk in data.chunks(block).chain([&[][..]]) {
+            let mut enc = DeflateEncoder::new(Vec::new(), Compression::fast());
+            enc.write_all(chunk).unwrap();
+            let deflated = enc.finish().unwrap();
+            let mut crc = Crc::new();
+            crc.update(chunk);
+
+            let size = (18 + deflated.len() + 8 - 1) as u16;
+            out.extend_from_slice(&[
+                0x1F, 0x8B, 8, 4, 0, 0, 0, 0, 0, 0xFF, 6, 0, b'B', b'C', 2, 0,
+            ]);
+            out.extend_from_slice(&size.to_le_bytes());
+            out.extend_from_slice(&deflated);
+            out.extend_from_slice(&crc.sum().to_le_bytes());
+            out.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+        }
+        out
+    }
+
+    #[test]
+    fn inflates_blocks_in_order_on_several_threads() {
+        let data: Vec<u8> = (0..200_000)
+            .flat_map(|i| format!("line {i}\n").into_bytes())
+            .collect();
+        let gz = bgzip(&data, 10_000);
+        assert!(is_bgzf(&gz));
+
+        let mut out = Vec::new();
+        ParallelBgzf::new(io::Cursor::new(gz), 3)
+            .read_to_end(&mut out)
+            .unwrap();
+        assert_eq!(out, data);
+    }
+
+    #[test]
+    fn plain_gzip_is_not_bgzf_and_damage_is_an_error() {
+        let mut enc = flate2::write::GzEncoder::new(Vec::new(), Compression::fast());
+        enc.write_all(b"hello\n").unwrap();
+        assert!(!is_bgzf(&enc.finish().unwrap()));
+
+        let mut gz = bgzip(b"hello\nworld\n", 6);
+        gz.truncate(gz.len() - 40);
+        let mut out = Vec::new();
+        assert!(ParallelBgzf::new(io::Cursor::new(gz), 2)
+            .read_to_end(&mut out)
+            .is_err());
+    }
+}
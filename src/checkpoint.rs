@@ -0,0 +1,131 @@
+//! `--checkpoint state.json` / `--resume`: how far a long run got, so an
+//! interrupted one continues from there instead of starting over.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// A save point: every record of `input` before `input_offset` is in the
+/// first `output_offset` bytes of `output`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub module: String,
+    pub input: PathBuf,
+    pub output: PathBuf,
+    /// Counted after decompression for `.gz` inputs.
+    pub input_offset: u64,
+    pub output_offset: u64,
+    /// RFC 3339, UTC.
+    pub updated_at: String,
+}
+
+impl Checkpoint {
+    /// The checkpoint at `path`, if there is one.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("read {}", path.display())),
+        };
+        serde_json::from_str(&text)
+            .map(Some)
+            .with_context(|| format!("parse checkpoint {}", path.display()))
+    }
+
+    /// Write to a temporary file renamed over `path`, so a crash while
+    /// saving leaves the previous checkpoint intact.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(self)?)
+            .with_context(|| format!("write checkpoint {}", path.display()))?;
+        std::fs::rename(&tmp, path).with_context(|| format!("write checkpoint {}", path.display()))
+    }
+
+    /// Fails unless this checkpoint was made by the same module, reading and
+    /// writing the same files.
+    pub fn check(&self, module: &str, input: &Path, output: &Path) -> Result<()> {
+        if self.module != module {
+            anyhow::bail!("checkpoint is for module {}, not {module}", self.module);
+        }
+        for (saved, given) in [(&self.input, input), (&self.output, output)] {
+            if !same_file(saved, given) {
+                anyhow::bail!(
+                    "checkpoint is for {}, not {}",
+                    saved.display(),
+                    given.display()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Cut the output back to `output_offset`, dropping records written
+    /// after the save.
+    pub fn truncate_output(&self) -> Result<()> {
+        let fh = std::fs::OpenOptions::new()
+            .write(true)
+            .open(&self.output)
+            .with_context(|| format!("open {}", self.output.display()))?;
+        let len = fh.metadata()?.len();
+        if len < self.output_offset {
+            anyhow::bail!(
+                "{} is shorter ({len} bytes) than the checkpoint says ({})",
+                self.output.display(),
+                self.output_offset
+            );
+        }
+        fh.set_len(self.output_offset)
+            .with_context(|| format!("truncate {}", self.output.display()))
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saves_loads_and_truncates_the_output() {
+        let dir = std::env::temp_dir().join(format!("turbolp-{}-checkpoint", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (state, input, output) = (
+            dir.join("state.json"),
+            dir.join("in.log"),
+            dir.join("out.jsonl"),
+        );
+        std::fs::write(&input, "a\nb\n").unwrap();
+        std::fs::write(&output, "{\"a\":1}\n{\"b\":2}\n{\"c\"").unwrap();
+
+        assert_eq!(Checkpoint::load(&state).unwrap(), None);
+        let cp = Checkpoint {
+            module: "echo".into(),
+            input: input.clone(),
+            output: output.clone(),
+            input_offset: 4,
+            output_offset: 16,
+            updated_at: "2024-01-02T03:04:05Z".into(),
+        };
+        cp.save(&state).unwrap();
+        let loaded = Checkpoint::load(&state).unwrap().unwrap();
+        assert_eq!(loaded, cp);
+
+        loaded.check("echo", &input, &output).unwrap();
+        assert!(loaded.check("other", &input, &output).is_err());
+        assert!(loaded.check("echo", &output, &output).is_err());
+
+        loaded.truncate_output().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "{\"a\":1}\n{\"b\":2}\n"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,268 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::{
+    core::OnError,
+    encoding::Encoding,
+    enrich::lookup::split_spec,
+    schema::Schema,
+    sink::{parse_size, KafkaCompression, OutputCompression, OutputFormat, RemoteSink},
+};
+
+/// A `run` described in a TOML file (`--config run.toml`).
+///
+/// Every key mirrors the `run` flag of the same name; flags given on the
+/// command line take precedence. Relative paths are resolved against the
+/// directory holding the config file.
+///
+/// ```toml
+/// module = "web-access"
+/// input_dir = "extract_uac/apache"
+/// recursive = true
+/// ext = ["log", "gz"]
+/// output = "out/"
+/// workers = 8
+/// output_compress = "zstd"
+/// max_output_size = "1G"
+///
+/// [options]
+/// fast_time = true
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RunConfig {
+    pub module: Option<String>,
+    pub multiline_start: Option<String>,
+    #[serde(default)]
+    pub geoip: Vec<PathBuf>,
+    #[serde(default)]
+    pub ip_field: Vec<String>,
+    #[serde(default)]
+    pub parse_ua: bool,
+    #[serde(default)]
+    pub ua_field: Vec<String>,
+    #[serde(default)]
+    pub ioc: Vec<PathBuf>,
+    /// Sigma rules or rule directories, as with `--sigma`.
+    #[serde(default)]
+    pub sigma: Vec<PathBuf>,
+    #[serde(default)]
+    pub sigma_only: bool,
+    #[serde(default)]
+    pub networks: Vec<PathBuf>,
+    /// `field=file.csv:key_column`, as with `--lookup`.
+    #[serde(default)]
+    pub lookup: Vec<String>,
+    /// `--transform` statements, run in order.
+    #[serde(default)]
+    pub transform: Vec<String>,
+    /// `--where` expression.
+    #[serde(rename = "where")]
+    pub filter: Option<String>,
+    #[serde(default)]
+    pub fields: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    pub schema: Option<Schema>,
+    pub input: Option<PathBuf>,
+    pub input_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub recursive: bool,
+    #[serde(default)]
+    pub ext: Vec<String>,
+    pub output: Option<PathBuf>,
+    pub format: Option<OutputFormat>,
+    #[serde(default)]
+    pub prefix_input_hash: bool,
+    pub output_compress: Option<OutputCompression>,
+    pub max_output_size: Option<SizeValue>,
+    pub max_output_records: Option<u64>,
+    pub es_index: Option<String>,
+    pub es_url: Option<String>,
+    pub es_api_key: Option<String>,
+    pub es_user: Option<String>,
+    pub es_batch_size: Option<SizeValue>,
+    pub sink: Option<RemoteSink>,
+    pub hec_url: Option<String>,
+    pub hec_token: Option<String>,
+    pub hec_sourcetype: Option<String>,
+    pub hec_index: Option<String>,
+    pub hec_source: Option<String>,
+    pub hec_batch_size: Option<SizeValue>,
+    #[serde(default)]
+    pub kafka_brokers: Vec<String>,
+    pub kafka_topic: Option<String>,
+    pub kafka_key: Option<String>,
+    pub kafka_compression: Option<KafkaCompression>,
+    pub kafka_batch_size: Option<SizeValue>,
+    pub rejects: Option<PathBuf>,
+    pub on_error: Option<OnError>,
+    #[serde(default)]
+    pub ordered: bool,
+    #[serde(default)]
+    pub follow: bool,
+    pub report: Option<PathBuf>,
+    pub checkpoint: Option<PathBuf>,
+    #[serde(default)]
+    pub resume: bool,
+    #[serde(default)]
+    pub no_progress: bool,
+    #[serde(default)]
+    pub no_mmap: bool,
+    pub encoding: Option<Encoding>,
+    #[serde(default)]
+    pub provenance: bool,
+    pub workers: Option<usize>,
+    /// Module options, as with `--opt key=value`.
+    #[serde(default)]
+    pub options: BTreeMap<String, toml::Value>,
+}
+
+/// A size written either as a byte count or a string with a unit (`"512M"`).
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum SizeValue {
+    Bytes(u64),
+    Text(String),
+}
+
+impl SizeValue {
+    pub fn bytes(&self) -> Result<u64> {
+        match self {
+            SizeValue::Bytes(n) => Ok(*n),
+            SizeValue::Text(s) => parse_size(s),
+        }
+    }
+}
+
+impl RunConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("read config {}", path.display()))?;
+        let mut cfg =
+            Self::parse(&text).with_context(|| format!("parse config {}", path.display()))?;
+
+        if let Some(base) = path.parent() {
+            cfg.resolve_paths(base);
+        }
+        Ok(cfg)
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    /// Make relative paths relative to `base` instead of the working directory.
+    fn resolve_paths(&mut self, base: &Path) {
+        for p in [
+            &mut self.input,
+            &mut self.input_dir,
+            &mut self.output,
+            &mut self.rejects,
+            &mut self.checkpoint,
+        ]
+        .into_iter()
+        .flatten()
+        .chain(&mut self.geoip)
+        .chain(&mut self.ioc)
+        .chain(&mut self.sigma)
+        .chain(&mut self.networks)
+        {
+            if p.is_relative() && p.as_os_str() != crate::core::STDIN_PATH {
+                *p = base.join(&*p);
+            }
+        }
+        for spec in &mut self.lookup {
+            if let Ok((field, path, key)) = split_spec(spec)
+                && Path::new(path).is_relative()
+            {
+                let path = base.join(path);
+                *spec = match key {
+                    Some(key) => format!("{field}={}:{key}", path.display()),
+                    None => format!("{field}={}", path.display()),
+                };
+            }
+        }
+    }
+
+    /// `[options]` rendered as `key=value` strings, ready for `ModuleOptions::parse`.
+    /// Arrays become comma-separated lists.
+    pub fn module_options(&self) -> Result<Vec<String>> {
+        self.options
+            .iter()
+            .map(|(k, v)| Ok(format!("{k}={}", option_value(k, v)?)))
+            .collect()
+    }
+}
+
+fn option_value(key: &str, v: &toml::Value) -> Result<String> {
+    Ok(match v {
+        toml::Value::String(s) => s.clone(),
+        toml::Value::Integer(i) => i.to_string(),
+        toml::Value::Float(f) => f.to_string(),
+        toml::Value::Boolean(b) => b.to_string(),
+        toml::Value::Array(items) => items
+            .iter()
+            .map(|i| option_value(key, i))
+            .collect::<Result<Vec<_>>>()?
+            .join(","),
+        other => anyhow::bail!("option {key}: unsupported value {other}"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_run_config_with_options() {
+        let cfg = RunConfig::parse(
+            r#"
+            module = "csv"
+            input = "export.csv"
+            workers = 4
+            output_compress = "zstd"
+            max_output_size = "64K"
+
+            [options]
+            headers = ["ts", "src"]
+            delim = ";"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(cfg.module.as_deref(), Some("csv"));
+        assert_eq!(cfg.workers, Some(4));
+        assert_eq!(cfg.output_compress, Some(OutputCompression::Zstd));
+        assert_eq!(
+            cfg.max_output_size.as_ref().unwrap().bytes().unwrap(),
+            64 << 10
+        );
+        assert_eq!(
+            cfg.module_options().unwrap(),
+            vec!["delim=;".to_string(), "headers=ts,src".to_string()]
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        assert!(RunConfig::parse("modul = \"web-access\"").is_err());
+    }
+
+    #[test]
+    fn relative_paths_follow_the_config_file() {
+        let mut cfg = RunConfig::parse(
+            "input = \"a.log\"\noutput = \"/abs/out.jsonl\"\nlookup = [\"user=users.csv:name\"]",
+        )
+        .unwrap();
+        cfg.resolve_paths(Path::new("/jobs"));
+        assert_eq!(cfg.input, Some(PathBuf::from("/jobs/a.log")));
+        assert_eq!(cfg.output, Some(PathBuf::from("/abs/out.jsonl")));
+        assert_eq!(cfg.lookup, ["user=/jobs/users.csv:name"]);
+    }
+}
@@ -1,225 +1,3230 @@
-use std::{
-    borrow::Cow,
-    fs::File,
-    io::{BufRead, BufReader, Read, Seek, Write},
-    path::Path,
-    sync::Arc,
-    thread,
-};
-
-use anyhow::{Context, Result};
-use crossbeam_channel::{bounded, Receiver, Sender};
-use flate2::read::GzDecoder;
-use memchr::memchr_iter;
-
-/* -------------------- Parser trait -------------------- */
-
-/// Every module only needs to process one line and append JSONL to `out`.
-pub trait Parser: Send + Sync {
-    fn name(&self) -> Cow<'static, str>;
-    fn description(&self) -> Cow<'static, str>;
-
-    /// Return true if a JSONL record was emitted.
-    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool;
-}
-
-/* -------------------- Gzip / IO helpers -------------------- */
-
-const READER_BUF: usize = 1 << 20; // 1 MiB
-
-/// Return a **BufRead** that transparently decompresses `.gz` if needed.
-pub fn open_maybe_gz_bufread(path: &Path, buf_size: usize) -> Result<Box<dyn BufRead + Send>> {
-    let mut fh = File::open(path).with_context(|| format!("open {}", path.display()))?;
-
-    // Peek gzip magic 0x1F 0x8B
-    let mut magic = [0u8; 2];
-    let n = fh.read(&mut magic)?;
-    fh.rewind()?;
-
-    if n == 2 && magic == [0x1F, 0x8B] {
-        let gz = GzDecoder::new(fh);
-        Ok(Box::new(BufReader::with_capacity(buf_size, gz)))
-    } else {
-        Ok(Box::new(BufReader::with_capacity(buf_size, fh)))
-    }
-}
-
-/// Return a **Read** that transparently decompresses `.gz` if needed
-/// (useful for fast scanning / counting).
-pub fn open_maybe_gz_read(path: &Path) -> Result<Box<dyn Read + Send>> {
-    let mut fh = File::open(path).with_context(|| format!("open {}", path.display()))?;
-
-    let mut magic = [0u8; 2];
-    let n = fh.read(&mut magic)?;
-    fh.rewind()?;
-
-    if n == 2 && magic == [0x1F, 0x8B] {
-        Ok(Box::new(GzDecoder::new(fh)))
-    } else {
-        Ok(Box::new(fh))
-    }
-}
-
-/// True if file starts with gzip magic bytes.
-pub fn is_gzip(path: &Path) -> Result<bool> {
-    let mut fh = File::open(path).with_context(|| format!("open {}", path.display()))?;
-    let mut magic = [0u8; 2];
-    let n = fh.read(&mut magic)?;
-    Ok(n == 2 && magic == [0x1F, 0x8B])
-}
-
-/* -------------------- High-throughput streaming runner -------------------- */
-
-/// High-throughput streaming runner (multithreaded only).
-pub fn run_streaming_parallel(
-    parser: &dyn Parser,
-    input: &Path,
-    writer: Box<dyn Write + Send>,
-    workers: usize,
-) -> Result<usize> {
-    const BYTES_BLOB_TARGET: usize = 4 << 20; // 4 MiB
-    const LINES_BLOB_MAX: usize = 16_384;
-    const LINES_CHAN_FACTOR: usize = 64;
-
-    let (tx_lines, rx_lines): (Sender<Vec<u8>>, Receiver<Vec<u8>>) =
-        bounded(workers * LINES_CHAN_FACTOR);
-    let (tx_blobs, rx_blobs): (Sender<Vec<u8>>, Receiver<Vec<u8>>) = bounded(workers * 4);
-    let (tx_counts, rx_counts): (Sender<usize>, Receiver<usize>) = bounded(workers);
-
-    // Writer thread
-    let writer_handle = thread::spawn(move || -> Result<()> {
-        let mut w = std::io::BufWriter::with_capacity(32 << 20, writer);
-        for blob in rx_blobs.iter() {
-            w.write_all(&blob)?;
-        }
-        w.flush()?;
-        Ok(())
-    });
-
-    // Share parser safely (parser lives for the whole run)
-    let parser_ref: &'static dyn Parser = unsafe { std::mem::transmute(parser as &dyn Parser) };
-    let parser_arc = Arc::new(parser_ref);
-
-    // Workers
-    let mut handles = Vec::with_capacity(workers);
-    for _ in 0..workers {
-        let rx = rx_lines.clone();
-        let tx_b = tx_blobs.clone();
-        let tx_c = tx_counts.clone();
-        let p = parser_arc.clone();
-
-        handles.push(thread::spawn(move || {
-            let mut local_count = 0usize;
-            let mut blob = Vec::with_capacity(BYTES_BLOB_TARGET);
-            let mut lines_in_blob = 0usize;
-
-            for line_bytes in rx.iter() {
-                if let Ok(mut s) = std::str::from_utf8(&line_bytes) {
-                    if s.as_bytes().last().copied() == Some(b'\n') {
-                        s = &s[..s.len() - 1];
-                    }
-                    if s.as_bytes().last().copied() == Some(b'\r') {
-                        s = &s[..s.len() - 1];
-                    }
-                    if p.process_line_to_buf(s, &mut blob) {
-                        local_count += 1;
-                        lines_in_blob += 1;
-                    }
-                }
-                if blob.len() >= BYTES_BLOB_TARGET || lines_in_blob >= LINES_BLOB_MAX {
-                    if tx_b.send(std::mem::take(&mut blob)).is_err() {
-                        break;
-                    }
-                    blob.reserve(BYTES_BLOB_TARGET);
-                    lines_in_blob = 0;
-                }
-            }
-
-            if !blob.is_empty() {
-                let _ = tx_b.send(blob);
-            }
-            let _ = tx_c.send(local_count);
-        }));
-    }
-
-    // Reader (supports .gz transparently)
-    let path_clone = input.to_path_buf();
-    let reader_handle = thread::spawn(move || -> Result<()> {
-        let mut r = open_maybe_gz_bufread(&path_clone, READER_BUF)?;
-        let mut buf = Vec::<u8>::with_capacity(64 * 1024);
-        loop {
-            buf.clear();
-            let n = r.read_until(b'\n', &mut buf)?;
-            if n == 0 {
-                break;
-            }
-            if tx_lines.send(buf.clone()).is_err() {
-                break;
-            }
-        }
-        drop(tx_lines);
-        Ok(())
-    });
-
-    reader_handle
-        .join()
-        .map_err(|_| anyhow::anyhow!("reader panicked"))??;
-
-    for h in handles {
-        let _ = h.join();
-    }
-    drop(tx_blobs); // close writer channel
-
-    let mut total = 0usize;
-    for _ in 0..workers {
-        if let Ok(n) = rx_counts.recv() {
-            total += n;
-        }
-    }
-
-    writer_handle
-        .join()
-        .map_err(|_| anyhow::anyhow!("writer panicked"))??;
-    Ok(total)
-}
-
-/* -------------------- Registry & utils -------------------- */
-
-pub type ParserFactory = fn() -> Box<dyn Parser>;
-pub fn registry() -> &'static [ParserFactory] {
-    &[
-        crate::modules::web_access::new,
-        crate::modules::mactime::new,
-        crate::modules::csv_dummy::new, // keep if useful
-    ]
-}
-
-pub fn format_size(bytes: u64) -> String {
-    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
-    let mut size = bytes as f64;
-    let mut unit = 0;
-    while size >= 1024.0 && unit < UNITS.len() - 1 {
-        size /= 1024.0;
-        unit += 1;
-    }
-    format!("{:.2} {}", size, UNITS[unit])
-}
-
-/// Fast line counter for **both plain and .gz** files.
-///
-/// Uses a big chunked read and `memchr` to count `\n` without per-line allocation.
-/// For `.gz`, this does a full decompress pass (inevitable if you want an exact count).
-pub fn count_lines_any(path: &Path) -> Result<u64> {
-    let mut r = open_maybe_gz_read(path)?;
-    let mut buf = vec![0u8; 256 * 1024]; // 256 KiB chunks
-    let mut total = 0u64;
-
-    loop {
-        let n = r.read(&mut buf)?;
-        if n == 0 {
-            break;
-        }
-        total += memchr_iter(b'\n', &buf[..n]).count() as u64;
-    }
-    Ok(total)
-}
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, VecDeque},
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    ops::{Deref, Range},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use crossbeam_channel::{bounded, Receiver, RecvTimeoutError, Sender};
+use flate2::read::MultiGzDecoder;
+use memchr::{memchr, memchr_iter, memrchr};
+use memmap2::Mmap;
+
+use crate::bgzf::{self, ParallelBgzf};
+use crate::encoding::{self, Encoding};
+use crate::progress::Progress;
+use crate::sink::Sink;
+
+/* -------------------- Parser trait -------------------- */
+
+/// Every module only needs to process one line and append JSONL to `out`.
+pub trait Parser: Send + Sync {
+    fn name(&self) -> Cow<'static, str>;
+    fn description(&self) -> Cow<'static, str>;
+
+    /// Options accepted by `configure` (shown by `list`).
+    fn options(&self) -> &'static [OptionSpec] {
+        &[]
+    }
+
+    /// Apply `--opt key=value` settings before the run starts.
+    /// Keys have already been checked against `options()`.
+    fn configure(&mut self, _opts: &ModuleOptions) -> Result<()> {
+        Ok(())
+    }
+
+    /// Return true if a JSONL record was emitted.
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool;
+
+    /// What the workers call, with the line's raw bytes. The default hands
+    /// the line to `process_line_to_buf`, replacing invalid UTF-8 with U+FFFD
+    /// rather than dropping the line. Modules that work on bytes override it
+    /// to skip the UTF-8 check.
+    fn process_bytes_to_buf(&self, line: &[u8], out: &mut Vec<u8>) -> bool {
+        match std::str::from_utf8(line) {
+            Ok(s) => self.process_line_to_buf(s, out),
+            Err(_) => self.process_line_to_buf(&String::from_utf8_lossy(line), out),
+        }
+    }
+
+    /// Header-driven formats (`#Fields:` directives, CSV header rows, ...):
+    /// called once per input with its first lines, before any line is
+    /// processed. Return a parser set up for this input, or `None` to use
+    /// `self` unchanged.
+    fn for_input(&self, _head: &[&str]) -> Result<Option<Box<dyn Parser>>> {
+        Ok(None)
+    }
+
+    /// Formats whose records span several lines: a fresh joiner per input,
+    /// run on the reader thread before lines reach `process_line_to_buf`.
+    fn line_joiner(&self) -> Option<Box<dyn LineJoiner>> {
+        None
+    }
+
+    /// Whether each input is a stream of lines or one whole document.
+    fn input_format(&self) -> InputFormat {
+        InputFormat::Lines
+    }
+
+    /// `InputFormat::Document` modules: turn one whole input into
+    /// newline-separated records, each of which is then handed to
+    /// `process_line_to_buf` as if it were an input line.
+    fn split_document(&self, _doc: &[u8], _out: &mut Vec<u8>) -> Result<()> {
+        anyhow::bail!("module {} does not read whole documents", self.name())
+    }
+
+    /// Wrapper formats (container logs, ...): the string field of this
+    /// module's records holding the embedded message, which a chained module
+    /// (`--module docker-json,web-access`) parses next.
+    fn message_field(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Fields of this module's records holding an IP address, for the
+    /// enrichment stages (`--geoip`, ...) to look up.
+    fn ip_fields(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Fields holding an HTTP `User-Agent` (`--parse-ua`).
+    fn user_agent_fields(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// The fields of this module's records and their types, in order, when
+    /// the module knows them up front. Parquet and Arrow outputs build their
+    /// schema from them; fields not listed are written as text.
+    fn fields(&self) -> Vec<(String, FieldType)> {
+        Vec::new()
+    }
+
+    /// True if `line` really is in this module's format (used by `detect`).
+    ///
+    /// The default treats any emitted record as a match, except the
+    /// `{"unparsed":true,...}` fallback some modules emit. Modules that
+    /// accept almost anything should override this with a stricter check.
+    fn recognizes(&self, line: &str) -> bool {
+        let mut scratch = Vec::new();
+        self.process_line_to_buf(line, &mut scratch) && !scratch.starts_with(UNPARSED_PREFIX)
+    }
+}
+
+/// Type of a field declared by [`Parser::fields`]. Values of another type
+/// still fit: the output column is widened to text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    Bool,
+    Int,
+    Float,
+    /// Strings, and objects or arrays as JSON.
+    Text,
+}
+
+/// Where a line starts in the input: its 1-based number and byte offset.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LinePos {
+    pub line: u64,
+    pub offset: u64,
+}
+
+/// Groups physical lines into logical records, in input order.
+///
+/// Lines arrive without their terminator, each with its position. A record
+/// handed to `emit` may contain newlines; it reaches `process_line_to_buf`
+/// as one "line". It goes out with the position of the line it started on,
+/// which `--provenance` reports.
+pub trait LineJoiner: Send {
+    fn push(&mut self, line: &[u8], at: LinePos, emit: &mut dyn FnMut(&[u8], LinePos));
+
+    /// End of input: emit whatever is still pending.
+    fn finish(&mut self, emit: &mut dyn FnMut(&[u8], LinePos));
+}
+
+/// Stateful grouping: gathers the lines sharing a key (mail queue id, audit
+/// event serial, ...) into one newline-joined record.
+///
+/// `key` returns a line's group and whether it closes the group; lines
+/// without a key pass through alone. Groups still open when more than
+/// `max_pending` are pending are flushed oldest first, and the rest at the
+/// end of input, so a record may come out after lines that followed it.
+pub struct GroupJoiner {
+    key: fn(&str) -> Option<(&str, bool)>,
+    max_pending: usize,
+    /// Lines of each open group, and where its first line was.
+    pending: HashMap<String, (LinePos, Vec<u8>)>,
+    order: VecDeque<String>,
+}
+
+impl GroupJoiner {
+    pub fn new(key: fn(&str) -> Option<(&str, bool)>, max_pending: usize) -> Self {
+        Self {
+            key,
+            max_pending: max_pending.max(1),
+            pending: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    fn flush_oldest(&mut self, emit: &mut dyn FnMut(&[u8], LinePos)) {
+        while let Some(id) = self.order.pop_front() {
+            if let Some((at, lines)) = self.pending.remove(&id) {
+                emit(&lines, at);
+                return;
+            }
+        }
+    }
+}
+
+impl LineJoiner for GroupJoiner {
+    fn push(&mut self, line: &[u8], at: LinePos, emit: &mut dyn FnMut(&[u8], LinePos)) {
+        let Some((id, last)) = std::str::from_utf8(line).ok().and_then(self.key) else {
+            emit(line, at);
+            return;
+        };
+
+        let lines = match self.pending.get_mut(id) {
+            Some((_, lines)) => {
+                lines.push(b'\n');
+                lines
+            }
+            None if last => {
+                emit(line, at);
+                return;
+            }
+            None => {
+                if self.pending.len() >= self.max_pending {
+                    self.flush_oldest(emit);
+                }
+                self.order.push_back(id.to_string());
+                &mut self
+                    .pending
+                    .entry(id.to_string())
+                    .or_insert((at, Vec::new()))
+                    .1
+            }
+        };
+        lines.extend_from_slice(line);
+
+        if last && let Some((start, lines)) = self.pending.remove(id) {
+            emit(&lines, start);
+        }
+    }
+
+    fn finish(&mut self, emit: &mut dyn FnMut(&[u8], LinePos)) {
+        while !self.pending.is_empty() {
+            self.flush_oldest(emit);
+        }
+        self.order.clear();
+    }
+}
+
+/// Where a line sits in a [`BlockJoiner`] record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Boundary {
+    /// Opens a record; the pending one is emitted first.
+    Start,
+    /// Closes the pending record, this line included.
+    End,
+    /// Continues the pending record.
+    Inside,
+}
+
+/// Records made of consecutive lines: a record opens at a `Start` line and
+/// runs until the next `Start`, an `End` line or the end of input.
+///
+/// `boundary` sees every line in order and may keep state (e.g. "a header
+/// line after a body line starts a record"). Lines arriving with no record
+/// open pass through alone. A record that reaches `max_lines` is emitted
+/// there, so a missing boundary can't buffer the rest of the input.
+pub struct BlockJoiner {
+    boundary: Box<dyn FnMut(&str) -> Boundary + Send>,
+    max_lines: usize,
+    pending: Vec<u8>,
+    lines: usize,
+    /// Where the pending record's first line was.
+    start: LinePos,
+}
+
+impl BlockJoiner {
+    pub fn new(boundary: impl FnMut(&str) -> Boundary + Send + 'static, max_lines: usize) -> Self {
+        Self {
+            boundary: Box::new(boundary),
+            max_lines: max_lines.max(1),
+            pending: Vec::new(),
+            lines: 0,
+            start: LinePos::default(),
+        }
+    }
+
+    fn flush(&mut self, emit: &mut dyn FnMut(&[u8], LinePos)) {
+        if self.lines > 0 {
+            emit(&self.pending, self.start);
+            self.pending.clear();
+            self.lines = 0;
+        }
+    }
+}
+
+impl LineJoiner for BlockJoiner {
+    fn push(&mut self, line: &[u8], at: LinePos, emit: &mut dyn FnMut(&[u8], LinePos)) {
+        let boundary = std::str::from_utf8(line).map_or(Boundary::Inside, &mut self.boundary);
+        match boundary {
+            Boundary::Start => self.flush(emit),
+            _ if self.lines == 0 => {
+                emit(line, at);
+                return;
+            }
+            _ => self.pending.push(b'\n'),
+        }
+        if self.lines == 0 {
+            self.start = at;
+        }
+        self.pending.extend_from_slice(line);
+        self.lines += 1;
+
+        if boundary == Boundary::End || self.lines >= self.max_lines {
+            self.flush(emit);
+        }
+    }
+
+    fn finish(&mut self, emit: &mut dyn FnMut(&[u8], LinePos)) {
+        self.flush(emit);
+    }
+}
+
+/// How the runner feeds an input to a module.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InputFormat {
+    /// Line by line, streamed.
+    #[default]
+    Lines,
+    /// Read whole (e.g. a JSON file holding a `Records` array), then split
+    /// into records by `Parser::split_document`.
+    Document,
+}
+
+/// Start of the fallback record emitted for lines a module cannot parse.
+pub(crate) const UNPARSED_PREFIX: &[u8] = br#"{"unparsed":true"#;
+
+/* -------------------- Module options -------------------- */
+
+/// Documentation for one module option.
+#[derive(Debug, Clone, Copy)]
+pub struct OptionSpec {
+    pub key: &'static str,
+    pub help: &'static str,
+}
+
+/// `--opt key=value` pairs, in command-line order. A key may repeat.
+#[derive(Debug, Clone, Default)]
+pub struct ModuleOptions {
+    pairs: Vec<(String, String)>,
+}
+
+impl ModuleOptions {
+    /// Parse `key=value` strings.
+    pub fn parse<S: AsRef<str>>(items: &[S]) -> Result<Self> {
+        let pairs = items
+            .iter()
+            .map(|item| {
+                let item = item.as_ref();
+                let (k, v) = item
+                    .split_once('=')
+                    .with_context(|| format!("module option '{item}' is not key=value"))?;
+                Ok((k.trim().to_string(), v.to_string()))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { pairs })
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) -> &mut Self {
+        self.pairs.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    /// Last value given for `key`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.pairs
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Every value given for `key`, in order.
+    pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.pairs
+            .iter()
+            .filter(move |(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Boolean option: `1/true/yes/on` or `0/false/no/off`.
+    pub fn flag(&self, key: &str) -> Result<Option<bool>> {
+        self.get(key)
+            .map(|v| match v.to_ascii_lowercase().as_str() {
+                "1" | "true" | "yes" | "on" | "" => Ok(true),
+                "0" | "false" | "no" | "off" => Ok(false),
+                _ => anyhow::bail!("option {key}: expected a boolean, got '{v}'"),
+            })
+            .transpose()
+    }
+
+    /// Comma-separated list option; empty items are dropped.
+    pub fn list(&self, key: &str) -> Option<Vec<String>> {
+        self.get(key).map(|v| {
+            v.split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect()
+        })
+    }
+
+    /// The pairs whose key is one of `specs`.
+    pub fn only(&self, specs: &[OptionSpec]) -> Self {
+        Self {
+            pairs: self
+                .pairs
+                .iter()
+                .filter(|(k, _)| specs.iter().any(|s| s.key == k))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Reject keys the module does not declare.
+    pub fn check_known(&self, module: &str, specs: &[OptionSpec]) -> Result<()> {
+        for (k, _) in &self.pairs {
+            if !specs.iter().any(|s| s.key == k) {
+                let known: Vec<&str> = specs.iter().map(|s| s.key).collect();
+                anyhow::bail!(
+                    "module {module} has no option '{k}' (known: {})",
+                    if known.is_empty() {
+                        "none".to_string()
+                    } else {
+                        known.join(", ")
+                    }
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Validate `opts` against the module's declared options and apply them.
+pub fn configure_parser(parser: &mut dyn Parser, opts: &ModuleOptions) -> Result<()> {
+    opts.check_known(&parser.name(), parser.options())?;
+    parser
+        .configure(opts)
+        .with_context(|| format!("configure module {}", parser.name()))
+}
+
+/* -------------------- Module chains -------------------- */
+
+/// `outer,inner` pipeline: `inner` parses the message `outer` extracted
+/// (its `message_field`) and its record is nested under `parsed`, `null`
+/// when it rejects the message. Longer chains nest: `a,b,c` is `a,(b,c)`.
+pub struct Chain {
+    outer: Box<dyn Parser>,
+    inner: Arc<dyn Parser>,
+    field: &'static str,
+}
+
+impl Chain {
+    pub fn new(outer: Box<dyn Parser>, inner: Box<dyn Parser>) -> Result<Self> {
+        let field = outer.message_field().with_context(|| {
+            format!(
+                "module {} has no message field for {} to parse",
+                outer.name(),
+                inner.name()
+            )
+        })?;
+        Ok(Self {
+            outer,
+            inner: inner.into(),
+            field,
+        })
+    }
+}
+
+/// Run `inner` on an embedded message and return its record as a value.
+/// Rejected messages and `{"unparsed":true,...}` fallbacks give `null`.
+pub(crate) fn parse_nested(inner: &dyn Parser, message: &str) -> serde_json::Value {
+    let mut buf = Vec::new();
+    if !inner.process_line_to_buf(message, &mut buf) || buf.starts_with(UNPARSED_PREFIX) {
+        return serde_json::Value::Null;
+    }
+    serde_json::from_slice(&buf).unwrap_or(serde_json::Value::Null)
+}
+
+impl Parser for Chain {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Owned(format!("{},{}", self.outer.name(), self.inner.name()))
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Owned(format!("{} -> {}", self.outer.name(), self.inner.name()))
+    }
+
+    /// Input-level hooks (headers, joining, documents) are the outer module's;
+    /// the inner one only ever sees extracted messages.
+    fn for_input(&self, head: &[&str]) -> Result<Option<Box<dyn Parser>>> {
+        Ok(self.outer.for_input(head)?.map(|outer| {
+            Box::new(Chain {
+                outer,
+                inner: Arc::clone(&self.inner),
+                field: self.field,
+            }) as Box<dyn Parser>
+        }))
+    }
+
+    fn line_joiner(&self) -> Option<Box<dyn LineJoiner>> {
+        self.outer.line_joiner()
+    }
+
+    fn input_format(&self) -> InputFormat {
+        self.outer.input_format()
+    }
+
+    fn split_document(&self, doc: &[u8], out: &mut Vec<u8>) -> Result<()> {
+        self.outer.split_document(doc, out)
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        let mut fields = self.outer.ip_fields();
+        fields.extend(self.inner.ip_fields().iter().map(|f| format!("parsed.{f}")));
+        fields
+    }
+
+    fn user_agent_fields(&self) -> Vec<String> {
+        let mut fields = self.outer.user_agent_fields();
+        fields.extend(self.inner.user_agent_fields().iter().map(|f| format!("parsed.{f}")));
+        fields
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        self.process_bytes_to_buf(line.as_bytes(), out)
+    }
+
+    fn process_bytes_to_buf(&self, line: &[u8], out: &mut Vec<u8>) -> bool {
+        let mut rec = Vec::new();
+        if !self.outer.process_bytes_to_buf(line, &mut rec) {
+            return false;
+        }
+        let Ok(serde_json::Value::Object(mut obj)) = serde_json::from_slice(&rec) else {
+            // Fallback records pass through untouched.
+            out.extend_from_slice(&rec);
+            return true;
+        };
+
+        let parsed = match obj.get(self.field) {
+            Some(serde_json::Value::String(msg)) => parse_nested(&*self.inner, msg),
+            _ => serde_json::Value::Null,
+        };
+        obj.insert("parsed".to_string(), parsed);
+        if serde_json::to_writer(&mut *out, &obj).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+/* -------------------- Multi-line records -------------------- */
+
+/// Longest record `Multiline` assembles before emitting it as is.
+const MULTILINE_MAX_LINES: usize = 10_000;
+
+/// `--multiline-start REGEX`: any module, with records that span several
+/// lines (stack traces, wrapped SQL, XML bodies). A line matching `start`
+/// opens a record and the lines up to the next match are appended to it,
+/// newline-joined. Lines before the first match pass through alone.
+///
+/// Replaces the module's own `line_joiner`; every other hook is the module's.
+pub struct Multiline {
+    inner: Box<dyn Parser>,
+    start: regex::Regex,
+}
+
+impl Multiline {
+    pub fn new(inner: Box<dyn Parser>, start: &str) -> Result<Self> {
+        let start = regex::Regex::new(start)
+            .with_context(|| format!("invalid multi-line start pattern {start:?}"))?;
+        Ok(Self { inner, start })
+    }
+}
+
+impl Parser for Multiline {
+    fn name(&self) -> Cow<'static, str> {
+        self.inner.name()
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        self.inner.description()
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        self.inner.options()
+    }
+
+    fn configure(&mut self, opts: &ModuleOptions) -> Result<()> {
+        self.inner.configure(opts)
+    }
+
+    fn for_input(&self, head: &[&str]) -> Result<Option<Box<dyn Parser>>> {
+        Ok(self.inner.for_input(head)?.map(|inner| {
+            Box::new(Multiline {
+                inner,
+                start: self.start.clone(),
+            }) as Box<dyn Parser>
+        }))
+    }
+
+    fn line_joiner(&self) -> Option<Box<dyn LineJoiner>> {
+        let start = self.start.clone();
+        let boundary = move |line: &str| {
+            if start.is_match(line) {
+                Boundary::Start
+            } else {
+                Boundary::Inside
+            }
+        };
+        Some(Box::new(BlockJoiner::new(boundary, MULTILINE_MAX_LINES)))
+    }
+
+    fn input_format(&self) -> InputFormat {
+        self.inner.input_format()
+    }
+
+    fn split_document(&self, doc: &[u8], out: &mut Vec<u8>) -> Result<()> {
+        self.inner.split_document(doc, out)
+    }
+
+    fn message_field(&self) -> Option<&'static str> {
+        self.inner.message_field()
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        self.inner.ip_fields()
+    }
+
+    fn user_agent_fields(&self) -> Vec<String> {
+        self.inner.user_agent_fields()
+    }
+
+    fn fields(&self) -> Vec<(String, FieldType)> {
+        self.inner.fields()
+    }
+
+    fn recognizes(&self, line: &str) -> bool {
+        self.inner.recognizes(line)
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        self.inner.process_line_to_buf(line, out)
+    }
+
+    fn process_bytes_to_buf(&self, line: &[u8], out: &mut Vec<u8>) -> bool {
+        self.inner.process_bytes_to_buf(line, out)
+    }
+}
+
+/* -------------------- Gzip / IO helpers -------------------- */
+
+pub(crate) const READER_BUF: usize = 1 << 20; // 1 MiB
+
+/// Input path that stands for stdin (`--input -`).
+pub const STDIN_PATH: &str = "-";
+
+/// True if `path` designates stdin rather than a file.
+pub fn is_stdin(path: &Path) -> bool {
+    path.as_os_str() == STDIN_PATH
+}
+
+/// Return a **BufRead** that transparently decompresses `.gz` if needed.
+///
+/// `-` reads stdin; gzip is detected by peeking the buffered bytes since
+/// stdin cannot be rewound.
+pub fn open_maybe_gz_bufread(path: &Path, buf_size: usize) -> Result<Box<dyn BufRead + Send>> {
+    if is_stdin(path) {
+        let mut r = BufReader::with_capacity(buf_size, std::io::stdin());
+        let buf = r.fill_buf().context("read stdin")?;
+        let head = buf[..buf.len().min(64)].to_vec();
+
+        if head.starts_with(&[0x1F, 0x8B]) {
+            let gz = gz_decoder(r, &head);
+            return Ok(Box::new(BufReader::with_capacity(buf_size, gz)));
+        }
+        return Ok(Box::new(r));
+    }
+
+    let (fh, head) = open_peek(path)?;
+    if head.starts_with(&[0x1F, 0x8B]) {
+        let gz = gz_decoder(fh, &head);
+        Ok(Box::new(BufReader::with_capacity(buf_size, gz)))
+    } else {
+        Ok(Box::new(BufReader::with_capacity(buf_size, fh)))
+    }
+}
+
+/// Return a **Read** that transparently decompresses `.gz` if needed
+/// (useful for fast scanning / counting).
+pub fn open_maybe_gz_read(path: &Path) -> Result<Box<dyn Read + Send>> {
+    let (fh, head) = open_peek(path)?;
+    if head.starts_with(&[0x1F, 0x8B]) {
+        Ok(gz_decoder(fh, &head))
+    } else {
+        Ok(Box::new(fh))
+    }
+}
+
+/// Open `path` along with its first bytes, enough to recognize BGZF.
+fn open_peek(path: &Path) -> Result<(File, Vec<u8>)> {
+    let mut fh = File::open(path).with_context(|| format!("open {}", path.display()))?;
+    let mut head = Vec::with_capacity(64);
+    (&mut fh).take(64).read_to_end(&mut head)?;
+    fh.rewind()?;
+    Ok((fh, head))
+}
+
+/// Decoder for gzip data starting with `head`. BGZF files are inflated on
+/// every core; other gzip files on this thread, member after member.
+fn gz_decoder<R: Read + Send + 'static>(r: R, head: &[u8]) -> Box<dyn Read + Send> {
+    if bgzf::is_bgzf(head) {
+        Box::new(ParallelBgzf::new(r, num_cpus::get()))
+    } else {
+        Box::new(MultiGzDecoder::new(r))
+    }
+}
+
+/// True if file starts with gzip magic bytes.
+pub fn is_gzip(path: &Path) -> Result<bool> {
+    let mut fh = File::open(path).with_context(|| format!("open {}", path.display()))?;
+    let mut magic = [0u8; 2];
+    let n = fh.read(&mut magic)?;
+    Ok(n == 2 && magic == [0x1F, 0x8B])
+}
+
+/// Enumerate regular files under `dir`, sorted for deterministic runs.
+///
+/// `exts` filters on the filename suffix (case-insensitive, leading dot
+/// optional), so `gz` matches `access.log.gz` and `log.gz` works too.
+/// An empty `exts` keeps every file. Symlinked directories are not followed.
+pub fn collect_input_files(dir: &Path, recursive: bool, exts: &[String]) -> Result<Vec<PathBuf>> {
+    let suffixes: Vec<String> = exts
+        .iter()
+        .map(|e| e.trim().trim_start_matches('.').to_ascii_lowercase())
+        .filter(|e| !e.is_empty())
+        .map(|e| format!(".{e}"))
+        .collect();
+
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+
+    while let Some(current) = pending.pop() {
+        let entries = std::fs::read_dir(&current)
+            .with_context(|| format!("read dir {}", current.display()))?;
+
+        for entry in entries {
+            let entry = entry.with_context(|| format!("read dir {}", current.display()))?;
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+
+            if file_type.is_dir() {
+                if recursive {
+                    pending.push(path);
+                }
+                continue;
+            }
+
+            if !path.is_file() {
+                continue;
+            }
+
+            let name = entry.file_name().to_string_lossy().to_ascii_lowercase();
+            if suffixes.is_empty() || suffixes.iter().any(|s| name.ends_with(s.as_str())) {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/* -------------------- High-throughput streaming runner -------------------- */
+
+/// How often a follow-mode reader polls for appended data.
+const FOLLOW_POLL: Duration = Duration::from_millis(250);
+/// How long a follow-mode worker holds a partial blob before sending it.
+const FOLLOW_FLUSH: Duration = Duration::from_secs(1);
+/// Target size of a reader -> worker slab. Slabs always end on a line boundary.
+const SLAB_TARGET: usize = 64 << 10; // 64 KiB
+
+/// Shared destination for rejected lines (`--rejects`). Workers append whole
+/// lines under the lock, so lines from different workers never interleave.
+pub type RejectsWriter = Arc<Mutex<dyn Write + Send>>;
+
+/// Knobs for `run_streaming_parallel`.
+#[derive(Clone)]
+pub struct RunOptions {
+    /// Number of worker threads (at least 1).
+    pub workers: usize,
+    /// Keep reading after EOF and stream appended lines (`tail -F` style).
+    /// The run only ends when the process is stopped, or through `stop`.
+    pub follow: bool,
+    /// Write records in input order. Costs some memory in the writer while
+    /// it waits for slow batches; throughput is otherwise unchanged.
+    pub ordered: bool,
+    /// Receives every non-blank line for which the module emitted nothing,
+    /// verbatim (bytes that are not valid UTF-8 included).
+    pub rejects: Option<RejectsWriter>,
+    /// Counts the lines and bytes worked through, for a progress bar.
+    pub progress: Option<Arc<Progress>>,
+    /// Lets the run be stopped early, e.g. on Ctrl-C.
+    pub stop: Option<Arc<StopSignal>>,
+    /// Start reading at this input byte (after decompression for `.gz`),
+    /// which must begin a line. Used to resume from a checkpoint.
+    pub start_offset: u64,
+    /// Save points for resuming the run later. Implies `ordered`.
+    pub checkpoint: Option<Checkpoints>,
+    /// Map uncompressed input files into memory and hand workers slices of
+    /// the mapping instead of copies. The file must not be truncated while
+    /// it is parsed: reading a page that is gone kills the process.
+    pub mmap: bool,
+    /// How input bytes become text. Anything but plain UTF-8 is decoded on
+    /// the reader thread, and input offsets then count decoded bytes.
+    pub encoding: Encoding,
+    /// Add `_src_file`, `_src_line` and `_src_offset` to every record: the
+    /// input path, and the line the record was parsed from (1-based) and
+    /// its byte offset. For a record joined from several lines, its first
+    /// line; for one split out of a whole document, the document's start.
+    pub provenance: bool,
+    /// What becomes of lines the module cannot parse.
+    pub on_error: OnError,
+}
+
+/// `--on-error`: what to do with a line the module rejects.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnError {
+    /// Count it as rejected (and write it to `rejects`), nothing more.
+    #[default]
+    Skip,
+    /// Also emit it as a `{"unparsed":true,"raw":"..."}` record, so the
+    /// output holds every line.
+    Raw,
+    /// Fail the run, on a rejected line or an `unparsed` fallback record.
+    Abort,
+}
+
+/// How a run reports its save points (`--checkpoint`).
+#[derive(Clone)]
+pub struct Checkpoints {
+    /// Minimum time between two saves. One more is made at the end.
+    pub every: Duration,
+    /// Called by the writer right after flushing the sink, with the input
+    /// offset every record before which is written, and the bytes the sink
+    /// was given so far in this run.
+    pub save: Arc<dyn Fn(u64, u64) -> Result<()> + Send + Sync>,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self {
+            workers: num_cpus::get(),
+            follow: false,
+            ordered: false,
+            rejects: None,
+            progress: None,
+            stop: None,
+            start_offset: 0,
+            checkpoint: None,
+            mmap: true,
+            encoding: Encoding::Auto,
+            provenance: false,
+            on_error: OnError::Skip,
+        }
+    }
+}
+
+/// Stops a run before the end of its input without losing what was read.
+///
+/// Once requested, the reader stops at the next line boundary. Lines already
+/// read are still parsed and written, and the sink is finished as usual, so
+/// the output ends on a complete record. A reader blocked on stdin only
+/// notices once more data (or EOF) arrives.
+#[derive(Debug, Default)]
+pub struct StopSignal {
+    requested: AtomicBool,
+    offset: AtomicU64,
+}
+
+impl StopSignal {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::Relaxed)
+    }
+
+    /// Input bytes handed to the workers by the last run, i.e. the offset
+    /// reached when it stopped. Counted after decompression for `.gz`.
+    pub fn offset(&self) -> u64 {
+        self.offset.load(Ordering::Relaxed)
+    }
+}
+
+/// Line and record counters for one run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct RunStats {
+    /// Lines read, blank ones included.
+    pub lines: u64,
+    /// Lines that produced at least one record.
+    pub parsed: u64,
+    /// Non-blank lines that produced no record.
+    pub rejected: u64,
+    /// Empty or whitespace-only lines.
+    pub blank: u64,
+    /// JSONL records written.
+    pub records: u64,
+}
+
+impl RunStats {
+    pub fn add(&mut self, other: &RunStats) {
+        self.lines += other.lines;
+        self.parsed += other.parsed;
+        self.rejected += other.rejected;
+        self.blank += other.blank;
+        self.records += other.records;
+    }
+}
+
+/// Consecutive complete input lines, tagged with their position in the stream.
+/// Only the very last slab of an input may end without a newline.
+///
+/// Records built by a `LineJoiner` may span several lines; their end offsets
+/// are then listed in `ends` (empty for plain newline-separated data), and
+/// where each started in `origins`.
+struct LineBatch {
+    seq: u64,
+    /// Input offset right after this batch.
+    end: u64,
+    /// Lines of the input before this batch, when counted (`provenance`).
+    first_line: u64,
+    data: Slab,
+    ends: Vec<usize>,
+    origins: Vec<LinePos>,
+}
+
+/// Bytes of a `LineBatch`: read into a pooled buffer, or a range of a
+/// mapped input file.
+enum Slab {
+    Owned(Vec<u8>),
+    Mapped(Arc<Mmap>, Range<usize>),
+}
+
+impl Deref for Slab {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Slab::Owned(buf) => buf,
+            Slab::Mapped(map, range) => &map[range.clone()],
+        }
+    }
+}
+
+impl LineBatch {
+    fn records(&self) -> Box<dyn Iterator<Item = &[u8]> + '_> {
+        if self.ends.is_empty() {
+            return Box::new(slab_lines(&self.data));
+        }
+        let mut start = 0;
+        Box::new(self.ends.iter().map(move |&end| {
+            let rec = &self.data[start..end];
+            start = end;
+            rec
+        }))
+    }
+}
+
+/// Worker output. Carries the sequence number and end offset of its batch in
+/// ordered mode (0 otherwise).
+type SeqBlob = (u64, u64, Vec<u8>);
+
+/// Recycles `Vec<u8>` allocations between pipeline stages.
+///
+/// Consumers `put` buffers back once done; producers `get` one instead of
+/// allocating. The pool is bounded: extra buffers are simply dropped, and an
+/// empty pool falls back to a fresh allocation.
+#[derive(Clone)]
+struct BufferPool {
+    tx: Sender<Vec<u8>>,
+    rx: Receiver<Vec<u8>>,
+    capacity: usize,
+}
+
+impl BufferPool {
+    fn new(slots: usize, capacity: usize) -> Self {
+        let (tx, rx) = bounded(slots);
+        Self { tx, rx, capacity }
+    }
+
+    fn get(&self) -> Vec<u8> {
+        self.rx
+            .try_recv()
+            .unwrap_or_else(|_| Vec::with_capacity(self.capacity))
+    }
+
+    fn put(&self, mut buf: Vec<u8>) {
+        buf.clear();
+        let _ = self.tx.try_send(buf);
+    }
+
+    /// Take back the buffer of `slab`, if it has one.
+    fn put_slab(&self, slab: Slab) {
+        if let Slab::Owned(buf) = slab {
+            self.put(buf);
+        }
+    }
+}
+
+/// Reads raw bytes into newline-aligned slabs and sends them as `LineBatch`es.
+/// A trailing partial line stays pending until its newline is read.
+/// With a joiner, complete lines go through it and the resulting records are sent instead.
+struct SlabSender<'a> {
+    tx: &'a Sender<LineBatch>,
+    pool: &'a BufferPool,
+    seq: u64,
+    /// Input bytes sent so far.
+    sent: u64,
+    /// Input lines sent so far, if they are counted.
+    lines: Option<u64>,
+    buf: Vec<u8>,
+    joiner: Option<Box<dyn LineJoiner>>,
+}
+
+impl<'a> SlabSender<'a> {
+    fn new(
+        tx: &'a Sender<LineBatch>,
+        pool: &'a BufferPool,
+        joiner: Option<Box<dyn LineJoiner>>,
+    ) -> Self {
+        Self {
+            tx,
+            pool,
+            seq: 0,
+            sent: 0,
+            lines: None,
+            buf: pool.get(),
+            joiner,
+        }
+    }
+
+    /// One `read` call into the pending slab. Returns 0 at EOF.
+    fn fill(&mut self, r: &mut dyn Read) -> std::io::Result<usize> {
+        let start = self.buf.len();
+        let want = SLAB_TARGET.max(self.buf.capacity() - start);
+        self.buf.resize(start + want, 0);
+
+        let n = loop {
+            match r.read(&mut self.buf[start..]) {
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                res => break res,
+            }
+        };
+        self.buf.truncate(start + *n.as_ref().unwrap_or(&0));
+        n
+    }
+
+    fn is_full(&self) -> bool {
+        self.buf.len() >= SLAB_TARGET
+    }
+
+    /// Send every complete line, keeping a trailing partial one.
+    /// Returns false once the workers are gone.
+    fn send_complete(&mut self) -> bool {
+        let Some(last_nl) = memrchr(b'\n', &self.buf) else {
+            return true;
+        };
+
+        let mut rest = self.pool.get();
+        rest.extend_from_slice(&self.buf[last_nl + 1..]);
+        self.buf.truncate(last_nl + 1);
+
+        let data = std::mem::replace(&mut self.buf, rest);
+        self.send(data)
+    }
+
+    /// Send everything, including a final line without newline and any
+    /// record still pending in the joiner (EOF).
+    fn send_all(&mut self) -> bool {
+        if !self.buf.is_empty() {
+            let data = std::mem::take(&mut self.buf);
+            if !self.send(data) {
+                return false;
+            }
+        }
+        self.finish_joiner()
+    }
+
+    /// Stop before EOF: send the complete lines and any record pending in the
+    /// joiner, dropping a trailing partial line.
+    fn send_stop(&mut self) -> bool {
+        if !self.send_complete() {
+            return false;
+        }
+        self.buf.clear();
+        self.finish_joiner()
+    }
+
+    fn finish_joiner(&mut self) -> bool {
+        let Some(joiner) = self.joiner.as_mut() else {
+            return true;
+        };
+        let mut batch = RecordBatch::new(self.pool);
+        joiner.finish(&mut |rec, at| batch.push(rec, at));
+        self.send_records(batch)
+    }
+
+    /// Count the lines of `data`, about to be sent, if lines are counted.
+    /// Returns the number of lines before it.
+    fn count_lines(&mut self, data: &[u8]) -> u64 {
+        let first_line = self.lines.unwrap_or(0);
+        if let Some(lines) = self.lines.as_mut() {
+            *lines += memchr_iter(b'\n', data).count() as u64;
+            *lines += u64::from(data.last().is_some_and(|&b| b != b'\n'));
+        }
+        first_line
+    }
+
+    fn send(&mut self, data: Vec<u8>) -> bool {
+        let start = self.sent;
+        self.sent += data.len() as u64;
+        let first_line = self.count_lines(&data);
+        let Some(joiner) = self.joiner.as_mut() else {
+            return self.send_batch(Slab::Owned(data), first_line);
+        };
+
+        let mut batch = RecordBatch::new(self.pool);
+        for (i, line) in slab_lines(&data).enumerate() {
+            let at = LinePos {
+                line: first_line + i as u64 + 1,
+                offset: start + (line.as_ptr() as usize - data.as_ptr() as usize) as u64,
+            };
+            joiner.push(line, at, &mut |rec, at| batch.push(rec, at));
+        }
+        self.pool.put(data);
+        self.send_records(batch)
+    }
+
+    /// Send `map[range]`, which holds whole lines, without copying it
+    /// (unless a joiner has to see the lines).
+    fn send_mapped(&mut self, map: &Arc<Mmap>, range: Range<usize>) -> bool {
+        if self.joiner.is_some() {
+            let mut data = self.pool.get();
+            data.extend_from_slice(&map[range]);
+            return self.send(data);
+        }
+        self.sent += range.len() as u64;
+        let first_line = self.count_lines(&map[range.clone()]);
+        self.send_batch(Slab::Mapped(map.clone(), range), first_line)
+    }
+
+    /// Send lines as they were read.
+    fn send_batch(&mut self, data: Slab, first_line: u64) -> bool {
+        self.send_line_batch(LineBatch {
+            seq: self.seq,
+            end: self.sent,
+            first_line,
+            data,
+            ends: Vec::new(),
+            origins: Vec::new(),
+        })
+    }
+
+    /// Send the records a joiner made.
+    fn send_records(&mut self, batch: RecordBatch) -> bool {
+        if batch.ends.is_empty() {
+            // Everything is still pending in the joiner.
+            self.pool.put(batch.data);
+            return true;
+        }
+        self.send_line_batch(LineBatch {
+            seq: self.seq,
+            end: self.sent,
+            first_line: 0,
+            data: Slab::Owned(batch.data),
+            ends: batch.ends,
+            origins: batch.origins,
+        })
+    }
+
+    fn send_line_batch(&mut self, batch: LineBatch) -> bool {
+        self.seq += 1;
+        self.tx.send(batch).is_ok()
+    }
+}
+
+/// Joined records being collected into one `LineBatch`.
+struct RecordBatch {
+    data: Vec<u8>,
+    ends: Vec<usize>,
+    origins: Vec<LinePos>,
+}
+
+impl RecordBatch {
+    fn new(pool: &BufferPool) -> Self {
+        Self {
+            data: pool.get(),
+            ends: Vec::new(),
+            origins: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, rec: &[u8], at: LinePos) {
+        self.data.extend_from_slice(rec);
+        self.ends.push(self.data.len());
+        self.origins.push(at);
+    }
+}
+
+/// Iterate the lines of a slab, without their `\n` / `\r\n` terminator.
+fn slab_lines(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut start = 0;
+    let mut ends = memchr_iter(b'\n', data);
+
+    std::iter::from_fn(move || {
+        let end = match ends.next() {
+            Some(nl) => nl,
+            None if start < data.len() => data.len(),
+            None => return None,
+        };
+        let mut line = &data[start..end];
+        start = end + 1;
+
+        if line.last() == Some(&b'\r') {
+            line = &line[..line.len() - 1];
+        }
+        Some(line)
+    })
+}
+
+/// High-throughput streaming runner (multithreaded only).
+///
+/// All threads are scoped to this call, so `parser` is only borrowed.
+pub fn run_streaming_parallel(
+    parser: &dyn Parser,
+    input: &Path,
+    sink: Box<dyn Sink>,
+    opts: &RunOptions,
+) -> Result<RunStats> {
+    let mut total = RunStats::default();
+    for stats in run_streaming_per_worker(parser, input, sink, opts)? {
+        total.add(&stats);
+    }
+    Ok(total)
+}
+
+/// [`run_streaming_parallel`], returning each worker's counters.
+pub fn run_streaming_per_worker(
+    parser: &dyn Parser,
+    input: &Path,
+    mut sink: Box<dyn Sink>,
+    opts: &RunOptions,
+) -> Result<Vec<RunStats>> {
+    const BYTES_BLOB_TARGET: usize = 4 << 20; // 4 MiB
+    const LINES_BLOB_MAX: usize = 16_384;
+    const BATCH_CHAN_FACTOR: usize = 4;
+    const REJECTS_FLUSH: usize = 1 << 20; // 1 MiB
+
+    let workers = opts.workers.max(1);
+    let follow = opts.follow;
+    let ordered = opts.ordered || opts.checkpoint.is_some();
+    let document = parser.input_format() == InputFormat::Document;
+    if document && follow {
+        anyhow::bail!(
+            "module {} reads whole documents and cannot follow an input",
+            parser.name()
+        );
+    }
+    let resumable = !document && !follow && !is_stdin(input);
+    if (opts.checkpoint.is_some() || opts.start_offset > 0) && !resumable {
+        anyhow::bail!("checkpoints need a regular input file, read once (no stdin or follow)");
+    }
+
+    let encoding = opts.encoding;
+    let mapped = match opts.mmap && resumable {
+        true => map_plain(input)?.filter(|map| encoding.is_verbatim(map)),
+        false => None,
+    };
+    if let Some(map) = &mapped
+        && (map.len() as u64) < opts.start_offset
+    {
+        anyhow::bail!(
+            "{} is shorter than offset {}",
+            input.display(),
+            opts.start_offset
+        );
+    }
+
+    // Open the input up front so header-driven modules can look at its first
+    // lines. A followed file is opened (and reopened) by `follow_lines`.
+    let mut reader: Option<Box<dyn BufRead + Send>> =
+        if mapped.is_some() || (follow && !is_stdin(input)) {
+            None
+        } else if document {
+            Some(read_document(parser, input, encoding)?)
+        } else if opts.start_offset > 0 {
+            Some(open_at(input, opts.start_offset, encoding)?)
+        } else {
+            Some(open_decoded(input, encoding)?)
+        };
+    let head = match (&mapped, reader.as_mut()) {
+        (Some(map), _) => head_lines(&mut &map[..map.len().min(READER_BUF)])?,
+        (None, Some(r)) if opts.start_offset == 0 => head_lines(r.as_mut())?,
+        _ => head_lines(open_decoded(input, encoding)?.as_mut())?,
+    };
+    let head: Vec<&str> = head.iter().map(String::as_str).collect();
+    let primed = parser
+        .for_input(&head)
+        .with_context(|| format!("read header of {}", input.display()))?;
+    let parser: &dyn Parser = primed.as_deref().unwrap_or(parser);
+    sink.declare(&parser.fields());
+    // Provenance: the input's name as a JSON string, and the lines skipped on resume.
+    let provenance = opts
+        .provenance
+        .then(|| serde_json::to_string(&input.to_string_lossy()))
+        .transpose()?;
+    let start_line = match provenance.is_some() && opts.start_offset > 0 {
+        true => lines_before(input, opts.start_offset, encoding)?,
+        false => 0,
+    };
+    let provenance = provenance.as_deref();
+    if opts.checkpoint.is_some() && parser.line_joiner().is_some() {
+        // Pending joined records would make batch offsets run ahead of the output.
+        anyhow::bail!(
+            "module {} joins lines into records, which checkpoints do not support",
+            parser.name()
+        );
+    }
+
+    let (tx_lines, rx_lines): (Sender<LineBatch>, Receiver<LineBatch>) =
+        bounded(workers * BATCH_CHAN_FACTOR);
+    let (tx_blobs, rx_blobs): (Sender<SeqBlob>, Receiver<SeqBlob>) = bounded(workers * 4);
+
+    // Slabs flow reader -> workers -> back; blobs flow workers -> writer -> back.
+    // Sized to what can be in flight at once.
+    let slab_pool = BufferPool::new(workers * (BATCH_CHAN_FACTOR + 2), SLAB_TARGET * 2);
+    let blob_pool = BufferPool::new(workers * 6, BYTES_BLOB_TARGET);
+    // Set by a worker failing the run (`OnError::Abort`): the reader, the
+    // other workers and the writer stop at their next batch.
+    let aborted = &AtomicBool::new(false);
+
+    thread::scope(|scope| -> Result<Vec<RunStats>> {
+        // Writer thread
+        let blobs_back = blob_pool.clone();
+        let checkpoint = opts.checkpoint.clone();
+        let start_offset = opts.start_offset;
+        let writer_handle = scope.spawn(move || -> Result<()> {
+            // Ordered mode: park early blobs until every earlier batch is written.
+            let mut pending: BTreeMap<u64, (u64, Vec<u8>)> = BTreeMap::new();
+            let mut next_seq = 0u64;
+            // Checkpoints: input offset fully written, and bytes written.
+            let (mut done, mut written) = (start_offset, 0u64);
+            let mut saved = Instant::now();
+
+            for (seq, end, blob) in rx_blobs.iter() {
+                if aborted.load(Ordering::Relaxed) {
+                    break;
+                }
+                if !ordered {
+                    sink.write_blob(&blob)?;
+                    blobs_back.put(blob);
+                } else {
+                    pending.insert(seq, (end, blob));
+                    while let Some((end, blob)) = pending.remove(&next_seq) {
+                        sink.write_blob(&blob)?;
+                        (done, written) = (end, written + blob.len() as u64);
+                        blobs_back.put(blob);
+                        next_seq += 1;
+                    }
+                }
+                if follow {
+                    sink.flush()?;
+                }
+                if let Some(c) = &checkpoint
+                    && saved.elapsed() >= c.every
+                {
+                    sink.flush()?;
+                    (c.save)(done, written)?;
+                    saved = Instant::now();
+                }
+            }
+            if let Some(c) = &checkpoint {
+                sink.flush()?;
+                (c.save)(done, written)?;
+            }
+            sink.finish()
+        });
+
+        // Workers
+        let mut handles = Vec::with_capacity(workers);
+        for _ in 0..workers {
+            let rx = rx_lines.clone();
+            let tx_b = tx_blobs.clone();
+            let slabs_back = slab_pool.clone();
+            let blobs = blob_pool.clone();
+
+            let rejects = opts.rejects.clone();
+            let progress = opts.progress.clone();
+            // Only an explicit `--encoding utf8` turns invalid bytes into rejects.
+            let strict = encoding == Encoding::Utf8;
+            let on_error = opts.on_error;
+
+            handles.push(scope.spawn(move || -> Result<RunStats> {
+                let mut stats = RunStats::default();
+                let mut blob = blobs.get();
+                let mut lines_in_blob = 0usize;
+                // Follow mode: when output was last pushed to the writer.
+                let mut sent_at = Instant::now();
+                let mut rejected = Vec::new();
+                let mut tagged = Vec::new();
+
+                let flush_rejects = |buf: &mut Vec<u8>| -> Result<()> {
+                    if let Some(w) = &rejects
+                        && !buf.is_empty()
+                    {
+                        let mut w = w
+                            .lock()
+                            .map_err(|_| anyhow::anyhow!("rejects lock poisoned"))?;
+                        w.write_all(buf).context("write rejects")?;
+                        if follow {
+                            w.flush()?;
+                        }
+                    }
+                    buf.clear();
+                    Ok(())
+                };
+
+                let abort = |line: &[u8]| {
+                    aborted.store(true, Ordering::Relaxed);
+                    mismatch(parser, input, line)
+                };
+
+                loop {
+                    if aborted.load(Ordering::Relaxed) {
+                        return Ok(stats);
+                    }
+                    let batch = if follow {
+                        match rx.recv_timeout(FOLLOW_FLUSH.saturating_sub(sent_at.elapsed())) {
+                            Ok(b) => b,
+                            Err(RecvTimeoutError::Timeout) => {
+                                // Idle: push out what we have so live output is not held back.
+                                sent_at = Instant::now();
+                                flush_rejects(&mut rejected)?;
+                                if !blob.is_empty()
+                                    && tx_b
+                                        .send((0, 0, std::mem::replace(&mut blob, blobs.get())))
+                                        .is_err()
+                                {
+                                    break;
+                                }
+                                lines_in_blob = 0;
+                                continue;
+                            }
+                            Err(RecvTimeoutError::Disconnected) => break,
+                        }
+                    } else {
+                        match rx.recv() {
+                            Ok(b) => b,
+                            Err(_) => break,
+                        }
+                    };
+
+                    let lines_before = stats.lines;
+                    let start = batch.end - batch.data.len() as u64;
+                    for (i, line_bytes) in batch.records().enumerate() {
+                        stats.lines += 1;
+                        if line_bytes.iter().all(u8::is_ascii_whitespace) {
+                            stats.blank += 1;
+                            continue;
+                        }
+
+                        let before = blob.len();
+                        if (!strict || std::str::from_utf8(line_bytes).is_ok())
+                            && parser.process_bytes_to_buf(line_bytes, &mut blob)
+                        {
+                            if on_error == OnError::Abort
+                                && blob[before..].starts_with(UNPARSED_PREFIX)
+                            {
+                                return Err(abort(line_bytes));
+                            }
+                            stats.parsed += 1;
+                            stats.records += memchr_iter(b'\n', &blob[before..]).count() as u64;
+                            lines_in_blob += 1;
+                        } else {
+                            stats.rejected += 1;
+                            match on_error {
+                                OnError::Skip => {}
+                                OnError::Raw => {
+                                    let raw = String::from_utf8_lossy(line_bytes);
+                                    let rec = serde_json::json!({"unparsed": true, "raw": raw});
+                                    serde_json::to_writer(&mut blob, &rec)?;
+                                    blob.push(b'\n');
+                                    stats.records += 1;
+                                    lines_in_blob += 1;
+                                }
+                                OnError::Abort => return Err(abort(line_bytes)),
+                            }
+                            if rejects.is_some() {
+                                rejected.extend_from_slice(line_bytes);
+                                rejected.push(b'\n');
+                            }
+                        }
+                        if let Some(file) = provenance
+                            && blob.len() > before
+                        {
+                            let at = if document {
+                                // Split out of the whole input: point at its start.
+                                LinePos { line: 1, offset: 0 }
+                            } else if let Some(&at) = batch.origins.get(i) {
+                                at
+                            } else {
+                                // One line per record: it is a slice of the batch.
+                                let at =
+                                    line_bytes.as_ptr() as usize - batch.data.as_ptr() as usize;
+                                LinePos {
+                                    line: batch.first_line + i as u64 + 1,
+                                    offset: start + at as u64,
+                                }
+                            };
+                            let source = Source {
+                                file,
+                                line: at.line,
+                                offset: at.offset,
+                            };
+                            source.tag(&mut blob, before, &mut tagged);
+                        }
+                    }
+                    if let Some(p) = &progress {
+                        p.add(stats.lines - lines_before, batch.data.len() as u64);
+                    }
+                    slabs_back.put_slab(batch.data);
+
+                    // A steady trickle of input never lets the receive time
+                    // out, so follow mode also sends once `FOLLOW_FLUSH` is up.
+                    let due = follow && sent_at.elapsed() >= FOLLOW_FLUSH;
+                    if due {
+                        sent_at = Instant::now();
+                    }
+                    if rejected.len() >= REJECTS_FLUSH || due {
+                        flush_rejects(&mut rejected)?;
+                    }
+
+                    // Ordered mode sends exactly one blob per batch (even empty)
+                    // so the writer never waits on a sequence number that won't come.
+                    if ordered {
+                        if tx_b
+                            .send((
+                                batch.seq,
+                                batch.end,
+                                std::mem::replace(&mut blob, blobs.get()),
+                            ))
+                            .is_err()
+                        {
+                            break;
+                        }
+                        lines_in_blob = 0;
+                    } else if blob.len() >= BYTES_BLOB_TARGET
+                        || lines_in_blob >= LINES_BLOB_MAX
+                        || due && !blob.is_empty()
+                    {
+                        if tx_b
+                            .send((0, 0, std::mem::replace(&mut blob, blobs.get())))
+                            .is_err()
+                        {
+                            break;
+                        }
+                        lines_in_blob = 0;
+                    }
+                }
+
+                if !blob.is_empty() {
+                    let _ = tx_b.send((0, 0, blob));
+                }
+                flush_rejects(&mut rejected)?;
+                Ok(stats)
+            }));
+        }
+        // Only the workers hold channel ends from here on.
+        drop(rx_lines);
+        drop(tx_blobs);
+
+        // Reader (supports .gz transparently)
+        let stop = opts.stop.as_deref();
+        let reader_handle = scope.spawn(move || -> Result<u64> {
+            let mut slabs = SlabSender::new(&tx_lines, &slab_pool, parser.line_joiner());
+            slabs.sent = opts.start_offset;
+            slabs.lines = provenance.is_some().then_some(start_line);
+            let stopped =
+                || aborted.load(Ordering::Relaxed) || stop.is_some_and(StopSignal::is_requested);
+
+            if let Some(map) = mapped {
+                send_map(&map, opts.start_offset as usize, &mut slabs, &stopped);
+                return Ok(slabs.sent);
+            }
+            let mut r = match reader {
+                Some(r) if !follow => r,
+                stdin => {
+                    follow_lines(input, encoding, stdin, &mut slabs, &stopped)?;
+                    return Ok(slabs.sent);
+                }
+            };
+            loop {
+                if stopped() {
+                    slabs.send_stop();
+                    break;
+                }
+                if slabs.fill(&mut r)? == 0 {
+                    slabs.send_all();
+                    break;
+                }
+                if slabs.is_full() && !slabs.send_complete() {
+                    break;
+                }
+            }
+            Ok(slabs.sent)
+        });
+
+        let read_result = reader_handle
+            .join()
+            .map_err(|_| anyhow::anyhow!("reader panicked"))?;
+
+        let mut per_worker = Vec::with_capacity(handles.len());
+        let mut worker_error = None;
+        for h in handles {
+            match h.join().map_err(|_| anyhow::anyhow!("worker panicked"))? {
+                Ok(stats) => per_worker.push(stats),
+                Err(e) => worker_error = worker_error.or(Some(e)),
+            }
+        }
+
+        // A writer error (e.g. disk full) is the root cause when the reader
+        // stopped early because the pipeline shut down, so report it first.
+        writer_handle
+            .join()
+            .map_err(|_| anyhow::anyhow!("writer panicked"))??;
+        if let Some(e) = worker_error {
+            return Err(e);
+        }
+        let offset = read_result?;
+        if let Some(stop) = stop {
+            stop.offset.store(offset, Ordering::Relaxed);
+        }
+
+        if let Some(w) = &opts.rejects {
+            w.lock()
+                .map_err(|_| anyhow::anyhow!("rejects lock poisoned"))?
+                .flush()
+                .context("flush rejects")?;
+        }
+        Ok(per_worker)
+    })
+}
+
+/// Outcome of `validate`: how well a module fits an input.
+#[derive(Debug, Clone, Default)]
+pub struct ValidateReport {
+    pub stats: RunStats,
+    /// Records that are only the `{"unparsed":true,...}` fallback.
+    pub fallback: u64,
+    /// JSONL bytes a real run would write, before compression.
+    pub output_bytes: u64,
+    /// First failing lines, verbatim: rejected lines, then fallback ones.
+    pub examples: Vec<String>,
+}
+
+impl ValidateReport {
+    /// Non-blank lines the module rejected or only wrapped as unparsed.
+    pub fn failed(&self) -> u64 {
+        self.stats.rejected + self.fallback
+    }
+
+    /// Share of non-blank lines that parsed for real (1.0 for an empty input).
+    pub fn success_rate(&self) -> f64 {
+        let non_blank = self.stats.lines - self.stats.blank;
+        if non_blank == 0 {
+            return 1.0;
+        }
+        non_blank.saturating_sub(self.failed()) as f64 / non_blank as f64
+    }
+}
+
+/// Dry run: parse all of `input` with `parser`, count what a real run would
+/// write, keep up to `max_examples` failing lines, and write nothing.
+///
+/// `opts.rejects` and `opts.follow` are ignored.
+pub fn validate(
+    parser: &dyn Parser,
+    input: &Path,
+    opts: &RunOptions,
+    max_examples: usize,
+) -> Result<ValidateReport> {
+    let tally = Arc::new(Mutex::new(Tally::new(max_examples)));
+    let rejected = Arc::new(Mutex::new(Tally::new(max_examples)));
+
+    let opts = RunOptions {
+        follow: false,
+        rejects: Some(rejected.clone()),
+        ..opts.clone()
+    };
+    let stats = run_streaming_parallel(parser, input, Box::new(TallySink(tally.clone())), &opts)?;
+
+    let take = |t: &Mutex<Tally>| {
+        t.lock()
+            .map(|mut t| std::mem::replace(&mut *t, Tally::new(0)))
+            .map_err(|_| anyhow::anyhow!("validate tally poisoned"))
+    };
+    let tally = take(&tally)?;
+    let mut examples = take(&rejected)?.examples;
+    examples.extend(tally.examples);
+    examples.truncate(max_examples);
+
+    Ok(ValidateReport {
+        stats,
+        fallback: tally.fallback,
+        output_bytes: tally.bytes,
+        examples,
+    })
+}
+
+/// Counters behind `validate`. As a sink it sees JSONL records; as the
+/// rejects writer it sees raw lines.
+struct Tally {
+    bytes: u64,
+    fallback: u64,
+    examples: Vec<String>,
+    max_examples: usize,
+}
+
+impl Tally {
+    fn new(max_examples: usize) -> Self {
+        Self {
+            bytes: 0,
+            fallback: 0,
+            examples: Vec::new(),
+            max_examples,
+        }
+    }
+
+    fn keep(&mut self, line: &[u8]) {
+        if self.examples.len() < self.max_examples {
+            self.examples
+                .push(String::from_utf8_lossy(line).into_owned());
+        }
+    }
+}
+
+impl Write for Tally {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // The runner only ever hands over whole lines.
+        for line in buf.split(|&b| b == b'\n').filter(|l| !l.is_empty()) {
+            self.keep(line);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct TallySink(Arc<Mutex<Tally>>);
+
+impl Sink for TallySink {
+    fn write_blob(&mut self, blob: &[u8]) -> Result<()> {
+        let mut t = self
+            .0
+            .lock()
+            .map_err(|_| anyhow::anyhow!("validate tally poisoned"))?;
+        t.bytes += blob.len() as u64;
+
+        for record in blob.split(|&b| b == b'\n') {
+            if !record.starts_with(UNPARSED_PREFIX) {
+                continue;
+            }
+            t.fallback += 1;
+            if t.examples.len() < t.max_examples {
+                let raw = serde_json::from_slice::<serde_json::Value>(record)
+                    .ok()
+                    .and_then(|v| v.get("raw")?.as_str().map(str::to_owned));
+                match raw {
+                    Some(raw) => t.examples.push(raw),
+                    None => t.keep(record),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Number of leading lines shown to `Parser::for_input`.
+const HEAD_LINES: usize = 64;
+
+/// Up to `HEAD_LINES` complete lines from the start of `r`, without consuming them.
+fn head_lines(r: &mut dyn BufRead) -> Result<Vec<String>> {
+    let buf = r.fill_buf().context("read input head")?;
+    Ok(
+        slab_lines(&buf[..memrchr(b'\n', buf).map_or(0, |nl| nl + 1)])
+            .take(HEAD_LINES)
+            .map(|l| String::from_utf8_lossy(l).into_owned())
+            .collect(),
+    )
+}
+
+/// `input` mapped into memory, when it is a non-empty uncompressed file.
+fn map_plain(input: &Path) -> Result<Option<Arc<Mmap>>> {
+    let fh = File::open(input).with_context(|| format!("open {}", input.display()))?;
+    let meta = fh.metadata()?;
+    if !meta.is_file() || meta.len() == 0 || is_gzip(input)? {
+        return Ok(None);
+    }
+    // SAFETY: the mapping is only read, and outlives every slice handed out
+    // (each batch holds an `Arc`). A file truncated during the run faults on
+    // the lost pages; `RunOptions::mmap` warns about that.
+    let map = unsafe { Mmap::map(&fh) }.with_context(|| format!("map {}", input.display()))?;
+    #[cfg(unix)]
+    let _ = map.advise(memmap2::Advice::Sequential);
+    Ok(Some(Arc::new(map)))
+}
+
+/// Mapped-file reader: hand out newline-aligned ranges of `map` from `start`.
+fn send_map(map: &Arc<Mmap>, start: usize, slabs: &mut SlabSender, stopped: &dyn Fn() -> bool) {
+    let mut pos = start;
+    while pos < map.len() {
+        if stopped() {
+            slabs.send_stop();
+            return;
+        }
+        let end = match memchr(b'\n', map.get(pos + SLAB_TARGET..).unwrap_or_default()) {
+            Some(nl) => pos + SLAB_TARGET + nl + 1,
+            None => map.len(),
+        };
+        if !slabs.send_mapped(map, pos..end) {
+            return;
+        }
+        pos = end;
+    }
+    slabs.send_all();
+}
+
+/// The `--on-error abort` failure for `line`, quoted up to a point.
+fn mismatch(parser: &dyn Parser, input: &Path, line: &[u8]) -> anyhow::Error {
+    const QUOTED: usize = 200;
+    let line = String::from_utf8_lossy(line);
+    let cut = line.floor_char_boundary(QUOTED);
+    anyhow::anyhow!(
+        "module {} cannot parse a line of {}: {}{}",
+        parser.name(),
+        input.display(),
+        &line[..cut],
+        if cut < line.len() { "..." } else { "" }
+    )
+}
+
+/// Where an input line came from (`--provenance`).
+struct Source<'a> {
+    /// The input path as a JSON string.
+    file: &'a str,
+    /// 1-based.
+    line: u64,
+    offset: u64,
+}
+
+impl Source<'_> {
+    /// Add `_src_file`, `_src_line` and `_src_offset` to the records in
+    /// `blob[from..]`, each a JSON object on its own line. `scratch` is
+    /// reused between calls.
+    fn tag(&self, blob: &mut Vec<u8>, from: usize, scratch: &mut Vec<u8>) {
+        scratch.clear();
+        scratch.extend_from_slice(&blob[from..]);
+        blob.truncate(from);
+        for rec in scratch.split_inclusive(|&b| b == b'\n') {
+            let Some(close) = memrchr(b'}', rec) else {
+                blob.extend_from_slice(rec);
+                continue;
+            };
+            let body = rec[..close].trim_ascii_end();
+            blob.extend_from_slice(body);
+            if body.last() != Some(&b'{') {
+                blob.push(b',');
+            }
+            let _ = write!(
+                blob,
+                r#""_src_file":{},"_src_line":{},"_src_offset":{}"#,
+                self.file, self.line, self.offset
+            );
+            blob.extend_from_slice(&rec[close..]);
+        }
+    }
+}
+
+/// Lines in the first `offset` bytes of `input`, decoded as `encoding`.
+fn lines_before(input: &Path, offset: u64, encoding: Encoding) -> Result<u64> {
+    let mut r = open_decoded(input, encoding)?.take(offset);
+    let mut lines = 0;
+    loop {
+        let buf = r
+            .fill_buf()
+            .with_context(|| format!("read {}", input.display()))?;
+        if buf.is_empty() {
+            return Ok(lines);
+        }
+        lines += memchr_iter(b'\n', buf).count() as u64;
+        let n = buf.len();
+        r.consume(n);
+    }
+}
+
+/// `input` decoded as `encoding`, plain, gzip or stdin.
+fn open_decoded(input: &Path, encoding: Encoding) -> Result<Box<dyn BufRead + Send>> {
+    encoding::decode(open_maybe_gz_bufread(input, READER_BUF)?, encoding)
+        .with_context(|| format!("read {}", input.display()))
+}
+
+/// `input` from byte `offset` on, counted after decompression for `.gz` and
+/// after decoding for inputs that are not read as they are.
+fn open_at(input: &Path, offset: u64, encoding: Encoding) -> Result<Box<dyn BufRead + Send>> {
+    let (_, head) = open_peek(input)?;
+    if is_gzip(input)? || !encoding.is_verbatim(&head) {
+        let mut r = open_decoded(input, encoding)?;
+        let skipped = io::copy(&mut r.by_ref().take(offset), &mut io::sink())
+            .with_context(|| format!("read {}", input.display()))?;
+        if skipped < offset {
+            anyhow::bail!("{} is shorter than offset {offset}", input.display());
+        }
+        return Ok(r);
+    }
+    let mut f = File::open(input).with_context(|| format!("open {}", input.display()))?;
+    if f.metadata()?.len() < offset {
+        anyhow::bail!("{} is shorter than offset {offset}", input.display());
+    }
+    f.seek(SeekFrom::Start(offset))?;
+    Ok(Box::new(BufReader::with_capacity(READER_BUF, f)))
+}
+
+/// Read a whole document input and split it into records, one per line.
+fn read_document(
+    parser: &dyn Parser,
+    input: &Path,
+    encoding: Encoding,
+) -> Result<Box<dyn BufRead + Send>> {
+    let mut doc = Vec::new();
+    open_decoded(input, encoding)?
+        .read_to_end(&mut doc)
+        .with_context(|| format!("read {}", input.display()))?;
+    let mut records = Vec::new();
+    parser
+        .split_document(&doc, &mut records)
+        .with_context(|| format!("split {}", input.display()))?;
+    Ok(Box::new(std::io::Cursor::new(records)))
+}
+
+/// Follow-mode reader: stream complete lines as they are appended to `path`.
+///
+/// A trailing partial line is held back until its newline arrives. If the
+/// file shrinks (truncated or rotated in place), reading restarts from the top.
+/// Returns once `stopped` says so (checked between reads).
+fn follow_lines(
+    path: &Path,
+    encoding: Encoding,
+    stdin: Option<Box<dyn BufRead + Send>>,
+    slabs: &mut SlabSender,
+    stopped: &dyn Fn() -> bool,
+) -> Result<()> {
+    if let Some(mut r) = stdin {
+        // stdin already blocks until more data arrives; EOF is final.
+        // Lines are handed over after every read so a slow pipe still streams.
+        loop {
+            if stopped() {
+                slabs.send_stop();
+                return Ok(());
+            }
+            if slabs.fill(&mut r)? == 0 {
+                slabs.send_all();
+                return Ok(());
+            }
+            if !slabs.send_complete() {
+                return Ok(());
+            }
+        }
+    }
+
+    // The file is read through the decoder; `fh` tells how far into it that got.
+    let open = || -> Result<(Arc<File>, Box<dyn BufRead + Send>)> {
+        let fh = Arc::new(File::open(path).with_context(|| format!("open {}", path.display()))?);
+        let r = BufReader::with_capacity(READER_BUF, fh.clone());
+        Ok((fh, encoding::decode(Box::new(r), encoding)?))
+    };
+
+    let (mut fh, mut r) = open()?;
+
+    loop {
+        if stopped() {
+            slabs.send_stop();
+            return Ok(());
+        }
+        let n = slabs.fill(&mut r)?;
+        if n > 0 {
+            if slabs.is_full() && !slabs.send_complete() {
+                return Ok(());
+            }
+            continue;
+        }
+
+        // Caught up: hand over what we have before waiting.
+        if !slabs.send_complete() {
+            return Ok(());
+        }
+        thread::sleep(FOLLOW_POLL);
+
+        let offset = (&*fh).stream_position()?;
+        let len = std::fs::metadata(path).map(|m| m.len()).unwrap_or(offset);
+        if len < offset {
+            (fh, r) = open()?;
+            slabs.buf.clear();
+            slabs.sent = 0;
+            slabs.lines = slabs.lines.map(|_| 0);
+        }
+    }
+}
+
+/* -------------------- Registry & utils -------------------- */
+
+pub type ParserFactory = fn() -> Box<dyn Parser>;
+
+/// Built-in modules.
+pub fn registry() -> &'static [ParserFactory] {
+    &[
+        crate::modules::web_access::new,
+        crate::modules::mactime::new,
+        crate::modules::csv::new,
+        crate::modules::logfmt::new,
+        crate::modules::cef::new,
+        crate::modules::leef::new,
+        crate::modules::json::new,
+        crate::modules::vpc_flow::new,
+        crate::modules::elb::new,
+        crate::modules::cloudtrail::new,
+        crate::modules::cloudfront::new,
+        crate::modules::cri::new,
+        crate::modules::docker_json::new,
+        crate::modules::haproxy::new,
+        crate::modules::squid::new,
+        crate::modules::postfix::new,
+        crate::modules::authlog::new,
+        crate::modules::auditd::new,
+        crate::modules::zeek::new,
+        crate::modules::suricata::new,
+        crate::modules::fortigate::new,
+        crate::modules::panos::new,
+        crate::modules::asa::new,
+        crate::modules::modsecurity::new,
+        crate::modules::nginx_error::new,
+        crate::modules::apache_error::new,
+        crate::modules::mysql_slow::new,
+        crate::modules::postgres::new,
+        crate::modules::journald::new,
+        crate::modules::winevt_xml::new,
+        crate::modules::okta::new,
+        crate::modules::m365_audit::new,
+        crate::modules::exchange_tracking::new,
+        crate::modules::nsg_flow::new,
+        crate::modules::gcp_audit::new,
+        crate::modules::cloudflare::new,
+        crate::modules::zscaler::new,
+        crate::modules::checkpoint::new,
+        crate::modules::bind::new,
+        crate::modules::resolver::new,
+        crate::modules::dhcp::new,
+        crate::modules::openvpn::new,
+        crate::modules::radius::new,
+        crate::modules::fail2ban::new,
+        crate::modules::xferlog::new,
+        crate::modules::selinux::new,
+        crate::modules::samba::new,
+        crate::modules::sysmon::new,
+        crate::modules::ps_transcript::new,
+    ]
+}
+
+/// Set of available modules. Starts from the built-ins (or empty) and lets
+/// embedding programs add their own parsers.
+#[derive(Clone, Default)]
+pub struct Registry {
+    factories: Vec<ParserFactory>,
+}
+
+impl Registry {
+    /// Registry with no modules.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registry pre-filled with the built-in modules.
+    pub fn with_builtin() -> Self {
+        Self {
+            factories: registry().to_vec(),
+        }
+    }
+
+    /// Add a module. A later registration wins over an earlier one with the same name.
+    pub fn register(&mut self, factory: ParserFactory) -> &mut Self {
+        self.factories.push(factory);
+        self
+    }
+
+    /// Module, or `,`-separated chain of modules (`docker-json,web-access`),
+    /// configured with `opts`. In a chain each key goes to every module
+    /// declaring it; a key none of them declares is an error.
+    pub fn create_configured(&self, spec: &str, opts: &ModuleOptions) -> Result<Box<dyn Parser>> {
+        let mut stages = spec
+            .split(',')
+            .map(str::trim)
+            .map(|name| {
+                self.create(name)
+                    .with_context(|| format!("unknown module: {name}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if let [parser] = stages.as_mut_slice() {
+            configure_parser(parser.as_mut(), opts)?;
+        } else {
+            let specs: Vec<OptionSpec> = stages
+                .iter()
+                .flat_map(|p| p.options().iter().copied())
+                .collect();
+            opts.check_known(spec, &specs)?;
+            for p in &mut stages {
+                let own = opts.only(p.options());
+                configure_parser(p.as_mut(), &own)?;
+            }
+        }
+
+        let mut parser = stages.pop().context("no module given")?;
+        while let Some(outer) = stages.pop() {
+            parser = Box::new(Chain::new(outer, parser)?);
+        }
+        Ok(parser)
+    }
+
+    /// Fresh instance of the module called `name`.
+    pub fn create(&self, name: &str) -> Option<Box<dyn Parser>> {
+        self.factories
+            .iter()
+            .rev()
+            .map(|f| f())
+            .find(|p| p.name() == name)
+    }
+
+    /// How well each module recognizes `sample` (non-blank lines), best first.
+    pub fn score(&self, sample: &[String]) -> Vec<ModuleScore> {
+        let mut scores: Vec<ModuleScore> = self
+            .parsers()
+            .into_iter()
+            .map(|p| ModuleScore {
+                matched: sample.iter().filter(|l| p.recognizes(l)).count(),
+                sampled: sample.len(),
+                module: p.name().into_owned(),
+            })
+            .collect();
+
+        scores.sort_by(|a, b| b.rate().total_cmp(&a.rate()).then(a.module.cmp(&b.module)));
+        scores
+    }
+
+    /// One fresh instance of every module, in registration order, skipping
+    /// names shadowed by a later registration.
+    pub fn parsers(&self) -> Vec<Box<dyn Parser>> {
+        let mut out: Vec<Box<dyn Parser>> = Vec::with_capacity(self.factories.len());
+        for p in self.factories.iter().map(|f| f()) {
+            match out.iter().position(|q| q.name() == p.name()) {
+                Some(i) => out[i] = p,
+                None => out.push(p),
+            }
+        }
+        out
+    }
+}
+
+/// Result of `Registry::score` for one module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleScore {
+    pub module: String,
+    pub matched: usize,
+    pub sampled: usize,
+}
+
+impl ModuleScore {
+    /// Fraction of sampled lines the module recognized (0.0 when nothing was sampled).
+    pub fn rate(&self) -> f64 {
+        if self.sampled == 0 {
+            0.0
+        } else {
+            self.matched as f64 / self.sampled as f64
+        }
+    }
+}
+
+/// First `max_lines` non-blank lines of `path` (plain, gzip or stdin),
+/// UTF-16 decoded as `--encoding auto` would.
+/// Invalid UTF-8 is replaced rather than skipped so it still counts against every module.
+pub fn sample_lines(path: &Path, max_lines: usize) -> Result<Vec<String>> {
+    let mut r = open_decoded(path, Encoding::Auto)?;
+    let mut out = Vec::with_capacity(max_lines.min(4096));
+    let mut buf = Vec::new();
+
+    while out.len() < max_lines {
+        buf.clear();
+        if r.read_until(b'\n', &mut buf)? == 0 {
+            break;
+        }
+        let line = String::from_utf8_lossy(&buf);
+        let line = line.trim_end_matches(['\n', '\r']);
+        if !line.trim().is_empty() {
+            out.push(line.to_string());
+        }
+    }
+    Ok(out)
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.2} {}", size, UNITS[unit])
+}
+
+/// Fast line counter for **both plain and .gz** files.
+///
+/// Uses a big chunked read and `memchr` to count `\n` without per-line allocation.
+/// For `.gz`, this does a full decompress pass (inevitable if you want an exact count).
+pub fn count_lines_any(path: &Path) -> Result<u64> {
+    let mut r = open_maybe_gz_read(path)?;
+    let mut buf = vec![0u8; 256 * 1024]; // 256 KiB chunks
+    let mut total = 0u64;
+
+    loop {
+        let n = r.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        total += memchr_iter(b'\n', &buf[..n]).count() as u64;
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::WriterSink;
+
+    /// Echoes each line back as-is, so output order is easy to check.
+    struct Echo;
+
+    impl Parser for Echo {
+        fn name(&self) -> Cow<'static, str> {
+            Cow::Borrowed("echo")
+        }
+
+        fn description(&self) -> Cow<'static, str> {
+            Cow::Borrowed("test parser")
+        }
+
+        fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+            out.extend_from_slice(line.as_bytes());
+            out.push(b'\n');
+            true
+        }
+    }
+
+    /// `Write` into a shared buffer the test can inspect after the run.
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn temp_input(name: &str, lines: usize) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("turbolp-{}-{name}", std::process::id()));
+        let body: String = (0..lines).map(|i| format!("{i}\n")).collect();
+        std::fs::write(&path, body).unwrap();
+        path
+    }
+
+    #[test]
+    fn registry_later_registration_shadows_builtin() {
+        fn custom_web() -> Box<dyn Parser> {
+            struct Custom;
+            impl Parser for Custom {
+                fn name(&self) -> Cow<'static, str> {
+                    Cow::Borrowed("web-access")
+                }
+                fn description(&self) -> Cow<'static, str> {
+                    Cow::Borrowed("custom")
+                }
+                fn process_line_to_buf(&self, _line: &str, _out: &mut Vec<u8>) -> bool {
+                    false
+                }
+            }
+            Box::new(Custom)
+        }
+
+        let mut reg = Registry::with_builtin();
+        let builtin_count = reg.parsers().len();
+        reg.register(custom_web);
+
+        assert_eq!(reg.create("web-access").unwrap().description(), "custom");
+        assert_eq!(reg.parsers().len(), builtin_count);
+        assert!(Registry::new().create("web-access").is_none());
+    }
+
+    #[test]
+    fn registry_score_ranks_matching_module_first() {
+        let sample = vec![
+            r#"1.2.3.4 - - [10/Oct/2000:13:55:36 -0700] "GET / HTTP/1.0" 200 5"#.to_string(),
+            r#"5.6.7.8 - bob [10/Oct/2000:13:55:37 -0700] "POST /a HTTP/1.1" 302 -"#.to_string(),
+        ];
+        let scores = Registry::with_builtin().score(&sample);
+
+        assert_eq!(scores[0].module, "web-access");
+        assert_eq!(scores[0].matched, 2);
+        let mactime = scores.iter().find(|s| s.module == "mactime").unwrap();
+        assert_eq!(mactime.matched, 0);
+    }
+
+    #[test]
+    fn validate_counts_fallback_records_as_failures() {
+        let path =
+            std::env::temp_dir().join(format!("turbolp-{}-validate.log", std::process::id()));
+        std::fs::write(
+            &path,
+            "1.2.3.4 - - [10/Oct/2000:13:55:36 -0700] \"GET / HTTP/1.0\" 200 5\nnot a log line\n\n",
+        )
+        .unwrap();
+
+        let parser = Registry::with_builtin().create("web-access").unwrap();
+        let opts = RunOptions {
+            workers: 2,
+            ..RunOptions::default()
+        };
+        let report = validate(parser.as_ref(), &path, &opts, 5).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(report.stats.parsed, 2);
+        assert_eq!(report.stats.blank, 1);
+        assert_eq!(report.fallback, 1);
+        assert_eq!(report.success_rate(), 0.5);
+        assert_eq!(report.examples, vec!["not a log line".to_string()]);
+        assert!(report.output_bytes > 0);
+    }
+
+    #[test]
+    fn document_modules_split_whole_inputs() {
+        struct Words;
+        impl Parser for Words {
+            fn name(&self) -> Cow<'static, str> {
+                Cow::Borrowed("words")
+            }
+            fn description(&self) -> Cow<'static, str> {
+                Cow::Borrowed("")
+            }
+            fn input_format(&self) -> InputFormat {
+                InputFormat::Document
+            }
+            fn split_document(&self, doc: &[u8], out: &mut Vec<u8>) -> Result<()> {
+                for w in doc
+                    .split(|b| b.is_ascii_whitespace())
+                    .filter(|w| !w.is_empty())
+                {
+                    out.extend_from_slice(w);
+                    out.push(b'\n');
+                }
+                Ok(())
+            }
+            fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+                out.extend_from_slice(format!("\"{line}\"\n").as_bytes());
+                true
+            }
+        }
+
+        let path = std::env::temp_dir().join(format!("turbolp-{}-doc.txt", std::process::id()));
+        std::fs::write(&path, "a b\nc").unwrap();
+        let out = SharedBuf::default();
+        let sink = Box::new(WriterSink::new(Box::new(out.clone()), None).unwrap());
+        let opts = RunOptions {
+            workers: 1,
+            ..RunOptions::default()
+        };
+        let stats = run_streaming_parallel(&Words, &path, sink, &opts).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(stats.records, 3);
+        assert_eq!(&*out.0.lock().unwrap(), b"\"a\"\n\"b\"\n\"c\"\n");
+
+        let follow = RunOptions {
+            follow: true,
+            ..opts
+        };
+        let sink = Box::new(WriterSink::new(Box::new(out.clone()), None).unwrap());
+        assert!(run_streaming_parallel(&Words, &path, sink, &follow).is_err());
+    }
+
+    #[test]
+    fn follow_flushes_while_lines_keep_arriving() {
+        let path = std::env::temp_dir().join(format!("turbolp-{}-trickle.log", std::process::id()));
+        std::fs::write(&path, "").unwrap();
+        let out = SharedBuf::default();
+        let sink = Box::new(WriterSink::new(Box::new(out.clone()), None).unwrap());
+        let stop = StopSignal::new();
+        let opts = RunOptions {
+            workers: 1,
+            follow: true,
+            stop: Some(stop.clone()),
+            ..RunOptions::default()
+        };
+
+        thread::scope(|s| {
+            let run = s.spawn(|| run_streaming_parallel(&Echo, &path, sink, &opts));
+            // A line every 200 ms: the worker never sits idle for FOLLOW_FLUSH.
+            let mut file = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&path)
+                .unwrap();
+            for i in 0..10 {
+                writeln!(file, "{i}").unwrap();
+                thread::sleep(Duration::from_millis(200));
+            }
+            let seen = out.0.lock().unwrap().len();
+            stop.request();
+            run.join().unwrap().unwrap();
+            assert!(seen > 0, "nothing written while input kept arriving");
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn follow_restarts_line_numbers_after_truncation() {
+        let path = std::env::temp_dir().join(format!("turbolp-{}-rotate.log", std::process::id()));
+        std::fs::write(&path, "{\"a\":1}\n{\"b\":2}\n").unwrap();
+        let file = serde_json::to_string(&path.to_string_lossy()).unwrap();
+        let out = SharedBuf::default();
+        let sink = Box::new(WriterSink::new(Box::new(out.clone()), None).unwrap());
+        let stop = StopSignal::new();
+        let opts = RunOptions {
+            workers: 1,
+            follow: true,
+            provenance: true,
+            stop: Some(stop.clone()),
+            ..RunOptions::default()
+        };
+        let wait_for = |records: usize| {
+            for _ in 0..100 {
+                if memchr_iter(b'\n', &out.0.lock().unwrap()).count() >= records {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+            panic!("fewer than {records} records written");
+        };
+
+        thread::scope(|s| {
+            let run = s.spawn(|| run_streaming_parallel(&Echo, &path, sink, &opts));
+            wait_for(2);
+            std::fs::write(&path, "{\"c\":3}\n").unwrap();
+            wait_for(3);
+            stop.request();
+            run.join().unwrap().unwrap();
+        });
+        std::fs::remove_file(&path).unwrap();
+
+        let out = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            out.lines().last().unwrap(),
+            format!(r#"{{"c":3,"_src_file":{file},"_src_line":1,"_src_offset":0}}"#)
+        );
+    }
+
+    #[test]
+    fn for_input_sees_head_lines_before_the_run() {
+        struct Tagged(String);
+        impl Parser for Tagged {
+            fn name(&self) -> Cow<'static, str> {
+                Cow::Borrowed("tagged")
+            }
+            fn description(&self) -> Cow<'static, str> {
+                Cow::Borrowed("")
+            }
+            fn for_input(&self, head: &[&str]) -> Result<Option<Box<dyn Parser>>> {
+                Ok(head
+                    .iter()
+                    .find_map(|l| l.strip_prefix("#tag "))
+                    .map(|t| Box::new(Tagged(t.to_string())) as Box<dyn Parser>))
+            }
+            fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+                if line.starts_with('#') {
+                    return false;
+                }
+                out.extend_from_slice(format!("{}:{line}\n", self.0).as_bytes());
+                true
+            }
+        }
+
+        let path = std::env::temp_dir().join(format!("turbolp-{}-head.txt", std::process::id()));
+        std::fs::write(&path, "#tag T\na\nb\n").unwrap();
+        let out = SharedBuf::default();
+        let sink = Box::new(WriterSink::new(Box::new(out.clone()), None).unwrap());
+        let opts = RunOptions {
+            workers: 1,
+            ordered: true,
+            ..RunOptions::default()
+        };
+        run_streaming_parallel(&Tagged("none".into()), &path, sink, &opts).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(&*out.0.lock().unwrap(), b"T:a\nT:b\n");
+    }
+
+    #[test]
+    fn line_joiner_records_keep_embedded_newlines() {
+        /// Indented lines continue the previous record.
+        #[derive(Default)]
+        struct Indent(LinePos, Vec<u8>);
+        impl LineJoiner for Indent {
+            fn push(&mut self, line: &[u8], at: LinePos, emit: &mut dyn FnMut(&[u8], LinePos)) {
+                if line.starts_with(b" ") && !self.1.is_empty() {
+                    self.1.push(b'\n');
+                    self.1.extend_from_slice(line);
+                    return;
+                }
+                if !self.1.is_empty() {
+                    emit(&self.1, self.0);
+                }
+                *self = Indent(at, line.to_vec());
+            }
+            fn finish(&mut self, emit: &mut dyn FnMut(&[u8], LinePos)) {
+                if !self.1.is_empty() {
+                    emit(&std::mem::take(&mut self.1), self.0);
+                }
+            }
+        }
+
+        struct Joined;
+        impl Parser for Joined {
+            fn name(&self) -> Cow<'static, str> {
+                Cow::Borrowed("joined")
+            }
+            fn description(&self) -> Cow<'static, str> {
+                Cow::Borrowed("")
+            }
+            fn line_joiner(&self) -> Option<Box<dyn LineJoiner>> {
+                Some(Box::<Indent>::default())
+            }
+            fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+                out.extend_from_slice(format!("{}\n", line.lines().count()).as_bytes());
+                true
+            }
+        }
+
+        // Enough records to span several slabs.
+        let mut input = String::new();
+        for i in 0..20_000 {
+            input.push_str(&format!("record {i}\n  at frame a\n  at frame b\n"));
+        }
+        let path = std::env::temp_dir().join(format!("turbolp-{}-join.txt", std::process::id()));
+        std::fs::write(&path, input).unwrap();
+
+        let out = SharedBuf::default();
+        let sink = Box::new(WriterSink::new(Box::new(out.clone()), None).unwrap());
+        let opts = RunOptions {
+            workers: 3,
+            ordered: true,
+            ..RunOptions::default()
+        };
+        let stats = run_streaming_parallel(&Joined, &path, sink, &opts).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(stats.records, 20_000);
+        assert!(out
+            .0
+            .lock()
+            .unwrap()
+            .split(|&b| b == b'\n')
+            .filter(|l| !l.is_empty())
+            .all(|l| l == b"3"));
+    }
+
+    #[test]
+    fn provenance_points_at_the_first_line_of_joined_records() {
+        let input =
+            std::env::temp_dir().join(format!("turbolp-{}-src-join.log", std::process::id()));
+        let file = serde_json::to_string(&input.to_string_lossy()).unwrap();
+        let run = |module: &str, body: &str| {
+            std::fs::write(&input, body).unwrap();
+            let registry = Registry::with_builtin();
+            let parser = match module {
+                "json" => {
+                    Box::new(Multiline::new(registry.create("json").unwrap(), r"^\{").unwrap())
+                }
+                other => registry.create(other).unwrap(),
+            };
+            let buf = SharedBuf::default();
+            let sink = Box::new(WriterSink::new(Box::new(buf.clone()), None).unwrap());
+            let opts = RunOptions {
+                ordered: true,
+                provenance: true,
+                ..RunOptions::default()
+            };
+            run_streaming_parallel(parser.as_ref(), &input, sink, &opts).unwrap();
+            String::from_utf8(buf.0.lock().unwrap().clone()).unwrap()
+        };
+
+        let src = |line: u64, offset: u64| {
+            format!(r#""_src_file":{file},"_src_line":{line},"_src_offset":{offset}"#)
+        };
+        assert_eq!(
+            run("json", "{\"a\":\n  1}\n\n{\"b\":\n 2,\n \"c\": 3}\n"),
+            format!(
+                "{{\"a\":1,{}}}\n{{\"b\":2,\"c\":3,{}}}\n",
+                src(1, 0),
+                src(4, 12)
+            )
+        );
+        // Records split out of a whole document point at its start.
+        let out = run(
+            "cloudtrail",
+            r#"{"Records":[{"eventName":"A"},{"eventName":"B"}]}"#,
+        );
+        assert_eq!(out.lines().count(), 2);
+        assert!(out
+            .lines()
+            .all(|l| l.ends_with(&format!("{}}}", src(1, 0)))));
+        std::fs::remove_file(&input).unwrap();
+    }
+
+    #[test]
+    fn multiline_start_joins_continuation_lines() {
+        let json = Registry::with_builtin().create("json").unwrap();
+        let p = Multiline::new(json, r"^\{").unwrap();
+        assert_eq!(p.name(), "json");
+
+        let mut j = p.line_joiner().unwrap();
+        let mut records = Vec::new();
+        for l in [
+            "stray",
+            "{",
+            "  \"a\": {",
+            "    \"b\": 1",
+            "  }",
+            "}",
+            "{\"c\": 2}",
+        ] {
+            j.push(l.as_bytes(), LinePos::default(), &mut |r, _| {
+                records.push(r.to_vec())
+            });
+        }
+        j.finish(&mut |r, _| records.push(r.to_vec()));
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0], b"stray");
+
+        let mut out = Vec::new();
+        assert!(p.process_line_to_buf(std::str::from_utf8(&records[1]).unwrap(), &mut out));
+        let v: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(v["a.b"], 1);
+
+        assert!(Multiline::new(Registry::with_builtin().create("json").unwrap(), "(").is_err());
+    }
+
+    #[test]
+    fn chained_modules_nest_the_inner_record() {
+        let registry = Registry::with_builtin();
+        let opts = ModuleOptions::parse(&["separator=_"]).unwrap();
+        let p = registry
+            .create_configured("cri, docker-json ,json", &opts)
+            .unwrap();
+        assert_eq!(p.name(), "cri,docker-json,json");
+
+        let mut out = Vec::new();
+        assert!(p.process_line_to_buf(
+            r#"2024-01-02T15:04:05Z stdout F {"log":"{\"a\":{\"b\":1}}\n","stream":"stderr"}"#,
+            &mut out
+        ));
+        let v: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(v["stream"], "stdout");
+        assert_eq!(v["parsed"]["stream"], "stderr");
+        assert_eq!(v["parsed"]["parsed"]["a_b"], 1);
+
+        out.clear();
+        assert!(p.process_line_to_buf("2024-01-02T15:04:05Z stdout F plain text", &mut out));
+        let v: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert!(v["parsed"].is_null());
+    }
+
+    #[test]
+    fn chain_errors() {
+        let registry = Registry::with_builtin();
+        let none = ModuleOptions::default();
+        // logfmt records carry no embedded message.
+        assert!(registry.create_configured("logfmt,json", &none).is_err());
+        assert!(registry.create_configured("cri,nope", &none).is_err());
+        let unknown = ModuleOptions::parse(&["nope=1"]).unwrap();
+        assert!(registry.create_configured("cri,json", &unknown).is_err());
+    }
+
+    #[test]
+    fn group_joiner_groups_by_key_and_flushes_oldest() {
+        // `<key> <text>`; text `end` closes the group, no key passes through.
+        fn key(line: &str) -> Option<(&str, bool)> {
+            let (k, text) = line.split_once(' ')?;
+            Some((k, text == "end"))
+        }
+
+        let mut j = GroupJoiner::new(key, 2);
+        let mut out = Vec::new();
+        for l in [
+            "a 1", "b 1", "lone", "a end", "c 1", "d 1", "b end", "x end",
+        ] {
+            j.push(l.as_bytes(), LinePos::default(), &mut |r, _| {
+                out.push(r.to_vec())
+            });
+        }
+        j.finish(&mut |r, _| out.push(r.to_vec()));
+
+        let out: Vec<&str> = out
+            .iter()
+            .map(|r| std::str::from_utf8(r).unwrap())
+            .collect();
+        assert_eq!(
+            out,
+            ["lone", "a 1\na end", "b 1", "b end", "x end", "c 1", "d 1"]
+        );
+    }
+
+    #[test]
+    fn block_joiner_splits_on_start_and_end_lines() {
+        // `[` opens a record, `]` closes it.
+        let boundary = |line: &str| match line {
+            "[" => Boundary::Start,
+            "]" => Boundary::End,
+            _ => Boundary::Inside,
+        };
+
+        let mut j = BlockJoiner::new(boundary, 3);
+        let mut out = Vec::new();
+        for l in ["lone", "[", "a", "]", "stray", "[", "b", "[", "c", "d", "e"] {
+            j.push(l.as_bytes(), LinePos::default(), &mut |r, _| {
+                out.push(r.to_vec())
+            });
+        }
+        j.finish(&mut |r, _| out.push(r.to_vec()));
+
+        let out: Vec<&str> = out
+            .iter()
+            .map(|r| std::str::from_utf8(r).unwrap())
+            .collect();
+        assert_eq!(out, ["lone", "[\na\n]", "stray", "[\nb", "[\nc\nd", "e"]);
+    }
+
+    #[test]
+    fn module_options_last_value_wins_and_unknown_keys_fail() {
+        let opts = ModuleOptions::parse(&["a=1", "b=x,y", "a=2", "c="]).unwrap();
+        assert_eq!(opts.get("a"), Some("2"));
+        assert_eq!(opts.get_all("a").collect::<Vec<_>>(), vec!["1", "2"]);
+        assert_eq!(opts.list("b").unwrap(), vec!["x", "y"]);
+        assert_eq!(opts.flag("c").unwrap(), Some(true));
+        assert!(ModuleOptions::parse(&["novalue"]).is_err());
+
+        let specs = [OptionSpec { key: "a", help: "" }];
+        assert!(opts.check_known("m", &specs).is_err());
+        assert!(ModuleOptions::parse(&["a=1"])
+            .unwrap()
+            .check_known("m", &specs)
+            .is_ok());
+    }
+
+    #[test]
+    fn buffer_pool_recycles_cleared_buffers() {
+        let pool = BufferPool::new(1, 16);
+        let mut a = pool.get();
+        a.extend_from_slice(b"hello");
+        let ptr = a.as_ptr();
+        pool.put(a);
+
+        let b = pool.get();
+        assert!(b.is_empty());
+        assert_eq!(b.as_ptr(), ptr);
+
+        // Full pool drops extras instead of blocking.
+        pool.put(b);
+        pool.put(Vec::new());
+    }
+
+    #[test]
+    fn reads_every_gzip_member_and_bgzf() {
+        use flate2::{write::GzEncoder, Compression};
+
+        let path = std::env::temp_dir().join(format!("turbolp-{}-members.gz", std::process::id()));
+        let mut members = Vec::new();
+        for part in [&b"a\nb\n"[..], b"c\n"] {
+            let mut enc = GzEncoder::new(Vec::new(), Compression::fast());
+            enc.write_all(part).unwrap();
+            members.extend(enc.finish().unwrap());
+        }
+        std::fs::write(&path, members).unwrap();
+        let mut out = String::new();
+        open_maybe_gz_read(&path)
+            .unwrap()
+            .read_to_string(&mut out)
+            .unwrap();
+        assert_eq!(out, "a\nb\nc\n");
+
+        let data: String = (0..50_000).map(|i| format!("{i}\n")).collect();
+        std::fs::write(&path, bgzf::tests::bgzip(data.as_bytes(), 4096)).unwrap();
+        assert_eq!(count_lines_any(&path).unwrap(), 50_000);
+        let mut out = String::new();
+        open_maybe_gz_bufread(&path, READER_BUF)
+            .unwrap()
+            .read_to_string(&mut out)
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(out, data);
+    }
+
+    #[test]
+    fn slab_lines_strips_terminators_and_keeps_final_partial_line() {
+        let lines: Vec<&[u8]> = slab_lines(b"a\r\n\nb\nc").collect();
+        assert_eq!(lines, vec![&b"a"[..], b"", b"b", b"c"]);
+        assert_eq!(slab_lines(b"").count(), 0);
+    }
+
+    /// Accepts only lines starting with a digit.
+    struct DigitsOnly;
+
+    impl Parser for DigitsOnly {
+        fn name(&self) -> Cow<'static, str> {
+            Cow::Borrowed("digits")
+        }
+
+        fn description(&self) -> Cow<'static, str> {
+            Cow::Borrowed("test parser")
+        }
+
+        fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+            if !line.starts_with(|c: char| c.is_ascii_digit()) {
+                return false;
+            }
+            out.extend_from_slice(b"{}\n");
+            true
+        }
+    }
+
+    #[test]
+    fn counts_rejected_and_blank_lines_and_writes_rejects() {
+        let input =
+            std::env::temp_dir().join(format!("turbolp-{}-rejects.log", std::process::id()));
+        std::fs::write(&input, b"1\nnope\n\n  \n2\n\xff\xfe\nbad\r\n").unwrap();
+
+        let rejects = SharedBuf::default();
+        let opts = RunOptions {
+            workers: 2,
+            rejects: Some(Arc::new(Mutex::new(rejects.clone()))),
+            ..RunOptions::default()
+        };
+        let sink = Box::new(WriterSink::new(Box::new(std::io::sink()), None).unwrap());
+
+        let stats = run_streaming_parallel(&DigitsOnly, &input, sink, &opts).unwrap();
+        std::fs::remove_file(&input).unwrap();
+
+        assert_eq!(
+            stats,
+            RunStats {
+                lines: 7,
+                parsed: 2,
+                rejected: 3,
+                blank: 2,
+                records: 2,
+            }
+        );
+        let mut lines: Vec<Vec<u8>> = rejects
+            .0
+            .lock()
+            .unwrap()
+            .split(|&b| b == b'\n')
+            .map(|l| l.to_vec())
+            .filter(|l| !l.is_empty())
+            .collect();
+        lines.sort();
+        assert_eq!(
+            lines,
+            vec![b"bad".to_vec(), b"nope".to_vec(), b"\xff\xfe".to_vec()]
+        );
+    }
+
+    #[test]
+    fn invalid_utf8_reaches_the_module_instead_of_being_dropped() {
+        struct Lengths;
+
+        impl Parser for Lengths {
+            fn name(&self) -> Cow<'static, str> {
+                Cow::Borrowed("lengths")
+            }
+
+            fn description(&self) -> Cow<'static, str> {
+                Cow::Borrowed("test parser")
+            }
+
+            fn process_line_to_buf(&self, _line: &str, _out: &mut Vec<u8>) -> bool {
+                unreachable!("workers hand over bytes")
+            }
+
+            fn process_bytes_to_buf(&self, line: &[u8], out: &mut Vec<u8>) -> bool {
+                out.extend_from_slice(format!("{}\n", line.len()).as_bytes());
+                true
+            }
+        }
+
+        let input = std::env::temp_dir().join(format!("turbolp-{}-utf8.log", std::process::id()));
+        std::fs::write(&input, b"ok\nbad\xffbyte\n").unwrap();
+        let run = |parser: &dyn Parser| {
+            let buf = SharedBuf::default();
+            let sink = Box::new(WriterSink::new(Box::new(buf.clone()), None).unwrap());
+            let opts = RunOptions {
+                ordered: true,
+                ..RunOptions::default()
+            };
+            let stats = run_streaming_parallel(parser, &input, sink, &opts).unwrap();
+            assert_eq!((stats.parsed, stats.rejected), (2, 0));
+            String::from_utf8(buf.0.lock().unwrap().clone()).unwrap()
+        };
+
+        assert_eq!(run(&Echo), "ok\nbad\u{FFFD}byte\n");
+        assert_eq!(run(&Lengths), "2\n8\n");
+        std::fs::remove_file(&input).unwrap();
+    }
+
+    #[test]
+    fn decodes_utf16_inputs_and_rejects_invalid_utf8_when_asked() {
+        let input = std::env::temp_dir().join(format!("turbolp-{}-utf16.log", std::process::id()));
+        let run = |bytes: &[u8], encoding: Encoding| {
+            std::fs::write(&input, bytes).unwrap();
+            let buf = SharedBuf::default();
+            let sink = Box::new(WriterSink::new(Box::new(buf.clone()), None).unwrap());
+            let opts = RunOptions {
+                ordered: true,
+                encoding,
+                ..RunOptions::default()
+            };
+            let stats = run_streaming_parallel(&Echo, &input, sink, &opts).unwrap();
+            let out = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+            (out, stats.rejected)
+        };
+
+        let utf16: Vec<u8> = [0xFEFF]
+            .into_iter()
+            .chain("caf\u{e9}\r\nok\r\n".encode_utf16())
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        assert_eq!(run(&utf16, Encoding::Auto), ("caf\u{e9}\nok\n".into(), 0));
+        assert_eq!(
+            run(b"caf\xe9\nok\n", Encoding::Latin1),
+            ("caf\u{e9}\nok\n".into(), 0)
+        );
+        assert_eq!(run(b"caf\xe9\nok\n", Encoding::Utf8), ("ok\n".into(), 1));
+        std::fs::remove_file(&input).unwrap();
+    }
+
+    #[test]
+    fn provenance_tags_records_with_file_line_and_offset() {
+        let input = std::env::temp_dir().join(format!("turbolp-{}-src.log", std::process::id()));
+        std::fs::write(&input, "{\"a\":1}\n\n{}\n{\"b\":2}\r\n{\"c\":3}").unwrap();
+        let file = serde_json::to_string(&input.to_string_lossy()).unwrap();
+        let run = |mmap: bool, start_offset: u64| {
+            let buf = SharedBuf::default();
+            let sink = Box::new(WriterSink::new(Box::new(buf.clone()), None).unwrap());
+            let opts = RunOptions {
+                ordered: true,
+                mmap,
+                start_offset,
+                provenance: true,
+                ..RunOptions::default()
+            };
+            run_streaming_parallel(&Echo, &input, sink, &opts).unwrap();
+            String::from_utf8(buf.0.lock().unwrap().clone()).unwrap()
+        };
+
+        let src = |line: u64, offset: u64| {
+            format!(r#""_src_file":{file},"_src_line":{line},"_src_offset":{offset}"#)
+        };
+        let tail = format!("{{\"b\":2,{}}}\n{{\"c\":3,{}}}\n", src(4, 12), src(5, 21));
+        let all = format!("{{\"a\":1,{}}}\n{{{}}}\n{tail}", src(1, 0), src(3, 9));
+        assert_eq!(run(true, 0), all);
+        assert_eq!(run(false, 0), all);
+        assert_eq!(run(true, 12), tail);
+        std::fs::remove_file(&input).unwrap();
+    }
+
+    #[test]
+    fn error_policy_emits_raw_records_or_aborts() {
+        let input =
+            std::env::temp_dir().join(format!("turbolp-{}-on-error.log", std::process::id()));
+        std::fs::write(&input, "1\nnope \"x\"\n\n2\n").unwrap();
+        let run = |on_error: OnError| {
+            let buf = SharedBuf::default();
+            let sink = Box::new(WriterSink::new(Box::new(buf.clone()), None).unwrap());
+            let opts = RunOptions {
+                ordered: true,
+                on_error,
+                ..RunOptions::default()
+            };
+            run_streaming_parallel(&DigitsOnly, &input, sink, &opts).map(|stats| {
+                let out = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+                (out, stats.rejected, stats.records)
+            })
+        };
+
+        assert_eq!(run(OnError::Skip).unwrap(), ("{}\n{}\n".into(), 1, 2));
+        assert_eq!(
+            run(OnError::Raw).unwrap(),
+            (
+                "{}\n{\"unparsed\":true,\"raw\":\"nope \\\"x\\\"\"}\n{}\n".into(),
+                1,
+                3
+            )
+        );
+        let want = format!(
+            r#"module digits cannot parse a line of {}: nope "x""#,
+            input.display()
+        );
+        assert_eq!(run(OnError::Abort).unwrap_err().to_string(), want);
+        std::fs::remove_file(&input).unwrap();
+    }
+
+    /// `DigitsOnly`, counting the lines it is given.
+    #[derive(Default)]
+    struct CountingDigits(AtomicU64);
+
+    impl Parser for CountingDigits {
+        fn name(&self) -> Cow<'static, str> {
+            DigitsOnly.name()
+        }
+
+        fn description(&self) -> Cow<'static, str> {
+            Cow::Borrowed("test parser")
+        }
+
+        fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            DigitsOnly.process_line_to_buf(line, out)
+        }
+    }
+
+    #[test]
+    fn abort_stops_every_stage_early() {
+        let input = temp_input("abort.log", 1_000_000);
+        let mut body = b"bad\n".to_vec();
+        body.extend_from_slice(&std::fs::read(&input).unwrap());
+        std::fs::write(&input, &body).unwrap();
+
+        for ordered in [false, true] {
+            let buf = SharedBuf::default();
+            let sink = Box::new(WriterSink::new(Box::new(buf.clone()), None).unwrap());
+            let opts = RunOptions {
+                workers: 4,
+                ordered,
+                on_error: OnError::Abort,
+                ..RunOptions::default()
+            };
+            let parser = CountingDigits::default();
+            assert!(run_streaming_parallel(&parser, &input, sink, &opts).is_err());
+            // A few batches per worker at most, not the million lines after.
+            let seen = parser.0.load(Ordering::Relaxed);
+            assert!(seen < 100_000, "parsed {seen} lines");
+            assert!(buf.0.lock().unwrap().len() < 200_000);
+        }
+        std::fs::remove_file(&input).unwrap();
+    }
+
+    #[test]
+    fn ordered_mode_preserves_input_order() {
+        let input = temp_input("ordered.log", 20_000);
+        let buf = SharedBuf::default();
+        let sink = Box::new(WriterSink::new(Box::new(buf.clone()), None).unwrap());
+        let opts = RunOptions {
+            workers: 4,
+            ordered: true,
+            ..RunOptions::default()
+        };
+
+        let stats = run_streaming_parallel(&Echo, &input, sink, &opts).unwrap();
+        std::fs::remove_file(&input).unwrap();
+
+        assert_eq!(stats.records, 20_000);
+        let out = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+        let expected: String = (0..20_000).map(|i| format!("{i}\n")).collect();
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn mapped_and_read_inputs_give_the_same_records() {
+        let input = temp_input("mmap.log", 50_000);
+        let mut body = std::fs::read(&input).unwrap();
+        body.extend_from_slice(b"crlf\r\n\nlast");
+        std::fs::write(&input, &body).unwrap();
+
+        let run = |mmap: bool| {
+            let buf = SharedBuf::default();
+            let sink = Box::new(WriterSink::new(Box::new(buf.clone()), None).unwrap());
+            let opts = RunOptions {
+                workers: 3,
+                ordered: true,
+                mmap,
+                ..RunOptions::default()
+            };
+            let stats = run_streaming_parallel(&Echo, &input, sink, &opts).unwrap();
+            (stats, buf.0.lock().unwrap().clone())
+        };
+        let (mapped, read) = (run(true), run(false));
+        std::fs::remove_file(&input).unwrap();
+
+        assert_eq!(mapped, read);
+        assert_eq!(mapped.0.lines, 50_003);
+        assert!(mapped.1.ends_with(b"49999\ncrlf\nlast\n"));
+    }
+
+    /// Echoes lines and requests a stop on the first one it sees.
+    struct StopAtOnce(Arc<StopSignal>);
+
+    impl Parser for StopAtOnce {
+        fn name(&self) -> Cow<'static, str> {
+            Cow::Borrowed("stop")
+        }
+
+        fn description(&self) -> Cow<'static, str> {
+            Cow::Borrowed("test parser")
+        }
+
+        fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+            self.0.request();
+            Echo.process_line_to_buf(line, out)
+        }
+    }
+
+    #[test]
+    fn stop_writes_every_line_read_up_to_the_offset() {
+        let input = temp_input("stop.log", 500_000);
+        let buf = SharedBuf::default();
+        let sink = Box::new(WriterSink::new(Box::new(buf.clone()), None).unwrap());
+        let stop = StopSignal::new();
+        let opts = RunOptions {
+            workers: 2,
+            ordered: true,
+            stop: Some(stop.clone()),
+            ..RunOptions::default()
+        };
+
+        let stats = run_streaming_parallel(&StopAtOnce(stop.clone()), &input, sink, &opts).unwrap();
+        let body = std::fs::read(&input).unwrap();
+        std::fs::remove_file(&input).unwrap();
+
+        assert!(stats.lines > 0 && stats.lines < 500_000);
+        let out = buf.0.lock().unwrap().clone();
+        assert_eq!(out.len() as u64, stop.offset());
+        assert_eq!(out, body[..out.len()]);
+        assert_eq!(out.last(), Some(&b'\n'));
+    }
+
+    #[test]
+    fn checkpoints_match_written_output_and_resume_from_offset() {
+        let input = temp_input("checkpoint.log", 200_000);
+        let body = std::fs::read(&input).unwrap();
+        let saves = Arc::new(Mutex::new(Vec::new()));
+        let buf = SharedBuf::default();
+        let sink = Box::new(WriterSink::new(Box::new(buf.clone()), None).unwrap());
+        let recorded = saves.clone();
+        let opts = RunOptions {
+            workers: 4,
+            checkpoint: Some(Checkpoints {
+                every: Duration::ZERO,
+                save: Arc::new(move |input, written| {
+                    recorded.lock().unwrap().push((input, written));
+                    Ok(())
+                }),
+            }),
+            ..RunOptions::default()
+        };
+        run_streaming_parallel(&Echo, &input, sink, &opts).unwrap();
+
+        // Echo writes each line back, so every save point is a common prefix.
+        let saves = saves.lock().unwrap().clone();
+        assert!(saves.len() > 1);
+        for &(input_offset, written) in &saves {
+            assert_eq!(input_offset, written);
+        }
+        assert_eq!(saves.last(), Some(&(body.len() as u64, body.len() as u64)));
+        assert_eq!(*buf.0.lock().unwrap(), body);
+
+        let (mid, _) = saves[saves.len() / 2];
+        let buf = SharedBuf::default();
+        let sink = Box::new(WriterSink::new(Box::new(buf.clone()), None).unwrap());
+        let opts = RunOptions {
+            workers: 4,
+            ordered: true,
+            start_offset: mid,
+            ..RunOptions::default()
+        };
+        run_streaming_parallel(&Echo, &input, sink, &opts).unwrap();
+        std::fs::remove_file(&input).unwrap();
+
+        assert_eq!(*buf.0.lock().unwrap(), body[mid as usize..]);
+    }
+}
@@ -0,0 +1,299 @@
+//! `--encoding`: inputs that are not UTF-8 (UTF-16 logs from Windows hosts,
+//! Latin-1 exports) decoded on the reader thread, so modules only ever see
+//! UTF-8.
+
+use std::io::{self, BufRead};
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::core::{open_maybe_gz_bufread, READER_BUF};
+
+/// Text encoding of an input.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Encoding {
+    /// From the byte order mark; else UTF-16 if the first bytes look like
+    /// it, else UTF-8 with invalid bytes replaced.
+    #[default]
+    Auto,
+    /// UTF-8; lines that are not valid UTF-8 are rejected.
+    Utf8,
+    /// UTF-8, invalid bytes replaced by U+FFFD before any module sees them.
+    Utf8Lossy,
+    /// ISO-8859-1: every byte is one character.
+    Latin1,
+    /// UTF-16, little-endian (Windows' "Unicode").
+    #[value(name = "utf16le")]
+    #[serde(rename = "utf16le")]
+    Utf16Le,
+    /// UTF-16, big-endian.
+    #[value(name = "utf16be")]
+    #[serde(rename = "utf16be")]
+    Utf16Be,
+}
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+/// Bytes looked at to tell UTF-16 without a byte order mark (as many as
+/// `open_peek` gives).
+const SNIFF: usize = 64;
+
+impl Encoding {
+    /// The `--encoding` value.
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Auto => "auto",
+            Encoding::Utf8 => "utf8",
+            Encoding::Utf8Lossy => "utf8-lossy",
+            Encoding::Latin1 => "latin1",
+            Encoding::Utf16Le => "utf16le",
+            Encoding::Utf16Be => "utf16be",
+        }
+    }
+
+    /// The decoding for an input starting with `head`, and the length of
+    /// its byte order mark. `Auto` never comes back; `Utf8` means the bytes
+    /// pass through as they are.
+    pub(crate) fn resolve(self, head: &[u8]) -> (Encoding, usize) {
+        match self {
+            Encoding::Auto | Encoding::Utf8 if head.starts_with(UTF8_BOM) => (Encoding::Utf8, 3),
+            Encoding::Utf8Lossy if head.starts_with(UTF8_BOM) => (self, 3),
+            Encoding::Auto | Encoding::Utf16Le if head.starts_with(UTF16LE_BOM) => {
+                (Encoding::Utf16Le, 2)
+            }
+            Encoding::Auto | Encoding::Utf16Be if head.starts_with(UTF16BE_BOM) => {
+                (Encoding::Utf16Be, 2)
+            }
+            Encoding::Auto => (sniff_utf16(head).unwrap_or(Encoding::Utf8), 0),
+            _ => (self, 0),
+        }
+    }
+
+    /// Whether inputs are read as they are, so byte offsets in the file and
+    /// in the decoded text agree.
+    pub(crate) fn is_verbatim(self, head: &[u8]) -> bool {
+        self.resolve(head) == (Encoding::Utf8, 0)
+    }
+}
+
+/// UTF-16 text without a byte order mark: mostly ASCII, so every other byte
+/// is zero.
+fn sniff_utf16(head: &[u8]) -> Option<Encoding> {
+    let pairs: Vec<&[u8]> = head[..head.len().min(SNIFF)].chunks_exact(2).collect();
+    if pairs.len() < 2 {
+        return None;
+    }
+    let zeros = |i: usize| pairs.iter().filter(|p| p[i] == 0).count();
+    let (first, second) = (zeros(0), zeros(1));
+    let most = pairs.len() * 3 / 4;
+    let few = pairs.len() / 8;
+    match () {
+        _ if second >= most && first <= few => Some(Encoding::Utf16Le),
+        _ if first >= most && second <= few => Some(Encoding::Utf16Be),
+        _ => None,
+    }
+}
+
+/// The encoding `path` is read as under `encoding`, from its first bytes
+/// (after decompression).
+pub fn detect(path: &Path, encoding: Encoding) -> Result<Encoding> {
+    let mut r = open_maybe_gz_bufread(path, READER_BUF)?;
+    Ok(encoding.resolve(r.fill_buf()?).0)
+}
+
+/// `r` decoded to UTF-8 as `encoding` says, without its byte order mark.
+pub(crate) fn decode(
+    mut r: Box<dyn BufRead + Send>,
+    encoding: Encoding,
+) -> io::Result<Box<dyn BufRead + Send>> {
+    let (encoding, bom) = encoding.resolve(r.fill_buf()?);
+    r.consume(bom);
+    if encoding == Encoding::Utf8 {
+        return Ok(r);
+    }
+    Ok(Box::new(Transcoder {
+        inner: r,
+        encoding,
+        pending: Vec::new(),
+        out: Vec::new(),
+        pos: 0,
+    }))
+}
+
+/// Decodes `inner` a buffer at a time. A character split between two reads
+/// waits in `pending`; one left incomplete at the very end is dropped.
+struct Transcoder<R> {
+    inner: R,
+    encoding: Encoding,
+    pending: Vec<u8>,
+    out: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: BufRead> Transcoder<R> {
+    fn refill(&mut self) -> io::Result<()> {
+        self.out.clear();
+        self.pos = 0;
+        while self.out.is_empty() {
+            let chunk = self.inner.fill_buf()?;
+            if chunk.is_empty() {
+                return Ok(());
+            }
+            let n = chunk.len();
+            self.pending.extend_from_slice(chunk);
+            self.inner.consume(n);
+            let used = decode_into(self.encoding, &self.pending, &mut self.out);
+            self.pending.drain(..used);
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> io::Read for Transcoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = {
+            let data = self.fill_buf()?;
+            let n = data.len().min(buf.len());
+            buf[..n].copy_from_slice(&data[..n]);
+            n
+        };
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for Transcoder<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.out.len() {
+            self.refill()?;
+        }
+        Ok(&self.out[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.out.len());
+    }
+}
+
+/// Append `data` decoded to `out`; the bytes used, short of a trailing
+/// incomplete character.
+fn decode_into(encoding: Encoding, data: &[u8], out: &mut Vec<u8>) -> usize {
+    match encoding {
+        Encoding::Latin1 => {
+            data.iter().for_each(|&b| push(out, char::from(b)));
+            data.len()
+        }
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            let unit = |p: &[u8]| match encoding {
+                Encoding::Utf16Le => u16::from_le_bytes([p[0], p[1]]),
+                _ => u16::from_be_bytes([p[0], p[1]]),
+            };
+            let mut units: Vec<u16> = data.chunks_exact(2).map(unit).collect();
+            // A leading surrogate waits for the unit after it.
+            if units.last().is_some_and(|u| (0xD800..0xDC00).contains(u)) {
+                units.pop();
+            }
+            char::decode_utf16(units.iter().copied())
+                .for_each(|c| push(out, c.unwrap_or(char::REPLACEMENT_CHARACTER)));
+            units.len() * 2
+        }
+        _ => {
+            let mut rest = data;
+            loop {
+                match std::str::from_utf8(rest) {
+                    Ok(s) => {
+                        out.extend_from_slice(s.as_bytes());
+                        return data.len();
+                    }
+                    Err(e) => {
+                        let (valid, after) = rest.split_at(e.valid_up_to());
+                        out.extend_from_slice(valid);
+                        let Some(bad) = e.error_len() else {
+                            return data.len() - after.len();
+                        };
+                        push(out, char::REPLACEMENT_CHARACTER);
+                        rest = &after[bad..];
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn push(out: &mut Vec<u8>, c: char) {
+    out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    /// `bytes` decoded as `encoding`, handed over `chunk` bytes at a time.
+    fn decoded(bytes: &[u8], encoding: Encoding, chunk: usize) -> String {
+        let r = io::BufReader::with_capacity(chunk, io::Cursor::new(bytes.to_vec()));
+        let mut out = String::new();
+        decode(Box::new(r), encoding)
+            .unwrap()
+            .read_to_string(&mut out)
+            .unwrap();
+        out
+    }
+
+    fn utf16(text: &str, bom: bool, le: bool) -> Vec<u8> {
+        let bom = bom.then_some(0xFEFF);
+        bom.into_iter()
+            .chain(text.encode_utf16())
+            .flat_map(|u| if le { u.to_le_bytes() } else { u.to_be_bytes() })
+            .collect()
+    }
+
+    #[test]
+    fn detects_byte_order_marks_and_bare_utf16() {
+        let text = "GET /caf\u{e9} 200\n\u{1F600} ok\n";
+        for chunk in [4, 5, 4096] {
+            assert_eq!(
+                decoded(&utf16(text, true, true), Encoding::Auto, chunk),
+                text
+            );
+            assert_eq!(
+                decoded(&utf16(text, true, false), Encoding::Auto, chunk),
+                text
+            );
+            assert_eq!(
+                decoded(&utf16(text, false, true), Encoding::Auto, chunk),
+                text
+            );
+            assert_eq!(
+                decoded(&utf16(text, false, false), Encoding::Utf16Be, chunk),
+                text
+            );
+        }
+        let mut bom = UTF8_BOM.to_vec();
+        bom.extend_from_slice(text.as_bytes());
+        assert_eq!(decoded(&bom, Encoding::Auto, 3), text);
+        assert_eq!(decoded(text.as_bytes(), Encoding::Auto, 2), text);
+        assert_eq!(
+            Encoding::Auto.resolve(b"plain ascii\n"),
+            (Encoding::Utf8, 0)
+        );
+    }
+
+    #[test]
+    fn replaces_invalid_utf8_and_decodes_latin1() {
+        for chunk in [1, 2, 4096] {
+            assert_eq!(
+                decoded(b"a\xffb \xc3\xa9 \xe2\x82", Encoding::Utf8Lossy, chunk),
+                "a\u{FFFD}b \u{e9} "
+            );
+            assert_eq!(
+                decoded(b"caf\xe9 \xff\n", Encoding::Latin1, chunk),
+                "caf\u{e9} \u{ff}\n"
+            );
+        }
+        assert_eq!(decoded(b"\xe2\x82x", Encoding::Utf8Lossy, 1), "\u{FFFD}x");
+    }
+}
@@ -0,0 +1,174 @@
+//! `--geoip`: country, region, city and location from a GeoLite2/GeoIP2
+//! City (or Country) database, and the autonomous system from an ASN one.
+
+use std::{net::IpAddr, path::PathBuf};
+
+use anyhow::Result;
+use serde_json::{Map, Value};
+
+use super::{mmdb::Mmdb, parent_mut, Enricher, FieldTags};
+
+/// Adds `<field>_geo` next to each tagged IP field that any of the
+/// databases knows, e.g. `ip_geo: {country_code, country, region, city,
+/// lat, lon, asn, as_org}`. Absent when no database has the address.
+pub struct GeoIp {
+    dbs: Vec<Mmdb>,
+}
+
+impl GeoIp {
+    pub fn open(paths: &[PathBuf]) -> Result<Self> {
+        Ok(Self::new(
+            paths.iter().map(|p| Mmdb::open(p)).collect::<Result<_>>()?,
+        ))
+    }
+
+    pub fn new(dbs: Vec<Mmdb>) -> Self {
+        Self { dbs }
+    }
+
+    fn geo(&self, ip: IpAddr) -> Map<String, Value> {
+        let mut geo = Map::new();
+        for db in &self.dbs {
+            if let Some(rec) = db.lookup(ip) {
+                summarize(&rec, &mut geo);
+            }
+        }
+        geo
+    }
+}
+
+impl Enricher for GeoIp {
+    fn enrich(&self, rec: &mut Map<String, Value>, fields: &FieldTags) {
+        for field in &fields.ip {
+            let Some((parent, key)) = parent_mut(rec, field) else {
+                continue;
+            };
+            let Some(ip) = parent.get(key).and_then(Value::as_str).and_then(parse_ip) else {
+                continue;
+            };
+            let geo = self.geo(ip);
+            if !geo.is_empty() {
+                parent.insert(format!("{key}_geo"), Value::Object(geo));
+            }
+        }
+    }
+}
+
+/// An address as logged: bare, `[v6]`, or with a `:port`.
+pub(crate) fn parse_ip(s: &str) -> Option<IpAddr> {
+    let s = s.trim();
+    if let Ok(ip) = s.parse() {
+        return Some(ip);
+    }
+    let host = match s.strip_prefix('[') {
+        Some(rest) => rest.split_once(']')?.0,
+        None => s.rsplit_once(':')?.0,
+    };
+    host.parse().ok()
+}
+
+/// Pick the commonly wanted fields out of a City/Country/ASN record.
+fn summarize(rec: &Value, geo: &mut Map<String, Value>) {
+    let name = |v: &Value| v.pointer("/names/en").cloned();
+    let picks = [
+        ("country_code", rec.pointer("/country/iso_code").cloned()),
+        ("country", rec.get("country").and_then(name)),
+        ("region", rec.pointer("/subdivisions/0").and_then(name)),
+        ("city", rec.get("city").and_then(name)),
+        ("lat", rec.pointer("/location/latitude").cloned()),
+        ("lon", rec.pointer("/location/longitude").cloned()),
+        ("asn", rec.get("autonomous_system_number").cloned()),
+        ("as_org", rec.get("autonomous_system_organization").cloned()),
+    ];
+    for (key, v) in picks {
+        if let Some(v) = v {
+            geo.insert(key.to_string(), v);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enrich::mmdb::testdb;
+    use serde_json::json;
+
+    fn geoip() -> GeoIp {
+        let city = testdb::build(
+            4,
+            &[(
+                &[81, 2, 69, 0],
+                24,
+                json!({
+                    "city": {"names": {"en": "London", "de": "London"}},
+                    "country": {"iso_code": "GB", "names": {"en": "United Kingdom"}},
+                    "location": {"latitude": 51.5, "longitude": -0.125},
+                    "subdivisions": [{"iso_code": "ENG", "names": {"en": "England"}}],
+                }),
+            )],
+        );
+        let asn = testdb::build(
+            4,
+            &[(
+                &[81, 0, 0, 0],
+                8,
+                json!({
+                    "autonomous_system_number": 5089,
+                    "autonomous_system_organization": "Virgin Media Limited",
+                }),
+            )],
+        );
+        GeoIp::new(vec![
+            Mmdb::from_bytes(city).unwrap(),
+            Mmdb::from_bytes(asn).unwrap(),
+        ])
+    }
+
+    #[test]
+    fn adds_location_and_asn_next_to_ip_fields() {
+        let g = geoip();
+        let mut rec =
+            json!({"ip": "81.2.69.160", "parsed": {"src": "81.9.9.9:443"}, "dst": "10.0.0.1"})
+                .as_object()
+                .unwrap()
+                .clone();
+        let fields = FieldTags {
+            ip: vec![
+                "ip".into(),
+                "parsed.src".into(),
+                "dst".into(),
+                "missing".into(),
+            ],
+            ..FieldTags::default()
+        };
+        g.enrich(&mut rec, &fields);
+
+        assert_eq!(
+            rec["ip_geo"],
+            json!({
+                "country_code": "GB",
+                "country": "United Kingdom",
+                "region": "England",
+                "city": "London",
+                "lat": 51.5,
+                "lon": -0.125,
+                "asn": 5089,
+                "as_org": "Virgin Media Limited",
+            })
+        );
+        assert_eq!(
+            rec["parsed"]["src_geo"],
+            json!({"asn": 5089, "as_org": "Virgin Media Limited"})
+        );
+        assert!(rec.get("dst_geo").is_none());
+    }
+
+    #[test]
+    fn parses_logged_addresses() {
+        assert_eq!(parse_ip("1.2.3.4"), "1.2.3.4".parse().ok());
+        assert_eq!(parse_ip("1.2.3.4:8080"), "1.2.3.4".parse().ok());
+        assert_eq!(parse_ip("[2001:db8::1]:443"), "2001:db8::1".parse().ok());
+        assert_eq!(parse_ip("2001:db8::1"), "2001:db8::1".parse().ok());
+        assert_eq!(parse_ip("-"), None);
+    }
+}
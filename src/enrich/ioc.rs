@@ -0,0 +1,279 @@
+//! `--ioc`: threat-intel indicator matching. Indicator files hold one IP
+//! address, domain or file hash (MD5, SHA-1, SHA-256) per line, optionally
+//! followed by a label (`evil.example,phishing kit`). Defanged forms
+//! (`evil[.]example`, `hxxp://...`) are accepted; `#` lines are comments.
+
+use std::{collections::HashMap, net::IpAddr, path::PathBuf, sync::Arc};
+
+use anyhow::{bail, Context, Result};
+use serde_json::{Map, Value};
+
+use super::{geoip::parse_ip, Enricher, FieldTags};
+
+struct Indicator {
+    value: String,
+    kind: &'static str,
+    source: Arc<str>,
+    label: Option<String>,
+}
+
+/// Checks every string in a record against the loaded indicators: IP
+/// addresses exactly, domains and any of their subdomains (also as the host
+/// of a URL), and hashes case-insensitively. A record with hits gets
+/// `ioc_match: true` and `ioc: [{indicator, type, field, source, label}]`.
+#[derive(Default)]
+pub struct Ioc {
+    ips: HashMap<IpAddr, Indicator>,
+    domains: HashMap<String, Indicator>,
+    hashes: HashMap<String, Indicator>,
+}
+
+impl Ioc {
+    /// Load indicator files; each file name becomes its indicators' source.
+    pub fn open(paths: &[PathBuf]) -> Result<Self> {
+        let mut ioc = Self::default();
+        for path in paths {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("read {}", path.display()))?;
+            let source = path.file_name().map_or_else(
+                || path.display().to_string(),
+                |n| n.to_string_lossy().into(),
+            );
+            ioc.load(&source, &text)
+                .with_context(|| format!("load indicators from {}", path.display()))?;
+        }
+        Ok(ioc)
+    }
+
+    pub fn load(&mut self, source: &str, text: &str) -> Result<()> {
+        let source: Arc<str> = source.into();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (value, label) = match line.split_once([',', '\t', ' ']) {
+                Some((v, l)) => (v, Some(l.trim().to_string()).filter(|l| !l.is_empty())),
+                None => (line, None),
+            };
+            let value = refang(value);
+
+            let indicator = |kind| Indicator {
+                value: value.clone(),
+                kind,
+                source: Arc::clone(&source),
+                label: label.clone(),
+            };
+            if let Some(ip) = parse_ip(&value) {
+                self.ips.insert(ip, indicator("ip"));
+            } else if let Some(kind) = hash_kind(&value) {
+                self.hashes
+                    .insert(value.to_ascii_lowercase(), indicator(kind));
+            } else if let Some(host) = host_of(&value) {
+                self.domains.insert(host, indicator("domain"));
+            } else {
+                bail!("line {}: {value:?} is not an IP, domain or hash", n + 1);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.ips.len() + self.domains.len() + self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lookup(&self, s: &str) -> Option<&Indicator> {
+        if !self.ips.is_empty()
+            && let Some(ip) = parse_ip(s)
+        {
+            return self.ips.get(&ip);
+        }
+        if !self.hashes.is_empty() && hash_kind(s).is_some() {
+            return self.hashes.get(&s.to_ascii_lowercase());
+        }
+        if self.domains.is_empty() {
+            return None;
+        }
+        // `a.b.evil.example` matches `evil.example`.
+        let host = host_of(s)?;
+        let mut rest = host.as_str();
+        loop {
+            if let Some(hit) = self.domains.get(rest) {
+                return Some(hit);
+            }
+            rest = rest.split_once('.')?.1;
+        }
+    }
+
+    fn scan(&self, v: &Value, path: &mut String, hits: &mut Vec<Value>) {
+        match v {
+            Value::String(s) => {
+                if let Some(hit) = self.lookup(s) {
+                    hits.push(serde_json::json!({
+                        "indicator": hit.value,
+                        "type": hit.kind,
+                        "field": path.as_str(),
+                        "source": &*hit.source,
+                        "label": hit.label,
+                    }));
+                }
+            }
+            Value::Array(items) => items.iter().for_each(|i| self.scan(i, path, hits)),
+            Value::Object(map) => {
+                for (k, v) in map {
+                    let len = path.len();
+                    if !path.is_empty() {
+                        path.push('.');
+                    }
+                    path.push_str(k);
+                    self.scan(v, path, hits);
+                    path.truncate(len);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Enricher for Ioc {
+    fn enrich(&self, rec: &mut Map<String, Value>, _fields: &FieldTags) {
+        let mut hits = Vec::new();
+        let mut path = String::new();
+        for (k, v) in rec.iter() {
+            // The raw line holds the same values again.
+            if k == "raw" {
+                continue;
+            }
+            path.clear();
+            path.push_str(k);
+            self.scan(v, &mut path, &mut hits);
+        }
+        if !hits.is_empty() {
+            rec.insert("ioc_match".to_string(), true.into());
+            rec.insert("ioc".to_string(), hits.into());
+        }
+    }
+}
+
+/// Undo the usual defanging: `[.]`, `(.)`, `[:]`, `hxxp`.
+fn refang(s: &str) -> String {
+    let s = s
+        .replace("[.]", ".")
+        .replace("(.)", ".")
+        .replace("[:]", ":");
+    match s.strip_prefix("hxxp") {
+        Some(rest) => format!("http{rest}"),
+        None => s,
+    }
+}
+
+fn hash_kind(s: &str) -> Option<&'static str> {
+    if !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    match s.len() {
+        32 => Some("md5"),
+        40 => Some("sha1"),
+        64 => Some("sha256"),
+        _ => None,
+    }
+}
+
+/// The lowercased host name in `s`, a bare domain or a URL.
+fn host_of(s: &str) -> Option<String> {
+    let s = s.trim();
+    let rest = s.split_once("://").map_or(s, |(_, r)| r);
+    let rest = rest.rsplit_once('@').map_or(rest, |(_, r)| r);
+    let end = rest.find(['/', ':', '?', '#']).unwrap_or(rest.len());
+    let host = rest[..end].trim_end_matches('.');
+    let valid = host.contains('.')
+        && !host.starts_with('.')
+        && host
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_'))
+        && host.bytes().any(|b| b.is_ascii_alphabetic());
+    valid.then(|| host.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn ioc() -> Ioc {
+        let mut ioc = Ioc::default();
+        ioc.load(
+            "feed.txt",
+            "# sample feed\n\
+             203.0.113.7,c2 server\n\
+             evil[.]example\tphishing kit\n\
+             hxxps://bad.example/payload.exe\n\
+             44D88612FEA8A8F36DE82E1278ABB02F  eicar\n\
+             \n",
+        )
+        .unwrap();
+        ioc
+    }
+
+    #[test]
+    fn matches_ips_domains_and_hashes() {
+        let ioc = ioc();
+        assert_eq!(ioc.len(), 4);
+        assert_eq!(
+            ioc.lookup("203.0.113.7:4444").unwrap().label.as_deref(),
+            Some("c2 server")
+        );
+        assert!(ioc.lookup("203.0.113.8").is_none());
+        assert_eq!(
+            ioc.lookup("login.evil.example").unwrap().value,
+            "evil.example"
+        );
+        assert!(ioc.lookup("https://cdn.bad.example/x.js?y=1").is_some());
+        assert!(ioc.lookup("notevil.example").is_none());
+        assert_eq!(
+            ioc.lookup("44d88612fea8a8f36de82e1278abb02f").unwrap().kind,
+            "md5"
+        );
+        assert!(ioc.lookup("/index.html").is_none());
+    }
+
+    #[test]
+    fn tags_matching_records() {
+        let ioc = ioc();
+        let mut rec = json!({
+            "ip": "203.0.113.7",
+            "parsed": {"referer": "http://www.evil.example/"},
+            "raw": "203.0.113.7 ...",
+        })
+        .as_object()
+        .unwrap()
+        .clone();
+        ioc.enrich(&mut rec, &FieldTags::default());
+        assert_eq!(rec["ioc_match"], true);
+        assert_eq!(
+            rec["ioc"],
+            json!([
+                {"indicator": "203.0.113.7", "type": "ip", "field": "ip",
+                 "source": "feed.txt", "label": "c2 server"},
+                {"indicator": "evil.example", "type": "domain", "field": "parsed.referer",
+                 "source": "feed.txt", "label": "phishing kit"},
+            ])
+        );
+
+        let mut clean = json!({"ip": "10.0.0.1"}).as_object().unwrap().clone();
+        ioc.enrich(&mut clean, &FieldTags::default());
+        assert!(clean.get("ioc_match").is_none());
+    }
+
+    #[test]
+    fn rejects_unknown_indicators() {
+        let err = Ioc::default()
+            .load("x", "1.2.3.4\nnot an indicator\n")
+            .unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{err}");
+    }
+}
@@ -0,0 +1,169 @@
+//! `--lookup field=users.csv:key_column`: join a small CSV dictionary onto
+//! records, e.g. user names to departments or host IDs to asset owners.
+
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{bail, Context, Result};
+use serde_json::{Map, Value};
+
+use super::{parent_mut, Enricher, FieldTags};
+
+/// Adds the other columns of the CSV row whose key column equals the
+/// record's `field` next to it, as `<field>_<column>`. Empty cells become
+/// `null`; records whose value has no row are left alone.
+pub struct Lookup {
+    field: String,
+    /// Names of the joined columns, in file order.
+    columns: Vec<String>,
+    rows: HashMap<String, Vec<Value>>,
+}
+
+/// `field=path[:key_column]` split into its parts. The key column defaults
+/// to the first one.
+pub fn split_spec(spec: &str) -> Result<(&str, &str, Option<&str>)> {
+    let Some((field, rest)) = spec.split_once('=') else {
+        bail!("lookup {spec:?} is not field=file.csv:key_column");
+    };
+    let (path, key) = match rest.rsplit_once(':') {
+        // `C:\dicts\users.csv` has no key column.
+        Some((path, key)) if !key.contains(['/', '\\']) => (path, Some(key)),
+        _ => (rest, None),
+    };
+    if field.is_empty() || path.is_empty() || key == Some("") {
+        bail!("lookup {spec:?} is not field=file.csv:key_column");
+    }
+    Ok((field, path, key))
+}
+
+impl Lookup {
+    /// Load the dictionary named by a `field=path:key_column` spec.
+    pub fn open(spec: &str) -> Result<Self> {
+        let (field, path, key) = split_spec(spec)?;
+        let text =
+            std::fs::read_to_string(Path::new(path)).with_context(|| format!("read {path}"))?;
+        Self::load(field, &text, key).with_context(|| format!("load lookup table {path}"))
+    }
+
+    /// Build from CSV `text` with a header row, keyed on `key` (default: the
+    /// first column).
+    pub fn load(field: &str, text: &str, key: Option<&str>) -> Result<Self> {
+        let mut rdr = csv::ReaderBuilder::new()
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .from_reader(text.as_bytes());
+        let header: Vec<String> = rdr.headers()?.iter().map(String::from).collect();
+        let key_at = match key {
+            Some(k) => header
+                .iter()
+                .position(|h| h == k)
+                .with_context(|| format!("no column {k:?} (columns: {})", header.join(", ")))?,
+            None => 0,
+        };
+        if header.len() < 2 {
+            bail!("needs a key column and at least one column to join");
+        }
+
+        let columns = header
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| i != key_at)
+            .map(|(_, h)| h.clone())
+            .collect();
+        let mut rows = HashMap::new();
+        for (n, row) in rdr.records().enumerate() {
+            let row = row.with_context(|| format!("row {}", n + 2))?;
+            let Some(k) = row.get(key_at).filter(|k| !k.is_empty()) else {
+                continue;
+            };
+            let values = (0..header.len())
+                .filter(|&i| i != key_at)
+                .map(|i| match row.get(i) {
+                    Some(v) if !v.is_empty() => Value::from(v),
+                    _ => Value::Null,
+                })
+                .collect();
+            rows.insert(k.to_string(), values);
+        }
+        Ok(Self {
+            field: field.to_string(),
+            columns,
+            rows,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+}
+
+impl Enricher for Lookup {
+    fn enrich(&self, rec: &mut Map<String, Value>, _fields: &FieldTags) {
+        let Some((parent, key)) = parent_mut(rec, &self.field) else {
+            return;
+        };
+        let value = match parent.get(key) {
+            Some(Value::String(s)) => self.rows.get(s.as_str()),
+            Some(Value::Number(n)) => self.rows.get(&n.to_string()),
+            _ => None,
+        };
+        let Some(values) = value else {
+            return;
+        };
+        for (column, v) in self.columns.iter().zip(values) {
+            parent.insert(format!("{key}_{column}"), v.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const USERS: &str = "id,username,department,manager\n\
+                         1,alice,Finance,carol\n\
+                         2, bob ,IT,\n";
+
+    #[test]
+    fn joins_columns_on_the_key() {
+        let users = Lookup::load("parsed.user", USERS, Some("username")).unwrap();
+        assert_eq!(users.len(), 2);
+        let mut rec = json!({"parsed": {"user": "bob"}, "ip": "10.0.0.1"})
+            .as_object()
+            .unwrap()
+            .clone();
+        users.enrich(&mut rec, &FieldTags::default());
+        assert_eq!(
+            rec["parsed"],
+            json!({"user": "bob", "user_id": "2", "user_department": "IT", "user_manager": null})
+        );
+
+        let hosts = Lookup::load("uid", USERS, None).unwrap();
+        let mut rec = json!({"uid": 1}).as_object().unwrap().clone();
+        hosts.enrich(&mut rec, &FieldTags::default());
+        assert_eq!(rec["uid_username"], "alice");
+
+        let mut miss = json!({"uid": "3"}).as_object().unwrap().clone();
+        hosts.enrich(&mut miss, &FieldTags::default());
+        assert_eq!(miss.len(), 1);
+    }
+
+    #[test]
+    fn specs() {
+        assert_eq!(
+            split_spec("user=dicts/users.csv:username").unwrap(),
+            ("user", "dicts/users.csv", Some("username"))
+        );
+        assert_eq!(
+            split_spec(r"user=C:\dicts\users.csv").unwrap(),
+            ("user", r"C:\dicts\users.csv", None)
+        );
+        assert!(split_spec("users.csv:username").is_err());
+        assert!(split_spec("user=users.csv:").is_err());
+        assert!(Lookup::load("user", USERS, Some("email")).is_err());
+    }
+}
@@ -0,0 +1,418 @@
+//! Reader for MaxMind DB files (`.mmdb`: GeoLite2/GeoIP2 City, Country,
+//! ASN, ...), after the published format spec: a binary search tree over
+//! address bits whose leaves point into a typed data section, and a
+//! metadata map at the end of the file.
+
+use std::{net::IpAddr, path::Path};
+
+use anyhow::{bail, Context, Result};
+use memchr::memmem;
+use serde_json::{Map, Value};
+
+const METADATA_MARKER: &[u8] = b"\xAB\xCD\xEFMaxMind.com";
+
+/// Nesting deeper than this is a corrupt (or hostile) file.
+const MAX_DEPTH: usize = 32;
+
+pub struct Mmdb {
+    buf: Vec<u8>,
+    node_count: u32,
+    record_size: usize,
+    ip_version: u64,
+    /// Byte length of the search tree; the data section starts 16 bytes later.
+    tree_size: usize,
+    /// Node reached after the 96 zero bits IPv4 addresses live under in an
+    /// IPv6 tree.
+    ipv4_start: u32,
+    pub database_type: String,
+}
+
+impl Mmdb {
+    pub fn open(path: &Path) -> Result<Self> {
+        let buf = std::fs::read(path).with_context(|| format!("read {}", path.display()))?;
+        Self::from_bytes(buf).with_context(|| format!("{} is not a MaxMind DB", path.display()))
+    }
+
+    pub fn from_bytes(buf: Vec<u8>) -> Result<Self> {
+        let start = memmem::rfind(&buf, METADATA_MARKER).context("no metadata marker")?
+            + METADATA_MARKER.len();
+        let meta = match (Decoder {
+            buf: &buf,
+            base: start,
+        })
+        .value(start, 0)
+        {
+            Some((Value::Object(meta), _)) => meta,
+            _ => bail!("unreadable metadata"),
+        };
+        let uint = |key: &str| {
+            meta.get(key)
+                .and_then(Value::as_u64)
+                .with_context(|| format!("metadata has no {key}"))
+        };
+
+        let node_count = u32::try_from(uint("node_count")?).context("node_count out of range")?;
+        let record_size = uint("record_size")? as usize;
+        if ![24, 28, 32].contains(&record_size) {
+            bail!("unsupported record size {record_size}");
+        }
+        let ip_version = uint("ip_version")?;
+        let tree_size = node_count as usize * record_size / 4;
+        if tree_size + 16 > start {
+            bail!("search tree runs past the data section");
+        }
+        let database_type = meta
+            .get("database_type")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+
+        let mut db = Self {
+            buf,
+            node_count,
+            record_size,
+            ip_version,
+            tree_size,
+            ipv4_start: 0,
+            database_type,
+        };
+        if ip_version == 6 {
+            let mut node = 0;
+            for _ in 0..96 {
+                if node >= node_count {
+                    break;
+                }
+                node = db.record(node, 0).context("truncated search tree")?;
+            }
+            db.ipv4_start = node;
+        }
+        Ok(db)
+    }
+
+    /// The data record for the network holding `ip`, if any.
+    pub fn lookup(&self, ip: IpAddr) -> Option<Value> {
+        let (bytes, start) = match ip {
+            IpAddr::V4(v4) if self.ip_version == 6 => (v4.octets().to_vec(), self.ipv4_start),
+            IpAddr::V4(v4) => (v4.octets().to_vec(), 0),
+            IpAddr::V6(v6) if self.ip_version == 6 => (v6.octets().to_vec(), 0),
+            IpAddr::V6(v6) => (v6.to_ipv4_mapped()?.octets().to_vec(), 0),
+        };
+
+        let mut node = start;
+        for i in 0..bytes.len() * 8 {
+            if node >= self.node_count {
+                break;
+            }
+            let bit = (bytes[i / 8] >> (7 - i % 8)) & 1;
+            node = self.record(node, bit)?;
+        }
+        if node <= self.node_count {
+            return None;
+        }
+
+        let data = self.tree_size + 16;
+        let at = self.tree_size + (node - self.node_count) as usize;
+        let decoder = Decoder {
+            buf: &self.buf,
+            base: data,
+        };
+        decoder.value(at, 0).map(|(v, _)| v)
+    }
+
+    /// Left (`bit` 0) or right record of `node`.
+    fn record(&self, node: u32, bit: u8) -> Option<u32> {
+        let width = self.record_size / 4;
+        let at = node as usize * width;
+        let b = self.buf.get(at..at + width)?;
+        let be = |b: &[u8]| b.iter().fold(0u32, |acc, &x| acc << 8 | x as u32);
+        Some(match (self.record_size, bit) {
+            (24, 0) => be(&b[..3]),
+            (24, _) => be(&b[3..]),
+            (28, 0) => (b[3] as u32 & 0xF0) << 20 | be(&b[..3]),
+            (28, _) => (b[3] as u32 & 0x0F) << 24 | be(&b[4..]),
+            (_, 0) => be(&b[..4]),
+            _ => be(&b[4..]),
+        })
+    }
+}
+
+/// Data-section decoder; pointers are relative to `base`.
+struct Decoder<'a> {
+    buf: &'a [u8],
+    base: usize,
+}
+
+impl Decoder<'_> {
+    fn bytes(&self, at: usize, n: usize) -> Option<&[u8]> {
+        self.buf.get(at..at.checked_add(n)?)
+    }
+
+    fn uint(&self, at: usize, n: usize) -> Option<u128> {
+        Some(
+            self.bytes(at, n)?
+                .iter()
+                .fold(0u128, |acc, &b| acc << 8 | b as u128),
+        )
+    }
+
+    /// The value at `at`, and the offset just past it.
+    fn value(&self, at: usize, depth: usize) -> Option<(Value, usize)> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        let ctrl = *self.buf.get(at)?;
+        let mut p = at + 1;
+        let mut kind = ctrl >> 5;
+
+        if kind == 1 {
+            let high = (ctrl & 0x07) as usize;
+            let (ptr, n) = match (ctrl >> 3) & 0x03 {
+                0 => ((high << 8 | self.uint(p, 1)? as usize), 1),
+                1 => ((high << 16 | self.uint(p, 2)? as usize) + 2048, 2),
+                2 => ((high << 24 | self.uint(p, 3)? as usize) + 526_336, 3),
+                _ => (self.uint(p, 4)? as usize, 4),
+            };
+            let (v, _) = self.value(self.base.checked_add(ptr)?, depth + 1)?;
+            return Some((v, p + n));
+        }
+        if kind == 0 {
+            kind = 7 + *self.buf.get(p)?;
+            p += 1;
+        }
+
+        let mut size = (ctrl & 0x1F) as usize;
+        match size {
+            29 => {
+                size = 29 + self.uint(p, 1)? as usize;
+                p += 1;
+            }
+            30 => {
+                size = 285 + self.uint(p, 2)? as usize;
+                p += 2;
+            }
+            31 => {
+                size = 65_821 + self.uint(p, 3)? as usize;
+                p += 3;
+            }
+            _ => {}
+        }
+
+        let value = match kind {
+            2 => Value::String(std::str::from_utf8(self.bytes(p, size)?).ok()?.to_string()),
+            3 => f64::from_be_bytes(self.bytes(p, size)?.try_into().ok()?).into(),
+            4 => self
+                .bytes(p, size)?
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<String>()
+                .into(),
+            5 | 6 | 9 => (self.uint(p, size.min(8))? as u64).into(),
+            10 => match self.uint(p, size.min(16))? {
+                n if n <= u64::MAX as u128 => (n as u64).into(),
+                n => n.to_string().into(),
+            },
+            8 => (self.uint(p, size.min(4))? as u32 as i32).into(),
+            15 => (f32::from_be_bytes(self.bytes(p, size)?.try_into().ok()?) as f64).into(),
+            14 => return Some((Value::Bool(size != 0), p)),
+            7 => {
+                let mut map = Map::new();
+                for _ in 0..size {
+                    let (key, after) = self.value(p, depth + 1)?;
+                    let (v, after) = self.value(after, depth + 1)?;
+                    map.insert(key.as_str()?.to_string(), v);
+                    p = after;
+                }
+                return Some((Value::Object(map), p));
+            }
+            11 => {
+                let mut items = Vec::with_capacity(size.min(1024));
+                for _ in 0..size {
+                    let (v, after) = self.value(p, depth + 1)?;
+                    items.push(v);
+                    p = after;
+                }
+                return Some((Value::Array(items), p));
+            }
+            _ => return None,
+        };
+        Some((value, p + size))
+    }
+}
+
+/// Builds small databases for tests (24-bit records, IPv4 or IPv6 tree).
+#[cfg(test)]
+pub(crate) mod testdb {
+    use serde_json::Value;
+
+    fn ctrl(out: &mut Vec<u8>, kind: u8, size: usize) {
+        assert!(size < 285);
+        let low = size.min(29) as u8;
+        if kind <= 7 {
+            out.push(kind << 5 | low);
+        } else {
+            out.push(low);
+            out.push(kind - 7);
+        }
+        if size >= 29 {
+            out.push((size - 29) as u8);
+        }
+    }
+
+    pub fn encode(v: &Value, out: &mut Vec<u8>) {
+        match v {
+            Value::String(s) => {
+                ctrl(out, 2, s.len());
+                out.extend_from_slice(s.as_bytes());
+            }
+            Value::Number(n) if n.is_f64() => {
+                ctrl(out, 3, 8);
+                out.extend_from_slice(&n.as_f64().unwrap().to_be_bytes());
+            }
+            Value::Number(n) => {
+                let b = (n.as_u64().unwrap() as u32).to_be_bytes();
+                ctrl(out, 6, 4);
+                out.extend_from_slice(&b);
+            }
+            Value::Bool(b) => ctrl(out, 14, *b as usize),
+            Value::Object(map) => {
+                ctrl(out, 7, map.len());
+                for (k, v) in map {
+                    encode(&Value::String(k.clone()), out);
+                    encode(v, out);
+                }
+            }
+            Value::Array(items) => {
+                ctrl(out, 11, items.len());
+                for v in items {
+                    encode(v, out);
+                }
+            }
+            Value::Null => unreachable!(),
+        }
+    }
+
+    enum Rec {
+        Empty,
+        Node(usize),
+        Data(usize),
+    }
+
+    /// `networks`: (address bytes, prefix length, record).
+    pub fn build(ip_version: u64, networks: &[(&[u8], usize, Value)]) -> Vec<u8> {
+        let mut nodes: Vec<[Rec; 2]> = vec![[Rec::Empty, Rec::Empty]];
+        for (k, (addr, len, _)) in networks.iter().enumerate() {
+            let mut node = 0;
+            for i in 0..*len {
+                let bit = ((addr[i / 8] >> (7 - i % 8)) & 1) as usize;
+                if i + 1 == *len {
+                    nodes[node][bit] = Rec::Data(k);
+                    break;
+                }
+                node = match nodes[node][bit] {
+                    Rec::Node(n) => n,
+                    _ => {
+                        nodes.push([Rec::Empty, Rec::Empty]);
+                        nodes[node][bit] = Rec::Node(nodes.len() - 1);
+                        nodes.len() - 1
+                    }
+                };
+            }
+        }
+
+        let mut data = Vec::new();
+        let mut offsets = Vec::new();
+        for (_, _, v) in networks {
+            offsets.push(data.len());
+            encode(v, &mut data);
+        }
+
+        let count = nodes.len();
+        let mut out = Vec::new();
+        for node in &nodes {
+            for rec in node {
+                let v = match rec {
+                    Rec::Empty => count,
+                    Rec::Node(n) => *n,
+                    Rec::Data(k) => count + 16 + offsets[*k],
+                };
+                out.extend_from_slice(&(v as u32).to_be_bytes()[1..]);
+            }
+        }
+        out.extend_from_slice(&[0; 16]);
+        out.extend_from_slice(&data);
+        out.extend_from_slice(super::METADATA_MARKER);
+        let meta = serde_json::json!({
+            "node_count": count,
+            "record_size": 24,
+            "ip_version": ip_version,
+            "database_type": "Test",
+        });
+        encode(&meta, &mut out);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn looks_up_networks_in_both_tree_kinds() {
+        let v4 = testdb::build(
+            4,
+            &[
+                (&[10, 0, 0, 0], 8, json!({"name": "ten"})),
+                (
+                    &[192, 0, 2, 0],
+                    24,
+                    json!({"n": 7, "f": 1.5, "ok": true, "a": ["x"]}),
+                ),
+            ],
+        );
+        let db = Mmdb::from_bytes(v4).unwrap();
+        assert_eq!(db.database_type, "Test");
+        assert_eq!(
+            db.lookup("10.9.8.7".parse().unwrap()),
+            Some(json!({"name": "ten"}))
+        );
+        assert_eq!(
+            db.lookup("192.0.2.200".parse().unwrap()),
+            Some(json!({"n": 7, "f": 1.5, "ok": true, "a": ["x"]}))
+        );
+        assert_eq!(db.lookup("192.0.3.1".parse().unwrap()), None);
+        assert_eq!(
+            db.lookup("::ffff:10.0.0.1".parse().unwrap()),
+            Some(json!({"name": "ten"}))
+        );
+        assert_eq!(db.lookup("2001:db8::1".parse().unwrap()), None);
+
+        // IPv4 networks sit under ::/96 in an IPv6 tree.
+        let mut v4_in_v6 = [0u8; 16];
+        v4_in_v6[12] = 10;
+        let v6 = testdb::build(
+            6,
+            &[
+                (&v4_in_v6, 104, json!("ten")),
+                (&[0x20, 0x01, 0x0d, 0xb8], 32, json!("doc")),
+            ],
+        );
+        let db = Mmdb::from_bytes(v6).unwrap();
+        assert_eq!(db.lookup("10.1.2.3".parse().unwrap()), Some(json!("ten")));
+        assert_eq!(
+            db.lookup("2001:db8::1".parse().unwrap()),
+            Some(json!("doc"))
+        );
+        assert_eq!(db.lookup("2001:db9::1".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(Mmdb::from_bytes(b"not a database".to_vec()).is_err());
+        let mut db = testdb::build(4, &[(&[10, 0, 0, 0], 8, json!("x"))]);
+        // Claim more nodes than the file holds.
+        let at = memmem::rfind(&db, b"node_count").unwrap() + 10;
+        db[at + 1..at + 5].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(Mmdb::from_bytes(db).is_err());
+    }
+}
@@ -0,0 +1,327 @@
+//! Post-parse enrichment: stages that add fields to every record a module
+//! emits (GeoIP, user agents, threat intel, network ranges, lookup tables,
+//! ...). `Enriched` wraps any parser, so every module gets them without
+//! knowing about them.
+
+use std::{borrow::Cow, sync::Arc};
+
+use anyhow::Result;
+use serde_json::{Map, Value};
+
+use crate::{
+    core::{
+        FieldType, InputFormat, LineJoiner, ModuleOptions, OptionSpec, Parser, UNPARSED_PREFIX,
+    },
+    filter::Filter,
+    projection::Projection,
+    transform::Transform,
+};
+
+pub mod geoip;
+pub mod ioc;
+pub mod lookup;
+pub mod mmdb;
+pub mod networks;
+pub mod sigma;
+pub mod useragent;
+
+pub use geoip::GeoIp;
+pub use ioc::Ioc;
+pub use lookup::Lookup;
+pub use networks::Networks;
+pub use sigma::Sigma;
+pub use useragent::UserAgent;
+
+/// One enrichment stage.
+pub trait Enricher: Send + Sync {
+    /// Add fields to one parsed record.
+    fn enrich(&self, rec: &mut Map<String, Value>, fields: &FieldTags);
+}
+
+/// Which record fields hold what, so stages know where to look. Dotted
+/// names reach into nested objects (`parsed.ip`).
+#[derive(Clone, Debug, Default)]
+pub struct FieldTags {
+    /// Fields holding an IP address.
+    pub ip: Vec<String>,
+    /// Fields holding an HTTP `User-Agent`.
+    pub user_agent: Vec<String>,
+}
+
+impl FieldTags {
+    /// The fields `parser` tags itself.
+    pub fn of(parser: &dyn Parser) -> Self {
+        Self {
+            ip: parser.ip_fields(),
+            user_agent: parser.user_agent_fields(),
+        }
+    }
+
+    /// Add `other`'s fields, skipping ones already tagged.
+    pub fn merge(&mut self, other: &FieldTags) {
+        for (ours, theirs) in [
+            (&mut self.ip, &other.ip),
+            (&mut self.user_agent, &other.user_agent),
+        ] {
+            for f in theirs {
+                if !ours.contains(f) {
+                    ours.push(f.clone());
+                }
+            }
+        }
+    }
+}
+
+/// The object holding `path` and the key within it. A literal key wins
+/// over a nested path, so flattened `a.b` keys are found too.
+pub(crate) fn parent_mut<'r, 'p>(
+    rec: &'r mut Map<String, Value>,
+    path: &'p str,
+) -> Option<(&'r mut Map<String, Value>, &'p str)> {
+    if rec.contains_key(path) {
+        return Some((rec, path));
+    }
+    let (head, rest) = path.split_once('.')?;
+    match rec.get_mut(head)? {
+        Value::Object(inner) => parent_mut(inner, rest),
+        _ => None,
+    }
+}
+
+/// Any module, with `stages` run over each record it emits, then optionally
+/// a `--transform`, a `--where` filter and a projection. Unparsed fallback
+/// records skip all but the filter.
+pub struct Enriched {
+    inner: Box<dyn Parser>,
+    stages: Arc<[Box<dyn Enricher>]>,
+    fields: Arc<FieldTags>,
+    transform: Option<Arc<Transform>>,
+    filter: Option<Arc<Filter>>,
+    projection: Option<Arc<Projection>>,
+}
+
+impl Enriched {
+    /// `extra` adds to the fields the module itself tags.
+    pub fn new(inner: Box<dyn Parser>, stages: Vec<Box<dyn Enricher>>, extra: &FieldTags) -> Self {
+        let mut fields = FieldTags::of(&*inner);
+        fields.merge(extra);
+        Self {
+            inner,
+            stages: stages.into(),
+            fields: Arc::new(fields),
+            transform: None,
+            filter: None,
+            projection: None,
+        }
+    }
+
+    /// Reshape records with `transform` once the stages ran.
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.transform = Some(Arc::new(transform));
+        self
+    }
+
+    /// Drop records not matching `filter`, tested after the stages and
+    /// transform ran.
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// Trim kept records to `projection` before they are written.
+    pub fn with_projection(mut self, projection: Projection) -> Self {
+        self.projection = Some(Arc::new(projection));
+        self
+    }
+}
+
+impl Parser for Enriched {
+    fn name(&self) -> Cow<'static, str> {
+        self.inner.name()
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        self.inner.description()
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        self.inner.options()
+    }
+
+    fn configure(&mut self, opts: &ModuleOptions) -> Result<()> {
+        self.inner.configure(opts)
+    }
+
+    fn for_input(&self, head: &[&str]) -> Result<Option<Box<dyn Parser>>> {
+        Ok(self.inner.for_input(head)?.map(|inner| {
+            Box::new(Enriched {
+                inner,
+                stages: Arc::clone(&self.stages),
+                fields: Arc::clone(&self.fields),
+                transform: self.transform.clone(),
+                filter: self.filter.clone(),
+                projection: self.projection.clone(),
+            }) as Box<dyn Parser>
+        }))
+    }
+
+    fn line_joiner(&self) -> Option<Box<dyn LineJoiner>> {
+        self.inner.line_joiner()
+    }
+
+    fn input_format(&self) -> InputFormat {
+        self.inner.input_format()
+    }
+
+    fn split_document(&self, doc: &[u8], out: &mut Vec<u8>) -> Result<()> {
+        self.inner.split_document(doc, out)
+    }
+
+    fn message_field(&self) -> Option<&'static str> {
+        self.inner.message_field()
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        self.fields.ip.clone()
+    }
+
+    fn user_agent_fields(&self) -> Vec<String> {
+        self.fields.user_agent.clone()
+    }
+
+    fn fields(&self) -> Vec<(String, FieldType)> {
+        // A transform may rename or retype any field.
+        if self.transform.is_some() {
+            return Vec::new();
+        }
+        let fields = self.inner.fields();
+        match &self.projection {
+            Some(projection) => projection.apply_to_fields(fields),
+            None => fields,
+        }
+    }
+
+    fn recognizes(&self, line: &str) -> bool {
+        self.inner.recognizes(line)
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        self.process_bytes_to_buf(line.as_bytes(), out)
+    }
+
+    fn process_bytes_to_buf(&self, line: &[u8], out: &mut Vec<u8>) -> bool {
+        let mut rec = Vec::new();
+        if !self.inner.process_bytes_to_buf(line, &mut rec) {
+            return false;
+        }
+        let unparsed = rec.starts_with(UNPARSED_PREFIX);
+        let parsed = match unparsed && self.filter.is_none() {
+            true => None,
+            false => serde_json::from_slice(&rec).ok(),
+        };
+        let Some(Value::Object(mut obj)) = parsed else {
+            out.extend_from_slice(&rec);
+            return true;
+        };
+
+        if !unparsed {
+            for stage in self.stages.iter() {
+                stage.enrich(&mut obj, &self.fields);
+            }
+            if let Some(transform) = &self.transform {
+                transform.apply(&mut obj);
+            }
+        }
+        if let Some(filter) = &self.filter
+            && !filter.matches(&obj)
+        {
+            // Parsed fine, just not wanted: no record, but not a reject.
+            return true;
+        }
+        if let Some(projection) = &self.projection
+            && !unparsed
+        {
+            projection.apply(&mut obj);
+        }
+        if serde_json::to_writer(&mut *out, &obj).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Mark;
+
+    impl Enricher for Mark {
+        fn enrich(&self, rec: &mut Map<String, Value>, fields: &FieldTags) {
+            for f in &fields.ip {
+                if let Some((parent, key)) = parent_mut(rec, f) {
+                    parent.insert(format!("{key}_seen"), true.into());
+                }
+            }
+        }
+    }
+
+    fn run(p: &dyn Parser, line: &str) -> String {
+        let mut out = Vec::new();
+        assert!(p.process_line_to_buf(line, &mut out));
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn stages_see_tagged_fields_and_skip_fallbacks() {
+        let web = crate::modules::web_access::new();
+        let extra = FieldTags {
+            ip: vec!["user".into(), "ip".into()],
+            ..FieldTags::default()
+        };
+        let p = Enriched::new(web, vec![Box::new(Mark)], &extra);
+        assert_eq!(p.ip_fields(), ["ip", "user"]);
+        assert_eq!(p.user_agent_fields(), ["user_agent"]);
+
+        let line = r#"1.2.3.4 - bob [10/Oct/2000:13:55:36 -0700] "GET / HTTP/1.1" 200 1"#;
+        let v: Value = serde_json::from_str(&run(&p, line)).unwrap();
+        assert_eq!(v["ip_seen"], true);
+        assert_eq!(v["user_seen"], true);
+
+        assert!(run(&p, "garbage").starts_with(r#"{"unparsed":true"#));
+    }
+
+    #[test]
+    fn filter_runs_after_the_stages() {
+        let extra = FieldTags {
+            ip: vec!["ip".into()],
+            ..FieldTags::default()
+        };
+        let filter = Filter::parse("ip_seen && status >= 500").unwrap();
+        let p = Enriched::new(
+            crate::modules::web_access::new(),
+            vec![Box::new(Mark)],
+            &extra,
+        )
+        .with_filter(filter);
+        let line = |status| {
+            format!(r#"1.2.3.4 - - [10/Oct/2000:13:55:36 -0700] "GET / HTTP/1.1" {status} 1"#)
+        };
+        assert!(run(&p, &line(503)).contains(r#""status":503"#));
+        assert_eq!(run(&p, &line(200)), "");
+        assert_eq!(run(&p, "garbage"), "");
+    }
+
+    #[test]
+    fn parent_mut_follows_nested_and_flattened_keys() {
+        let mut rec = serde_json::json!({"a.b": 1, "parsed": {"ip": "x"}})
+            .as_object()
+            .unwrap()
+            .clone();
+        assert_eq!(parent_mut(&mut rec, "a.b").unwrap().1, "a.b");
+        let (parent, key) = parent_mut(&mut rec, "parsed.ip").unwrap();
+        assert_eq!((parent["ip"].as_str(), key), (Some("x"), "ip"));
+        assert!(parent_mut(&mut rec, "parsed.nope").is_none());
+    }
+}
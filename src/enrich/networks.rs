@@ -0,0 +1,232 @@
+//! `--networks`: label IP fields with the named ranges holding them
+//! (`10.0.0.0/8=internal`, VPN pools, DMZ, ...).
+
+use std::{net::IpAddr, path::PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde_json::{Map, Value};
+
+use super::{geoip::parse_ip, parent_mut, Enricher, FieldTags};
+
+/// Binary trie node: children by the next address bit, and the labels of
+/// the ranges ending here.
+#[derive(Default)]
+struct Node {
+    children: [Option<u32>; 2],
+    labels: Vec<String>,
+}
+
+/// One trie per address family.
+struct Trie {
+    nodes: Vec<Node>,
+}
+
+impl Trie {
+    fn new() -> Self {
+        Self {
+            nodes: vec![Node::default()],
+        }
+    }
+
+    fn insert(&mut self, addr: &[u8], len: usize, label: &str) {
+        let mut node = 0;
+        for i in 0..len {
+            let bit = ((addr[i / 8] >> (7 - i % 8)) & 1) as usize;
+            node = match self.nodes[node].children[bit] {
+                Some(next) => next as usize,
+                None => {
+                    self.nodes.push(Node::default());
+                    let next = self.nodes.len() - 1;
+                    self.nodes[node].children[bit] = Some(next as u32);
+                    next
+                }
+            };
+        }
+        let labels = &mut self.nodes[node].labels;
+        if !labels.iter().any(|l| l == label) {
+            labels.push(label.to_string());
+        }
+    }
+
+    /// Labels of every range holding `addr`, most specific first.
+    fn lookup(&self, addr: &[u8]) -> Vec<&str> {
+        let mut found = Vec::new();
+        let mut node = 0;
+        for i in 0..=addr.len() * 8 {
+            found.extend(self.nodes[node].labels.iter().rev().map(String::as_str));
+            if i == addr.len() * 8 {
+                break;
+            }
+            let bit = ((addr[i / 8] >> (7 - i % 8)) & 1) as usize;
+            match self.nodes[node].children[bit] {
+                Some(next) => node = next as usize,
+                None => break,
+            }
+        }
+        found.reverse();
+        found
+    }
+}
+
+/// Adds `<field>_net: [label, ...]` next to each tagged IP field that falls
+/// in a listed range, most specific range first. IPv4-mapped IPv6
+/// addresses match IPv4 ranges.
+pub struct Networks {
+    v4: Trie,
+    v6: Trie,
+}
+
+impl Default for Networks {
+    fn default() -> Self {
+        Self {
+            v4: Trie::new(),
+            v6: Trie::new(),
+        }
+    }
+}
+
+impl Networks {
+    /// Load range files: one `CIDR=label` per line (`,`, tab or space also
+    /// separate the label); a bare address is a single host. `#` lines are
+    /// comments.
+    pub fn open(paths: &[PathBuf]) -> Result<Self> {
+        let mut nets = Self::default();
+        for path in paths {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("read {}", path.display()))?;
+            nets.load(&text)
+                .with_context(|| format!("load networks from {}", path.display()))?;
+        }
+        Ok(nets)
+    }
+
+    pub fn load(&mut self, text: &str) -> Result<()> {
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((cidr, label)) = line.split_once(['=', ',', '\t', ' ']) else {
+                bail!(
+                    "line {}: {line:?} has no label (expected CIDR=label)",
+                    n + 1
+                );
+            };
+            let label = label.trim();
+            if label.is_empty() {
+                bail!("line {}: {line:?} has an empty label", n + 1);
+            }
+            self.insert(cidr.trim(), label)
+                .with_context(|| format!("line {}", n + 1))?;
+        }
+        Ok(())
+    }
+
+    pub fn insert(&mut self, cidr: &str, label: &str) -> Result<()> {
+        let (addr, len) = match cidr.split_once('/') {
+            Some((a, l)) => (a, Some(l)),
+            None => (cidr, None),
+        };
+        let addr: IpAddr = addr
+            .parse()
+            .with_context(|| format!("{cidr:?} is not an address or CIDR range"))?;
+        let (trie, bytes) = match addr {
+            IpAddr::V4(v4) => (&mut self.v4, v4.octets().to_vec()),
+            IpAddr::V6(v6) => (&mut self.v6, v6.octets().to_vec()),
+        };
+        let max = bytes.len() * 8;
+        let len = match len {
+            Some(l) => l
+                .parse::<usize>()
+                .ok()
+                .filter(|&l| l <= max)
+                .with_context(|| format!("{cidr:?} has a bad prefix length"))?,
+            None => max,
+        };
+        trie.insert(&bytes, len, label);
+        Ok(())
+    }
+
+    pub fn lookup(&self, ip: IpAddr) -> Vec<&str> {
+        match ip {
+            IpAddr::V4(v4) => self.v4.lookup(&v4.octets()),
+            IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+                Some(v4) => self.v4.lookup(&v4.octets()),
+                None => self.v6.lookup(&v6.octets()),
+            },
+        }
+    }
+}
+
+impl Enricher for Networks {
+    fn enrich(&self, rec: &mut Map<String, Value>, fields: &FieldTags) {
+        for field in &fields.ip {
+            let Some((parent, key)) = parent_mut(rec, field) else {
+                continue;
+            };
+            let Some(ip) = parent.get(key).and_then(Value::as_str).and_then(parse_ip) else {
+                continue;
+            };
+            let labels = self.lookup(ip);
+            if !labels.is_empty() {
+                parent.insert(format!("{key}_net"), labels.into());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn nets() -> Networks {
+        let mut nets = Networks::default();
+        nets.load(
+            "# site ranges\n\
+             10.0.0.0/8=internal\n\
+             10.8.0.0/16=vpn\n\
+             192.168.0.0/16 internal\n\
+             203.0.113.10,dmz web\n\
+             fd00::/8=internal\n\
+             0.0.0.0/0=any\n",
+        )
+        .unwrap();
+        nets
+    }
+
+    #[test]
+    fn most_specific_range_first() {
+        let nets = nets();
+        let ip = |s: &str| s.parse().unwrap();
+        assert_eq!(nets.lookup(ip("10.8.1.2")), ["vpn", "internal", "any"]);
+        assert_eq!(nets.lookup(ip("10.9.1.2")), ["internal", "any"]);
+        assert_eq!(nets.lookup(ip("203.0.113.10")), ["dmz web", "any"]);
+        assert_eq!(nets.lookup(ip("8.8.8.8")), ["any"]);
+        assert_eq!(nets.lookup(ip("::ffff:192.168.1.1")), ["internal", "any"]);
+        assert_eq!(nets.lookup(ip("fd12::1")), ["internal"]);
+        assert!(nets.lookup(ip("2001:db8::1")).is_empty());
+    }
+
+    #[test]
+    fn tags_ip_fields() {
+        let mut rec = json!({"ip": "10.8.0.5:51000", "dst": "2001:db8::1"})
+            .as_object()
+            .unwrap()
+            .clone();
+        let fields = FieldTags {
+            ip: vec!["ip".into(), "dst".into()],
+            ..FieldTags::default()
+        };
+        nets().enrich(&mut rec, &fields);
+        assert_eq!(rec["ip_net"], json!(["vpn", "internal", "any"]));
+        assert!(rec.get("dst_net").is_none());
+    }
+
+    #[test]
+    fn rejects_bad_lines() {
+        for bad in ["10.0.0.0/8", "10.0.0.0/33=x", "host=x", "10.0.0.0/8= "] {
+            assert!(Networks::default().load(bad).is_err(), "{bad}");
+        }
+    }
+}
@@ -0,0 +1,1044 @@
+//! `--sigma`: Sigma detection rules (YAML) run against every record. Rule
+//! field names are resolved to the module's own fields when the rules are
+//! loaded: through the module's ECS mapping for web logs, and into
+//! `event_data` for Windows events. Each rule's `logsource` picks the
+//! modules it applies to; its category or service adds the implied event
+//! IDs or channel.
+//!
+//! Supported: field maps and lists of them, keyword lists, the `contains`,
+//! `startswith`, `endswith`, `all`, `re` (with `i`, `m`, `s`), `cidr`,
+//! `exists`, `windash`, `lt`/`lte`/`gt`/`gte` and `cased` modifiers, and
+//! conditions with `and`, `or`, `not`, parentheses, `1 of`/`all of`
+//! (patterns or `them`). Rules with aggregations (`| count()`), other
+//! modifiers or several YAML documents are skipped.
+
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde_json::{Map, Value};
+
+use super::{geoip::parse_ip, Enricher, FieldTags, Networks};
+use crate::{
+    expr::field,
+    schema::{ecs, split_chain},
+};
+
+/// A category or service a rule may name, with the field and values that
+/// events of that kind have (`None`: no narrowing).
+type Scope = (
+    &'static str,
+    Option<(&'static str, &'static [&'static str])>,
+);
+
+/// The Sigma log sources one module's records are.
+struct LogSource {
+    module: &'static str,
+    products: &'static [&'static str],
+    categories: &'static [Scope],
+    services: &'static [Scope],
+    /// Rule field names the module calls differently.
+    fields: &'static [(&'static str, &'static str)],
+    /// Where other rule fields live (`event_data.` for Windows events).
+    prefix: &'static str,
+}
+
+const SYSMON: &str = "Microsoft-Windows-Sysmon/Operational";
+
+const LOG_SOURCES: &[LogSource] = &[
+    LogSource {
+        module: "web-access",
+        products: &["apache", "nginx"],
+        categories: &[("webserver", None)],
+        services: &[("access", None)],
+        fields: &[],
+        prefix: "",
+    },
+    LogSource {
+        module: "authlog",
+        products: &["linux"],
+        categories: &[],
+        services: &[("auth", None), ("sshd", None), ("sudo", None), ("su", None)],
+        fields: &[],
+        prefix: "",
+    },
+    LogSource {
+        module: "winevt-xml",
+        products: &["windows"],
+        categories: &[
+            ("process_creation", Some(("event_id", &["1"]))),
+            ("network_connection", Some(("event_id", &["3"]))),
+            ("driver_load", Some(("event_id", &["6"]))),
+            ("image_load", Some(("event_id", &["7"]))),
+            ("create_remote_thread", Some(("event_id", &["8"]))),
+            ("raw_access_thread", Some(("event_id", &["9"]))),
+            ("process_access", Some(("event_id", &["10"]))),
+            ("file_event", Some(("event_id", &["11"]))),
+            ("registry_add", Some(("event_id", &["12"]))),
+            ("registry_delete", Some(("event_id", &["12"]))),
+            ("registry_set", Some(("event_id", &["13"]))),
+            ("registry_rename", Some(("event_id", &["14"]))),
+            ("registry_event", Some(("event_id", &["12", "13", "14"]))),
+            ("create_stream_hash", Some(("event_id", &["15"]))),
+            ("pipe_created", Some(("event_id", &["17", "18"]))),
+            ("wmi_event", Some(("event_id", &["19", "20", "21"]))),
+            ("dns_query", Some(("event_id", &["22"]))),
+            ("file_delete", Some(("event_id", &["23", "26"]))),
+            ("ps_module", Some(("event_id", &["4103"]))),
+            ("ps_script", Some(("event_id", &["4104"]))),
+        ],
+        services: &[
+            ("security", Some(("channel", &["Security"]))),
+            ("system", Some(("channel", &["System"]))),
+            ("application", Some(("channel", &["Application"]))),
+            ("sysmon", Some(("channel", &[SYSMON]))),
+            (
+                "powershell",
+                Some(("channel", &["Microsoft-Windows-PowerShell/Operational"])),
+            ),
+            (
+                "powershell-classic",
+                Some(("channel", &["Windows PowerShell"])),
+            ),
+            (
+                "taskscheduler",
+                Some(("channel", &["Microsoft-Windows-TaskScheduler/Operational"])),
+            ),
+            (
+                "windefend",
+                Some((
+                    "channel",
+                    &["Microsoft-Windows-Windows Defender/Operational"],
+                )),
+            ),
+        ],
+        fields: &[
+            ("EventID", "event_id"),
+            ("Channel", "channel"),
+            ("Provider_Name", "provider"),
+            ("Computer", "computer"),
+            ("EventRecordID", "record_id"),
+        ],
+        prefix: "event_data.",
+    },
+    LogSource {
+        module: "okta",
+        products: &["okta"],
+        categories: &[],
+        services: &[("okta", None)],
+        fields: &[
+            ("eventtype", "eventType"),
+            ("displaymessage", "displayMessage"),
+            ("published", "ts"),
+        ],
+        prefix: "",
+    },
+];
+
+/// Sigma's web server field names, as ECS fields.
+const WEB_FIELDS: &[(&str, &str)] = &[
+    ("c-ip", "source.ip"),
+    ("cs-method", "http.request.method"),
+    ("cs-uri-stem", "url.path"),
+    ("c-uri-stem", "url.path"),
+    ("cs-uri-query", "url.query"),
+    ("c-uri-query", "url.query"),
+    ("cs-uri", "url.original"),
+    ("c-uri", "url.original"),
+    ("cs-referer", "http.request.referrer"),
+    ("cs-referrer", "http.request.referrer"),
+    ("cs-user-agent", "user_agent.original"),
+    ("c-useragent", "user_agent.original"),
+    ("sc-status", "http.response.status_code"),
+    ("sc-bytes", "http.response.body.bytes"),
+    ("cs-host", "url.domain"),
+    ("cs-username", "user.name"),
+    ("cs-version", "http.version"),
+];
+
+/// Loaded rules for one module. A record matching any gets
+/// `sigma_match: true` and `sigma: [{id, title, level}]`.
+pub struct Sigma {
+    rules: Vec<Rule>,
+    /// Rules for other log sources.
+    other: usize,
+    /// Rules that could not be used, with why.
+    skipped: Vec<String>,
+}
+
+struct Rule {
+    id: Value,
+    title: String,
+    level: Value,
+    selections: Vec<Selection>,
+    condition: Cond,
+    /// From the log source: every one must match too.
+    implied: Vec<FieldMatch>,
+}
+
+enum Cond {
+    Selection(usize),
+    Not(Box<Cond>),
+    And(Vec<Cond>),
+    Or(Vec<Cond>),
+}
+
+/// Maps of field matches, any of which (each taken whole) matches.
+struct Selection(Vec<Vec<FieldMatch>>);
+
+struct FieldMatch {
+    /// `None`: any string in the record (keywords).
+    path: Option<String>,
+    /// All values must match (`|all`), not just one.
+    all: bool,
+    values: Vec<Matcher>,
+}
+
+enum Matcher {
+    /// Compared with the value as a string, case-insensitively unless
+    /// `cased`.
+    Text {
+        pattern: Pattern,
+        cased: bool,
+    },
+    Regex(Regex),
+    Null,
+    Exists(bool),
+    Cidr(Networks),
+    Compare(f64, &'static [std::cmp::Ordering]),
+}
+
+enum Pattern {
+    Exact(String),
+    Prefix(String),
+    Suffix(String),
+    Contains(String),
+    Wildcard(Regex),
+}
+
+impl Sigma {
+    /// Load the rules in `paths` (files, or directories searched for
+    /// `.yml` / `.yaml`) for the records of `module`.
+    pub fn open(paths: &[PathBuf], module: &str) -> Result<Self> {
+        let (outer, inner) = split_chain(module);
+        let source = LOG_SOURCES
+            .iter()
+            .find(|s| s.module == inner)
+            .with_context(|| {
+                let known: Vec<_> = LOG_SOURCES.iter().map(|s| s.module).collect();
+                format!(
+                    "no Sigma log source for module {inner} (known: {})",
+                    known.join(", ")
+                )
+            })?;
+        let mut sigma = Self {
+            rules: Vec::new(),
+            other: 0,
+            skipped: Vec::new(),
+        };
+        let mut files = Vec::new();
+        for path in paths {
+            rule_files(path, &mut files)?;
+        }
+        let resolver = Resolver {
+            source,
+            parsed: outer.is_some(),
+        };
+        for file in files {
+            let text = std::fs::read_to_string(&file)
+                .with_context(|| format!("read {}", file.display()))?;
+            match load(&text, &resolver) {
+                Ok(Some(rule)) => sigma.rules.push(rule),
+                Ok(None) => sigma.other += 1,
+                Err(e) => sigma.skipped.push(format!("{}: {e:#}", file.display())),
+            }
+        }
+        Ok(sigma)
+    }
+
+    /// Rules that apply to the module.
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Rules written for other log sources.
+    pub fn other(&self) -> usize {
+        self.other
+    }
+
+    /// Rules that could not be used, each with the reason.
+    pub fn skipped(&self) -> &[String] {
+        &self.skipped
+    }
+}
+
+impl Enricher for Sigma {
+    fn enrich(&self, rec: &mut Map<String, Value>, _fields: &FieldTags) {
+        let hits: Vec<Value> = self
+            .rules
+            .iter()
+            .filter(|r| r.matches(rec))
+            .map(|r| serde_json::json!({"id": r.id, "title": r.title, "level": r.level}))
+            .collect();
+        if !hits.is_empty() {
+            rec.insert("sigma_match".to_string(), true.into());
+            rec.insert("sigma".to_string(), hits.into());
+        }
+    }
+}
+
+fn rule_files(path: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    if !path.is_dir() {
+        out.push(path.to_path_buf());
+        return Ok(());
+    }
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(path)
+        .with_context(|| format!("read {}", path.display()))?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<std::io::Result<_>>()?;
+    entries.sort();
+    for entry in entries {
+        let yaml = entry
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("yml") || e.eq_ignore_ascii_case("yaml"));
+        if entry.is_dir() || yaml {
+            rule_files(&entry, out)?;
+        }
+    }
+    Ok(())
+}
+
+/* -------------------- Loading -------------------- */
+
+/// Turns rule field names into paths in the module's records.
+struct Resolver {
+    source: &'static LogSource,
+    /// Chained module: the records are under `parsed`.
+    parsed: bool,
+}
+
+impl Resolver {
+    fn path(&self, name: &str) -> String {
+        let s = self.source;
+        let own = s.fields.iter().find(|(k, _)| *k == name).map(|(_, v)| *v);
+        let ecs_name = WEB_FIELDS
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map_or(name, |(_, v)| *v);
+        let path = match own.or_else(|| ecs::module_field(s.module, ecs_name)) {
+            Some(path) => path.to_string(),
+            None => format!("{}{name}", s.prefix),
+        };
+        match self.parsed {
+            true => format!("parsed.{path}"),
+            false => path,
+        }
+    }
+
+    /// The narrowing `logsource` implies, or `None` when the rule is for
+    /// another log source.
+    fn implied(&self, logsource: Option<&Value>) -> Option<Vec<FieldMatch>> {
+        let s = self.source;
+        let get = |key| logsource.and_then(|l| l.get(key)).and_then(Value::as_str);
+        if get("product").is_some_and(|p| !s.products.contains(&p)) {
+            return None;
+        }
+        let mut implied = Vec::new();
+        for (key, scopes) in [("category", s.categories), ("service", s.services)] {
+            let Some(name) = get(key) else { continue };
+            let (_, narrow) = scopes.iter().find(|(n, _)| *n == name)?;
+            if let Some((field, values)) = narrow {
+                implied.push(FieldMatch {
+                    path: Some(self.path_of_own(field)),
+                    all: false,
+                    values: values
+                        .iter()
+                        .map(|v| Matcher::Text {
+                            pattern: Pattern::Exact(v.to_lowercase()),
+                            cased: false,
+                        })
+                        .collect(),
+                });
+            }
+        }
+        Some(implied)
+    }
+
+    /// A field of the module's own records.
+    fn path_of_own(&self, field: &str) -> String {
+        match self.parsed {
+            true => format!("parsed.{field}"),
+            false => field.to_string(),
+        }
+    }
+}
+
+/// The rule in `text`, or `None` when it is for another log source.
+fn load(text: &str, resolver: &Resolver) -> Result<Option<Rule>> {
+    let mut docs = serde_yaml::Deserializer::from_str(text);
+    let doc = docs.next().context("empty file")?;
+    if docs.next().is_some() {
+        bail!("rule collections (several YAML documents) are not supported");
+    }
+    let rule: Value = serde::Deserialize::deserialize(doc).context("parse YAML")?;
+    let Some(implied) = resolver.implied(rule.get("logsource")) else {
+        return Ok(None);
+    };
+    let detection = rule
+        .get("detection")
+        .and_then(Value::as_object)
+        .context("no detection")?;
+
+    let mut names = Vec::new();
+    let mut selections = Vec::new();
+    for (name, body) in detection {
+        if name == "condition" || name == "timeframe" {
+            continue;
+        }
+        let selection = selection(body, resolver).with_context(|| format!("selection {name}"))?;
+        names.push(name.as_str());
+        selections.push(selection);
+    }
+    let condition = match detection.get("condition") {
+        Some(Value::String(c)) => condition(c, &names)?,
+        Some(Value::Array(cs)) => Cond::Or(
+            cs.iter()
+                .map(|c| condition(c.as_str().context("condition is not a string")?, &names))
+                .collect::<Result<_>>()?,
+        ),
+        _ => bail!("no condition"),
+    };
+
+    Ok(Some(Rule {
+        id: rule.get("id").cloned().unwrap_or(Value::Null),
+        title: rule
+            .get("title")
+            .and_then(Value::as_str)
+            .unwrap_or("untitled")
+            .to_string(),
+        level: rule.get("level").cloned().unwrap_or(Value::Null),
+        selections,
+        condition,
+        implied,
+    }))
+}
+
+fn selection(body: &Value, resolver: &Resolver) -> Result<Selection> {
+    let maps = match body {
+        Value::Object(map) => vec![field_matches(map, resolver)?],
+        Value::Array(items) if items.iter().all(Value::is_object) => items
+            .iter()
+            .map(|m| field_matches(m.as_object().expect("checked"), resolver))
+            .collect::<Result<_>>()?,
+        // Keywords: any string of the record.
+        keywords => vec![vec![field_match(None, &[], keywords)?]],
+    };
+    Ok(Selection(maps))
+}
+
+fn field_matches(map: &Map<String, Value>, resolver: &Resolver) -> Result<Vec<FieldMatch>> {
+    map.iter()
+        .map(|(key, values)| {
+            let mut parts = key.split('|');
+            let name = parts.next().unwrap_or_default();
+            let modifiers: Vec<&str> = parts.collect();
+            let path = (!name.is_empty()).then(|| resolver.path(name));
+            field_match(path, &modifiers, values).with_context(|| format!("field {key}"))
+        })
+        .collect()
+}
+
+fn field_match(path: Option<String>, modifiers: &[&str], values: &Value) -> Result<FieldMatch> {
+    let values: Vec<&Value> = match values {
+        Value::Array(items) => items.iter().collect(),
+        v => vec![v],
+    };
+    let mut all = false;
+    let mut cased = false;
+    let mut windash = false;
+    let mut kind = if path.is_none() { "contains" } else { "" };
+    let mut flags = String::new();
+    for &m in modifiers {
+        match m {
+            "all" => all = true,
+            "cased" => cased = true,
+            "windash" => windash = true,
+            "i" | "m" | "s" => flags.push_str(m),
+            "contains" | "startswith" | "endswith" | "re" | "cidr" | "exists" | "lt" | "lte"
+            | "gt" | "gte" => kind = m,
+            other => bail!("modifier {other} is not supported"),
+        }
+    }
+
+    let mut matchers = Vec::new();
+    for v in values {
+        let text = match v {
+            Value::Null => {
+                matchers.push(Matcher::Null);
+                continue;
+            }
+            Value::String(s) => s.clone(),
+            Value::Number(_) | Value::Bool(_) => v.to_string(),
+            _ => bail!("value {v} is not a string or number"),
+        };
+        match kind {
+            "re" => {
+                let re = match flags.is_empty() {
+                    true => text,
+                    false => format!("(?{flags}){text}"),
+                };
+                matchers.push(Matcher::Regex(
+                    Regex::new(&re).with_context(|| format!("regex {re:?}"))?,
+                ));
+            }
+            "cidr" => {
+                let mut nets = Networks::default();
+                nets.insert(&text, "")?;
+                matchers.push(Matcher::Cidr(nets));
+            }
+            "exists" => matchers.push(Matcher::Exists(text == "true")),
+            "lt" | "lte" | "gt" | "gte" => {
+                use std::cmp::Ordering::*;
+                let n = text
+                    .parse()
+                    .with_context(|| format!("{text:?} is not a number"))?;
+                let want: &'static [std::cmp::Ordering] = match kind {
+                    "lt" => &[Less],
+                    "lte" => &[Less, Equal],
+                    "gt" => &[Greater],
+                    _ => &[Greater, Equal],
+                };
+                matchers.push(Matcher::Compare(n, want));
+            }
+            _ => {
+                let variants = match windash {
+                    true => ["-", "/", "\u{2013}", "\u{2014}", "\u{2015}"]
+                        .iter()
+                        .map(|d| text.replace('-', d))
+                        .collect(),
+                    false => vec![text],
+                };
+                for t in variants {
+                    let pattern = pattern(&t, kind, cased)?;
+                    matchers.push(Matcher::Text { pattern, cased });
+                }
+            }
+        }
+    }
+    Ok(FieldMatch {
+        path,
+        all,
+        values: matchers,
+    })
+}
+
+/// A Sigma string (`*` and `?` wildcards, `\` escaping them) as a pattern
+/// for the `kind` of match.
+fn pattern(text: &str, kind: &str, cased: bool) -> Result<Pattern> {
+    let mut literal = String::new();
+    let mut regex = String::new();
+    let mut wild = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if matches!(chars.peek(), Some('*' | '?' | '\\')) => {
+                let c = chars.next().expect("peeked");
+                literal.push(c);
+                regex.push_str(&regex::escape(&c.to_string()));
+            }
+            '*' => {
+                wild = true;
+                regex.push_str(".*");
+            }
+            '?' => {
+                wild = true;
+                regex.push('.');
+            }
+            c => {
+                literal.push(c);
+                regex.push_str(&regex::escape(&c.to_string()));
+            }
+        }
+    }
+    if !cased {
+        literal = literal.to_lowercase();
+    }
+    if !wild {
+        return Ok(match kind {
+            "contains" => Pattern::Contains(literal),
+            "startswith" => Pattern::Prefix(literal),
+            "endswith" => Pattern::Suffix(literal),
+            _ => Pattern::Exact(literal),
+        });
+    }
+    let (head, tail) = match kind {
+        "contains" => (".*", ".*"),
+        "startswith" => ("", ".*"),
+        "endswith" => (".*", ""),
+        _ => ("", ""),
+    };
+    let flags = if cased { "s" } else { "si" };
+    let re = format!("(?{flags})^{head}{regex}{tail}$");
+    Ok(Pattern::Wildcard(Regex::new(&re)?))
+}
+
+/// Parse a condition over the selections `names`.
+fn condition(text: &str, names: &[&str]) -> Result<Cond> {
+    if text.contains('|') {
+        bail!("aggregations in conditions are not supported");
+    }
+    let spaced = text.replace('(', " ( ").replace(')', " ) ");
+    let tokens: Vec<&str> = spaced.split_whitespace().collect();
+    let mut p = CondParser {
+        tokens,
+        pos: 0,
+        names,
+    };
+    let cond = p.or()?;
+    if p.pos < p.tokens.len() {
+        bail!("unexpected {:?} in condition {text:?}", p.tokens[p.pos]);
+    }
+    Ok(cond)
+}
+
+struct CondParser<'a> {
+    tokens: Vec<&'a str>,
+    pos: usize,
+    names: &'a [&'a str],
+}
+
+impl<'a> CondParser<'a> {
+    fn peek_is(&self, word: &str) -> bool {
+        self.tokens
+            .get(self.pos)
+            .is_some_and(|t| t.eq_ignore_ascii_case(word))
+    }
+
+    fn next(&mut self) -> Result<&'a str> {
+        let t = *self
+            .tokens
+            .get(self.pos)
+            .context("condition ends too early")?;
+        self.pos += 1;
+        Ok(t)
+    }
+
+    fn or(&mut self) -> Result<Cond> {
+        let mut terms = vec![self.and()?];
+        while self.peek_is("or") {
+            self.pos += 1;
+            terms.push(self.and()?);
+        }
+        Ok(flatten(terms, Cond::Or))
+    }
+
+    fn and(&mut self) -> Result<Cond> {
+        let mut terms = vec![self.not()?];
+        while self.peek_is("and") {
+            self.pos += 1;
+            terms.push(self.not()?);
+        }
+        Ok(flatten(terms, Cond::And))
+    }
+
+    fn not(&mut self) -> Result<Cond> {
+        if self.peek_is("not") {
+            self.pos += 1;
+            return Ok(Cond::Not(Box::new(self.not()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Cond> {
+        let t = self.next()?;
+        if t == "(" {
+            let cond = self.or()?;
+            if self.next()? != ")" {
+                bail!("missing ) in condition");
+            }
+            return Ok(cond);
+        }
+        if (t == "1" || t.eq_ignore_ascii_case("any") || t.eq_ignore_ascii_case("all"))
+            && self.peek_is("of")
+        {
+            self.pos += 1;
+            let target = self.next()?;
+            let picked: Vec<Cond> = self
+                .names
+                .iter()
+                .enumerate()
+                .filter(|(_, n)| match target {
+                    "them" => !n.starts_with('_'),
+                    glob => glob_match(glob, n),
+                })
+                .map(|(i, _)| Cond::Selection(i))
+                .collect();
+            if picked.is_empty() {
+                bail!("no selection matches {target:?}");
+            }
+            return Ok(match t.eq_ignore_ascii_case("all") {
+                true => Cond::And(picked),
+                false => Cond::Or(picked),
+            });
+        }
+        match self.names.iter().position(|n| *n == t) {
+            Some(i) => Ok(Cond::Selection(i)),
+            None => bail!("unknown selection {t:?} in condition"),
+        }
+    }
+}
+
+fn flatten(mut terms: Vec<Cond>, join: fn(Vec<Cond>) -> Cond) -> Cond {
+    match terms.len() {
+        1 => terms.pop().expect("one term"),
+        _ => join(terms),
+    }
+}
+
+/// `selection_*` style name patterns of `1 of` / `all of`.
+fn glob_match(glob: &str, name: &str) -> bool {
+    match glob.split_once('*') {
+        Some((head, rest)) => {
+            name.starts_with(head)
+                && (head.len()..=name.len())
+                    .filter(|&i| name.is_char_boundary(i))
+                    .any(|i| glob_match(rest, &name[i..]))
+        }
+        None => glob == name,
+    }
+}
+
+/* -------------------- Matching -------------------- */
+
+impl Rule {
+    fn matches(&self, rec: &Map<String, Value>) -> bool {
+        self.implied.iter().all(|m| m.matches(rec)) && self.eval(&self.condition, rec)
+    }
+
+    fn eval(&self, cond: &Cond, rec: &Map<String, Value>) -> bool {
+        match cond {
+            Cond::Selection(i) => self.selections[*i].matches(rec),
+            Cond::Not(c) => !self.eval(c, rec),
+            Cond::And(cs) => cs.iter().all(|c| self.eval(c, rec)),
+            Cond::Or(cs) => cs.iter().any(|c| self.eval(c, rec)),
+        }
+    }
+}
+
+impl Selection {
+    fn matches(&self, rec: &Map<String, Value>) -> bool {
+        self.0.iter().any(|map| map.iter().all(|m| m.matches(rec)))
+    }
+}
+
+impl FieldMatch {
+    fn matches(&self, rec: &Map<String, Value>) -> bool {
+        let value = match &self.path {
+            Some(path) => field(rec, path),
+            None => {
+                let mut strings = Vec::new();
+                for v in rec.values() {
+                    strings_in(v, &mut strings);
+                }
+                let test = |m: &Matcher| strings.iter().any(|s| m.matches_text(s));
+                return match self.all {
+                    true => self.values.iter().all(test),
+                    false => self.values.iter().any(test),
+                };
+            }
+        };
+        let test = |m: &Matcher| m.matches(value);
+        match self.all {
+            true => self.values.iter().all(test),
+            false => self.values.iter().any(test),
+        }
+    }
+}
+
+impl Matcher {
+    fn matches(&self, value: Option<&Value>) -> bool {
+        match (self, value) {
+            (Matcher::Null, v) => v.is_none_or(Value::is_null),
+            (Matcher::Exists(want), v) => v.is_some_and(|v| !v.is_null()) == *want,
+            (Matcher::Compare(n, want), Some(v)) => {
+                let got = match v {
+                    Value::Number(x) => x.as_f64(),
+                    Value::String(s) => s.trim().parse().ok(),
+                    _ => None,
+                };
+                got.and_then(|g| g.partial_cmp(n))
+                    .is_some_and(|o| want.contains(&o))
+            }
+            (_, Some(Value::Array(items))) => items.iter().any(|i| self.matches(Some(i))),
+            (_, Some(Value::String(s))) => self.matches_text(s),
+            (_, Some(v @ (Value::Number(_) | Value::Bool(_)))) => self.matches_text(&v.to_string()),
+            _ => false,
+        }
+    }
+
+    fn matches_text(&self, s: &str) -> bool {
+        match self {
+            Matcher::Text { pattern, cased } => {
+                let lower;
+                let s = match cased {
+                    true => s,
+                    false => {
+                        lower = s.to_lowercase();
+                        &lower
+                    }
+                };
+                match pattern {
+                    Pattern::Exact(p) => s == p,
+                    Pattern::Prefix(p) => s.starts_with(p.as_str()),
+                    Pattern::Suffix(p) => s.ends_with(p.as_str()),
+                    Pattern::Contains(p) => s.contains(p.as_str()),
+                    Pattern::Wildcard(re) => re.is_match(s),
+                }
+            }
+            Matcher::Regex(re) => re.is_match(s),
+            Matcher::Cidr(nets) => parse_ip(s).is_some_and(|ip| !nets.lookup(ip).is_empty()),
+            _ => false,
+        }
+    }
+}
+
+/// Every string (and number) in `v`, for keyword searches.
+fn strings_in<'v>(v: &'v Value, out: &mut Vec<Cow<'v, str>>) {
+    match v {
+        Value::String(s) => out.push(s.as_str().into()),
+        Value::Number(n) => out.push(n.to_string().into()),
+        Value::Array(items) => items.iter().for_each(|i| strings_in(i, out)),
+        Value::Object(map) => map.values().for_each(|i| strings_in(i, out)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sigma(module: &str, rules: &[&str]) -> Sigma {
+        let (outer, inner) = split_chain(module);
+        let resolver = Resolver {
+            source: LOG_SOURCES.iter().find(|s| s.module == inner).unwrap(),
+            parsed: outer.is_some(),
+        };
+        let mut sigma = Sigma {
+            rules: Vec::new(),
+            other: 0,
+            skipped: Vec::new(),
+        };
+        for text in rules {
+            match load(text, &resolver) {
+                Ok(Some(rule)) => sigma.rules.push(rule),
+                Ok(None) => sigma.other += 1,
+                Err(e) => sigma.skipped.push(format!("{e:#}")),
+            }
+        }
+        sigma
+    }
+
+    /// The titles of the rules `rec` matches.
+    fn hits(sigma: &Sigma, rec: Value) -> Vec<String> {
+        let mut rec = rec.as_object().unwrap().clone();
+        sigma.enrich(&mut rec, &FieldTags::default());
+        rec.get("sigma")
+            .and_then(Value::as_array)
+            .map(|hits| hits.iter().map(|h| h["title"].to_string()).collect())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn matches_web_rules_through_the_ecs_mapping() {
+        let sigma = sigma(
+            "web-access",
+            &[
+                r#"
+title: Path traversal
+id: 1
+level: high
+logsource: {category: webserver}
+detection:
+  selection:
+    cs-uri-query|contains: ['../', '..%2f']
+  filter:
+    sc-status: 404
+  condition: selection and not filter
+"#,
+                r#"
+title: Scanner UA
+logsource: {product: nginx, category: webserver}
+detection:
+  ua:
+    c-useragent|startswith: ['sqlmap', 'Nikto']
+  internal:
+    c-ip|cidr: 10.0.0.0/8
+  condition: ua and not internal
+"#,
+                r#"
+title: Wildcards
+logsource: {category: webserver}
+detection:
+  sel:
+    cs-method: 'P?ST'
+    cs-uri-stem: '/wp-*/admin\*.php'
+  condition: all of sel*
+"#,
+                "title: Windows\nlogsource: {product: windows}\ndetection: {s: {EventID: 1}, condition: s}\n",
+                "title: Count\nlogsource: {category: webserver}\ndetection: {s: {sc-status: 401}, condition: s | count() > 5}\n",
+            ],
+        );
+        assert_eq!(
+            (sigma.len(), sigma.other(), sigma.skipped().len()),
+            (3, 1, 1)
+        );
+
+        let rec = json!({"ip": "203.0.113.9", "method": "GET", "path": "/x",
+            "query": "f=..%2F..%2Fetc/passwd", "status": 200, "user_agent": "sqlmap/1.7"});
+        assert_eq!(
+            hits(&sigma, rec),
+            [r#""Path traversal""#, r#""Scanner UA""#]
+        );
+        let rec = json!({"ip": "10.1.2.3", "query": "f=../x", "status": 404,
+            "user_agent": "sqlmap/1.7"});
+        assert!(hits(&sigma, rec).is_empty());
+        let rec = json!({"method": "post", "path": "/wp-content/admin*.php"});
+        assert_eq!(hits(&sigma, rec), [r#""Wildcards""#]);
+        let rec = json!({"method": "POST", "path": "/wp-content/admin1.php"});
+        assert!(hits(&sigma, rec).is_empty());
+    }
+
+    #[test]
+    fn windows_categories_imply_event_ids_and_channels() {
+        let sigma = sigma(
+            "winevt-xml",
+            &[
+                r#"
+title: Encoded PowerShell
+logsource: {product: windows, category: process_creation}
+detection:
+  img:
+    - Image|endswith: '\powershell.exe'
+    - OriginalFileName: PowerShell.EXE
+  enc:
+    CommandLine|windash|contains: ' -enc '
+  condition: 1 of img* and enc
+"#,
+                r#"
+title: Failed logon
+logsource: {product: windows, service: security}
+detection:
+  sel: {EventID: 4625, TargetUserName|re: '(?i)^adm'}
+  condition: sel
+"#,
+                "title: Linux\nlogsource: {product: linux}\ndetection: {s: {a: 1}, condition: s}\n",
+            ],
+        );
+        assert_eq!((sigma.len(), sigma.other()), (2, 1));
+
+        let process = |id, cmd: &str| {
+            json!({"event_id": id, "channel": SYSMON, "event_data": {
+                "Image": "C:\\Windows\\System32\\WindowsPowerShell\\v1.0\\PowerShell.exe",
+                "CommandLine": cmd}})
+        };
+        assert_eq!(
+            hits(&sigma, process(1, "powershell /enc SQBFAFgA")),
+            [r#""Encoded PowerShell""#]
+        );
+        assert!(hits(&sigma, process(1, "powershell -File x.ps1")).is_empty());
+        assert!(hits(&sigma, process(3, "powershell -enc SQBFAFgA")).is_empty());
+
+        let logon = |channel| {
+            json!({"event_id": 4625, "channel": channel,
+                "event_data": {"TargetUserName": "Administrator"}})
+        };
+        assert_eq!(hits(&sigma, logon("Security")), [r#""Failed logon""#]);
+        assert!(hits(&sigma, logon("System")).is_empty());
+    }
+
+    #[test]
+    fn keywords_and_chained_records() {
+        let sigma = sigma(
+            "docker-json,authlog",
+            &[r#"
+title: Root login
+logsource: {product: linux, service: sshd}
+detection:
+  keywords:
+    - 'Accepted password for root'
+    - 'Accepted publickey for root'
+  sel:
+    program: sshd
+  condition: keywords and sel
+"#],
+        );
+        let rec = json!({"stream": "stdout", "parsed": {"program": "sshd",
+            "message": "Accepted password for ROOT from 10.0.0.1 port 22"}});
+        assert_eq!(hits(&sigma, rec), [r#""Root login""#]);
+        let rec = json!({"parsed": {"program": "sudo",
+            "message": "Accepted password for root"}});
+        assert!(hits(&sigma, rec).is_empty());
+    }
+
+    #[test]
+    fn okta_rules_use_the_flattened_keys() {
+        let sigma = sigma(
+            "okta",
+            &[r#"
+title: MFA reset
+logsource: {product: okta, service: okta}
+detection:
+  selection:
+    eventtype: user.mfa.factor.reset_all
+    outcome.result: SUCCESS
+  condition: selection
+"#],
+        );
+        let rec = json!({"eventType": "user.mfa.factor.reset_all", "outcome.result": "SUCCESS"});
+        assert_eq!(hits(&sigma, rec), [r#""MFA reset""#]);
+    }
+
+    #[test]
+    fn parses_conditions() {
+        let names = ["sel_a", "sel_b", "filter", "_hidden"];
+        /// `cond` with the selections `on` matching.
+        fn eval(cond: &Cond, on: &[usize]) -> bool {
+            match cond {
+                Cond::Selection(i) => on.contains(i),
+                Cond::Not(c) => !eval(c, on),
+                Cond::And(cs) => cs.iter().all(|c| eval(c, on)),
+                Cond::Or(cs) => cs.iter().any(|c| eval(c, on)),
+            }
+        }
+        for (text, on, want) in [
+            ("sel_a and not filter", &[0][..], true),
+            ("sel_a and not filter", &[0, 2], false),
+            ("1 of sel_* and not filter", &[1], true),
+            ("all of sel_*", &[1], false),
+            ("all of them", &[0, 1, 2], true),
+            ("(sel_a or sel_b) and (not filter)", &[2], false),
+            ("not sel_a or sel_b and filter", &[0, 1, 2], true),
+        ] {
+            let cond = condition(text, &names).unwrap();
+            assert_eq!(eval(&cond, on), want, "{text} with {on:?}");
+        }
+        for (text, msg) in [
+            ("sel_c", "unknown selection"),
+            ("1 of x*", "no selection matches"),
+            ("(sel_a", "ends too early"),
+            ("sel_a sel_b", "unexpected"),
+            ("sel_a | count() > 3", "aggregations"),
+        ] {
+            let err = condition(text, &names).err().unwrap().to_string();
+            assert!(err.contains(msg), "{text}: {err}");
+        }
+    }
+}
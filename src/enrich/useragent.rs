@@ -0,0 +1,361 @@
+//! `--parse-ua`: browser, OS and device class out of `User-Agent` strings,
+//! from a compact rule set covering the browsers, crawlers and HTTP
+//! libraries common in access logs.
+
+use std::{cell::RefCell, collections::HashMap};
+
+use serde_json::{Map, Value};
+
+use super::{parent_mut, Enricher, FieldTags};
+
+/// Distinct user agents remembered per worker thread; the cache is simply
+/// dropped when it fills up.
+const CACHE_SIZE: usize = 4096;
+
+thread_local! {
+    static CACHE: RefCell<HashMap<String, Value>> = RefCell::new(HashMap::new());
+}
+
+/// Adds `ua: {browser, browser_version, os, os_version, device, bot}` next
+/// to each tagged user-agent field. Unknown parts are `null`; `device` is
+/// `desktop`, `mobile`, `tablet`, `bot` or `other`.
+pub struct UserAgent;
+
+impl Enricher for UserAgent {
+    fn enrich(&self, rec: &mut Map<String, Value>, fields: &FieldTags) {
+        for field in &fields.user_agent {
+            let Some((parent, key)) = parent_mut(rec, field) else {
+                continue;
+            };
+            let Some(ua) = parent.get(key).and_then(Value::as_str) else {
+                continue;
+            };
+            if ua.is_empty() || ua == "-" {
+                continue;
+            }
+            let parsed = CACHE.with(|cache| {
+                let mut cache = cache.borrow_mut();
+                if let Some(v) = cache.get(ua) {
+                    return v.clone();
+                }
+                if cache.len() >= CACHE_SIZE {
+                    cache.clear();
+                }
+                let v = parse(ua);
+                cache.insert(ua.to_string(), v.clone());
+                v
+            });
+            parent.insert("ua".to_string(), parsed);
+        }
+    }
+}
+
+/// Tokens naming automated clients, matched case-insensitively.
+const BOT_HINTS: &[&str] = &[
+    "bot",
+    "crawl",
+    "spider",
+    "slurp",
+    "headless",
+    "curl/",
+    "wget/",
+    "python-",
+    "go-http-client",
+    "java/",
+    "libwww",
+    "httpclient",
+    "okhttp",
+    "scrapy",
+    "postmanruntime",
+    "facebookexternalhit",
+    "preview",
+    "monitor",
+    "pingdom",
+    "nmap",
+    "masscan",
+    "zgrab",
+    "nikto",
+    "sqlmap",
+];
+
+/// `(token, family)` in priority order: derived browsers announce the
+/// engines they are built on too (`Edg/` comes with `Chrome/` and
+/// `Safari/`), so they are checked first.
+const BROWSERS: &[(&str, &str)] = &[
+    ("Edg/", "Edge"),
+    ("EdgA/", "Edge"),
+    ("EdgiOS/", "Edge"),
+    ("Edge/", "Edge"),
+    ("OPR/", "Opera"),
+    ("Opera/", "Opera"),
+    ("SamsungBrowser/", "Samsung Internet"),
+    ("YaBrowser/", "Yandex Browser"),
+    ("Vivaldi/", "Vivaldi"),
+    ("UCBrowser/", "UC Browser"),
+    ("FxiOS/", "Firefox"),
+    ("Firefox/", "Firefox"),
+    ("HeadlessChrome/", "HeadlessChrome"),
+    ("CriOS/", "Chrome"),
+    ("Chrome/", "Chrome"),
+    ("MSIE ", "IE"),
+    ("Googlebot/", "Googlebot"),
+    ("bingbot/", "Bingbot"),
+    ("YandexBot/", "YandexBot"),
+    ("DuckDuckBot/", "DuckDuckBot"),
+    ("Baiduspider/", "Baiduspider"),
+    ("facebookexternalhit/", "facebookexternalhit"),
+    ("curl/", "curl"),
+    ("Wget/", "Wget"),
+    ("python-requests/", "python-requests"),
+    ("Python-urllib/", "Python-urllib"),
+    ("Go-http-client/", "Go-http-client"),
+    ("okhttp/", "okhttp"),
+    ("PostmanRuntime/", "PostmanRuntime"),
+    ("Apache-HttpClient/", "Apache-HttpClient"),
+    ("Java/", "Java"),
+];
+
+/// Version digits right after `token` (`120.0.6099.71`).
+fn version_after<'a>(ua: &'a str, token: &str) -> Option<&'a str> {
+    let rest = &ua[ua.find(token)? + token.len()..];
+    let end = rest
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(rest.len());
+    Some(rest[..end].trim_end_matches('.')).filter(|v| !v.is_empty())
+}
+
+/// Browser (or client) family and version.
+fn browser(ua: &str) -> (Option<&str>, Option<&str>) {
+    for (token, family) in BROWSERS {
+        if ua.contains(token) {
+            let version = match *token {
+                "Opera/" => version_after(ua, "Version/"),
+                _ => None,
+            };
+            return (Some(family), version.or_else(|| version_after(ua, token)));
+        }
+    }
+    if ua.contains("Trident/") {
+        return (Some("IE"), version_after(ua, "rv:"));
+    }
+    if ua.contains("Safari/") && ua.contains("Version/") {
+        return (Some("Safari"), version_after(ua, "Version/"));
+    }
+    if ua.contains("AppleWebKit/") && (ua.contains("iPhone") || ua.contains("iPad")) {
+        return (Some("Mobile Safari UI/WKWebView"), None);
+    }
+    // Anything else that names itself `Something-bot/1.2`.
+    let lower = ua.to_ascii_lowercase();
+    if let Some(at) = lower.find("bot") {
+        let start = ua[..at]
+            .rfind(|c: char| !c.is_ascii_alphanumeric() && c != '-' && c != '_')
+            .map_or(0, |i| i + 1);
+        let end = ua[at..]
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '-' && c != '_')
+            .map_or(ua.len(), |i| at + i);
+        let name = &ua[start..end];
+        if !name.is_empty() {
+            let version = ua[end..]
+                .starts_with('/')
+                .then(|| version_after(&ua[end..], "/"))
+                .flatten();
+            return (Some(name), version);
+        }
+    }
+    (None, None)
+}
+
+/// Operating system and version.
+fn os(ua: &str) -> (Option<&'static str>, Option<String>) {
+    if let Some(nt) = version_after(ua, "Windows NT ") {
+        let version = match nt {
+            "10.0" => "10",
+            "6.3" => "8.1",
+            "6.2" => "8",
+            "6.1" => "7",
+            "6.0" => "Vista",
+            "5.1" | "5.2" => "XP",
+            other => other,
+        };
+        return (Some("Windows"), Some(version.to_string()));
+    }
+    if ua.contains("Windows") {
+        return (Some("Windows"), None);
+    }
+    if ua.contains("Android") {
+        return (
+            Some("Android"),
+            version_after(ua, "Android ").map(str::to_string),
+        );
+    }
+    if ua.contains("iPhone") || ua.contains("iPad") || ua.contains("iPod") {
+        let version = ua.find(" OS ").map(|at| {
+            let rest = &ua[at + 4..];
+            let end = rest
+                .find(|c: char| !c.is_ascii_digit() && c != '_')
+                .unwrap_or(rest.len());
+            rest[..end].replace('_', ".")
+        });
+        return (Some("iOS"), version.filter(|v| !v.is_empty()));
+    }
+    if let Some(at) = ua.find("Mac OS X") {
+        let rest = ua[at + 8..].trim_start();
+        let end = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '_' && c != '.')
+            .unwrap_or(rest.len());
+        let version = rest[..end].replace('_', ".");
+        return (Some("macOS"), Some(version).filter(|v| !v.is_empty()));
+    }
+    if ua.contains("CrOS") {
+        return (Some("Chrome OS"), None);
+    }
+    if ua.contains("Linux") || ua.contains("X11") {
+        return (Some("Linux"), None);
+    }
+    (None, None)
+}
+
+fn parse(ua: &str) -> Value {
+    let lower = ua.to_ascii_lowercase();
+    let bot = BOT_HINTS.iter().any(|h| lower.contains(h));
+    let (browser, browser_version) = browser(ua);
+    let (os, os_version) = os(ua);
+
+    let device = if bot {
+        "bot"
+    } else if ua.contains("iPad")
+        || ua.contains("Tablet")
+        || (ua.contains("Android") && !ua.contains("Mobile"))
+    {
+        "tablet"
+    } else if ua.contains("Mobile") || ua.contains("iPhone") || ua.contains("iPod") {
+        "mobile"
+    } else if os.is_some() {
+        "desktop"
+    } else {
+        "other"
+    };
+
+    serde_json::json!({
+        "browser": browser,
+        "browser_version": browser_version,
+        "os": os,
+        "os_version": os_version,
+        "device": device,
+        "bot": bot,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn ua(s: &str) -> (Value, Value, Value, Value, Value, Value) {
+        let v = parse(s);
+        (
+            v["browser"].clone(),
+            v["browser_version"].clone(),
+            v["os"].clone(),
+            v["os_version"].clone(),
+            v["device"].clone(),
+            v["bot"].clone(),
+        )
+    }
+
+    #[test]
+    fn browsers_and_platforms() {
+        let chrome = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 \
+                      (KHTML, like Gecko) Chrome/120.0.6099.71 Safari/537.36";
+        assert_eq!(
+            ua(chrome),
+            (
+                json!("Chrome"),
+                json!("120.0.6099.71"),
+                json!("Windows"),
+                json!("10"),
+                json!("desktop"),
+                json!(false)
+            )
+        );
+
+        let edge = format!("{chrome} Edg/120.0.2210.61");
+        assert_eq!(ua(&edge).0, "Edge");
+        assert_eq!(ua(&edge).1, "120.0.2210.61");
+
+        let iphone =
+            "Mozilla/5.0 (iPhone; CPU iPhone OS 17_1_2 like Mac OS X) AppleWebKit/605.1.15 \
+                      (KHTML, like Gecko) Version/17.1.2 Mobile/15E148 Safari/604.1";
+        assert_eq!(
+            ua(iphone),
+            (
+                json!("Safari"),
+                json!("17.1.2"),
+                json!("iOS"),
+                json!("17.1.2"),
+                json!("mobile"),
+                json!(false)
+            )
+        );
+
+        let tablet = "Mozilla/5.0 (Linux; Android 13; SM-X700) AppleWebKit/537.36 \
+                      (KHTML, like Gecko) Chrome/119.0.0.0 Safari/537.36";
+        assert_eq!(ua(tablet).2, "Android");
+        assert_eq!(ua(tablet).3, "13");
+        assert_eq!(ua(tablet).4, "tablet");
+
+        let firefox =
+            "Mozilla/5.0 (Macintosh; Intel Mac OS X 10.15; rv:121.0) Gecko/20100101 Firefox/121.0";
+        assert_eq!(
+            ua(firefox),
+            (
+                json!("Firefox"),
+                json!("121.0"),
+                json!("macOS"),
+                json!("10.15"),
+                json!("desktop"),
+                json!(false)
+            )
+        );
+
+        let ie = "Mozilla/5.0 (Windows NT 6.1; Trident/7.0; rv:11.0) like Gecko";
+        assert_eq!(
+            (ua(ie).0, ua(ie).1, ua(ie).3),
+            (json!("IE"), json!("11.0"), json!("7"))
+        );
+    }
+
+    #[test]
+    fn bots_and_tools() {
+        let google = "Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)";
+        assert_eq!(ua(google).0, "Googlebot");
+        assert_eq!(ua(google).1, "2.1");
+        assert_eq!(ua(google).4, "bot");
+        assert_eq!(ua(google).5, true);
+
+        assert_eq!(ua("curl/8.4.0").0, "curl");
+        assert_eq!(ua("curl/8.4.0").5, true);
+
+        let other = "Mozilla/5.0 (compatible; AhrefsBot/7.0; +http://ahrefs.com/robot/)";
+        assert_eq!(ua(other).0, "AhrefsBot");
+        assert_eq!(ua(other).1, "7.0");
+
+        assert_eq!(ua("something").4, "other");
+        assert_eq!(ua("something").0, Value::Null);
+    }
+
+    #[test]
+    fn enriches_tagged_fields() {
+        let mut rec = json!({"user_agent": "curl/8.4.0", "parsed": {"agent": "-"}})
+            .as_object()
+            .unwrap()
+            .clone();
+        let fields = FieldTags {
+            user_agent: vec!["user_agent".into(), "parsed.agent".into()],
+            ..FieldTags::default()
+        };
+        UserAgent.enrich(&mut rec, &fields);
+        assert_eq!(rec["ua"]["browser"], "curl");
+        assert!(rec["parsed"].get("ua").is_none());
+    }
+}
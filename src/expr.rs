@@ -0,0 +1,770 @@
+//! The expression language shared by `--where` and `--transform`.
+//!
+//! Operands are field names (dotted names reach into nested objects, a
+//! literal `a.b` key wins; `` `odd-name` `` quotes any other), strings in
+//! `"` or `'`, numbers, `true`, `false` and `null`. Loosest binding first:
+//!
+//! - `||` (`or`), `&&` (`and`), `!` (`not`)
+//! - `==`, `!=`, `<`, `<=`, `>`, `>=`, and regex matches `=~` / `!~`
+//! - `+` (adds numbers, joins strings), `-`
+//! - `*`, `/` (whole numbers divide to a whole number), `%`
+//! - unary `-`
+//! - indexing `x[i]` and slicing `x[start:end]` of strings and arrays
+//!   (negative positions count from the end)
+//!
+//! Functions: `lower`, `upper`, `trim`, `len`, `str`, `int`, `float`,
+//! `split(s, sep)`, `contains(s, x)`, `starts_with(s, prefix)`,
+//! `ends_with(s, suffix)`, `replace(s, "regex", "with $1")` and
+//! `coalesce(a, b, ...)`. A missing field is `null`; arithmetic with `null`
+//! or non-numbers gives `null`.
+
+use std::cmp::Ordering;
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde_json::{Map, Number, Value};
+
+#[derive(Debug)]
+pub(crate) enum Expr {
+    Literal(Value),
+    Field(String),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Binary(Box<Expr>, BinOp, Box<Expr>),
+    Matches(Box<Expr>, Regex, bool),
+    Index(Box<Expr>, Box<Expr>),
+    Slice(Box<Expr>, Option<Box<Expr>>, Option<Box<Expr>>),
+    Call(Func, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum BinOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+#[derive(Debug)]
+pub(crate) enum Func {
+    Lower,
+    Upper,
+    Trim,
+    Len,
+    Str,
+    Int,
+    Float,
+    Split,
+    Contains,
+    StartsWith,
+    EndsWith,
+    /// The pattern is compiled once, when the expression is parsed.
+    Replace(Regex),
+    Coalesce,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Token {
+    Ident(String),
+    /// A `` `quoted` `` field name.
+    Field(String),
+    Str(String),
+    Num(Number),
+    Op(&'static str),
+}
+
+/// Operators, longest first so `<=` is not read as `<`.
+const OPS: &[&str] = &[
+    "&&", "||", "==", "!=", "<=", ">=", "=~", "!~", "->", "<", ">", "!", "(", ")", "[", "]", ",",
+    ":", ";", "=", "+", "-", "*", "/", "%",
+];
+
+/// A value counts as true unless it is `null`, `false`, `0` or `""`.
+pub(crate) fn truthy(v: &Value) -> bool {
+    match v {
+        Value::Null | Value::Bool(false) => false,
+        Value::String(s) => !s.is_empty(),
+        Value::Number(n) => n.as_f64() != Some(0.0),
+        _ => true,
+    }
+}
+
+impl Expr {
+    pub(crate) fn eval(&self, rec: &Map<String, Value>) -> Value {
+        match self {
+            Expr::Literal(v) => v.clone(),
+            Expr::Field(path) => field(rec, path).cloned().unwrap_or(Value::Null),
+            Expr::Not(e) => Value::Bool(!truthy(&e.eval(rec))),
+            Expr::Neg(e) => match number(&e.eval(rec)) {
+                Some(Num::Int(i)) => i.checked_neg().map_or(Value::Null, Value::from),
+                Some(Num::Float(f)) => float(-f),
+                None => Value::Null,
+            },
+            Expr::And(a, b) => Value::Bool(truthy(&a.eval(rec)) && truthy(&b.eval(rec))),
+            Expr::Or(a, b) => Value::Bool(truthy(&a.eval(rec)) || truthy(&b.eval(rec))),
+            Expr::Binary(a, op, b) => binary(&a.eval(rec), *op, &b.eval(rec)),
+            Expr::Matches(e, re, negate) => {
+                let hit = match e.eval(rec) {
+                    Value::String(s) => re.is_match(&s),
+                    v @ (Value::Number(_) | Value::Bool(_)) => re.is_match(&v.to_string()),
+                    _ => false,
+                };
+                Value::Bool(hit != *negate)
+            }
+            Expr::Index(e, i) => index(e.eval(rec), &i.eval(rec)),
+            Expr::Slice(e, start, end) => {
+                let bound = |b: &Option<Box<Expr>>| {
+                    b.as_ref()
+                        .map(|b| number(&b.eval(rec)).map(|n| Some(n.int())))
+                        .unwrap_or(Some(None))
+                };
+                match (bound(start), bound(end)) {
+                    (Some(start), Some(end)) => slice(e.eval(rec), start, end),
+                    _ => Value::Null,
+                }
+            }
+            Expr::Call(func, args) => {
+                let args: Vec<Value> = args.iter().map(|a| a.eval(rec)).collect();
+                call(func, args)
+            }
+        }
+    }
+}
+
+/// The value at `path`; a literal key wins over a nested path.
+pub(crate) fn field<'r>(rec: &'r Map<String, Value>, path: &str) -> Option<&'r Value> {
+    if let Some(v) = rec.get(path) {
+        return Some(v);
+    }
+    let (head, rest) = path.split_once('.')?;
+    match rec.get(head)? {
+        Value::Object(inner) => field(inner, rest),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Num {
+    Int(i64),
+    Float(f64),
+}
+
+impl Num {
+    fn int(self) -> i64 {
+        match self {
+            Num::Int(i) => i,
+            Num::Float(f) => f as i64,
+        }
+    }
+
+    fn float(self) -> f64 {
+        match self {
+            Num::Int(i) => i as f64,
+            Num::Float(f) => f,
+        }
+    }
+}
+
+/// Numbers, and strings holding one.
+fn number(v: &Value) -> Option<Num> {
+    match v {
+        Value::Number(n) => n.as_i64().map(Num::Int).or(n.as_f64().map(Num::Float)),
+        Value::String(s) => {
+            let s = s.trim();
+            s.parse()
+                .map(Num::Int)
+                .ok()
+                .or_else(|| s.parse().ok().map(Num::Float))
+        }
+        _ => None,
+    }
+}
+
+fn float(f: f64) -> Value {
+    Number::from_f64(f).map_or(Value::Null, Value::Number)
+}
+
+/// A value as text: strings as they are, `null` as nothing, the rest as JSON.
+fn text(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// Numbers compare numerically (numeric strings included), strings
+/// lexically (so ISO timestamps order by time); other mixes are unordered.
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (Value::Number(_), _) | (_, Value::Number(_)) => {
+            number(a)?.float().partial_cmp(&number(b)?.float())
+        }
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        (Value::Null, Value::Null) => Some(Ordering::Equal),
+        _ => None,
+    }
+}
+
+fn binary(a: &Value, op: BinOp, b: &Value) -> Value {
+    let ord = || compare(a, b);
+    let is = |want: &[Ordering]| Value::Bool(ord().is_some_and(|o| want.contains(&o)));
+    match op {
+        BinOp::Eq => is(&[Ordering::Equal]),
+        BinOp::Ne => Value::Bool(ord() != Some(Ordering::Equal)),
+        BinOp::Lt => is(&[Ordering::Less]),
+        BinOp::Le => is(&[Ordering::Less, Ordering::Equal]),
+        BinOp::Gt => is(&[Ordering::Greater]),
+        BinOp::Ge => is(&[Ordering::Greater, Ordering::Equal]),
+        BinOp::Add if a.is_string() || b.is_string() => Value::from(text(a) + &text(b)),
+        _ => {
+            let (Some(x), Some(y)) = (number(a), number(b)) else {
+                return Value::Null;
+            };
+            match (x, y) {
+                (Num::Int(x), Num::Int(y)) => {
+                    let r = match op {
+                        BinOp::Add => x.checked_add(y),
+                        BinOp::Sub => x.checked_sub(y),
+                        BinOp::Mul => x.checked_mul(y),
+                        BinOp::Div => x.checked_div(y),
+                        _ => x.checked_rem(y),
+                    };
+                    r.map_or(Value::Null, Value::from)
+                }
+                (x, y) => {
+                    let (x, y) = (x.float(), y.float());
+                    float(match op {
+                        BinOp::Add => x + y,
+                        BinOp::Sub => x - y,
+                        BinOp::Mul => x * y,
+                        BinOp::Div => x / y,
+                        _ => x % y,
+                    })
+                }
+            }
+        }
+    }
+}
+
+/// Position `i` of a sequence of `len`, negative ones from the end.
+fn position(i: i64, len: usize) -> usize {
+    if i < 0 {
+        len.saturating_sub(i.unsigned_abs() as usize)
+    } else {
+        (i as usize).min(len)
+    }
+}
+
+fn index(v: Value, i: &Value) -> Value {
+    match (v, i) {
+        (Value::Object(mut map), Value::String(key)) => map.remove(key).unwrap_or(Value::Null),
+        (v, i) => {
+            let Some(i) = number(i).map(Num::int) else {
+                return Value::Null;
+            };
+            match slice(v, Some(i), Some(i.saturating_add(1)).filter(|&e| e != 0)) {
+                Value::Array(mut items) if items.len() == 1 => items.remove(0),
+                Value::String(s) if !s.is_empty() => Value::String(s),
+                _ => Value::Null,
+            }
+        }
+    }
+}
+
+fn slice(v: Value, start: Option<i64>, end: Option<i64>) -> Value {
+    let range = |len: usize| {
+        let start = start.map_or(0, |s| position(s, len));
+        let end = end.map_or(len, |e| position(e, len));
+        start..end.max(start)
+    };
+    match v {
+        Value::String(s) => {
+            let chars: Vec<char> = s.chars().collect();
+            Value::String(chars[range(chars.len())].iter().collect())
+        }
+        Value::Array(items) => {
+            let r = range(items.len());
+            Value::Array(items[r].to_vec())
+        }
+        _ => Value::Null,
+    }
+}
+
+fn call(func: &Func, mut args: Vec<Value>) -> Value {
+    let str_map = |v: Value, f: fn(&str) -> String| match v {
+        Value::String(s) => Value::String(f(&s)),
+        other => other,
+    };
+    let arg = args.remove(0);
+    match func {
+        Func::Lower => str_map(arg, str::to_lowercase),
+        Func::Upper => str_map(arg, str::to_uppercase),
+        Func::Trim => str_map(arg, |s| s.trim().to_string()),
+        Func::Len => match arg {
+            Value::String(s) => s.chars().count().into(),
+            Value::Array(a) => a.len().into(),
+            Value::Object(o) => o.len().into(),
+            Value::Null => 0.into(),
+            _ => Value::Null,
+        },
+        Func::Str => match arg {
+            Value::Null => Value::Null,
+            other => Value::String(text(&other)),
+        },
+        Func::Int => match arg {
+            Value::Bool(b) => (b as i64).into(),
+            other => number(&other).map_or(Value::Null, |n| n.int().into()),
+        },
+        Func::Float => number(&arg).map_or(Value::Null, |n| float(n.float())),
+        Func::Split => match arg {
+            Value::String(s) => {
+                let sep = text(&args[0]);
+                if sep.is_empty() {
+                    return Value::Array(vec![Value::String(s)]);
+                }
+                s.split(sep.as_str()).map(Value::from).collect()
+            }
+            _ => Value::Null,
+        },
+        Func::Contains => match arg {
+            Value::String(s) => Value::Bool(s.contains(&text(&args[0]))),
+            Value::Array(items) => Value::Bool(items.contains(&args[0])),
+            _ => Value::Bool(false),
+        },
+        Func::StartsWith => {
+            Value::Bool(arg.as_str().is_some_and(|s| s.starts_with(&text(&args[0]))))
+        }
+        Func::EndsWith => Value::Bool(arg.as_str().is_some_and(|s| s.ends_with(&text(&args[0])))),
+        Func::Replace(re) => match arg {
+            Value::String(s) => Value::from(re.replace_all(&s, text(&args[0]).as_str())),
+            other => other,
+        },
+        Func::Coalesce => std::iter::once(arg)
+            .chain(args)
+            .find(|v| !v.is_null())
+            .unwrap_or(Value::Null),
+    }
+}
+
+pub(crate) fn tokenize(text: &str) -> Result<Vec<(Token, usize)>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some(&(i, c)) = chars.peek() {
+        let col = i + 1;
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        if c == '"' || c == '\'' || c == '`' {
+            chars.next();
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, '\\')) if c != '`' => match chars.next() {
+                        Some((_, e)) => s.push(e),
+                        None => bail!("unterminated string at column {col}"),
+                    },
+                    Some((_, q)) if q == c => break,
+                    Some((_, ch)) => s.push(ch),
+                    None => bail!("unterminated string at column {col}"),
+                }
+            }
+            let tok = match c {
+                '`' => Token::Field(s),
+                _ => Token::Str(s),
+            };
+            tokens.push((tok, col));
+            continue;
+        }
+
+        let rest = &text[i..];
+        let (tok, len) = if c.is_ascii_digit() {
+            let len = rest
+                .find(|ch: char| !ch.is_ascii_digit() && ch != '.')
+                .unwrap_or(rest.len());
+            let s = &rest[..len];
+            let num = match s.parse::<i64>() {
+                Ok(n) => Number::from(n),
+                Err(_) => s
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(Number::from_f64)
+                    .with_context(|| format!("bad number {s:?} at column {col}"))?,
+            };
+            (Token::Num(num), len)
+        } else if c.is_alphabetic() || c == '_' || c == '@' {
+            let len = rest
+                .find(|ch: char| !(ch.is_alphanumeric() || matches!(ch, '_' | '@' | '.')))
+                .unwrap_or(rest.len());
+            (Token::Ident(rest[..len].to_string()), len)
+        } else if let Some(op) = OPS.iter().find(|op| rest.starts_with(*op)) {
+            (Token::Op(op), op.len())
+        } else {
+            bail!("unexpected {c:?} at column {col}");
+        };
+        tokens.push((tok, col));
+        // Every token above is ASCII-led; identifiers may hold wider chars.
+        while chars.peek().is_some_and(|&(j, _)| j < i + len) {
+            chars.next();
+        }
+    }
+    Ok(tokens)
+}
+
+fn describe(tok: &Token) -> String {
+    match tok {
+        Token::Ident(s) => format!("{s:?}"),
+        Token::Field(s) => format!("`{s}`"),
+        Token::Str(s) => format!("string {s:?}"),
+        Token::Num(n) => format!("number {n}"),
+        Token::Op(op) => format!("{op:?}"),
+    }
+}
+
+/// Recursive descent over a token stream, loosest binding first.
+pub(crate) struct ExprParser {
+    tokens: Vec<(Token, usize)>,
+    at: usize,
+}
+
+impl ExprParser {
+    pub(crate) fn new(text: &str) -> Result<Self> {
+        Ok(Self {
+            tokens: tokenize(text)?,
+            at: 0,
+        })
+    }
+
+    pub(crate) fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.at).map(|(t, _)| t)
+    }
+
+    pub(crate) fn peek_at(&self, ahead: usize) -> Option<&Token> {
+        self.tokens.get(self.at + ahead).map(|(t, _)| t)
+    }
+
+    pub(crate) fn at_end(&self) -> bool {
+        self.at >= self.tokens.len()
+    }
+
+    /// Consume the next token if it is operator `op` (or its keyword form).
+    pub(crate) fn eat(&mut self, op: &str, word: Option<&str>) -> bool {
+        let hit = match self.peek() {
+            Some(Token::Op(o)) => *o == op,
+            Some(Token::Ident(w)) => Some(w.as_str()) == word,
+            _ => false,
+        };
+        self.at += hit as usize;
+        hit
+    }
+
+    pub(crate) fn expect(&mut self, op: &str) -> Result<()> {
+        if !self.eat(op, None) {
+            bail!("expected {op:?} {}", self.position());
+        }
+        Ok(())
+    }
+
+    /// A field name, bare or `` `quoted` ``.
+    pub(crate) fn field_name(&mut self) -> Result<String> {
+        let name = match self.peek() {
+            Some(Token::Ident(w)) if !is_keyword(w) => w.clone(),
+            Some(Token::Field(w)) => w.clone(),
+            _ => bail!("expected a field name {}", self.position()),
+        };
+        self.at += 1;
+        Ok(name)
+    }
+
+    /// Error unless every token was used.
+    pub(crate) fn finish(&self) -> Result<()> {
+        match self.tokens.get(self.at) {
+            Some((tok, col)) => bail!("unexpected {} at column {col}", describe(tok)),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn position(&self) -> String {
+        match self.tokens.get(self.at) {
+            Some((tok, col)) => format!("at column {col}, found {}", describe(tok)),
+            None => "at the end".to_string(),
+        }
+    }
+
+    pub(crate) fn expr(&mut self) -> Result<Expr> {
+        let mut e = self.and()?;
+        while self.eat("||", Some("or")) {
+            e = Expr::Or(Box::new(e), Box::new(self.and()?));
+        }
+        Ok(e)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut e = self.not()?;
+        while self.eat("&&", Some("and")) {
+            e = Expr::And(Box::new(e), Box::new(self.not()?));
+        }
+        Ok(e)
+    }
+
+    fn not(&mut self) -> Result<Expr> {
+        if self.eat("!", Some("not")) {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr> {
+        let left = self.sum()?;
+        let op = match self.peek() {
+            Some(Token::Op("==")) => BinOp::Eq,
+            Some(Token::Op("!=")) => BinOp::Ne,
+            Some(Token::Op("<")) => BinOp::Lt,
+            Some(Token::Op("<=")) => BinOp::Le,
+            Some(Token::Op(">")) => BinOp::Gt,
+            Some(Token::Op(">=")) => BinOp::Ge,
+            Some(Token::Op(op @ ("=~" | "!~"))) => {
+                let negate = *op == "!~";
+                self.at += 1;
+                let re = self.regex()?;
+                return Ok(Expr::Matches(Box::new(left), re, negate));
+            }
+            _ => return Ok(left),
+        };
+        self.at += 1;
+        Ok(Expr::Binary(Box::new(left), op, Box::new(self.sum()?)))
+    }
+
+    fn sum(&mut self) -> Result<Expr> {
+        let mut e = self.term()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Op("+")) => BinOp::Add,
+                Some(Token::Op("-")) => BinOp::Sub,
+                _ => return Ok(e),
+            };
+            self.at += 1;
+            e = Expr::Binary(Box::new(e), op, Box::new(self.term()?));
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr> {
+        let mut e = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Op("*")) => BinOp::Mul,
+                Some(Token::Op("/")) => BinOp::Div,
+                Some(Token::Op("%")) => BinOp::Rem,
+                _ => return Ok(e),
+            };
+            self.at += 1;
+            e = Expr::Binary(Box::new(e), op, Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.eat("-", None) {
+            return Ok(match self.unary()? {
+                Expr::Literal(Value::Number(n)) => match number(&Value::Number(n)) {
+                    Some(Num::Int(i)) => Expr::Literal(Value::from(-i)),
+                    Some(Num::Float(f)) => Expr::Literal(float(-f)),
+                    None => Expr::Literal(Value::Null),
+                },
+                e => Expr::Neg(Box::new(e)),
+            });
+        }
+        self.postfix()
+    }
+
+    fn postfix(&mut self) -> Result<Expr> {
+        let mut e = self.primary()?;
+        while self.eat("[", None) {
+            let start = match self.peek() {
+                Some(Token::Op(":")) => None,
+                _ => Some(Box::new(self.expr()?)),
+            };
+            if !self.eat(":", None) {
+                self.expect("]")?;
+                let i = start.context("empty index")?;
+                e = Expr::Index(Box::new(e), i);
+                continue;
+            }
+            let end = match self.peek() {
+                Some(Token::Op("]")) => None,
+                _ => Some(Box::new(self.expr()?)),
+            };
+            self.expect("]")?;
+            e = Expr::Slice(Box::new(e), start, end);
+        }
+        Ok(e)
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        if self.eat("(", None) {
+            let e = self.expr()?;
+            self.expect(")")?;
+            return Ok(e);
+        }
+        let Some(tok) = self.peek().cloned() else {
+            bail!("expected a field or value at the end");
+        };
+        let e = match tok {
+            Token::Str(s) => Expr::Literal(Value::String(s)),
+            Token::Num(n) => Expr::Literal(Value::Number(n)),
+            Token::Field(f) => Expr::Field(f),
+            Token::Ident(w) => match w.as_str() {
+                "true" => Expr::Literal(Value::Bool(true)),
+                "false" => Expr::Literal(Value::Bool(false)),
+                "null" => Expr::Literal(Value::Null),
+                _ if is_keyword(&w) => bail!("expected a field or value {}", self.position()),
+                _ if self.peek_at(1) == Some(&Token::Op("(")) => {
+                    self.at += 2;
+                    return self.call(&w);
+                }
+                _ => Expr::Field(w),
+            },
+            Token::Op(_) => bail!("expected a field or value {}", self.position()),
+        };
+        self.at += 1;
+        Ok(e)
+    }
+
+    /// Arguments of function `name`, after its `(`.
+    fn call(&mut self, name: &str) -> Result<Expr> {
+        let (arity, func) = match name {
+            "lower" => (1, Func::Lower),
+            "upper" => (1, Func::Upper),
+            "trim" => (1, Func::Trim),
+            "len" => (1, Func::Len),
+            "str" => (1, Func::Str),
+            "int" => (1, Func::Int),
+            "float" => (1, Func::Float),
+            "split" => (2, Func::Split),
+            "contains" => (2, Func::Contains),
+            "starts_with" => (2, Func::StartsWith),
+            "ends_with" => (2, Func::EndsWith),
+            "coalesce" => (0, Func::Coalesce),
+            "replace" => {
+                let s = self.expr()?;
+                self.expect(",")?;
+                let re = self.regex()?;
+                self.expect(",")?;
+                let with = self.expr()?;
+                self.expect(")")?;
+                return Ok(Expr::Call(Func::Replace(re), vec![s, with]));
+            }
+            _ => bail!("unknown function {name:?}"),
+        };
+        let mut args = Vec::new();
+        if !self.eat(")", None) {
+            loop {
+                args.push(self.expr()?);
+                if self.eat(")", None) {
+                    break;
+                }
+                self.expect(",")?;
+            }
+        }
+        match arity {
+            0 if args.is_empty() => bail!("{name}() takes at least 1 argument"),
+            0 => {}
+            1 if args.len() != 1 => bail!("{name}() takes 1 argument, got {}", args.len()),
+            n if args.len() != n => bail!("{name}() takes {n} arguments, got {}", args.len()),
+            _ => {}
+        }
+        Ok(Expr::Call(func, args))
+    }
+
+    /// A regex given as a string literal, compiled now.
+    fn regex(&mut self) -> Result<Regex> {
+        let Some((Token::Str(pattern), col)) = self.tokens.get(self.at).cloned() else {
+            bail!("expected a regex string {}", self.position());
+        };
+        self.at += 1;
+        Regex::new(&pattern).with_context(|| format!("bad regex at column {col}"))
+    }
+}
+
+fn is_keyword(w: &str) -> bool {
+    matches!(w, "and" | "or" | "not")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn eval(expr: &str, rec: &Value) -> Value {
+        let mut p = ExprParser::new(expr).unwrap();
+        let e = p.expr().unwrap_or_else(|e| panic!("{expr}: {e:#}"));
+        p.finish().unwrap();
+        e.eval(rec.as_object().unwrap())
+    }
+
+    #[test]
+    fn computes_values() {
+        let rec = json!({
+            "status": 503, "bytes": "1024", "dur": 0.25,
+            "path": "/API/Users", "ua": "curl/8.4.0", "tags": ["a", "b", "c"],
+        });
+        for (expr, want) in [
+            ("status / 100", json!(5)),
+            ("status % 100 + 1", json!(4)),
+            ("bytes * 2 - -1", json!(2049)),
+            ("dur * 1000", json!(250.0)),
+            ("-status", json!(-503)),
+            ("status / 0", Value::Null),
+            ("missing + 1", Value::Null),
+            (r#""s" + status"#, json!("s503")),
+            ("lower(path)[1:4]", json!("api")),
+            ("path[-5:]", json!("Users")),
+            ("path[0]", json!("/")),
+            ("tags[-1]", json!("c")),
+            ("tags[1:]", json!(["b", "c"])),
+            ("split(ua, '/')[1]", json!("8.4.0")),
+            ("int(split(split(ua, '/')[1], '.')[0])", json!(8)),
+            ("int('8.4.0')", Value::Null),
+            ("len(tags) + len(path)", json!(13)),
+            (r#"replace(path, "(?i)/api", "/v1")"#, json!("/v1/Users")),
+            ("upper(trim(' x '))", json!("X")),
+            ("coalesce(missing, null, status)", json!(503)),
+            (
+                "contains(tags, 'b') && starts_with(ua, 'curl')",
+                json!(true),
+            ),
+            ("str(status) + '!'", json!("503!")),
+            ("float(bytes) / 4", json!(256.0)),
+            ("(status + 1) * 2", json!(1008)),
+        ] {
+            assert_eq!(eval(expr, &rec), want, "{expr}");
+        }
+    }
+
+    #[test]
+    fn reports_errors() {
+        for (expr, msg) in [
+            ("lower(a, b)", "takes 1 argument, got 2"),
+            ("nope(a)", "unknown function"),
+            ("replace(a, b, c)", "regex string"),
+            ("a[", "at the end"),
+            ("`a", "unterminated"),
+        ] {
+            let err = ExprParser::new(expr)
+                .and_then(|mut p| p.expr())
+                .unwrap_err();
+            let err = format!("{err:#}");
+            assert!(err.contains(msg), "{expr}: {err}");
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! `--where`: a small expression language evaluated on each parsed record,
+//! so only matching records are written.
+//!
+//! ```text
+//! status >= 500 && path != "/healthz"
+//! method == "POST" || (user_agent =~ "(?i)curl" && !ioc_match)
+//! ```
+//!
+//! See [`crate::expr`] for the operators and functions. A record is kept
+//! when the expression is true: anything but `null`, `false`, `0` or `""`,
+//! so a bare field (`ioc_match`) tests that it is set.
+
+use anyhow::Result;
+use serde_json::{Map, Value};
+
+use crate::expr::{truthy, Expr, ExprParser};
+
+/// A compiled `--where` expression.
+#[derive(Debug)]
+pub struct Filter {
+    expr: Expr,
+}
+
+impl Filter {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut p = ExprParser::new(text)?;
+        let expr = p.expr()?;
+        p.finish()?;
+        Ok(Self { expr })
+    }
+
+    /// Records both `self` and `other` keep.
+    pub fn and(self, other: Filter) -> Self {
+        Self {
+            expr: Expr::And(Box::new(self.expr), Box::new(other.expr)),
+        }
+    }
+
+    pub fn matches(&self, rec: &Map<String, Value>) -> bool {
+        truthy(&self.expr.eval(rec))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn check(expr: &str, rec: &Value) -> bool {
+        Filter::parse(expr)
+            .unwrap_or_else(|e| panic!("{expr}: {e:#}"))
+            .matches(rec.as_object().unwrap())
+    }
+
+    #[test]
+    fn evaluates_records() {
+        let rec = json!({
+            "status": 503, "path": "/api/users", "method": "GET",
+            "bytes": "1024", "ts": "2024-01-02T03:04:05Z", "user": null,
+            "ip_geo": {"country_code": "NL"}, "id.orig_h": "10.0.0.1",
+        });
+        for (expr, want) in [
+            (r#"status >= 500 && path != "/healthz""#, true),
+            (r#"status >= 500 && path != "/api/users""#, false),
+            (
+                "status == 503 and not (method == 'POST' or method == 'PUT')",
+                true,
+            ),
+            ("bytes > 1000 && bytes < 2000.5", true),
+            (r#"ts >= "2024-01-02" && ts < "2024-01-03""#, true),
+            (r#"path =~ "^/api/" && method !~ "(?i)post""#, true),
+            ("user || missing", false),
+            ("user == null && missing == null && path != null", true),
+            (
+                r#"ip_geo.country_code == "NL" && id.orig_h == '10.0.0.1'"#,
+                true,
+            ),
+            ("status < -1 || !status", false),
+        ] {
+            assert_eq!(check(expr, &rec), want, "{expr}");
+        }
+    }
+
+    #[test]
+    fn reports_syntax_errors() {
+        for (expr, msg) in [
+            ("status >=", "at the end"),
+            ("(status > 1", "expected \")\""),
+            ("status > 1 path", "unexpected \"path\" at column 12"),
+            ("path =~ 5", "regex string"),
+            (r#"path =~ "(""#, "bad regex"),
+            ("status = 5", "unexpected \"=\" at column 8"),
+            (r#"path == "/x"#, "unterminated"),
+        ] {
+            let err = format!("{:#}", Filter::parse(expr).unwrap_err());
+            assert!(err.contains(msg), "{expr}: {err}");
+        }
+    }
+}
@@ -0,0 +1,59 @@
+//! TurboLP parsing engine.
+//!
+//! The CLI is a thin wrapper around this crate: pick a [`Parser`] (built-in
+//! or your own), open a [`Sink`], and hand both to [`run_streaming_parallel`].
+//!
+//! Sinks report what they wrote through the `log` crate; install any logger
+//! to see those lines, or [`logging::init`] for the CLI's stderr format.
+//!
+//! ```no_run
+//! use turbolp::{open_sink, run_streaming_parallel, Registry, RunOptions, SinkOptions};
+//! use std::path::Path;
+//!
+//! let registry = Registry::with_builtin();
+//! let parser = registry.create("web-access").unwrap();
+//! let sink = open_sink(Some(Path::new("out.jsonl")), &SinkOptions::default()).unwrap();
+//! let stats =
+//!     run_streaming_parallel(parser.as_ref(), Path::new("access.log"), sink, &RunOptions::default())
+//!         .unwrap();
+//! println!("{} records", stats.records);
+//! ```
+
+mod bgzf;
+pub mod checkpoint;
+pub mod config;
+pub mod core;
+pub mod encoding;
+pub mod enrich;
+pub mod expr;
+pub mod filter;
+pub mod logging;
+pub mod modules;
+pub mod progress;
+pub mod projection;
+pub mod report;
+pub mod schema;
+pub mod sink;
+pub mod stats;
+pub mod transform;
+
+pub use crate::core::{
+    collect_input_files, configure_parser, count_lines_any, is_gzip, is_stdin,
+    open_maybe_gz_bufread, open_maybe_gz_read, registry, run_streaming_parallel, sample_lines,
+    validate, BlockJoiner, Boundary, Chain, Checkpoints, FieldType, GroupJoiner, InputFormat,
+    LineJoiner, LinePos, ModuleOptions, ModuleScore, Multiline, OnError, OptionSpec, Parser,
+    ParserFactory, Registry, RejectsWriter, RunOptions, RunStats, StopSignal, ValidateReport,
+    STDIN_PATH,
+};
+pub use crate::encoding::Encoding;
+pub use crate::enrich::{
+    Enriched, Enricher, FieldTags, GeoIp, Ioc, Lookup, Networks, Sigma, UserAgent,
+};
+pub use crate::filter::Filter;
+pub use crate::projection::Projection;
+pub use crate::schema::Schema;
+pub use crate::sink::{
+    open_sink, ElasticAuth, ElasticOptions, HecOptions, KafkaCompression, KafkaOptions,
+    OutputCompression, OutputFormat, RemoteOptions, RemoteSink, ShardLimits, Sink, SinkOptions,
+};
+pub use crate::transform::Transform;
@@ -0,0 +1,66 @@
+//! The `[INFO] ...` status lines, written to stderr through the `log`
+//! facade so stdout carries only records (or a subcommand's report).
+
+use std::io::Write;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use crate::progress;
+
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let mut err = std::io::stderr().lock();
+        // Take the progress bar's line; it is redrawn on its next tick.
+        let clear = if progress::is_drawn() { "\r\x1b[K" } else { "" };
+        let _ = writeln!(err, "{clear}[{}] {}", record.level(), record.args());
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stderr().flush();
+    }
+}
+
+/// The level for `--quiet` / `-v` / `-vv`: warnings only, info (the
+/// default), debug, trace.
+pub fn level(quiet: bool, verbose: u8) -> LevelFilter {
+    match (quiet, verbose) {
+        (true, _) => LevelFilter::Warn,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    }
+}
+
+/// Install the stderr logger at `level`. Later calls only change the level.
+pub fn init(level: LevelFilter) {
+    let _ = log::set_logger(&StderrLogger);
+    log::set_max_level(level);
+}
+
+/// Whether `level` would be written.
+pub fn enabled(level: Level) -> bool {
+    level <= log::max_level()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_flags_to_levels() {
+        assert_eq!(level(false, 0), LevelFilter::Info);
+        assert_eq!(level(true, 0), LevelFilter::Warn);
+        assert_eq!(level(true, 2), LevelFilter::Warn);
+        assert_eq!(level(false, 1), LevelFilter::Debug);
+        assert_eq!(level(false, 5), LevelFilter::Trace);
+    }
+}
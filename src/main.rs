@@ -1,48 +1,100 @@
-mod core;
-mod modules;
-
-use crate::core::{count_lines_any, format_size, registry, run_streaming_parallel, Parser};
 use anyhow::{bail, Context, Result};
-use clap::{Parser as ClapParser, Subcommand};
-use once_cell::sync::Lazy;
+use clap::{Args, Parser as ClapParser, Subcommand};
 use std::{
     ffi::OsString,
     fs::File,
-    io::{self, Write},
+    io::BufWriter,
     path::{Path, PathBuf},
-    time::Instant,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use turbolp::checkpoint::Checkpoint;
+use turbolp::config::RunConfig;
+use turbolp::core::{
+    collect_input_files, count_lines_any, format_size, is_gzip, is_stdin, run_streaming_per_worker,
+    sample_lines, validate, Checkpoints, InputFormat, ModuleOptions, Multiline, OnError, Parser,
+    Registry, RejectsWriter, RunOptions, RunStats, StopSignal, STDIN_PATH,
+};
+use turbolp::encoding::{self, Encoding};
+use turbolp::enrich::{
+    Enriched, Enricher, FieldTags, GeoIp, Ioc, Lookup, Networks, Sigma, UserAgent,
 };
+use turbolp::filter::Filter;
+use turbolp::logging;
+use turbolp::progress::Progress;
+use turbolp::projection::Projection;
+use turbolp::report::{InputReport, OutputFile, OutputReport, RunReport};
+use turbolp::schema::Schema;
+use turbolp::sink::{
+    open_sink, output_files, parse_size, ElasticAuth, ElasticOptions, HecOptions, KafkaCompression,
+    KafkaOptions, OutputCompression, OutputFormat, RemoteOptions, RemoteSink, ShardLimits,
+    SinkOptions,
+};
+use turbolp::stats::{self, parse_bucket, StatsSpec};
+use turbolp::transform::Transform;
 
 #[derive(ClapParser, Debug)]
-#[command(name = "minimal-parser", version, about = "Modular file parser (multithreaded only)")]
+#[command(
+    name = "minimal-parser",
+    version,
+    about = "Modular file parser (multithreaded only)"
+)]
 struct Cli {
     #[command(subcommand)]
     cmd: Command,
+
+    /// Only print warnings and errors on stderr (also hides the progress bar).
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// More detail on stderr: `-v` adds debug lines, `-vv` trace lines.
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
 }
 
 #[derive(Subcommand, Debug)]
 enum Command {
     /// Run a specific module on an input file (multithreaded).
-    Run {
-        /// Module name (see `list`).
-        #[arg(long)]
-        module: String,
+    Run(Box<RunArgs>),
+
+    /// List available modules and their descriptions.
+    List,
 
-        /// Input file path.
+    /// Guess which module fits an input by trying every module on its first lines.
+    Detect {
+        /// Input file path (`-` for stdin). Gzip is handled transparently.
         #[arg(long)]
         input: PathBuf,
 
-        /// Output file path. If omitted, JSONL is written to stdout.
+        /// Number of non-blank lines to sample.
+        #[arg(long, default_value_t = 1000)]
+        lines: usize,
+    },
+
+    /// Dry run: parse a whole input with a module and report how well it fits,
+    /// without writing any output.
+    Validate {
+        /// Module name (see `list`) or `,`-separated chain.
         #[arg(long)]
-        output: Option<PathBuf>,
+        module: String,
 
-        /// Prefix the output filename with a short deterministic hash of the input path.
-        ///
-        /// Example:
-        ///   --input /a/bodyfile.txt --output /out/bodyfile.jsonl --prefix-input-hash
-        ///   => /out/1a2b3c4d-bodyfile.jsonl
+        /// Input file path (`-` for stdin). Gzip is handled transparently.
         #[arg(long)]
-        prefix_input_hash: bool,
+        input: PathBuf,
+
+        /// Module option as key=value (repeatable).
+        #[arg(long = "opt", value_name = "KEY=VALUE")]
+        opts: Vec<String>,
+
+        /// Regex matching the first line of each record; following lines
+        /// are joined to it (see `run --multiline-start`).
+        #[arg(long, value_name = "REGEX")]
+        multiline_start: Option<String>,
+
+        /// Number of failing lines to show.
+        #[arg(long, default_value_t = 5)]
+        examples: usize,
 
         /// Number of worker threads.
         ///
@@ -51,44 +103,1166 @@ enum Command {
         workers: Option<usize>,
     },
 
-    /// List available modules and their descriptions.
-    List,
+    /// Parse an input with a module and print counts instead of records:
+    /// top values (`--by`), counts per time bucket (`--bucket`) and numeric
+    /// min/max/avg (`--num`).
+    Stats(Box<StatsArgs>),
 }
 
-static PARSERS: Lazy<Vec<Box<dyn Parser>>> = Lazy::new(|| registry().iter().map(|f| f()).collect());
+#[derive(Args, Debug)]
+struct StatsArgs {
+    /// Module name (see `list`) or `,`-separated chain.
+    #[arg(long)]
+    module: String,
+
+    /// Input file path (`-` for stdin). Gzip is handled transparently.
+    #[arg(long)]
+    input: PathBuf,
+
+    /// Module option as key=value (repeatable).
+    #[arg(long = "opt", value_name = "KEY=VALUE")]
+    opts: Vec<String>,
+
+    /// Regex matching the first line of each record (see `run --multiline-start`).
+    #[arg(long, value_name = "REGEX")]
+    multiline_start: Option<String>,
+
+    /// Only count records matching this expression (see `run --where`).
+    #[arg(long = "where", value_name = "EXPR")]
+    filter: Option<String>,
+
+    /// Count records per value of this field; repeat (or separate with
+    /// commas) to count value combinations. Dotted names reach into nested objects.
+    #[arg(long, value_delimiter = ',', value_name = "FIELD")]
+    by: Vec<String>,
+
+    /// Number of `--by` groups shown, most frequent first.
+    #[arg(long, default_value_t = 20)]
+    top: usize,
+
+    /// Count records per time bucket of this width: `30s`, `5m`, `1h`, `1d`.
+    #[arg(long, value_parser = parse_bucket, value_name = "WIDTH")]
+    bucket: Option<i64>,
+
+    /// Field holding the record time for `--bucket` (RFC 3339 or epoch).
+    ///
+    /// Default: the first of @timestamp, ts, time, start.
+    #[arg(long, value_name = "FIELD")]
+    time_field: Option<String>,
+
+    /// Numeric field to summarize with count, min, max and average (repeatable).
+    #[arg(long, value_delimiter = ',', value_name = "FIELD")]
+    num: Vec<String>,
+
+    /// Number of worker threads.
+    ///
+    /// Default: num_cpus::get()
+    #[arg(long)]
+    workers: Option<usize>,
+}
+
+#[derive(Args, Debug)]
+struct RunArgs {
+    /// TOML file describing the run. Flags given on the command line win
+    /// over the file's values.
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Module name (see `list`), or a `,`-separated chain such as
+    /// `docker-json,web-access` where each module parses the message the
+    /// previous one extracted.
+    #[arg(long, required_unless_present = "config")]
+    module: Option<String>,
+
+    /// Input file path. `-` (or omitting both `--input` and `--input-dir`) reads stdin.
+    #[arg(long, conflicts_with = "input_dir")]
+    input: Option<PathBuf>,
+
+    /// Input directory. Every matching file is parsed in turn.
+    ///
+    /// With `--output`, the output path is treated as a directory and
+    /// mirrors the input tree: <input-dir>/a/access.log.gz => <output>/a/access.log.gz.jsonl
+    #[arg(long)]
+    input_dir: Option<PathBuf>,
+
+    /// Descend into subdirectories of `--input-dir`.
+    #[arg(long)]
+    recursive: bool,
+
+    /// Comma-separated file extensions to keep in `--input-dir` mode (e.g. `log,gz`).
+    ///
+    /// Default: every regular file.
+    #[arg(long, value_delimiter = ',')]
+    ext: Vec<String>,
+
+    /// Module option as key=value (repeatable). See `list` for each module's options.
+    #[arg(long = "opt", value_name = "KEY=VALUE")]
+    opts: Vec<String>,
+
+    /// Regex matching the first line of each record. Lines that don't match
+    /// (stack traces, wrapped statements, ...) are appended to the record
+    /// before them, newline-joined, and the module parses the whole record.
+    /// Overrides the module's own multi-line handling.
+    ///
+    /// Example: --multiline-start '^\d{4}-\d{2}-\d{2} '
+    #[arg(long, value_name = "REGEX")]
+    multiline_start: Option<String>,
+
+    /// MaxMind database (`.mmdb`: GeoLite2-City, GeoLite2-ASN, ...) to look
+    /// the record's IP fields up in (repeatable). Adds `<field>_geo` with
+    /// country, region, city, lat/lon and ASN, where known.
+    #[arg(long, value_name = "MMDB")]
+    geoip: Vec<PathBuf>,
+
+    /// Record field holding an IP address, in addition to the ones the
+    /// module tags (repeatable). Dotted names reach into nested objects,
+    /// e.g. `parsed.client`.
+    #[arg(long, value_name = "FIELD")]
+    ip_field: Vec<String>,
+
+    /// Parse the record's `User-Agent` fields into `ua` (browser and
+    /// version, OS and version, device class, bot flag).
+    #[arg(long)]
+    parse_ua: bool,
+
+    /// Record field holding a `User-Agent`, in addition to the ones the
+    /// module tags (repeatable).
+    #[arg(long, value_name = "FIELD")]
+    ua_field: Vec<String>,
+
+    /// Threat-intel indicator file (repeatable): one IP, domain or
+    /// MD5/SHA-1/SHA-256 hash per line, optionally followed by a label.
+    /// Records with any string value matching one get `ioc_match: true` and
+    /// the hits under `ioc`.
+    #[arg(long, value_name = "FILE")]
+    ioc: Vec<PathBuf>,
+
+    /// Sigma rule (`.yml`) or directory of rules (repeatable), matched
+    /// against every record. Records matching any rule get
+    /// `sigma_match: true` and the rules' `id`, `title` and `level` under
+    /// `sigma`. Supported for web-access, authlog, winevt-xml and okta records.
+    #[arg(long, value_name = "PATH")]
+    sigma: Vec<PathBuf>,
+
+    /// With --sigma, write only the records matching a rule.
+    #[arg(long)]
+    sigma_only: bool,
+
+    /// Network ranges file (repeatable): one `CIDR=label` per line, e.g.
+    /// `10.0.0.0/8=internal`. Adds `<field>_net` next to each IP field with
+    /// the labels of the ranges holding it, most specific first.
+    #[arg(long, value_name = "FILE")]
+    networks: Vec<PathBuf>,
+
+    /// Join a CSV dictionary onto the records (repeatable): the row whose
+    /// `key_column` (default: the first column) equals the record's `field`
+    /// adds its other columns as `<field>_<column>`.
+    ///
+    /// Example: --lookup user=users.csv:username
+    #[arg(long, value_name = "FIELD=CSV:KEY_COLUMN")]
+    lookup: Vec<String>,
+
+    /// Reshape each record (repeatable, run in order): `field = expr`,
+    /// `rename old -> new` and `del field, ...` statements separated by `;`.
+    /// Expressions are those of `--where`, plus arithmetic, slicing
+    /// (`path[0:4]`) and functions like `lower(...)`.
+    ///
+    /// Example: --transform 'status_class = status / 100; del raw'
+    #[arg(long, value_name = "STATEMENTS")]
+    transform: Vec<String>,
+
+    /// Only write records matching this expression, tested after
+    /// enrichment and `--transform`. Compare fields with `==`, `!=`, `<`, `<=`, `>`, `>=`,
+    /// match regexes with `=~` / `!~`, combine with `&&`, `||`, `!` and
+    /// parentheses.
+    ///
+    /// Example: --where 'status >= 500 && path != "/healthz"'
+    #[arg(long = "where", value_name = "EXPR")]
+    filter: Option<String>,
+
+    /// Comma-separated fields to keep, in this order; everything else is
+    /// dropped. Dotted names keep one key of a nested object.
+    ///
+    /// Example: --fields ip,ts,status,path
+    #[arg(long, value_delimiter = ',', value_name = "FIELDS")]
+    fields: Vec<String>,
+
+    /// Comma-separated fields to drop, e.g. `--exclude raw`. Applied after
+    /// `--fields`.
+    #[arg(long, value_delimiter = ',', value_name = "FIELDS")]
+    exclude: Vec<String>,
+
+    /// Rename fields to a standard schema after enrichment: `ecs` for
+    /// `source.ip`, `url.path`, `@timestamp` and `event.*`, `ocsf` for OCSF
+    /// classes with `class_uid`, `activity_id` and `metadata`.
+    /// `--transform`, `--where` and `--fields` then use the schema's names.
+    #[arg(long, value_enum)]
+    schema: Option<Schema>,
+
+    /// Output file path. If omitted, JSONL is written to stdout.
+    #[arg(long)]
+    output: Option<PathBuf>,
+
+    /// Output layout. `sqlite` inserts records into a table named after the
+    /// module (columns from the records' fields) in the `--output` database;
+    /// with `--input-dir`, every file goes to that one database. `parquet`
+    /// writes a Parquet file with one column per field, and `arrow` an Arrow
+    /// IPC stream of the same columns (to stdout without `--output`). `csv`
+    /// and `tsv` write a header row and one row per record, the columns in
+    /// `--fields` order when given.
+    #[arg(long, value_enum)]
+    format: Option<OutputFormat>,
+
+    /// Prefix the output filename with a short deterministic hash of the input path.
+    ///
+    /// Example:
+    ///   --input /a/bodyfile.txt --output /out/bodyfile.jsonl --prefix-input-hash
+    ///   => /out/1a2b3c4d-bodyfile.jsonl
+    #[arg(long)]
+    prefix_input_hash: bool,
+
+    /// Compress the JSONL output inline. The matching suffix (`.gz` / `.zst`)
+    /// is appended to `--output` when missing.
+    #[arg(long, value_enum)]
+    output_compress: Option<OutputCompression>,
+
+    /// Roll the output into numbered shards (`out.0001.jsonl`, ...) once a
+    /// shard reaches this many uncompressed bytes. Accepts K/M/G/T suffixes.
+    #[arg(long, value_parser = parse_size)]
+    max_output_size: Option<u64>,
+
+    /// Roll the output into numbered shards once a shard holds this many records.
+    #[arg(long)]
+    max_output_records: Option<u64>,
+
+    /// Write Elasticsearch/OpenSearch `_bulk` lines: a `create` action for
+    /// this index before each record. `%Y`, `%m` and `%d` are filled from
+    /// the record's date (`@timestamp`, `ts`, `time`, ...), or today's.
+    ///
+    /// Example: --es-index 'weblogs-%Y.%m.%d'
+    #[arg(long, value_name = "INDEX")]
+    es_index: Option<String>,
+
+    /// Send the `_bulk` lines to this cluster in batches instead of writing
+    /// them, retrying with backoff when it pushes back (429, 5xx).
+    #[arg(long, value_name = "URL")]
+    es_url: Option<String>,
+
+    /// API key for --es-url (the base64 form Elasticsearch hands out).
+    #[arg(long, value_name = "KEY")]
+    es_api_key: Option<String>,
+
+    /// Basic-auth credentials for --es-url.
+    #[arg(long, value_name = "USER:PASSWORD")]
+    es_user: Option<String>,
+
+    /// Bulk request body size to send at. Accepts K/M/G suffixes.
+    /// [default: 5M]
+    #[arg(long, value_parser = parse_size)]
+    es_batch_size: Option<u64>,
+
+    /// Send records to a service instead of writing them: `splunk-hec`
+    /// posts batches of events to --hec-url with --hec-token, `kafka`
+    /// produces them to --kafka-topic on --kafka-brokers.
+    #[arg(long, value_enum)]
+    sink: Option<RemoteSink>,
+
+    /// Splunk HTTP Event Collector base URL, e.g. `https://splunk:8088`.
+    #[arg(long, value_name = "URL")]
+    hec_url: Option<String>,
+
+    /// HEC token.
+    #[arg(long, value_name = "TOKEN")]
+    hec_token: Option<String>,
+
+    /// Sourcetype of the events. [default: _json]
+    #[arg(long, value_name = "SOURCETYPE")]
+    hec_sourcetype: Option<String>,
+
+    /// Index for the events (default: the token's).
+    #[arg(long, value_name = "INDEX")]
+    hec_index: Option<String>,
+
+    /// `source` of the events (default: whatever the token sets).
+    #[arg(long, value_name = "SOURCE")]
+    hec_source: Option<String>,
+
+    /// HEC request body size to send at. Accepts K/M/G suffixes.
+    /// [default: 1M]
+    #[arg(long, value_parser = parse_size)]
+    hec_batch_size: Option<u64>,
+
+    /// Comma-separated Kafka bootstrap brokers, `host:port`.
+    #[arg(long, value_delimiter = ',', value_name = "HOSTS")]
+    kafka_brokers: Vec<String>,
+
+    /// Kafka topic to produce to.
+    #[arg(long, value_name = "TOPIC")]
+    kafka_topic: Option<String>,
+
+    /// Record field keying each message (dotted for nested), so records
+    /// with the same value land in the same partition. Without it,
+    /// messages are spread over the partitions.
+    #[arg(long, value_name = "FIELD")]
+    kafka_key: Option<String>,
+
+    /// Compress Kafka message sets.
+    #[arg(long, value_enum)]
+    kafka_compression: Option<KafkaCompression>,
+
+    /// Bytes of records per Kafka produce request. Accepts K/M/G suffixes.
+    /// [default: 512K]
+    #[arg(long, value_parser = parse_size)]
+    kafka_batch_size: Option<u64>,
+
+    /// Write every line the module could not turn into a record to this
+    /// file, verbatim, one per line. Blank lines are not included.
+    #[arg(long)]
+    rejects: Option<PathBuf>,
+
+    /// What to do with a line the module cannot parse: `skip` it (the
+    /// default; it still counts as rejected), emit it as a `raw`
+    /// `{"unparsed":true,"raw":"..."}` record, or `abort` the run on the
+    /// first one (an `unparsed` fallback record counts too).
+    #[arg(long, value_enum)]
+    on_error: Option<OnError>,
+
+    /// Keep output records in input line order (default: whatever order
+    /// workers finish in).
+    #[arg(long)]
+    ordered: bool,
+
+    /// Keep the input open and stream appended lines as they arrive
+    /// (like `tail -f`). Output is flushed at least once per second.
+    /// Stop with Ctrl-C.
+    #[arg(long)]
+    follow: bool,
+
+    /// Write a JSON summary of the run here: each input's size, line and
+    /// record counts, per-worker counts, duration and output files. Also
+    /// written when the run fails, with the error.
+    #[arg(long, value_name = "PATH")]
+    report: Option<PathBuf>,
+
+    /// Record how far the run got in this JSON file every few seconds, so an
+    /// interrupted run can be continued with `--resume`. Needs one `--input`
+    /// file and a plain JSONL `--output`; records are kept in input order.
+    #[arg(long, value_name = "PATH")]
+    checkpoint: Option<PathBuf>,
+
+    /// Continue from the `--checkpoint` file instead of starting over. Starts
+    /// from the beginning when there is no checkpoint yet.
+    #[arg(long)]
+    resume: bool,
+
+    /// Do not draw the progress bar (drawn on stderr when it is a terminal).
+    #[arg(long)]
+    no_progress: bool,
+
+    /// Read uncompressed inputs with plain reads instead of mapping them
+    /// into memory. Use it for files that may be truncated during the run.
+    #[arg(long)]
+    no_mmap: bool,
+
+    /// Text encoding of the inputs. `auto` (the default) follows a byte
+    /// order mark, recognizes UTF-16 without one, and otherwise reads UTF-8
+    /// with invalid bytes replaced by U+FFFD. `utf8` rejects lines that are
+    /// not valid UTF-8 instead.
+    #[arg(long, value_enum)]
+    encoding: Option<Encoding>,
+
+    /// Add `_src_file`, `_src_line` and `_src_offset` to every record: the
+    /// input path, and the line (1-based) and byte offset the record was
+    /// parsed from. A record joined from several lines points at the first.
+    #[arg(long)]
+    provenance: bool,
+
+    /// Number of worker threads.
+    ///
+    /// Default: num_cpus::get()
+    #[arg(long)]
+    workers: Option<usize>,
+}
+
+impl RunArgs {
+    /// Fill everything not given on the command line from `cfg`.
+    fn merge_config(&mut self, cfg: RunConfig) -> Result<()> {
+        // Config options go first so a repeated `--opt` on the CLI wins.
+        let mut opts = cfg.module_options()?;
+        opts.append(&mut self.opts);
+        self.opts = opts;
+
+        // An input given on the CLI replaces the config's input source entirely.
+        if self.input.is_none() && self.input_dir.is_none() {
+            self.input = cfg.input;
+            self.input_dir = cfg.input_dir;
+        }
+
+        self.module = self.module.take().or(cfg.module);
+        self.multiline_start = self.multiline_start.take().or(cfg.multiline_start);
+        if self.geoip.is_empty() {
+            self.geoip = cfg.geoip;
+        }
+        if self.ip_field.is_empty() {
+            self.ip_field = cfg.ip_field;
+        }
+        self.parse_ua |= cfg.parse_ua;
+        if self.ua_field.is_empty() {
+            self.ua_field = cfg.ua_field;
+        }
+        if self.ioc.is_empty() {
+            self.ioc = cfg.ioc;
+        }
+        if self.sigma.is_empty() {
+            self.sigma = cfg.sigma;
+        }
+        self.sigma_only |= cfg.sigma_only;
+        if self.networks.is_empty() {
+            self.networks = cfg.networks;
+        }
+        if self.lookup.is_empty() {
+            self.lookup = cfg.lookup;
+        }
+        if self.transform.is_empty() {
+            self.transform = cfg.transform;
+        }
+        self.filter = self.filter.take().or(cfg.filter);
+        if self.fields.is_empty() {
+            self.fields = cfg.fields;
+        }
+        if self.exclude.is_empty() {
+            self.exclude = cfg.exclude;
+        }
+        self.schema = self.schema.or(cfg.schema);
+        self.recursive |= cfg.recursive;
+        if self.ext.is_empty() {
+            self.ext = cfg.ext;
+        }
+        self.output = self.output.take().or(cfg.output);
+        self.format = self.format.or(cfg.format);
+        self.prefix_input_hash |= cfg.prefix_input_hash;
+        self.output_compress = self.output_compress.or(cfg.output_compress);
+        if self.max_output_size.is_none() {
+            self.max_output_size = cfg.max_output_size.map(|s| s.bytes()).transpose()?;
+        }
+        self.max_output_records = self.max_output_records.or(cfg.max_output_records);
+        self.es_index = self.es_index.take().or(cfg.es_index);
+        self.es_url = self.es_url.take().or(cfg.es_url);
+        self.es_api_key = self.es_api_key.take().or(cfg.es_api_key);
+        self.es_user = self.es_user.take().or(cfg.es_user);
+        if self.es_batch_size.is_none() {
+            self.es_batch_size = cfg.es_batch_size.map(|s| s.bytes()).transpose()?;
+        }
+        self.sink = self.sink.or(cfg.sink);
+        self.hec_url = self.hec_url.take().or(cfg.hec_url);
+        self.hec_token = self.hec_token.take().or(cfg.hec_token);
+        self.hec_sourcetype = self.hec_sourcetype.take().or(cfg.hec_sourcetype);
+        self.hec_index = self.hec_index.take().or(cfg.hec_index);
+        self.hec_source = self.hec_source.take().or(cfg.hec_source);
+        if self.hec_batch_size.is_none() {
+            self.hec_batch_size = cfg.hec_batch_size.map(|s| s.bytes()).transpose()?;
+        }
+        if self.kafka_brokers.is_empty() {
+            self.kafka_brokers = cfg.kafka_brokers;
+        }
+        self.kafka_topic = self.kafka_topic.take().or(cfg.kafka_topic);
+        self.kafka_key = self.kafka_key.take().or(cfg.kafka_key);
+        self.kafka_compression = self.kafka_compression.or(cfg.kafka_compression);
+        if self.kafka_batch_size.is_none() {
+            self.kafka_batch_size = cfg.kafka_batch_size.map(|s| s.bytes()).transpose()?;
+        }
+        self.rejects = self.rejects.take().or(cfg.rejects);
+        self.on_error = self.on_error.or(cfg.on_error);
+        self.ordered |= cfg.ordered;
+        self.follow |= cfg.follow;
+        self.no_progress |= cfg.no_progress;
+        self.no_mmap |= cfg.no_mmap;
+        self.encoding = self.encoding.or(cfg.encoding);
+        self.provenance |= cfg.provenance;
+        self.report = self.report.take().or(cfg.report);
+        self.checkpoint = self.checkpoint.take().or(cfg.checkpoint);
+        self.resume |= cfg.resume;
+        self.workers = self.workers.or(cfg.workers);
+        Ok(())
+    }
+
+    /// Cross-flag rules clap cannot see once a config file is involved.
+    fn validate(&self) -> Result<()> {
+        if self.input.is_some() && self.input_dir.is_some() {
+            bail!("--input and --input-dir are mutually exclusive");
+        }
+        if self.input_dir.is_none() && (self.recursive || !self.ext.is_empty()) {
+            bail!("--recursive and --ext require --input-dir");
+        }
+        if self.input_dir.is_some() && self.follow {
+            bail!("--follow cannot be used with --input-dir");
+        }
+        if self.resume && self.checkpoint.is_none() {
+            bail!("--resume requires --checkpoint");
+        }
+        Ok(())
+    }
+}
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
+    logging::init(logging::level(cli.quiet, cli.verbose));
 
     match cli.cmd {
         Command::List => {
             println!("Available modules:");
-            for p in PARSERS.iter() {
+            for p in Registry::with_builtin().parsers() {
                 println!("  {:<16} - {}", p.name(), p.description());
+                for o in p.options() {
+                    println!("      --opt {:<14} {}", format!("{}=", o.key), o.help);
+                }
             }
         }
 
-        Command::Run {
+        Command::Detect { input, lines } => {
+            let sample = sample_lines(&input, lines.max(1))?;
+            if sample.is_empty() {
+                bail!("{} has no non-blank lines to sample", input.display());
+            }
+
+            println!("Sampled {} lines from {}", sample.len(), input.display());
+            for s in Registry::with_builtin().score(&sample) {
+                println!(
+                    "  {:<16} {:>6.1}%  ({}/{})",
+                    s.module,
+                    s.rate() * 100.0,
+                    s.matched,
+                    s.sampled
+                );
+            }
+        }
+
+        Command::Validate {
             module,
             input,
-            output,
-            prefix_input_hash,
+            opts,
+            multiline_start,
+            examples,
             workers,
         } => {
-            let parser = PARSERS
-                .iter()
-                .find(|p| p.name() == module)
-                .with_context(|| format!("unknown module: {module}"))?;
+            let parser = create_parser(&module, &opts, multiline_start.as_deref())?;
+            let run_opts = RunOptions {
+                workers: workers.unwrap_or_else(num_cpus::get).max(1),
+                ..RunOptions::default()
+            };
+            let report = validate(parser.as_ref(), &input, &run_opts, examples)?;
+            let stats = &report.stats;
 
-            let final_output = resolve_output_path(&input, output, prefix_input_hash)?;
+            println!("Module: {}  |  Input: {}", parser.name(), input.display());
+            println!("Lines:  {} ({} blank)", stats.lines, stats.blank);
+            println!(
+                "Parsed: {} of {} non-blank ({:.2}%)",
+                (stats.lines - stats.blank).saturating_sub(report.failed()),
+                stats.lines - stats.blank,
+                report.success_rate() * 100.0
+            );
+            println!(
+                "Failed: {} ({} rejected, {} unparsed fallback)",
+                report.failed(),
+                stats.rejected,
+                report.fallback
+            );
+            println!(
+                "Estimated output: {} records, {} uncompressed",
+                stats.records,
+                format_size(report.output_bytes)
+            );
+            if !report.examples.is_empty() {
+                println!("Example failing lines:");
+                for line in &report.examples {
+                    println!("  {}", truncate_for_display(line, 200));
+                }
+            }
+        }
 
-            run_with_threads(parser.as_ref(), &input, final_output.as_deref(), workers)?;
+        Command::Stats(args) => run_stats(*args)?,
+
+        Command::Run(mut args) => {
+            if let Some(path) = args.config.take() {
+                log::debug!("Config file: {}", path.display());
+                args.merge_config(RunConfig::load(&path)?)?;
+            }
+            args.validate()?;
+            run(*args)?;
         }
     }
 
     Ok(())
 }
 
+fn run_stats(args: StatsArgs) -> Result<()> {
+    let StatsArgs {
+        module,
+        input,
+        opts,
+        multiline_start,
+        filter,
+        by,
+        top,
+        bucket,
+        time_field,
+        num,
+        workers,
+    } = args;
+    if by.is_empty() && bucket.is_none() && num.is_empty() {
+        bail!("nothing to compute: give --by, --bucket or --num");
+    }
+
+    let parser = create_parser(&module, &opts, multiline_start.as_deref())?;
+    let sources = EnrichSources {
+        geoip: &[],
+        parse_ua: false,
+        ioc: &[],
+        sigma: &[],
+        sigma_only: false,
+        networks: &[],
+        lookup: &[],
+        schema: None,
+        transform: &[],
+        filter: filter.as_deref(),
+        projection: &Projection::default(),
+    };
+    let parser = enrich(parser, &sources, &FieldTags::default())?;
+    let run_opts = RunOptions {
+        workers: workers.unwrap_or_else(num_cpus::get).max(1),
+        ..RunOptions::default()
+    };
+    let spec = StatsSpec {
+        by,
+        top,
+        bucket,
+        time_field,
+        numeric: num,
+    };
+    let (run, agg) = stats::collect(parser.as_ref(), &input, &run_opts, &spec)?;
+
+    println!("Module: {}  |  Input: {}", parser.name(), input.display());
+    println!(
+        "Lines:  {} ({} parsed, {} rejected, {} blank)",
+        run.lines, run.parsed, run.rejected, run.blank
+    );
+    print!("{}", agg.report(&spec));
+    Ok(())
+}
+
+/// Instantiate `module` (or chain), apply its `--opt key=value` options and
+/// wrap it for `--multiline-start`.
+fn create_parser(
+    module: &str,
+    opts: &[String],
+    multiline_start: Option<&str>,
+) -> Result<Box<dyn Parser>> {
+    let parser = Registry::with_builtin().create_configured(module, &ModuleOptions::parse(opts)?)?;
+    Ok(match multiline_start {
+        Some(start) => Box::new(Multiline::new(parser, start)?),
+        None => parser,
+    })
+}
+
+/// The enrichment, transform, filter and projection flags of a `run`.
+struct EnrichSources<'a> {
+    geoip: &'a [PathBuf],
+    parse_ua: bool,
+    ioc: &'a [PathBuf],
+    sigma: &'a [PathBuf],
+    sigma_only: bool,
+    networks: &'a [PathBuf],
+    lookup: &'a [String],
+    schema: Option<Schema>,
+    transform: &'a [String],
+    filter: Option<&'a str>,
+    projection: &'a Projection,
+}
+
+/// Wrap `parser` in the enrichment stages, transform, filter and projection
+/// asked for, if any.
+/// `fields` adds to the fields the module tags itself.
+fn enrich(
+    parser: Box<dyn Parser>,
+    sources: &EnrichSources,
+    fields: &FieldTags,
+) -> Result<Box<dyn Parser>> {
+    let EnrichSources {
+        geoip,
+        parse_ua,
+        ioc,
+        sigma,
+        sigma_only,
+        networks,
+        lookup,
+        schema,
+        transform,
+        filter,
+        projection,
+    } = *sources;
+    let mut tags = FieldTags::of(parser.as_ref());
+    tags.merge(fields);
+
+    let mut stages: Vec<Box<dyn Enricher>> = Vec::new();
+    if (!geoip.is_empty() || !networks.is_empty()) && tags.ip.is_empty() {
+        bail!(
+            "module {} tags no IP fields to look up; name them with --ip-field",
+            parser.name()
+        );
+    }
+    if !geoip.is_empty() {
+        stages.push(Box::new(GeoIp::open(geoip)?));
+    }
+    if !networks.is_empty() {
+        stages.push(Box::new(Networks::open(networks)?));
+    }
+    if parse_ua {
+        if tags.user_agent.is_empty() {
+            bail!(
+                "module {} tags no user-agent fields to parse; name them with --ua-field",
+                parser.name()
+            );
+        }
+        stages.push(Box::new(UserAgent));
+    }
+    if !ioc.is_empty() {
+        let ioc = Ioc::open(ioc)?;
+        log::info!("Loaded {} indicators", ioc.len());
+        stages.push(Box::new(ioc));
+    }
+    for spec in lookup {
+        let table = Lookup::open(spec)?;
+        log::info!("Loaded {} lookup rows from {spec}", table.len());
+        stages.push(Box::new(table));
+    }
+    if !sigma.is_empty() {
+        let rules = Sigma::open(sigma, &parser.name())?;
+        for skipped in rules.skipped() {
+            log::warn!("Skipped Sigma rule {skipped}");
+        }
+        log::info!(
+            "Loaded {} Sigma rules ({} for other log sources, {} skipped)",
+            rules.len(),
+            rules.other(),
+            rules.skipped().len()
+        );
+        stages.push(Box::new(rules));
+    } else if sigma_only {
+        bail!("--sigma-only needs --sigma");
+    }
+    if let Some(schema) = schema {
+        stages.push(schema.mapper(&parser.name())?);
+    }
+    let transform = Transform::parse_all(transform)?;
+    let mut filter = filter
+        .map(|f| Filter::parse(f).with_context(|| format!("--where {f:?}")))
+        .transpose()?;
+    if sigma_only {
+        let matched = Filter::parse("sigma_match")?;
+        filter = Some(match filter {
+            Some(f) => f.and(matched),
+            None => matched,
+        });
+    }
+    if stages.is_empty() && transform.is_empty() && filter.is_none() && projection.is_empty() {
+        return Ok(parser);
+    }
+    let mut enriched = Enriched::new(parser, stages, fields);
+    if !transform.is_empty() {
+        enriched = enriched.with_transform(transform);
+    }
+    if let Some(filter) = filter {
+        enriched = enriched.with_filter(filter);
+    }
+    if !projection.is_empty() {
+        enriched = enriched.with_projection(projection.clone());
+    }
+    Ok(Box::new(enriched))
+}
+
+/// First `max_chars` characters of `line`, with an ellipsis when cut.
+fn truncate_for_display(line: &str, max_chars: usize) -> String {
+    match line.char_indices().nth(max_chars) {
+        Some((i, _)) => format!("{}...", &line[..i]),
+        None => line.to_string(),
+    }
+}
+
+fn run(args: RunArgs) -> Result<()> {
+    let RunArgs {
+        config: _,
+        module,
+        opts: module_opts,
+        multiline_start,
+        geoip,
+        ip_field,
+        parse_ua,
+        ua_field,
+        ioc,
+        sigma,
+        sigma_only,
+        networks,
+        lookup,
+        transform,
+        filter,
+        fields: keep,
+        exclude,
+        schema,
+        input,
+        input_dir,
+        recursive,
+        ext,
+        output,
+        format,
+        prefix_input_hash,
+        output_compress,
+        max_output_size,
+        max_output_records,
+        es_index,
+        es_url,
+        es_api_key,
+        es_user,
+        es_batch_size,
+        sink,
+        hec_url,
+        hec_token,
+        hec_sourcetype,
+        hec_index,
+        hec_source,
+        hec_batch_size,
+        kafka_brokers,
+        kafka_topic,
+        kafka_key,
+        kafka_compression,
+        kafka_batch_size,
+        rejects,
+        on_error,
+        ordered,
+        follow,
+        report: report_path,
+        checkpoint,
+        resume,
+        no_progress,
+        no_mmap,
+        encoding,
+        provenance,
+        workers,
+    } = args;
+
+    let module = module.context("no module given (use --module or `module` in --config)")?;
+    let parser = create_parser(&module, &module_opts, multiline_start.as_deref())?;
+    let table = parser
+        .name()
+        .replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+    let fields = FieldTags {
+        ip: ip_field,
+        user_agent: ua_field,
+    };
+    let sources = EnrichSources {
+        geoip: &geoip,
+        parse_ua,
+        ioc: &ioc,
+        sigma: &sigma,
+        sigma_only,
+        networks: &networks,
+        lookup: &lookup,
+        schema,
+        transform: &transform,
+        filter: filter.as_deref(),
+        projection: &Projection {
+            fields: keep.clone(),
+            exclude,
+        },
+    };
+    let parser = enrich(parser, &sources, &fields)?;
+
+    // One rejects file for the whole invocation, shared by every input.
+    let rejects = rejects
+        .map(|path| -> Result<RejectsWriter> {
+            let fh = File::create(&path).with_context(|| format!("create {}", path.display()))?;
+            Ok(Arc::new(Mutex::new(BufWriter::new(fh))))
+        })
+        .transpose()?;
+
+    let opts = RunOptions {
+        workers: workers.unwrap_or_else(num_cpus::get).max(1),
+        follow,
+        ordered,
+        rejects,
+        progress: None,
+        stop: Some(on_ctrl_c()?),
+        start_offset: 0,
+        checkpoint: None,
+        mmap: !no_mmap,
+        encoding: encoding.unwrap_or_default(),
+        provenance,
+        on_error: on_error.unwrap_or_default(),
+    };
+    let auth = match (es_api_key, es_user) {
+        (Some(_), Some(_)) => bail!("give either --es-api-key or --es-user, not both"),
+        (Some(key), None) => Some(ElasticAuth::ApiKey(key)),
+        (None, Some(user)) => Some(ElasticAuth::Basic(user)),
+        (None, None) => None,
+    };
+    let elastic = match es_index {
+        Some(index) => Some(ElasticOptions {
+            index,
+            url: es_url,
+            auth,
+            batch_bytes: es_batch_size.unwrap_or(5 << 20) as usize,
+        }),
+        None if es_url.is_some() => bail!("--es-url needs --es-index"),
+        None => None,
+    };
+    let remote = match sink {
+        Some(RemoteSink::SplunkHec) => Some(RemoteOptions::SplunkHec(HecOptions {
+            url: hec_url.context("--sink splunk-hec needs --hec-url")?,
+            token: hec_token.context("--sink splunk-hec needs --hec-token")?,
+            sourcetype: hec_sourcetype.unwrap_or_else(|| "_json".to_string()),
+            index: hec_index,
+            source: hec_source,
+            batch_bytes: hec_batch_size.unwrap_or(1 << 20) as usize,
+        })),
+        Some(RemoteSink::Kafka) => {
+            if kafka_brokers.is_empty() {
+                bail!("--sink kafka needs --kafka-brokers");
+            }
+            Some(RemoteOptions::Kafka(KafkaOptions {
+                brokers: kafka_brokers,
+                topic: kafka_topic.context("--sink kafka needs --kafka-topic")?,
+                key_field: kafka_key,
+                compression: kafka_compression,
+                batch_bytes: kafka_batch_size.unwrap_or(512 << 10) as usize,
+            }))
+        }
+        None if hec_url.is_some() => bail!("--hec-url needs --sink splunk-hec"),
+        None if kafka_topic.is_some() => bail!("--kafka-topic needs --sink kafka"),
+        None => None,
+    };
+    let sink_opts = SinkOptions {
+        compress: output_compress,
+        shard: ShardLimits {
+            max_bytes: max_output_size,
+            max_records: max_output_records,
+        },
+        elastic,
+        remote,
+        format: format.unwrap_or_default(),
+        table: Some(table),
+        columns: keep,
+        append: false,
+    };
+    if checkpoint.is_some() {
+        let plain = sink_opts.format == OutputFormat::Jsonl
+            && output_compress.is_none()
+            && !sink_opts.shard.is_enabled()
+            && sink_opts.elastic.is_none()
+            && sink_opts.remote.is_none();
+        if input.as_deref().is_none_or(is_stdin) || output.is_none() || follow || !plain {
+            bail!(
+                "--checkpoint needs one --input file and a plain JSONL --output \
+                 (no compression, sharding, service or --follow)"
+            );
+        }
+    }
+
+    let started_at = OffsetDateTime::now_utc()
+        .format(&Rfc3339)
+        .unwrap_or_default();
+    let start = Instant::now();
+    let mut report = RunReport::new(&parser.name(), started_at);
+    let result = (|| -> Result<()> {
+        match (input, input_dir) {
+            (None, None) => {
+                let input = PathBuf::from(STDIN_PATH);
+                let final_output = resolve_output_path(&input, output, prefix_input_hash)?
+                    .map(|p| with_compression_suffix(p, output_compress));
+
+                report.push(run_with_threads(
+                    parser.as_ref(),
+                    &input,
+                    final_output.as_deref(),
+                    &opts,
+                    &sink_opts,
+                    !no_progress,
+                )?);
+            }
+            (Some(input), _) => {
+                let final_output = resolve_output_path(&input, output, prefix_input_hash)?
+                    .map(|p| with_compression_suffix(p, output_compress));
+
+                let (opts, sink_opts) = match (&checkpoint, &final_output) {
+                    (Some(state), Some(out)) => checkpointed(
+                        state,
+                        resume,
+                        &parser.name(),
+                        &input,
+                        out,
+                        &opts,
+                        &sink_opts,
+                    )?,
+                    _ => (opts.clone(), sink_opts.clone()),
+                };
+                report.push(run_with_threads(
+                    parser.as_ref(),
+                    &input,
+                    final_output.as_deref(),
+                    &opts,
+                    &sink_opts,
+                    !no_progress,
+                )?);
+                if let Some(state) = &checkpoint {
+                    if interrupted(&opts) {
+                        log::info!(
+                            "Checkpoint saved to {}; continue with --resume",
+                            state.display()
+                        );
+                    } else {
+                        std::fs::remove_file(state)
+                            .with_context(|| format!("remove {}", state.display()))?;
+                    }
+                }
+            }
+            (None, Some(dir)) => {
+                let files = collect_input_files(&dir, recursive, &ext)?;
+                if files.is_empty() {
+                    bail!("no matching files under {}", dir.display());
+                }
+
+                log::info!("Input directory: {} ({} files)", dir.display(), files.len());
+
+                for input in &files {
+                    let per_file_output = output.as_deref().map(|out| match sink_opts.format {
+                        OutputFormat::Sqlite => out.to_path_buf(),
+                        format => mirrored_output_path(&dir, input, out, format.name()),
+                    });
+                    let final_output =
+                        resolve_output_path(input, per_file_output, prefix_input_hash)?
+                            .map(|p| with_compression_suffix(p, output_compress));
+
+                    report.push(run_with_threads(
+                        parser.as_ref(),
+                        input,
+                        final_output.as_deref(),
+                        &opts,
+                        &sink_opts,
+                        !no_progress,
+                    )?);
+                    if interrupted(&opts) {
+                        break;
+                    }
+                }
+            }
+        }
+        // Ctrl-C is how a `--follow` run ends: after a clean flush it succeeded.
+        if interrupted(&opts) && !follow {
+            bail!("interrupted by Ctrl-C");
+        }
+        Ok(())
+    })();
+
+    if let Some(path) = report_path {
+        report.duration_secs = start.elapsed().as_secs_f64();
+        report.error = result.as_ref().err().map(|e| format!("{e:#}"));
+        report.write(&path)?;
+    }
+    result
+}
+
+/// First Ctrl-C: stop reading, write out what was read and finish the
+/// outputs. A second one exits at once.
+fn on_ctrl_c() -> Result<Arc<StopSignal>> {
+    let stop = StopSignal::new();
+    let handler = stop.clone();
+    ctrlc::set_handler(move || {
+        if handler.is_requested() {
+            std::process::exit(130);
+        }
+        log::warn!("Interrupted: finishing the lines already read (Ctrl-C again to abort)");
+        handler.request();
+    })
+    .context("install Ctrl-C handler")?;
+    Ok(stop)
+}
+
+/// How often `--checkpoint` is written.
+const CHECKPOINT_EVERY: Duration = Duration::from_secs(10);
+
+/// `opts` and `sink_opts` for a run saving checkpoints to `state`, starting
+/// from the saved one with `resume`.
+fn checkpointed(
+    state: &Path,
+    resume: bool,
+    module: &str,
+    input: &Path,
+    output: &Path,
+    opts: &RunOptions,
+    sink_opts: &SinkOptions,
+) -> Result<(RunOptions, SinkOptions)> {
+    let base = match Checkpoint::load(state)? {
+        Some(_) if !resume => bail!(
+            "{} holds a checkpoint; pass --resume to continue from it, or delete it to start over",
+            state.display()
+        ),
+        Some(saved) => {
+            saved.check(module, input, output)?;
+            saved.truncate_output()?;
+            log::info!(
+                "Resuming from {}: input byte {}, output byte {}",
+                state.display(),
+                saved.input_offset,
+                saved.output_offset
+            );
+            saved
+        }
+        None => {
+            if resume {
+                log::info!("No checkpoint at {} yet, starting over", state.display());
+            }
+            Checkpoint {
+                module: module.to_string(),
+                input: input.to_path_buf(),
+                output: output.to_path_buf(),
+                input_offset: 0,
+                output_offset: 0,
+                updated_at: String::new(),
+            }
+        }
+    };
+
+    let (start_offset, append) = (base.input_offset, base.output_offset > 0);
+    let state = state.to_path_buf();
+    let save = move |input_offset, written| {
+        Checkpoint {
+            input_offset,
+            output_offset: base.output_offset + written,
+            updated_at: OffsetDateTime::now_utc()
+                .format(&Rfc3339)
+                .unwrap_or_default(),
+            ..base.clone()
+        }
+        .save(&state)
+    };
+    Ok((
+        RunOptions {
+            start_offset,
+            checkpoint: Some(Checkpoints {
+                every: CHECKPOINT_EVERY,
+                save: Arc::new(save),
+            }),
+            ..opts.clone()
+        },
+        SinkOptions {
+            append,
+            ..sink_opts.clone()
+        },
+    ))
+}
+
+fn interrupted(opts: &RunOptions) -> bool {
+    opts.stop.as_ref().is_some_and(|s| s.is_requested())
+}
+
 fn resolve_output_path(
     input: &Path,
     output: Option<PathBuf>,
@@ -105,9 +1279,12 @@ fn resolve_output_path(
         return Ok(Some(output));
     }
 
-    let filename = output
-        .file_name()
-        .with_context(|| format!("output path '{}' does not contain a filename", output.display()))?;
+    let filename = output.file_name().with_context(|| {
+        format!(
+            "output path '{}' does not contain a filename",
+            output.display()
+        )
+    })?;
 
     let mut prefixed_filename = OsString::new();
     prefixed_filename.push(short_input_path_hash(input));
@@ -119,6 +1296,41 @@ fn resolve_output_path(
     Ok(Some(output))
 }
 
+/// Append `.gz` / `.zst` to `output` unless it already ends with it.
+fn with_compression_suffix(output: PathBuf, compress: Option<OutputCompression>) -> PathBuf {
+    let Some(c) = compress else {
+        return output;
+    };
+
+    let suffix = format!(".{}", c.extension());
+    if output.as_os_str().to_string_lossy().ends_with(&suffix) {
+        return output;
+    }
+
+    let mut name = output.into_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Output path for `input` (found under `input_dir`) inside `output_dir`.
+///
+/// The relative directory layout is kept and `.jsonl` (or the `--format`'s
+/// extension) is appended to the full filename, so `access.log` and `access.log.gz` never collide.
+fn mirrored_output_path(
+    input_dir: &Path,
+    input: &Path,
+    output_dir: &Path,
+    extension: &str,
+) -> PathBuf {
+    let rel = input.strip_prefix(input_dir).unwrap_or(input);
+
+    let mut name = rel.as_os_str().to_os_string();
+    name.push(".");
+    name.push(extension);
+
+    output_dir.join(name)
+}
+
 /// Deterministic short hash for differentiating files with identical basenames.
 fn short_input_path_hash(input: &Path) -> String {
     // FNV-1a 64-bit, truncated to 32 bits for an 8-hex-character prefix.
@@ -136,72 +1348,155 @@ fn run_with_threads(
     parser: &dyn Parser,
     input: &Path,
     output: Option<&Path>,
-    workers: Option<usize>,
-) -> Result<()> {
-    let meta = std::fs::metadata(input).with_context(|| format!("metadata {}", input.display()))?;
-    let file_size = meta.len();
+    opts: &RunOptions,
+    sink_opts: &SinkOptions,
+    progress: bool,
+) -> Result<InputReport> {
+    let size_bytes = (!is_stdin(input))
+        .then(|| std::fs::metadata(input).map(|m| m.len()).ok())
+        .flatten();
+    let gzip = !is_stdin(input) && is_gzip(input).unwrap_or(false);
 
-    // Exact line count for both text and .gz.
-    let line_count = count_lines_any(input)?;
-
-    let n_workers = workers.unwrap_or_else(num_cpus::get).max(1);
+    // Exact line count for both text and .gz; stdin can only be read once
+    // and a followed file keeps growing, so the pre-pass is skipped there.
+    let line_count = if is_stdin(input) {
+        log::info!("Input: stdin");
+        None
+    } else if opts.follow {
+        log::info!("Input file: {} (following)", input.display());
+        None
+    } else if parser.input_format() == InputFormat::Document {
+        // Records only exist once the document is split; count them during the run.
+        log::info!("Input document: {}", input.display());
+        None
+    } else {
+        let meta =
+            std::fs::metadata(input).with_context(|| format!("metadata {}", input.display()))?;
+        let line_count = count_lines_any(input)?;
 
-    println!(
-        "[INFO] Input file: {} ({}), {} lines",
-        input.display(),
-        format_size(file_size),
-        line_count
-    );
+        log::info!(
+            "Input file: {} ({}{}), {} lines",
+            input.display(),
+            format_size(meta.len()),
+            if gzip { ", gzip" } else { "" },
+            line_count
+        );
+        let decoded = encoding::detect(input, opts.encoding)?;
+        if decoded != Encoding::Utf8 {
+            log::info!("Decoding {} to UTF-8", decoded.name());
+        }
+        Some(line_count)
+    };
 
-    println!(
-        "[INFO] Module: {}  |  Threads: {}",
-        parser.name(),
-        n_workers
-    );
+    log::info!("Module: {}  |  Threads: {}", parser.name(), opts.workers);
 
     let start = Instant::now();
 
-    let writer: Box<dyn Write + Send> = match output {
-        Some(path) => {
-            if let Some(parent) = path.parent() {
-                if !parent.as_os_str().is_empty() {
-                    std::fs::create_dir_all(parent)
-                        .with_context(|| format!("create output directory {}", parent.display()))?;
-                }
-            }
-
-            Box::new(File::create(path).with_context(|| format!("create {}", path.display()))?)
-        }
-        None => Box::new(io::stdout()),
+    let sink = open_sink(output, sink_opts)?;
+    let progress =
+        (progress && logging::enabled(log::Level::Info)).then(|| Progress::new(line_count));
+    let opts = RunOptions {
+        progress: progress.clone(),
+        ..opts.clone()
     };
+    let bar = progress.as_ref().map(|p| p.show());
+    let workers = run_streaming_per_worker(parser, input, sink, &opts)?;
+    drop(bar);
+    let mut stats = RunStats::default();
+    for (i, w) in workers.iter().enumerate() {
+        log::debug!(
+            "Worker {i}: {} lines, {} parsed, {} rejected, {} records",
+            w.lines,
+            w.parsed,
+            w.rejected,
+            w.records
+        );
+        stats.add(w);
+    }
 
-    let emitted = run_streaming_parallel(parser, input, writer, n_workers)?;
-
-    println!("[INFO] Emitted {} records", emitted);
+    log::info!(
+        "Lines: {} parsed, {} rejected, {} blank  |  Emitted {} records",
+        stats.parsed,
+        stats.rejected,
+        stats.blank,
+        stats.records
+    );
+    let stopped_at = opts
+        .stop
+        .as_ref()
+        .filter(|s| s.is_requested())
+        .map(|s| s.offset());
+    if let Some(offset) = stopped_at {
+        log::warn!(
+            "Stopped early after {} lines: input read up to byte {offset}{}",
+            stats.lines,
+            if gzip { " (uncompressed)" } else { "" }
+        );
+    }
 
     let elapsed = start.elapsed().as_secs_f64();
+    let lines_done = match stopped_at {
+        None if opts.start_offset == 0 => line_count.unwrap_or(stats.lines),
+        _ => stats.lines,
+    };
+    let lines_per_sec = lines_done as f64 / elapsed;
+    let rate = format!("{lines_per_sec:.1} lines/s");
+
+    let report_output = if let Some(remote) = sink_opts.remote_name() {
+        log::info!(
+            "Output: {}, processed in {:.3}s ({})",
+            remote,
+            elapsed,
+            rate
+        );
+        OutputReport::Remote { target: remote }
+    } else if let Some(out_path) = output {
+        let files: Vec<_> = output_files(out_path, sink_opts)
+            .into_iter()
+            .map(|path| OutputFile {
+                size_bytes: std::fs::metadata(&path).map(|m| m.len()).ok(),
+                path,
+            })
+            .collect();
+        let out_size = files
+            .iter()
+            .map(|f| f.size_bytes)
+            .sum::<Option<u64>>()
+            .map(format_size)
+            .unwrap_or_else(|| "unknown".into());
 
-    if let Some(out_path) = output {
-        let out_size = std::fs::metadata(out_path)
-            .map(|m| format_size(m.len()))
-            .unwrap_or_else(|_| "unknown".into());
+        let shards = if sink_opts.shard.is_enabled() {
+            format!(", {} shards", files.len())
+        } else {
+            String::new()
+        };
 
-        println!(
-            "[INFO] Output: {} ({}), processed in {:.3}s ({:.1} lines/s)",
+        log::info!(
+            "Output: {} ({}{}), processed in {:.3}s ({})",
             out_path.display(),
             out_size,
+            shards,
             elapsed,
-            line_count as f64 / elapsed
+            rate
         );
+        OutputReport::Files { files }
     } else {
-        println!(
-            "[INFO] Output: stdout, processed in {:.3}s ({:.1} lines/s)",
-            elapsed,
-            line_count as f64 / elapsed
-        );
-    }
+        log::info!("Output: stdout, processed in {:.3}s ({})", elapsed, rate);
+        OutputReport::Stdout
+    };
 
-    Ok(())
+    Ok(InputReport {
+        path: input.to_path_buf(),
+        size_bytes,
+        gzip,
+        line_count,
+        stats,
+        duration_secs: elapsed,
+        lines_per_sec,
+        stopped_at,
+        workers,
+        output: report_output,
+    })
 }
 
 #[cfg(test)]
@@ -242,4 +1537,34 @@ mod tests {
 
         assert_ne!(a.file_name(), b.file_name());
     }
+
+    #[test]
+    fn compression_suffix_is_appended_once() {
+        let gz =
+            with_compression_suffix(PathBuf::from("/out/a.jsonl"), Some(OutputCompression::Gzip));
+        assert_eq!(gz, PathBuf::from("/out/a.jsonl.gz"));
+
+        let zst = with_compression_suffix(
+            PathBuf::from("/out/a.jsonl.zst"),
+            Some(OutputCompression::Zstd),
+        );
+        assert_eq!(zst, PathBuf::from("/out/a.jsonl.zst"));
+    }
+
+    #[test]
+    fn mirrored_output_keeps_relative_layout_and_full_name() {
+        let out = mirrored_output_path(
+            Path::new("/cases/uac"),
+            Path::new("/cases/uac/apache/access.log.gz"),
+            Path::new("/out"),
+            "jsonl",
+        );
+        assert_eq!(out, PathBuf::from("/out/apache/access.log.gz.jsonl"));
+    }
+
+    #[test]
+    fn display_truncation_respects_char_boundaries() {
+        assert_eq!(truncate_for_display("abc", 5), "abc");
+        assert_eq!(truncate_for_display("héllo wörld", 5), "héllo...");
+    }
 }
@@ -0,0 +1,214 @@
+use crate::core::Parser;
+use crate::modules::common::month_number;
+use serde::Serialize;
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(ApacheError)
+}
+
+/// Apache httpd `error_log`, both layouts:
+/// 2.2 `[Sat Jun 01 12:34:56 2013] [error] [client 1.2.3.4] message` and
+/// 2.4 `[Sat Jun 01 12:34:56.123456 2013] [core:error] [pid 1:tid 2] [client 1.2.3.4:5678] AH00128: message`.
+pub struct ApacheError;
+
+const LEVELS: &[&str] = &[
+    "emerg", "alert", "crit", "error", "warn", "notice", "info", "debug",
+];
+
+#[derive(Default, Serialize)]
+struct Record<'a> {
+    /// Local time, ISO 8601 without offset (Apache doesn't log one).
+    ts: String,
+    level: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    module: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tid: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_ip: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_port: Option<u16>,
+    /// `AH00128`-style message id (2.4).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<&'a str>,
+    message: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    referer: Option<&'a str>,
+}
+
+impl Parser for ApacheError {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("apache-error")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses Apache 2.2/2.4 error_log lines -> normalized JSONL")
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["client_ip".to_string()]
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let Some(rec) = parse_error(line) else {
+            return false;
+        };
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+/// Next `[...]` group and the text after it.
+fn bracket(s: &str) -> Option<(&str, &str)> {
+    let (inner, rest) = s.strip_prefix('[')?.split_once(']')?;
+    Some((inner, rest.strip_prefix(' ').unwrap_or(rest)))
+}
+
+/// `Sat Jun 01 12:34:56[.123456] 2013` -> `2013-06-01T12:34:56[.123456]`.
+fn timestamp(s: &str) -> Option<String> {
+    let mut t = s.split_whitespace();
+    let _weekday = t.next()?;
+    let month = month_number(t.next()?)?;
+    let day: u8 = t.next()?.parse().ok()?;
+    let time = t.next()?;
+    let year: u16 = t.next()?.parse().ok()?;
+    if t.next().is_some() || time.len() < 8 || time.as_bytes()[2] != b':' {
+        return None;
+    }
+    Some(format!("{year:04}-{month:02}-{day:02}T{time}"))
+}
+
+fn parse_error(line: &str) -> Option<Record<'_>> {
+    let (ts, mut rest) = bracket(line)?;
+    let mut rec = Record {
+        ts: timestamp(ts)?,
+        ..Default::default()
+    };
+
+    let mut client = None;
+    while let Some((inner, after)) = bracket(rest) {
+        if let Some((module, level)) = inner.split_once(':')
+            && !module.is_empty()
+            && module
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'_')
+            && level.bytes().all(|b| b.is_ascii_alphanumeric())
+        {
+            rec.module = Some(module);
+            rec.level = level;
+        } else if LEVELS.contains(&inner) {
+            rec.level = inner;
+        } else if let Some(ids) = inner.strip_prefix("pid ") {
+            let (pid, tid) = match ids.split_once(":tid ") {
+                Some((p, t)) => (p, t.parse().ok()),
+                None => (ids, None),
+            };
+            rec.pid = pid.parse().ok();
+            rec.tid = tid;
+        } else if let Some(c) = inner
+            .strip_prefix("client ")
+            .or_else(|| inner.strip_prefix("remote "))
+        {
+            client = Some(c);
+        } else {
+            break;
+        }
+        rest = after;
+    }
+    if rec.level.is_empty() {
+        return None;
+    }
+
+    // 2.4 (module or pid present) appends the client port.
+    if let Some(c) = client {
+        match c.rsplit_once(':') {
+            Some((ip, port)) if rec.module.is_some() || rec.pid.is_some() => {
+                rec.client_ip = Some(ip);
+                rec.client_port = port.parse().ok();
+            }
+            _ => rec.client_ip = Some(c),
+        }
+    }
+
+    if let Some((code, msg)) = rest.split_once(": ")
+        && code.len() == 7
+        && code.starts_with("AH")
+        && code[2..].bytes().all(|b| b.is_ascii_digit())
+    {
+        rec.error_code = Some(code);
+        rest = msg;
+    }
+    match rest.rsplit_once(", referer: ") {
+        Some((msg, referer)) => {
+            rec.message = msg;
+            rec.referer = Some(referer);
+        }
+        None => rec.message = rest,
+    }
+    Some(rec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(line: &str) -> Option<serde_json::Value> {
+        let mut out = Vec::new();
+        ApacheError
+            .process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn parses_24_layout() {
+        let v = run("[Sat Jun 01 12:34:56.123456 2013] [core:error] [pid 1234:tid 140234] [client 10.0.0.1:51234] AH00128: File does not exist: /var/www/x, referer: http://example.com/").unwrap();
+        assert_eq!(v["ts"], "2013-06-01T12:34:56.123456");
+        assert_eq!(v["level"], "error");
+        assert_eq!(v["module"], "core");
+        assert_eq!(v["pid"], 1234);
+        assert_eq!(v["tid"], 140234);
+        assert_eq!(v["client_ip"], "10.0.0.1");
+        assert_eq!(v["client_port"], 51234);
+        assert_eq!(v["error_code"], "AH00128");
+        assert_eq!(v["message"], "File does not exist: /var/www/x");
+        assert_eq!(v["referer"], "http://example.com/");
+
+        let v = run("[Mon Jan 02 03:04:05.000001 2024] [mpm_event:notice] [pid 1:tid 2] AH00489: Apache/2.4.57 configured").unwrap();
+        assert_eq!(v["module"], "mpm_event");
+        assert!(v.get("client_ip").is_none());
+    }
+
+    #[test]
+    fn parses_22_layout() {
+        let v = run("[Sat Jun 01 12:34:56 2013] [error] [client 1.2.3.4] File does not exist: /var/www/favicon.ico").unwrap();
+        assert_eq!(v["ts"], "2013-06-01T12:34:56");
+        assert_eq!(v["level"], "error");
+        assert!(v.get("module").is_none());
+        assert_eq!(v["client_ip"], "1.2.3.4");
+        assert!(v.get("client_port").is_none());
+        assert_eq!(v["message"], "File does not exist: /var/www/favicon.ico");
+
+        let v = run(
+            "[Sat Jun 01 12:34:56 2013] [notice] Apache configured -- resuming normal operations",
+        )
+        .unwrap();
+        assert_eq!(v["level"], "notice");
+        assert_eq!(
+            v["message"],
+            "Apache configured -- resuming normal operations"
+        );
+    }
+
+    #[test]
+    fn rejects_other_lines() {
+        assert!(run("[Sat Jun 01 12:34:56 2013] no level here").is_none());
+        assert!(run("2024/01/02 03:04:05 [error] 1#1: x").is_none());
+    }
+}
@@ -0,0 +1,578 @@
+use crate::core::Parser;
+use serde::Serialize;
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(Asa)
+}
+
+/// Cisco ASA (and FTD) syslog: `%ASA-<severity>-<message id>: <text>`,
+/// optionally behind a syslog header. Connection, deny, VPN, AAA, login and
+/// command messages are decoded by message id into one normalized schema;
+/// the rest keep `message_id` and `message` only.
+pub struct Asa;
+
+#[derive(Default, Serialize)]
+struct Record<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    syslog_timestamp: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    host: Option<&'a str>,
+    /// `ASA` or `FTD`.
+    product: &'a str,
+    severity: u8,
+    message_id: u32,
+    /// `connection_built`, `connection_teardown`, `deny`, `acl`,
+    /// `vpn_disconnect`, `auth`, `login`, `command` or `other`.
+    event: &'static str,
+    /// `allow` or `deny`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    action: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    protocol: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    direction: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    connection_id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    src_interface: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    src_ip: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    src_port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    src_mapped_ip: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    src_mapped_port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dst_interface: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dst_ip: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dst_port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dst_mapped_ip: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dst_mapped_port: Option<u16>,
+    /// ACL / access-group name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    acl: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hit_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tcp_flags: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user: Option<&'a str>,
+    /// VPN group policy / tunnel group.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<&'a str>,
+    /// AAA server.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    server: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session_type: Option<&'a str>,
+    /// As written: `0:00:05` or `0h:10m:20s`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bytes_sent: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bytes_received: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    command: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'a str>,
+    message: &'a str,
+}
+
+impl Parser for Asa {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("asa")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses Cisco ASA/FTD syslog by message id -> normalized JSONL")
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["src_ip".to_string(), "dst_ip".to_string()]
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let line = line.strip_suffix('\r').unwrap_or(line).trim();
+        let Some(rec) = parse_asa(line) else {
+            return false;
+        };
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+fn parse_asa(line: &str) -> Option<Record<'_>> {
+    let start = line.find("%ASA-").or_else(|| line.find("%FTD-"))?;
+    let (tag, message) = line[start + 1..].split_once(": ")?;
+    let mut parts = tag.splitn(3, '-');
+    let product = parts.next()?;
+    let severity = parts.next()?.parse().ok()?;
+    let message_id = parts.next()?.parse().ok()?;
+
+    let (syslog_timestamp, host) = preamble(&line[..start]);
+    let mut rec = Record {
+        syslog_timestamp,
+        host,
+        product,
+        severity,
+        message_id,
+        event: "other",
+        message,
+        ..Default::default()
+    };
+
+    // A message that doesn't match its id's layout still yields the header.
+    let _ = match message_id {
+        302013 | 302015 => built(message, &mut rec),
+        302014 | 302016 => teardown(message, &mut rec),
+        106023 => access_group_deny(message, &mut rec),
+        106100 => access_list(message, &mut rec),
+        106001 | 106006 | 106007 | 106015 => denied_from(message, &mut rec),
+        113019 => vpn_disconnect(message, &mut rec),
+        113004 | 113005 | 113012 | 113015 => aaa(message_id, message, &mut rec),
+        605004 | 605005 => login(message, &mut rec),
+        111008 => command(message, &mut rec),
+        _ => None,
+    };
+    Some(rec)
+}
+
+/// `[<PRI>]<timestamp> [host] :` before the tag. The host is the last token
+/// unless that looks like a time of day (`logging hostname` off).
+fn preamble(pre: &str) -> (Option<&str>, Option<&str>) {
+    let mut pre = pre.trim_end().trim_end_matches(':').trim_end();
+    if let Some((_, rest)) = pre.strip_prefix('<').and_then(|r| r.split_once('>')) {
+        pre = rest;
+    }
+    let pre = pre.trim();
+    if pre.is_empty() {
+        return (None, None);
+    }
+    match pre.rsplit_once(' ') {
+        Some((ts, host)) if !host.bytes().all(|b| b.is_ascii_digit() || b == b':') => {
+            (Some(ts.trim_end()), Some(host))
+        }
+        _ => (Some(pre), None),
+    }
+}
+
+fn port(s: &str) -> Option<u16> {
+    s.parse().ok()
+}
+
+/// `10.0.0.1/1234` -> address and port.
+fn addr_port(s: &str) -> (&str, Option<u16>) {
+    match s.rsplit_once('/') {
+        Some((ip, p)) => (ip, port(p)),
+        None => (s, None),
+    }
+}
+
+/// `outside:10.0.0.1/1234` -> interface, address and port.
+fn endpoint(s: &str) -> Option<(&str, &str, Option<u16>)> {
+    let (iface, rest) = s.split_once(':')?;
+    let (ip, port) = addr_port(rest);
+    Some((iface, ip, port))
+}
+
+/// `(LOCAL\alice)` or `(alice)` after an endpoint, but not `(10.0.0.1/80)`.
+fn paren_user(s: &str) -> Option<&str> {
+    let u = s.strip_prefix('(')?.strip_suffix(')')?;
+    (!u.contains('/')).then_some(u)
+}
+
+/// One end of a built connection.
+#[derive(Default)]
+struct Side<'a> {
+    iface: Option<&'a str>,
+    ip: Option<&'a str>,
+    port: Option<u16>,
+    mapped_ip: Option<&'a str>,
+    mapped_port: Option<u16>,
+    user: Option<&'a str>,
+}
+
+/// `Built inbound TCP connection 123 for outside:1.2.3.4/1234 (1.2.3.4/1234)
+/// [(user)] to inside:10.0.0.1/80 (10.0.0.1/80) [(user)]`. For outbound
+/// connections the `to` side is the initiator, so it becomes the source.
+fn built<'a>(msg: &'a str, rec: &mut Record<'a>) -> Option<()> {
+    let mut t = msg.split_whitespace();
+    (t.next()? == "Built").then_some(())?;
+    let direction = t.next()?;
+    let protocol = t.next()?;
+    (t.next()? == "connection").then_some(())?;
+    let id = t.next()?.parse().ok()?;
+    (t.next()? == "for").then_some(())?;
+
+    let mut sides = [Side::default(), Side::default()];
+    for (i, side) in sides.iter_mut().enumerate() {
+        let (iface, ip, port) = endpoint(t.next()?)?;
+        (side.iface, side.ip, side.port) = (Some(iface), Some(ip), port);
+        for tok in t.by_ref() {
+            if tok == "to" && i == 0 {
+                break;
+            }
+            if let Some(mapped) = tok.strip_prefix('(').and_then(|m| m.strip_suffix(')'))
+                && mapped.contains('/')
+            {
+                let (ip, port) = addr_port(mapped);
+                (side.mapped_ip, side.mapped_port) = (Some(ip), port);
+            } else if let Some(user) = paren_user(tok) {
+                side.user = Some(user);
+            }
+        }
+    }
+    if direction == "outbound" {
+        sides.swap(0, 1);
+    }
+
+    let [src, dst] = sides;
+    rec.event = "connection_built";
+    rec.action = Some("allow");
+    rec.direction = Some(direction);
+    rec.protocol = Some(protocol.to_ascii_lowercase());
+    rec.connection_id = Some(id);
+    (rec.src_interface, rec.src_ip, rec.src_port) = (src.iface, src.ip, src.port);
+    (rec.src_mapped_ip, rec.src_mapped_port) = (src.mapped_ip, src.mapped_port);
+    (rec.dst_interface, rec.dst_ip, rec.dst_port) = (dst.iface, dst.ip, dst.port);
+    (rec.dst_mapped_ip, rec.dst_mapped_port) = (dst.mapped_ip, dst.mapped_port);
+    rec.user = src.user.or(dst.user);
+    Some(())
+}
+
+/// `Teardown TCP connection 123 for outside:1.2.3.4/1234 [(user)] to
+/// inside:10.0.0.1/80 [(user)] duration 0:00:05 bytes 1234 [TCP FINs]`.
+fn teardown<'a>(msg: &'a str, rec: &mut Record<'a>) -> Option<()> {
+    let rest = msg.strip_prefix("Teardown ")?;
+    let (protocol, rest) = rest.split_once(" connection ")?;
+    let (id, rest) = rest.split_once(" for ")?;
+    let (ends, stats) = rest.split_once(" duration ")?;
+    let (from, to) = ends.split_once(" to ")?;
+
+    let mut from = from.split_whitespace();
+    let (si, sip, sp) = endpoint(from.next()?)?;
+    let mut to = to.split_whitespace();
+    let (di, dip, dp) = endpoint(to.next()?)?;
+
+    let mut stats = stats.splitn(4, ' ');
+    let duration = stats.next()?;
+    let bytes = match stats.next() {
+        Some("bytes") => stats.next().and_then(|b| b.parse().ok()),
+        _ => None,
+    };
+
+    rec.event = "connection_teardown";
+    rec.protocol = Some(protocol.to_ascii_lowercase());
+    rec.connection_id = id.parse().ok();
+    (rec.src_interface, rec.src_ip, rec.src_port) = (Some(si), Some(sip), sp);
+    (rec.dst_interface, rec.dst_ip, rec.dst_port) = (Some(di), Some(dip), dp);
+    rec.user = from
+        .find_map(paren_user)
+        .or_else(|| to.find_map(paren_user));
+    rec.duration = Some(duration);
+    rec.bytes = bytes;
+    rec.reason = stats.next().filter(|r| !r.is_empty());
+    Some(())
+}
+
+/// `Deny tcp src outside:1.2.3.4/1234 dst inside:10.0.0.1/80 by
+/// access-group "outside_in" [0x0, 0x0]`.
+fn access_group_deny<'a>(msg: &'a str, rec: &mut Record<'a>) -> Option<()> {
+    let mut t = msg.split_whitespace();
+    (t.next()? == "Deny").then_some(())?;
+    let protocol = t.next()?;
+    (t.next()? == "src").then_some(())?;
+    let (si, sip, sp) = endpoint(t.next()?)?;
+    (t.next()? == "dst").then_some(())?;
+    let (di, dip, dp) = endpoint(t.next()?)?;
+
+    rec.event = "deny";
+    rec.action = Some("deny");
+    rec.protocol = Some(protocol.to_ascii_lowercase());
+    (rec.src_interface, rec.src_ip, rec.src_port) = (Some(si), Some(sip), sp);
+    (rec.dst_interface, rec.dst_ip, rec.dst_port) = (Some(di), Some(dip), dp);
+    rec.acl = msg
+        .split_once("access-group \"")
+        .and_then(|(_, r)| r.split_once('"'))
+        .map(|(acl, _)| acl);
+    Some(())
+}
+
+/// `access-list outside_in denied tcp outside/1.2.3.4(1234) [(user)] ->
+/// inside/10.0.0.1(80) [(user)] hit-cnt 1 first hit [0x0, 0x0]`.
+fn access_list<'a>(msg: &'a str, rec: &mut Record<'a>) -> Option<()> {
+    fn side(s: &str) -> Option<(&str, &str, Option<u16>)> {
+        let (iface, rest) = s.split_once('/')?;
+        let (ip, p) = rest.strip_suffix(')')?.split_once('(')?;
+        Some((iface, ip, port(p)))
+    }
+
+    let mut t = msg.split_whitespace();
+    (t.next()? == "access-list").then_some(())?;
+    let acl = t.next()?;
+    let verdict = t.next()?;
+    let protocol = t.next()?;
+    let (si, sip, sp) = side(t.next()?)?;
+    let mut user = None;
+    let mut tok = t.next()?;
+    if let Some(u) = paren_user(tok) {
+        user = Some(u);
+        tok = t.next()?;
+    }
+    (tok == "->").then_some(())?;
+    let (di, dip, dp) = side(t.next()?)?;
+    let mut tok = t.next();
+    if let Some(u) = tok.and_then(paren_user) {
+        user = user.or(Some(u));
+        tok = t.next();
+    }
+
+    rec.event = "acl";
+    rec.action = Some(if verdict == "denied" { "deny" } else { "allow" });
+    rec.acl = Some(acl);
+    rec.protocol = Some(protocol.to_ascii_lowercase());
+    (rec.src_interface, rec.src_ip, rec.src_port) = (Some(si), Some(sip), sp);
+    (rec.dst_interface, rec.dst_ip, rec.dst_port) = (Some(di), Some(dip), dp);
+    rec.user = user;
+    if tok == Some("hit-cnt") {
+        rec.hit_count = t.next().and_then(|n| n.parse().ok());
+    }
+    Some(())
+}
+
+/// `Inbound TCP connection denied from 1.2.3.4/1234 to 10.0.0.1/80 flags SYN
+/// on interface outside`, `Deny TCP (no connection) from ... flags RST on
+/// interface outside`, `Deny inbound UDP from ... due to DNS Query`.
+fn denied_from<'a>(msg: &'a str, rec: &mut Record<'a>) -> Option<()> {
+    let (head, rest) = msg.split_once(" from ")?;
+    let protocol = head
+        .split_whitespace()
+        .find(|w| w.bytes().all(|b| b.is_ascii_uppercase()))?;
+    let mut t = rest.split_whitespace();
+    let (sip, sp) = addr_port(t.next()?);
+    (t.next()? == "to").then_some(())?;
+    let (dip, dp) = addr_port(t.next()?);
+
+    rec.event = "deny";
+    rec.action = Some("deny");
+    rec.protocol = Some(protocol.to_ascii_lowercase());
+    (rec.src_ip, rec.src_port) = (Some(sip), sp);
+    (rec.dst_ip, rec.dst_port) = (Some(dip), dp);
+    rec.tcp_flags = rest
+        .split_once(" flags ")
+        .map(|(_, f)| f.split_once(" on interface").map_or(f, |(f, _)| f).trim());
+    rec.src_interface = rest
+        .split_once(" on interface ")
+        .map(|(_, i)| i.split_whitespace().next().unwrap_or(i));
+    Some(())
+}
+
+/// `Group = G, Username = alice, IP = 1.2.3.4, Session disconnected. Session
+/// Type: SSL, Duration: 0h:10m:20s, Bytes xmt: 123, Bytes rcv: 456, Reason: ...`.
+fn vpn_disconnect<'a>(msg: &'a str, rec: &mut Record<'a>) -> Option<()> {
+    let (ids, stats) = msg.split_once("Session disconnected.")?;
+    rec.event = "vpn_disconnect";
+    for part in ids.split(',') {
+        match part.trim().split_once(" = ") {
+            Some(("Group", v)) => rec.group = Some(v),
+            Some(("Username", v)) => rec.user = Some(v),
+            Some(("IP", v)) => rec.src_ip = Some(v),
+            _ => {}
+        }
+    }
+    let (stats, reason) = match stats.split_once(", Reason: ") {
+        Some((s, r)) => (s, Some(r.trim())),
+        None => (stats, None),
+    };
+    for part in stats.split(',') {
+        match part.trim().split_once(": ") {
+            Some(("Session Type", v)) => rec.session_type = Some(v),
+            Some(("Duration", v)) => rec.duration = Some(v),
+            Some(("Bytes xmt", v)) => rec.bytes_sent = v.parse().ok(),
+            Some(("Bytes rcv", v)) => rec.bytes_received = v.parse().ok(),
+            _ => {}
+        }
+    }
+    rec.reason = reason;
+    Some(())
+}
+
+/// `AAA user authentication Rejected : reason = AAA failure : server =
+/// 10.0.0.5 : user = alice : user IP = 1.2.3.4`.
+fn aaa<'a>(id: u32, msg: &'a str, rec: &mut Record<'a>) -> Option<()> {
+    rec.event = "auth";
+    rec.action = Some(if matches!(id, 113004 | 113012) {
+        "allow"
+    } else {
+        "deny"
+    });
+    for part in msg.split(" : ").skip(1) {
+        match part.split_once(" = ") {
+            Some(("reason", v)) => rec.reason = Some(v),
+            Some(("server", v)) => rec.server = Some(v),
+            Some(("user", v)) => rec.user = Some(v),
+            Some(("user IP", v)) => rec.src_ip = Some(v),
+            _ => {}
+        }
+    }
+    Some(())
+}
+
+/// `Login permitted from 10.0.0.5/52000 to inside:10.0.0.1/ssh for user "admin"`.
+fn login<'a>(msg: &'a str, rec: &mut Record<'a>) -> Option<()> {
+    let mut t = msg.split_whitespace();
+    (t.next()? == "Login").then_some(())?;
+    let verdict = t.next()?;
+    (t.next()? == "from").then_some(())?;
+    let (sip, sp) = addr_port(t.next()?);
+    (t.next()? == "to").then_some(())?;
+    let (di, dip, service) = t.next()?.split_once(':').map(|(i, r)| {
+        let (ip, service) = r.rsplit_once('/').unwrap_or((r, ""));
+        (i, ip, service)
+    })?;
+
+    rec.event = "login";
+    rec.action = Some(if verdict == "permitted" {
+        "allow"
+    } else {
+        "deny"
+    });
+    (rec.src_ip, rec.src_port) = (Some(sip), sp);
+    (rec.dst_interface, rec.dst_ip) = (Some(di), Some(dip));
+    rec.protocol = (!service.is_empty()).then(|| service.to_string());
+    rec.user = msg
+        .split_once("for user \"")
+        .and_then(|(_, r)| r.split_once('"'))
+        .map(|(u, _)| u);
+    Some(())
+}
+
+/// `User 'admin' executed the 'write memory' command.`
+fn command<'a>(msg: &'a str, rec: &mut Record<'a>) -> Option<()> {
+    let rest = msg.strip_prefix("User '")?;
+    let (user, rest) = rest.split_once("' executed the '")?;
+    let (cmd, _) = rest.rsplit_once("' command")?;
+    rec.event = "command";
+    rec.user = Some(user);
+    rec.command = Some(cmd);
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(line: &str) -> Option<serde_json::Value> {
+        let mut out = Vec::new();
+        Asa.process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn built_and_teardown() {
+        let v = run("<166>Jan 02 2024 03:04:05 asa01 : %ASA-6-302013: Built inbound TCP connection 1234 for outside:203.0.113.5/51000 (203.0.113.5/51000) to inside:10.0.0.10/443 (198.51.100.1/443)").unwrap();
+        assert_eq!(v["syslog_timestamp"], "Jan 02 2024 03:04:05");
+        assert_eq!(v["host"], "asa01");
+        assert_eq!(v["severity"], 6);
+        assert_eq!(v["message_id"], 302013);
+        assert_eq!(v["event"], "connection_built");
+        assert_eq!(v["action"], "allow");
+        assert_eq!(v["protocol"], "tcp");
+        assert_eq!(v["connection_id"], 1234);
+        assert_eq!(v["src_interface"], "outside");
+        assert_eq!(v["src_ip"], "203.0.113.5");
+        assert_eq!(v["src_port"], 51000);
+        assert_eq!(v["dst_ip"], "10.0.0.10");
+        assert_eq!(v["dst_mapped_ip"], "198.51.100.1");
+
+        let v = run(r"%ASA-6-302015: Built outbound UDP connection 7 for outside:8.8.8.8/53 (8.8.8.8/53) to inside:10.0.0.10/5353 (192.0.2.1/40000) (LOCAL\alice)").unwrap();
+        assert_eq!(v["src_ip"], "10.0.0.10");
+        assert_eq!(v["src_mapped_port"], 40000);
+        assert_eq!(v["dst_ip"], "8.8.8.8");
+        assert_eq!(v["user"], r"LOCAL\alice");
+        assert!(v.get("host").is_none());
+
+        let v = run("Jan  2 03:04:06 asa01 %ASA-6-302014: Teardown TCP connection 1234 for outside:203.0.113.5/51000 to inside:10.0.0.10/443 duration 0:00:05 bytes 6789 TCP FINs").unwrap();
+        assert_eq!(v["event"], "connection_teardown");
+        assert_eq!(v["duration"], "0:00:05");
+        assert_eq!(v["bytes"], 6789);
+        assert_eq!(v["reason"], "TCP FINs");
+    }
+
+    #[test]
+    fn denies_and_acls() {
+        let v = run(r#"%ASA-4-106023: Deny tcp src outside:203.0.113.5/51000 dst inside:10.0.0.10/22 by access-group "outside_in" [0x0, 0x0]"#).unwrap();
+        assert_eq!(v["event"], "deny");
+        assert_eq!(v["action"], "deny");
+        assert_eq!(v["acl"], "outside_in");
+        assert_eq!(v["dst_port"], 22);
+
+        let v = run("%ASA-6-106100: access-list inside_in permitted udp inside/10.0.0.10(5353) -> outside/8.8.8.8(53) hit-cnt 3 first hit [0x1, 0x0]").unwrap();
+        assert_eq!(v["event"], "acl");
+        assert_eq!(v["action"], "allow");
+        assert_eq!(v["acl"], "inside_in");
+        assert_eq!(v["src_interface"], "inside");
+        assert_eq!(v["dst_ip"], "8.8.8.8");
+        assert_eq!(v["hit_count"], 3);
+
+        let v = run("%ASA-2-106001: Inbound TCP connection denied from 203.0.113.5/51000 to 10.0.0.10/23 flags SYN  on interface outside").unwrap();
+        assert_eq!(v["protocol"], "tcp");
+        assert_eq!(v["src_ip"], "203.0.113.5");
+        assert_eq!(v["dst_port"], 23);
+        assert_eq!(v["tcp_flags"], "SYN");
+        assert_eq!(v["src_interface"], "outside");
+    }
+
+    #[test]
+    fn vpn_auth_login_command() {
+        let v = run("%ASA-4-113019: Group = staff, Username = alice, IP = 198.51.100.7, Session disconnected. Session Type: SSL, Duration: 0h:10m:20s, Bytes xmt: 1234, Bytes rcv: 5678, Reason: User Requested").unwrap();
+        assert_eq!(v["event"], "vpn_disconnect");
+        assert_eq!(v["group"], "staff");
+        assert_eq!(v["user"], "alice");
+        assert_eq!(v["src_ip"], "198.51.100.7");
+        assert_eq!(v["duration"], "0h:10m:20s");
+        assert_eq!(v["bytes_sent"], 1234);
+        assert_eq!(v["bytes_received"], 5678);
+        assert_eq!(v["reason"], "User Requested");
+
+        let v = run("%ASA-6-113005: AAA user authentication Rejected : reason = AAA failure : server = 10.0.0.5 : user = bob : user IP = 198.51.100.8").unwrap();
+        assert_eq!(v["event"], "auth");
+        assert_eq!(v["action"], "deny");
+        assert_eq!(v["user"], "bob");
+        assert_eq!(v["server"], "10.0.0.5");
+        assert_eq!(v["src_ip"], "198.51.100.8");
+
+        let v = run(r#"%ASA-6-605005: Login permitted from 10.0.0.5/52000 to inside:10.0.0.1/ssh for user "admin""#).unwrap();
+        assert_eq!(v["event"], "login");
+        assert_eq!(v["action"], "allow");
+        assert_eq!(v["protocol"], "ssh");
+        assert_eq!(v["user"], "admin");
+
+        let v = run("%ASA-5-111008: User 'admin' executed the 'write memory' command.").unwrap();
+        assert_eq!(v["event"], "command");
+        assert_eq!(v["command"], "write memory");
+    }
+
+    #[test]
+    fn unknown_ids_and_garbage() {
+        let v = run("%ASA-7-609001: Built local-host inside:10.0.0.10").unwrap();
+        assert_eq!(v["event"], "other");
+        assert_eq!(v["message"], "Built local-host inside:10.0.0.10");
+        assert!(v.get("action").is_none());
+
+        assert!(run("Jan  2 03:04:05 host sshd[1]: hello").is_none());
+        assert!(run("%ASA-x-1: bad").is_none());
+    }
+}
@@ -0,0 +1,395 @@
+use crate::core::{GroupJoiner, LineJoiner, ModuleOptions, OptionSpec, Parser};
+use crate::modules::common::{epoch_to_rfc3339, split_syslog};
+use anyhow::Result;
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(Auditd { merge: false })
+}
+
+/// Linux audit logs (`/var/log/audit/audit.log`):
+/// `[node=N ]type=T msg=audit(<epoch>:<serial>): key=value ...`, raw or
+/// `ENRICHED`, optionally behind a syslog preamble.
+///
+/// With `merge`, the records of one event (same timestamp and serial) are
+/// gathered on the reader thread and come out as one record.
+pub struct Auditd {
+    merge: bool,
+}
+
+const OPTIONS: &[OptionSpec] = &[OptionSpec {
+    key: "merge",
+    help: "Merge all records of an event (same serial) into one JSON event (default: false)",
+}];
+
+/// Open events when merging; the oldest is flushed past this.
+const MAX_PENDING: usize = 64;
+
+/// Record types that belong to a multi-record (syscall) event, closed by `EOE`.
+/// Any other type is an event on its own.
+const MULTI_RECORD_TYPES: &[&str] = &[
+    "SYSCALL",
+    "EXECVE",
+    "PATH",
+    "CWD",
+    "PROCTITLE",
+    "SOCKADDR",
+    "SOCKETCALL",
+    "IPC",
+    "MMAP",
+    "FD_PAIR",
+    "OBJ_PID",
+    "BPRM_FCAPS",
+    "CAPSET",
+    "KERN_MODULE",
+    "NETFILTER_CFG",
+    "AVC",
+    "SELINUX_ERR",
+    "EOE",
+];
+
+/// Unquoted values of these keys are hex-encoded strings (auditd does that
+/// whenever the value holds spaces, quotes or control characters).
+const HEX_KEYS: &[&str] = &[
+    "proctitle",
+    "cmd",
+    "comm",
+    "exe",
+    "name",
+    "cwd",
+    "path",
+    "acct",
+    "data",
+    "key",
+    "old",
+    "new",
+    "ocomm",
+    "subj",
+];
+
+/// Numeric ids and counters.
+const INT_KEYS: &[&str] = &[
+    "pid", "ppid", "uid", "auid", "euid", "suid", "fsuid", "gid", "egid", "sgid", "fsgid", "ses",
+    "exit", "items", "argc", "inode", "ouid", "ogid", "opid", "oauid", "oses",
+];
+
+struct AuditLine<'a> {
+    node: Option<&'a str>,
+    kind: &'a str,
+    epoch: &'a str,
+    serial: &'a str,
+    body: &'a str,
+}
+
+fn split_audit(line: &str) -> Option<AuditLine<'_>> {
+    let mut s = line.trim();
+    if !s.starts_with("type=") && !s.starts_with("node=") {
+        s = split_syslog(s)?.1;
+    }
+
+    let node = match s.strip_prefix("node=") {
+        Some(rest) => {
+            let (node, rest) = rest.split_once(' ')?;
+            s = rest;
+            Some(node)
+        }
+        None => None,
+    };
+    let (kind, rest) = s.strip_prefix("type=")?.split_once(' ')?;
+    let (stamp, body) = rest.strip_prefix("msg=audit(")?.split_once(')')?;
+    let (epoch, serial) = stamp.split_once(':')?;
+    let body = body.strip_prefix(':').unwrap_or(body).trim_start();
+    Some(AuditLine {
+        node,
+        kind,
+        epoch,
+        serial,
+        body,
+    })
+}
+
+/// Groups lines by `<epoch>:<serial>`.
+fn event_key(line: &str) -> Option<(&str, bool)> {
+    let start = line.find("msg=audit(")? + "msg=audit(".len();
+    let len = line[start..].find(')')?;
+    let kind = split_audit(line)?.kind;
+    Some((
+        &line[start..start + len],
+        !MULTI_RECORD_TYPES.contains(&kind) || kind == "EOE",
+    ))
+}
+
+/// `key=value` tokens; values may be `"double"` or `'single'` quoted.
+pub(crate) fn pairs(s: &str) -> Vec<(&str, &str, u8)> {
+    let b = s.as_bytes();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < b.len() {
+        while i < b.len() && b[i] == b' ' {
+            i += 1;
+        }
+        let start = i;
+        while i < b.len() && b[i] != b'=' && b[i] != b' ' {
+            i += 1;
+        }
+        if i >= b.len() || b[i] == b' ' {
+            continue;
+        }
+        let key = &s[start..i];
+        i += 1;
+
+        let quote = match b.get(i) {
+            Some(&q @ (b'"' | b'\'')) => q,
+            _ => 0,
+        };
+        let value = if quote != 0 {
+            let end = s[i + 1..]
+                .find(quote as char)
+                .map_or(b.len(), |n| i + 1 + n);
+            let v = &s[i + 1..end];
+            i = end + 1;
+            v
+        } else {
+            let end = s[i..].find(' ').map_or(b.len(), |n| i + n);
+            let v = &s[i..end];
+            i = end;
+            v
+        };
+        if !key.is_empty() {
+            out.push((key, value, quote));
+        }
+    }
+    out
+}
+
+/// Hex-encoded string with NUL separators (argv) turned into spaces.
+pub(crate) fn decode_hex(v: &str) -> Option<String> {
+    if v.len() < 2 || !v.len().is_multiple_of(2) || !v.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let bytes: Vec<u8> = (0..v.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&v[i..i + 2], 16).unwrap_or(b'?'))
+        .map(|b| if b == 0 { b' ' } else { b })
+        .collect();
+    Some(String::from_utf8_lossy(&bytes).trim_end().to_string())
+}
+
+fn is_execve_arg(kind: &str, key: &str) -> bool {
+    kind == "EXECVE"
+        && key
+            .strip_prefix('a')
+            .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
+
+fn fields(kind: &str, body: &str, out: &mut Map<String, Value>) {
+    // ENRICHED format: translated names (`UID="root"`) after a GS byte.
+    let (raw, enriched) = body.split_once('\u{1d}').unwrap_or((body, ""));
+
+    for (key, v, quote) in pairs(raw).into_iter().chain(pairs(enriched)) {
+        let value = match quote {
+            // `msg='op=PAM:authentication acct="root" ... res=failed'`
+            b'\'' => {
+                fields(kind, v, out);
+                continue;
+            }
+            b'"' => Value::from(v),
+            _ if v == "(null)" || v == "?" => Value::Null,
+            _ if HEX_KEYS.contains(&key) || is_execve_arg(kind, key) => {
+                decode_hex(v).map_or_else(|| Value::from(v), Value::from)
+            }
+            _ if INT_KEYS.contains(&key) => v
+                .parse::<i64>()
+                .map_or_else(|_| Value::from(v), Value::from),
+            _ => Value::from(v),
+        };
+        out.insert(key.to_string(), value);
+    }
+}
+
+fn header(l: &AuditLine<'_>, out: &mut Map<String, Value>) {
+    out.insert("ts".into(), epoch_to_rfc3339(l.epoch).into());
+    out.insert(
+        "serial".into(),
+        l.serial
+            .parse::<u64>()
+            .map_or_else(|_| Value::from(l.serial), Value::from),
+    );
+    if let Some(node) = l.node {
+        out.insert("node".into(), node.into());
+    }
+}
+
+fn record(l: &AuditLine<'_>) -> Map<String, Value> {
+    let mut rec = Map::new();
+    rec.insert("type".into(), l.kind.into());
+    header(l, &mut rec);
+    fields(l.kind, l.body, &mut rec);
+    rec
+}
+
+/// One object for a whole event: each record's fields under its lowercased
+/// type (an array when the type repeats, e.g. `path`), plus the decoded
+/// `EXECVE` arguments as `cmdline`.
+fn event(lines: &[AuditLine<'_>]) -> Map<String, Value> {
+    let mut ev = Map::new();
+    header(&lines[0], &mut ev);
+
+    let mut types = Vec::new();
+    for l in lines.iter().filter(|l| l.kind != "EOE") {
+        types.push(Value::from(l.kind));
+        let mut f = Map::new();
+        fields(l.kind, l.body, &mut f);
+
+        if l.kind == "EXECVE" {
+            let argc = f.get("argc").and_then(Value::as_u64).unwrap_or(0);
+            let args: Vec<&str> = (0..argc)
+                .filter_map(|i| f.get(&format!("a{i}")).and_then(Value::as_str))
+                .collect();
+            ev.insert("cmdline".into(), args.join(" ").into());
+        }
+
+        let key = l.kind.to_ascii_lowercase();
+        match ev.get_mut(&key) {
+            Some(Value::Array(items)) => items.push(f.into()),
+            Some(first) => *first = Value::Array(vec![first.take(), f.into()]),
+            None => {
+                ev.insert(key, f.into());
+            }
+        }
+    }
+    ev.insert("types".into(), types.into());
+    ev
+}
+
+impl Parser for Auditd {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("auditd")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses Linux audit.log records -> JSONL, hex decoded, optional event merge")
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        OPTIONS
+    }
+
+    fn configure(&mut self, opts: &ModuleOptions) -> Result<()> {
+        if let Some(m) = opts.flag("merge")? {
+            self.merge = m;
+        }
+        Ok(())
+    }
+
+    fn line_joiner(&self) -> Option<Box<dyn LineJoiner>> {
+        self.merge
+            .then(|| Box::new(GroupJoiner::new(event_key, MAX_PENDING)) as Box<dyn LineJoiner>)
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["addr".to_string()]
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let lines: Option<Vec<AuditLine>> = line
+            .split('\n')
+            .map(|l| split_audit(l.strip_suffix('\r').unwrap_or(l)))
+            .collect();
+        let rec = match lines.as_deref() {
+            Some([]) | None => return false,
+            Some([l]) if !self.merge => record(l),
+            Some(lines) => event(lines),
+        };
+
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::LinePos;
+
+    const EVENT: &[&str] = &[
+        r#"type=SYSCALL msg=audit(1364481363.243:24287): arch=c000003e syscall=2 success=no exit=-13 a0=7fffd19c5592 a1=0 ppid=2686 pid=3538 auid=1000 uid=1000 comm="cat" exe="/usr/bin/cat" key="sshd_config""#,
+        "type=CWD msg=audit(1364481363.243:24287): cwd=\"/home/shadowman\"",
+        r#"type=EXECVE msg=audit(1364481363.243:24287): argc=2 a0="cat" a1=2F6574632F7373682F737368645F636F6E666967"#,
+        r#"type=PATH msg=audit(1364481363.243:24287): item=0 name="/etc/ssh/sshd_config" inode=409248"#,
+        r#"type=PATH msg=audit(1364481363.243:24287): item=1 name=(null) inode=409249"#,
+        "type=PROCTITLE msg=audit(1364481363.243:24287): proctitle=636174002F6574632F7373682F737368645F636F6E666967",
+        "type=EOE msg=audit(1364481363.243:24287): ",
+    ];
+
+    fn run(p: &Auditd, line: &str) -> Option<Value> {
+        let mut out = Vec::new();
+        p.process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn parses_single_records_and_decodes_hex() {
+        let p = Auditd { merge: false };
+        let v = run(&p, EVENT[0]).unwrap();
+        assert_eq!(v["type"], "SYSCALL");
+        assert_eq!(v["ts"], "2013-03-28T14:36:03.243Z");
+        assert_eq!(v["serial"], 24287);
+        assert_eq!(v["exit"], -13);
+        assert_eq!(v["success"], "no");
+        assert_eq!(v["exe"], "/usr/bin/cat");
+
+        let v = run(&p, EVENT[5]).unwrap();
+        assert_eq!(v["proctitle"], "cat /etc/ssh/sshd_config");
+        let v = run(&p, EVENT[4]).unwrap();
+        assert!(v["name"].is_null());
+
+        let v = run(&p, "node=web1 type=USER_AUTH msg=audit(1700000000.001:77): pid=1 uid=0 auid=4294967295 ses=4294967295 msg='op=PAM:authentication grantors=? acct=\"root\" exe=\"/usr/sbin/sshd\" addr=203.0.113.7 terminal=ssh res=failed'\u{1d}UID=\"root\" AUID=\"unset\"").unwrap();
+        assert_eq!(v["node"], "web1");
+        assert_eq!(v["op"], "PAM:authentication");
+        assert_eq!(v["acct"], "root");
+        assert_eq!(v["addr"], "203.0.113.7");
+        assert_eq!(v["res"], "failed");
+        assert!(v["grantors"].is_null());
+        assert_eq!(v["AUID"], "unset");
+
+        assert!(run(&p, "Mar  1 10:00:00 srv sshd[1]: hello").is_none());
+    }
+
+    #[test]
+    fn merges_event_records() {
+        let mut p = Auditd { merge: false };
+        p.configure(&ModuleOptions::parse(&["merge=1"]).unwrap())
+            .unwrap();
+
+        let mut j = p.line_joiner().unwrap();
+        let mut events = Vec::new();
+        let login = "type=USER_LOGIN msg=audit(1364481364.000:24288): pid=1 res=success";
+        for l in EVENT.iter().take(3).chain([&login]).chain(&EVENT[3..]) {
+            j.push(l.as_bytes(), LinePos::default(), &mut |r, _| {
+                events.push(String::from_utf8(r.to_vec()).unwrap())
+            });
+        }
+        j.finish(&mut |r, _| events.push(String::from_utf8(r.to_vec()).unwrap()));
+        assert_eq!(events.len(), 2);
+
+        let v = run(&p, &events[0]).unwrap();
+        assert_eq!(v["types"], serde_json::json!(["USER_LOGIN"]));
+
+        let v = run(&p, &events[1]).unwrap();
+        assert_eq!(v["serial"], 24287);
+        assert_eq!(
+            v["types"],
+            serde_json::json!(["SYSCALL", "CWD", "EXECVE", "PATH", "PATH", "PROCTITLE"])
+        );
+        assert_eq!(v["syscall"]["comm"], "cat");
+        assert_eq!(v["cwd"]["cwd"], "/home/shadowman");
+        assert_eq!(v["cmdline"], "cat /etc/ssh/sshd_config");
+        assert_eq!(v["path"][1]["inode"], 409249);
+        assert_eq!(v["proctitle"]["proctitle"], "cat /etc/ssh/sshd_config");
+    }
+}
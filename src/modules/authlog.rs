@@ -0,0 +1,389 @@
+use crate::core::Parser;
+use crate::modules::common::{split_syslog, Syslog};
+use serde::Serialize;
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(AuthLog)
+}
+
+/// Linux `auth.log` / `secure`: sshd logins, PAM sessions, sudo and su,
+/// normalized into one `event` vocabulary. Lines from other programs are
+/// rejected unless they are PAM messages.
+pub struct AuthLog;
+
+#[derive(Default, Serialize)]
+struct Record<'a> {
+    #[serde(flatten)]
+    syslog: Option<Syslog<'a>>,
+    /// `ssh_login`, `ssh_invalid_user`, `ssh_disconnect`, `session_opened`,
+    /// `session_closed`, `auth_failure`, `sudo`, `su` or `other`.
+    event: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    outcome: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    invalid_user: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    auth_method: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    src_ip: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    src_port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key_type: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key_fingerprint: Option<&'a str>,
+    /// PAM service (`sshd`, `sudo`, `cron`, ...).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pam_service: Option<&'a str>,
+    /// sudo `USER=` / su `(to ...)` / PAM `session opened for user`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target_user: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tty: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pwd: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    command: Option<&'a str>,
+    /// Why sudo / su refused.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'a str>,
+    message: &'a str,
+}
+
+impl Parser for AuthLog {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("authlog")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses auth.log sshd/sudo/su/PAM lines -> normalized JSONL")
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["src_ip".to_string()]
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let line = line.strip_suffix('\r').unwrap_or(line).trim();
+        let Some(rec) = parse_auth(line) else {
+            return false;
+        };
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+fn parse_auth(line: &str) -> Option<Record<'_>> {
+    let (syslog, message) = split_syslog(line)?;
+    let program = syslog.program;
+    let mut rec = Record {
+        event: "other",
+        message,
+        ..Record::default()
+    };
+
+    if message.starts_with("pam_") {
+        pam(message, &mut rec);
+    } else {
+        match program {
+            "sshd" => sshd(message, &mut rec),
+            "sudo" => sudo(message, &mut rec),
+            "su" => su(message, &mut rec),
+            _ => return None,
+        };
+    }
+    rec.syslog = Some(syslog);
+    Some(rec)
+}
+
+/// `<user> from <ip> port <port>`; the user may be missing.
+fn user_from<'a>(s: &'a str, rec: &mut Record<'a>) {
+    let (user, addr) = match s.rsplit_once(" from ") {
+        Some((user, addr)) => (Some(user), addr),
+        None => (None, s.strip_prefix("from ").unwrap_or(s)),
+    };
+    let user = user.map(|u| match u.strip_prefix("invalid user ") {
+        Some(u) => {
+            rec.invalid_user = Some(true);
+            u
+        }
+        None => u,
+    });
+    rec.user = user.filter(|u| !u.is_empty());
+    address(addr, rec);
+}
+
+/// `<ip> port <port>[ ...]`.
+fn address<'a>(s: &'a str, rec: &mut Record<'a>) {
+    let mut parts = s.split(' ');
+    rec.src_ip = parts.next().filter(|ip| !ip.is_empty());
+    if parts.next() == Some("port") {
+        rec.src_port = parts
+            .next()
+            .and_then(|p| p.trim_end_matches(':').split(':').next())
+            .and_then(|p| p.parse().ok());
+    }
+}
+
+fn sshd<'a>(msg: &'a str, rec: &mut Record<'a>) {
+    let msg = msg.trim_end_matches(" [preauth]");
+
+    let outcome = if msg.starts_with("Accepted ") {
+        Some("success")
+    } else if msg.starts_with("Failed ") {
+        Some("failure")
+    } else {
+        None
+    };
+    if let Some(outcome) = outcome {
+        let rest = msg.split_once(' ').map_or("", |(_, r)| r);
+        let Some((method, rest)) = rest.split_once(" for ") else {
+            return;
+        };
+        rec.event = "ssh_login";
+        rec.outcome = Some(outcome);
+        rec.auth_method = Some(method);
+
+        // `... port 22 ssh2: RSA SHA256:...`
+        let (rest, key) = match rest.split_once(": ") {
+            Some((rest, key)) => (rest, Some(key)),
+            None => (rest, None),
+        };
+        let rest = rest.strip_suffix(" ssh2").unwrap_or(rest);
+        user_from(rest, rec);
+        if let Some((kind, fp)) = key.and_then(|k| k.split_once(' ')) {
+            rec.key_type = Some(kind);
+            rec.key_fingerprint = Some(fp);
+        }
+        if rec.invalid_user.is_none() {
+            rec.invalid_user = Some(false);
+        }
+        return;
+    }
+
+    if let Some(rest) = msg.strip_prefix("Invalid user ") {
+        rec.event = "ssh_invalid_user";
+        rec.outcome = Some("failure");
+        rec.invalid_user = Some(true);
+        user_from(rest, rec);
+        return;
+    }
+
+    let disconnect = [
+        "Connection closed by ",
+        "Disconnected from ",
+        "Received disconnect from ",
+    ]
+    .iter()
+    .find_map(|p| msg.strip_prefix(p));
+    if let Some(rest) = disconnect {
+        rec.event = "ssh_disconnect";
+        let who = ["authenticating user ", "invalid user ", "user "]
+            .into_iter()
+            .find(|p| rest.starts_with(p));
+        match who {
+            Some(p) => {
+                rec.invalid_user = Some(p == "invalid user ");
+                let rest = &rest[p.len()..];
+                let (user, addr) = rest.split_once(' ').unwrap_or((rest, ""));
+                rec.user = Some(user);
+                address(addr, rec);
+            }
+            None => address(rest, rec),
+        }
+    }
+}
+
+/// `pam_unix(sshd:session): session opened for user bob(uid=1000) by (uid=0)`.
+fn pam<'a>(msg: &'a str, rec: &mut Record<'a>) {
+    let Some((module, text)) = msg.split_once("): ") else {
+        return;
+    };
+    rec.pam_service = module
+        .split_once('(')
+        .and_then(|(_, svc)| svc.split(':').next());
+
+    let user = |s: &'a str| s.split(['(', ' ']).next().filter(|u| !u.is_empty());
+    if let Some(rest) = text.strip_prefix("session opened for user ") {
+        rec.event = "session_opened";
+        rec.target_user = user(rest);
+        rec.user = rest.split_once(" by ").and_then(|(_, by)| user(by));
+    } else if let Some(rest) = text.strip_prefix("session closed for user ") {
+        rec.event = "session_closed";
+        rec.target_user = user(rest);
+    } else if let Some(rest) = text.strip_prefix("authentication failure;") {
+        rec.event = "auth_failure";
+        rec.outcome = Some("failure");
+        for kv in rest.split_ascii_whitespace() {
+            match kv.split_once('=') {
+                Some(("rhost", v)) if !v.is_empty() => rec.src_ip = Some(v),
+                Some(("user", v)) if !v.is_empty() => rec.target_user = Some(v),
+                Some(("ruser", v)) if !v.is_empty() => rec.user = Some(v),
+                Some(("tty", v)) if !v.is_empty() => rec.tty = Some(v),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// `bob : TTY=pts/0 ; PWD=/home/bob ; USER=root ; COMMAND=/bin/ls -la`,
+/// with a leading reason on refusals (`3 incorrect password attempts ; ...`).
+fn sudo<'a>(msg: &'a str, rec: &mut Record<'a>) {
+    let Some((user, rest)) = msg.split_once(" : ") else {
+        return;
+    };
+    rec.event = "sudo";
+    // sudo pads the user name to line up the columns.
+    rec.message = msg.trim();
+    rec.user = Some(user.trim());
+
+    for part in rest.split(" ; ") {
+        match part.split_once('=') {
+            Some(("TTY", v)) => rec.tty = Some(v),
+            Some(("PWD", v)) => rec.pwd = Some(v),
+            Some(("USER", v)) => rec.target_user = Some(v),
+            Some(("COMMAND", v)) => rec.command = Some(v),
+            Some(_) => {}
+            None => rec.reason = Some(part.trim()),
+        }
+    }
+    rec.outcome = Some(if rec.reason.is_some() {
+        "failure"
+    } else {
+        "success"
+    });
+}
+
+/// `(to root) bob on pts/0`, `FAILED SU (to root) bob on pts/0`, or the older
+/// `Successful su for root by bob` / `FAILED su for root by bob`.
+fn su<'a>(msg: &'a str, rec: &mut Record<'a>) {
+    let (mut failed, rest) = match msg.strip_prefix("FAILED SU ") {
+        Some(rest) => (true, rest),
+        None => (false, msg),
+    };
+    if let Some((target, rest)) = rest.strip_prefix("(to ").and_then(|r| r.split_once(") ")) {
+        let (user, tty) = rest.split_once(" on ").unwrap_or((rest, ""));
+        rec.user = Some(user);
+        rec.tty = (!tty.is_empty()).then_some(tty);
+        rec.target_user = Some(target);
+    } else if let Some((result, rest)) = msg.split_once(" su for ") {
+        let (target, user) = rest.split_once(" by ").unwrap_or((rest, ""));
+        rec.user = (!user.is_empty()).then_some(user);
+        rec.target_user = Some(target);
+        failed = result == "FAILED";
+    } else {
+        return;
+    }
+    rec.event = "su";
+    rec.outcome = Some(if failed { "failure" } else { "success" });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn run(line: &str) -> Option<Value> {
+        let mut out = Vec::new();
+        AuthLog
+            .process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn ssh_logins() {
+        let v = run("Mar  1 10:00:00 srv sshd[100]: Accepted publickey for bob from 198.51.100.4 port 50022 ssh2: RSA SHA256:abcDEF").unwrap();
+        assert_eq!(v["event"], "ssh_login");
+        assert_eq!(v["outcome"], "success");
+        assert_eq!(v["auth_method"], "publickey");
+        assert_eq!(v["user"], "bob");
+        assert_eq!(v["invalid_user"], false);
+        assert_eq!(v["src_ip"], "198.51.100.4");
+        assert_eq!(v["src_port"], 50022);
+        assert_eq!(v["key_type"], "RSA");
+        assert_eq!(v["key_fingerprint"], "SHA256:abcDEF");
+        assert_eq!(v["host"], "srv");
+
+        let v = run("Mar  1 10:00:01 srv sshd[101]: Failed password for invalid user admin from 203.0.113.7 port 4242 ssh2").unwrap();
+        assert_eq!(v["outcome"], "failure");
+        assert_eq!(v["user"], "admin");
+        assert_eq!(v["invalid_user"], true);
+        assert_eq!(v["src_ip"], "203.0.113.7");
+
+        let v =
+            run("Mar  1 10:00:01 srv sshd[101]: Invalid user oracle from 203.0.113.7 port 4243")
+                .unwrap();
+        assert_eq!(v["event"], "ssh_invalid_user");
+        assert_eq!(v["user"], "oracle");
+        assert_eq!(v["src_port"], 4243);
+
+        let v = run("Mar  1 10:00:02 srv sshd[101]: Connection closed by authenticating user root 203.0.113.7 port 4244 [preauth]").unwrap();
+        assert_eq!(v["event"], "ssh_disconnect");
+        assert_eq!(v["user"], "root");
+        assert_eq!(v["src_port"], 4244);
+
+        let v = run("Mar  1 10:00:02 srv sshd[101]: Received disconnect from 203.0.113.7 port 4245:11: Bye Bye [preauth]").unwrap();
+        assert_eq!(v["src_ip"], "203.0.113.7");
+        assert_eq!(v["src_port"], 4245);
+    }
+
+    #[test]
+    fn pam_sessions_and_failures() {
+        let v = run("Mar  1 10:00:00 srv sshd[100]: pam_unix(sshd:session): session opened for user bob(uid=1000) by (uid=0)").unwrap();
+        assert_eq!(v["event"], "session_opened");
+        assert_eq!(v["pam_service"], "sshd");
+        assert_eq!(v["target_user"], "bob");
+
+        let v = run(
+            "Mar  1 10:05:00 srv CRON[7]: pam_unix(cron:session): session closed for user root",
+        )
+        .unwrap();
+        assert_eq!(v["event"], "session_closed");
+        assert_eq!(v["pam_service"], "cron");
+
+        let v = run("Mar  1 10:00:00 srv sshd[100]: pam_unix(sshd:auth): authentication failure; logname= uid=0 euid=0 tty=ssh ruser= rhost=203.0.113.7  user=root").unwrap();
+        assert_eq!(v["event"], "auth_failure");
+        assert_eq!(v["src_ip"], "203.0.113.7");
+        assert_eq!(v["target_user"], "root");
+    }
+
+    #[test]
+    fn sudo_and_su() {
+        let v = run("Mar  1 10:00:00 srv sudo:      bob : TTY=pts/0 ; PWD=/home/bob ; USER=root ; COMMAND=/bin/cat /etc/shadow").unwrap();
+        assert_eq!(v["event"], "sudo");
+        assert_eq!(v["outcome"], "success");
+        assert_eq!(v["user"], "bob");
+        assert_eq!(v["target_user"], "root");
+        assert_eq!(v["command"], "/bin/cat /etc/shadow");
+        assert_eq!(v["pwd"], "/home/bob");
+        assert_eq!(
+            v["message"],
+            "bob : TTY=pts/0 ; PWD=/home/bob ; USER=root ; COMMAND=/bin/cat /etc/shadow"
+        );
+
+        let v = run("Mar  1 10:00:00 srv sudo:      eve : 3 incorrect password attempts ; TTY=pts/1 ; PWD=/tmp ; USER=root ; COMMAND=/bin/sh").unwrap();
+        assert_eq!(v["outcome"], "failure");
+        assert_eq!(v["reason"], "3 incorrect password attempts");
+
+        let v = run("Mar  1 10:00:00 srv su[9]: FAILED SU (to root) eve on pts/1").unwrap();
+        assert_eq!(v["event"], "su");
+        assert_eq!(v["outcome"], "failure");
+        assert_eq!(v["user"], "eve");
+        assert_eq!(v["target_user"], "root");
+        assert_eq!(v["tty"], "pts/1");
+    }
+
+    #[test]
+    fn rejects_unrelated_programs() {
+        assert!(run("Mar  1 10:00:00 srv kernel: [1.0] usb 1-1: new device").is_none());
+        assert!(run("not syslog").is_none());
+        let v = run("Mar  1 10:00:00 srv sshd[1]: Server listening on 0.0.0.0 port 22.").unwrap();
+        assert_eq!(v["event"], "other");
+    }
+}
@@ -0,0 +1,266 @@
+use crate::core::Parser;
+use crate::modules::common::{rfc3339_to_utc, split_syslog};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use time::PrimitiveDateTime;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(Bind)
+}
+
+/// BIND `querylog` lines, written to a channel file (with `print-time`,
+/// `print-category`, `print-severity`) or through syslog:
+/// `client @0x7f.. 192.0.2.10#53422 (example.com): view int: query:
+/// example.com IN A +E(0)K (10.0.0.1)`.
+pub struct Bind;
+
+/// `20-Jan-2024 10:11:12.345` (local time) -> `2024-01-20T10:11:12.345`;
+/// `print-time iso8601-utc` stamps become RFC 3339 UTC.
+fn normalize_time(value: &str) -> Option<String> {
+    let fmt = time::macros::format_description!(
+        "[day]-[month repr:short]-[year] [hour]:[minute]:[second][optional [.[subsecond]]]"
+    );
+    match PrimitiveDateTime::parse(value, &fmt) {
+        Ok(t) => Some(format!("{}T{}", t.date(), value.split_once(' ')?.1)),
+        Err(_) => rfc3339_to_utc(value),
+    }
+}
+
+/// Split off the `print-time` stamp and the category / severity prefixes.
+fn strip_prefix(line: &str) -> (Option<&str>, &str) {
+    let mut ts = None;
+    let mut rest = line;
+    if !rest.starts_with("client ") {
+        // `20-Jan-2024 10:11:12.345 ` is two tokens, ISO 8601 one.
+        let mut end = rest.find(' ').unwrap_or(0);
+        if rest[..end].contains('-') && !rest[..end].contains('T') {
+            end += rest[end + 1..].find(' ').map_or(0, |n| n + 1);
+        }
+        if end > 0 && normalize_time(&rest[..end]).is_some() {
+            ts = Some(&rest[..end]);
+            rest = &rest[end + 1..];
+        }
+    }
+    for prefix in ["queries: ", "query-errors: ", "info: "] {
+        rest = rest.strip_prefix(prefix).unwrap_or(rest);
+    }
+    (ts, rest)
+}
+
+/// `+E(0)K` -> (recursion desired, EDNS version, TCP, DO, CD).
+fn decode_flags(flags: &str) -> (bool, Option<u8>, bool, bool, bool) {
+    let edns = flags
+        .split_once("E(")
+        .and_then(|(_, v)| v.split_once(')'))
+        .and_then(|(v, _)| v.parse().ok());
+    // Drop the `(n)` so digits can't be mistaken for flags.
+    let letters: String = match flags.split_once('(') {
+        Some((a, b)) => format!("{a}{}", b.split_once(')').map_or("", |(_, r)| r)),
+        None => flags.to_string(),
+    };
+    (
+        flags.starts_with('+'),
+        edns,
+        letters.contains('T'),
+        letters.contains('D'),
+        letters.contains('C'),
+    )
+}
+
+impl Parser for Bind {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("bind")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses BIND querylog lines (file or syslog) -> JSONL")
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["client_ip".to_string(), "server_ip".to_string()]
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let mut rec = Map::new();
+        let (ts, rest) = match split_syslog(line) {
+            Some((syslog, message)) if message.contains("client ") => {
+                // Only an RFC 3339 syslog stamp has a year and a zone.
+                rec.insert(
+                    "ts".to_string(),
+                    normalize_time(syslog.syslog_timestamp).into(),
+                );
+                if let Ok(Value::Object(fields)) = serde_json::to_value(&syslog) {
+                    rec.extend(fields);
+                }
+                strip_prefix(message)
+            }
+            _ => strip_prefix(line),
+        };
+        if let Some(ts) = ts {
+            rec.insert("ts".to_string(), normalize_time(ts).into());
+        }
+        rec.entry("ts").or_insert(Value::Null);
+
+        let Some(rest) = rest.strip_prefix("client ") else {
+            return false;
+        };
+        let (client_id, rest) = match rest.strip_prefix('@') {
+            Some(r) => match r.split_once(' ') {
+                Some((id, r)) => (Some(id), r),
+                None => return false,
+            },
+            None => (None, rest),
+        };
+        let Some((ip, rest)) = rest.split_once('#') else {
+            return false;
+        };
+        let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+        let Ok(port) = rest[..digits].parse::<u16>() else {
+            return false;
+        };
+        let rest = &rest[digits..];
+        // `(qname): ` since 9.10; `view v: ` when views are configured.
+        let rest = rest.trim_start_matches(':').trim_start();
+        let rest = match rest.strip_prefix('(') {
+            Some(r) => match r.split_once("): ") {
+                Some((_, r)) => r,
+                None => return false,
+            },
+            None => rest,
+        };
+        let (view, rest) = match rest.strip_prefix("view ") {
+            Some(r) => match r.split_once(": ") {
+                Some((v, r)) => (Some(v), r),
+                None => return false,
+            },
+            None => (None, rest),
+        };
+        let Some(query) = rest.strip_prefix("query: ") else {
+            return false;
+        };
+        // Newer releases append the EDNS client subnet: `[ECS 1.2.3.0/24/0]`.
+        let (query, ecs) = match query.rsplit_once(" [ECS ") {
+            Some((q, e)) => (q, e.strip_suffix(']')),
+            None => (query, None),
+        };
+        let mut tokens = query.rsplitn(5, ' ');
+        let (Some(server), Some(flags), Some(qtype), Some(qclass), Some(qname)) = (
+            tokens.next(),
+            tokens.next(),
+            tokens.next(),
+            tokens.next(),
+            tokens.next(),
+        ) else {
+            return false;
+        };
+        let Some(server) = server.strip_prefix('(').and_then(|s| s.strip_suffix(')')) else {
+            return false;
+        };
+        if !flags.starts_with(['+', '-']) {
+            return false;
+        }
+        let (rd, edns, tcp, dnssec_ok, cd) = decode_flags(flags);
+
+        let fields = serde_json::json!({
+            "client_ip": ip,
+            "client_port": port,
+            "client_id": client_id,
+            "view": view,
+            "qname": qname,
+            "qclass": qclass,
+            "qtype": qtype,
+            "flags": flags,
+            "recursion_desired": rd,
+            "edns_version": edns,
+            "tcp": tcp,
+            "dnssec_ok": dnssec_ok,
+            "checking_disabled": cd,
+            "server_ip": server,
+            "ecs": ecs,
+        });
+        if let Value::Object(fields) = fields {
+            rec.extend(fields);
+        }
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(line: &str) -> Option<Value> {
+        let mut out = Vec::new();
+        Bind.process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn parses_channel_file_lines() {
+        let v = run("20-Jan-2024 10:11:12.345 queries: info: client @0x7f8b2c0a1b10 192.0.2.10#53422 (www.example.com): view internal: query: www.example.com IN AAAA +E(0)DK (10.0.0.1)").unwrap();
+        assert_eq!(v["ts"], "2024-01-20T10:11:12.345");
+        assert_eq!(v["client_ip"], "192.0.2.10");
+        assert_eq!(v["client_port"], 53422);
+        assert_eq!(v["client_id"], "0x7f8b2c0a1b10");
+        assert_eq!(v["view"], "internal");
+        assert_eq!(v["qname"], "www.example.com");
+        assert_eq!(v["qclass"], "IN");
+        assert_eq!(v["qtype"], "AAAA");
+        assert_eq!(v["flags"], "+E(0)DK");
+        assert_eq!(v["recursion_desired"], true);
+        assert_eq!(v["edns_version"], 0);
+        assert_eq!(v["dnssec_ok"], true);
+        assert_eq!(v["tcp"], false);
+        assert_eq!(v["server_ip"], "10.0.0.1");
+
+        let v = run("2024-01-20T10:11:12.345Z client 2001:db8::5#5353: query: example.org IN MX -T (2001:db8::1) [ECS 198.51.100.0/24/0]").unwrap();
+        assert_eq!(v["ts"], "2024-01-20T10:11:12.345Z");
+        assert_eq!(v["client_ip"], "2001:db8::5");
+        assert_eq!(v["view"], Value::Null);
+        assert_eq!(v["recursion_desired"], false);
+        assert_eq!(v["tcp"], true);
+        assert_eq!(v["edns_version"], Value::Null);
+        assert_eq!(v["ecs"], "198.51.100.0/24/0");
+    }
+
+    #[test]
+    fn parses_syslog_lines_and_rejects_others() {
+        let v = run("Jan 20 10:11:12 ns1 named[812]: client @0x7f1 192.0.2.10#53422 (example.com): query: example.com IN A +E(0)K (10.0.0.1)").unwrap();
+        assert_eq!(v["ts"], Value::Null);
+        assert_eq!(v["host"], "ns1");
+        assert_eq!(v["program"], "named");
+        assert_eq!(v["qtype"], "A");
+        assert!(
+            run("Jan 20 10:11:12 ns1 named[812]: zone example.com/IN: loaded serial 1").is_none()
+        );
+        assert!(
+            run("20-Jan-2024 10:11:12.345 client 192.0.2.10#53422: update 'x/IN' denied").is_none()
+        );
+        assert!(run("client 192.0.2.10#x: query: a IN A + (1.2.3.4)").is_none());
+    }
+
+    #[test]
+    fn rejects_malformed_timestamps() {
+        assert_eq!(
+            normalize_time("20-Jan-2024 10:11:12"),
+            Some("2024-01-20T10:11:12".into())
+        );
+        for ts in [
+            "20-Jan-2024 ab:cd:ef.345",
+            "20-Jan-2024 25:11:12.345",
+            "31-Feb-2024 10:11:12.345",
+            "20-Jan-2024T10:11:12",
+        ] {
+            assert_eq!(normalize_time(ts), None, "{ts}");
+        }
+        assert!(
+            run("20-Jan-2024 ab:cd:ef.345 client 192.0.2.10#53422: query: a IN A + (1.2.3.4)")
+                .is_none()
+        );
+    }
+}
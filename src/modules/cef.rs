@@ -0,0 +1,258 @@
+use crate::core::Parser;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde::Serialize as DeriveSerialize;
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(Cef)
+}
+
+/// ArcSight Common Event Format:
+/// `CEF:Version|Vendor|Product|DeviceVersion|SignatureID|Name|Severity|Extension`,
+/// optionally behind a syslog header.
+pub struct Cef;
+
+impl Parser for Cef {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("cef")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses CEF (Common Event Format) events -> JSONL, header + extension")
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let s = trim_cr(line).trim();
+        let Some(rec) = parse_cef(s) else {
+            return false;
+        };
+
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+#[derive(DeriveSerialize)]
+struct Record<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    syslog_prefix: Option<&'a str>,
+    cef_version: Cow<'a, str>,
+    device_vendor: Cow<'a, str>,
+    device_product: Cow<'a, str>,
+    device_version: Cow<'a, str>,
+    signature_id: Cow<'a, str>,
+    name: Cow<'a, str>,
+    severity: Cow<'a, str>,
+    extension: OrderedMap<'a>,
+    raw: &'a str,
+}
+
+fn parse_cef(line: &str) -> Option<Record<'_>> {
+    let start = line.find("CEF:")?;
+    let prefix = line[..start].trim();
+    let (header, ext) = split_header::<7>(&line[start + 4..])?;
+
+    Some(Record {
+        syslog_prefix: (!prefix.is_empty()).then_some(prefix),
+        cef_version: unescape_header(header[0]),
+        device_vendor: unescape_header(header[1]),
+        device_product: unescape_header(header[2]),
+        device_version: unescape_header(header[3]),
+        signature_id: unescape_header(header[4]),
+        name: unescape_header(header[5]),
+        severity: unescape_header(header[6]),
+        extension: OrderedMap(parse_extension(ext)),
+        raw: line,
+    })
+}
+
+/// Split the first `N` `|`-separated header fields (honouring `\|` and `\\`)
+/// from the remainder of the line. Fields are still escaped.
+pub(crate) fn split_header<const N: usize>(s: &str) -> Option<([&str; N], &str)> {
+    let b = s.as_bytes();
+    let mut fields = [""; N];
+    let mut field = 0;
+    let mut start = 0;
+    let mut i = 0;
+
+    while i < b.len() {
+        match b[i] {
+            b'\\' => i += 2,
+            b'|' => {
+                fields[field] = &s[start..i];
+                field += 1;
+                start = i + 1;
+                if field == N {
+                    return Some((fields, &s[start..]));
+                }
+                i += 1;
+            }
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// Header fields escape only `|` and `\`.
+pub(crate) fn unescape_header(s: &str) -> Cow<'_, str> {
+    unescape(s, |c| matches!(c, '|' | '\\').then_some(c))
+}
+
+/// Extension values escape `=`, `\`, newlines and carriage returns.
+fn unescape_value(s: &str) -> Cow<'_, str> {
+    unescape(s, |c| match c {
+        '=' | '\\' | '|' => Some(c),
+        'n' => Some('\n'),
+        'r' => Some('\r'),
+        _ => None,
+    })
+}
+
+/// Replace `\x` sequences `decode` knows; keep unknown ones as written.
+fn unescape(s: &str, decode: impl Fn(char) -> Option<char>) -> Cow<'_, str> {
+    if !s.contains('\\') {
+        return Cow::Borrowed(s);
+    }
+
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(n) => match decode(n) {
+                Some(d) => out.push(d),
+                None => {
+                    out.push('\\');
+                    out.push(n);
+                }
+            },
+            None => out.push('\\'),
+        }
+    }
+    Cow::Owned(out)
+}
+
+fn is_key_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'_' | b'.' | b'-' | b'[' | b']')
+}
+
+/// Parse `k1=v1 k2=value with spaces k3=v3`.
+///
+/// Values may contain spaces, so a value runs until the next ` key=`. An
+/// unescaped `=` not preceded by a key-like word stays part of the value.
+/// A repeated key keeps its last value.
+fn parse_extension(s: &str) -> Vec<(&str, Cow<'_, str>)> {
+    let b = s.as_bytes();
+
+    // (key start, index of '=') for every key
+    let mut keys = Vec::new();
+    let mut i = 0;
+    while i < b.len() {
+        match b[i] {
+            b'\\' => i += 2,
+            b'=' => {
+                let mut j = i;
+                while j > 0 && is_key_byte(b[j - 1]) {
+                    j -= 1;
+                }
+                if j < i && (j == 0 || b[j - 1] == b' ') {
+                    keys.push((j, i));
+                }
+                i += 1;
+            }
+            _ => i += 1,
+        }
+    }
+
+    let mut fields: Vec<(&str, Cow<'_, str>)> = Vec::with_capacity(keys.len());
+    for (n, &(key_start, eq)) in keys.iter().enumerate() {
+        let end = keys.get(n + 1).map_or(s.len(), |&(next, _)| next);
+        let key = &s[key_start..eq];
+        let value = unescape_value(s[eq + 1..end].trim_end_matches(' '));
+
+        match fields.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, slot)) => *slot = value,
+            None => fields.push((key, value)),
+        }
+    }
+    fields
+}
+
+/// Key/value pairs serialized as a JSON object in their original order.
+pub(crate) struct OrderedMap<'a>(pub Vec<(&'a str, Cow<'a, str>)>);
+
+impl Serialize for OrderedMap<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (k, v) in &self.0 {
+            map.serialize_entry(k, v)?;
+        }
+        map.end()
+    }
+}
+
+fn trim_cr(s: &str) -> &str {
+    s.strip_suffix('\r').unwrap_or(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_header_and_extension() {
+        let line = r"Sep 19 08:26:10 host CEF:0|Security|threatmanager|1.0|100|worm successfully stopped|10|src=10.0.0.1 dst=2.1.2.2 spt=1232 msg=Detected a threat. No action needed cs1Label=rule";
+        let rec = parse_cef(line).unwrap();
+
+        assert_eq!(rec.syslog_prefix, Some("Sep 19 08:26:10 host"));
+        assert_eq!(rec.cef_version, "0");
+        assert_eq!(rec.device_vendor, "Security");
+        assert_eq!(rec.signature_id, "100");
+        assert_eq!(rec.name, "worm successfully stopped");
+        assert_eq!(rec.severity, "10");
+
+        let ext = &rec.extension.0;
+        assert_eq!(ext[0], ("src", Cow::Borrowed("10.0.0.1")));
+        assert_eq!(
+            ext[3],
+            ("msg", Cow::Borrowed("Detected a threat. No action needed"))
+        );
+        assert_eq!(ext[4], ("cs1Label", Cow::Borrowed("rule")));
+    }
+
+    #[test]
+    fn honours_header_and_extension_escapes() {
+        let line = r"CEF:0|Ven\|dor|Prod\\uct|1|sig|name|5|filePath=C:\\dir\\a.txt msg=a\=b\nc";
+        let rec = parse_cef(line).unwrap();
+
+        assert_eq!(rec.device_vendor, "Ven|dor");
+        assert_eq!(rec.device_product, r"Prod\uct");
+        let ext = &rec.extension.0;
+        assert_eq!(ext[0].1, r"C:\dir\a.txt");
+        assert_eq!(ext[1].1, "a=b\nc");
+    }
+
+    #[test]
+    fn empty_extension_and_pipes_in_extension() {
+        let rec = parse_cef("CEF:1|V|P|1|s|n|Low|").unwrap();
+        assert!(rec.extension.0.is_empty());
+
+        let rec = parse_cef("CEF:0|V|P|1|s|n|3|request=/a|b act=blocked").unwrap();
+        assert_eq!(rec.extension.0[0].1, "/a|b");
+        assert_eq!(rec.extension.0[1].1, "blocked");
+    }
+
+    #[test]
+    fn rejects_truncated_header() {
+        let mut out = Vec::new();
+        assert!(!Cef.process_line_to_buf("CEF:0|V|P|1|s", &mut out));
+        assert!(!Cef.process_line_to_buf("no cef here", &mut out));
+        assert!(out.is_empty());
+    }
+}
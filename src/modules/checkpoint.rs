@@ -0,0 +1,249 @@
+use crate::core::Parser;
+use crate::modules::common::{epoch_to_rfc3339, rfc3339_to_utc, split_syslog};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(CheckPoint)
+}
+
+/// Check Point Log Exporter output in its syslog layout (`<134>1 ... -
+/// [action:"Accept"; origin:"10.0.0.1"; ...]`) or as `key=value` pairs
+/// separated by `;`, `|` or spaces (the splunk and generic layouts). The
+/// action is lowercased and the connection tuple gets the usual names.
+pub struct CheckPoint;
+
+/// Exporter field -> record field.
+const RENAMES: &[(&str, &str)] = &[("src", "src_ip"), ("dst", "dst_ip"), ("s_port", "src_port")];
+
+/// Ports, counters and ids the exporter writes as strings.
+const INT_KEYS: &[&str] = &[
+    "proto",
+    "s_port",
+    "xlatesport",
+    "xlatedport",
+    "sequencenum",
+    "client_inbound_bytes",
+    "client_outbound_bytes",
+    "server_inbound_bytes",
+    "server_outbound_bytes",
+    "client_inbound_packets",
+    "client_outbound_packets",
+    "server_inbound_packets",
+    "server_outbound_packets",
+    "bytes",
+    "packets",
+    "elapsed",
+];
+
+/// Fields written first, in this order, when present.
+const LEADING: &[&str] = &[
+    "action",
+    "rule_name",
+    "origin",
+    "src_ip",
+    "src_port",
+    "dst_ip",
+    "dst_port",
+    "proto",
+];
+
+/// `k:"v"; k2:"v2"`, `k=v|k2=v2` or `k="v" k2=v2` -> pairs. Quoted values
+/// may hold any separator; `\"` and `\\` are decoded. `None` on an
+/// unterminated quote or a token that isn't a pair.
+fn parse_fields(s: &str) -> Option<Vec<(&str, Cow<'_, str>)>> {
+    let mut pairs = Vec::new();
+    let mut rest = s;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c == ';' || c == '|' || c.is_whitespace());
+        if rest.is_empty() {
+            return Some(pairs);
+        }
+        let end = rest.find([':', '='])?;
+        let key = &rest[..end];
+        if key.is_empty() || key.contains(|c: char| c.is_whitespace() || c == ';' || c == '|') {
+            return None;
+        }
+        rest = &rest[end + 1..];
+        let value = if let Some(quoted) = rest.strip_prefix('"') {
+            let mut value = String::new();
+            let mut chars = quoted.char_indices();
+            let close = loop {
+                match chars.next()? {
+                    (_, '\\') => match chars.next()? {
+                        (_, c @ ('"' | '\\')) => value.push(c),
+                        (_, c) => {
+                            value.push('\\');
+                            value.push(c);
+                        }
+                    },
+                    (i, '"') => break i,
+                    (_, c) => value.push(c),
+                }
+            };
+            rest = &quoted[close + 1..];
+            Cow::Owned(value)
+        } else {
+            let end = rest
+                .find(|c: char| c == ';' || c == '|' || c.is_whitespace())
+                .unwrap_or(rest.len());
+            let value = &rest[..end];
+            rest = &rest[end..];
+            Cow::Borrowed(value)
+        };
+        pairs.push((key, value));
+    }
+}
+
+/// The field list of a line and the header timestamp, when there is one.
+fn payload(line: &str) -> Option<(&str, Option<&str>)> {
+    // RFC 5424: `<PRI>1 TIMESTAMP HOST APP PROCID MSGID [fields]`
+    if let Some(start) = line.find(" [")
+        && let Some(body) = line[start + 2..].strip_suffix(']')
+    {
+        let header = line[..start].split_ascii_whitespace().nth(1);
+        return Some((body, header));
+    }
+    if let Some((_, message)) = split_syslog(line) {
+        return Some((message, None));
+    }
+    let rest = line.strip_prefix('<').and_then(|r| r.split_once('>'));
+    match rest {
+        Some((pri, rest)) if pri.bytes().all(|b| b.is_ascii_digit()) => Some((rest, None)),
+        Some(_) => None,
+        None => Some((line, None)),
+    }
+}
+
+/// `time` is epoch seconds in the syslog layout; RFC 3339 is accepted too.
+fn normalize_time(value: &str) -> Option<String> {
+    if value.bytes().all(|b| b.is_ascii_digit()) {
+        return epoch_to_rfc3339(value);
+    }
+    rfc3339_to_utc(value)
+}
+
+impl Parser for CheckPoint {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("checkpoint")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses Check Point Log Exporter syslog/key=value lines -> normalized JSONL")
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["src_ip".to_string(), "dst_ip".to_string()]
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let Some((body, header_ts)) = payload(line) else {
+            return false;
+        };
+        let Some(pairs) = parse_fields(body) else {
+            return false;
+        };
+        if !pairs.iter().any(|(k, _)| *k == "origin" || *k == "product") {
+            return false;
+        }
+
+        let mut ts = None;
+        let mut fields = Map::new();
+        for (key, value) in pairs {
+            let key = match key {
+                "time" if ts.is_none() => {
+                    ts = normalize_time(&value);
+                    if ts.is_some() {
+                        continue;
+                    }
+                    key
+                }
+                "service" if value.parse::<u16>().is_ok() => "dst_port",
+                _ => key,
+            };
+            let value = match key {
+                "action" => value.to_lowercase().into(),
+                "dst_port" => value.parse::<u16>().map_or(Value::Null, Into::into),
+                _ if INT_KEYS.contains(&key) => value
+                    .parse::<i64>()
+                    .map_or_else(|_| value.into_owned().into(), Into::into),
+                _ => value.into_owned().into(),
+            };
+            let key = RENAMES
+                .iter()
+                .find(|(from, _)| *from == key)
+                .map_or(key, |(_, to)| to);
+            fields.insert(key.to_string(), value);
+        }
+        let ts = ts.or_else(|| header_ts.and_then(normalize_time));
+
+        let mut rec = Map::new();
+        rec.insert("ts".to_string(), ts.map_or(Value::Null, Value::String));
+        for key in LEADING {
+            if let Some(v) = fields.shift_remove(*key) {
+                rec.insert(key.to_string(), v);
+            }
+        }
+        rec.extend(fields);
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYSLOG: &str = r#"<134>1 2021-03-09T10:45:24Z gw-313b4f CheckPoint 24553 - [action:"Accept"; flags:"411908"; ifdir:"inbound"; ifname:"eth1"; loguid:"{0x6047503c,0x0,0x353b1dac,0xc0000000}"; origin:"10.1.1.1"; originsicname:"CN=gw-313b4f,O=mgmt..abc"; sequencenum:"1"; time:"1615286724"; version:"5"; dst:"10.2.2.2"; inzone:"Internal"; layer_name:"Network"; rule_name:"Allow DNS; internal"; outzone:"External"; product:"VPN-1 & FireWall-1"; proto:"17"; s_port:"53211"; service:"53"; service_id:"domain-udp"; src:"10.1.1.5"; msg:"say \"hi\""]"#;
+
+    fn run(line: &str) -> Option<Value> {
+        let mut out = Vec::new();
+        CheckPoint
+            .process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn parses_the_bracketed_syslog_layout() {
+        let v = run(SYSLOG).unwrap();
+        assert_eq!(v["ts"], "2021-03-09T10:45:24Z");
+        assert_eq!(v["action"], "accept");
+        assert_eq!(v["rule_name"], "Allow DNS; internal");
+        assert_eq!(v["origin"], "10.1.1.1");
+        assert_eq!(v["src_ip"], "10.1.1.5");
+        assert_eq!(v["src_port"], 53211);
+        assert_eq!(v["dst_ip"], "10.2.2.2");
+        assert_eq!(v["dst_port"], 53);
+        assert_eq!(v["proto"], 17);
+        assert_eq!(v["service_id"], "domain-udp");
+        assert_eq!(v["product"], "VPN-1 & FireWall-1");
+        assert_eq!(v["msg"], r#"say "hi""#);
+        assert!(v.get("time").is_none() && v.get("src").is_none());
+        let keys: Vec<&String> = v.as_object().unwrap().keys().take(3).collect();
+        assert_eq!(keys, ["ts", "action", "rule_name"]);
+    }
+
+    #[test]
+    fn parses_key_value_layouts() {
+        let v = run("time=1615286724|hostname=gw|product=Firewall|action=Drop|origin=10.1.1.1|src=10.1.1.5|dst=10.2.2.2|service=http|s_port=1234").unwrap();
+        assert_eq!(v["ts"], "2021-03-09T10:45:24Z");
+        assert_eq!(v["action"], "drop");
+        assert_eq!(v["service"], "http");
+        assert_eq!(v["dst_port"], Value::Null);
+        let v = run(r#"Mar  9 10:45:24 gw CheckPoint[123]: action="Reject"; origin="10.1.1.1"; rule_name="Cleanup""#).unwrap();
+        assert_eq!(v["action"], "reject");
+        assert_eq!(v["ts"], Value::Null);
+        assert_eq!(v["rule_name"], "Cleanup");
+    }
+
+    #[test]
+    fn rejects_other_lines() {
+        assert!(run("date=2024-01-02 time=03:04:05 logid=0000000013 action=accept").is_none());
+        assert!(run(r#"action:"Accept"; origin:"unterminated"#).is_none());
+        assert!(run("plain text").is_none());
+    }
+}
@@ -0,0 +1,211 @@
+use crate::core::{ModuleOptions, OptionSpec, Parser};
+use crate::modules::common::{epoch_to_rfc3339, rfc3339_to_utc};
+use anyhow::Result;
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(Cloudflare::default())
+}
+
+/// Cloudflare Logpush `http_requests` jobs: one JSON object per line. The
+/// common fields get the names other web modules use, `EdgeStartTimestamp`
+/// (RFC 3339, `unix` or `unixnano`) becomes `ts`, and noisy fields are
+/// dropped.
+pub struct Cloudflare {
+    drop: Vec<String>,
+}
+
+impl Default for Cloudflare {
+    fn default() -> Self {
+        Self {
+            drop: DEFAULT_DROP.iter().map(|f| f.to_string()).collect(),
+        }
+    }
+}
+
+const OPTIONS: &[OptionSpec] = &[OptionSpec {
+    key: "drop",
+    help: "Comma-separated fields to leave out; empty keeps all \
+           (default: RequestHeaders,ResponseHeaders,Cookies)",
+}];
+
+/// Header and cookie maps: large, and mostly the same on every request.
+const DEFAULT_DROP: &[&str] = &["RequestHeaders", "ResponseHeaders", "Cookies"];
+
+/// Logpush field -> record field.
+const RENAMES: &[(&str, &str)] = &[
+    ("ClientIP", "ip"),
+    ("ClientRequestHost", "vhost"),
+    ("ClientRequestMethod", "method"),
+    ("ClientRequestURI", "target"),
+    ("ClientRequestProtocol", "protocol"),
+    ("EdgeResponseStatus", "status"),
+    ("EdgeResponseBytes", "bytes"),
+    ("ClientRequestReferer", "referer"),
+    ("ClientRequestUserAgent", "user_agent"),
+    ("ClientCountry", "country"),
+    ("WAFAction", "waf_action"),
+    ("RayID", "ray_id"),
+];
+
+/// Timestamps in the job's `timestamp_format`.
+const TIME_FIELDS: &[&str] = &["EdgeStartTimestamp", "EdgeEndTimestamp"];
+
+/// A `unix` or `unixnano` timestamp as epoch seconds with a fraction
+/// (`1704164645.123456789`); milliseconds and microseconds are told apart
+/// by size too. `None` for anything else, RFC 3339 strings included.
+fn epoch_seconds(v: &Value) -> Option<String> {
+    let n = match v {
+        Value::String(s) => s.parse::<i64>().ok()?,
+        Value::Number(n) => match n.as_i64() {
+            Some(n) => n,
+            None => return n.is_f64().then(|| n.to_string()),
+        },
+        _ => return None,
+    };
+    let digits = match n.unsigned_abs() {
+        n if n >= 100_000_000_000_000_000 => 9,
+        n if n >= 100_000_000_000_000 => 6,
+        n if n >= 100_000_000_000 => 3,
+        _ => return Some(n.to_string()),
+    };
+    let unit = 10_i64.pow(digits);
+    Some(format!(
+        "{}.{:0width$}",
+        n.div_euclid(unit),
+        n.rem_euclid(unit),
+        width = digits as usize
+    ))
+}
+
+impl Parser for Cloudflare {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("cloudflare")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Cloudflare Logpush http_requests NDJSON -> normalized JSONL")
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        OPTIONS
+    }
+
+    fn configure(&mut self, opts: &ModuleOptions) -> Result<()> {
+        if let Some(drop) = opts.list("drop") {
+            self.drop = drop;
+        }
+        Ok(())
+    }
+
+    fn recognizes(&self, line: &str) -> bool {
+        line.contains("\"RayID\"") && self.process_line_to_buf(line, &mut Vec::new())
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["ip".to_string()]
+    }
+
+    fn user_agent_fields(&self) -> Vec<String> {
+        vec!["user_agent".to_string()]
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let Ok(Value::Object(mut event)) = serde_json::from_str::<Value>(line.trim()) else {
+            return false;
+        };
+        if !event.contains_key("RayID") && !event.contains_key("EdgeStartTimestamp") {
+            return false;
+        }
+        for f in &self.drop {
+            event.shift_remove(f);
+        }
+
+        // RFC 3339 in UTC, or left as logged if it isn't a timestamp.
+        let normalize = |v: Value| {
+            let ts = match epoch_seconds(&v) {
+                Some(secs) => epoch_to_rfc3339(&secs),
+                None => v.as_str().and_then(rfc3339_to_utc),
+            };
+            ts.map_or(v, Value::String)
+        };
+        let mut rec = Map::new();
+        let ts = event.shift_remove("EdgeStartTimestamp");
+        rec.insert("ts".to_string(), ts.map_or(Value::Null, normalize));
+        for (from, to) in RENAMES {
+            if let Some(v) = event.shift_remove(*from) {
+                rec.insert(to.to_string(), v);
+            }
+        }
+        for (key, v) in event {
+            let v = match TIME_FIELDS.contains(&key.as_str()) {
+                true => normalize(v),
+                false => v,
+            };
+            rec.insert(key, v);
+        }
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REQUEST: &str = r#"{"ClientIP":"198.51.100.4","ClientRequestHost":"www.example.com","ClientRequestMethod":"POST","ClientRequestURI":"/login?next=%2F","ClientRequestProtocol":"HTTP/2","ClientRequestUserAgent":"curl/8.4.0","ClientCountry":"nl","EdgeStartTimestamp":1704164645123456789,"EdgeEndTimestamp":1704164645223456789,"EdgeResponseStatus":403,"EdgeResponseBytes":1024,"OriginResponseStatus":0,"WAFAction":"block","SecurityAction":"block","RayID":"84a1b2c3d4e5f607","CacheCacheStatus":"unknown","RequestHeaders":{"x-forwarded-for":"10.0.0.1"},"Cookies":{}}"#;
+
+    fn run(p: &Cloudflare, line: &str) -> Option<Value> {
+        let mut out = Vec::new();
+        p.process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn maps_common_fields_and_drops_noisy_ones() {
+        let v = run(&Cloudflare::default(), REQUEST).unwrap();
+        assert_eq!(v["ts"], "2024-01-02T03:04:05.123456789Z");
+        assert_eq!(v["EdgeEndTimestamp"], "2024-01-02T03:04:05.223456789Z");
+        assert_eq!(v["ip"], "198.51.100.4");
+        assert_eq!(v["target"], "/login?next=%2F");
+        assert_eq!(v["status"], 403);
+        assert_eq!(v["waf_action"], "block");
+        assert_eq!(v["ray_id"], "84a1b2c3d4e5f607");
+        assert_eq!(v["OriginResponseStatus"], 0);
+        assert!(v.get("RequestHeaders").is_none() && v.get("ClientIP").is_none());
+
+        let mut custom = Cloudflare::default();
+        custom
+            .configure(&ModuleOptions::parse(&["drop=CacheCacheStatus"]).unwrap())
+            .unwrap();
+        let v = run(&custom, REQUEST).unwrap();
+        assert_eq!(v["RequestHeaders"]["x-forwarded-for"], "10.0.0.1");
+        assert!(v.get("CacheCacheStatus").is_none());
+    }
+
+    #[test]
+    fn normalizes_timestamp_formats() {
+        for (ts, want) in [
+            (r#""2024-01-02T04:04:05+01:00""#, "2024-01-02T03:04:05Z"),
+            ("1704164645", "2024-01-02T03:04:05Z"),
+            (r#""1704164645""#, "2024-01-02T03:04:05Z"),
+            ("1704164645123", "2024-01-02T03:04:05.123Z"),
+            ("1704164645000000000", "2024-01-02T03:04:05Z"),
+            ("1704164645.5", "2024-01-02T03:04:05.5Z"),
+            ("-1500", "1969-12-31T23:35:00Z"),
+            (r#""yesterday""#, "yesterday"),
+        ] {
+            let line = format!(r#"{{"RayID":"x","EdgeStartTimestamp":{ts}}}"#);
+            assert_eq!(
+                run(&Cloudflare::default(), &line).unwrap()["ts"],
+                want,
+                "{ts}"
+            );
+        }
+        assert!(run(&Cloudflare::default(), r#"{"eventType":"x"}"#).is_none());
+    }
+}
@@ -0,0 +1,248 @@
+use crate::core::{FieldType, Parser};
+use crate::modules::common::percent_decode;
+use anyhow::Result;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(CloudFront::with_fields(DEFAULT_FIELDS))
+}
+
+/// Amazon CloudFront standard (access) logs: tab-separated, URL-encoded
+/// values, with `#Version:` / `#Fields:` directives at the top of each file.
+pub struct CloudFront {
+    fields: Vec<Field>,
+}
+
+/// Column order of current standard logs, used when a file has no `#Fields:` line.
+const DEFAULT_FIELDS: &str = "date time x-edge-location sc-bytes c-ip cs-method cs(Host) \
+    cs-uri-stem sc-status cs(Referer) cs(User-Agent) cs-uri-query cs(Cookie) \
+    x-edge-result-type x-edge-request-id x-host-header cs-protocol cs-bytes time-taken \
+    x-forwarded-for ssl-protocol ssl-cipher x-edge-response-result-type cs-protocol-version \
+    fle-status fle-encrypted-fields c-port time-to-first-byte x-edge-detailed-result-type \
+    sc-content-type sc-content-len sc-range-start sc-range-end";
+
+const INT_FIELDS: &[&str] = &[
+    "sc-bytes",
+    "sc-status",
+    "cs-bytes",
+    "c-port",
+    "sc-content-len",
+    "sc-range-start",
+    "sc-range-end",
+];
+const FLOAT_FIELDS: &[&str] = &["time-taken", "time-to-first-byte"];
+
+/// Anything shorter is not a CloudFront entry (date .. sc-status).
+const MIN_COLUMNS: usize = 9;
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Text,
+    Int,
+    Float,
+}
+
+struct Field {
+    /// JSON key: `cs(User-Agent)` -> `cs_user_agent`.
+    key: String,
+    kind: Kind,
+}
+
+impl CloudFront {
+    fn with_fields(spec: &str) -> Self {
+        let fields = spec
+            .split_ascii_whitespace()
+            .map(|name| Field {
+                key: json_key(name),
+                kind: if INT_FIELDS.contains(&name) {
+                    Kind::Int
+                } else if FLOAT_FIELDS.contains(&name) {
+                    Kind::Float
+                } else {
+                    Kind::Text
+                },
+            })
+            .collect();
+        Self { fields }
+    }
+}
+
+fn json_key(name: &str) -> String {
+    let mut key = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            key.push(c.to_ascii_lowercase());
+        } else if !key.is_empty() && !key.ends_with('_') {
+            key.push('_');
+        }
+    }
+    key.trim_end_matches('_').to_string()
+}
+
+impl Parser for CloudFront {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("cloudfront")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses CloudFront standard logs (honours #Fields) -> typed JSONL")
+    }
+
+    fn for_input(&self, head: &[&str]) -> Result<Option<Box<dyn Parser>>> {
+        Ok(head
+            .iter()
+            .find_map(|l| l.strip_prefix("#Fields:"))
+            .map(|spec| Box::new(CloudFront::with_fields(spec)) as Box<dyn Parser>))
+    }
+
+    fn recognizes(&self, line: &str) -> bool {
+        line.starts_with("#Fields:") || self.process_line_to_buf(line, &mut Vec::new())
+    }
+
+    fn user_agent_fields(&self) -> Vec<String> {
+        vec!["cs_user_agent".to_string()]
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["c_ip".to_string()]
+    }
+
+    fn fields(&self) -> Vec<(String, FieldType)> {
+        let has = |key: &str| self.fields.iter().any(|f| f.key == key);
+        let ts = (has("date") && has("time")).then(|| ("ts".to_string(), FieldType::Text));
+        ts.into_iter()
+            .chain(self.fields.iter().map(|f| {
+                let ty = match f.kind {
+                    Kind::Text => FieldType::Text,
+                    Kind::Int => FieldType::Int,
+                    Kind::Float => FieldType::Float,
+                };
+                (f.key.clone(), ty)
+            }))
+            .collect()
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        // `#Version:` / `#Fields:` directives carry no event.
+        if line.starts_with('#') {
+            return false;
+        }
+
+        let values: Vec<&str> = line.split('\t').collect();
+        if values.len() < MIN_COLUMNS || !values[0].starts_with(|c: char| c.is_ascii_digit()) {
+            return false;
+        }
+
+        let rec = Record {
+            fields: &self.fields,
+            values: &values,
+        };
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+struct Record<'a> {
+    fields: &'a [Field],
+    values: &'a [&'a str],
+}
+
+impl Serialize for Record<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+
+        let column = |key: &str| {
+            self.fields
+                .iter()
+                .position(|f| f.key == key)
+                .and_then(|i| self.values.get(i))
+        };
+        if let (Some(date), Some(time)) = (column("date"), column("time")) {
+            map.serialize_entry("ts", &format!("{date}T{time}Z"))?;
+        }
+
+        for (f, &v) in self.fields.iter().zip(self.values) {
+            if v == "-" {
+                map.serialize_entry(&f.key, &())?;
+                continue;
+            }
+            match f.kind {
+                Kind::Int => match v.parse::<i64>() {
+                    Ok(n) => map.serialize_entry(&f.key, &n)?,
+                    Err(_) => map.serialize_entry(&f.key, v)?,
+                },
+                Kind::Float => match v.parse::<f64>() {
+                    Ok(n) => map.serialize_entry(&f.key, &n)?,
+                    Err(_) => map.serialize_entry(&f.key, v)?,
+                },
+                Kind::Text => map.serialize_entry(&f.key, &percent_decode(v))?,
+            }
+        }
+
+        if self.values.len() > self.fields.len() {
+            map.serialize_entry("extra", &self.values[self.fields.len()..])?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(p: &dyn Parser, line: &str) -> Option<serde_json::Value> {
+        let mut out = Vec::new();
+        p.process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn parses_default_layout_and_decodes_values() {
+        let line = "2019-12-04\t21:02:31\tLAX1\t392\t192.0.2.100\tGET\td111111abcdef8.cloudfront.net\t/index.html\t200\t-\tMozilla/5.0%20(Windows%20NT%2010.0)\ta=b%20c\t-\tHit\tSOX4xwn4XV6Q4rgb7XiVGOHms_BGlTAC4KyHmureZmBNrjGdRLiNIQ==\td111111abcdef8.cloudfront.net\thttps\t23\t0.001\t-\tTLSv1.2\tECDHE-RSA-AES128-GCM-SHA256\tHit\tHTTP/2.0\t-\t-\t11040\t0.001\tHit\ttext/html\t78\t-\t-";
+        let v = run(&*new(), line).unwrap();
+
+        assert_eq!(v["ts"], "2019-12-04T21:02:31Z");
+        assert_eq!(v["x_edge_location"], "LAX1");
+        assert_eq!(v["sc_bytes"], 392);
+        assert_eq!(v["cs_host"], "d111111abcdef8.cloudfront.net");
+        assert_eq!(v["sc_status"], 200);
+        assert!(v["cs_referer"].is_null());
+        assert_eq!(v["cs_user_agent"], "Mozilla/5.0 (Windows NT 10.0)");
+        assert_eq!(v["cs_uri_query"], "a=b c");
+        assert_eq!(v["time_taken"], 0.001);
+        assert_eq!(v["c_port"], 11040);
+        assert!(v.get("extra").is_none());
+    }
+
+    #[test]
+    fn fields_directive_sets_the_columns() {
+        let head = [
+            "#Version: 1.0",
+            "#Fields: date time c-ip sc-status cs-uri-stem",
+        ];
+        let p = new().for_input(&head).unwrap().unwrap();
+
+        let mut out = Vec::new();
+        assert!(!p.process_line_to_buf(head[1], &mut out));
+        let v = run(
+            &*p,
+            "2024-01-01\t00:00:01\t10.0.0.1\t404\t/a%2Fb\t-\t-\t-\t-\tx",
+        )
+        .unwrap();
+        assert_eq!(v["c_ip"], "10.0.0.1");
+        assert_eq!(v["sc_status"], 404);
+        assert_eq!(v["cs_uri_stem"], "/a/b");
+        assert_eq!(v["extra"], serde_json::json!(["-", "-", "-", "-", "x"]));
+    }
+
+    #[test]
+    fn json_keys_are_snake_case() {
+        assert_eq!(json_key("cs(User-Agent)"), "cs_user_agent");
+        assert_eq!(json_key("x-edge-location"), "x_edge_location");
+    }
+}
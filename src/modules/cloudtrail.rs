@@ -0,0 +1,122 @@
+use crate::core::{InputFormat, Parser};
+use crate::modules::common::{explode_json_records, flatten};
+use anyhow::Result;
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(CloudTrail)
+}
+
+/// AWS CloudTrail log files: (gzip) JSON documents holding a `Records` array.
+/// Each event becomes one flattened JSONL record.
+pub struct CloudTrail;
+
+/// Free-form, per-API payloads. Flattening them would create thousands of
+/// distinct keys across a trail, so they stay nested objects.
+const NESTED_KEYS: &[&str] = &[
+    "requestParameters",
+    "responseElements",
+    "additionalEventData",
+    "serviceEventDetails",
+];
+
+impl Parser for CloudTrail {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("cloudtrail")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("AWS CloudTrail JSON files -> one flattened JSONL record per event")
+    }
+
+    fn input_format(&self) -> InputFormat {
+        InputFormat::Document
+    }
+
+    fn split_document(&self, doc: &[u8], out: &mut Vec<u8>) -> Result<()> {
+        explode_json_records(doc, "Records", out)
+    }
+
+    /// `detect` samples raw file lines: accept a single event or a whole
+    /// one-line `{"Records":[...]}` file.
+    fn recognizes(&self, line: &str) -> bool {
+        let mut scratch = Vec::new();
+        if self.process_line_to_buf(line, &mut scratch) {
+            return true;
+        }
+        let mut events = Vec::new();
+        explode_json_records(line.as_bytes(), "Records", &mut events).is_ok()
+            && events
+                .split(|&b| b == b'\n')
+                .find(|l| !l.is_empty())
+                .and_then(|l| std::str::from_utf8(l).ok())
+                .is_some_and(|l| self.process_line_to_buf(l, &mut scratch))
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let Ok(Value::Object(event)) = serde_json::from_str::<Value>(line.trim()) else {
+            return false;
+        };
+        if !event.contains_key("eventName") {
+            return false;
+        }
+
+        let mut flat = Map::new();
+        flatten("", event, NESTED_KEYS, &mut flat);
+        if serde_json::to_writer(&mut *out, &flat).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE: &str = r#"{"Records":[
+      {"eventVersion":"1.08","userIdentity":{"type":"IAMUser","arn":"arn:aws:iam::123456789012:user/Alice","sessionContext":{"attributes":{"mfaAuthenticated":"false"}}},
+       "eventTime":"2023-01-01T00:00:00Z","eventSource":"s3.amazonaws.com","eventName":"GetObject",
+       "sourceIPAddress":"198.51.100.1","requestParameters":{"bucketName":"b","key":"k"}},
+      {"eventVersion":"1.08","eventName":"ConsoleLogin","sourceIPAddress":"203.0.113.5","responseElements":{"ConsoleLogin":"Success"}}
+    ]}"#;
+
+    #[test]
+    fn splits_records_and_flattens_events() {
+        let mut lines = Vec::new();
+        CloudTrail
+            .split_document(FILE.as_bytes(), &mut lines)
+            .unwrap();
+        let lines: Vec<&str> = std::str::from_utf8(&lines).unwrap().lines().collect();
+        assert_eq!(lines.len(), 2);
+
+        let mut out = Vec::new();
+        assert!(CloudTrail.process_line_to_buf(lines[0], &mut out));
+        let v: Value = serde_json::from_slice(&out).unwrap();
+
+        assert_eq!(v["eventName"], "GetObject");
+        assert_eq!(v["userIdentity.type"], "IAMUser");
+        assert_eq!(
+            v["userIdentity.sessionContext.attributes.mfaAuthenticated"],
+            "false"
+        );
+        assert_eq!(v["sourceIPAddress"], "198.51.100.1");
+        assert_eq!(v["requestParameters"]["bucketName"], "b");
+    }
+
+    #[test]
+    fn rejects_non_events() {
+        let mut out = Vec::new();
+        assert!(!CloudTrail.process_line_to_buf(r#"{"digestStartTime":"x"}"#, &mut out));
+        assert!(!CloudTrail.process_line_to_buf("not json", &mut out));
+    }
+
+    #[test]
+    fn recognizes_one_line_trail_files() {
+        let one_line: String = FILE.lines().map(str::trim).collect();
+        assert!(CloudTrail.recognizes(&one_line));
+        assert!(!CloudTrail.recognizes(r#"{"Records":[]}"#));
+    }
+}
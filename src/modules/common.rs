@@ -0,0 +1,353 @@
+//! Small tokenizing helpers shared by several modules.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime, UtcOffset};
+
+/// Split on runs of spaces/tabs, treating `"..."` as one token.
+///
+/// Quotes are removed and `\"` / `\\` inside them decoded. Returns `None` on
+/// an unterminated quote.
+pub(crate) fn split_quoted(line: &str) -> Option<Vec<Cow<'_, str>>> {
+    let b = line.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    loop {
+        while i < b.len() && (b[i] == b' ' || b[i] == b'\t') {
+            i += 1;
+        }
+        if i == b.len() {
+            return Some(tokens);
+        }
+
+        if b[i] != b'"' {
+            let start = i;
+            while i < b.len() && b[i] != b' ' && b[i] != b'\t' {
+                i += 1;
+            }
+            tokens.push(Cow::Borrowed(&line[start..i]));
+            continue;
+        }
+
+        i += 1;
+        let start = i;
+        let mut owned: Option<String> = None;
+        let mut run = start;
+        loop {
+            match b.get(i)? {
+                b'"' => break,
+                b'\\' if matches!(b.get(i + 1), Some(b'"' | b'\\')) => {
+                    let s = owned.get_or_insert_with(String::new);
+                    s.push_str(&line[run..i]);
+                    s.push(b[i + 1] as char);
+                    i += 2;
+                    run = i;
+                }
+                _ => i += 1,
+            }
+        }
+        tokens.push(match owned {
+            Some(mut s) => {
+                s.push_str(&line[run..i]);
+                Cow::Owned(s)
+            }
+            None => Cow::Borrowed(&line[start..i]),
+        });
+        i += 1; // closing quote
+    }
+}
+
+/// Decode `%XX` escapes. Malformed escapes are kept as written and invalid
+/// UTF-8 is replaced.
+pub(crate) fn percent_decode(s: &str) -> Cow<'_, str> {
+    if !s.contains('%') {
+        return Cow::Borrowed(s);
+    }
+
+    let b = s.as_bytes();
+    let mut out = Vec::with_capacity(b.len());
+    let mut i = 0;
+    while i < b.len() {
+        if b[i] == b'%'
+            && let Some(byte) = b
+                .get(i + 1..i + 3)
+                .and_then(|h| std::str::from_utf8(h).ok())
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+        {
+            out.push(byte);
+            i += 3;
+        } else {
+            out.push(b[i]);
+            i += 1;
+        }
+    }
+    Cow::Owned(String::from_utf8_lossy(&out).into_owned())
+}
+
+/// Split `host:port` (IPv6 hosts may be bracketed). A missing or
+/// non-numeric port yields `None` for the port.
+pub(crate) fn split_host_port(s: &str) -> (&str, Option<u16>) {
+    match s.rsplit_once(':') {
+        Some((host, port)) if host.starts_with('[') || !host.contains(':') => {
+            let host = host.trim_start_matches('[').trim_end_matches(']');
+            (host, port.parse().ok())
+        }
+        _ => (s, None),
+    }
+}
+
+/// Unix epoch seconds with an optional fraction (`1286536308.779`) -> RFC 3339
+/// (UTC), keeping the fraction.
+pub(crate) fn epoch_to_rfc3339(value: &str) -> Option<String> {
+    let (secs, frac) = value.split_once('.').unwrap_or((value, ""));
+    if frac.len() > 9 || !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let nanos = format!("{frac:0<9}").parse::<i128>().ok()?;
+    let total = secs.parse::<i128>().ok()? * 1_000_000_000 + nanos;
+    OffsetDateTime::from_unix_timestamp_nanos(total)
+        .ok()?
+        .format(&Rfc3339)
+        .ok()
+}
+
+/// RFC 3339 with any offset (`2024-01-02T04:04:05.123+01:00`) -> RFC 3339
+/// in UTC.
+pub(crate) fn rfc3339_to_utc(value: &str) -> Option<String> {
+    OffsetDateTime::parse(value, &Rfc3339)
+        .ok()?
+        .to_offset(UtcOffset::UTC)
+        .format(&Rfc3339)
+        .ok()
+}
+
+/// Nested objects -> dotted keys (`userIdentity.sessionContext.mfa`).
+/// Objects under the `keep` keys stay nested, and so do empty ones.
+pub(crate) fn flatten(
+    prefix: &str,
+    obj: Map<String, Value>,
+    keep: &[&str],
+    out: &mut Map<String, Value>,
+) {
+    flatten_with(prefix, obj, keep, out, &|_, v| v);
+}
+
+/// [`flatten`], passing each value through `map` with its dotted key first:
+/// it may rewrite a leaf, or turn an array into an object to flatten.
+pub(crate) fn flatten_with(
+    prefix: &str,
+    obj: Map<String, Value>,
+    keep: &[&str],
+    out: &mut Map<String, Value>,
+    map: &dyn Fn(&str, Value) -> Value,
+) {
+    for (k, v) in obj {
+        let key = if prefix.is_empty() {
+            k
+        } else {
+            format!("{prefix}.{k}")
+        };
+
+        match map(&key, v) {
+            Value::Object(inner) if !inner.is_empty() && !keep.contains(&key.as_str()) => {
+                flatten_with(&key, inner, keep, out, map)
+            }
+            v => {
+                out.insert(key, v);
+            }
+        }
+    }
+}
+
+/// `Jan`..`Dec` -> 1..12.
+pub(crate) fn month_number(abbr: &str) -> Option<u8> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    MONTHS.iter().position(|m| *m == abbr).map(|i| i as u8 + 1)
+}
+
+/// BSD syslog preamble. Flatten it into a module's record with
+/// `#[serde(flatten)]`.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct Syslog<'a> {
+    /// As written: `Feb  6 12:14:14` (no year) or RFC 3339.
+    pub syslog_timestamp: &'a str,
+    pub host: &'a str,
+    pub program: &'a str,
+    pub pid: Option<u32>,
+}
+
+/// Split `[<PRI>]Mmm dd hh:mm:ss host program[pid]: message` (the timestamp
+/// may also be RFC 3339, as rsyslog writes it) into preamble and message.
+pub(crate) fn split_syslog(line: &str) -> Option<(Syslog<'_>, &str)> {
+    let mut s = line;
+    if let Some(rest) = s.strip_prefix('<') {
+        let (pri, rest) = rest.split_once('>')?;
+        if pri.is_empty() || !pri.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        s = rest;
+    }
+
+    let b = s.as_bytes();
+    let ts_len = if b.len() > 15
+        && b[..3].iter().all(u8::is_ascii_alphabetic)
+        && b[3] == b' '
+        && b[6] == b' '
+        && b[9] == b':'
+        && b[12] == b':'
+        && b[15] == b' '
+    {
+        15
+    } else if b.first().is_some_and(u8::is_ascii_digit) {
+        let n = s.find(' ')?;
+        if !s[..n].contains('T') {
+            return None;
+        }
+        n
+    } else {
+        return None;
+    };
+    let syslog_timestamp = &s[..ts_len];
+
+    let (host, rest) = s[ts_len + 1..].split_once(' ')?;
+    let (tag, message) = match rest.split_once(": ") {
+        Some(split) => split,
+        None => (rest.strip_suffix(':')?, ""),
+    };
+    if host.is_empty() || tag.is_empty() || tag.contains(' ') {
+        return None;
+    }
+    let (program, pid) = match tag.strip_suffix(']').and_then(|t| t.split_once('[')) {
+        Some((program, pid)) => (program, Some(pid.parse().ok()?)),
+        None => (tag, None),
+    };
+
+    Some((
+        Syslog {
+            syslog_timestamp,
+            host,
+            program,
+            pid,
+        },
+        message,
+    ))
+}
+
+/// Write the records of a JSON document as compact JSON, one per line.
+///
+/// The document may hold several concatenated values (JSON Lines included).
+/// A top-level array, or an object whose `key` member is an array, yields one
+/// record per element; any other value is a record itself.
+pub(crate) fn explode_json_records(doc: &[u8], key: &str, out: &mut Vec<u8>) -> Result<()> {
+    for value in serde_json::Deserializer::from_slice(doc).into_iter::<Value>() {
+        let value = value.context("invalid JSON")?;
+        let records = match value {
+            Value::Array(items) => items,
+            Value::Object(mut obj) => {
+                if let Some(Value::Array(items)) = obj.get_mut(key) {
+                    std::mem::take(items)
+                } else {
+                    vec![Value::Object(obj)]
+                }
+            }
+            other => vec![other],
+        };
+        for rec in records {
+            serde_json::to_writer(&mut *out, &rec)?;
+            out.push(b'\n');
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_quoted_handles_quotes_and_escapes() {
+        let t = split_quoted(r#"a "b c" "" "say \"hi\"" d"#).unwrap();
+        assert_eq!(t, vec!["a", "b c", "", r#"say "hi""#, "d"]);
+        assert!(split_quoted(r#"a "never closed"#).is_none());
+    }
+
+    #[test]
+    fn percent_decode_keeps_malformed_escapes() {
+        assert_eq!(percent_decode("/a%20b%2Fc"), "/a b/c");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%C3%A9"), "%zzé");
+    }
+
+    #[test]
+    fn explodes_records_arrays_and_plain_values() {
+        let mut out = Vec::new();
+        explode_json_records(
+            br#"{"Records":[{"a":1},{"a":2}]} {"b":3}
+[{"c":4}]"#,
+            "Records",
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(out, b"{\"a\":1}\n{\"a\":2}\n{\"b\":3}\n{\"c\":4}\n");
+        assert!(explode_json_records(b"{oops", "Records", &mut out).is_err());
+    }
+
+    #[test]
+    fn split_syslog_preambles() {
+        let (sys, msg) =
+            split_syslog("Feb  6 12:14:14 localhost haproxy[14389]: 10.0.1.2:33317 x").unwrap();
+        assert_eq!(
+            sys,
+            Syslog {
+                syslog_timestamp: "Feb  6 12:14:14",
+                host: "localhost",
+                program: "haproxy",
+                pid: Some(14389),
+            }
+        );
+        assert_eq!(msg, "10.0.1.2:33317 x");
+
+        let (sys, msg) =
+            split_syslog("<38>2024-01-02T03:04:05.123+01:00 web-1 kernel: [ 1.0] oops").unwrap();
+        assert_eq!(sys.syslog_timestamp, "2024-01-02T03:04:05.123+01:00");
+        assert_eq!((sys.program, sys.pid), ("kernel", None));
+        assert_eq!(msg, "[ 1.0] oops");
+
+        assert!(split_syslog("10.0.0.1 - - [x] \"GET / HTTP/1.1\"").is_none());
+        assert!(split_syslog("Feb  6 12:14:14 host no tag here").is_none());
+    }
+
+    #[test]
+    fn split_host_port_variants() {
+        assert_eq!(split_host_port("10.0.0.1:443"), ("10.0.0.1", Some(443)));
+        assert_eq!(
+            split_host_port("[2001:db8::1]:80"),
+            ("2001:db8::1", Some(80))
+        );
+        assert_eq!(split_host_port("2001:db8::1"), ("2001:db8::1", None));
+        assert_eq!(split_host_port("-"), ("-", None));
+    }
+
+    #[test]
+    fn flattens_objects_but_kept_keys() {
+        let obj = serde_json::json!({"a": {"b": {"c": 1}, "e": {}}, "keep": {"x": 1}, "t": "2024-01-02T04:04:05+01:00"});
+        let Value::Object(obj) = obj else {
+            unreachable!()
+        };
+        let mut out = Map::new();
+        flatten_with("", obj, &["keep"], &mut out, &|key, v| match v {
+            Value::String(s) if key == "t" => rfc3339_to_utc(&s).unwrap_or(s).into(),
+            v => v,
+        });
+        assert_eq!(
+            Value::Object(out),
+            serde_json::json!({"a.b.c": 1, "a.e": {}, "keep": {"x": 1}, "t": "2024-01-02T03:04:05Z"})
+        );
+        assert_eq!(rfc3339_to_utc("2024-01-02"), None);
+    }
+}
@@ -0,0 +1,206 @@
+use crate::core::{LineJoiner, LinePos, Parser};
+use serde::Serialize;
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(Cri)
+}
+
+/// Kubernetes CRI container logs (`/var/log/pods/.../0.log`):
+/// `<RFC 3339 time> <stdout|stderr> <P|F> <message>`.
+///
+/// Long lines are written as `P` (partial) chunks closed by an `F` line; the
+/// joiner glues them back together per stream before parsing.
+pub struct Cri;
+
+#[derive(Serialize)]
+struct Record<'a> {
+    ts: &'a str,
+    stream: &'a str,
+    /// True only for a partial line whose final chunk never arrived.
+    partial: bool,
+    message: &'a str,
+}
+
+struct CriLine<'a> {
+    timestamp: &'a str,
+    stream: &'a str,
+    /// `P` or `F`, possibly followed by `:`-separated extra tags.
+    tag: &'a str,
+    message: &'a str,
+}
+
+fn parse_cri(line: &str) -> Option<CriLine<'_>> {
+    let mut parts = line.splitn(4, ' ');
+    let timestamp = parts.next()?;
+    let stream = parts.next()?;
+    let tag = parts.next()?;
+    let message = parts.next().unwrap_or("");
+
+    if !timestamp.starts_with(|c: char| c.is_ascii_digit())
+        || !matches!(stream, "stdout" | "stderr")
+        || !(tag.starts_with('P') || tag.starts_with('F'))
+    {
+        return None;
+    }
+    Some(CriLine {
+        timestamp,
+        stream,
+        tag,
+        message,
+    })
+}
+
+impl Parser for Cri {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("cri")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Kubernetes CRI container logs -> JSONL, partial lines reassembled")
+    }
+
+    fn line_joiner(&self) -> Option<Box<dyn LineJoiner>> {
+        Some(Box::<PartialJoiner>::default())
+    }
+
+    fn message_field(&self) -> Option<&'static str> {
+        Some("message")
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let Some(l) = parse_cri(line) else {
+            return false;
+        };
+
+        let rec = Record {
+            ts: l.timestamp,
+            stream: l.stream,
+            partial: l.tag.starts_with('P'),
+            message: l.message,
+        };
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+/// Pending `P` chunks, kept apart per stream since stdout and stderr interleave.
+#[derive(Default)]
+struct PartialJoiner {
+    pending: Vec<Pending>,
+}
+
+struct Pending {
+    stream: String,
+    /// Time and input position of the first chunk.
+    timestamp: String,
+    at: LinePos,
+    message: Vec<u8>,
+}
+
+impl Pending {
+    /// Rebuild a CRI line carrying the whole message.
+    fn line(&self, tag: &str) -> Vec<u8> {
+        let mut line = format!("{} {} {tag} ", self.timestamp, self.stream).into_bytes();
+        line.extend_from_slice(&self.message);
+        line
+    }
+}
+
+impl LineJoiner for PartialJoiner {
+    fn push(&mut self, line: &[u8], at: LinePos, emit: &mut dyn FnMut(&[u8], LinePos)) {
+        let Some(l) = std::str::from_utf8(line).ok().and_then(parse_cri) else {
+            emit(line, at);
+            return;
+        };
+
+        let slot = self.pending.iter().position(|p| p.stream == l.stream);
+        let partial = l.tag.starts_with('P');
+
+        match (slot, partial) {
+            (None, false) => emit(line, at),
+            (None, true) => self.pending.push(Pending {
+                stream: l.stream.to_string(),
+                timestamp: l.timestamp.to_string(),
+                at,
+                message: l.message.as_bytes().to_vec(),
+            }),
+            (Some(i), true) => self.pending[i]
+                .message
+                .extend_from_slice(l.message.as_bytes()),
+            (Some(i), false) => {
+                let mut p = self.pending.swap_remove(i);
+                p.message.extend_from_slice(l.message.as_bytes());
+                emit(&p.line("F"), p.at);
+            }
+        }
+    }
+
+    fn finish(&mut self, emit: &mut dyn FnMut(&[u8], LinePos)) {
+        // No final chunk: emit what we have, still marked partial.
+        for p in self.pending.drain(..) {
+            emit(&p.line("P"), p.at);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn join(lines: &[&str]) -> Vec<String> {
+        let mut j = PartialJoiner::default();
+        let mut out = Vec::new();
+        for l in lines {
+            j.push(l.as_bytes(), LinePos::default(), &mut |r, _| {
+                out.push(String::from_utf8(r.to_vec()).unwrap())
+            });
+        }
+        j.finish(&mut |r, _| out.push(String::from_utf8(r.to_vec()).unwrap()));
+        out
+    }
+
+    #[test]
+    fn parses_full_line() {
+        let mut out = Vec::new();
+        assert!(Cri.process_line_to_buf(
+            "2024-01-02T15:04:05.123456789Z stdout F hello world",
+            &mut out
+        ));
+        let v: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(v["ts"], "2024-01-02T15:04:05.123456789Z");
+        assert_eq!(v["stream"], "stdout");
+        assert_eq!(v["partial"], false);
+        assert_eq!(v["message"], "hello world");
+    }
+
+    #[test]
+    fn joins_partial_chunks_per_stream() {
+        let out = join(&[
+            "2024-01-02T15:04:05Z stdout P hello ",
+            "2024-01-02T15:04:05Z stderr F oops",
+            "2024-01-02T15:04:06Z stdout P big ",
+            "2024-01-02T15:04:07Z stdout F world",
+            "2024-01-02T15:04:08Z stderr P never finished",
+        ]);
+        assert_eq!(
+            out,
+            vec![
+                "2024-01-02T15:04:05Z stderr F oops",
+                "2024-01-02T15:04:05Z stdout F hello big world",
+                "2024-01-02T15:04:08Z stderr P never finished",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_other_formats() {
+        let mut out = Vec::new();
+        assert!(!Cri.process_line_to_buf("2024-01-02 stdout X msg", &mut out));
+        assert!(!Cri.process_line_to_buf(r#"{"log":"x"}"#, &mut out));
+        assert_eq!(join(&["not cri"]), vec!["not cri"]);
+    }
+}
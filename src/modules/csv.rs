@@ -0,0 +1,892 @@
+use crate::core::{BlockJoiner, Boundary, LineJoiner, LinePos, ModuleOptions, OptionSpec, Parser};
+use anyhow::{Context, Result};
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+use std::{borrow::Cow, collections::HashMap};
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(Csv::new())
+}
+
+/// RFC 4180 CSV. Quoted fields may hold delimiters, doubled quotes and line
+/// breaks: physical lines are joined until the quotes balance, so one
+/// record may span several lines. Rows become objects keyed by `headers`,
+/// or plain arrays without them.
+#[derive(Clone)]
+pub struct Csv {
+    headers: Option<Vec<String>>,
+    /// Take the column names from each input's first row.
+    header_row: bool,
+    delim: u8,
+    quote: u8,
+    /// Pick the delimiter / quote per input from its first lines.
+    sniff_delim: bool,
+    sniff_quote: bool,
+    /// Column name (or 1-based position) -> type, from a `types=` schema.
+    schema: Option<HashMap<String, Kind>>,
+    /// How each column is typed; columns past the end use `default_kind`.
+    kinds: Vec<Kind>,
+    default_kind: Kind,
+    /// Column shaping, by input column name (`_extra` included).
+    select: Option<Vec<String>>,
+    drop: Vec<String>,
+    rename: Vec<(String, String)>,
+    /// Output columns as `(input column, output name)`, from the above.
+    plan: Vec<(usize, String)>,
+    keep_extra: bool,
+}
+
+/// JSON type of a column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    String,
+    /// Per value: integer, float, boolean, `null` for empty, else string.
+    Auto,
+    Int,
+    Float,
+    Bool,
+}
+
+impl Kind {
+    fn parse(s: &str) -> Option<Kind> {
+        Some(match s {
+            "string" => Kind::String,
+            "auto" => Kind::Auto,
+            "int" | "integer" => Kind::Int,
+            "float" | "number" => Kind::Float,
+            "bool" | "boolean" => Kind::Bool,
+            _ => return None,
+        })
+    }
+}
+
+const OPTIONS: &[OptionSpec] = &[
+    OptionSpec {
+        key: "headers",
+        help: "Comma-separated column names; rows become objects instead of arrays",
+    },
+    OptionSpec {
+        key: "header_row",
+        help: "true: the first row of each input holds the column names (default: false)",
+    },
+    OptionSpec {
+        key: "delim",
+        help: r"Field delimiter, one byte, \t or auto (default: auto among , \t ; |)",
+    },
+    OptionSpec {
+        key: "quote",
+        help: "Quote character, one byte or auto (default: auto among \" and ')",
+    },
+    OptionSpec {
+        key: "select",
+        help: "Comma-separated columns to keep, in this order (_extra included)",
+    },
+    OptionSpec {
+        key: "drop",
+        help: "Comma-separated columns to leave out",
+    },
+    OptionSpec {
+        key: "rename",
+        help: "Comma-separated old:new column renames (e.g. tgt:dst_ip)",
+    },
+    OptionSpec {
+        key: "types",
+        help: "string (default), auto (numbers, booleans, null for empty) or a JSON schema \
+               file mapping columns to string|int|float|bool|auto",
+    },
+];
+
+/// Guards against an unbalanced quote swallowing the rest of the input.
+const MAX_LINES: usize = 10_000;
+
+/// Where fields beyond the named columns go.
+const EXTRA: &str = "_extra";
+
+/// Sniffing candidates, most likely first.
+const DELIMS: &[u8] = b",\t;|";
+const QUOTES: &[u8] = b"\"'";
+
+impl Csv {
+    fn new() -> Self {
+        Self {
+            headers: None,
+            header_row: false,
+            delim: b',',
+            quote: b'"',
+            sniff_delim: true,
+            sniff_quote: true,
+            schema: None,
+            kinds: Vec::new(),
+            default_kind: Kind::String,
+            select: None,
+            drop: Vec::new(),
+            rename: Vec::new(),
+            plan: Vec::new(),
+            keep_extra: true,
+        }
+    }
+
+    /// Everything derived from the column names: types and output columns.
+    fn resolve(&mut self) -> Result<()> {
+        self.resolve_kinds();
+        let Some(headers) = &self.headers else {
+            return Ok(());
+        };
+
+        let known = |name: &str| name == EXTRA || headers.iter().any(|h| h == name);
+        let wanted = self.select.iter().flatten().chain(&self.drop);
+        if let Some(missing) = wanted
+            .chain(self.rename.iter().map(|(old, _)| old))
+            .find(|n| !known(n))
+        {
+            anyhow::bail!("no column '{missing}' (columns: {})", headers.join(", "));
+        }
+
+        let order: Vec<&str> = match &self.select {
+            Some(select) => select.iter().map(String::as_str).collect(),
+            None => headers.iter().map(String::as_str).chain([EXTRA]).collect(),
+        };
+        let mut plan = Vec::new();
+        let mut keep_extra = false;
+        for name in order {
+            if self.drop.iter().any(|d| d == name) {
+                continue;
+            }
+            if name == EXTRA {
+                keep_extra = true;
+                continue;
+            }
+            let Some(i) = headers.iter().position(|h| h == name) else {
+                continue;
+            };
+            let out = self
+                .rename
+                .iter()
+                .find(|(old, _)| old == name)
+                .map_or(name, |(_, new)| new);
+            plan.push((i, out.to_string()));
+        }
+        self.plan = plan;
+        self.keep_extra = keep_extra;
+        Ok(())
+    }
+
+    /// Resolve the schema against the column names in use.
+    fn resolve_kinds(&mut self) {
+        let Some(schema) = &self.schema else {
+            return;
+        };
+        let width = self.headers.as_ref().map_or(0, Vec::len).max(
+            schema
+                .keys()
+                .filter_map(|k| k.parse::<usize>().ok())
+                .max()
+                .unwrap_or(0),
+        );
+        self.kinds = (0..width)
+            .map(|i| {
+                let by_name = self
+                    .headers
+                    .as_ref()
+                    .and_then(|h| h.get(i))
+                    .and_then(|h| schema.get(h));
+                by_name
+                    .or_else(|| schema.get(&(i + 1).to_string()))
+                    .copied()
+                    .unwrap_or(Kind::String)
+            })
+            .collect();
+    }
+
+    fn kind(&self, column: usize) -> Kind {
+        self.kinds.get(column).copied().unwrap_or(self.default_kind)
+    }
+
+    fn joiner(&self) -> BlockJoiner {
+        record_joiner(self.quote)
+    }
+}
+
+/// A line opens a record unless a quote is still open from the lines before it.
+fn record_joiner(quote: u8) -> BlockJoiner {
+    let mut in_quotes = false;
+    let boundary = move |line: &str| {
+        let odd = !line
+            .bytes()
+            .filter(|&b| b == quote)
+            .count()
+            .is_multiple_of(2);
+        let start = !in_quotes;
+        in_quotes ^= odd;
+        if start {
+            Boundary::Start
+        } else {
+            Boundary::Inside
+        }
+    };
+    BlockJoiner::new(boundary, MAX_LINES)
+}
+
+/// The non-blank records of an input's first lines, joined with `quote`.
+fn head_records(head: &[&str], quote: u8) -> Vec<String> {
+    let mut records = Vec::new();
+    let mut keep = |rec: &[u8], _| {
+        if !is_blank(rec) {
+            records.push(String::from_utf8_lossy(rec).into_owned());
+        }
+    };
+    let mut joiner = record_joiner(quote);
+    for line in head {
+        joiner.push(line.as_bytes(), LinePos::default(), &mut keep);
+    }
+    joiner.finish(&mut keep);
+    records
+}
+
+/// The delimiter and quote splitting the most records into the same number
+/// (two or more) of fields, wider rows breaking ties; `None` if nothing
+/// splits. Remaining ties go to the earlier candidate.
+fn sniff(head: &[&str], delims: &[u8], quotes: &[u8]) -> Option<(u8, u8)> {
+    let mut best: Option<((usize, usize), u8, u8)> = None;
+    for &quote in quotes {
+        let records = head_records(head, quote);
+        for &delim in delims {
+            let mut widths: HashMap<usize, usize> = HashMap::new();
+            for rec in &records {
+                if let Some(fields) = split_record(rec, delim, quote) {
+                    *widths.entry(fields.len()).or_default() += 1;
+                }
+            }
+            let Some(score) = widths
+                .into_iter()
+                .filter(|&(width, _)| width >= 2)
+                .map(|(width, rows)| (rows, width))
+                .max()
+            else {
+                continue;
+            };
+            if best.is_none_or(|(top, ..)| score > top) {
+                best = Some((score, delim, quote));
+            }
+        }
+    }
+    best.map(|(_, delim, quote)| (delim, quote))
+}
+
+/// Column names from a header row: a leading byte order mark is dropped,
+/// blank names become `column<N>` and repeats get a `_<N>` suffix.
+fn header_names(fields: &[Cow<str>]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::with_capacity(fields.len());
+    for (i, f) in fields.iter().enumerate() {
+        let f = f.trim_start_matches('\u{feff}').trim();
+        let base = if f.is_empty() {
+            format!("column{}", i + 1)
+        } else {
+            f.to_string()
+        };
+        let mut name = base.clone();
+        let mut n = 1;
+        while names.contains(&name) {
+            n += 1;
+            name = format!("{base}_{n}");
+        }
+        names.push(name);
+    }
+    names
+}
+
+fn is_blank(rec: &[u8]) -> bool {
+    rec.iter().all(u8::is_ascii_whitespace)
+}
+
+/// Passes every record but an input's first non-blank one (its header row) on.
+struct SkipFirst {
+    inner: BlockJoiner,
+    skipped: bool,
+}
+
+impl SkipFirst {
+    fn pass<'a>(
+        skipped: &'a mut bool,
+        emit: &'a mut dyn FnMut(&[u8], LinePos),
+    ) -> impl FnMut(&[u8], LinePos) + 'a {
+        move |rec, at| {
+            if *skipped || is_blank(rec) {
+                emit(rec, at);
+            } else {
+                *skipped = true;
+            }
+        }
+    }
+}
+
+impl LineJoiner for SkipFirst {
+    fn push(&mut self, line: &[u8], at: LinePos, emit: &mut dyn FnMut(&[u8], LinePos)) {
+        self.inner
+            .push(line, at, &mut Self::pass(&mut self.skipped, emit));
+    }
+
+    fn finish(&mut self, emit: &mut dyn FnMut(&[u8], LinePos)) {
+        self.inner.finish(&mut Self::pass(&mut self.skipped, emit));
+    }
+}
+
+/// One field, typed. Values that don't fit a schema type are `null`, and
+/// so are empty ones unless the type is `string`.
+struct Cell<'a>(&'a str, Kind);
+
+impl Serialize for Cell<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Cell(v, kind) = *self;
+        if v.is_empty() && kind != Kind::String {
+            return serializer.serialize_none();
+        }
+        match kind {
+            Kind::String => serializer.serialize_str(v),
+            Kind::Auto => {
+                if let Some(b) = parse_bool(v) {
+                    serializer.serialize_bool(b)
+                } else if let Some(n) = parse_int(v) {
+                    serializer.serialize_i64(n)
+                } else if let Some(f) = parse_float(v) {
+                    serializer.serialize_f64(f)
+                } else {
+                    serializer.serialize_str(v)
+                }
+            }
+            Kind::Int => match v.trim().parse::<i64>() {
+                Ok(n) => serializer.serialize_i64(n),
+                Err(_) => serializer.serialize_none(),
+            },
+            Kind::Float => match v.trim().parse::<f64>() {
+                Ok(f) if f.is_finite() => serializer.serialize_f64(f),
+                _ => serializer.serialize_none(),
+            },
+            Kind::Bool => match parse_bool(v.trim()) {
+                Some(b) => serializer.serialize_bool(b),
+                None => serializer.serialize_none(),
+            },
+        }
+    }
+}
+
+fn parse_bool(v: &str) -> Option<bool> {
+    if v.eq_ignore_ascii_case("true") {
+        Some(true)
+    } else if v.eq_ignore_ascii_case("false") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Plain decimal integers only: `007` (an id, a zip code) stays a string.
+fn parse_int(v: &str) -> Option<i64> {
+    decimal(v).then(|| v.parse().ok()).flatten()
+}
+
+/// `1.5`, `-0.25`, `1e6`, `2.5E-3`, with an integer part as for `parse_int`.
+fn parse_float(v: &str) -> Option<f64> {
+    let (mantissa, exp) = match v.split_once(['e', 'E']) {
+        Some((m, e)) => (m, Some(e)),
+        None => (v, None),
+    };
+    let (int, frac) = match mantissa.split_once('.') {
+        Some((i, f)) => (i, Some(f)),
+        None => (mantissa, None),
+    };
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    let exp_ok = exp.is_none_or(|e| digits(e.strip_prefix(['+', '-']).unwrap_or(e)));
+    if (frac.is_none() && exp.is_none()) || !decimal(int) || !frac.is_none_or(digits) || !exp_ok {
+        return None;
+    }
+    v.parse::<f64>().ok().filter(|f| f.is_finite())
+}
+
+/// Optional `-`, then digits without a leading zero (`0` itself is fine).
+fn decimal(v: &str) -> bool {
+    let digits = v.strip_prefix('-').unwrap_or(v);
+    !digits.is_empty()
+        && digits.bytes().all(|b| b.is_ascii_digit())
+        && (digits.len() == 1 || !digits.starts_with('0'))
+}
+
+/// Row as an object: extra fields go to `_extra`, missing ones are `null`.
+/// Columns are laid out by the `select` / `drop` / `rename` plan.
+struct Row<'a> {
+    csv: &'a Csv,
+    headers: &'a [String],
+    fields: Vec<Cow<'a, str>>,
+}
+
+impl Serialize for Row<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        for (i, name) in &self.csv.plan {
+            let cell = self.fields.get(*i).map(|f| Cell(f, self.csv.kind(*i)));
+            map.serialize_entry(name, &cell)?;
+        }
+        if self.csv.keep_extra
+            && let Some(extra) = self.fields.get(self.headers.len()..)
+            && !extra.is_empty()
+        {
+            let extra = Columns {
+                csv: self.csv,
+                first: self.headers.len(),
+                fields: extra,
+            };
+            map.serialize_entry(EXTRA, &extra)?;
+        }
+        map.end()
+    }
+}
+
+/// Fields as an array, starting at column `first`.
+struct Columns<'a> {
+    csv: &'a Csv,
+    first: usize,
+    fields: &'a [Cow<'a, str>],
+}
+
+impl Serialize for Columns<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.fields.len()))?;
+        for (i, f) in self.fields.iter().enumerate() {
+            seq.serialize_element(&Cell(f, self.csv.kind(self.first + i)))?;
+        }
+        seq.end()
+    }
+}
+
+/// Split one record on `delim`. Quoted fields lose their quotes and `""`
+/// becomes `"`; text after a closing quote is kept as written. `None` if a
+/// quote is left open.
+fn split_record(rec: &str, delim: u8, quote: u8) -> Option<Vec<Cow<'_, str>>> {
+    let b = rec.as_bytes();
+    let mut fields = Vec::new();
+    let mut i = 0;
+    loop {
+        if b.get(i) != Some(&quote) {
+            let end = b[i..]
+                .iter()
+                .position(|&c| c == delim)
+                .map_or(b.len(), |p| i + p);
+            fields.push(Cow::Borrowed(&rec[i..end]));
+            if end == b.len() {
+                return Some(fields);
+            }
+            i = end + 1;
+            continue;
+        }
+
+        // Quoted: runs up to a quote not followed by another quote.
+        let start = i + 1;
+        let mut j = start;
+        let mut doubled = false;
+        loop {
+            let q = j + b[j..].iter().position(|&c| c == quote)?;
+            if b.get(q + 1) == Some(&quote) {
+                doubled = true;
+                j = q + 2;
+                continue;
+            }
+            j = q;
+            break;
+        }
+        let inner = &rec[start..j];
+        let mut field = if doubled {
+            let q = char::from(quote);
+            Cow::Owned(inner.replace(&format!("{q}{q}"), &q.to_string()))
+        } else {
+            Cow::Borrowed(inner)
+        };
+
+        let end = b[j + 1..]
+            .iter()
+            .position(|&c| c == delim)
+            .map_or(b.len(), |p| j + 1 + p);
+        if end > j + 1 {
+            field.to_mut().push_str(&rec[j + 1..end]);
+        }
+        fields.push(field);
+        if end == b.len() {
+            return Some(fields);
+        }
+        i = end + 1;
+    }
+}
+
+impl Parser for Csv {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("csv")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("RFC 4180 CSV -> JSONL (quoted multi-line fields; --opt headers=...)")
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        OPTIONS
+    }
+
+    fn configure(&mut self, opts: &ModuleOptions) -> Result<()> {
+        if let Some(h) = opts.list("headers") {
+            self.headers = if h.is_empty() { None } else { Some(h) };
+        }
+
+        if let Some(h) = opts.flag("header_row")? {
+            self.header_row = h;
+        }
+
+        if let Some(d) = opts.get("delim") {
+            self.sniff_delim = d == "auto";
+            self.delim = match d {
+                "auto" => b',',
+                r"\t" => b'\t',
+                _ if d.len() == 1 => d.as_bytes()[0],
+                _ => anyhow::bail!("delim must be a single byte, \\t or auto, got '{d}'"),
+            };
+        }
+
+        if let Some(q) = opts.get("quote") {
+            self.sniff_quote = q == "auto";
+            self.quote = match q {
+                "auto" => b'"',
+                _ if q.len() == 1 => q.as_bytes()[0],
+                _ => anyhow::bail!("quote must be a single byte or auto, got '{q}'"),
+            };
+        }
+
+        if let Some(t) = opts.get("types") {
+            (self.schema, self.default_kind) = match t {
+                "string" => (None, Kind::String),
+                "auto" => (None, Kind::Auto),
+                path => (Some(load_schema(path)?), Kind::String),
+            };
+            self.kinds.clear();
+        }
+
+        if let Some(select) = opts.list("select") {
+            self.select = (!select.is_empty()).then_some(select);
+        }
+        if let Some(drop) = opts.list("drop") {
+            self.drop = drop;
+        }
+        if let Some(rename) = opts.list("rename") {
+            self.rename = rename
+                .iter()
+                .map(|r| match r.split_once(':') {
+                    Some((old, new)) if !old.is_empty() && !new.is_empty() => {
+                        Ok((old.to_string(), new.to_string()))
+                    }
+                    _ => anyhow::bail!("rename: expected old:new, got '{r}'"),
+                })
+                .collect::<Result<_>>()?;
+        }
+        let shaped = self.select.is_some() || !self.drop.is_empty() || !self.rename.is_empty();
+        if shaped && self.headers.is_none() && !self.header_row {
+            anyhow::bail!("select, drop and rename need named columns (headers or header_row)");
+        }
+        self.resolve()
+    }
+
+    /// Sniffs the delimiter and quote, then with `header_row` names the
+    /// columns after the first record. Explicit `headers` still win, but the
+    /// row is skipped either way.
+    fn for_input(&self, head: &[&str]) -> Result<Option<Box<dyn Parser>>> {
+        if !self.sniff_delim && !self.sniff_quote && (!self.header_row || self.headers.is_some()) {
+            return Ok(None);
+        }
+        let mut primed = self.clone();
+        let delims = if self.sniff_delim {
+            DELIMS
+        } else {
+            &[self.delim]
+        };
+        let quotes = if self.sniff_quote {
+            QUOTES
+        } else {
+            &[self.quote]
+        };
+        if let Some((delim, quote)) = sniff(head, delims, quotes) {
+            primed.delim = delim;
+            primed.quote = quote;
+        }
+
+        if self.header_row
+            && self.headers.is_none()
+            && let Some(first) = head_records(head, primed.quote).first()
+            && let Some(fields) = split_record(first, primed.delim, primed.quote)
+        {
+            primed.headers = Some(header_names(&fields));
+            primed.resolve()?;
+        }
+        Ok(Some(Box::new(primed)))
+    }
+
+    fn line_joiner(&self) -> Option<Box<dyn LineJoiner>> {
+        if self.header_row {
+            return Some(Box::new(SkipFirst {
+                inner: self.joiner(),
+                skipped: false,
+            }));
+        }
+        Some(Box::new(self.joiner()))
+    }
+
+    /// Any text is a one-column CSV, so require at least one delimiter.
+    fn recognizes(&self, line: &str) -> bool {
+        let delims = if self.sniff_delim {
+            DELIMS
+        } else {
+            &[self.delim]
+        };
+        delims
+            .iter()
+            .any(|&d| line.as_bytes().contains(&d) && split_record(line, d, self.quote).is_some())
+    }
+
+    fn process_line_to_buf(&self, record: &str, out: &mut Vec<u8>) -> bool {
+        let record = record.strip_prefix('\u{feff}').unwrap_or(record);
+        if record.trim().is_empty() {
+            return false;
+        }
+        let Some(fields) = split_record(record, self.delim, self.quote) else {
+            return false;
+        };
+        let written = match &self.headers {
+            Some(headers) => {
+                let row = Row {
+                    csv: self,
+                    headers,
+                    fields,
+                };
+                serde_json::to_writer(&mut *out, &row)
+            }
+            None => {
+                let row = Columns {
+                    csv: self,
+                    first: 0,
+                    fields: &fields,
+                };
+                serde_json::to_writer(&mut *out, &row)
+            }
+        };
+        if written.is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+/// `{"ts": "string", "bytes": "int", "3": "float"}`: keys are column names
+/// or 1-based positions.
+fn load_schema(path: &str) -> Result<HashMap<String, Kind>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("types: expected string, auto or a schema file, read {path}"))?;
+    let raw: HashMap<String, String> =
+        serde_json::from_str(&text).with_context(|| format!("parse schema {path}"))?;
+    raw.into_iter()
+        .map(|(col, t)| {
+            let kind = Kind::parse(&t)
+                .with_context(|| format!("schema {path}: column {col} has unknown type '{t}'"))?;
+            Ok((col, kind))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(p: &Csv, input: &str) -> Vec<Option<serde_json::Value>> {
+        let mut j = p.line_joiner().unwrap();
+        let mut joined = Vec::new();
+        for l in input.lines() {
+            j.push(l.as_bytes(), LinePos::default(), &mut |r, _| {
+                joined.push(r.to_vec())
+            });
+        }
+        j.finish(&mut |r, _| joined.push(r.to_vec()));
+
+        joined
+            .iter()
+            .map(|r| {
+                let mut out = Vec::new();
+                p.process_line_to_buf(std::str::from_utf8(r).unwrap(), &mut out)
+                    .then(|| serde_json::from_slice(&out).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn quoted_fields_span_lines() {
+        let input = "1,\"a, \"\"quoted\"\"\nnote\",x\n2,plain,\"\"\n3,\"two\n\nblank lines\",y\n";
+        let recs = records(&Csv::new(), input);
+        assert_eq!(
+            recs,
+            vec![
+                Some(serde_json::json!(["1", "a, \"quoted\"\nnote", "x"])),
+                Some(serde_json::json!(["2", "plain", ""])),
+                Some(serde_json::json!(["3", "two\n\nblank lines", "y"])),
+            ]
+        );
+    }
+
+    #[test]
+    fn headers_and_delimiter() {
+        let mut p = Csv::new();
+        p.configure(&ModuleOptions::parse(&["headers=ts,src", r"delim=\t"]).unwrap())
+            .unwrap();
+        let recs = records(&p, "t1\t10.0.0.1\textra\nt2\n");
+        assert_eq!(
+            recs[0],
+            Some(serde_json::json!({"ts": "t1", "src": "10.0.0.1", "_extra": ["extra"]}))
+        );
+        assert_eq!(recs[1], Some(serde_json::json!({"ts": "t2", "src": null})));
+
+        assert!(p
+            .configure(&ModuleOptions::parse(&["delim=;;"]).unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn header_row_names_the_columns() {
+        let mut p = Csv::new();
+        p.configure(&ModuleOptions::parse(&["header_row=true"]).unwrap())
+            .unwrap();
+        let input = "\n\u{feff}ts,\"src\nip\",ts,\n1,a,b,c\n";
+        let head: Vec<&str> = input.lines().collect();
+        let primed = p.for_input(&head).unwrap().unwrap();
+
+        let mut j = primed.line_joiner().unwrap();
+        let mut joined = Vec::new();
+        for l in &head {
+            j.push(l.as_bytes(), LinePos::default(), &mut |r, _| {
+                joined.push(r.to_vec())
+            });
+        }
+        j.finish(&mut |r, _| joined.push(r.to_vec()));
+        assert_eq!(joined, vec![b"".to_vec(), b"1,a,b,c".to_vec()]);
+
+        let mut out = Vec::new();
+        assert!(primed.process_line_to_buf("1,a,b,c", &mut out));
+        let v: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(
+            v,
+            serde_json::json!({"ts": "1", "src\nip": "a", "ts_2": "b", "column4": "c"})
+        );
+    }
+
+    #[test]
+    fn auto_types_and_schema() {
+        let mut p = Csv::new();
+        p.configure(&ModuleOptions::parse(&["types=auto"]).unwrap())
+            .unwrap();
+        let mut out = Vec::new();
+        assert!(p.process_line_to_buf("42,-7,007,1.5,2e3,1.,TRUE,false,,x,0,-0.25", &mut out));
+        let v: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(
+            v,
+            serde_json::json!([42, -7, "007", 1.5, 2000.0, "1.", true, false, null, "x", 0, -0.25])
+        );
+
+        let schema =
+            std::env::temp_dir().join(format!("turbolp-{}-schema.json", std::process::id()));
+        std::fs::write(
+            &schema,
+            r#"{"id": "string", "bytes": "int", "ok": "bool", "4": "float"}"#,
+        )
+        .unwrap();
+        let mut p = Csv::new();
+        let types = format!("types={}", schema.display());
+        p.configure(&ModuleOptions::parse(&["headers=id,bytes,ok,ratio", &types]).unwrap())
+            .unwrap();
+        std::fs::remove_file(&schema).unwrap();
+
+        out.clear();
+        assert!(p.process_line_to_buf("10,n/a,True,0.5,9", &mut out));
+        let v: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(
+            v,
+            serde_json::json!({"id": "10", "bytes": null, "ok": true, "ratio": 0.5, "_extra": ["9"]})
+        );
+
+        assert!(Csv::new()
+            .configure(&ModuleOptions::parse(&["types=/nonexistent.json"]).unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn sniffs_delimiter_and_quote() {
+        let all = |head: &[&str]| sniff(head, DELIMS, QUOTES);
+        assert_eq!(all(&["a;b;c", "1,5;2,5;x", "3;4;y"]), Some((b';', b'"')));
+        assert_eq!(all(&["a\tb", "1\t2, 3"]), Some((b'\t', b'"')));
+        assert_eq!(
+            all(&["'a|b'|c", "'x'|y", "'O''Brien'|z"]),
+            Some((b'|', b'\''))
+        );
+        assert_eq!(
+            all(&["id,name", "1,\"Smith, J\"", "2,O'Brien"]),
+            Some((b',', b'"'))
+        );
+        assert_eq!(all(&["no delimiters here"]), None);
+
+        // Only the quote is sniffed once the delimiter is given.
+        let mut p = Csv::new();
+        p.configure(&ModuleOptions::parse(&["delim=;"]).unwrap())
+            .unwrap();
+        let primed = p.for_input(&["a,b;c", "'x;y';z"]).unwrap().unwrap();
+        let mut out = Vec::new();
+        assert!(primed.process_line_to_buf("'x;y';z", &mut out));
+        assert_eq!(out, b"[\"x;y\",\"z\"]\n");
+    }
+
+    #[test]
+    fn select_drop_and_rename_columns() {
+        let shaped = |opts: &[&str], row: &str| {
+            let mut p = Csv::new();
+            p.configure(&ModuleOptions::parse(opts).unwrap()).unwrap();
+            let mut out = Vec::new();
+            assert!(p.process_line_to_buf(row, &mut out));
+            serde_json::from_slice::<serde_json::Value>(&out).unwrap()
+        };
+
+        let v = shaped(
+            &[
+                "headers=ts,src,tgt,act",
+                "select=act,tgt,ts",
+                "rename=tgt:dst_ip",
+            ],
+            "t,1.1.1.1,2.2.2.2,allow,extra",
+        );
+        assert_eq!(
+            v,
+            serde_json::json!({"act": "allow", "dst_ip": "2.2.2.2", "ts": "t"})
+        );
+        assert_eq!(
+            serde_json::to_string(&v).unwrap(),
+            r#"{"act":"allow","dst_ip":"2.2.2.2","ts":"t"}"#
+        );
+
+        let v = shaped(&["headers=ts,src,noise", "drop=noise,_extra"], "t,s,n,x");
+        assert_eq!(v, serde_json::json!({"ts": "t", "src": "s"}));
+
+        let bad = |opts: &[&str]| Csv::new().configure(&ModuleOptions::parse(opts).unwrap());
+        assert!(bad(&["headers=a,b", "select=c"]).is_err());
+        assert!(bad(&["headers=a,b", "rename=a"]).is_err());
+        assert!(bad(&["drop=a"]).is_err());
+        assert!(bad(&["header_row=true", "drop=a"]).is_ok());
+    }
+
+    #[test]
+    fn split_edge_cases() {
+        assert_eq!(split_record("", b',', b'"').unwrap(), vec![""]);
+        assert_eq!(split_record("a,,", b',', b'"').unwrap(), vec!["a", "", ""]);
+        assert_eq!(
+            split_record("\"a\"b,c", b',', b'"').unwrap(),
+            vec!["ab", "c"]
+        );
+        assert!(split_record("\"open,c", b',', b'"').is_none());
+    }
+}
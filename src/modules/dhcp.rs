@@ -0,0 +1,293 @@
+use crate::core::Parser;
+use crate::modules::common::split_syslog;
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use std::net::IpAddr;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime, UtcOffset};
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(Dhcp)
+}
+
+/// DHCP server logs: ISC dhcpd syslog lines (`DHCPACK on 10.0.0.50 to
+/// 00:11:22:33:44:55 (laptop) via eth0`) and the Windows DHCP Server audit
+/// log (`DhcpSrvLog-*.log`, CSV by event ID). Both give `event`, `ip`,
+/// `mac` and `hostname`, for mapping addresses to machines over time.
+pub struct Dhcp;
+
+/// Windows audit log columns after `ID,Date,Time,Description,IP Address,
+/// Host Name,MAC Address` (Windows Server 2012 and later).
+const WINDOWS_EXTRA: &[&str] = &[
+    "user_name",
+    "transaction_id",
+    "qresult",
+    "probation_time",
+    "correlation_id",
+    "dhcid",
+    "vendor_class_hex",
+    "vendor_class",
+    "user_class_hex",
+    "user_class",
+    "relay_agent_information",
+    "dns_reg_error",
+];
+
+/// `001122AABBCC`, `00-11-22-AA-BB-CC` -> `00:11:22:aa:bb:cc`; other values
+/// (hardware types, client ids) lowercased as written.
+fn normalize_mac(value: &str) -> String {
+    let hex: String = value
+        .chars()
+        .filter(|c| !matches!(c, ':' | '-' | '.'))
+        .collect();
+    if hex.len() == 12 && hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        let pairs: Vec<&str> = (0..12).step_by(2).map(|i| &hex[i..i + 2]).collect();
+        return pairs.join(":").to_ascii_lowercase();
+    }
+    value.to_ascii_lowercase()
+}
+
+/// `DHCPREQUEST for 10.0.0.50 (10.0.0.1) from 00:11:22:33:44:55 (laptop)
+/// via eth0: wrong network.` -> fields after `event`.
+fn isc(message: &str, rec: &mut Map<String, Value>) -> Option<()> {
+    let (body, detail) = match message.split_once(": ") {
+        Some((body, detail)) => (body, Some(detail)),
+        None => (message, None),
+    };
+    let mut tokens = body.split_ascii_whitespace();
+    let kind = tokens.next()?.strip_prefix("DHCP")?;
+    if !matches!(
+        kind,
+        "DISCOVER"
+            | "OFFER"
+            | "REQUEST"
+            | "ACK"
+            | "NAK"
+            | "RELEASE"
+            | "DECLINE"
+            | "INFORM"
+            | "LEASEQUERY"
+            | "LEASEUNKNOWN"
+            | "LEASEACTIVE"
+    ) {
+        return None;
+    }
+    rec.insert("event".to_string(), kind.to_ascii_lowercase().into());
+
+    let (mut ip, mut server_ip, mut mac, mut hostname, mut via) = (None, None, None, None, None);
+    // What the last address was, so a `(...)` that follows it is placed.
+    let mut last = "";
+    while let Some(token) = tokens.next() {
+        match token {
+            "on" | "for" | "of" => {
+                ip = tokens.next();
+                last = "ip";
+            }
+            "to" | "from" => {
+                let addr = tokens.next()?;
+                if addr.parse::<IpAddr>().is_ok() {
+                    ip = Some(addr);
+                    last = "";
+                } else {
+                    mac = Some(normalize_mac(addr));
+                    last = "mac";
+                }
+            }
+            "via" => {
+                via = tokens.next();
+                last = "";
+            }
+            t if t.starts_with('(') => {
+                let mut text = t.to_string();
+                while !text.ends_with(')') {
+                    text.push(' ');
+                    text.push_str(tokens.next()?);
+                }
+                let text = text[1..text.len() - 1].to_string();
+                match last {
+                    "ip" => server_ip = Some(text),
+                    "mac" => hostname = Some(text),
+                    _ => {}
+                }
+                last = "";
+            }
+            _ => last = "",
+        }
+    }
+    let relay = via.filter(|v| v.parse::<IpAddr>().is_ok());
+    let interface = via.filter(|_| relay.is_none());
+    for (key, value) in [
+        ("ip", ip.map(str::to_string)),
+        ("mac", mac),
+        ("hostname", hostname),
+        ("server_ip", server_ip),
+        ("interface", interface.map(str::to_string)),
+        ("relay", relay.map(str::to_string)),
+        ("detail", detail.map(str::to_string)),
+    ] {
+        rec.insert(key.to_string(), value.into());
+    }
+    Some(())
+}
+
+/// `10,01/20/24,10:11:12,Assign,10.0.0.50,laptop.corp.local,001122334455,...`
+fn windows(line: &str, rec: &mut Map<String, Value>) -> Option<()> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(line.as_bytes());
+    let row = rdr.records().next()?.ok()?;
+    if row.len() < 7 {
+        return None;
+    }
+    let event_id: u16 = row[0].trim().parse().ok()?;
+    // MM/DD/YY (or YYYY) in the server's local time.
+    let mut date = row[1].split('/');
+    let (month, day, year) = (date.next()?, date.next()?, date.next()?);
+    let (month, day, year): (u8, u8, u16) =
+        (month.parse().ok()?, day.parse().ok()?, year.parse().ok()?);
+    let year = if year < 100 { 2000 + year } else { year };
+    let time = row[2].trim();
+    if time.len() != 8 || time.as_bytes()[2] != b':' || time.as_bytes()[5] != b':' {
+        return None;
+    }
+    let field = |i: usize| -> Value {
+        row.get(i)
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map_or(Value::Null, Into::into)
+    };
+
+    rec.insert(
+        "ts".to_string(),
+        format!("{year:04}-{month:02}-{day:02}T{time}").into(),
+    );
+    rec.insert("server".to_string(), "windows".into());
+    rec.insert(
+        "event".to_string(),
+        row[3].trim().to_ascii_lowercase().into(),
+    );
+    rec.insert("event_id".to_string(), event_id.into());
+    rec.insert("ip".to_string(), field(4));
+    rec.insert(
+        "mac".to_string(),
+        row.get(6)
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map_or(Value::Null, |m| normalize_mac(m).into()),
+    );
+    rec.insert("hostname".to_string(), field(5));
+    rec.insert("description".to_string(), field(3));
+    for (i, key) in WINDOWS_EXTRA.iter().enumerate() {
+        if i + 7 < row.len() {
+            rec.insert(key.to_string(), field(i + 7));
+        }
+    }
+    Some(())
+}
+
+impl Parser for Dhcp {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("dhcp")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses ISC dhcpd syslog and Windows DHCP audit logs -> lease JSONL")
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["ip".to_string()]
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let mut rec = Map::new();
+        let parsed = if line.starts_with(|c: char| c.is_ascii_digit()) && line.contains(',') {
+            windows(line, &mut rec)
+        } else {
+            let message = match split_syslog(line) {
+                Some((syslog, message)) if syslog.program.starts_with("dhcpd") => {
+                    // Only an RFC 3339 syslog stamp has a year and a zone.
+                    let ts = OffsetDateTime::parse(syslog.syslog_timestamp, &Rfc3339)
+                        .ok()
+                        .and_then(|t| t.to_offset(UtcOffset::UTC).format(&Rfc3339).ok());
+                    rec.insert("ts".to_string(), ts.into());
+                    if let Ok(Value::Object(fields)) = serde_json::to_value(&syslog) {
+                        rec.extend(fields);
+                    }
+                    message
+                }
+                Some(_) => return false,
+                None => line,
+            };
+            rec.insert("server".to_string(), "isc".into());
+            isc(message, &mut rec)
+        };
+        if parsed.is_none() {
+            return false;
+        }
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(line: &str) -> Option<Value> {
+        let mut out = Vec::new();
+        Dhcp.process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn parses_isc_dhcpd_lines() {
+        let v = run("Jan 20 10:11:12 gw dhcpd[812]: DHCPREQUEST for 10.0.0.50 (10.0.0.1) from 00:11:22:AA:BB:CC (Bobs Laptop) via eth0").unwrap();
+        assert_eq!(v["host"], "gw");
+        assert_eq!(v["syslog_timestamp"], "Jan 20 10:11:12");
+        assert_eq!(v["server"], "isc");
+        assert_eq!(v["event"], "request");
+        assert_eq!(v["ip"], "10.0.0.50");
+        assert_eq!(v["server_ip"], "10.0.0.1");
+        assert_eq!(v["mac"], "00:11:22:aa:bb:cc");
+        assert_eq!(v["hostname"], "Bobs Laptop");
+        assert_eq!(v["interface"], "eth0");
+
+        let v = run("DHCPDISCOVER from 00:11:22:aa:bb:cc via 10.20.0.1: network 10.20.0.0/24: no free leases").unwrap();
+        assert_eq!(v["event"], "discover");
+        assert_eq!(v["ip"], Value::Null);
+        assert_eq!(v["relay"], "10.20.0.1");
+        assert_eq!(v["detail"], "network 10.20.0.0/24: no free leases");
+        let v = run("Jan 20 10:11:12 gw dhcpd: DHCPRELEASE of 10.0.0.50 from 00:11:22:aa:bb:cc (laptop) via eth0 (not found)").unwrap();
+        assert_eq!(v["event"], "release");
+        assert_eq!(v["hostname"], "laptop");
+        let v = run("Jan 20 10:11:12 gw dhcpd: DHCPINFORM from 10.0.0.77 via eth0").unwrap();
+        assert_eq!(v["ip"], "10.0.0.77");
+        assert!(run("Jan 20 10:11:12 gw dhcpd: Wrote 12 leases to leases file.").is_none());
+        assert!(run("Jan 20 10:11:12 gw sshd[1]: DHCPACK on 10.0.0.5 to aa").is_none());
+    }
+
+    #[test]
+    fn parses_windows_audit_log() {
+        let v = run("10,01/20/24,10:11:12,Assign,10.0.0.50,laptop.corp.local,001122AABBCC,,2874567211,0,,,,0x4D53465420352E30,MSFT 5.0,,,,0").unwrap();
+        assert_eq!(v["ts"], "2024-01-20T10:11:12");
+        assert_eq!(v["server"], "windows");
+        assert_eq!(v["event"], "assign");
+        assert_eq!(v["event_id"], 10);
+        assert_eq!(v["ip"], "10.0.0.50");
+        assert_eq!(v["mac"], "00:11:22:aa:bb:cc");
+        assert_eq!(v["hostname"], "laptop.corp.local");
+        assert_eq!(v["transaction_id"], "2874567211");
+        assert_eq!(v["vendor_class"], "MSFT 5.0");
+        assert_eq!(v["user_name"], Value::Null);
+
+        let v = run("11,01/20/2024,10:11:12,Renew,10.0.0.50,laptop,001122AABBCC").unwrap();
+        assert_eq!(v["event"], "renew");
+        assert!(v.get("vendor_class").is_none());
+        assert!(run("ID,Date,Time,Description,IP Address,Host Name,MAC Address").is_none());
+        assert!(run("00\tThe log was started.").is_none());
+    }
+}
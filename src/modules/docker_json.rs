@@ -0,0 +1,248 @@
+use crate::core::{parse_nested, LineJoiner, LinePos, ModuleOptions, OptionSpec, Parser, Registry};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(DockerJson { inner: None })
+}
+
+/// Docker `json-file` log driver output (`<container>-json.log`):
+/// `{"log":"...\n","stream":"stdout","time":"..."}` per line.
+///
+/// Docker cuts messages over 16 KiB into several entries; only the last one
+/// ends with `\n`. The joiner glues them back together per stream.
+pub struct DockerJson {
+    /// Module parsing the message itself (`--opt inner=web-access`).
+    inner: Option<Box<dyn Parser>>,
+}
+
+const OPTIONS: &[OptionSpec] = &[OptionSpec {
+    key: "inner",
+    help: "Module that parses each message; its record goes under `parsed` (null if rejected)",
+}];
+
+#[derive(Deserialize)]
+struct Entry<'a> {
+    #[serde(borrow)]
+    log: Cow<'a, str>,
+    #[serde(borrow)]
+    stream: Cow<'a, str>,
+    #[serde(borrow, default)]
+    time: Option<Cow<'a, str>>,
+    /// Labels / env added by `--log-opt labels=...`.
+    #[serde(default)]
+    attrs: Option<Map<String, Value>>,
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    ts: Option<&'a str>,
+    stream: &'a str,
+    /// True only for a split message whose last piece never arrived.
+    partial: bool,
+    message: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attrs: Option<&'a Map<String, Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parsed: Option<Value>,
+}
+
+fn parse_entry(line: &str) -> Option<Entry<'_>> {
+    serde_json::from_str(line.trim()).ok()
+}
+
+impl Parser for DockerJson {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("docker-json")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed(
+            "Docker json-file logs -> JSONL, split messages rejoined, optional inner module",
+        )
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        OPTIONS
+    }
+
+    fn configure(&mut self, opts: &ModuleOptions) -> Result<()> {
+        if let Some(name) = opts.get("inner") {
+            let inner = Registry::with_builtin()
+                .create(name)
+                .with_context(|| format!("unknown inner module: {name}"))?;
+            self.inner = Some(inner);
+        }
+        Ok(())
+    }
+
+    fn line_joiner(&self) -> Option<Box<dyn LineJoiner>> {
+        Some(Box::<SplitJoiner>::default())
+    }
+
+    fn message_field(&self) -> Option<&'static str> {
+        Some("message")
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let Some(e) = parse_entry(line) else {
+            return false;
+        };
+
+        let complete = e.log.ends_with('\n');
+        let message = e.log.strip_suffix('\n').unwrap_or(&e.log);
+        let message = message.strip_suffix('\r').unwrap_or(message);
+
+        let rec = Record {
+            ts: e.time.as_deref(),
+            stream: &e.stream,
+            partial: !complete,
+            message,
+            attrs: e.attrs.as_ref(),
+            parsed: self
+                .inner
+                .as_deref()
+                .map(|inner| parse_nested(inner, message)),
+        };
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+/// Pieces of split messages, kept apart per stream since stdout and stderr
+/// interleave. Each pending entry is the first piece with `log` extended,
+/// and where that piece was.
+#[derive(Default)]
+struct SplitJoiner {
+    pending: Vec<(String, LinePos, Map<String, Value>)>,
+}
+
+impl SplitJoiner {
+    fn emit_entry(entry: &Map<String, Value>, at: LinePos, emit: &mut dyn FnMut(&[u8], LinePos)) {
+        if let Ok(line) = serde_json::to_vec(entry) {
+            emit(&line, at);
+        }
+    }
+}
+
+impl LineJoiner for SplitJoiner {
+    fn push(&mut self, line: &[u8], at: LinePos, emit: &mut dyn FnMut(&[u8], LinePos)) {
+        let Some(e) = std::str::from_utf8(line).ok().and_then(parse_entry) else {
+            emit(line, at);
+            return;
+        };
+
+        let slot = self.pending.iter().position(|(s, ..)| *s == e.stream);
+        let complete = e.log.ends_with('\n');
+
+        match slot {
+            None if complete => emit(line, at),
+            None => {
+                if let Ok(Value::Object(entry)) = serde_json::from_slice(line) {
+                    self.pending.push((e.stream.into_owned(), at, entry));
+                } else {
+                    emit(line, at);
+                }
+            }
+            Some(i) => {
+                if let Some(Value::String(log)) = self.pending[i].2.get_mut("log") {
+                    log.push_str(&e.log);
+                }
+                if complete {
+                    let (_, start, entry) = self.pending.swap_remove(i);
+                    Self::emit_entry(&entry, start, emit);
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self, emit: &mut dyn FnMut(&[u8], LinePos)) {
+        // No final piece: emit what we have; it still lacks the `\n`.
+        for (_, at, entry) in self.pending.drain(..) {
+            Self::emit_entry(&entry, at, emit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(p: &dyn Parser, line: &str) -> Option<Value> {
+        let mut out = Vec::new();
+        p.process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn unescapes_and_trims_the_message() {
+        let v = run(
+            &*new(),
+            r#"{"log":"say \"hi\"\tthereé\n","stream":"stderr","time":"2024-01-02T15:04:05.123456789Z"}"#,
+        )
+        .unwrap();
+        assert_eq!(v["ts"], "2024-01-02T15:04:05.123456789Z");
+        assert_eq!(v["stream"], "stderr");
+        assert_eq!(v["partial"], false);
+        assert_eq!(v["message"], "say \"hi\"\tthereé");
+        assert!(v.get("parsed").is_none());
+    }
+
+    #[test]
+    fn inner_module_parses_the_message() {
+        let mut p = new();
+        p.configure(&ModuleOptions::parse(&["inner=logfmt"]).unwrap())
+            .unwrap();
+
+        let v = run(
+            &*p,
+            r#"{"log":"level=info msg=ok\n","stream":"stdout","time":"t"}"#,
+        )
+        .unwrap();
+        assert_eq!(v["parsed"]["level"], "info");
+        assert_eq!(v["parsed"]["msg"], "ok");
+
+        assert!(p
+            .configure(&ModuleOptions::parse(&["inner=nope"]).unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn joins_split_messages_per_stream() {
+        let mut j = SplitJoiner::default();
+        let mut out = Vec::new();
+        for l in [
+            r#"{"log":"aaa","stream":"stdout","time":"t1"}"#,
+            r#"{"log":"err\n","stream":"stderr","time":"t2"}"#,
+            r#"{"log":"bbb\n","stream":"stdout","time":"t3"}"#,
+            r#"{"log":"cut","stream":"stderr","time":"t4"}"#,
+        ] {
+            j.push(l.as_bytes(), LinePos::default(), &mut |r, _| {
+                out.push(r.to_vec())
+            });
+        }
+        j.finish(&mut |r, _| out.push(r.to_vec()));
+
+        let msgs: Vec<Value> = out
+            .iter()
+            .map(|r| run(&*new(), std::str::from_utf8(r).unwrap()).unwrap())
+            .collect();
+        assert_eq!(msgs.len(), 3);
+        assert_eq!(msgs[0]["message"], "err");
+        assert_eq!(msgs[1]["message"], "aaabbb");
+        assert_eq!(msgs[1]["ts"], "t1");
+        assert_eq!(msgs[2]["message"], "cut");
+        assert_eq!(msgs[2]["partial"], true);
+    }
+
+    #[test]
+    fn rejects_other_json() {
+        assert!(run(&*new(), r#"{"msg":"x"}"#).is_none());
+        assert!(run(&*new(), "not json").is_none());
+    }
+}
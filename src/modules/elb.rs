@@ -0,0 +1,309 @@
+use crate::core::{FieldType, Parser};
+use crate::modules::common::{percent_decode, rfc3339_to_utc, split_host_port, split_quoted};
+use anyhow::Result;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(Elb::default())
+}
+
+/// AWS Application Load Balancer and Classic Load Balancer access logs.
+/// ALB lines start with the request type (`http`, `h2`, ...), Classic lines
+/// with the timestamp.
+#[derive(Default)]
+pub struct Elb {
+    /// Layout of the input's first entry, for [`Parser::fields`].
+    layout: Option<&'static [(&'static str, Kind)]>,
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Text,
+    Int,
+    Float,
+    /// RFC 3339 timestamp, emitted in UTC.
+    Time,
+    /// `ip:port`, emitted as `<name>_ip` / `<name>_port`.
+    Addr,
+    /// `"METHOD url PROTOCOL"`, emitted whole plus split and URL-decoded.
+    Request,
+}
+
+const ALB_FIELDS: &[(&str, Kind)] = &[
+    ("type", Kind::Text),
+    ("ts", Kind::Time),
+    ("elb", Kind::Text),
+    ("client", Kind::Addr),
+    ("target", Kind::Addr),
+    ("request_processing_time", Kind::Float),
+    ("target_processing_time", Kind::Float),
+    ("response_processing_time", Kind::Float),
+    ("elb_status_code", Kind::Int),
+    ("target_status_code", Kind::Int),
+    ("received_bytes", Kind::Int),
+    ("sent_bytes", Kind::Int),
+    ("request", Kind::Request),
+    ("user_agent", Kind::Text),
+    ("ssl_cipher", Kind::Text),
+    ("ssl_protocol", Kind::Text),
+    ("target_group_arn", Kind::Text),
+    ("trace_id", Kind::Text),
+    ("domain_name", Kind::Text),
+    ("chosen_cert_arn", Kind::Text),
+    ("matched_rule_priority", Kind::Int),
+    ("request_creation_time", Kind::Text),
+    ("actions_executed", Kind::Text),
+    ("redirect_url", Kind::Text),
+    ("error_reason", Kind::Text),
+    ("target_port_list", Kind::Text),
+    ("target_status_code_list", Kind::Text),
+    ("classification", Kind::Text),
+    ("classification_reason", Kind::Text),
+    ("conn_trace_id", Kind::Text),
+];
+
+const CLASSIC_FIELDS: &[(&str, Kind)] = &[
+    ("ts", Kind::Time),
+    ("elb", Kind::Text),
+    ("client", Kind::Addr),
+    ("backend", Kind::Addr),
+    ("request_processing_time", Kind::Float),
+    ("backend_processing_time", Kind::Float),
+    ("response_processing_time", Kind::Float),
+    ("elb_status_code", Kind::Int),
+    ("backend_status_code", Kind::Int),
+    ("received_bytes", Kind::Int),
+    ("sent_bytes", Kind::Int),
+    ("request", Kind::Request),
+    ("user_agent", Kind::Text),
+    ("ssl_cipher", Kind::Text),
+    ("ssl_protocol", Kind::Text),
+];
+
+const ALB_TYPES: &[&str] = &["http", "https", "h2", "grpcs", "ws", "wss"];
+
+/// ALB or Classic columns from an entry's first token, and how many of them
+/// an entry needs: older log versions end early, but everything up to the
+/// request line is required.
+fn layout(first: &str) -> Option<(&'static [(&'static str, Kind)], usize)> {
+    if ALB_TYPES.contains(&first) {
+        Some((ALB_FIELDS, 13))
+    } else if first.as_bytes().first().is_some_and(u8::is_ascii_digit) {
+        Some((CLASSIC_FIELDS, 12))
+    } else {
+        None
+    }
+}
+
+impl Parser for Elb {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("elb")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses AWS ALB / Classic ELB access logs -> typed JSONL")
+    }
+
+    fn for_input(&self, head: &[&str]) -> Result<Option<Box<dyn Parser>>> {
+        let first = head.iter().find_map(|l| l.split_ascii_whitespace().next());
+        Ok(first.and_then(layout).map(|(fields, _)| {
+            Box::new(Elb {
+                layout: Some(fields),
+            }) as Box<dyn Parser>
+        }))
+    }
+
+    fn user_agent_fields(&self) -> Vec<String> {
+        vec!["user_agent".to_string()]
+    }
+
+    fn fields(&self) -> Vec<(String, FieldType)> {
+        let mut out = Vec::new();
+        for &(name, kind) in self.layout.unwrap_or_default() {
+            match kind {
+                Kind::Text | Kind::Time => out.push((name.to_string(), FieldType::Text)),
+                Kind::Int => out.push((name.to_string(), FieldType::Int)),
+                Kind::Float => out.push((name.to_string(), FieldType::Float)),
+                Kind::Addr => {
+                    out.push((format!("{name}_ip"), FieldType::Text));
+                    out.push((format!("{name}_port"), FieldType::Int));
+                }
+                Kind::Request => {
+                    for key in [name, "request_method", "request_url", "request_protocol"] {
+                        out.push((key.to_string(), FieldType::Text));
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec![
+            "client_ip".to_string(),
+            "target_ip".to_string(),
+            "backend_ip".to_string(),
+        ]
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let Some(tokens) = split_quoted(line.trim()) else {
+            return false;
+        };
+        let Some(first) = tokens.first() else {
+            return false;
+        };
+
+        let Some((fields, required)) = layout(first) else {
+            return false;
+        };
+        if tokens.len() < required {
+            return false;
+        }
+
+        let rec = Record {
+            fields,
+            tokens: &tokens,
+        };
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+struct Record<'a> {
+    fields: &'static [(&'static str, Kind)],
+    tokens: &'a [Cow<'a, str>],
+}
+
+/// `-` means "not available" in every column.
+fn opt(v: &str) -> Option<&str> {
+    (v != "-").then_some(v)
+}
+
+impl Serialize for Record<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+
+        for (&(name, kind), v) in self.fields.iter().zip(self.tokens) {
+            let v = v.as_ref();
+            match kind {
+                Kind::Text => map.serialize_entry(name, &opt(v))?,
+                Kind::Time => match opt(v) {
+                    Some(v) => {
+                        map.serialize_entry(name, &rfc3339_to_utc(v).as_deref().unwrap_or(v))?
+                    }
+                    None => map.serialize_entry(name, &())?,
+                },
+                Kind::Int => match opt(v).map(str::parse::<i64>) {
+                    Some(Ok(n)) => map.serialize_entry(name, &n)?,
+                    Some(Err(_)) => map.serialize_entry(name, v)?,
+                    None => map.serialize_entry(name, &())?,
+                },
+                Kind::Float => match opt(v).map(str::parse::<f64>) {
+                    Some(Ok(n)) => map.serialize_entry(name, &n)?,
+                    Some(Err(_)) => map.serialize_entry(name, v)?,
+                    None => map.serialize_entry(name, &())?,
+                },
+                Kind::Addr => {
+                    let (ip, port) = match opt(v) {
+                        Some(v) => {
+                            let (ip, port) = split_host_port(v);
+                            (Some(ip), port)
+                        }
+                        None => (None, None),
+                    };
+                    map.serialize_entry(&format!("{name}_ip"), &ip)?;
+                    map.serialize_entry(&format!("{name}_port"), &port)?;
+                }
+                Kind::Request => {
+                    map.serialize_entry(name, v)?;
+                    let mut parts = v.splitn(3, ' ');
+                    let (method, url, proto) = (parts.next(), parts.next(), parts.next());
+                    map.serialize_entry("request_method", &method.and_then(opt))?;
+                    map.serialize_entry(
+                        "request_url",
+                        &url.and_then(opt).map(|u| percent_decode(u)),
+                    )?;
+                    map.serialize_entry("request_protocol", &proto.and_then(opt))?;
+                }
+            }
+        }
+
+        if self.tokens.len() > self.fields.len() {
+            map.serialize_entry("extra", &self.tokens[self.fields.len()..])?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(line: &str) -> Option<serde_json::Value> {
+        let mut out = Vec::new();
+        Elb::default()
+            .process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn parses_alb_https_entry() {
+        let v = run(r#"https 2018-07-02T22:23:00.186641Z app/my-loadbalancer/50dc6c495c0c9188 192.168.131.39:2817 10.0.0.1:80 0.086 0.048 0.037 200 200 0 57 "GET https://www.example.com:443/a%20b?x=1 HTTP/1.1" "curl/7.46.0" ECDHE-RSA-AES128-GCM-SHA256 TLSv1.2 arn:aws:elasticloadbalancing:us-east-2:123456789012:targetgroup/my-targets/73e2d6bc24d8a067 "Root=1-58337281-1d84f3d73c47ec4e58577259" "www.example.com" "arn:aws:acm:us-east-2:123456789012:certificate/12345678-1234-1234-1234-123456789012" 1 2018-07-02T22:22:48.364000Z "authenticate,forward" "-" "-" "10.0.0.1:80" "200" "-" "-""#).unwrap();
+
+        assert_eq!(v["type"], "https");
+        assert_eq!(v["ts"], "2018-07-02T22:23:00.186641Z");
+        assert_eq!(v["client_ip"], "192.168.131.39");
+        assert_eq!(v["client_port"], 2817);
+        assert_eq!(v["target_port"], 80);
+        assert_eq!(v["target_processing_time"], 0.048);
+        assert_eq!(v["elb_status_code"], 200);
+        assert_eq!(v["sent_bytes"], 57);
+        assert_eq!(v["request_method"], "GET");
+        assert_eq!(v["request_url"], "https://www.example.com:443/a b?x=1");
+        assert_eq!(v["user_agent"], "curl/7.46.0");
+        assert_eq!(v["ssl_protocol"], "TLSv1.2");
+        assert_eq!(v["matched_rule_priority"], 1);
+        assert!(v["redirect_url"].is_null());
+        assert!(v.get("extra").is_none());
+    }
+
+    #[test]
+    fn parses_classic_entry_with_missing_backend() {
+        let v = run(r#"2015-05-13T23:39:43.945958Z my-loadbalancer 192.168.131.39:2817 - -1 -1 -1 503 0 0 0 "GET http://www.example.com:80/ HTTP/1.1" "curl/7.38.0" - -"#).unwrap();
+
+        assert_eq!(v["ts"], "2015-05-13T23:39:43.945958Z");
+        assert_eq!(v["elb"], "my-loadbalancer");
+        assert!(v["backend_ip"].is_null());
+        assert_eq!(v["request_processing_time"], -1.0);
+        assert_eq!(v["elb_status_code"], 503);
+        assert!(v["ssl_cipher"].is_null());
+        assert!(v.get("type").is_none());
+    }
+
+    #[test]
+    fn declares_the_layout_of_the_first_entry() {
+        let head = [
+            "",
+            r#"2015-05-13T23:39:43.945958Z my-loadbalancer 192.168.131.39:2817 - -1 -1 -1 503 0 0 0 "GET / HTTP/1.1" "curl/7.38.0" - -"#,
+        ];
+        let elb = Elb::default().for_input(&head).unwrap().unwrap();
+        let fields = elb.fields();
+        let names: Vec<_> = fields.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(&names[..4], ["ts", "elb", "client_ip", "client_port"]);
+        assert!(names.contains(&"backend_status_code"));
+        assert!(!names.contains(&"type"));
+        assert_eq!(fields[3].1, FieldType::Int);
+        assert!(Elb::default().fields().is_empty());
+    }
+
+    #[test]
+    fn rejects_other_lines() {
+        assert!(run("hello world").is_none());
+        assert!(run(r#"http 2018-07-02T22:23:00Z app/x 1.2.3.4:1 "unterminated"#).is_none());
+    }
+}
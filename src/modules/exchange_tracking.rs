@@ -0,0 +1,241 @@
+use crate::core::Parser;
+use crate::modules::common::rfc3339_to_utc;
+use anyhow::Result;
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use std::net::IpAddr;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(ExchangeTracking::with_fields(DEFAULT_FIELDS))
+}
+
+/// Exchange message tracking logs (`MSGTRK*.LOG`): CSV under `#Software:` /
+/// `#Fields:` directives. Multi-recipient columns are `;`-separated lists.
+/// `date-time` becomes `ts`; lines without it are rejected.
+pub struct ExchangeTracking {
+    /// JSON keys: `event-id` -> `event_id`.
+    fields: Vec<String>,
+}
+
+/// Columns of Exchange 2013 and later, used when a file has no `#Fields:` line.
+const DEFAULT_FIELDS: &str = "date-time,client-ip,client-hostname,server-ip,server-hostname,\
+    source-context,connector-id,source,event-id,internal-message-id,message-id,\
+    network-message-id,recipient-address,recipient-status,total-bytes,recipient-count,\
+    related-recipient-address,reference,message-subject,sender-address,return-path,\
+    message-info,directionality,tenant-id,original-client-ip,original-server-ip,custom-data,\
+    transport-traffic-type,log-id,schema-version";
+
+/// `;`-separated lists, one entry per recipient (or referenced message).
+const LIST_FIELDS: &[&str] = &[
+    "recipient_address",
+    "recipient_status",
+    "related_recipient_address",
+    "reference",
+];
+const INT_FIELDS: &[&str] = &["total_bytes", "recipient_count", "internal_message_id"];
+const IP_FIELDS: &[&str] = &[
+    "client_ip",
+    "server_ip",
+    "original_client_ip",
+    "original_server_ip",
+];
+/// Addresses compared case-insensitively by Exchange, written lowercase.
+const ADDRESS_FIELDS: &[&str] = &[
+    "sender_address",
+    "return_path",
+    "recipient_address",
+    "related_recipient_address",
+];
+
+impl ExchangeTracking {
+    fn with_fields(spec: &str) -> Self {
+        let fields = spec
+            .split(',')
+            .map(|name| name.trim().replace('-', "_"))
+            .collect();
+        Self { fields }
+    }
+}
+
+/// `::ffff:10.0.0.5` -> `10.0.0.5`; other values as written.
+fn normalize_ip(value: &str) -> String {
+    match value.parse::<IpAddr>() {
+        Ok(IpAddr::V6(v6)) => match v6.to_ipv4_mapped() {
+            Some(v4) => v4.to_string(),
+            None => v6.to_string(),
+        },
+        Ok(v4) => v4.to_string(),
+        Err(_) => value.to_string(),
+    }
+}
+
+/// `<id@host>` -> `id@host`.
+fn normalize_message_id(value: &str) -> &str {
+    value
+        .strip_prefix('<')
+        .and_then(|v| v.strip_suffix('>'))
+        .unwrap_or(value)
+}
+
+fn value(key: &str, raw: &str) -> Value {
+    if raw.is_empty() {
+        return Value::Null;
+    }
+    let scalar = |v: &str| -> Value {
+        if ADDRESS_FIELDS.contains(&key) {
+            v.to_lowercase().into()
+        } else {
+            v.into()
+        }
+    };
+    match key {
+        _ if LIST_FIELDS.contains(&key) => raw
+            .split(';')
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(scalar)
+            .collect(),
+        _ if INT_FIELDS.contains(&key) => {
+            raw.parse::<i64>().map_or_else(|_| raw.into(), Into::into)
+        }
+        _ if IP_FIELDS.contains(&key) => normalize_ip(raw).into(),
+        "message_id" => normalize_message_id(raw).into(),
+        "event_id" => raw.to_ascii_uppercase().into(),
+        _ => scalar(raw),
+    }
+}
+
+impl Parser for ExchangeTracking {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("exchange-tracking")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses Exchange message tracking logs (honours #Fields) -> typed JSONL")
+    }
+
+    fn for_input(&self, head: &[&str]) -> Result<Option<Box<dyn Parser>>> {
+        Ok(head
+            .iter()
+            .find_map(|l| l.strip_prefix("#Fields:"))
+            .map(|spec| Box::new(ExchangeTracking::with_fields(spec)) as Box<dyn Parser>))
+    }
+
+    fn recognizes(&self, line: &str) -> bool {
+        line.starts_with("#Log-type: Message Tracking Log")
+            || line.starts_with("#Fields: date-time,client-ip")
+            || self.process_line_to_buf(line, &mut Vec::new())
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        IP_FIELDS
+            .iter()
+            .filter(|f| self.fields.iter().any(|k| k == *f))
+            .map(|f| f.to_string())
+            .collect()
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        // `#Software:` / `#Fields:` ... directives carry no event.
+        if line.starts_with('#') {
+            return false;
+        }
+        let mut rdr = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(line.as_bytes());
+        let Some(Ok(row)) = rdr.records().next() else {
+            return false;
+        };
+        if row.len() != self.fields.len() {
+            return false;
+        }
+        let mut rec = Map::new();
+        for (key, raw) in self.fields.iter().zip(row.iter()) {
+            if key == "date_time" {
+                let Some(ts) = rfc3339_to_utc(raw) else {
+                    return false;
+                };
+                rec.insert("ts".to_string(), ts.into());
+            } else {
+                rec.insert(key.clone(), value(key, raw));
+            }
+        }
+        if !rec.contains_key("ts") {
+            return false;
+        }
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEAD: &[&str] = &[
+        "#Software: Microsoft Exchange Server",
+        "#Version: 15.02.1118.007",
+        "#Log-type: Message Tracking Log",
+        "#Date: 2024-02-03T00:00:01.207Z",
+        "#Fields: date-time,client-ip,client-hostname,server-ip,server-hostname,source-context,connector-id,source,event-id,internal-message-id,message-id,network-message-id,recipient-address,recipient-status,total-bytes,recipient-count,related-recipient-address,reference,message-subject,sender-address,return-path,message-info,directionality,tenant-id,original-client-ip,original-server-ip,custom-data,transport-traffic-type,log-id,schema-version",
+    ];
+    const DELIVER: &str = r#"2024-02-03T08:15:42.123Z,::ffff:10.0.0.5,EX01,10.0.0.6,EX02.corp.local,"08DC1;250 2.0.0 OK",Intra-Organization SMTP Send Connector,SMTP,SEND,123456,<AM0PR@mail.contoso.com>,1b2c-3d4e,"Alice@Contoso.com;bob@contoso.com","250 2.1.5 Recipient OK;250 2.1.5 Recipient OK",20480,2,,,"Q1 report; draft",Carol@Fabrikam.com,carol@fabrikam.com,,Incoming,,,,,Email,1b2c-3d4e,15.02.1118.007"#;
+
+    fn run(p: &ExchangeTracking, line: &str) -> Option<Value> {
+        let mut out = Vec::new();
+        p.process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn splits_recipients_and_normalizes_fields() {
+        let p = ExchangeTracking::with_fields(DEFAULT_FIELDS);
+        let v = run(&p, DELIVER).unwrap();
+        assert_eq!(v["ts"], "2024-02-03T08:15:42.123Z");
+        assert_eq!(v["client_ip"], "10.0.0.5");
+        assert_eq!(v["server_hostname"], "EX02.corp.local");
+        assert_eq!(v["event_id"], "SEND");
+        assert_eq!(v["message_id"], "AM0PR@mail.contoso.com");
+        assert_eq!(
+            v["recipient_address"],
+            serde_json::json!(["alice@contoso.com", "bob@contoso.com"])
+        );
+        assert_eq!(v["recipient_status"][1], "250 2.1.5 Recipient OK");
+        assert_eq!(v["recipient_count"], 2);
+        assert_eq!(v["total_bytes"], 20480);
+        assert_eq!(v["message_subject"], "Q1 report; draft");
+        assert_eq!(v["sender_address"], "carol@fabrikam.com");
+        assert_eq!(v["source_context"], "08DC1;250 2.0.0 OK");
+        assert_eq!(v["related_recipient_address"], Value::Null);
+    }
+
+    #[test]
+    fn fields_directive_sets_the_columns() {
+        let p = ExchangeTracking::with_fields(DEFAULT_FIELDS)
+            .for_input(HEAD)
+            .unwrap()
+            .unwrap();
+        let mut out = Vec::new();
+        assert!(p.process_line_to_buf(DELIVER, &mut out));
+        let short = ExchangeTracking::with_fields("date-time,event-id,recipient-address");
+        let v = run(&short, "2024-02-03T08:15:42Z,receive,a@x.com").unwrap();
+        assert_eq!(v["event_id"], "RECEIVE");
+        assert_eq!(v["recipient_address"], serde_json::json!(["a@x.com"]));
+        assert!(ExchangeTracking::with_fields(DEFAULT_FIELDS).recognizes(HEAD[2]));
+    }
+
+    #[test]
+    fn rejects_directives_and_other_csv() {
+        let p = ExchangeTracking::with_fields(DEFAULT_FIELDS);
+        for line in HEAD {
+            assert!(run(&p, line).is_none(), "{line}");
+        }
+        assert!(run(&p, "a,b,c").is_none());
+        assert!(!p.recognizes("2024-02-03T08:15:42Z,GET,/index.html"));
+    }
+}
@@ -0,0 +1,214 @@
+use crate::core::Parser;
+use crate::modules::common::{split_syslog, Syslog};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::net::IpAddr;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(Fail2ban)
+}
+
+/// `fail2ban.log` (or fail2ban through syslog): the `Found`, `Ban`,
+/// `Unban`, `Restore Ban`, `already banned` and `Ignore` lines of each jail,
+/// for block timelines to lay next to the auth logs. Startup and
+/// configuration lines are rejected.
+pub struct Fail2ban;
+
+#[derive(Default, Serialize)]
+struct Record<'a> {
+    #[serde(flatten)]
+    syslog: Option<Syslog<'a>>,
+    /// `fail2ban.log` stamp in the server's local time.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ts: Option<String>,
+    /// `fail2ban.filter`, `fail2ban.actions`, ...
+    logger: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pid: Option<u32>,
+    level: &'a str,
+    jail: &'a str,
+    /// `found`, `ban`, `unban`, `restore_ban`, `already_banned` or `ignore`.
+    action: &'static str,
+    ip: &'a str,
+    /// When the filter saw the matching log line (`Found ... - <time>`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    match_time: Option<String>,
+    /// Why an address was ignored (`by ip`, `by ignoreself rule`, ...).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'a str>,
+}
+
+/// `2024-01-20 10:11:12,345` -> `2024-01-20T10:11:12.345`.
+fn local_time(s: &str) -> Option<String> {
+    let b = s.as_bytes();
+    if b.len() < 19 || b[4] != b'-' || b[7] != b'-' || b[10] != b' ' || b[13] != b':' {
+        return None;
+    }
+    if !s[..4].bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(format!("{}T{}", &s[..10], s[11..].replacen(',', ".", 1)))
+}
+
+/// `fail2ban.actions        [1234]: NOTICE  [sshd] ...` (0.9 and later) or
+/// `fail2ban.actions: WARNING [ssh] ...` (0.8).
+fn split_logger(s: &str) -> Option<(&str, Option<u32>, &str)> {
+    let end = s.find([' ', ':', '['])?;
+    let logger = &s[..end];
+    if !logger.starts_with("fail2ban") {
+        return None;
+    }
+    let rest = s[end..].trim_start();
+    let (pid, rest) = match rest.strip_prefix('[') {
+        Some(r) => {
+            let (pid, r) = r.split_once(']')?;
+            (Some(pid.parse().ok()?), r)
+        }
+        None => (None, rest),
+    };
+    Some((logger, pid, rest.strip_prefix(':')?.trim_start()))
+}
+
+/// `NOTICE  [sshd] Ban 192.0.2.10` -> level, jail, action fields.
+fn parse_message<'a>(rec: &mut Record<'a>, message: &'a str) -> Option<()> {
+    let (level, rest) = message.split_once(' ')?;
+    let (jail, event) = rest.trim_start().strip_prefix('[')?.split_once("] ")?;
+    rec.level = level;
+    rec.jail = jail;
+
+    let event = event.trim();
+    let (action, rest) = if let Some(r) = event.strip_prefix("Found ") {
+        ("found", r)
+    } else if let Some(r) = event.strip_prefix("Restore Ban ") {
+        ("restore_ban", r)
+    } else if let Some(r) = event.strip_prefix("Ban ") {
+        ("ban", r)
+    } else if let Some(r) = event.strip_prefix("Unban ") {
+        ("unban", r)
+    } else if let Some(r) = event.strip_prefix("Ignore ") {
+        ("ignore", r)
+    } else if let Some(ip) = event.strip_suffix(" already banned") {
+        ("already_banned", ip)
+    } else {
+        return None;
+    };
+    let (ip, tail) = rest.split_once(' ').unwrap_or((rest, ""));
+    ip.parse::<IpAddr>().ok()?;
+    rec.action = action;
+    rec.ip = ip;
+    match action {
+        "found" => rec.match_time = tail.strip_prefix("- ").and_then(local_time),
+        "ignore" => rec.reason = Some(tail).filter(|t| !t.is_empty()),
+        _ => {}
+    }
+    Some(())
+}
+
+impl Parser for Fail2ban {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("fail2ban")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses fail2ban.log Found/Ban/Unban lines -> JSONL")
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["ip".to_string()]
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let mut rec = Record::default();
+        let message = match split_syslog(line) {
+            Some((syslog, message)) if syslog.program.starts_with("fail2ban") => {
+                rec.logger = syslog.program;
+                rec.syslog = Some(syslog);
+                message
+            }
+            _ => {
+                let Some((stamp, rest)) = line.get(..23).zip(line.get(23..)) else {
+                    return false;
+                };
+                let Some((logger, pid, message)) = split_logger(rest.trim_start()) else {
+                    return false;
+                };
+                rec.ts = local_time(stamp);
+                rec.logger = logger;
+                rec.pid = pid;
+                message
+            }
+        };
+        if parse_message(&mut rec, message).is_none() {
+            return false;
+        }
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn run(line: &str) -> Option<Value> {
+        let mut out = Vec::new();
+        Fail2ban
+            .process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn parses_log_file_lines() {
+        let v = run(
+            "2024-01-20 10:11:12,345 fail2ban.actions        [812]: NOTICE  [sshd] Ban 192.0.2.10",
+        )
+        .unwrap();
+        assert_eq!(v["ts"], "2024-01-20T10:11:12.345");
+        assert_eq!(v["logger"], "fail2ban.actions");
+        assert_eq!(v["pid"], 812);
+        assert_eq!(v["level"], "NOTICE");
+        assert_eq!(v["jail"], "sshd");
+        assert_eq!(v["action"], "ban");
+        assert_eq!(v["ip"], "192.0.2.10");
+
+        let v = run("2024-01-20 10:11:10,001 fail2ban.filter         [812]: INFO    [sshd] Found 2001:db8::5 - 2024-01-20 10:11:09").unwrap();
+        assert_eq!(v["action"], "found");
+        assert_eq!(v["ip"], "2001:db8::5");
+        assert_eq!(v["match_time"], "2024-01-20T10:11:09");
+        let v = run("2024-01-20 10:11:12,345 fail2ban.actions        [812]: NOTICE  [nginx-http-auth] Restore Ban 192.0.2.10").unwrap();
+        assert_eq!(v["action"], "restore_ban");
+        let v = run("2024-01-20 10:11:12,345 fail2ban.actions        [812]: NOTICE  [sshd] 192.0.2.10 already banned").unwrap();
+        assert_eq!(v["action"], "already_banned");
+        let v = run("2024-01-20 10:11:12,345 fail2ban.filter         [812]: INFO    [sshd] Ignore 10.0.0.1 by ip").unwrap();
+        assert_eq!(
+            (&v["action"], &v["reason"]),
+            (&"ignore".into(), &"by ip".into())
+        );
+        let v = run("2014-01-20 10:11:12,345 fail2ban.actions: WARNING [ssh] Unban 192.0.2.10")
+            .unwrap();
+        assert_eq!(v["pid"], Value::Null);
+        assert_eq!(v["action"], "unban");
+    }
+
+    #[test]
+    fn parses_syslog_and_rejects_other_lines() {
+        let v =
+            run("Jan 20 10:11:12 gw fail2ban.actions[812]: NOTICE [sshd] Ban 192.0.2.10").unwrap();
+        assert_eq!(v["host"], "gw");
+        assert_eq!(v["syslog_timestamp"], "Jan 20 10:11:12");
+        assert_eq!(v["logger"], "fail2ban.actions");
+        assert_eq!(v["jail"], "sshd");
+        assert!(v.get("ts").is_none());
+        assert!(run(
+            "2024-01-20 10:11:12,345 fail2ban.jail           [812]: INFO    Jail 'sshd' started"
+        )
+        .is_none());
+        assert!(run("2024-01-20 10:11:12,345 fail2ban.filter         [812]: INFO    [sshd] Added logfile: '/var/log/auth.log'").is_none());
+        assert!(run("Jan 20 10:11:12 gw sshd[1]: NOTICE [sshd] Ban 192.0.2.10").is_none());
+    }
+}
@@ -0,0 +1,195 @@
+use crate::core::Parser;
+use crate::modules::logfmt::parse_pairs;
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use time::{
+    format_description::well_known::Rfc3339, macros::format_description, PrimitiveDateTime,
+    UtcOffset,
+};
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(FortiGate)
+}
+
+/// FortiGate (FortiOS) logs: `key=value` pairs with double-quoted strings,
+/// optionally behind a `<PRI>` or a syslog header. The split `date=`/`time=`
+/// columns (plus `tz=` when present) are merged into one `ts`.
+pub struct FortiGate;
+
+/// Counters, ports and ids that FortiOS writes unquoted.
+const INT_KEYS: &[&str] = &[
+    "eventtime",
+    "srcport",
+    "dstport",
+    "transport",
+    "policyid",
+    "sessionid",
+    "proto",
+    "duration",
+    "sentbyte",
+    "rcvdbyte",
+    "sentpkt",
+    "rcvdpkt",
+    "sentdelta",
+    "rcvddelta",
+    "appid",
+    "countapp",
+    "crscore",
+    "craction",
+    "vwlid",
+    "lanin",
+    "lanout",
+    "wanin",
+    "wanout",
+];
+
+impl Parser for FortiGate {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("fortigate")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses FortiGate key=value logs -> typed JSONL with a merged timestamp")
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["srcip".to_string(), "dstip".to_string()]
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let Some(payload) = payload(line) else {
+            return false;
+        };
+        let Some(pairs) = parse_pairs(payload) else {
+            return false;
+        };
+
+        let mut date = None;
+        let mut time = None;
+        let mut tz = None;
+        let mut fields = Map::new();
+        for pair in pairs {
+            let Some(value) = pair.value else {
+                continue;
+            };
+            match pair.key {
+                "date" => date = Some(value),
+                "time" => time = Some(value),
+                key => {
+                    if key == "tz" {
+                        tz = Some(value.clone());
+                    }
+                    fields.insert(key.to_string(), typed(key, value));
+                }
+            }
+        }
+        if !fields.contains_key("logid") && !fields.contains_key("log_id") {
+            return false;
+        }
+
+        let mut rec = Map::new();
+        let timestamp = date
+            .zip(time)
+            .and_then(|(d, t)| merge_timestamp(&d, &t, tz.as_deref()));
+        rec.insert("ts".into(), timestamp.map_or(Value::Null, Value::String));
+        rec.extend(fields);
+
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+/// The `key=value` part of the line: from `date=` when a syslog header or
+/// `<PRI>` precedes it, else the whole line.
+fn payload(line: &str) -> Option<&str> {
+    if line.starts_with("date=") {
+        return Some(line);
+    }
+    if let Some(i) = line.find(" date=") {
+        return Some(&line[i + 1..]);
+    }
+    let rest = line.strip_prefix('<').and_then(|r| r.split_once('>'));
+    match rest {
+        Some((pri, rest)) if pri.bytes().all(|b| b.is_ascii_digit()) => Some(rest),
+        Some(_) => None,
+        None => Some(line),
+    }
+}
+
+fn typed(key: &str, value: Cow<'_, str>) -> Value {
+    if INT_KEYS.contains(&key)
+        && let Ok(n) = value.parse::<i64>()
+    {
+        return Value::from(n);
+    }
+    Value::String(value.into_owned())
+}
+
+/// `2024-01-02` + `03:04:05` (+ `+0100`) -> RFC 3339 in UTC, or the local
+/// `2024-01-02T03:04:05` when the offset is unknown.
+fn merge_timestamp(date: &str, time: &str, tz: Option<&str>) -> Option<String> {
+    let fmt = format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
+    let local = PrimitiveDateTime::parse(&format!("{date} {time}"), &fmt).ok()?;
+    let offset = tz.and_then(|tz| {
+        let fmt = format_description!("[offset_hour sign:mandatory][optional [:]][offset_minute]");
+        UtcOffset::parse(tz, &fmt).ok()
+    });
+    match offset {
+        Some(offset) => local
+            .assume_offset(offset)
+            .to_offset(UtcOffset::UTC)
+            .format(&Rfc3339)
+            .ok(),
+        None => Some(format!("{date}T{time}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(line: &str) -> Option<serde_json::Value> {
+        let mut out = Vec::new();
+        FortiGate
+            .process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn parses_traffic_log() {
+        let v = run(r#"<189>date=2024-01-02 time=03:04:05 devname="FGT60E" devid="FGT60ETK1800" eventtime=1704161045123456789 tz="+0100" logid="0000000013" type="traffic" subtype="forward" level="notice" vd="root" srcip=10.0.0.1 srcport=51234 srcintf="port1" dstip=8.8.8.8 dstport=53 action="accept" policyid=1 service="DNS" sentbyte=100 rcvdbyte=200 duration=3 msg="a \"quoted\" note""#).unwrap();
+
+        assert_eq!(v["ts"], "2024-01-02T02:04:05Z");
+        assert!(v.get("date").is_none());
+        assert_eq!(v["devname"], "FGT60E");
+        assert_eq!(v["eventtime"], 1704161045123456789_i64);
+        assert_eq!(v["logid"], "0000000013");
+        assert_eq!(v["srcip"], "10.0.0.1");
+        assert_eq!(v["srcport"], 51234);
+        assert_eq!(v["dstport"], 53);
+        assert_eq!(v["action"], "accept");
+        assert_eq!(v["policyid"], 1);
+        assert_eq!(v["service"], "DNS");
+        assert_eq!(v["sentbyte"], 100);
+        assert_eq!(v["msg"], r#"a "quoted" note"#);
+    }
+
+    #[test]
+    fn handles_syslog_header_and_missing_tz() {
+        let v = run("Jan  2 03:04:05 10.1.1.1 date=2024-01-02 time=03:04:05 logid=0100032001 type=event subtype=system user=admin action=login status=success").unwrap();
+        assert_eq!(v["ts"], "2024-01-02T03:04:05");
+        assert_eq!(v["logid"], "0100032001");
+        assert_eq!(v["user"], "admin");
+    }
+
+    #[test]
+    fn rejects_other_key_value_lines() {
+        assert!(run("level=info msg=hello").is_none());
+        assert!(run(r#"date=2024-01-02 logid="1 msg=unterminated"#).is_none());
+        assert!(run("plain text").is_none());
+    }
+}
@@ -0,0 +1,153 @@
+use crate::core::Parser;
+use crate::modules::common::{flatten, rfc3339_to_utc};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(GcpAudit)
+}
+
+/// GCP Cloud Audit Logs as exported by a Cloud Logging sink (one `LogEntry`
+/// per line). The `protoPayload` fields move to the top level, flattened to
+/// dotted keys (`authenticationInfo.principalEmail`,
+/// `requestMetadata.callerIp`), next to `resource.type` /
+/// `resource.labels.*`, `severity` and a `ts` in RFC 3339 UTC.
+pub struct GcpAudit;
+
+const AUDIT_LOG_TYPE: &str = "type.googleapis.com/google.cloud.audit.AuditLog";
+
+/// Service-specific payloads. Flattening them would create thousands of
+/// distinct keys across a project, so they stay nested objects.
+const NESTED_KEYS: &[&str] = &[
+    "request",
+    "response",
+    "metadata",
+    "serviceData",
+    "resourceOriginalState",
+];
+
+/// `projects/p/logs/cloudaudit.googleapis.com%2Factivity` -> `activity`.
+fn log_type(log_name: &str) -> Option<&str> {
+    let name = log_name.rsplit_once("/logs/")?.1;
+    name.strip_prefix("cloudaudit.googleapis.com%2F")
+        .or_else(|| name.strip_prefix("cloudaudit.googleapis.com/"))
+}
+
+impl Parser for GcpAudit {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("gcp-audit")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("GCP Cloud Audit Log JSON entries -> flattened protoPayload JSONL")
+    }
+
+    fn recognizes(&self, line: &str) -> bool {
+        line.contains("google.cloud.audit.AuditLog")
+            && self.process_line_to_buf(line, &mut Vec::new())
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["requestMetadata.callerIp".to_string()]
+    }
+
+    fn user_agent_fields(&self) -> Vec<String> {
+        vec!["requestMetadata.callerSuppliedUserAgent".to_string()]
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let Ok(Value::Object(mut entry)) = serde_json::from_str::<Value>(line.trim()) else {
+            return false;
+        };
+        let Some(Value::Object(mut payload)) = entry.shift_remove("protoPayload") else {
+            return false;
+        };
+        if payload
+            .shift_remove("@type")
+            .as_ref()
+            .and_then(Value::as_str)
+            != Some(AUDIT_LOG_TYPE)
+        {
+            return false;
+        }
+
+        let mut flat = Map::new();
+        let ts = entry.shift_remove("timestamp");
+        flat.insert(
+            "ts".to_string(),
+            match ts {
+                Some(Value::String(s)) => Value::String(rfc3339_to_utc(&s).unwrap_or(s)),
+                other => other.unwrap_or(Value::Null),
+            },
+        );
+        for key in ["severity", "logName"] {
+            flat.insert(
+                key.to_string(),
+                entry.shift_remove(key).unwrap_or(Value::Null),
+            );
+        }
+        let kind = flat["logName"]
+            .as_str()
+            .and_then(log_type)
+            .map(str::to_string);
+        flat.insert("logType".to_string(), kind.into());
+        flatten("", payload, NESTED_KEYS, &mut flat);
+        if let Some(Value::String(s)) = entry.get_mut("receiveTimestamp")
+            && let Some(t) = rfc3339_to_utc(s)
+        {
+            *s = t;
+        }
+        flatten("", entry, NESTED_KEYS, &mut flat);
+        if serde_json::to_writer(&mut *out, &flat).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INSERT: &str = r#"{"protoPayload":{"@type":"type.googleapis.com/google.cloud.audit.AuditLog","status":{"code":7,"message":"PERMISSION_DENIED"},"authenticationInfo":{"principalEmail":"alice@example.com","serviceAccountDelegationInfo":[{"firstPartyPrincipal":{"principalEmail":"sa@p.iam.gserviceaccount.com"}}]},"requestMetadata":{"callerIp":"203.0.113.9","callerSuppliedUserAgent":"google-cloud-sdk gcloud/460.0.0","requestAttributes":{"time":"2024-02-03T04:05:06.789Z","auth":{}},"destinationAttributes":{}},"serviceName":"compute.googleapis.com","methodName":"v1.compute.instances.insert","authorizationInfo":[{"permission":"compute.instances.create","granted":false,"resourceAttributes":{"name":"projects/p1/zones/us-central1-a/instances/vm1"}}],"resourceName":"projects/p1/zones/us-central1-a/instances/vm1","request":{"@type":"type.googleapis.com/compute.instances.insert","name":"vm1","disks":[{"boot":true}]}},"insertId":"-abc123","resource":{"type":"gce_instance","labels":{"instance_id":"42","project_id":"p1","zone":"us-central1-a"}},"timestamp":"2024-02-03T05:05:06.789+01:00","severity":"ERROR","logName":"projects/p1/logs/cloudaudit.googleapis.com%2Factivity","operation":{"id":"op-1","producer":"compute.googleapis.com","first":true},"receiveTimestamp":"2024-02-03T04:05:07.123456789Z"}"#;
+
+    fn run(line: &str) -> Option<Value> {
+        let mut out = Vec::new();
+        GcpAudit
+            .process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn flattens_the_audit_payload_and_resource() {
+        let v = run(INSERT).unwrap();
+        assert_eq!(v["ts"], "2024-02-03T04:05:06.789Z");
+        assert_eq!(v["severity"], "ERROR");
+        assert_eq!(v["logType"], "activity");
+        assert_eq!(v["methodName"], "v1.compute.instances.insert");
+        assert_eq!(v["serviceName"], "compute.googleapis.com");
+        assert_eq!(v["authenticationInfo.principalEmail"], "alice@example.com");
+        assert_eq!(v["requestMetadata.callerIp"], "203.0.113.9");
+        assert_eq!(v["status.code"], 7);
+        assert_eq!(v["authorizationInfo"][0]["granted"], false);
+        assert_eq!(v["request"]["name"], "vm1");
+        assert_eq!(v["resource.type"], "gce_instance");
+        assert_eq!(v["resource.labels.zone"], "us-central1-a");
+        assert_eq!(v["operation.first"], true);
+        assert_eq!(v["receiveTimestamp"], "2024-02-03T04:05:07.123456789Z");
+        assert!(v.get("@type").is_none() && v.get("protoPayload").is_none());
+    }
+
+    #[test]
+    fn rejects_other_log_entries() {
+        assert!(run(r#"{"textPayload":"hello","severity":"INFO"}"#).is_none());
+        let other = INSERT.replace("google.cloud.audit.AuditLog", "google.cloud.Other");
+        assert!(run(&other).is_none());
+        assert!(GcpAudit.recognizes(INSERT));
+        assert_eq!(
+            log_type("organizations/1/logs/cloudaudit.googleapis.com%2Fdata_access"),
+            Some("data_access")
+        );
+    }
+}
@@ -0,0 +1,262 @@
+use crate::core::Parser;
+use crate::modules::common::{month_number, split_host_port, split_syslog, Syslog};
+use serde::Serialize;
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(HaProxy)
+}
+
+/// HAProxy HTTP log format (`option httplog`), with or without the syslog
+/// preamble:
+/// `client:port [accept_date] frontend backend/server Tq/Tw/Tc/Tr/Tt status
+/// bytes req_cookie res_cookie term_state actconn/feconn/beconn/srv_conn/retries
+/// srv_queue/backend_queue {req_headers} {res_headers} "request"`.
+pub struct HaProxy;
+
+#[derive(Serialize)]
+struct Record<'a> {
+    #[serde(flatten)]
+    syslog: Option<Syslog<'a>>,
+    client_ip: &'a str,
+    client_port: Option<u16>,
+    accept_date: &'a str,
+    /// `accept_date` as ISO 8601, in HAProxy's local time (no offset is logged).
+    ts: Option<String>,
+    frontend: &'a str,
+    /// Frontend name ended with `~`.
+    ssl: bool,
+    backend: &'a str,
+    server: &'a str,
+    tq: i64,
+    tw: i64,
+    tc: i64,
+    tr: i64,
+    tt: i64,
+    status: i64,
+    bytes_read: i64,
+    captured_request_cookie: Option<&'a str>,
+    captured_response_cookie: Option<&'a str>,
+    termination_state: &'a str,
+    actconn: i64,
+    feconn: i64,
+    beconn: i64,
+    srv_conn: i64,
+    retries: i64,
+    /// Retries field prefixed with `+`: the request was redispatched.
+    redispatched: bool,
+    srv_queue: i64,
+    backend_queue: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    captured_request_headers: Option<Vec<&'a str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    captured_response_headers: Option<Vec<&'a str>>,
+    request: &'a str,
+    request_method: Option<&'a str>,
+    request_url: Option<&'a str>,
+    request_protocol: Option<&'a str>,
+}
+
+impl Parser for HaProxy {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("haproxy")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses HAProxy HTTP logs (httplog) -> typed JSONL")
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["client_ip".to_string()]
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let line = line.strip_suffix('\r').unwrap_or(line).trim();
+        let Some(rec) = parse_haproxy(line) else {
+            return false;
+        };
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+/// Next space-separated token and the rest.
+fn token(s: &str) -> Option<(&str, &str)> {
+    let s = s.trim_start_matches(' ');
+    if s.is_empty() {
+        return None;
+    }
+    Some(s.split_once(' ').unwrap_or((s, "")))
+}
+
+/// `a/b/c` counters; `+` prefixes (logasap, redispatch) are dropped.
+fn counters<const N: usize>(s: &str) -> Option<[i64; N]> {
+    let mut out = [0; N];
+    let mut parts = s.split('/');
+    for v in &mut out {
+        *v = parts.next()?.trim_start_matches('+').parse().ok()?;
+    }
+    parts.next().is_none().then_some(out)
+}
+
+/// `06/Feb/2009:12:14:14.655` -> `2009-02-06T12:14:14.655`.
+fn iso_date(d: &str) -> Option<String> {
+    let (date, time) = d.split_once(':')?;
+    let mut parts = date.split('/');
+    let (day, month, year) = (parts.next()?, parts.next()?, parts.next()?);
+    let month = month_number(month)?;
+    Some(format!("{year}-{month:02}-{day}T{time}"))
+}
+
+fn dash(s: &str) -> Option<&str> {
+    (s != "-").then_some(s)
+}
+
+fn parse_haproxy(line: &str) -> Option<Record<'_>> {
+    let (syslog, rest) = match split_syslog(line) {
+        Some((sys, msg)) => (Some(sys), msg),
+        None => (None, line),
+    };
+
+    let (client, rest) = token(rest)?;
+    let rest = rest.strip_prefix('[')?;
+    let (accept_date, rest) = rest.split_once("] ")?;
+    let (client_ip, client_port) = split_host_port(client);
+
+    let (frontend, rest) = token(rest)?;
+    let (backend_server, rest) = token(rest)?;
+    let (timers, rest) = token(rest)?;
+    let (status, rest) = token(rest)?;
+    let (bytes_read, rest) = token(rest)?;
+    let (req_cookie, rest) = token(rest)?;
+    let (res_cookie, rest) = token(rest)?;
+    let (termination_state, rest) = token(rest)?;
+    let (conns, rest) = token(rest)?;
+    let (queues, mut rest) = token(rest)?;
+
+    let (backend, server) = backend_server.split_once('/')?;
+    let [tq, tw, tc, tr, tt] = counters::<5>(timers)?;
+    let [actconn, feconn, beconn, srv_conn, retries] = counters::<5>(conns)?;
+    let [srv_queue, backend_queue] = counters::<2>(queues)?;
+
+    // Header captures come first, request ones before response ones. `{}`
+    // captured nothing.
+    let mut captures = Vec::new();
+    while let Some(block) = rest.trim_start().strip_prefix('{') {
+        let (headers, after) = block.split_once('}')?;
+        captures.push(match headers {
+            "" => Vec::new(),
+            _ => headers.split('|').collect::<Vec<_>>(),
+        });
+        rest = after;
+    }
+    let mut captures = captures.into_iter();
+
+    // A truncated request line loses its closing quote.
+    let request = rest.trim_start().strip_prefix('"')?;
+    let request = request.strip_suffix('"').unwrap_or(request);
+    let mut parts = request.splitn(3, ' ');
+
+    Some(Record {
+        syslog,
+        client_ip,
+        client_port,
+        accept_date,
+        ts: iso_date(accept_date),
+        ssl: frontend.ends_with('~'),
+        frontend: frontend.trim_end_matches('~'),
+        backend,
+        server,
+        tq,
+        tw,
+        tc,
+        tr,
+        tt,
+        status: status.parse().ok()?,
+        bytes_read: bytes_read.trim_start_matches('+').parse().ok()?,
+        captured_request_cookie: dash(req_cookie),
+        captured_response_cookie: dash(res_cookie),
+        termination_state,
+        actconn,
+        feconn,
+        beconn,
+        srv_conn,
+        redispatched: conns.rsplit('/').next()?.starts_with('+'),
+        retries,
+        srv_queue,
+        backend_queue,
+        captured_request_headers: captures.next(),
+        captured_response_headers: captures.next(),
+        request,
+        request_method: parts.next().filter(|m| !m.is_empty()),
+        request_url: parts.next(),
+        request_protocol: parts.next(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(line: &str) -> Option<serde_json::Value> {
+        let mut out = Vec::new();
+        HaProxy
+            .process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn parses_syslog_http_line() {
+        let v = run(r#"Feb  6 12:14:14 localhost haproxy[14389]: 10.0.1.2:33317 [06/Feb/2009:12:14:14.655] http-in~ static/srv1 10/0/30/69/109 200 2750 - - ---- 1/1/1/1/+1 0/0 {1wt.eu|} {} "GET /index.html HTTP/1.1""#).unwrap();
+
+        assert_eq!(v["host"], "localhost");
+        assert_eq!(v["pid"], 14389);
+        assert_eq!(v["client_ip"], "10.0.1.2");
+        assert_eq!(v["client_port"], 33317);
+        assert_eq!(v["ts"], "2009-02-06T12:14:14.655");
+        assert_eq!(v["frontend"], "http-in");
+        assert_eq!(v["ssl"], true);
+        assert_eq!(v["backend"], "static");
+        assert_eq!(v["server"], "srv1");
+        assert_eq!(v["tc"], 30);
+        assert_eq!(v["tt"], 109);
+        assert_eq!(v["status"], 200);
+        assert_eq!(v["bytes_read"], 2750);
+        assert!(v["captured_request_cookie"].is_null());
+        assert_eq!(v["termination_state"], "----");
+        assert_eq!(v["retries"], 1);
+        assert_eq!(v["redispatched"], true);
+        assert_eq!(
+            v["captured_request_headers"],
+            serde_json::json!(["1wt.eu", ""])
+        );
+        assert_eq!(v["captured_response_headers"], serde_json::json!([]));
+        assert_eq!(v["request_method"], "GET");
+        assert_eq!(v["request_url"], "/index.html");
+        assert_eq!(v["request_protocol"], "HTTP/1.1");
+    }
+
+    #[test]
+    fn parses_bare_line_with_aborted_request() {
+        let v = run(r#"192.168.1.5:4711 [06/Feb/2009:12:12:51.443] fe be/<NOSRV> -1/-1/-1/-1/+8 400 187 - - PR-- 1/1/0/0/0 0/0 "<BADREQ>"#).unwrap();
+        assert!(v.get("host").is_none());
+        assert_eq!(v["server"], "<NOSRV>");
+        assert_eq!(v["tq"], -1);
+        assert_eq!(v["tt"], 8);
+        assert_eq!(v["redispatched"], false);
+        assert_eq!(v["request"], "<BADREQ>");
+        assert!(v["request_url"].is_null());
+        assert!(v.get("captured_request_headers").is_none());
+    }
+
+    #[test]
+    fn rejects_other_lines() {
+        assert!(run("Feb  6 12:14:14 localhost haproxy[1]: Proxy http-in started.").is_none());
+        assert!(
+            run(r#"127.0.0.1 - - [10/Oct/2000:13:55:36 -0700] "GET / HTTP/1.0" 200 1"#).is_none()
+        );
+    }
+}
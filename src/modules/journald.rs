@@ -0,0 +1,265 @@
+use crate::core::{LineJoiner, LinePos, Parser};
+use crate::modules::common::epoch_to_rfc3339;
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(Journald)
+}
+
+/// `journalctl -o export`: entries are blank-line-separated blocks of
+/// `FIELD=value` lines. Values holding newlines or binary data are written
+/// as `FIELD\n`, a 64-bit little-endian length, the raw bytes and `\n`.
+pub struct Journald;
+
+/// Guards against a runaway entry (or a file that isn't an export).
+const MAX_ENTRY_BYTES: usize = 16 << 20;
+
+impl Parser for Journald {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("journald")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("journalctl -o export entries -> one JSONL record per entry")
+    }
+
+    fn line_joiner(&self) -> Option<Box<dyn LineJoiner>> {
+        Some(Box::<ExportJoiner>::default())
+    }
+
+    fn recognizes(&self, line: &str) -> bool {
+        line.split_once('=')
+            .is_some_and(|(name, _)| is_field_name(name))
+    }
+
+    fn process_line_to_buf(&self, record: &str, out: &mut Vec<u8>) -> bool {
+        let Some(rec) = parse_entry(record) else {
+            return false;
+        };
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+/// Upper-case letters, digits and `_`, not starting with a digit.
+fn is_field_name(s: &str) -> bool {
+    !s.is_empty()
+        && !s.starts_with(|c: char| c.is_ascii_digit())
+        && s.bytes()
+            .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit() || b == b'_')
+}
+
+/// Collects one entry per blank line. Binary fields are re-framed as text so
+/// the record stays valid UTF-8: `FIELD`, the decimal byte length of the
+/// (lossily decoded) value, then the value, each on its own line.
+#[derive(Default)]
+struct ExportJoiner {
+    entry: Vec<u8>,
+    /// Where the entry's first field was.
+    start: LinePos,
+    binary: Option<Binary>,
+}
+
+/// A binary field being read: its name, and its lines so far (the length
+/// prefix included) glued back with the newlines they were split on.
+struct Binary {
+    name: Vec<u8>,
+    data: Vec<u8>,
+    lines: usize,
+}
+
+impl ExportJoiner {
+    fn flush(&mut self, emit: &mut dyn FnMut(&[u8], LinePos)) {
+        if !self.entry.is_empty() {
+            emit(&self.entry, self.start);
+            self.entry.clear();
+        }
+    }
+
+    fn append(&mut self, field: &[u8]) {
+        if !self.entry.is_empty() {
+            self.entry.push(b'\n');
+        }
+        self.entry.extend_from_slice(field);
+    }
+}
+
+impl LineJoiner for ExportJoiner {
+    fn push(&mut self, line: &[u8], at: LinePos, emit: &mut dyn FnMut(&[u8], LinePos)) {
+        if self.entry.is_empty() && self.binary.is_none() {
+            self.start = at;
+        }
+        if let Some(mut bin) = self.binary.take() {
+            if bin.lines > 0 {
+                bin.data.push(b'\n');
+            }
+            bin.data.extend_from_slice(line);
+            bin.lines += 1;
+
+            let data = &bin.data;
+            let want = data
+                .get(..8)
+                .map(|len| u64::from_le_bytes(len.try_into().unwrap_or_default()));
+            match want {
+                Some(want) if (data.len() - 8) as u64 >= want => {
+                    let end = 8 + (want as usize).min(data.len() - 8);
+                    let value = String::from_utf8_lossy(&data[8..end]);
+                    let field = format!(
+                        "{}\n{}\n{value}",
+                        String::from_utf8_lossy(&bin.name),
+                        value.len()
+                    );
+                    self.append(field.as_bytes());
+                }
+                _ if data.len() > MAX_ENTRY_BYTES => {
+                    self.entry.clear();
+                }
+                _ => self.binary = Some(bin),
+            }
+            return;
+        }
+
+        if line.is_empty() {
+            self.flush(emit);
+        } else if line.contains(&b'=') {
+            self.append(line);
+            if self.entry.len() > MAX_ENTRY_BYTES {
+                self.flush(emit);
+            }
+        } else {
+            self.binary = Some(Binary {
+                name: line.to_vec(),
+                data: Vec::new(),
+                lines: 0,
+            });
+        }
+    }
+
+    fn finish(&mut self, emit: &mut dyn FnMut(&[u8], LinePos)) {
+        self.binary = None;
+        self.flush(emit);
+    }
+}
+
+fn parse_entry(record: &str) -> Option<Map<String, Value>> {
+    let mut rec = Map::new();
+    let mut rest = record;
+    while !rest.is_empty() {
+        let (line, after) = rest.split_once('\n').unwrap_or((rest, ""));
+        rest = after;
+
+        let (name, value) = match line.split_once('=') {
+            Some((name, value)) => (name, value),
+            None => {
+                // Re-framed binary field: `NAME\n<len>\n<value>`.
+                let (len, after) = rest.split_once('\n')?;
+                let len: usize = len.parse().ok()?;
+                let value = after.get(..len)?;
+                rest = after[len..].strip_prefix('\n').unwrap_or(&after[len..]);
+                (line, value)
+            }
+        };
+        if !is_field_name(name) {
+            return None;
+        }
+
+        let value = match name {
+            "__REALTIME_TIMESTAMP" => realtime(value).unwrap_or_else(|| value.to_string()),
+            _ => value.to_string(),
+        };
+        // A field may repeat within an entry; keep every value.
+        match rec.get_mut(name) {
+            Some(Value::Array(values)) => values.push(value.into()),
+            Some(prev) => *prev = Value::Array(vec![prev.take(), value.into()]),
+            None => {
+                rec.insert(name.to_string(), value.into());
+            }
+        }
+    }
+    (!rec.is_empty()).then_some(rec)
+}
+
+/// Microseconds since the epoch -> RFC 3339 (UTC).
+fn realtime(us: &str) -> Option<String> {
+    if us.is_empty() || !us.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let us = format!("{us:0>7}");
+    let (secs, frac) = us.split_at(us.len() - 6);
+    epoch_to_rfc3339(&format!("{secs}.{frac}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(input: &[u8]) -> Vec<Option<serde_json::Value>> {
+        let mut j = Journald.line_joiner().unwrap();
+        let mut joined = Vec::new();
+        for l in input.split(|&b| b == b'\n') {
+            j.push(l, LinePos::default(), &mut |r, _| joined.push(r.to_vec()));
+        }
+        j.finish(&mut |r, _| joined.push(r.to_vec()));
+
+        joined
+            .iter()
+            .map(|r| {
+                let mut out = Vec::new();
+                Journald
+                    .process_line_to_buf(std::str::from_utf8(r).unwrap(), &mut out)
+                    .then(|| serde_json::from_slice(&out).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn text_and_binary_fields() {
+        let mut input = b"__CURSOR=s=abc;i=1\n__REALTIME_TIMESTAMP=1704164645123456\n\
+_SYSTEMD_UNIT=sshd.service\nMESSAGE=Accepted publickey\n_PID=42\n\n"
+            .to_vec();
+        input.extend_from_slice(b"__REALTIME_TIMESTAMP=1704164646000000\nMESSAGE\n");
+        let msg = b"line one\n\nline \xff three";
+        input.extend_from_slice(&(msg.len() as u64).to_le_bytes());
+        input.extend_from_slice(msg);
+        input.extend_from_slice(b"\nTAG=a\nTAG=b\n\n");
+
+        let recs = records(&input);
+        assert_eq!(recs.len(), 2);
+
+        let v = recs[0].as_ref().unwrap();
+        assert_eq!(v["__REALTIME_TIMESTAMP"], "2024-01-02T03:04:05.123456Z");
+        assert_eq!(v["__CURSOR"], "s=abc;i=1");
+        assert_eq!(v["_SYSTEMD_UNIT"], "sshd.service");
+        assert_eq!(v["MESSAGE"], "Accepted publickey");
+        assert_eq!(v["_PID"], "42");
+
+        let v = recs[1].as_ref().unwrap();
+        assert_eq!(v["__REALTIME_TIMESTAMP"], "2024-01-02T03:04:06Z");
+        assert_eq!(v["MESSAGE"], "line one\n\nline \u{fffd} three");
+        assert_eq!(v["TAG"], serde_json::json!(["a", "b"]));
+    }
+
+    #[test]
+    fn length_prefix_containing_a_newline_byte() {
+        // Length 10 is written as 0x0a 00 ...: the prefix itself splits.
+        let mut input = b"MESSAGE\n".to_vec();
+        input.extend_from_slice(&10u64.to_le_bytes());
+        input.extend_from_slice(b"0123\n56789\nPRIORITY=6\n");
+
+        let recs = records(&input);
+        let v = recs[0].as_ref().unwrap();
+        assert_eq!(v["MESSAGE"], "0123\n56789");
+        assert_eq!(v["PRIORITY"], "6");
+    }
+
+    #[test]
+    fn rejects_other_text() {
+        assert!(records(b"hello world\n=x\n").iter().all(Option::is_none));
+        assert!(Journald.recognizes("_BOOT_ID=0123"));
+        assert!(!Journald.recognizes("path=/x"));
+    }
+}
@@ -0,0 +1,189 @@
+use crate::core::{ModuleOptions, OptionSpec, Parser};
+use anyhow::{Context, Result};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(Json::new())
+}
+
+/// JSON Lines passthrough: one object per line, nested objects flattened to
+/// dotted keys, optionally reduced to an allowlist of keys.
+pub struct Json {
+    /// How many levels of nesting to flatten (`None` = all).
+    depth: Option<usize>,
+    separator: String,
+    fields: Vec<String>,
+}
+
+const OPTIONS: &[OptionSpec] = &[
+    OptionSpec {
+        key: "depth",
+        help: "Levels of nested objects to flatten; 0 keeps objects as-is (default: all)",
+    },
+    OptionSpec {
+        key: "separator",
+        help: "Joins nested key names (default: .)",
+    },
+    OptionSpec {
+        key: "fields",
+        help: "Comma-separated flattened keys to keep; a key also keeps everything below it",
+    },
+];
+
+impl Json {
+    fn new() -> Self {
+        Self {
+            depth: None,
+            separator: ".".into(),
+            fields: Vec::new(),
+        }
+    }
+
+    fn keep(&self, key: &str) -> bool {
+        self.fields.is_empty()
+            || self.fields.iter().any(|f| {
+                key.strip_prefix(f.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with(&self.separator))
+            })
+    }
+
+    fn flatten_into(
+        &self,
+        prefix: &str,
+        obj: Map<String, Value>,
+        depth: usize,
+        out: &mut Map<String, Value>,
+    ) {
+        for (k, v) in obj {
+            let key = if prefix.is_empty() {
+                k
+            } else {
+                format!("{prefix}{}{k}", self.separator)
+            };
+
+            match v {
+                Value::Object(inner)
+                    if !inner.is_empty() && self.depth.is_none_or(|max| depth < max) =>
+                {
+                    self.flatten_into(&key, inner, depth + 1, out);
+                }
+                v => {
+                    if self.keep(&key) {
+                        out.insert(key, v);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Parser for Json {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("json")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("JSON Lines passthrough, nested objects flattened to dotted keys")
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        OPTIONS
+    }
+
+    fn configure(&mut self, opts: &ModuleOptions) -> Result<()> {
+        if let Some(d) = opts.get("depth") {
+            self.depth = Some(
+                d.parse()
+                    .with_context(|| format!("depth must be a number, got '{d}'"))?,
+            );
+        }
+        if let Some(sep) = opts.get("separator") {
+            self.separator = sep.to_string();
+        }
+        if let Some(fields) = opts.list("fields") {
+            self.fields = fields;
+        }
+        Ok(())
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        self.process_bytes_to_buf(line.as_bytes(), out)
+    }
+
+    /// serde_json checks UTF-8 itself, only inside strings; a line with
+    /// invalid bytes is rejected rather than passed on with U+FFFD.
+    fn process_bytes_to_buf(&self, line: &[u8], out: &mut Vec<u8>) -> bool {
+        let Ok(Value::Object(obj)) = serde_json::from_slice::<Value>(line.trim_ascii()) else {
+            return false;
+        };
+
+        let mut flat = Map::new();
+        self.flatten_into("", obj, 0, &mut flat);
+        // With an allowlist, a line holding none of the wanted keys is dropped.
+        if flat.is_empty() && !self.fields.is_empty() {
+            return false;
+        }
+
+        if serde_json::to_writer(&mut *out, &flat).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(p: &Json, line: &str) -> Option<String> {
+        let mut out = Vec::new();
+        p.process_line_to_buf(line, &mut out)
+            .then(|| String::from_utf8(out).unwrap().trim_end().to_string())
+    }
+
+    fn with(opts: &[&str]) -> Json {
+        let mut p = Json::new();
+        p.configure(&ModuleOptions::parse(opts).unwrap()).unwrap();
+        p
+    }
+
+    #[test]
+    fn flattens_nested_objects_in_order() {
+        let line = r#"{"z":1,"src":{"ip":"1.2.3.4","geo":{"cc":"FR"}},"tags":[{"a":1}],"e":{}}"#;
+        assert_eq!(
+            run(&Json::new(), line).unwrap(),
+            r#"{"z":1,"src.ip":"1.2.3.4","src.geo.cc":"FR","tags":[{"a":1}],"e":{}}"#
+        );
+    }
+
+    #[test]
+    fn depth_limits_flattening() {
+        let line = r#"{"src":{"ip":"1.2.3.4","geo":{"cc":"FR"}}}"#;
+        assert_eq!(
+            run(&with(&["depth=1"]), line).unwrap(),
+            r#"{"src.ip":"1.2.3.4","src.geo":{"cc":"FR"}}"#
+        );
+        assert_eq!(run(&with(&["depth=0"]), line).unwrap(), line);
+    }
+
+    #[test]
+    fn allowlist_keeps_keys_and_their_children() {
+        let p = with(&["fields=src,event_type", "separator=_"]);
+        let line = r#"{"event_type":"alert","src":{"ip":"1.2.3.4"},"srcport":1,"dst":"x"}"#;
+        assert_eq!(
+            run(&p, line).unwrap(),
+            r#"{"event_type":"alert","src_ip":"1.2.3.4"}"#
+        );
+        assert_eq!(run(&p, r#"{"dst":"x"}"#), None);
+    }
+
+    #[test]
+    fn rejects_non_objects_and_garbage() {
+        let p = Json::new();
+        assert_eq!(run(&p, "[1,2]"), None);
+        assert_eq!(run(&p, "{not json"), None);
+        assert!(!p.process_bytes_to_buf(b"{\"a\":\"\xff\"}", &mut Vec::new()));
+    }
+}
@@ -0,0 +1,188 @@
+use crate::core::Parser;
+use crate::modules::cef::{split_header, unescape_header, OrderedMap};
+use serde::Serialize;
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(Leef)
+}
+
+/// IBM QRadar Log Event Extended Format, versions 1.0 and 2.0:
+/// `LEEF:1.0|Vendor|Product|Version|EventID|k=v<tab>k=v`
+/// `LEEF:2.0|Vendor|Product|Version|EventID|Delimiter|k=v<delim>k=v`
+pub struct Leef;
+
+impl Parser for Leef {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("leef")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses LEEF 1.0/2.0 (QRadar) events -> JSONL, header + attributes")
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let s = trim_cr(line).trim();
+        let Some(rec) = parse_leef(s) else {
+            return false;
+        };
+
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    syslog_prefix: Option<&'a str>,
+    leef_version: &'a str,
+    vendor: Cow<'a, str>,
+    product: Cow<'a, str>,
+    version: Cow<'a, str>,
+    event_id: Cow<'a, str>,
+    attributes: OrderedMap<'a>,
+    raw: &'a str,
+}
+
+fn parse_leef(line: &str) -> Option<Record<'_>> {
+    let start = line.find("LEEF:")?;
+    let prefix = line[..start].trim();
+    let (header, rest) = split_header::<5>(&line[start + 5..])?;
+
+    let (delim, attrs) = if header[0].starts_with('2') {
+        let (delim, attrs) = rest.split_once('|')?;
+        (parse_delimiter(delim)?, attrs)
+    } else {
+        ('\t', rest)
+    };
+
+    Some(Record {
+        syslog_prefix: (!prefix.is_empty()).then_some(prefix),
+        leef_version: header[0],
+        vendor: unescape_header(header[1]),
+        product: unescape_header(header[2]),
+        version: unescape_header(header[3]),
+        event_id: unescape_header(header[4]),
+        attributes: OrderedMap(parse_attributes(attrs, delim)),
+        raw: line,
+    })
+}
+
+/// LEEF 2.0 delimiter field: a single character or its hex code
+/// (`^`, `0x5E`, `x5E`). Empty means tab.
+fn parse_delimiter(s: &str) -> Option<char> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (None, _) => Some('\t'),
+        (Some(c), None) => Some(c),
+        _ => {
+            let hex = s
+                .strip_prefix("0x")
+                .or_else(|| s.strip_prefix("0X"))
+                .or_else(|| s.strip_prefix('x'))?;
+            char::from_u32(u32::from_str_radix(hex, 16).ok()?)
+        }
+    }
+}
+
+/// Split `k=v<delim>k=v`. A piece without `=` is taken to be a value that
+/// contained the delimiter and is glued back onto the previous value.
+/// A repeated key keeps its last value.
+fn parse_attributes(s: &str, delim: char) -> Vec<(&str, Cow<'_, str>)> {
+    let mut fields: Vec<(&str, Cow<'_, str>)> = Vec::new();
+    let mut last: Option<usize> = None;
+
+    for piece in s.split(delim) {
+        match piece.split_once('=') {
+            Some((key, value)) if !key.is_empty() => {
+                let key = key.trim();
+                let idx = match fields.iter().position(|(k, _)| *k == key) {
+                    Some(i) => {
+                        fields[i].1 = Cow::Borrowed(value);
+                        i
+                    }
+                    None => {
+                        fields.push((key, Cow::Borrowed(value)));
+                        fields.len() - 1
+                    }
+                };
+                last = Some(idx);
+            }
+            _ if piece.is_empty() => {}
+            _ => {
+                if let Some(i) = last {
+                    let v = fields[i].1.to_mut();
+                    v.push(delim);
+                    v.push_str(piece);
+                }
+            }
+        }
+    }
+    fields
+}
+
+fn trim_cr(s: &str) -> &str {
+    s.strip_suffix('\r').unwrap_or(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_leef_1_with_tabs() {
+        let line = "Jan 18 11:07:53 host LEEF:1.0|Microsoft|MSExchange|4.0 SP1|15345|src=10.50.1.1\tdst=2.10.20.20\tsev=5";
+        let rec = parse_leef(line).unwrap();
+
+        assert_eq!(rec.syslog_prefix, Some("Jan 18 11:07:53 host"));
+        assert_eq!(rec.leef_version, "1.0");
+        assert_eq!(rec.vendor, "Microsoft");
+        assert_eq!(rec.version, "4.0 SP1");
+        assert_eq!(rec.event_id, "15345");
+        assert_eq!(
+            rec.attributes.0,
+            vec![
+                ("src", Cow::Borrowed("10.50.1.1")),
+                ("dst", Cow::Borrowed("2.10.20.20")),
+                ("sev", Cow::Borrowed("5")),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_leef_2_custom_and_hex_delimiters() {
+        let rec =
+            parse_leef("LEEF:2.0|Lancope|StealthWatch|1.0|41|^|src=10.0.1.8^dst=10.0.0.5^sev=5")
+                .unwrap();
+        assert_eq!(rec.attributes.0.len(), 3);
+        assert_eq!(rec.attributes.0[1].1, "10.0.0.5");
+
+        let rec = parse_leef("LEEF:2.0|V|P|1|41|0x7C|a=1|b=2").unwrap();
+        assert_eq!(
+            rec.attributes.0,
+            vec![("a", Cow::Borrowed("1")), ("b", Cow::Borrowed("2"))]
+        );
+
+        let rec = parse_leef("LEEF:2.0|V|P|1|41||a=1\tb=2").unwrap();
+        assert_eq!(rec.attributes.0[1], ("b", Cow::Borrowed("2")));
+    }
+
+    #[test]
+    fn delimiter_inside_value_is_kept() {
+        let rec = parse_leef("LEEF:2.0|V|P|1|41|^|msg=a^b^c^usrName=bob").unwrap();
+        assert_eq!(rec.attributes.0[0].1, "a^b^c");
+        assert_eq!(rec.attributes.0[1].1, "bob");
+    }
+
+    #[test]
+    fn rejects_non_leef() {
+        let mut out = Vec::new();
+        assert!(!Leef.process_line_to_buf("LEEF:1.0|V|P", &mut out));
+        assert!(!Leef.process_line_to_buf("CEF:0|V|P|1|s|n|5|a=b", &mut out));
+        assert!(out.is_empty());
+    }
+}
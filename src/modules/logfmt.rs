@@ -0,0 +1,273 @@
+use crate::core::Parser;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(Logfmt)
+}
+
+/// `key=value key2="quoted value" flag` lines (Heroku/Go logfmt and the many
+/// appliances that log the same way).
+pub struct Logfmt;
+
+impl Parser for Logfmt {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("logfmt")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses logfmt key=value lines -> flat JSONL object plus raw")
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let s = trim_cr(line).trim();
+        if s.is_empty() {
+            return false;
+        }
+
+        let Some(pairs) = parse_pairs(s) else {
+            return false;
+        };
+        // A line of bare words is prose, not logfmt.
+        if pairs.iter().all(|p| p.value.is_none()) {
+            return false;
+        }
+
+        let rec = Record {
+            fields: Fields::from_pairs(pairs),
+            raw: s,
+        };
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+/// One `key=value` token. `value` is `None` for a bare `flag`.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Pair<'a> {
+    pub key: &'a str,
+    pub value: Option<Cow<'a, str>>,
+}
+
+/// Split a logfmt line into pairs, in line order.
+///
+/// Values may be bare (`k=v`, `k=`) or double-quoted with backslash escapes
+/// (`k="a \"b\""`). Returns `None` on an unterminated quote or a quoted
+/// string with no key.
+pub(crate) fn parse_pairs(line: &str) -> Option<Vec<Pair<'_>>> {
+    let b = line.as_bytes();
+    let mut pairs = Vec::new();
+    let mut i = 0;
+
+    loop {
+        while i < b.len() && b[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if i == b.len() {
+            return Some(pairs);
+        }
+
+        let start = i;
+        while i < b.len() && !b[i].is_ascii_whitespace() && b[i] != b'=' && b[i] != b'"' {
+            i += 1;
+        }
+        if i == start {
+            // `"..."` or `=...` with no key in front.
+            return None;
+        }
+        let key = &line[start..i];
+
+        if i == b.len() || b[i] != b'=' {
+            if i < b.len() && b[i] == b'"' {
+                return None;
+            }
+            pairs.push(Pair { key, value: None });
+            continue;
+        }
+        i += 1; // '='
+
+        let value = if i < b.len() && b[i] == b'"' {
+            let (value, end) = quoted(line, i + 1)?;
+            i = end;
+            value
+        } else {
+            let start = i;
+            while i < b.len() && !b[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            Cow::Borrowed(&line[start..i])
+        };
+        pairs.push(Pair {
+            key,
+            value: Some(value),
+        });
+    }
+}
+
+/// Decode a quoted value starting just after the opening quote.
+/// Returns the value and the index just past the closing quote.
+fn quoted(line: &str, start: usize) -> Option<(Cow<'_, str>, usize)> {
+    let b = line.as_bytes();
+    let mut i = start;
+    let mut owned: Option<String> = None;
+    let mut run = start; // start of the not-yet-copied literal run
+
+    while i < b.len() {
+        match b[i] {
+            b'"' => {
+                let value = match owned {
+                    Some(mut s) => {
+                        s.push_str(&line[run..i]);
+                        Cow::Owned(s)
+                    }
+                    None => Cow::Borrowed(&line[start..i]),
+                };
+                return Some((value, i + 1));
+            }
+            b'\\' if i + 1 < b.len() => {
+                let s = owned.get_or_insert_with(String::new);
+                s.push_str(&line[run..i]);
+                match b[i + 1] {
+                    b'"' => s.push('"'),
+                    b'\\' => s.push('\\'),
+                    b'n' => s.push('\n'),
+                    b't' => s.push('\t'),
+                    b'r' => s.push('\r'),
+                    // Unknown escape: keep it as written.
+                    _ => {
+                        run = i;
+                        i += 1;
+                        continue;
+                    }
+                }
+                i += 2;
+                run = i;
+            }
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// Pairs grouped by key in first-seen order. A key seen more than once
+/// keeps every value, as an array.
+pub(crate) struct Fields<'a>(Vec<(&'a str, Value<'a>)>);
+
+enum Value<'a> {
+    Flag,
+    One(Cow<'a, str>),
+    Many(Vec<Cow<'a, str>>),
+}
+
+impl<'a> Fields<'a> {
+    pub fn from_pairs(pairs: Vec<Pair<'a>>) -> Self {
+        let mut fields: Vec<(&'a str, Value<'a>)> = Vec::with_capacity(pairs.len());
+
+        for Pair { key, value } in pairs {
+            let Some(value) = value else {
+                if !fields.iter().any(|(k, _)| *k == key) {
+                    fields.push((key, Value::Flag));
+                }
+                continue;
+            };
+
+            match fields.iter_mut().find(|(k, _)| *k == key) {
+                None => fields.push((key, Value::One(value))),
+                Some((_, slot)) => {
+                    *slot = match std::mem::replace(slot, Value::Flag) {
+                        Value::Flag => Value::One(value),
+                        Value::One(first) => Value::Many(vec![first, value]),
+                        Value::Many(mut all) => {
+                            all.push(value);
+                            Value::Many(all)
+                        }
+                    }
+                }
+            }
+        }
+        Self(fields)
+    }
+}
+
+struct Record<'a> {
+    fields: Fields<'a>,
+    raw: &'a str,
+}
+
+impl Serialize for Record<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        // The original line always goes to `raw`; a field of that name moves to `_raw`.
+        for (key, value) in &self.fields.0 {
+            let key = if *key == "raw" { "_raw" } else { key };
+            match value {
+                Value::Flag => map.serialize_entry(key, &true)?,
+                Value::One(v) => map.serialize_entry(key, v)?,
+                Value::Many(vs) => map.serialize_entry(key, vs)?,
+            }
+        }
+        map.serialize_entry("raw", self.raw)?;
+        map.end()
+    }
+}
+
+fn trim_cr(s: &str) -> &str {
+    s.strip_suffix('\r').unwrap_or(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_json(line: &str) -> serde_json::Value {
+        let mut out = Vec::new();
+        assert!(Logfmt.process_line_to_buf(line, &mut out));
+        serde_json::from_slice(&out).unwrap()
+    }
+
+    #[test]
+    fn parses_bare_quoted_and_flag_values() {
+        let v = to_json(r#"level=info msg="hello \"big\" world" dry_run path=/a empty="#);
+        assert_eq!(v["level"], "info");
+        assert_eq!(v["msg"], r#"hello "big" world"#);
+        assert_eq!(v["dry_run"], true);
+        assert_eq!(v["path"], "/a");
+        assert_eq!(v["empty"], "");
+        assert_eq!(
+            v["raw"],
+            r#"level=info msg="hello \"big\" world" dry_run path=/a empty="#
+        );
+    }
+
+    #[test]
+    fn duplicate_keys_become_arrays() {
+        let v = to_json("tag=a user=x tag=b tag=c");
+        assert_eq!(v["tag"], serde_json::json!(["a", "b", "c"]));
+        assert_eq!(v["user"], "x");
+    }
+
+    #[test]
+    fn raw_field_does_not_clobber_line() {
+        let v = to_json("raw=1 a=2");
+        assert_eq!(v["_raw"], "1");
+        assert_eq!(v["raw"], "raw=1 a=2");
+    }
+
+    #[test]
+    fn rejects_prose_and_broken_quotes() {
+        let mut out = Vec::new();
+        assert!(!Logfmt.process_line_to_buf("just some words", &mut out));
+        assert!(!Logfmt.process_line_to_buf(r#"msg="never closed"#, &mut out));
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn keeps_unknown_escapes_verbatim() {
+        let pairs = parse_pairs(r#"p="C:\dir\n" q="\x""#).unwrap();
+        assert_eq!(pairs[0].value.as_deref(), Some("C:\\dir\n"));
+        assert_eq!(pairs[1].value.as_deref(), Some("\\x"));
+    }
+}
@@ -0,0 +1,223 @@
+use crate::core::Parser;
+use crate::modules::common::{flatten_with, rfc3339_to_utc, split_host_port};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use time::{format_description::well_known::Rfc3339, PrimitiveDateTime};
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(M365Audit)
+}
+
+/// Microsoft 365 Unified Audit Log exports: the Purview CSV (`CreationDate,
+/// UserIds,Operations,AuditData` or the newer `RecordId,...` layout) whose
+/// `AuditData` column holds the event as JSON, or JSON Lines of events
+/// (Management Activity API content, `Search-UnifiedAuditLog` objects with a
+/// string `AuditData`). One flattened record per event.
+pub struct M365Audit;
+
+/// Lists of `{Name, Value}` pairs, turned into objects keyed by `Name`.
+const NAME_VALUE_KEYS: &[&str] = &[
+    "ExtendedProperties",
+    "Parameters",
+    "DeviceProperties",
+    "ModifiedProperties",
+    "OperationProperties",
+];
+
+/// The event of one line: the CSV row's `AuditData` cell, or the JSON
+/// object itself (or its `AuditData` string).
+fn audit_data(line: &str) -> Option<Map<String, Value>> {
+    let line = line.trim();
+    if line.starts_with('{') {
+        let Ok(Value::Object(mut obj)) = serde_json::from_str::<Value>(line) else {
+            return None;
+        };
+        return match obj.shift_remove("AuditData") {
+            Some(Value::String(data)) => decode(&data),
+            Some(Value::Object(data)) => Some(data),
+            _ => Some(obj),
+        };
+    }
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(line.as_bytes());
+    let row = rdr.records().next()?.ok()?;
+    row.iter()
+        .find(|cell| cell.trim_start().starts_with('{'))
+        .and_then(decode)
+}
+
+fn decode(data: &str) -> Option<Map<String, Value>> {
+    match serde_json::from_str(data) {
+        Ok(Value::Object(obj)) => Some(obj),
+        _ => None,
+    }
+}
+
+/// [`flatten_with`] hook: turns the [`NAME_VALUE_KEYS`] lists into objects
+/// with [`name_values`], so they flatten to `key.<Name>`.
+fn named_lists(key: &str, v: Value) -> Value {
+    match v {
+        Value::Array(items) if NAME_VALUE_KEYS.contains(&key) => match name_values(&items) {
+            Some(named) if !named.is_empty() => Value::Object(named),
+            _ => Value::Array(items),
+        },
+        v => v,
+    }
+}
+
+/// `[{Name: a, Value: 1}, {Name: b, NewValue: 2, OldValue: 3}]` ->
+/// `{a: 1, b: {NewValue: 2, OldValue: 3}}`. A repeated name collects its
+/// values in an array. `None` when an entry has no `Name`.
+fn name_values(items: &[Value]) -> Option<Map<String, Value>> {
+    let mut named = Map::new();
+    for item in items {
+        let obj = item.as_object()?;
+        let name = obj.get("Name")?.as_str()?;
+        let value = match obj.get("Value") {
+            Some(v) if obj.len() == 2 => v.clone(),
+            _ => Value::Object(
+                obj.iter()
+                    .filter(|(k, _)| *k != "Name")
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect(),
+            ),
+        };
+        match named.get_mut(name) {
+            Some(Value::Array(seen)) => seen.push(value),
+            Some(seen) => *seen = Value::Array(vec![seen.take(), value]),
+            None => {
+                named.insert(name.to_string(), value);
+            }
+        }
+    }
+    Some(named)
+}
+
+/// `CreationTime` is UTC without an offset (`2024-01-02T03:04:05`);
+/// RFC 3339 in UTC.
+fn normalize_time(value: &str) -> Option<String> {
+    let fmt = time::macros::format_description!(
+        "[year]-[month]-[day]T[hour]:[minute]:[second][optional [.[subsecond]]]"
+    );
+    match PrimitiveDateTime::parse(value, &fmt) {
+        Ok(t) => t.assume_utc().format(&Rfc3339).ok(),
+        Err(_) => rfc3339_to_utc(value),
+    }
+}
+
+impl Parser for M365Audit {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("m365-audit")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Microsoft 365 Unified Audit Log CSV/JSON -> AuditData flattened to JSONL")
+    }
+
+    fn recognizes(&self, line: &str) -> bool {
+        line.contains("CreationTime")
+            && line.contains("Workload")
+            && self.process_line_to_buf(line, &mut Vec::new())
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["ClientIP".to_string()]
+    }
+
+    fn user_agent_fields(&self) -> Vec<String> {
+        vec!["ExtendedProperties.UserAgent".to_string()]
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let Some(mut event) = audit_data(line) else {
+            return false;
+        };
+        let Some(ts) = event
+            .get("CreationTime")
+            .and_then(Value::as_str)
+            .and_then(normalize_time)
+        else {
+            return false;
+        };
+        if !event.contains_key("Operation") {
+            return false;
+        }
+        // `ClientIP` often carries the port: `198.51.100.7:51234`.
+        if let Some(Value::String(client)) = event.get_mut("ClientIP")
+            && let (ip, Some(port)) = split_host_port(client)
+        {
+            *client = ip.to_string();
+            event.insert("ClientPort".to_string(), port.into());
+        }
+
+        let mut flat = Map::new();
+        flat.insert("ts".to_string(), ts.into());
+        flatten_with("", event, &[], &mut flat, &named_lists);
+        if serde_json::to_writer(&mut *out, &flat).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AUDIT: &str = r#"{"CreationTime":"2024-05-06T07:08:09","Id":"a1b2","Operation":"New-InboxRule","OrganizationId":"org","RecordType":1,"ResultStatus":"True","UserKey":"1003","UserType":2,"Version":1,"Workload":"Exchange","ClientIP":"198.51.100.7:51234","ObjectId":"alice\\rule","UserId":"alice@contoso.com","AppId":"","Parameters":[{"Name":"Name","Value":"x"},{"Name":"ForwardTo","Value":"eve@evil.example"},{"Name":"DeleteMessage","Value":"True"}],"SessionId":"s1","ExtendedProperties":[{"Name":"UserAgent","Value":"Mozilla/5.0"}],"ModifiedProperties":[{"Name":"Role","NewValue":"Admin","OldValue":""}],"Target":[{"Type":2,"ID":"bob"}],"Actor":{"ID":"alice"}}"#;
+
+    fn run(line: &str) -> Option<Value> {
+        let mut out = Vec::new();
+        M365Audit
+            .process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    fn check(v: &Value) {
+        assert_eq!(v["ts"], "2024-05-06T07:08:09Z");
+        assert_eq!(v["Operation"], "New-InboxRule");
+        assert_eq!(v["ClientIP"], "198.51.100.7");
+        assert_eq!(v["ClientPort"], 51234);
+        assert_eq!(v["Parameters.ForwardTo"], "eve@evil.example");
+        assert_eq!(v["ExtendedProperties.UserAgent"], "Mozilla/5.0");
+        assert_eq!(v["ModifiedProperties.Role.NewValue"], "Admin");
+        assert_eq!(v["Target"][0]["ID"], "bob");
+        assert_eq!(v["Actor.ID"], "alice");
+    }
+
+    #[test]
+    fn decodes_the_audit_data_csv_column() {
+        let cell = format!("\"{}\"", AUDIT.replace('"', "\"\""));
+        let v = run(&format!(
+            "2024-05-06T07:08:09.0000000Z,alice@contoso.com,New-InboxRule,{cell}"
+        ))
+        .unwrap();
+        check(&v);
+        let v = run(&format!(
+            "a1b2,2024-05-06T07:08:09.0000000Z,ExchangeAdmin,New-InboxRule,alice@contoso.com,{cell},,"
+        ))
+        .unwrap();
+        check(&v);
+        assert!(run("CreationDate,UserIds,Operations,AuditData").is_none());
+    }
+
+    #[test]
+    fn reads_json_events_and_envelopes() {
+        check(&run(AUDIT).unwrap());
+        let envelope = serde_json::json!({"RecordType": "ExchangeAdmin", "AuditData": AUDIT});
+        check(&run(&envelope.to_string()).unwrap());
+        assert!(M365Audit.recognizes(AUDIT));
+        assert!(run(r#"{"eventType":"user.session.start"}"#).is_none());
+    }
+
+    #[test]
+    fn repeated_names_collect_values() {
+        let items = serde_json::json!([{"Name": "a", "Value": 1}, {"Name": "a", "Value": 2}]);
+        let named = name_values(items.as_array().unwrap()).unwrap();
+        assert_eq!(named["a"], serde_json::json!([1, 2]));
+        assert!(name_values(&[serde_json::json!({"Value": 1})]).is_none());
+    }
+}
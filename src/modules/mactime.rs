@@ -18,6 +18,10 @@ impl Parser for MactimeBodyfile {
         Cow::Borrowed("Parses UAC bodyfile lines -> compact JSONL, one record per input line")
     }
 
+    fn recognizes(&self, line: &str) -> bool {
+        parse_bodyfile_line(trim_cr(line)).is_some()
+    }
+
     fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
         let s = trim_cr(line);
 
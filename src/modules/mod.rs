@@ -1,3 +1,50 @@
-pub mod csv_dummy;
-pub mod mactime;
-pub mod web_access;
+pub mod apache_error;
+pub mod asa;
+pub mod auditd;
+pub mod authlog;
+pub mod bind;
+pub mod cef;
+pub mod checkpoint;
+pub mod cloudflare;
+pub mod cloudfront;
+pub mod cloudtrail;
+pub(crate) mod common;
+pub mod cri;
+pub mod csv;
+pub mod dhcp;
+pub mod docker_json;
+pub mod elb;
+pub mod exchange_tracking;
+pub mod fail2ban;
+pub mod fortigate;
+pub mod gcp_audit;
+pub mod haproxy;
+pub mod journald;
+pub mod json;
+pub mod leef;
+pub mod logfmt;
+pub mod m365_audit;
+pub mod mactime;
+pub mod modsecurity;
+pub mod mysql_slow;
+pub mod nginx_error;
+pub mod nsg_flow;
+pub mod okta;
+pub mod openvpn;
+pub mod panos;
+pub mod postfix;
+pub mod postgres;
+pub mod ps_transcript;
+pub mod radius;
+pub mod resolver;
+pub mod samba;
+pub mod selinux;
+pub mod squid;
+pub mod suricata;
+pub mod sysmon;
+pub mod vpc_flow;
+pub mod web_access;
+pub mod winevt_xml;
+pub mod xferlog;
+pub mod zeek;
+pub mod zscaler;
@@ -0,0 +1,367 @@
+use crate::core::{BlockJoiner, Boundary, LineJoiner, Parser};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime, UtcOffset};
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(ModSecurity)
+}
+
+/// ModSecurity serial audit log: one transaction per `--<id>-A--` ...
+/// `--<id>-Z--` block. The audit header (A), request (B, C/I), response
+/// (F, E) and trailer (H) sections become one object per transaction.
+pub struct ModSecurity;
+
+/// Guards against a block that never reaches its `Z` section.
+const MAX_LINES: usize = 10_000;
+
+/// `--a1b2c3d4-B--` -> (`a1b2c3d4`, `B`).
+fn section(line: &str) -> Option<(&str, char)> {
+    let inner = line.trim_end().strip_prefix("--")?.strip_suffix("--")?;
+    let (id, part) = inner.rsplit_once('-')?;
+    let mut chars = part.chars();
+    let part = chars.next().filter(|c| c.is_ascii_uppercase())?;
+    (chars.next().is_none() && !id.is_empty() && id.bytes().all(|b| b.is_ascii_alphanumeric()))
+        .then_some((id, part))
+}
+
+fn boundary(line: &str) -> Boundary {
+    match section(line) {
+        Some((_, 'A')) => Boundary::Start,
+        Some((_, 'Z')) => Boundary::End,
+        _ => Boundary::Inside,
+    }
+}
+
+impl Parser for ModSecurity {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("modsecurity")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("ModSecurity serial audit log -> one JSONL record per transaction")
+    }
+
+    fn line_joiner(&self) -> Option<Box<dyn LineJoiner>> {
+        Some(Box::new(BlockJoiner::new(boundary, MAX_LINES)))
+    }
+
+    fn recognizes(&self, line: &str) -> bool {
+        section(line).is_some()
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["client_ip".to_string(), "server_ip".to_string()]
+    }
+
+    fn process_line_to_buf(&self, record: &str, out: &mut Vec<u8>) -> bool {
+        let Some(rec) = parse_transaction(record) else {
+            return false;
+        };
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+fn parse_transaction(record: &str) -> Option<Map<String, Value>> {
+    let mut lines = record.lines().map(|l| l.strip_suffix('\r').unwrap_or(l));
+    let (id, 'A') = section(lines.next()?)? else {
+        return None;
+    };
+
+    // Section letter -> its lines, in order of appearance.
+    let mut sections: Vec<(char, Vec<&str>)> = Vec::new();
+    let mut current = ('A', Vec::new());
+    for line in lines {
+        match section(line) {
+            Some((sid, part)) if sid == id => {
+                sections.push(std::mem::replace(&mut current, (part, Vec::new())));
+            }
+            _ => current.1.push(line),
+        }
+    }
+    sections.push(current);
+    let body = |part: char| {
+        sections
+            .iter()
+            .find(|(p, _)| *p == part)
+            .map(|(_, lines)| lines.as_slice())
+    };
+
+    let mut rec = Map::new();
+    rec.insert("transaction_id".into(), id.into());
+    audit_header(body('A').unwrap_or_default(), &mut rec);
+
+    if let Some(lines) = body('B') {
+        let mut request = Map::new();
+        let (first, headers) = head_and_headers(lines);
+        let mut parts = first.splitn(3, ' ');
+        for key in ["method", "uri", "protocol"] {
+            if let Some(p) = parts.next().filter(|p| !p.is_empty()) {
+                request.insert(key.into(), p.into());
+            }
+        }
+        request.insert("headers".into(), headers.into());
+        if let Some(b) = body('C').or_else(|| body('I')) {
+            request.insert("body".into(), join(b).into());
+        }
+        rec.insert("request".into(), request.into());
+    }
+
+    if let Some(lines) = body('F') {
+        let mut response = Map::new();
+        let (first, headers) = head_and_headers(lines);
+        let mut parts = first.splitn(3, ' ');
+        if let Some(p) = parts.next().filter(|p| !p.is_empty()) {
+            response.insert("protocol".into(), p.into());
+        }
+        if let Some(status) = parts.next().and_then(|s| s.parse::<u16>().ok()) {
+            response.insert("status".into(), status.into());
+        }
+        if let Some(reason) = parts.next() {
+            response.insert("reason".into(), reason.into());
+        }
+        response.insert("headers".into(), headers.into());
+        if let Some(b) = body('E') {
+            response.insert("body".into(), join(b).into());
+        }
+        rec.insert("response".into(), response.into());
+    }
+
+    if let Some(lines) = body('H') {
+        let mut messages = Vec::new();
+        let mut trailer = Map::new();
+        for line in lines.iter().filter(|l| !l.is_empty()) {
+            match line.split_once(": ") {
+                Some(("Message", m)) => messages.push(Value::Object(message(m))),
+                Some((k, v)) => {
+                    trailer.insert(k.into(), v.trim_matches('"').into());
+                }
+                None => {}
+            }
+        }
+        rec.insert("messages".into(), messages.into());
+        rec.insert("trailer".into(), trailer.into());
+    }
+
+    if let Some(lines) = body('K') {
+        let rules: Vec<Value> = lines
+            .iter()
+            .filter(|l| !l.is_empty())
+            .map(|&l| l.into())
+            .collect();
+        rec.insert("matched_rules".into(), rules.into());
+    }
+
+    let parts: String = sections.iter().map(|(p, _)| *p).collect();
+    rec.insert("sections".into(), parts.into());
+    Some(rec)
+}
+
+/// `[02/Jan/2024:03:04:05 +0100] <unique id> <client ip> <port> <server ip> <port>`.
+fn audit_header(lines: &[&str], rec: &mut Map<String, Value>) {
+    let Some(line) = lines.iter().find(|l| !l.is_empty()) else {
+        return;
+    };
+    let (ts, rest) = match line.strip_prefix('[').and_then(|l| l.split_once("] ")) {
+        Some((ts, rest)) => (Some(ts), rest),
+        None => (None, *line),
+    };
+    rec.insert(
+        "ts".into(),
+        ts.and_then(clf_to_rfc3339)
+            .map_or(Value::Null, Value::String),
+    );
+
+    let mut t = rest.split_whitespace();
+    for (key, numeric) in [
+        ("unique_id", false),
+        ("client_ip", false),
+        ("client_port", true),
+        ("server_ip", false),
+        ("server_port", true),
+    ] {
+        let Some(v) = t.next() else { break };
+        let v = match v.parse::<u16>() {
+            Ok(n) if numeric => n.into(),
+            _ => v.into(),
+        };
+        rec.insert(key.into(), v);
+    }
+}
+
+/// `02/Jan/2024:03:04:05[.123456] +0100` -> RFC 3339 (UTC).
+fn clf_to_rfc3339(value: &str) -> Option<String> {
+    let fmt = time::macros::format_description!(
+        "[day]/[month repr:short]/[year]:[hour]:[minute]:[second][optional [.[subsecond]]] [offset_hour sign:mandatory][offset_minute]"
+    );
+    OffsetDateTime::parse(value, &fmt)
+        .ok()?
+        .to_offset(UtcOffset::UTC)
+        .format(&Rfc3339)
+        .ok()
+}
+
+/// Request/status line, then `Name: value` headers up to the first blank
+/// line. Repeated headers are joined with `, `.
+fn head_and_headers<'a>(lines: &[&'a str]) -> (&'a str, Map<String, Value>) {
+    let mut it = lines.iter().skip_while(|l| l.is_empty());
+    let first = it.next().copied().unwrap_or_default();
+    let mut headers = Map::new();
+    for line in it.take_while(|l| !l.is_empty()) {
+        let Some((k, v)) = line.split_once(':') else {
+            continue;
+        };
+        let v = v.trim();
+        match headers.get_mut(k) {
+            Some(Value::String(prev)) => {
+                prev.push_str(", ");
+                prev.push_str(v);
+            }
+            _ => {
+                headers.insert(k.into(), v.into());
+            }
+        }
+    }
+    (first, headers)
+}
+
+fn join(lines: &[&str]) -> String {
+    lines.join("\n").trim_end_matches('\n').to_string()
+}
+
+/// `Access denied with code 403 (phase 2). Pattern match ... [file "x"]
+/// [line "45"] [id "942100"] [msg "..."] [tag "a"] [tag "b"]`.
+fn message(m: &str) -> Map<String, Value> {
+    let mut out = Map::new();
+    let (text, mut rest) = match m.find(" [") {
+        Some(i) => (&m[..i], &m[i + 1..]),
+        None => (m, ""),
+    };
+    out.insert("message".into(), text.into());
+
+    let mut tags = Vec::new();
+    while let Some(r) = rest.trim_start().strip_prefix('[') {
+        let Some((key, r)) = r.split_once(" \"") else {
+            break;
+        };
+        let Some(end) = r.find("\"]") else {
+            break;
+        };
+        let value = &r[..end];
+        rest = &r[end + 2..];
+        if key == "tag" {
+            tags.push(Value::from(value));
+        } else {
+            out.insert(key.into(), value.into());
+        }
+    }
+    if !tags.is_empty() {
+        out.insert("tags".into(), tags.into());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::LinePos;
+
+    const LOG: &str = r#"--a1b2c3d4-A--
+[02/Jan/2024:03:04:05 +0100] ZZxy1234abcd 203.0.113.5 51234 10.0.0.2 80
+--a1b2c3d4-B--
+POST /login.php?id=1 HTTP/1.1
+Host: example.com
+Cookie: a=1
+Cookie: b=2
+
+--a1b2c3d4-C--
+user=admin' OR 1=1--
+--a1b2c3d4-F--
+HTTP/1.1 403 Forbidden
+Content-Type: text/html
+
+--a1b2c3d4-H--
+Message: Access denied with code 403 (phase 2). Detected SQLi. [file "/etc/crs/REQUEST-942.conf"] [line "45"] [id "942100"] [msg "SQL Injection Attack Detected via libinjection"] [severity "CRITICAL"] [tag "attack-sqli"] [tag "OWASP_CRS"]
+Action: Intercepted (phase 2)
+Engine-Mode: "ENABLED"
+
+--a1b2c3d4-Z--
+"#;
+
+    fn run(record: &str) -> Option<serde_json::Value> {
+        let mut out = Vec::new();
+        ModSecurity
+            .process_line_to_buf(record, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    fn joined(input: &str) -> Vec<String> {
+        let mut j = ModSecurity.line_joiner().unwrap();
+        let mut out = Vec::new();
+        for l in input.lines() {
+            j.push(l.as_bytes(), LinePos::default(), &mut |r, _| {
+                out.push(String::from_utf8(r.to_vec()).unwrap())
+            });
+        }
+        j.finish(&mut |r, _| out.push(String::from_utf8(r.to_vec()).unwrap()));
+        out
+    }
+
+    #[test]
+    fn parses_a_transaction() {
+        let records = joined(LOG);
+        assert_eq!(records.len(), 1);
+        let v = run(&records[0]).unwrap();
+
+        assert_eq!(v["transaction_id"], "a1b2c3d4");
+        assert_eq!(v["ts"], "2024-01-02T02:04:05Z");
+        assert_eq!(v["unique_id"], "ZZxy1234abcd");
+        assert_eq!(v["client_ip"], "203.0.113.5");
+        assert_eq!(v["client_port"], 51234);
+        assert_eq!(v["server_port"], 80);
+
+        assert_eq!(v["request"]["method"], "POST");
+        assert_eq!(v["request"]["uri"], "/login.php?id=1");
+        assert_eq!(v["request"]["headers"]["Cookie"], "a=1, b=2");
+        assert_eq!(v["request"]["body"], "user=admin' OR 1=1--");
+        assert_eq!(v["response"]["status"], 403);
+        assert_eq!(v["response"]["headers"]["Content-Type"], "text/html");
+
+        let m = &v["messages"][0];
+        assert_eq!(
+            m["message"],
+            "Access denied with code 403 (phase 2). Detected SQLi."
+        );
+        assert_eq!(m["id"], "942100");
+        assert_eq!(m["severity"], "CRITICAL");
+        assert_eq!(m["tags"], serde_json::json!(["attack-sqli", "OWASP_CRS"]));
+        assert_eq!(v["trailer"]["Action"], "Intercepted (phase 2)");
+        assert_eq!(v["trailer"]["Engine-Mode"], "ENABLED");
+        assert_eq!(v["sections"], "ABCFHZ");
+    }
+
+    #[test]
+    fn joiner_splits_transactions_and_truncated_blocks() {
+        let input =
+            format!("{LOG}--ffff0000-A--\n[02/Jan/2024:03:04:06.5 +0000] u 1.1.1.1 1 2.2.2.2 2\n");
+        let records = joined(&input);
+        assert_eq!(records.len(), 2);
+
+        let v = run(&records[1]).unwrap();
+        assert_eq!(v["ts"], "2024-01-02T03:04:06.5Z");
+        assert_eq!(v["sections"], "A");
+        assert!(v.get("request").is_none());
+    }
+
+    #[test]
+    fn rejects_lines_outside_a_transaction() {
+        assert!(run("GET / HTTP/1.1").is_none());
+        assert!(run("--a1b2c3d4-B--\nGET / HTTP/1.1").is_none());
+        assert!(ModSecurity.recognizes("--a1b2c3d4-Z--"));
+        assert!(!ModSecurity.recognizes("-- comment --"));
+    }
+}
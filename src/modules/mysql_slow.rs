@@ -0,0 +1,260 @@
+use crate::core::{BlockJoiner, Boundary, LineJoiner, Parser};
+use crate::modules::common::epoch_to_rfc3339;
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(MysqlSlow)
+}
+
+/// MySQL / MariaDB slow query log. Each entry is a run of `# ` header lines
+/// (`# Time:`, `# User@Host:`, `# Query_time:`, ...) followed by the
+/// statement, so lines are joined into one record per query.
+pub struct MysqlSlow;
+
+/// Guards against a runaway statement (or a file that isn't a slow log).
+const MAX_LINES: usize = 10_000;
+
+/// A header line after body lines opens the next entry. The server's
+/// restart banner (`... started with:`) also stands alone.
+fn boundaries() -> impl FnMut(&str) -> Boundary + Send {
+    let mut in_header = false;
+    move |line| {
+        let header = line.starts_with("# ");
+        let start = (header && !in_header) || line.ends_with(" started with:");
+        in_header = header;
+        if start {
+            Boundary::Start
+        } else {
+            Boundary::Inside
+        }
+    }
+}
+
+impl Parser for MysqlSlow {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("mysql-slow")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("MySQL/MariaDB slow query log -> one JSONL record per query")
+    }
+
+    fn line_joiner(&self) -> Option<Box<dyn LineJoiner>> {
+        Some(Box::new(BlockJoiner::new(boundaries(), MAX_LINES)))
+    }
+
+    fn recognizes(&self, line: &str) -> bool {
+        ["# Time: ", "# User@Host: ", "# Query_time: "]
+            .iter()
+            .any(|p| line.starts_with(p))
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["client_ip".to_string()]
+    }
+
+    fn process_line_to_buf(&self, record: &str, out: &mut Vec<u8>) -> bool {
+        let Some(rec) = parse_entry(record) else {
+            return false;
+        };
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+fn parse_entry(record: &str) -> Option<Map<String, Value>> {
+    let mut rec = Map::new();
+    rec.insert("ts".into(), Value::Null);
+    let mut statement: Vec<&str> = Vec::new();
+
+    for line in record.lines().map(|l| l.strip_suffix('\r').unwrap_or(l)) {
+        let Some(header) = line.strip_prefix("# ") else {
+            statement.push(line);
+            continue;
+        };
+        if let Some(t) = header.strip_prefix("Time: ") {
+            rec.insert("ts".into(), time_header(t.trim()).into());
+        } else if let Some(uh) = header.strip_prefix("User@Host: ") {
+            user_host(uh, &mut rec);
+        } else {
+            metrics(header, &mut rec);
+        }
+    }
+    if !rec.contains_key("query_time") {
+        return None;
+    }
+
+    // `use db;` and `SET timestamp=N;` are session context, not the query.
+    let mut query = Vec::new();
+    for line in statement {
+        let l = line.trim();
+        if let Some(db) = l.strip_prefix("use ").and_then(|d| d.strip_suffix(';')) {
+            rec.entry("schema").or_insert(db.trim_matches('`').into());
+        } else if let Some(ts) = l
+            .strip_prefix("SET timestamp=")
+            .and_then(|t| t.strip_suffix(';'))
+        {
+            if rec["ts"].is_null()
+                && let Some(ts) = epoch_to_rfc3339(ts)
+            {
+                rec.insert("ts".into(), ts.into());
+            }
+        } else if !l.is_empty() {
+            query.push(l);
+        }
+    }
+    let query = query.join(" ");
+    let query = query.split_whitespace().collect::<Vec<_>>().join(" ");
+    rec.insert("query".into(), query.into());
+    Some(rec)
+}
+
+/// `2024-01-02T03:04:05.123456Z` (5.7+) as is, or `240102  3:04:05` (5.6,
+/// MariaDB) as local ISO 8601.
+fn time_header(t: &str) -> String {
+    let Some((date, time)) = t.split_once(' ') else {
+        return t.to_string();
+    };
+    let time = time.trim_start();
+    if date.len() != 6 || !date.bytes().all(|b| b.is_ascii_digit()) {
+        return t.to_string();
+    }
+    let time = if time.len() == 7 {
+        format!("0{time}")
+    } else {
+        time.to_string()
+    };
+    format!("20{}-{}-{}T{time}", &date[..2], &date[2..4], &date[4..])
+}
+
+/// `app[app] @ web1 [10.0.0.5]  Id:    42`.
+fn user_host(s: &str, rec: &mut Map<String, Value>) {
+    let (who, id) = match s.split_once("Id:") {
+        Some((w, id)) => (w.trim(), id.trim().parse::<u64>().ok()),
+        None => (s.trim(), None),
+    };
+    let (user, host) = who.split_once(" @ ").unwrap_or((who, ""));
+    let user = user.split_once('[').map_or(user, |(u, _)| u);
+    rec.insert("user".into(), user.into());
+
+    let (host, ip) = match host.split_once('[') {
+        Some((h, ip)) => (h.trim(), ip.trim_end_matches(']')),
+        None => (host.trim(), ""),
+    };
+    if !host.is_empty() {
+        rec.insert("host".into(), host.into());
+    }
+    if !ip.is_empty() {
+        rec.insert("client_ip".into(), ip.into());
+    }
+    if let Some(id) = id {
+        rec.insert("thread_id".into(), id.into());
+    }
+}
+
+/// `Query_time: 2.000123  Lock_time: 0.0001 Rows_sent: 1  Rows_examined: 10`
+/// and MariaDB's extra lines (`Thread_id: 42  Schema: shop  QC_hit: No`).
+/// Keys are lowercased; numeric values become numbers.
+fn metrics(s: &str, rec: &mut Map<String, Value>) {
+    let mut tokens = s.split_whitespace().peekable();
+    while let Some(tok) = tokens.next() {
+        let Some(key) = tok.strip_suffix(':') else {
+            continue;
+        };
+        let value = match tokens.peek() {
+            Some(v) if !v.ends_with(':') => tokens.next().unwrap_or_default(),
+            _ => "",
+        };
+        let value = if let Ok(n) = value.parse::<u64>() {
+            n.into()
+        } else if let Ok(f) = value.parse::<f64>() {
+            f.into()
+        } else {
+            value.into()
+        };
+        rec.insert(key.to_ascii_lowercase(), value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::LinePos;
+
+    const LOG: &str =
+        "/usr/sbin/mysqld, Version: 8.0.35 (MySQL Community Server - GPL). started with:
+Tcp port: 3306  Unix socket: /var/run/mysqld/mysqld.sock
+Time                 Id Command    Argument
+# Time: 2024-01-02T03:04:05.123456Z
+# User@Host: app[app] @ web1 [10.0.0.5]  Id:    42
+# Query_time: 2.000123  Lock_time: 0.000100 Rows_sent: 1  Rows_examined: 100000
+use shop;
+SET timestamp=1704164645;
+SELECT *
+  FROM orders
+ WHERE id = 1;
+# User@Host: root[root] @ localhost []  Id:     7
+# Thread_id: 7  Schema: shop  QC_hit: No
+# Query_time: 0.5  Lock_time: 0 Rows_sent: 0  Rows_examined: 0
+SET timestamp=1704164646;
+DELETE FROM carts;
+";
+
+    fn records() -> Vec<Option<serde_json::Value>> {
+        let mut j = MysqlSlow.line_joiner().unwrap();
+        let mut joined = Vec::new();
+        for l in LOG.lines() {
+            j.push(l.as_bytes(), LinePos::default(), &mut |r, _| {
+                joined.push(r.to_vec())
+            });
+        }
+        j.finish(&mut |r, _| joined.push(r.to_vec()));
+
+        joined
+            .iter()
+            .map(|r| {
+                let mut out = Vec::new();
+                MysqlSlow
+                    .process_line_to_buf(std::str::from_utf8(r).unwrap(), &mut out)
+                    .then(|| serde_json::from_slice(&out).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn one_record_per_query() {
+        let recs = records();
+        assert_eq!(recs.len(), 3);
+        assert!(recs[0].is_none(), "the startup banner is rejected");
+
+        let v = recs[1].as_ref().unwrap();
+        assert_eq!(v["ts"], "2024-01-02T03:04:05.123456Z");
+        assert_eq!(v["user"], "app");
+        assert_eq!(v["host"], "web1");
+        assert_eq!(v["client_ip"], "10.0.0.5");
+        assert_eq!(v["thread_id"], 42);
+        assert_eq!(v["query_time"], 2.000123);
+        assert_eq!(v["rows_examined"], 100000);
+        assert_eq!(v["schema"], "shop");
+        assert_eq!(v["query"], "SELECT * FROM orders WHERE id = 1;");
+
+        let v = recs[2].as_ref().unwrap();
+        assert_eq!(v["ts"], "2024-01-02T03:04:06Z");
+        assert_eq!(v["host"], "localhost");
+        assert!(v.get("client_ip").is_none());
+        assert_eq!(v["schema"], "shop");
+        assert_eq!(v["qc_hit"], "No");
+        assert_eq!(v["lock_time"], 0);
+        assert_eq!(v["query"], "DELETE FROM carts;");
+    }
+
+    #[test]
+    fn old_time_header() {
+        assert_eq!(time_header("240102  3:04:05"), "2024-01-02T03:04:05");
+        assert_eq!(time_header("240102 13:04:05"), "2024-01-02T13:04:05");
+    }
+}
@@ -0,0 +1,179 @@
+use crate::core::Parser;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(NginxError)
+}
+
+/// nginx `error_log`: `2024/01/02 03:04:05 [error] pid#tid: *cid message,
+/// client: ..., server: ..., request: "...", upstream: "...", host: "..."`.
+/// The trailing `key: value` context becomes fields of its own.
+pub struct NginxError;
+
+const LEVELS: &[&str] = &[
+    "debug", "info", "notice", "warn", "error", "crit", "alert", "emerg",
+];
+
+struct Record<'a> {
+    /// Local time, ISO 8601 without offset (nginx doesn't log one).
+    ts: String,
+    level: &'a str,
+    pid: u32,
+    tid: u64,
+    connection_id: Option<u64>,
+    message: &'a str,
+    context: Vec<(&'a str, &'a str)>,
+}
+
+impl Serialize for Record<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("ts", &self.ts)?;
+        map.serialize_entry("level", self.level)?;
+        map.serialize_entry("pid", &self.pid)?;
+        map.serialize_entry("tid", &self.tid)?;
+        if let Some(cid) = self.connection_id {
+            map.serialize_entry("connection_id", &cid)?;
+        }
+        map.serialize_entry("message", self.message)?;
+        for (k, v) in &self.context {
+            map.serialize_entry(k, v)?;
+        }
+        map.end()
+    }
+}
+
+impl Parser for NginxError {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("nginx-error")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses nginx error_log lines -> JSONL with context fields")
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let Some(rec) = parse_error(line) else {
+            return false;
+        };
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+fn parse_error(line: &str) -> Option<Record<'_>> {
+    let b = line.as_bytes();
+    if b.len() < 21 || b[4] != b'/' || b[7] != b'/' || b[10] != b' ' || b[13] != b':' {
+        return None;
+    }
+    let (date, time) = (&line[..10], &line[11..19]);
+    if !date.bytes().all(|c| c.is_ascii_digit() || c == b'/')
+        || !time.bytes().all(|c| c.is_ascii_digit() || c == b':')
+    {
+        return None;
+    }
+
+    let rest = line[19..].strip_prefix(" [")?;
+    let (level, rest) = rest.split_once("] ")?;
+    if !LEVELS.contains(&level) {
+        return None;
+    }
+    let (ids, rest) = rest.split_once(": ")?;
+    let (pid, tid) = ids.split_once('#')?;
+
+    let (connection_id, rest) = match rest.strip_prefix('*').and_then(|r| r.split_once(' ')) {
+        Some((cid, r)) if cid.bytes().all(|c| c.is_ascii_digit()) => (cid.parse().ok(), r),
+        _ => (None, rest),
+    };
+    let (message, context) = split_context(rest);
+
+    Some(Record {
+        ts: format!("{}T{time}", date.replace('/', "-")),
+        level,
+        pid: pid.parse().ok()?,
+        tid: tid.parse().ok()?,
+        connection_id,
+        message,
+        context,
+    })
+}
+
+/// The context starts at `, client: ` (or `client: ` for messages that are
+/// only context). Values are bare up to the next `, ` or `"quoted"`.
+fn split_context(s: &str) -> (&str, Vec<(&str, &str)>) {
+    let (message, mut ctx) = match s.find(", client: ") {
+        Some(i) => (&s[..i], &s[i + 2..]),
+        None if s.starts_with("client: ") => ("", s),
+        None => return (s, Vec::new()),
+    };
+
+    let mut pairs = Vec::new();
+    while let Some((key, rest)) = ctx.split_once(": ") {
+        if key.is_empty() || key.contains(' ') {
+            break;
+        }
+        let (value, rest) = match rest.strip_prefix('"') {
+            Some(r) => match r.find("\", ").or_else(|| r.strip_suffix('"').map(str::len)) {
+                Some(end) => (&r[..end], r.get(end + 3..).unwrap_or("")),
+                None => (rest, ""),
+            },
+            None => rest.split_once(", ").unwrap_or((rest, "")),
+        };
+        pairs.push((key, value));
+        ctx = rest;
+    }
+    (message, pairs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(line: &str) -> Option<serde_json::Value> {
+        let mut out = Vec::new();
+        NginxError
+            .process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn parses_request_error_with_context() {
+        let v = run(r#"2024/01/02 03:04:05 [error] 1234#5678: *99 open() "/var/www/x, y" failed (2: No such file or directory), client: 10.0.0.1, server: example.com, request: "GET /x, y HTTP/1.1", upstream: "http://127.0.0.1:8080/x", host: "example.com""#).unwrap();
+        assert_eq!(v["ts"], "2024-01-02T03:04:05");
+        assert_eq!(v["level"], "error");
+        assert_eq!(v["pid"], 1234);
+        assert_eq!(v["tid"], 5678);
+        assert_eq!(v["connection_id"], 99);
+        assert_eq!(
+            v["message"],
+            r#"open() "/var/www/x, y" failed (2: No such file or directory)"#
+        );
+        assert_eq!(v["client"], "10.0.0.1");
+        assert_eq!(v["server"], "example.com");
+        assert_eq!(v["request"], "GET /x, y HTTP/1.1");
+        assert_eq!(v["upstream"], "http://127.0.0.1:8080/x");
+        assert_eq!(v["host"], "example.com");
+    }
+
+    #[test]
+    fn parses_process_messages() {
+        let v = run("2024/01/02 03:04:05 [notice] 1#1: signal process started").unwrap();
+        assert_eq!(v["level"], "notice");
+        assert!(v.get("connection_id").is_none());
+        assert_eq!(v["message"], "signal process started");
+        assert!(v.get("client").is_none());
+    }
+
+    #[test]
+    fn rejects_other_lines() {
+        assert!(run("2024/01/02 03:04:05 [bogus] 1#1: x").is_none());
+        assert!(
+            run("10.0.0.1 - - [02/Jan/2024:03:04:05 +0000] \"GET / HTTP/1.1\" 200 1").is_none()
+        );
+    }
+}
@@ -0,0 +1,261 @@
+use crate::core::{InputFormat, Parser};
+use crate::modules::common::{epoch_to_rfc3339, explode_json_records};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(NsgFlow)
+}
+
+/// Azure NSG flow logs (`PT1H.json` blobs holding a `records` array). Every
+/// flow tuple (`1542110377,94.102.49.190,10.5.16.4,28746,443,T,I,D,B,,,,`)
+/// becomes one record, with the rule, MAC and NSG it was logged under.
+pub struct NsgFlow;
+
+/// One tuple with its context: the lines `split_document` hands on.
+#[derive(Serialize, Deserialize)]
+struct Tuple<'a> {
+    #[serde(borrow)]
+    tuple: Cow<'a, str>,
+    #[serde(borrow)]
+    rule: Cow<'a, str>,
+    #[serde(borrow, default)]
+    mac: Option<Cow<'a, str>>,
+    #[serde(borrow, rename = "resourceId", default)]
+    resource_id: Option<Cow<'a, str>>,
+    #[serde(default)]
+    version: Option<u64>,
+}
+
+/// The part of a flow log record holding the tuples.
+#[derive(Deserialize)]
+struct Record {
+    #[serde(rename = "resourceId", default)]
+    resource_id: Option<String>,
+    properties: Properties,
+}
+
+#[derive(Deserialize)]
+struct Properties {
+    #[serde(rename = "Version", default)]
+    version: Option<u64>,
+    flows: Vec<RuleFlows>,
+}
+
+#[derive(Deserialize)]
+struct RuleFlows {
+    rule: String,
+    flows: Vec<MacFlows>,
+}
+
+#[derive(Deserialize)]
+struct MacFlows {
+    #[serde(default)]
+    mac: Option<String>,
+    #[serde(rename = "flowTuples")]
+    flow_tuples: Vec<String>,
+}
+
+/// `/SUBSCRIPTIONS/.../NETWORKSECURITYGROUPS/<name>` -> `<name>`.
+fn nsg_name(resource_id: &str) -> &str {
+    resource_id.rsplit('/').next().unwrap_or(resource_id)
+}
+
+fn count(v: Option<&str>) -> Value {
+    v.and_then(|v| v.parse::<u64>().ok())
+        .map_or(Value::Null, Into::into)
+}
+
+impl Parser for NsgFlow {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("nsg-flow")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Azure NSG flow log JSON blobs -> one JSONL record per flow tuple")
+    }
+
+    fn input_format(&self) -> InputFormat {
+        InputFormat::Document
+    }
+
+    fn split_document(&self, doc: &[u8], out: &mut Vec<u8>) -> Result<()> {
+        let mut records = Vec::new();
+        explode_json_records(doc, "records", &mut records)?;
+        for line in records.split(|&b| b == b'\n').filter(|l| !l.is_empty()) {
+            let rec: Record = serde_json::from_slice(line).context("not an NSG flow log record")?;
+            for rule in &rec.properties.flows {
+                for mac in &rule.flows {
+                    for tuple in &mac.flow_tuples {
+                        let t = Tuple {
+                            tuple: tuple.into(),
+                            rule: rule.rule.as_str().into(),
+                            mac: mac.mac.as_deref().map(Into::into),
+                            resource_id: rec.resource_id.as_deref().map(Into::into),
+                            version: rec.properties.version,
+                        };
+                        serde_json::to_writer(&mut *out, &t)?;
+                        out.push(b'\n');
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// `detect` samples raw file lines: accept a one-line blob.
+    fn recognizes(&self, line: &str) -> bool {
+        let mut tuples = Vec::new();
+        line.contains("flowTuples")
+            && self.split_document(line.as_bytes(), &mut tuples).is_ok()
+            && tuples
+                .split(|&b| b == b'\n')
+                .find(|l| !l.is_empty())
+                .and_then(|l| std::str::from_utf8(l).ok())
+                .is_some_and(|l| self.process_line_to_buf(l, &mut Vec::new()))
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["src_ip".to_string(), "dst_ip".to_string()]
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let Ok(t) = serde_json::from_str::<Tuple>(line.trim()) else {
+            return false;
+        };
+        let cols: Vec<&str> = t.tuple.split(',').collect();
+        if cols.len() < 8 {
+            return false;
+        }
+        let Some(ts) = epoch_to_rfc3339(cols[0]) else {
+            return false;
+        };
+        let (Ok(src_port), Ok(dst_port)) = (cols[3].parse::<u16>(), cols[4].parse::<u16>()) else {
+            return false;
+        };
+        let protocol = match cols[5] {
+            "T" => "tcp",
+            "U" => "udp",
+            other => other,
+        };
+        let direction = match cols[6] {
+            "I" => "inbound",
+            "O" => "outbound",
+            other => other,
+        };
+        let decision = match cols[7] {
+            "A" => "allow",
+            "D" => "deny",
+            other => other,
+        };
+        // Version 2 adds the flow state and the packet / byte counters.
+        let flow_state = cols.get(8).and_then(|s| match *s {
+            "B" => Some("begin"),
+            "C" => Some("continuing"),
+            "E" => Some("end"),
+            _ => None,
+        });
+
+        let rec = serde_json::json!({
+            "ts": ts,
+            "src_ip": cols[1],
+            "dst_ip": cols[2],
+            "src_port": src_port,
+            "dst_port": dst_port,
+            "protocol": protocol,
+            "direction": direction,
+            "decision": decision,
+            "flow_state": flow_state,
+            "packets_src_to_dst": count(cols.get(9).copied()),
+            "bytes_src_to_dst": count(cols.get(10).copied()),
+            "packets_dst_to_src": count(cols.get(11).copied()),
+            "bytes_dst_to_src": count(cols.get(12).copied()),
+            "rule": t.rule,
+            "mac": t.mac,
+            "nsg": t.resource_id.as_deref().map(nsg_name),
+            "resource_id": t.resource_id,
+            "version": t.version,
+        });
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOB: &str = r#"{"records":[
+      {"time":"2018-11-13T12:00:35.3899262Z","systemId":"a0fca5ce","macAddress":"000D3AF87856","category":"NetworkSecurityGroupFlowEvent",
+       "resourceId":"/SUBSCRIPTIONS/00000000/RESOURCEGROUPS/FABRIKAMRG/PROVIDERS/MICROSOFT.NETWORK/NETWORKSECURITYGROUPS/FABRIAKMVM1-NSG",
+       "operationName":"NetworkSecurityGroupFlowEvents","properties":{"Version":2,"flows":[
+         {"rule":"DefaultRule_DenyAllInBound","flows":[{"mac":"000D3AF87856","flowTuples":[
+           "1542110377,94.102.49.190,10.5.16.4,28746,443,T,I,D,B,,,,",
+           "1542110424,176.119.4.10,10.5.16.4,56509,59336,T,I,D,B,,,,"]}]},
+         {"rule":"DefaultRule_AllowInternetOutBound","flows":[{"mac":"000D3AF87856","flowTuples":[
+           "1542110377,10.5.16.4,13.67.143.118,59831,443,T,O,A,E,1,66,1,66"]}]}]}}]}"#;
+
+    fn records(doc: &str) -> Vec<Value> {
+        let mut lines = Vec::new();
+        NsgFlow.split_document(doc.as_bytes(), &mut lines).unwrap();
+        std::str::from_utf8(&lines)
+            .unwrap()
+            .lines()
+            .map(|l| {
+                let mut out = Vec::new();
+                assert!(NsgFlow.process_line_to_buf(l, &mut out), "{l}");
+                serde_json::from_slice(&out).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn explodes_each_flow_tuple() {
+        let recs = records(BLOB);
+        assert_eq!(recs.len(), 3);
+        let v = &recs[0];
+        assert_eq!(v["ts"], "2018-11-13T11:59:37Z");
+        assert_eq!(v["src_ip"], "94.102.49.190");
+        assert_eq!(v["dst_port"], 443);
+        assert_eq!(v["protocol"], "tcp");
+        assert_eq!(v["direction"], "inbound");
+        assert_eq!(v["decision"], "deny");
+        assert_eq!(v["flow_state"], "begin");
+        assert_eq!(v["bytes_src_to_dst"], Value::Null);
+        assert_eq!(v["rule"], "DefaultRule_DenyAllInBound");
+        assert_eq!(v["nsg"], "FABRIAKMVM1-NSG");
+        assert_eq!(v["version"], 2);
+
+        let v = &recs[2];
+        assert_eq!(v["direction"], "outbound");
+        assert_eq!(v["decision"], "allow");
+        assert_eq!(v["flow_state"], "end");
+        assert_eq!(v["packets_src_to_dst"], 1);
+        assert_eq!(v["bytes_dst_to_src"], 66);
+    }
+
+    #[test]
+    fn version_1_tuples_and_rejects() {
+        let v1 = BLOB
+            .replace(r#""Version":2"#, r#""Version":1"#)
+            .replace(",B,,,,", "")
+            .replace(",E,1,66,1,66", "");
+        let recs = records(&v1);
+        assert_eq!(recs[2]["flow_state"], Value::Null);
+        assert_eq!(recs[2]["packets_src_to_dst"], Value::Null);
+
+        let mut out = Vec::new();
+        assert!(!NsgFlow.process_line_to_buf(r#"{"tuple":"1,2,3","rule":"r"}"#, &mut out));
+        assert!(NsgFlow
+            .split_document(br#"{"records":[{"a":1}]}"#, &mut out)
+            .is_err());
+        let one_line: String = BLOB.lines().map(str::trim).collect();
+        assert!(NsgFlow.recognizes(&one_line));
+        assert!(!NsgFlow.recognizes(r#"{"Records":[]}"#));
+    }
+}
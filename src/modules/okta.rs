@@ -0,0 +1,156 @@
+use crate::core::Parser;
+use crate::modules::common::{flatten_with, rfc3339_to_utc};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(Okta)
+}
+
+/// Okta System Log events, one JSON object per line (log streaming, or the
+/// `/api/v1/logs` array split with `jq -c '.[]'`). Nested objects are
+/// flattened to dotted keys, the `target` list to one array per field, and
+/// `published` becomes an RFC 3339 UTC `ts`.
+pub struct Okta;
+
+/// Lists of objects kept as they are: their entries differ in shape.
+const NESTED_KEYS: &[&str] = &["request.ipChain"];
+
+/// [`flatten_with`] hook: `[{id: a, type: User}, {id: b}]` ->
+/// `{id: [a, b], type: ["User", null]}`, one array per field aligned by
+/// position, which then flattens to `key.id` and `key.type`.
+fn columns(key: &str, v: Value) -> Value {
+    let items = match v {
+        Value::Array(items)
+            if !NESTED_KEYS.contains(&key)
+                && !items.is_empty()
+                && items.iter().all(Value::is_object) =>
+        {
+            items
+        }
+        v => return v,
+    };
+    let rows: Vec<Map<String, Value>> = items
+        .into_iter()
+        .map(|item| {
+            let mut flat = Map::new();
+            if let Value::Object(obj) = item {
+                flatten_with("", obj, &[], &mut flat, &columns);
+            }
+            flat
+        })
+        .collect();
+    let mut names: Vec<String> = Vec::new();
+    for key in rows.iter().flat_map(Map::keys) {
+        if !names.contains(key) {
+            names.push(key.clone());
+        }
+    }
+    let mut out = Map::new();
+    for name in names {
+        let column = rows
+            .iter()
+            .map(|row| row.get(&name).cloned().unwrap_or(Value::Null))
+            .collect();
+        out.insert(name, Value::Array(column));
+    }
+    Value::Object(out)
+}
+
+impl Parser for Okta {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("okta")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Okta System Log JSON events -> flattened JSONL with ts")
+    }
+
+    fn recognizes(&self, line: &str) -> bool {
+        let line = line.trim();
+        line.starts_with('{')
+            && line.contains("\"eventType\"")
+            && line.contains("\"published\"")
+            && self.process_line_to_buf(line, &mut Vec::new())
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["client.ipAddress".to_string()]
+    }
+
+    fn user_agent_fields(&self) -> Vec<String> {
+        vec!["client.userAgent.rawUserAgent".to_string()]
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let Ok(Value::Object(mut event)) = serde_json::from_str::<Value>(line.trim()) else {
+            return false;
+        };
+        if !event.get("eventType").is_some_and(Value::is_string) {
+            return false;
+        }
+        let published = event.shift_remove("published");
+        let ts = match published {
+            Some(Value::String(s)) => Value::String(rfc3339_to_utc(&s).unwrap_or(s)),
+            other => other.unwrap_or(Value::Null),
+        };
+
+        let mut flat = Map::new();
+        flat.insert("ts".to_string(), ts);
+        flatten_with("", event, &[], &mut flat, &columns);
+        if serde_json::to_writer(&mut *out, &flat).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOGIN: &str = r#"{"actor":{"id":"00u1","type":"User","alternateId":"alice@example.com","displayName":"Alice","detailEntry":null},"client":{"userAgent":{"rawUserAgent":"Mozilla/5.0","os":"Mac OS X","browser":"CHROME"},"zone":"null","device":"Computer","id":null,"ipAddress":"203.0.113.7","geographicalContext":{"city":"Paris","state":"Ile-de-France","country":"France","postalCode":null,"geolocation":{"lat":48.85,"lon":2.35}}},"authenticationContext":{"externalSessionId":"102abc"},"displayMessage":"User login to Okta","eventType":"user.session.start","outcome":{"result":"FAILURE","reason":"INVALID_CREDENTIALS"},"published":"2024-03-01T10:20:30.123+01:00","securityContext":{},"severity":"WARN","debugContext":{"debugData":{"requestUri":"/api/v1/authn","threatSuspected":"false"}},"legacyEventType":"core.user_auth.login_failed","transaction":{"type":"WEB","id":"Zx1","detail":{}},"uuid":"5b1e","version":"0","request":{"ipChain":[{"ip":"203.0.113.7","geographicalContext":{"city":"Paris"},"version":"V4","source":null}]},"target":[{"id":"0oa1","type":"AppInstance","alternateId":"Salesforce","displayName":"Salesforce.com"},{"id":"00u2","type":"User","alternateId":"bob@example.com","displayName":"Bob","detailEntry":{"methodTypeUsed":"Okta Verify"}}]}"#;
+
+    fn run(line: &str) -> Option<Value> {
+        let mut out = Vec::new();
+        Okta.process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn flattens_actor_client_outcome_and_targets() {
+        let v = run(LOGIN).unwrap();
+        assert_eq!(v["ts"], "2024-03-01T09:20:30.123Z");
+        assert!(v.get("published").is_none());
+        assert_eq!(v["eventType"], "user.session.start");
+        assert_eq!(v["actor.alternateId"], "alice@example.com");
+        assert_eq!(v["client.ipAddress"], "203.0.113.7");
+        assert_eq!(v["client.userAgent.rawUserAgent"], "Mozilla/5.0");
+        assert_eq!(v["client.geographicalContext.country"], "France");
+        assert_eq!(v["client.geographicalContext.geolocation.lat"], 48.85);
+        assert_eq!(v["outcome.result"], "FAILURE");
+        assert_eq!(v["outcome.reason"], "INVALID_CREDENTIALS");
+        assert_eq!(v["debugContext.debugData.threatSuspected"], "false");
+        assert_eq!(v["securityContext"], serde_json::json!({}));
+
+        assert_eq!(v["target.type"], serde_json::json!(["AppInstance", "User"]));
+        assert_eq!(v["target.alternateId"][1], "bob@example.com");
+        assert_eq!(
+            v["target.detailEntry.methodTypeUsed"],
+            serde_json::json!([null, "Okta Verify"])
+        );
+        assert_eq!(
+            v["request.ipChain"][0]["geographicalContext"]["city"],
+            "Paris"
+        );
+    }
+
+    #[test]
+    fn rejects_other_json() {
+        assert!(run(r#"{"eventName":"GetObject","published":"x"}"#).is_none());
+        assert!(run("[1, 2]").is_none());
+        assert!(!Okta.recognizes(r#"{"timestamp":"x","event_type":"alert"}"#));
+        assert!(Okta.recognizes(LOGIN));
+    }
+}
@@ -0,0 +1,375 @@
+use crate::core::Parser;
+use crate::modules::common::{epoch_to_rfc3339, month_number, split_syslog, Syslog};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::net::IpAddr;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(OpenVpn)
+}
+
+/// OpenVPN server logs (`--log` files or syslog) and status files
+/// (`--status`, versions 1 to 3). Log lines are tagged with the client they
+/// concern and an `event`; status rows carry the per-client byte counters.
+pub struct OpenVpn;
+
+#[derive(Default, Serialize)]
+struct Record<'a> {
+    #[serde(flatten)]
+    syslog: Option<Syslog<'a>>,
+    /// `--log` stamps and status times; local time unless from a `time_t`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ts: Option<String>,
+    /// `connect`, `disconnect`, `address_assigned`, `route_learned`,
+    /// `auth_failure`, `tls_error`, `verify_ok`, `client_list`,
+    /// `routing_table` or `other`.
+    event: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    common_name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    real_ip: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    real_port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    virtual_ip: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    virtual_ipv6: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    username: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bytes_received: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bytes_sent: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    peer_id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cipher: Option<&'a str>,
+    /// Signal reason of a disconnect (`remote-exit`, `ping-restart`, ...) or
+    /// the text of an authentication / TLS failure.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<&'a str>,
+}
+
+/// `2024-01-20 10:11:12` or `Sat Jan 20 10:11:12 2024` -> `2024-01-20T10:11:12`.
+fn local_time(s: &str) -> Option<String> {
+    let b = s.as_bytes();
+    if b.len() == 19 && b[4] == b'-' && b[7] == b'-' && b[10] == b' ' && b[13] == b':' {
+        return Some(format!("{}T{}", &s[..10], &s[11..]));
+    }
+    let parts: Vec<&str> = s.split_ascii_whitespace().collect();
+    let [_, month, day, time, year] = parts[..] else {
+        return None;
+    };
+    let month = month_number(month)?;
+    let day: u8 = day.parse().ok()?;
+    let year: u16 = year.parse().ok()?;
+    if time.len() != 8 {
+        return None;
+    }
+    Some(format!("{year:04}-{month:02}-{day:02}T{time}"))
+}
+
+/// `203.0.113.5:51234`, `[AF_INET]203.0.113.5:51234` or an IPv6 address with
+/// its port after the last `:`.
+fn real_address(s: &str) -> Option<(&str, u16)> {
+    let s = s
+        .strip_prefix("[AF_INET]")
+        .or_else(|| s.strip_prefix("[AF_INET6]"))
+        .unwrap_or(s);
+    let (ip, port) = s.rsplit_once(':')?;
+    let ip = ip.trim_start_matches('[').trim_end_matches(']');
+    ip.parse::<IpAddr>().ok()?;
+    Some((ip, port.parse().ok()?))
+}
+
+/// Split the line's stamp or syslog preamble off.
+fn split_stamp<'a>(line: &'a str, rec: &mut Record<'a>) -> Option<&'a str> {
+    if let Some((syslog, message)) = split_syslog(line) {
+        if !syslog.program.contains("openvpn") && !syslog.program.starts_with("ovpn") {
+            return None;
+        }
+        rec.syslog = Some(syslog);
+        return Some(message);
+    }
+    for len in [19, 24] {
+        if let Some(stamp) = line.get(..len)
+            && let Some(ts) = local_time(stamp)
+        {
+            rec.ts = Some(ts);
+            return Some(line[len..].trim_start());
+        }
+    }
+    None
+}
+
+/// `alice/203.0.113.5:51234 MULTI: ...`, `203.0.113.5:51234 TLS: ...`.
+fn log_message<'a>(message: &'a str, rec: &mut Record<'a>) {
+    let mut rest = message;
+    if let Some((first, tail)) = message.split_once(' ') {
+        let (cn, addr) = match first.split_once('/') {
+            Some((cn, addr)) => (Some(cn), addr),
+            None => (None, first),
+        };
+        if let Some((ip, port)) = real_address(addr) {
+            rec.common_name = cn;
+            rec.real_ip = Some(ip);
+            rec.real_port = Some(port);
+            rest = tail;
+        }
+    }
+    rec.message = Some(rest);
+
+    rec.event = if let Some(tail) = rest.strip_prefix("MULTI_sva: pool returned IPv4=") {
+        let (v4, v6) = tail.split_once(", IPv6=").unwrap_or((tail, ""));
+        rec.virtual_ip = Some(v4).filter(|v| !v.starts_with('('));
+        rec.virtual_ipv6 = Some(v6).filter(|v| !v.is_empty() && !v.starts_with('('));
+        "address_assigned"
+    } else if let Some(tail) = rest.strip_prefix("MULTI: Learn: ") {
+        rec.virtual_ip = tail.split_once(" -> ").map(|(v, _)| v);
+        "route_learned"
+    } else if rest.contains("Peer Connection Initiated with") {
+        if let Some(cn) = rest.strip_prefix('[').and_then(|r| r.split_once(']')) {
+            rec.common_name = Some(cn.0);
+        }
+        "connect"
+    } else if rest.contains("client-instance exiting")
+        || rest.contains("client-instance restarting")
+    {
+        // `SIGTERM[soft,remote-exit] received, ...`
+        rec.reason = rest
+            .split_once('[')
+            .and_then(|(_, r)| r.split_once(']'))
+            .map(|(r, _)| r.rsplit(',').next().unwrap_or(r));
+        "disconnect"
+    } else if rest.starts_with("Connection reset") {
+        rec.reason = Some("connection-reset");
+        "disconnect"
+    } else if rest.starts_with("TLS Auth Error")
+        || rest.starts_with("VERIFY ERROR")
+        || rest.contains("AUTH_FAILED")
+        || rest.contains("verification failed")
+    {
+        rec.reason = Some(rest);
+        "auth_failure"
+    } else if rest.starts_with("TLS Error") {
+        rec.reason = Some(rest);
+        "tls_error"
+    } else if let Some(tail) = rest.strip_prefix("VERIFY OK: depth=0, ") {
+        rec.common_name = tail
+            .split([',', '/'])
+            .find_map(|p| p.trim().strip_prefix("CN="));
+        "verify_ok"
+    } else {
+        "other"
+    };
+    // `TLS Error: cannot locate HMAC in incoming packet from [AF_INET]...`
+    if rec.real_ip.is_none()
+        && let Some((_, addr)) = rest.rsplit_once(" from ")
+        && let Some((ip, port)) = real_address(addr.trim_end_matches([',', '.']))
+    {
+        rec.real_ip = Some(ip);
+        rec.real_port = Some(port);
+    }
+}
+
+/// Status file rows: `CLIENT_LIST,...` / `ROUTING_TABLE,...` (versions 2 and
+/// 3, comma or tab separated) or version 1's bare client and routing rows.
+fn status_row<'a>(line: &'a str, rec: &mut Record<'a>) -> Option<()> {
+    let sep = if line.contains('\t') { '\t' } else { ',' };
+    let cols: Vec<&str> = line.split(sep).collect();
+    let num = |s: &str| s.parse::<u64>().ok();
+    let real = |rec: &mut Record<'a>, s: &'a str| -> Option<()> {
+        let (ip, port) = real_address(s)?;
+        rec.real_ip = Some(ip);
+        rec.real_port = Some(port);
+        Some(())
+    };
+    let time = |stamp: &str, time_t: Option<&str>| {
+        time_t
+            .and_then(epoch_to_rfc3339)
+            .or_else(|| local_time(stamp))
+    };
+    match cols[..] {
+        // 2.4+: CN, real, virtual, virtual v6, recv, sent, since, since_t,
+        // username, client id, peer id[, cipher]; 2.3 lacks v6 and the ids.
+        ["CLIENT_LIST", cn, addr, ref rest @ ..] => {
+            let (virtual_ip, v6, rest) = match rest {
+                [v4, v6, rest @ ..] if rest.len() >= 7 => (*v4, Some(*v6), rest),
+                [v4, rest @ ..] => (*v4, None, rest),
+                [] => return None,
+            };
+            let [recv, sent, since, since_t, ref tail @ ..] = rest[..] else {
+                return None;
+            };
+            rec.event = "client_list";
+            rec.common_name = Some(cn);
+            real(rec, addr)?;
+            rec.virtual_ip = Some(virtual_ip).filter(|v| !v.is_empty());
+            rec.virtual_ipv6 = v6.filter(|v| !v.is_empty());
+            rec.bytes_received = Some(num(recv)?);
+            rec.bytes_sent = Some(num(sent)?);
+            rec.ts = time(since, Some(since_t));
+            rec.username = tail.first().copied().filter(|u| *u != "UNDEF");
+            rec.client_id = tail.get(1).and_then(|v| num(v));
+            rec.peer_id = tail.get(2).and_then(|v| num(v));
+            rec.cipher = tail.get(3).copied();
+        }
+        ["ROUTING_TABLE", virtual_ip, cn, addr, last_ref, ref rest @ ..] => {
+            rec.event = "routing_table";
+            rec.virtual_ip = Some(virtual_ip);
+            rec.common_name = Some(cn);
+            real(rec, addr)?;
+            rec.ts = time(last_ref, rest.first().copied());
+        }
+        // Version 1.
+        [cn, addr, recv, sent, since] if real_address(addr).is_some() => {
+            rec.event = "client_list";
+            rec.common_name = Some(cn);
+            real(rec, addr)?;
+            rec.bytes_received = Some(num(recv)?);
+            rec.bytes_sent = Some(num(sent)?);
+            rec.ts = Some(local_time(since)?);
+        }
+        [virtual_ip, cn, addr, last_ref] if real_address(addr).is_some() => {
+            rec.event = "routing_table";
+            rec.virtual_ip = Some(virtual_ip);
+            rec.common_name = Some(cn);
+            real(rec, addr)?;
+            rec.ts = Some(local_time(last_ref)?);
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+impl Parser for OpenVpn {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("openvpn")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses OpenVPN server logs and status files -> session JSONL")
+    }
+
+    /// Server messages that concern no client parse as `other`; detection
+    /// wants a client event or status row.
+    fn recognizes(&self, line: &str) -> bool {
+        let mut rec = Record::default();
+        match split_stamp(line, &mut rec) {
+            Some(message) => {
+                log_message(message, &mut rec);
+                rec.event != "other" || rec.real_ip.is_some()
+            }
+            None => status_row(line, &mut rec).is_some(),
+        }
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["real_ip".to_string()]
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let mut rec = Record::default();
+        match split_stamp(line, &mut rec) {
+            Some(message) => log_message(message, &mut rec),
+            None => {
+                if status_row(line, &mut rec).is_none() {
+                    return false;
+                }
+            }
+        }
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn run(line: &str) -> Option<Value> {
+        let mut out = Vec::new();
+        OpenVpn
+            .process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn tags_session_events() {
+        let v = run("2024-01-20 10:11:12 203.0.113.5:51234 [alice] Peer Connection Initiated with [AF_INET]203.0.113.5:51234").unwrap();
+        assert_eq!(v["ts"], "2024-01-20T10:11:12");
+        assert_eq!(v["event"], "connect");
+        assert_eq!(v["common_name"], "alice");
+        assert_eq!(v["real_ip"], "203.0.113.5");
+        assert_eq!(v["real_port"], 51234);
+
+        let v = run("Sat Jan 20 10:11:13 2024 alice/203.0.113.5:51234 MULTI_sva: pool returned IPv4=10.8.0.6, IPv6=(Not enabled)").unwrap();
+        assert_eq!(v["ts"], "2024-01-20T10:11:13");
+        assert_eq!(v["event"], "address_assigned");
+        assert_eq!(v["virtual_ip"], "10.8.0.6");
+        assert!(v.get("virtual_ipv6").is_none());
+
+        let v = run("Jan 20 11:00:00 vpn openvpn[812]: alice/203.0.113.5:51234 SIGTERM[soft,remote-exit] received, client-instance exiting").unwrap();
+        assert_eq!(v["host"], "vpn");
+        assert_eq!(v["event"], "disconnect");
+        assert_eq!(v["reason"], "remote-exit");
+        assert_eq!(v["common_name"], "alice");
+
+        let v = run("2024-01-20 10:11:12 198.51.100.7:40000 TLS Auth Error: Auth Username/Password verification failed for peer").unwrap();
+        assert_eq!(v["event"], "auth_failure");
+        assert_eq!(v["real_ip"], "198.51.100.7");
+        let v = run("2024-01-20 10:11:12 TLS Error: cannot locate HMAC in incoming packet from [AF_INET]198.51.100.8:1194").unwrap();
+        assert_eq!(v["event"], "tls_error");
+        assert_eq!(v["real_ip"], "198.51.100.8");
+        let v = run("2024-01-20 10:11:12 Initialization Sequence Completed").unwrap();
+        assert_eq!(v["event"], "other");
+        assert!(!OpenVpn.recognizes("2024-01-20 10:11:12 Initialization Sequence Completed"));
+        assert!(
+            run("Jan 20 11:00:00 vpn sshd[1]: alice/1.2.3.4:5 client-instance exiting").is_none()
+        );
+    }
+
+    #[test]
+    fn reads_status_rows() {
+        let v = run("CLIENT_LIST,alice,203.0.113.5:51234,10.8.0.6,,123456,654321,2024-01-20 09:00:00,1705741200,UNDEF,3,0,AES-256-GCM").unwrap();
+        assert_eq!(v["event"], "client_list");
+        assert_eq!(v["ts"], "2024-01-20T09:00:00Z");
+        assert_eq!(v["virtual_ip"], "10.8.0.6");
+        assert_eq!(v["bytes_received"], 123456);
+        assert_eq!(v["bytes_sent"], 654321);
+        assert_eq!(v["client_id"], 3);
+        assert_eq!(v["cipher"], "AES-256-GCM");
+        assert!(v.get("username").is_none());
+
+        let v = run("CLIENT_LIST\tbob\t198.51.100.7:1194\t10.8.0.10\t10\t20\tSat Jan 20 09:00:00 2024\t1705741200\tbob").unwrap();
+        assert_eq!(v["username"], "bob");
+        assert_eq!(v["bytes_sent"], 20);
+        let v =
+            run("ROUTING_TABLE,10.8.0.6,alice,203.0.113.5:51234,2024-01-20 10:11:10,1705745470")
+                .unwrap();
+        assert_eq!(v["event"], "routing_table");
+        assert_eq!(v["ts"], "2024-01-20T10:11:10Z");
+        let v = run("alice,203.0.113.5:51234,123,456,Sat Jan 20 09:00:00 2024").unwrap();
+        assert_eq!(
+            (&v["event"], &v["bytes_sent"]),
+            (&"client_list".into(), &456.into())
+        );
+        for header in [
+            "HEADER,CLIENT_LIST,Common Name,Real Address,Virtual Address",
+            "Common Name,Real Address,Bytes Received,Bytes Sent,Connected Since",
+            "Updated,2024-01-20 10:11:12",
+            "GLOBAL_STATS,Max bcast/mcast queue length,0",
+        ] {
+            assert!(run(header).is_none(), "{header}");
+        }
+    }
+}
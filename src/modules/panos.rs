@@ -0,0 +1,333 @@
+use crate::core::Parser;
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(PanOs)
+}
+
+/// Palo Alto Networks PAN-OS CSV logs (syslog or exported), one record per
+/// line. The `type` column (TRAFFIC, THREAT, SYSTEM) picks the column
+/// schema; other types get the common header columns and `extra`.
+pub struct PanOs;
+
+/// Columns shared by every log type. `""` marks a FUTURE_USE column.
+const HEADER: &[&str] = &[
+    "",
+    "receive_time",
+    "serial",
+    "type",
+    "subtype",
+    "",
+    "generated_time",
+];
+
+/// TRAFFIC and THREAT share their first 31 columns.
+const SESSION: &[&str] = &[
+    "src_ip",
+    "dst_ip",
+    "nat_src_ip",
+    "nat_dst_ip",
+    "rule",
+    "src_user",
+    "dst_user",
+    "app",
+    "vsys",
+    "src_zone",
+    "dst_zone",
+    "inbound_if",
+    "outbound_if",
+    "log_action",
+    "",
+    "session_id",
+    "repeat_count",
+    "src_port",
+    "dst_port",
+    "nat_src_port",
+    "nat_dst_port",
+    "flags",
+    "proto",
+    "action",
+];
+
+const TRAFFIC: &[&str] = &[
+    "bytes",
+    "bytes_sent",
+    "bytes_received",
+    "packets",
+    "start_time",
+    "elapsed_time",
+    "category",
+    "",
+    "seqno",
+    "action_flags",
+    "src_location",
+    "dst_location",
+    "",
+    "packets_sent",
+    "packets_received",
+    "session_end_reason",
+    "dg_hier_level_1",
+    "dg_hier_level_2",
+    "dg_hier_level_3",
+    "dg_hier_level_4",
+    "vsys_name",
+    "device_name",
+    "action_source",
+    "src_vm_uuid",
+    "dst_vm_uuid",
+    "tunnel_id",
+    "monitor_tag",
+    "parent_session_id",
+    "parent_start_time",
+    "tunnel_type",
+    "sctp_assoc_id",
+    "sctp_chunks",
+    "sctp_chunks_sent",
+    "sctp_chunks_received",
+    "rule_uuid",
+    "http2_connection",
+];
+
+const THREAT: &[&str] = &[
+    "misc",
+    "threat_id",
+    "category",
+    "severity",
+    "direction",
+    "seqno",
+    "action_flags",
+    "src_location",
+    "dst_location",
+    "",
+    "content_type",
+    "pcap_id",
+    "file_digest",
+    "cloud",
+    "url_idx",
+    "user_agent",
+    "file_type",
+    "xff",
+    "referer",
+    "sender",
+    "subject",
+    "recipient",
+    "report_id",
+    "dg_hier_level_1",
+    "dg_hier_level_2",
+    "dg_hier_level_3",
+    "dg_hier_level_4",
+    "vsys_name",
+    "device_name",
+    "",
+    "src_vm_uuid",
+    "dst_vm_uuid",
+    "http_method",
+    "tunnel_id",
+    "monitor_tag",
+    "parent_session_id",
+    "parent_start_time",
+    "tunnel_type",
+    "threat_category",
+    "content_version",
+];
+
+const SYSTEM: &[&str] = &[
+    "vsys",
+    "event_id",
+    "object",
+    "",
+    "",
+    "module",
+    "severity",
+    "description",
+    "seqno",
+    "action_flags",
+    "dg_hier_level_1",
+    "dg_hier_level_2",
+    "dg_hier_level_3",
+    "dg_hier_level_4",
+    "vsys_name",
+    "device_name",
+];
+
+const INT_KEYS: &[&str] = &[
+    "session_id",
+    "repeat_count",
+    "src_port",
+    "dst_port",
+    "nat_src_port",
+    "nat_dst_port",
+    "bytes",
+    "bytes_sent",
+    "bytes_received",
+    "packets",
+    "packets_sent",
+    "packets_received",
+    "elapsed_time",
+    "seqno",
+    "parent_session_id",
+    "pcap_id",
+    "url_idx",
+];
+
+impl Parser for PanOs {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("panos")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses PAN-OS TRAFFIC/THREAT/SYSTEM CSV logs -> named JSONL fields")
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec![
+            "src_ip".to_string(),
+            "dst_ip".to_string(),
+            "nat_src_ip".to_string(),
+            "nat_dst_ip".to_string(),
+        ]
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let Some(cols) = columns(csv_part(line)) else {
+            return false;
+        };
+        if cols.len() < HEADER.len() || !is_panos_time(&cols[1]) {
+            return false;
+        }
+
+        let body: &[&[&str]] = match cols[3].as_str() {
+            "TRAFFIC" => &[SESSION, TRAFFIC],
+            "THREAT" => &[SESSION, THREAT],
+            "SYSTEM" => &[SYSTEM],
+            _ => &[],
+        };
+        let mut names = HEADER.iter().chain(body.iter().flat_map(|s| s.iter()));
+
+        let mut rec = Map::new();
+        rec.insert(
+            "ts".into(),
+            Value::String(cols[6].replacen('/', "-", 2).replacen(' ', "T", 1)),
+        );
+        let mut extra = Vec::new();
+        for value in cols {
+            match names.next() {
+                Some(&"") => {}
+                Some(&name) => {
+                    rec.insert(name.into(), typed(name, value));
+                }
+                None => extra.push(value),
+            }
+        }
+        if !extra.is_empty() {
+            rec.insert("extra".into(), extra.into());
+        }
+
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+/// Drop a syslog header: the CSV starts after the last space before the
+/// first comma.
+fn csv_part(line: &str) -> &str {
+    let head = line.find(',').map_or(line, |i| &line[..i]);
+    match head.rfind(' ') {
+        Some(i) => &line[i + 1..],
+        None => line,
+    }
+}
+
+fn columns(s: &str) -> Option<Vec<String>> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(s.as_bytes());
+    let rec = rdr.records().next()?.ok()?;
+    Some(rec.iter().map(String::from).collect())
+}
+
+/// `2024/01/02 03:04:05`
+fn is_panos_time(s: &str) -> bool {
+    let b = s.as_bytes();
+    b.len() == 19 && b[4] == b'/' && b[7] == b'/' && b[10] == b' ' && b[13] == b':'
+}
+
+fn typed(name: &str, value: String) -> Value {
+    if value.is_empty() {
+        return Value::Null;
+    }
+    if INT_KEYS.contains(&name)
+        && let Ok(n) = value.parse::<i64>()
+    {
+        return Value::from(n);
+    }
+    Value::String(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(line: &str) -> Option<serde_json::Value> {
+        let mut out = Vec::new();
+        PanOs
+            .process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn parses_traffic() {
+        let v = run("<14>Jan  2 03:04:05 PA-VM 1,2024/01/02 03:04:05,007200001056,TRAFFIC,end,2305,2024/01/02 03:04:04,10.0.0.10,8.8.8.8,192.0.2.1,8.8.8.8,allow-dns,,,dns,vsys1,trust,untrust,ethernet1/2,ethernet1/1,default,,12345,1,51000,53,41000,53,0x400064,udp,allow,180,80,100,2,2024/01/02 03:04:03,1,any,,7000000001,0x0,10.0.0.0-10.255.255.255,United States,,1,1,aged-out,0,0,0,0,,PA-VM,from-policy").unwrap();
+
+        assert_eq!(v["ts"], "2024-01-02T03:04:04");
+        assert_eq!(v["type"], "TRAFFIC");
+        assert_eq!(v["subtype"], "end");
+        assert_eq!(v["src_ip"], "10.0.0.10");
+        assert_eq!(v["rule"], "allow-dns");
+        assert!(v["src_user"].is_null());
+        assert_eq!(v["session_id"], 12345);
+        assert_eq!(v["dst_port"], 53);
+        assert_eq!(v["proto"], "udp");
+        assert_eq!(v["action"], "allow");
+        assert_eq!(v["bytes"], 180);
+        assert_eq!(v["session_end_reason"], "aged-out");
+        assert_eq!(v["device_name"], "PA-VM");
+        assert_eq!(v["action_source"], "from-policy");
+        assert!(v.get("extra").is_none());
+    }
+
+    #[test]
+    fn parses_threat_with_quoted_commas() {
+        let v = run(r#"1,2024/01/02 03:04:05,007200001056,THREAT,url,2305,2024/01/02 03:04:05,10.0.0.10,203.0.113.5,,,web,,,web-browsing,vsys1,trust,untrust,ethernet1/2,ethernet1/1,default,,999,1,52000,80,0,0,0x0,tcp,alert,"example.com/a,b",Eicar Test File(39040),business-and-economy,informational,client-to-server"#).unwrap();
+        assert_eq!(v["misc"], "example.com/a,b");
+        assert_eq!(v["threat_id"], "Eicar Test File(39040)");
+        assert_eq!(v["severity"], "informational");
+        assert_eq!(v["direction"], "client-to-server");
+    }
+
+    #[test]
+    fn parses_system_and_keeps_unknown_columns() {
+        let v = run("1,2024/01/02 03:04:05,007200001056,SYSTEM,general,2305,2024/01/02 03:04:05,,general,,0,0,general,informational,\"User admin logged in, via Web\",1234,0x0,0,0,0,0,,PA-VM,x,y").unwrap();
+        assert_eq!(v["event_id"], "general");
+        assert_eq!(v["description"], "User admin logged in, via Web");
+        assert_eq!(v["seqno"], 1234);
+        assert_eq!(v["extra"], serde_json::json!(["x", "y"]));
+
+        let v =
+            run("1,2024/01/02 03:04:05,007200001056,CONFIG,0,2305,2024/01/02 03:04:05,a").unwrap();
+        assert_eq!(v["type"], "CONFIG");
+        assert_eq!(v["extra"], serde_json::json!(["a"]));
+    }
+
+    #[test]
+    fn rejects_other_csv() {
+        assert!(run("a,b,c,d,e,f,g,h").is_none());
+        assert!(run("plain text").is_none());
+    }
+}
@@ -0,0 +1,346 @@
+use crate::core::{GroupJoiner, LineJoiner, ModuleOptions, OptionSpec, Parser};
+use crate::modules::common::{split_syslog, Syslog};
+use anyhow::Result;
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(Postfix { correlate: false })
+}
+
+/// Postfix and sendmail maillog lines (`postfix/smtpd[123]: 4F9D41A2B3C: client=...`).
+///
+/// With `correlate`, the lines of one queue id are gathered on the reader
+/// thread until Postfix logs `removed` (or the input ends) and come out as a
+/// single delivery record.
+pub struct Postfix {
+    correlate: bool,
+}
+
+const OPTIONS: &[OptionSpec] = &[OptionSpec {
+    key: "correlate",
+    help: "Join all lines of a queue id into one delivery record (default: false)",
+}];
+
+/// Queue ids still open when correlating; the oldest is flushed past this.
+const MAX_PENDING: usize = 100_000;
+
+/// Keys holding `<address>` values.
+const ADDRESS_KEYS: &[&str] = &["from", "to", "orig_to", "message_id"];
+/// Keys describing one recipient in a delivery record.
+const RECIPIENT_KEYS: &[&str] = &[
+    "to",
+    "orig_to",
+    "relay",
+    "relay_host",
+    "relay_ip",
+    "relay_port",
+    "delay",
+    "delays",
+    "dsn",
+    "status",
+    "status_detail",
+];
+
+struct MailLine<'a> {
+    syslog: Syslog<'a>,
+    daemon: &'a str,
+    queue_id: Option<&'a str>,
+    text: &'a str,
+}
+
+fn split_mail(line: &str) -> Option<MailLine<'_>> {
+    let (syslog, message) = split_syslog(line)?;
+    let daemon = match syslog.program {
+        "sendmail" | "sm-mta" => syslog.program,
+        p if p.starts_with("postfix") => p.rsplit('/').next()?,
+        _ => return None,
+    };
+
+    let (queue_id, text) = match message.split_once(": ") {
+        Some((id, text)) if is_queue_id(id) => (Some(id), text),
+        Some(("NOQUEUE", text)) => (None, text),
+        _ => (None, message),
+    };
+    Some(MailLine {
+        syslog,
+        daemon,
+        queue_id,
+        text,
+    })
+}
+
+/// Postfix short (hex) and long queue ids, sendmail ids. Words such as
+/// `warning` are all lowercase and never match.
+fn is_queue_id(s: &str) -> bool {
+    s.len() >= 5
+        && s.bytes().all(|b| b.is_ascii_alphanumeric())
+        && s.bytes()
+            .any(|b| b.is_ascii_digit() || b.is_ascii_uppercase())
+        && s != "NOQUEUE"
+}
+
+/// `name[ip]:port` (port optional).
+fn split_bracketed(v: &str) -> Option<(&str, &str, Option<u16>)> {
+    let (host, rest) = v.split_once('[')?;
+    let (ip, rest) = rest.split_once(']')?;
+    let port = rest.strip_prefix(':').and_then(|p| p.parse().ok());
+    Some((host, ip, port))
+}
+
+/// The `key=value` pairs of a message, typed, plus the trailing
+/// `status=... (detail)` text.
+fn fields(text: &str, out: &mut Map<String, Value>) {
+    let (text, detail) = match text.find("status=").and_then(|i| {
+        let open = text[i..].find(" (")? + i;
+        text.ends_with(')')
+            .then(|| (&text[..open], &text[open + 2..text.len() - 1]))
+    }) {
+        Some((text, detail)) => (text, Some(detail)),
+        None => (text, None),
+    };
+
+    for tok in text.split_ascii_whitespace() {
+        let tok = tok.trim_end_matches([',', ';']);
+        let Some((key, v)) = tok.split_once('=') else {
+            continue;
+        };
+        if key.is_empty()
+            || !key
+                .bytes()
+                .all(|b| b.is_ascii_lowercase() || b == b'-' || b == b'_')
+        {
+            continue;
+        }
+        let key = key.replace('-', "_");
+
+        let value = match key.as_str() {
+            "size" | "nrcpt" => v.parse::<i64>().map(Value::from).ok(),
+            "delay" => v.parse::<f64>().ok().map(Value::from),
+            k if ADDRESS_KEYS.contains(&k) => Some(Value::from(
+                v.strip_prefix('<')
+                    .and_then(|a| a.strip_suffix('>'))
+                    .unwrap_or(v),
+            )),
+            _ => None,
+        };
+
+        if let ("client" | "relay", Some((host, ip, port))) = (key.as_str(), split_bracketed(v)) {
+            out.insert(format!("{key}_host"), host.into());
+            out.insert(format!("{key}_ip"), ip.into());
+            if let Some(port) = port {
+                out.insert(format!("{key}_port"), port.into());
+            }
+        }
+        out.insert(key, value.unwrap_or_else(|| v.into()));
+    }
+
+    if let Some(detail) = detail {
+        out.insert("status_detail".into(), detail.into());
+    }
+}
+
+fn line_record(l: &MailLine<'_>) -> Map<String, Value> {
+    let mut rec = Map::new();
+    rec.insert("syslog_timestamp".into(), l.syslog.syslog_timestamp.into());
+    rec.insert("host".into(), l.syslog.host.into());
+    rec.insert("program".into(), l.syslog.program.into());
+    rec.insert("pid".into(), l.syslog.pid.into());
+    rec.insert("daemon".into(), l.daemon.into());
+    rec.insert("queue_id".into(), l.queue_id.into());
+    rec.insert("message".into(), l.text.into());
+    fields(l.text, &mut rec);
+    rec
+}
+
+/// One record for all lines of a queue id: sender-side fields merged, one
+/// entry per delivery attempt in `recipients`.
+fn delivery_record(lines: &[MailLine<'_>]) -> Map<String, Value> {
+    let (first, last) = (&lines[0], &lines[lines.len() - 1]);
+    let mut rec = Map::new();
+    rec.insert("queue_id".into(), first.queue_id.into());
+    rec.insert("host".into(), first.syslog.host.into());
+    rec.insert("first_seen".into(), first.syslog.syslog_timestamp.into());
+    rec.insert("last_seen".into(), last.syslog.syslog_timestamp.into());
+
+    let mut daemons: Vec<&str> = Vec::new();
+    let mut recipients = Vec::new();
+    let mut removed = false;
+    for l in lines {
+        if !daemons.contains(&l.daemon) {
+            daemons.push(l.daemon);
+        }
+        removed |= l.text == "removed";
+
+        let mut f = Map::new();
+        fields(l.text, &mut f);
+        if f.contains_key("to") {
+            let mut r = Map::new();
+            r.insert("syslog_timestamp".into(), l.syslog.syslog_timestamp.into());
+            r.insert("daemon".into(), l.daemon.into());
+            for (k, v) in f {
+                if RECIPIENT_KEYS.contains(&k.as_str()) {
+                    r.insert(k, v);
+                } else {
+                    rec.entry(k).or_insert(v);
+                }
+            }
+            recipients.push(Value::Object(r));
+        } else {
+            for (k, v) in f {
+                rec.entry(k).or_insert(v);
+            }
+        }
+    }
+
+    rec.insert("daemons".into(), daemons.into());
+    rec.insert("recipients".into(), recipients.into());
+    rec.insert("removed".into(), removed.into());
+    rec.insert("lines".into(), lines.len().into());
+    rec
+}
+
+impl Parser for Postfix {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("postfix")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses Postfix/sendmail maillog -> JSONL, optional per-queue-id records")
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        OPTIONS
+    }
+
+    fn configure(&mut self, opts: &ModuleOptions) -> Result<()> {
+        if let Some(c) = opts.flag("correlate")? {
+            self.correlate = c;
+        }
+        Ok(())
+    }
+
+    fn line_joiner(&self) -> Option<Box<dyn LineJoiner>> {
+        self.correlate
+            .then(|| Box::new(GroupJoiner::new(queue_key, MAX_PENDING)) as Box<dyn LineJoiner>)
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["client_ip".to_string(), "relay_ip".to_string()]
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let lines: Option<Vec<MailLine>> = line
+            .split('\n')
+            .map(|l| split_mail(l.strip_suffix('\r').unwrap_or(l)))
+            .collect();
+        let rec = match lines.as_deref() {
+            Some([l]) if !self.correlate || l.queue_id.is_none() => line_record(l),
+            Some([]) | None => return false,
+            Some(lines) => delivery_record(lines),
+        };
+
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+/// Groups lines by queue id; Postfix logs `removed` last.
+fn queue_key(line: &str) -> Option<(&str, bool)> {
+    let l = split_mail(line)?;
+    Some((l.queue_id?, l.text == "removed"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::LinePos;
+
+    const LOG: &[&str] = &[
+        "Jan 10 10:00:01 mail postfix/smtpd[1234]: 4F9D41A2B3C: client=unknown[192.0.2.1]",
+        "Jan 10 10:00:01 mail postfix/smtpd[1234]: connect from unknown[192.0.2.1]",
+        "Jan 10 10:00:01 mail postfix/cleanup[1235]: 4F9D41A2B3C: message-id=<abc@example.com>",
+        "Jan 10 10:00:01 mail postfix/qmgr[99]: 4F9D41A2B3C: from=<alice@example.com>, size=1234, nrcpt=2 (queue active)",
+        "Jan 10 10:00:02 mail postfix/smtp[1236]: 4F9D41A2B3C: to=<bob@example.net>, relay=mx.example.net[198.51.100.1]:25, delay=1.2, delays=0.1/0/0.5/0.6, dsn=2.0.0, status=sent (250 2.0.0 Ok: queued as 123)",
+        "Jan 10 10:00:03 mail postfix/smtp[1236]: 4F9D41A2B3C: to=<carol@example.org>, relay=none, delay=2, delays=0.1/0/2/0, dsn=4.4.1, status=deferred (connect to example.org[203.0.113.9]:25: Connection refused)",
+        "Jan 10 10:00:04 mail postfix/qmgr[99]: 4F9D41A2B3C: removed",
+    ];
+
+    fn run(p: &Postfix, line: &str) -> Option<Value> {
+        let mut out = Vec::new();
+        p.process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn parses_single_lines() {
+        let p = Postfix { correlate: false };
+        let v = run(&p, LOG[4]).unwrap();
+        assert_eq!(v["daemon"], "smtp");
+        assert_eq!(v["queue_id"], "4F9D41A2B3C");
+        assert_eq!(v["to"], "bob@example.net");
+        assert_eq!(v["relay_host"], "mx.example.net");
+        assert_eq!(v["relay_ip"], "198.51.100.1");
+        assert_eq!(v["relay_port"], 25);
+        assert_eq!(v["delay"], 1.2);
+        assert_eq!(v["dsn"], "2.0.0");
+        assert_eq!(v["status"], "sent");
+        assert_eq!(v["status_detail"], "250 2.0.0 Ok: queued as 123");
+
+        let v = run(&p, LOG[3]).unwrap();
+        assert_eq!(v["from"], "alice@example.com");
+        assert_eq!(v["size"], 1234);
+
+        let v = run(&p, LOG[1]).unwrap();
+        assert!(v["queue_id"].is_null());
+        assert_eq!(v["message"], "connect from unknown[192.0.2.1]");
+
+        let v = run(&p, "Jan 10 10:00:05 mail postfix/smtpd[1234]: NOQUEUE: reject: RCPT from unknown[192.0.2.7]: 554 5.7.1 Relay access denied; from=<x@y.z> to=<a@b.c> proto=ESMTP helo=<evil>").unwrap();
+        assert!(v["queue_id"].is_null());
+        assert_eq!(v["to"], "a@b.c");
+        assert_eq!(v["helo"], "<evil>");
+
+        assert!(run(&p, "Jan 10 10:00:05 mail sshd[1]: Accepted password for x").is_none());
+    }
+
+    #[test]
+    fn correlates_lines_by_queue_id() {
+        let mut p = Postfix { correlate: false };
+        p.configure(&ModuleOptions::parse(&["correlate=true"]).unwrap())
+            .unwrap();
+
+        let mut j = p.line_joiner().unwrap();
+        let mut records = Vec::new();
+        for l in LOG {
+            j.push(l.as_bytes(), LinePos::default(), &mut |r, _| {
+                records.push(String::from_utf8(r.to_vec()).unwrap())
+            });
+        }
+        j.finish(&mut |r, _| records.push(String::from_utf8(r.to_vec()).unwrap()));
+        assert_eq!(records.len(), 2);
+
+        let connect = run(&p, &records[0]).unwrap();
+        assert_eq!(connect["message"], "connect from unknown[192.0.2.1]");
+
+        let v = run(&p, &records[1]).unwrap();
+        assert_eq!(v["queue_id"], "4F9D41A2B3C");
+        assert_eq!(v["first_seen"], "Jan 10 10:00:01");
+        assert_eq!(v["last_seen"], "Jan 10 10:00:04");
+        assert_eq!(v["client_ip"], "192.0.2.1");
+        assert_eq!(v["message_id"], "abc@example.com");
+        assert_eq!(v["from"], "alice@example.com");
+        assert_eq!(v["nrcpt"], 2);
+        assert_eq!(v["recipients"][0]["to"], "bob@example.net");
+        assert_eq!(v["recipients"][0]["status"], "sent");
+        assert_eq!(v["recipients"][1]["status"], "deferred");
+        assert_eq!(v["removed"], true);
+        assert_eq!(v["lines"], 6);
+        assert_eq!(
+            v["daemons"],
+            serde_json::json!(["smtpd", "cleanup", "qmgr", "smtp"])
+        );
+    }
+}
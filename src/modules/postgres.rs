@@ -0,0 +1,523 @@
+use crate::core::{BlockJoiner, Boundary, LineJoiner, ModuleOptions, OptionSpec, Parser};
+use crate::modules::common::epoch_to_rfc3339;
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use time::{
+    format_description::well_known::Rfc3339, macros::format_description, PrimitiveDateTime,
+    UtcOffset,
+};
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(Postgres {
+        prefix: prefix_regex(DEFAULT_PREFIX).expect("default log_line_prefix"),
+        format: Format::Auto,
+    })
+}
+
+/// PostgreSQL server logs: the default `stderr` destination, whose lines
+/// start with `log_line_prefix` (given as the `prefix` option), and `csvlog`.
+/// Multi-line messages (statements, quoted CSV fields) are joined first.
+pub struct Postgres {
+    prefix: Regex,
+    format: Format,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// Per record: CSV when it starts like a csvlog row, else stderr.
+    Auto,
+    Stderr,
+    Csv,
+}
+
+/// PostgreSQL's own default since 10.
+const DEFAULT_PREFIX: &str = "%m [%p] ";
+
+const OPTIONS: &[OptionSpec] = &[
+    OptionSpec {
+        key: "prefix",
+        help: "log_line_prefix of stderr logs, e.g. '%m [%p] %q%u@%d ' (default: '%m [%p] ')",
+    },
+    OptionSpec {
+        key: "format",
+        help: "stderr, csv or auto (default: auto)",
+    },
+];
+
+const MAX_LINES: usize = 10_000;
+
+/// csvlog columns in order (PostgreSQL 14+; older versions stop earlier).
+const CSV_COLUMNS: &[&str] = &[
+    "ts",
+    "user",
+    "database",
+    "pid",
+    "connection_from",
+    "session_id",
+    "session_line",
+    "command_tag",
+    "session_start",
+    "vxid",
+    "xid",
+    "level",
+    "sqlstate",
+    "message",
+    "detail",
+    "hint",
+    "internal_query",
+    "internal_query_pos",
+    "context",
+    "query",
+    "query_pos",
+    "location",
+    "application",
+    "backend_type",
+    "leader_pid",
+    "query_id",
+];
+
+const INT_FIELDS: &[&str] = &[
+    "pid",
+    "leader_pid",
+    "session_line",
+    "xid",
+    "internal_query_pos",
+    "query_pos",
+    "query_id",
+    "remote_port",
+];
+
+const LEVELS: &str =
+    "DEBUG[1-5]|INFO|NOTICE|WARNING|ERROR|LOG|FATAL|PANIC|DETAIL|HINT|QUERY|CONTEXT|LOCATION|STATEMENT";
+
+/// Translate a `log_line_prefix` into a regex capturing its escapes, followed
+/// by `LEVEL:  message`. Everything after `%q` is optional, as in the server.
+fn prefix_regex(prefix: &str) -> Result<Regex> {
+    let mut re = String::from("^");
+    let mut optional = false;
+    let mut chars = prefix.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            re.push_str(&regex::escape(&c.to_string()));
+            continue;
+        }
+        let esc = chars
+            .next()
+            .context("log_line_prefix ends with a lone '%'")?;
+        let part = match esc {
+            'm' => r"(?P<ts>\d{4}-\d\d-\d\d \d\d:\d\d:\d\d\.\d+(?: [A-Za-z0-9+:\-]+)?)",
+            't' => r"(?P<ts>\d{4}-\d\d-\d\d \d\d:\d\d:\d\d(?: [A-Za-z0-9+:\-]+)?)",
+            'n' => r"(?P<epoch>\d+\.\d+)",
+            'p' => r"(?P<pid>\d+)",
+            'P' => r"(?P<leader_pid>\d*)",
+            'u' => r"(?P<user>\S*?)",
+            'd' => r"(?P<database>\S*?)",
+            'a' => r"(?P<application>.*?)",
+            'r' => r"(?P<remote>\S*?)",
+            'h' => r"(?P<remote_host>\S*?)",
+            'b' => r"(?P<backend_type>.*?)",
+            'c' => r"(?P<session_id>[0-9a-f]+\.[0-9a-f]+)",
+            'l' => r"(?P<session_line>\d+)",
+            's' => r"(?P<session_start>\d{4}-\d\d-\d\d \d\d:\d\d:\d\d(?: [A-Za-z0-9+:\-]+)?)",
+            'v' => r"(?P<vxid>\S*?)",
+            'x' => r"(?P<xid>\d+)",
+            'e' => r"(?P<sqlstate>[0-9A-Z]{5})",
+            'i' => r"(?P<command_tag>.*?)",
+            'Q' => r"(?P<query_id>-?\d+)",
+            '%' => "%",
+            'q' => {
+                re.push_str("(?:");
+                optional = true;
+                continue;
+            }
+            other => bail!("unsupported log_line_prefix escape '%{other}'"),
+        };
+        re.push_str(part);
+    }
+    if optional {
+        re.push_str(")?");
+    }
+    re.push_str(&format!(r"(?P<level>{LEVELS}):\s+(?P<message>.*)$"));
+    Regex::new(&re).context("log_line_prefix")
+}
+
+/// `2024-01-02 03:04:05[.123] <tz>,` — a csvlog row starts with `log_time`.
+fn is_csv_start(line: &str) -> bool {
+    let Some((ts, _)) = line.split_once(',') else {
+        return false;
+    };
+    let b = ts.as_bytes();
+    if b.len() < 19
+        || b[4] != b'-'
+        || b[10] != b' '
+        || b[13] != b':'
+        || !b[..4].iter().all(u8::is_ascii_digit)
+    {
+        return false;
+    }
+    let rest = &ts[19..];
+    let rest = match rest.strip_prefix('.') {
+        Some(r) => r.trim_start_matches(|c: char| c.is_ascii_digit()),
+        None => rest,
+    };
+    rest.is_empty() || rest.strip_prefix(' ').is_some_and(|tz| !tz.contains(' '))
+}
+
+impl Parser for Postgres {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("postgres")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses PostgreSQL stderr (log_line_prefix) and csvlog -> JSONL")
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        OPTIONS
+    }
+
+    fn configure(&mut self, opts: &ModuleOptions) -> Result<()> {
+        if let Some(p) = opts.get("prefix") {
+            self.prefix = prefix_regex(p)?;
+        }
+        if let Some(f) = opts.get("format") {
+            self.format = match f {
+                "auto" => Format::Auto,
+                "stderr" => Format::Stderr,
+                "csv" => Format::Csv,
+                other => bail!("format must be stderr, csv or auto, got '{other}'"),
+            };
+        }
+        Ok(())
+    }
+
+    /// A record starts at a csvlog row (outside an open quoted field) or at
+    /// a line matching the prefix; anything else continues the previous one.
+    fn line_joiner(&self) -> Option<Box<dyn LineJoiner>> {
+        let prefix = self.prefix.clone();
+        let format = self.format;
+        let mut in_quotes = false;
+        let mut csv_record = false;
+        let boundary = move |line: &str| {
+            let quotes = line.bytes().filter(|&b| b == b'"').count();
+            if csv_record && in_quotes {
+                in_quotes = quotes.is_multiple_of(2);
+                return Boundary::Inside;
+            }
+            if format != Format::Stderr && is_csv_start(line) {
+                csv_record = true;
+                in_quotes = !quotes.is_multiple_of(2);
+                return Boundary::Start;
+            }
+            if format != Format::Csv && prefix.is_match(line) {
+                csv_record = false;
+                return Boundary::Start;
+            }
+            Boundary::Inside
+        };
+        Some(Box::new(BlockJoiner::new(boundary, MAX_LINES)))
+    }
+
+    fn process_line_to_buf(&self, record: &str, out: &mut Vec<u8>) -> bool {
+        let csv = match self.format {
+            Format::Auto => is_csv_start(record),
+            f => f == Format::Csv,
+        };
+        let rec = if csv {
+            parse_csv(record)
+        } else {
+            self.parse_stderr(record)
+        };
+        let Some(rec) = rec else {
+            return false;
+        };
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+impl Postgres {
+    fn parse_stderr(&self, record: &str) -> Option<Map<String, Value>> {
+        let (first, continuation) = match record.split_once('\n') {
+            Some((f, c)) => (f, Some(c)),
+            None => (record, None),
+        };
+        let first = first.strip_suffix('\r').unwrap_or(first);
+        let caps = self.prefix.captures(first)?;
+
+        let mut rec = Map::new();
+        for name in self.prefix.capture_names().flatten() {
+            let Some(m) = caps.name(name).filter(|m| !m.as_str().is_empty()) else {
+                continue;
+            };
+            match name {
+                "message" => {}
+                "epoch" => {
+                    if let Some(ts) = epoch_to_rfc3339(m.as_str()) {
+                        rec.insert("ts".into(), ts.into());
+                    }
+                }
+                "remote" => {
+                    // `10.0.0.1(51234)` or `[local]`.
+                    let (host, port) = match m.as_str().strip_suffix(')') {
+                        Some(r) => r.split_once('(').unwrap_or((r, "")),
+                        None => (m.as_str(), ""),
+                    };
+                    insert(&mut rec, "remote_host", host);
+                    insert(&mut rec, "remote_port", port);
+                }
+                _ => insert(&mut rec, name, m.as_str()),
+            }
+        }
+
+        let mut message = caps.name("message").map_or("", |m| m.as_str()).to_string();
+        if let Some(c) = continuation {
+            for line in c.lines() {
+                message.push('\n');
+                message.push_str(line.strip_prefix('\t').unwrap_or(line));
+            }
+        }
+        message_fields(&mut rec, message);
+        Some(rec)
+    }
+}
+
+fn parse_csv(record: &str) -> Option<Map<String, Value>> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(record.as_bytes());
+    let row = rdr.records().next()?.ok()?;
+    if row.len() < 14 {
+        return None;
+    }
+
+    let mut rec = Map::new();
+    let mut message = String::new();
+    for (name, value) in CSV_COLUMNS.iter().zip(row.iter()) {
+        match *name {
+            "message" => message = value.to_string(),
+            "connection_from" => {
+                let (host, port) = value.rsplit_once(':').unwrap_or((value, ""));
+                insert(&mut rec, "remote_host", host);
+                insert(&mut rec, "remote_port", port);
+            }
+            _ if value.is_empty() => {}
+            name => insert(&mut rec, name, value),
+        }
+    }
+    message_fields(&mut rec, message);
+    Some(rec)
+}
+
+/// Typed insert; timestamps are normalized.
+fn insert(rec: &mut Map<String, Value>, name: &str, value: &str) {
+    if value.is_empty() {
+        return;
+    }
+    let v = if INT_FIELDS.contains(&name)
+        && let Ok(n) = value.parse::<i64>()
+    {
+        n.into()
+    } else if name == "ts" || name == "session_start" {
+        normalize_time(value).into()
+    } else {
+        value.into()
+    };
+    rec.insert(name.into(), v);
+}
+
+/// `2024-01-02 03:04:05.123 UTC` -> RFC 3339 in UTC when the zone is UTC/GMT
+/// or numeric, else local ISO 8601 (`2024-01-02T03:04:05.123`).
+fn normalize_time(value: &str) -> String {
+    let (dt, tz) = match value.get(19..) {
+        Some(rest) => {
+            let frac_end = rest.find(' ').unwrap_or(rest.len());
+            (&value[..19 + frac_end], rest[frac_end..].trim())
+        }
+        None => (value, ""),
+    };
+    let offset = match tz {
+        "UTC" | "GMT" | "Z" => Some(UtcOffset::UTC),
+        tz => UtcOffset::parse(
+            tz,
+            &format_description!(
+                "[offset_hour sign:mandatory][optional [[optional [:]][offset_minute]]]"
+            ),
+        )
+        .ok(),
+    };
+    let fmt = format_description!(
+        "[year]-[month]-[day] [hour]:[minute]:[second][optional [.[subsecond]]]"
+    );
+    match (PrimitiveDateTime::parse(dt, &fmt), offset) {
+        (Ok(local), Some(offset)) => local
+            .assume_offset(offset)
+            .to_offset(UtcOffset::UTC)
+            .format(&Rfc3339)
+            .unwrap_or_else(|_| value.to_string()),
+        _ => dt.replacen(' ', "T", 1),
+    }
+}
+
+/// Level-specific extras pulled out of the message: a verbose-mode SQLSTATE
+/// prefix, `duration: N ms` and the logged statement.
+fn message_fields(rec: &mut Map<String, Value>, message: String) {
+    let mut msg = message.as_str();
+    if !rec.contains_key("sqlstate")
+        && let Some((code, rest)) = msg.split_once(": ")
+        && code.len() == 5
+        && code
+            .bytes()
+            .all(|b| b.is_ascii_digit() || b.is_ascii_uppercase())
+    {
+        rec.insert("sqlstate".into(), code.into());
+        msg = rest;
+    }
+
+    let mut rest = msg;
+    if let Some(d) = msg.strip_prefix("duration: ")
+        && let Some((ms, after)) = d.split_once(" ms")
+        && let Ok(ms) = ms.parse::<f64>()
+    {
+        rec.insert("duration_ms".into(), ms.into());
+        rest = after.trim_start();
+    }
+    let statement = if rec.get("level").and_then(Value::as_str) == Some("STATEMENT") {
+        Some(msg)
+    } else {
+        ["statement: ", "execute ", "bind ", "parse "]
+            .iter()
+            .find(|p| rest.starts_with(*p))
+            .and_then(|_| rest.split_once(": "))
+            .map(|(_, s)| s)
+    };
+    if let Some(s) = statement {
+        rec.insert("statement".into(), s.into());
+    }
+    rec.insert("message".into(), msg.into());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::LinePos;
+
+    fn with(opts: &[&str]) -> Box<dyn Parser> {
+        let mut p = new();
+        p.configure(&ModuleOptions::parse(opts).unwrap()).unwrap();
+        p
+    }
+
+    fn records(p: &dyn Parser, input: &str) -> Vec<Option<serde_json::Value>> {
+        let mut j = p.line_joiner().unwrap();
+        let mut joined = Vec::new();
+        for l in input.lines() {
+            j.push(l.as_bytes(), LinePos::default(), &mut |r, _| {
+                joined.push(r.to_vec())
+            });
+        }
+        j.finish(&mut |r, _| joined.push(r.to_vec()));
+        joined
+            .iter()
+            .map(|r| {
+                let mut out = Vec::new();
+                p.process_line_to_buf(std::str::from_utf8(r).unwrap(), &mut out)
+                    .then(|| serde_json::from_slice(&out).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn default_prefix_with_duration_and_continuation() {
+        let p = new();
+        let recs = records(
+            &*p,
+            "2024-01-02 03:04:05.123 UTC [1234] LOG:  duration: 12.345 ms  statement: SELECT *\n\tFROM t\n2024-01-02 03:04:06.000 +01 [1235] ERROR:  relation \"x\" does not exist at character 15\n",
+        );
+        assert_eq!(recs.len(), 2);
+
+        let v = recs[0].as_ref().unwrap();
+        assert_eq!(v["ts"], "2024-01-02T03:04:05.123Z");
+        assert_eq!(v["pid"], 1234);
+        assert_eq!(v["level"], "LOG");
+        assert_eq!(v["duration_ms"], 12.345);
+        assert_eq!(v["statement"], "SELECT *\nFROM t");
+
+        let v = recs[1].as_ref().unwrap();
+        assert_eq!(v["ts"], "2024-01-02T02:04:06Z");
+        assert_eq!(v["level"], "ERROR");
+        assert!(v.get("statement").is_none());
+    }
+
+    #[test]
+    fn custom_prefix() {
+        let p = with(&["prefix=%t [%p]: [%l-1] %qu=%u,db=%d,app=%a,client=%r %e "]);
+        let recs = records(
+            &*p,
+            "2024-01-02 03:04:05 CET [42]: [7-1] u=alice,db=shop,app=psql,client=10.0.0.1(51234) 42P01 ERROR:  relation \"x\" does not exist\n2024-01-02 03:04:05 CET [42]: [8-1] u=alice,db=shop,app=psql,client=10.0.0.1(51234) 42P01 STATEMENT:  SELECT * FROM x;\n2024-01-02 03:04:05 CET [7]: [1-1] LOG:  checkpoint starting: time\n",
+        );
+        let v = recs[0].as_ref().unwrap();
+        assert_eq!(v["ts"], "2024-01-02T03:04:05");
+        assert_eq!(v["session_line"], 7);
+        assert_eq!(v["user"], "alice");
+        assert_eq!(v["database"], "shop");
+        assert_eq!(v["application"], "psql");
+        assert_eq!(v["remote_host"], "10.0.0.1");
+        assert_eq!(v["remote_port"], 51234);
+        assert_eq!(v["sqlstate"], "42P01");
+
+        let v = recs[1].as_ref().unwrap();
+        assert_eq!(v["statement"], "SELECT * FROM x;");
+
+        let v = recs[2].as_ref().unwrap();
+        assert_eq!(v["pid"], 7);
+        assert!(v.get("user").is_none());
+        assert_eq!(v["message"], "checkpoint starting: time");
+    }
+
+    #[test]
+    fn csvlog_with_multiline_fields() {
+        let p = new();
+        let recs = records(
+            &*p,
+            "2024-01-02 03:04:05.123 UTC,\"alice\",\"shop\",1234,\"10.0.0.1:51234\",65938f2d.4d2,3,\"SELECT\",2024-01-02 03:00:00 UTC,3/17,0,LOG,00000,\"duration: 1.5 ms  statement: SELECT 1,\n  2\",,,,,,,,,\"psql\",\"client backend\",,0\n2024-01-02 03:04:06.000 UTC,,,99,,65938f2d.63,1,,2024-01-02 03:00:00 UTC,,0,ERROR,42601,\"syntax error\",,,,,,,,,\"\"\n",
+        );
+        assert_eq!(recs.len(), 2);
+
+        let v = recs[0].as_ref().unwrap();
+        assert_eq!(v["ts"], "2024-01-02T03:04:05.123Z");
+        assert_eq!(v["user"], "alice");
+        assert_eq!(v["database"], "shop");
+        assert_eq!(v["pid"], 1234);
+        assert_eq!(v["remote_port"], 51234);
+        assert_eq!(v["session_start"], "2024-01-02T03:00:00Z");
+        assert_eq!(v["sqlstate"], "00000");
+        assert_eq!(v["duration_ms"], 1.5);
+        assert_eq!(v["statement"], "SELECT 1,\n  2");
+        assert_eq!(v["application"], "psql");
+        assert_eq!(v["backend_type"], "client backend");
+
+        let v = recs[1].as_ref().unwrap();
+        assert_eq!(v["level"], "ERROR");
+        assert_eq!(v["sqlstate"], "42601");
+        assert!(v.get("user").is_none());
+    }
+
+    #[test]
+    fn bad_options_and_lines() {
+        let mut p = new();
+        assert!(p
+            .configure(&ModuleOptions::parse(&["prefix=%m %z"]).unwrap())
+            .is_err());
+        assert!(p
+            .configure(&ModuleOptions::parse(&["format=xml"]).unwrap())
+            .is_err());
+        assert_eq!(records(&*new(), "hello\n"), vec![None]);
+    }
+}
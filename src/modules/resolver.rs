@@ -0,0 +1,271 @@
+use crate::core::Parser;
+use crate::modules::common::epoch_to_rfc3339;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::net::IpAddr;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime, UtcOffset};
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(Resolver)
+}
+
+/// dnsmasq (`log-queries`) and Unbound (`log-queries`, `log-replies`) lines,
+/// through syslog or their own log files, as one DNS record shape so both
+/// can go through the same filters and lookups.
+pub struct Resolver;
+
+/// One query, forward or answer. Fields a line doesn't carry are `null`.
+#[derive(Serialize, Default)]
+struct Record<'a> {
+    ts: Option<String>,
+    /// BSD syslog stamp as written (`Jan 20 10:11:12`, no year).
+    syslog_timestamp: Option<&'a str>,
+    host: Option<&'a str>,
+    resolver: &'a str,
+    event: &'a str,
+    /// dnsmasq `log-queries=extra` serial tying a query to its replies.
+    id: Option<u64>,
+    qname: Option<&'a str>,
+    qtype: Option<&'a str>,
+    qclass: Option<&'a str>,
+    client: Option<&'a str>,
+    client_port: Option<u16>,
+    upstream: Option<&'a str>,
+    answer: Option<&'a str>,
+    rcode: Option<&'a str>,
+    /// Unbound `log-replies`: seconds spent, served from cache, reply size.
+    duration: Option<f64>,
+    cached: Option<bool>,
+    size: Option<u64>,
+}
+
+/// Split `[<PRI>]STAMP [host] program[pid]: message` (`STAMP` being a BSD
+/// syslog stamp, RFC 3339, or Unbound's `[1705745472]`) for the two
+/// resolvers. The host is optional: dnsmasq's `log-facility` file and
+/// Unbound's `log-time-ascii` leave it out.
+fn split(line: &str) -> Option<(Record<'_>, &str)> {
+    let mut rec = Record::default();
+    let mut s = line;
+    if let Some(rest) = s.strip_prefix('<') {
+        let (pri, rest) = rest.split_once('>')?;
+        if !pri.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        s = rest;
+    }
+    let b = s.as_bytes();
+    if let Some(rest) = s.strip_prefix('[') {
+        let (epoch, rest) = rest.split_once("] ")?;
+        rec.ts = Some(epoch_to_rfc3339(epoch)?);
+        s = rest;
+    } else if b.len() > 16 && b[3] == b' ' && b[6] == b' ' && b[9] == b':' && b[12] == b':' {
+        rec.syslog_timestamp = Some(&s[..15]);
+        s = &s[16..];
+    } else {
+        let (stamp, rest) = s.split_once(' ')?;
+        let t = OffsetDateTime::parse(stamp, &Rfc3339).ok()?;
+        rec.ts = t.to_offset(UtcOffset::UTC).format(&Rfc3339).ok();
+        s = rest;
+    }
+
+    let is_tag = |t: &str| t.starts_with("dnsmasq") || t.starts_with("unbound");
+    let (first, rest) = s.split_once(' ')?;
+    let (tag, rest) = if is_tag(first) {
+        (first, rest)
+    } else {
+        rec.host = Some(first);
+        rest.split_once(' ')?
+    };
+    rec.resolver = match tag.split(['[', ':']).next()? {
+        "dnsmasq" => "dnsmasq",
+        "unbound" => "unbound",
+        _ => return None,
+    };
+    // Unbound's `[pid:thread]` follows the tag under syslog.
+    let rest = match rest.strip_prefix('[') {
+        Some(r) => r.split_once("] ")?.1,
+        None => rest,
+    };
+    Some((rec, rest))
+}
+
+/// `query[A] example.com from 192.168.1.10`, `forwarded example.com to
+/// 1.1.1.1`, `reply example.com is 93.184.216.34`, `cached ... is
+/// NXDOMAIN`, `/etc/hosts ... is ...`; `log-queries=extra` prefixes
+/// `<serial> <client>/<port> `.
+fn dnsmasq<'a>(rec: &mut Record<'a>, message: &'a str) -> Option<()> {
+    let mut message = message;
+    if let [id, addr, rest] = message.splitn(3, ' ').collect::<Vec<_>>()[..]
+        && let Ok(id) = id.parse::<u64>()
+        && let Some((client, port)) = addr.rsplit_once('/')
+    {
+        rec.id = Some(id);
+        rec.client = Some(client);
+        rec.client_port = port.parse().ok();
+        message = rest;
+    }
+    let [event, name, connector, value] = message.splitn(4, ' ').collect::<Vec<_>>()[..] else {
+        return None;
+    };
+    let (event, qtype) = match event.split_once('[') {
+        Some((e, t)) => (e, Some(t.strip_suffix(']')?)),
+        None => (event, None),
+    };
+    rec.event = if event.starts_with('/') {
+        "hosts"
+    } else {
+        event
+    };
+    rec.qtype = qtype;
+    rec.qname = Some(name.trim_end_matches('.'));
+    match connector {
+        "from" => rec.client = Some(value),
+        "to" => rec.upstream = Some(value),
+        "is" => match value {
+            "NXDOMAIN" | "SERVFAIL" | "REFUSED" => rec.rcode = Some(value),
+            "NODATA" | "NODATA-IPv4" | "NODATA-IPv6" => rec.rcode = Some("NOERROR"),
+            _ => {
+                rec.rcode = Some("NOERROR");
+                rec.answer = Some(value);
+            }
+        },
+        _ => return None,
+    }
+    Some(())
+}
+
+/// `info: 192.168.1.10 example.com. A IN`, with `NOERROR 0.000123 0 45`
+/// appended by `log-replies`.
+fn unbound<'a>(rec: &mut Record<'a>, message: &'a str) -> Option<()> {
+    let tokens: Vec<&str> = message.strip_prefix("info: ")?.split(' ').collect();
+    let (client, port) = match tokens.first()?.split_once('@') {
+        Some((ip, port)) => (ip, port.parse().ok()),
+        None => (tokens[0], None),
+    };
+    client.parse::<IpAddr>().ok()?;
+    rec.client = Some(client);
+    rec.client_port = port;
+    rec.qname = Some(tokens.get(1)?.trim_end_matches('.'));
+    rec.qtype = Some(tokens.get(2)?);
+    rec.qclass = Some(tokens.get(3)?);
+    match tokens.len() {
+        4 => rec.event = "query",
+        8 => {
+            rec.event = "reply";
+            rec.rcode = Some(tokens[4]);
+            rec.duration = tokens[5].parse().ok();
+            rec.cached = Some(tokens[6] == "1");
+            rec.size = tokens[7].parse().ok();
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+impl Parser for Resolver {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("resolver")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses dnsmasq and Unbound query logs -> shared DNS JSONL")
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["client".to_string()]
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let Some((mut rec, message)) = split(line) else {
+            return false;
+        };
+        let parsed = match rec.resolver {
+            "dnsmasq" => dnsmasq(&mut rec, message),
+            _ => unbound(&mut rec, message),
+        };
+        if parsed.is_none() {
+            return false;
+        }
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn run(line: &str) -> Option<Value> {
+        let mut out = Vec::new();
+        Resolver
+            .process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn parses_dnsmasq_lines() {
+        let v = run("Jan 20 10:11:12 dnsmasq[1234]: query[AAAA] www.example.com from 192.168.1.10")
+            .unwrap();
+        assert_eq!(v["syslog_timestamp"], "Jan 20 10:11:12");
+        assert_eq!(v["host"], Value::Null);
+        assert_eq!(v["resolver"], "dnsmasq");
+        assert_eq!(v["event"], "query");
+        assert_eq!(v["qtype"], "AAAA");
+        assert_eq!(v["qname"], "www.example.com");
+        assert_eq!(v["client"], "192.168.1.10");
+
+        let v = run("Jan 20 10:11:12 router dnsmasq[1234]: 7 192.168.1.10/53422 reply www.example.com is 93.184.216.34").unwrap();
+        assert_eq!(v["host"], "router");
+        assert_eq!(v["id"], 7);
+        assert_eq!(v["client_port"], 53422);
+        assert_eq!(v["event"], "reply");
+        assert_eq!(v["answer"], "93.184.216.34");
+        assert_eq!(v["rcode"], "NOERROR");
+
+        let v = run("Jan 20 10:11:12 dnsmasq[1234]: forwarded example.com to 1.1.1.1").unwrap();
+        assert_eq!(v["upstream"], "1.1.1.1");
+        let v = run("Jan 20 10:11:12 dnsmasq[1234]: cached nope.example is NXDOMAIN").unwrap();
+        assert_eq!(
+            (&v["event"], &v["rcode"], &v["answer"]),
+            (&"cached".into(), &"NXDOMAIN".into(), &Value::Null)
+        );
+        let v = run("Jan 20 10:11:12 dnsmasq[1234]: /etc/hosts nas.lan is 192.168.1.2").unwrap();
+        assert_eq!(v["event"], "hosts");
+        assert!(
+            run("Jan 20 10:11:12 dnsmasq[1234]: started, version 2.89 cachesize 150").is_none()
+        );
+        assert!(
+            run("Jan 20 10:11:12 dnsmasq-dhcp[1234]: DHCPACK(eth0) 192.168.1.10 aa:bb").is_none()
+        );
+    }
+
+    #[test]
+    fn parses_unbound_lines() {
+        let v =
+            run("[1705745472] unbound[1234:0] info: 192.168.1.10 www.example.com. A IN").unwrap();
+        assert_eq!(v["ts"], "2024-01-20T10:11:12Z");
+        assert_eq!(v["resolver"], "unbound");
+        assert_eq!(v["event"], "query");
+        assert_eq!(v["qname"], "www.example.com");
+        assert_eq!(v["qtype"], "A");
+        assert_eq!(v["qclass"], "IN");
+
+        let v = run("Jan 20 10:11:12 ns1 unbound: [1234:0] info: 192.168.1.10@53422 www.example.com. A IN NXDOMAIN 0.012345 0 110").unwrap();
+        assert_eq!(v["host"], "ns1");
+        assert_eq!(v["client_port"], 53422);
+        assert_eq!(v["event"], "reply");
+        assert_eq!(v["rcode"], "NXDOMAIN");
+        assert_eq!(v["duration"], 0.012345);
+        assert_eq!(v["cached"], false);
+        assert_eq!(v["size"], 110);
+        assert!(run("[1705745472] unbound[1234:0] notice: init module 0: validator").is_none());
+        assert!(
+            run("[1705745472] unbound[1234:0] info: start of service (unbound 1.17.1).").is_none()
+        );
+    }
+}
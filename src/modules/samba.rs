@@ -0,0 +1,226 @@
+use crate::core::{ModuleOptions, OptionSpec, Parser};
+use crate::modules::common::split_syslog;
+use anyhow::{bail, Result};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime, UtcOffset};
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(Samba {
+        prefix: prefix_fields(DEFAULT_PREFIX).expect("default prefix"),
+    })
+}
+
+/// Samba `vfs_full_audit` syslog lines:
+/// `alice|192.0.2.10|finance|renameat|ok|q4.xlsx|old/q4.xlsx`, i.e. the
+/// `full_audit:prefix` columns, then operation, result and arguments.
+pub struct Samba {
+    /// Field name of each `full_audit:prefix` column.
+    prefix: Vec<&'static str>,
+}
+
+const OPTIONS: &[OptionSpec] = &[OptionSpec {
+    key: "prefix",
+    help: "full_audit:prefix as set in smb.conf, e.g. %u|%I|%m|%S (default: %u|%I|%S)",
+}];
+
+const DEFAULT_PREFIX: &str = "%u|%I|%S";
+
+/// `%u|%I|%S` -> `["user", "ip", "share"]`.
+fn prefix_fields(prefix: &str) -> Result<Vec<&'static str>> {
+    prefix
+        .split('|')
+        .map(|m| {
+            Ok(match m.trim() {
+                "%u" => "user",
+                "%U" => "session_user",
+                "%D" => "domain",
+                "%G" => "group",
+                "%I" => "ip",
+                "%m" => "machine",
+                "%M" => "client_host",
+                "%a" => "client_arch",
+                "%R" => "protocol",
+                "%S" => "share",
+                "%P" => "share_path",
+                "%i" => "server_ip",
+                "%L" => "server_name",
+                "%h" => "server_host",
+                other => bail!("unsupported full_audit:prefix item '{other}'"),
+            })
+        })
+        .collect()
+}
+
+impl Samba {
+    /// `<prefix>|op|ok|args...` or `<prefix>|op|fail (reason)|args...`.
+    fn audit(&self, message: &str, rec: &mut Map<String, Value>) -> Option<()> {
+        let parts: Vec<&str> = message.split('|').collect();
+        let n = self.prefix.len();
+        if parts.len() < n + 2 {
+            return None;
+        }
+        let (op, status, args) = (parts[n], parts[n + 1], &parts[n + 2..]);
+        let error = match status {
+            "ok" => None,
+            s => Some(s.strip_prefix("fail")?.trim()),
+        };
+        if op.is_empty() || op.contains(' ') {
+            return None;
+        }
+        for (key, value) in self.prefix.iter().zip(&parts) {
+            rec.insert(key.to_string(), (*value).into());
+        }
+        rec.insert("op".to_string(), op.into());
+        rec.insert(
+            "result".to_string(),
+            if error.is_some() { "fail" } else { "ok" }.into(),
+        );
+        // `fail (Permission denied)`, `fail (NT_STATUS_ACCESS_DENIED)`.
+        let error = error.map(|e| e.trim_start_matches('(').trim_end_matches(')'));
+        rec.insert("error".to_string(), error.into());
+
+        // `openat|ok|w|file`; `renameat|ok|from|to`; `connect|ok|share`.
+        let (path, target_path, mode) = match (op, args) {
+            ("connect" | "disconnect", _) => (None, None, None),
+            ("open" | "openat", [mode @ ("r" | "w"), path]) => (Some(*path), None, Some(*mode)),
+            ("rename" | "renameat" | "link" | "linkat" | "symlink" | "symlinkat", [from, to]) => {
+                (Some(*from), Some(*to), None)
+            }
+            (_, [.., last]) => (Some(*last), None, None),
+            (_, []) => (None, None, None),
+        };
+        rec.insert("path".to_string(), path.into());
+        if let Some(target) = target_path {
+            rec.insert("target_path".to_string(), target.into());
+        }
+        if let Some(mode) = mode {
+            rec.insert("mode".to_string(), mode.into());
+        }
+        rec.insert("args".to_string(), args.to_vec().into());
+        Some(())
+    }
+}
+
+impl Parser for Samba {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("samba")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses Samba full_audit syslog lines -> file share activity JSONL")
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        OPTIONS
+    }
+
+    fn configure(&mut self, opts: &ModuleOptions) -> Result<()> {
+        if let Some(p) = opts.get("prefix") {
+            self.prefix = prefix_fields(p)?;
+        }
+        Ok(())
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["ip".to_string()]
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let mut rec = Map::new();
+        let message = match split_syslog(line) {
+            Some((syslog, message)) if syslog.program.starts_with("smbd") => {
+                // Only an RFC 3339 syslog stamp has a year and a zone.
+                let ts = OffsetDateTime::parse(syslog.syslog_timestamp, &Rfc3339)
+                    .ok()
+                    .and_then(|t| t.to_offset(UtcOffset::UTC).format(&Rfc3339).ok());
+                rec.insert("ts".to_string(), ts.into());
+                if let Ok(Value::Object(fields)) = serde_json::to_value(&syslog) {
+                    rec.extend(fields);
+                }
+                message
+            }
+            Some(_) => return false,
+            None => line,
+        };
+        if self.audit(message, &mut rec).is_none() {
+            return false;
+        }
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(p: &Samba, line: &str) -> Option<Value> {
+        let mut out = Vec::new();
+        p.process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    fn samba() -> Samba {
+        Samba {
+            prefix: prefix_fields(DEFAULT_PREFIX).unwrap(),
+        }
+    }
+
+    #[test]
+    fn parses_default_prefix_lines() {
+        let p = samba();
+        let v = run(
+            &p,
+            "Jan 20 10:11:12 fs1 smbd_audit: alice|192.0.2.10|finance|openat|ok|w|reports/q4.xlsx",
+        )
+        .unwrap();
+        assert_eq!(v["host"], "fs1");
+        assert_eq!(v["program"], "smbd_audit");
+        assert_eq!(v["ts"], Value::Null);
+        assert_eq!(v["user"], "alice");
+        assert_eq!(v["ip"], "192.0.2.10");
+        assert_eq!(v["share"], "finance");
+        assert_eq!(v["op"], "openat");
+        assert_eq!(v["result"], "ok");
+        assert_eq!(v["error"], Value::Null);
+        assert_eq!(v["mode"], "w");
+        assert_eq!(v["path"], "reports/q4.xlsx");
+
+        let v = run(&p, "2024-01-20T10:11:12+01:00 fs1 smbd_audit[812]: bob|192.0.2.11|finance|renameat|ok|q4.xlsx|old/q4.xlsx").unwrap();
+        assert_eq!(v["ts"], "2024-01-20T09:11:12Z");
+        assert_eq!(v["path"], "q4.xlsx");
+        assert_eq!(v["target_path"], "old/q4.xlsx");
+        let v = run(
+            &p,
+            "bob|192.0.2.11|finance|unlinkat|fail (Permission denied)|secret.docx",
+        )
+        .unwrap();
+        assert_eq!(v["result"], "fail");
+        assert_eq!(v["error"], "Permission denied");
+        assert_eq!(v["path"], "secret.docx");
+        let v = run(&p, "bob|192.0.2.11|finance|connect|ok|finance").unwrap();
+        assert_eq!(v["path"], Value::Null);
+        assert_eq!(v["args"], serde_json::json!(["finance"]));
+        assert!(run(&p, "Jan 20 10:11:12 fs1 sshd[1]: a|b|c|d|ok|e").is_none());
+        assert!(run(&p, "bob|192.0.2.11|finance|unlinkat|maybe|x").is_none());
+    }
+
+    #[test]
+    fn custom_prefix() {
+        let mut p = samba();
+        p.configure(&ModuleOptions::parse(&["prefix=%u|%I|%m|%S"]).unwrap())
+            .unwrap();
+        let v = run(&p, "alice|192.0.2.10|ws042|finance|mkdirat|ok|new folder").unwrap();
+        assert_eq!(v["machine"], "ws042");
+        assert_eq!(v["share"], "finance");
+        assert_eq!(v["path"], "new folder");
+        assert!(p
+            .configure(&ModuleOptions::parse(&["prefix=%u|%X"]).unwrap())
+            .is_err());
+    }
+}
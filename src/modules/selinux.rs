@@ -0,0 +1,212 @@
+use crate::core::Parser;
+use crate::modules::auditd::{decode_hex, pairs};
+use crate::modules::common::{epoch_to_rfc3339, split_syslog};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(Selinux)
+}
+
+/// SELinux AVC messages wherever they land: `type=AVC` / `type=USER_AVC`
+/// records in `audit.log`, `audit: type=1400` lines in dmesg or the kernel's
+/// syslog. One record per decision, with the denied permissions as an array
+/// and the source / target types split out of the contexts.
+pub struct Selinux;
+
+/// Unquoted values of these keys are hex-encoded, as in `audit.log`.
+const HEX_KEYS: &[&str] = &["comm", "name", "path", "exe", "cmdline"];
+
+const INT_KEYS: &[&str] = &[
+    "pid", "ppid", "ino", "uid", "gid", "auid", "ses", "sauid", "lport", "fport", "src", "dest",
+    "sport", "dport",
+];
+
+/// `system_u:system_r:httpd_t:s0` -> `httpd_t`.
+fn context_type(context: &str) -> Option<&str> {
+    context.split(':').nth(2)
+}
+
+fn parse(line: &str) -> Option<Map<String, Value>> {
+    let mut rec = Map::new();
+    // `audit.log` records and dmesg lines have no syslog preamble.
+    let body = if line.starts_with('[') || line.starts_with("type=") || line.starts_with("node=") {
+        line
+    } else {
+        match split_syslog(line) {
+            Some((syslog, message)) => {
+                if let Ok(Value::Object(fields)) = serde_json::to_value(&syslog) {
+                    rec.extend(fields);
+                }
+                message
+            }
+            None => line,
+        }
+    };
+
+    let avc_at = body.find("avc:")?;
+    let header = &body[..avc_at];
+    let stamp_at = header.find("audit(")? + "audit(".len();
+    let (stamp, _) = header[stamp_at..].split_once(')')?;
+    let (epoch, serial) = stamp.split_once(':')?;
+    let kind = header
+        .split_once("type=")
+        .and_then(|(_, k)| k.split(' ').next());
+
+    rec.insert("ts".into(), epoch_to_rfc3339(epoch).into());
+    rec.insert(
+        "serial".into(),
+        serial
+            .parse::<u64>()
+            .map_or_else(|_| Value::from(serial), Value::from),
+    );
+    if let Some(node) = header
+        .strip_prefix("node=")
+        .and_then(|n| n.split(' ').next())
+    {
+        rec.insert("node".into(), node.into());
+    }
+    // USER_AVC (1107) comes from a userspace object manager: systemd, dbus.
+    let source = match kind {
+        Some("USER_AVC" | "1107") => "user",
+        _ => "kernel",
+    };
+    rec.insert("source".into(), source.into());
+
+    let (result, rest) = body[avc_at + 4..].trim_start().split_once(' ')?;
+    if !matches!(result, "denied" | "granted") {
+        return None;
+    }
+    rec.insert("result".into(), result.into());
+    let (perms, rest) = rest.trim_start().strip_prefix('{')?.split_once('}')?;
+    let perms: Vec<Value> = perms.split_ascii_whitespace().map(Value::from).collect();
+    rec.insert("permissions".into(), perms.into());
+
+    let rest = rest.trim_start();
+    let rest = rest.strip_prefix("for").unwrap_or(rest);
+    // USER_AVC text sits inside `msg='...'`, possibly followed by ENRICHED
+    // names after a GS byte.
+    let rest = rest.split('\u{1d}').next().unwrap_or(rest);
+    let rest = if source == "user" {
+        rest.trim_end().trim_end_matches('\'')
+    } else {
+        rest
+    };
+    for (key, v, quote) in pairs(rest) {
+        let value = match quote {
+            b'"' | b'\'' => Value::from(v),
+            _ if v == "?" || v == "(null)" => Value::Null,
+            _ if key == "permissive" => Value::from(v == "1"),
+            _ if HEX_KEYS.contains(&key) => {
+                decode_hex(v).map_or_else(|| Value::from(v), Value::from)
+            }
+            _ if INT_KEYS.contains(&key) => v
+                .parse::<i64>()
+                .map_or_else(|_| Value::from(v), Value::from),
+            _ => Value::from(v),
+        };
+        rec.insert(key.to_string(), value);
+        match key {
+            "scontext" => {
+                rec.insert("source_type".into(), context_type(v).into());
+            }
+            "tcontext" => {
+                rec.insert("target_type".into(), context_type(v).into());
+            }
+            _ => {}
+        }
+    }
+    Some(rec)
+}
+
+impl Parser for Selinux {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("selinux")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses SELinux AVC denials (audit.log, dmesg, syslog) -> JSONL")
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        ["saddr", "daddr", "laddr", "faddr"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect()
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let Some(rec) = parse(line) else {
+            return false;
+        };
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(line: &str) -> Option<Value> {
+        let mut out = Vec::new();
+        Selinux
+            .process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn parses_audit_log_avc() {
+        let v = run(r#"type=AVC msg=audit(1705745472.123:456): avc:  denied  { read write } for  pid=1234 comm="httpd" name="index.html" dev="dm-0" ino=12345 scontext=system_u:system_r:httpd_t:s0 tcontext=unconfined_u:object_r:user_home_t:s0 tclass=file permissive=0"#).unwrap();
+        assert_eq!(v["ts"], "2024-01-20T10:11:12.123Z");
+        assert_eq!(v["serial"], 456);
+        assert_eq!(v["source"], "kernel");
+        assert_eq!(v["result"], "denied");
+        assert_eq!(v["permissions"], serde_json::json!(["read", "write"]));
+        assert_eq!(v["pid"], 1234);
+        assert_eq!(v["comm"], "httpd");
+        assert_eq!(v["name"], "index.html");
+        assert_eq!(v["ino"], 12345);
+        assert_eq!(v["scontext"], "system_u:system_r:httpd_t:s0");
+        assert_eq!(v["source_type"], "httpd_t");
+        assert_eq!(v["target_type"], "user_home_t");
+        assert_eq!(v["tclass"], "file");
+        assert_eq!(v["permissive"], false);
+
+        let v = run("node=web1 type=AVC msg=audit(1705745472.123:457): avc:  denied  { execute } for  pid=1 comm=2F62696E2F7368 path=\"/tmp/x\" scontext=a:b:c_t:s0 tcontext=a:b:d_t:s0 tclass=file permissive=1").unwrap();
+        assert_eq!(v["node"], "web1");
+        assert_eq!(v["comm"], "/bin/sh");
+        assert_eq!(v["path"], "/tmp/x");
+        assert_eq!(v["permissive"], true);
+
+        let v = run(r#"type=USER_AVC msg=audit(1705745472.123:458): pid=1 uid=0 auid=4294967295 ses=4294967295 subj=system_u:system_r:init_t:s0 msg='avc:  denied  { status } for auid=1000 uid=0 gid=0 cmdline="" scontext=unconfined_u:unconfined_r:unconfined_t:s0 tcontext=system_u:system_r:init_t:s0 tclass=system permissive=0  exe="/usr/lib/systemd/systemd" sauid=0 hostname=? addr=? terminal=?'"#).unwrap();
+        assert_eq!(v["source"], "user");
+        assert_eq!(v["auid"], 1000);
+        assert_eq!(v["tclass"], "system");
+        assert_eq!(v["exe"], "/usr/lib/systemd/systemd");
+        assert_eq!(v["terminal"], Value::Null);
+    }
+
+    #[test]
+    fn parses_dmesg_and_syslog_lines() {
+        let v = run("[ 1234.567890] audit: type=1400 audit(1705745472.123:459): avc:  denied  { name_connect } for  pid=812 comm=\"php-fpm\" dest=5432 scontext=system_u:system_r:httpd_t:s0 tcontext=system_u:object_r:postgresql_port_t:s0 tclass=tcp_socket permissive=0").unwrap();
+        assert_eq!(v["serial"], 459);
+        assert_eq!(v["permissions"], serde_json::json!(["name_connect"]));
+        assert_eq!(v["dest"], 5432);
+        assert_eq!(v["tclass"], "tcp_socket");
+
+        let v = run("Jan 20 10:11:12 web1 kernel: audit: type=1400 audit(1705745472.123:460): avc:  granted  { setsecparam } for  pid=1 comm=\"load_policy\" scontext=a:b:c_t:s0 tcontext=a:b:d_t:s0 tclass=security").unwrap();
+        assert_eq!(v["host"], "web1");
+        assert_eq!(v["program"], "kernel");
+        assert_eq!(v["result"], "granted");
+        assert!(run("type=SYSCALL msg=audit(1705745472.123:456): arch=c000003e syscall=2 success=no exit=-13").is_none());
+        assert!(run(
+            "Jan 20 10:11:12 web1 kernel: SELinux:  policy capability network_peer_controls=1"
+        )
+        .is_none());
+    }
+}
@@ -0,0 +1,220 @@
+use crate::core::Parser;
+use crate::modules::common::{epoch_to_rfc3339, split_quoted};
+use serde::Serialize;
+use std::borrow::Cow;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime, UtcOffset};
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(Squid)
+}
+
+/// Squid access.log, native format:
+/// `time elapsed client code/status bytes method URL user hierarchy/peer type`,
+/// or the Apache-like "common" emulation (`logformat common`), told apart per line.
+pub struct Squid;
+
+#[derive(Serialize)]
+struct Record<'a> {
+    /// `native` or `common`.
+    format: &'static str,
+    ts: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    elapsed_ms: Option<i64>,
+    client_ip: &'a str,
+    user: Option<&'a str>,
+    /// Squid result code (`TCP_MISS`, `TCP_DENIED`, ...).
+    result_code: Option<&'a str>,
+    status: Option<i64>,
+    bytes: Option<i64>,
+    method: Option<&'a str>,
+    url: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    protocol: Option<&'a str>,
+    /// Hierarchy code (`DIRECT`, `HIER_NONE`, `PARENT_HIT`, ...).
+    hierarchy_code: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    peer_host: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_type: Option<&'a str>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    extra: &'a [Cow<'a, str>],
+}
+
+impl Parser for Squid {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("squid")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses Squid access.log (native or common) -> typed JSONL")
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["client_ip".to_string()]
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let Some(tokens) = split_quoted(line) else {
+            return false;
+        };
+        let Some(rec) = parse_native(&tokens).or_else(|| parse_common(&tokens)) else {
+            return false;
+        };
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+/// `-` means "not available".
+fn opt(v: &str) -> Option<&str> {
+    (v != "-").then_some(v)
+}
+
+/// `10/Oct/2000:13:55:36 -0700` -> RFC 3339 (UTC).
+fn clf_to_rfc3339(value: &str) -> Option<String> {
+    let fmt = time::macros::format_description!(
+        "[day]/[month repr:short]/[year]:[hour]:[minute]:[second] [offset_hour sign:mandatory][offset_minute]"
+    );
+    OffsetDateTime::parse(value, &fmt)
+        .ok()?
+        .to_offset(UtcOffset::UTC)
+        .format(&Rfc3339)
+        .ok()
+}
+
+fn parse_native<'a>(t: &'a [Cow<'a, str>]) -> Option<Record<'a>> {
+    if t.len() < 10 {
+        return None;
+    }
+    let timestamp = epoch_to_rfc3339(&t[0])?;
+    let elapsed_ms = t[1].parse().ok()?;
+    let (result_code, status) = t[3].split_once('/')?;
+    let (hierarchy_code, peer_host) = t[8].split_once('/').unwrap_or((&t[8], "-"));
+
+    Some(Record {
+        format: "native",
+        ts: Some(timestamp),
+        elapsed_ms: Some(elapsed_ms),
+        client_ip: &t[2],
+        user: opt(&t[7]),
+        result_code: Some(result_code),
+        status: status.parse().ok(),
+        bytes: t[4].parse().ok(),
+        method: opt(&t[5]),
+        url: opt(&t[6]),
+        protocol: None,
+        hierarchy_code: opt(hierarchy_code),
+        peer_host: opt(peer_host),
+        content_type: opt(&t[9]),
+        extra: &t[10..],
+    })
+}
+
+/// A Squid result or hierarchy code: `TCP_MISS`, `HIER_NONE`, `DIRECT`, ...
+fn is_code(v: &str) -> bool {
+    !v.is_empty()
+        && v.bytes()
+            .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit() || b == b'_')
+}
+
+/// `client ident user [date tz] "method URL HTTP/v" status bytes code:hierarchy`.
+/// The `code:hierarchy` suffix tells it apart from an Apache combined line.
+fn parse_common<'a>(t: &'a [Cow<'a, str>]) -> Option<Record<'a>> {
+    if t.len() < 9 {
+        return None;
+    }
+    let date = t[3].strip_prefix('[')?;
+    let tz = t[4].strip_suffix(']')?;
+    let mut request = t[5].splitn(3, ' ');
+    let (result_code, hierarchy_code) = t[8].split_once(':')?;
+    if !is_code(result_code) || !(is_code(hierarchy_code) || hierarchy_code == "-") {
+        return None;
+    }
+
+    Some(Record {
+        format: "common",
+        ts: clf_to_rfc3339(&format!("{date} {tz}")),
+        elapsed_ms: None,
+        client_ip: &t[0],
+        user: opt(&t[2]),
+        result_code: Some(result_code),
+        status: t[6].parse().ok(),
+        bytes: t[7].parse().ok(),
+        method: request.next().and_then(opt),
+        url: request.next(),
+        protocol: request.next(),
+        hierarchy_code: opt(hierarchy_code),
+        peer_host: None,
+        content_type: None,
+        extra: &t[9..],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(line: &str) -> Option<serde_json::Value> {
+        let mut out = Vec::new();
+        Squid
+            .process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn parses_native_line() {
+        let v = run("1286536308.779    180 192.168.0.224 TCP_MISS/200 411 GET http://www.google.com/ - DIRECT/209.85.135.104 text/html").unwrap();
+        assert_eq!(v["format"], "native");
+        assert_eq!(v["ts"], "2010-10-08T11:11:48.779Z");
+        assert_eq!(v["elapsed_ms"], 180);
+        assert_eq!(v["client_ip"], "192.168.0.224");
+        assert_eq!(v["result_code"], "TCP_MISS");
+        assert_eq!(v["status"], 200);
+        assert_eq!(v["bytes"], 411);
+        assert_eq!(v["method"], "GET");
+        assert_eq!(v["url"], "http://www.google.com/");
+        assert!(v["user"].is_null());
+        assert_eq!(v["hierarchy_code"], "DIRECT");
+        assert_eq!(v["peer_host"], "209.85.135.104");
+        assert_eq!(v["content_type"], "text/html");
+        assert!(v.get("extra").is_none());
+    }
+
+    #[test]
+    fn parses_denied_connect_with_no_peer() {
+        let v = run("1286536309.000      0 10.0.0.5 TCP_DENIED/403 3650 CONNECT evil.example:443 alice HIER_NONE/- text/html").unwrap();
+        assert_eq!(v["status"], 403);
+        assert_eq!(v["user"], "alice");
+        assert_eq!(v["hierarchy_code"], "HIER_NONE");
+        assert!(v["peer_host"].is_null());
+    }
+
+    #[test]
+    fn falls_back_to_common_format() {
+        let v = run(r#"192.168.0.1 - bob [10/Oct/2000:13:55:36 -0700] "GET http://example.com/ HTTP/1.1" 200 2326 TCP_HIT:HIER_NONE"#).unwrap();
+        assert_eq!(v["format"], "common");
+        assert_eq!(v["ts"], "2000-10-10T20:55:36Z");
+        assert_eq!(v["user"], "bob");
+        assert_eq!(v["method"], "GET");
+        assert_eq!(v["protocol"], "HTTP/1.1");
+        assert_eq!(v["result_code"], "TCP_HIT");
+        assert!(v.get("elapsed_ms").is_none());
+    }
+
+    #[test]
+    fn rejects_other_lines() {
+        assert!(run("hello world").is_none());
+        assert!(
+            run(r#"127.0.0.1 - - [10/Oct/2000:13:55:36 -0700] "GET / HTTP/1.0" 200 2326"#)
+                .is_none()
+        );
+        assert!(
+            run(r#"127.0.0.1 - - [10/Oct/2000:13:55:36 -0700] "GET / HTTP/1.0" 200 2326 "http://example.com/start" "Mozilla/5.0 (X11; Linux x86_64)""#)
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,163 @@
+use crate::core::{ModuleOptions, OptionSpec, Parser};
+use crate::modules::common::flatten_with;
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime, UtcOffset};
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(Suricata::default())
+}
+
+/// Suricata `eve.json`: one event per line. Nested objects (`alert`, `flow`,
+/// `http`, `dns`, `tls`, ...) are flattened to dotted keys, arrays stay as-is.
+#[derive(Default)]
+pub struct Suricata {
+    /// Keep only these `event_type`s (empty = all).
+    event_types: Vec<String>,
+}
+
+const OPTIONS: &[OptionSpec] = &[OptionSpec {
+    key: "event_types",
+    help: "Comma-separated event_type values to keep, e.g. alert,dns (default: all)",
+}];
+
+/// Keys holding Suricata's `2023-01-01T12:00:00.123456+0000` timestamps.
+const TIME_KEYS: &[&str] = &["flow.start", "flow.end", "netflow.start", "netflow.end"];
+
+/// Only what the `event_type` filter needs, so dropped events are never
+/// materialised as a `Value`.
+#[derive(Deserialize)]
+struct Head<'a> {
+    #[serde(borrow)]
+    event_type: Cow<'a, str>,
+}
+
+/// `+0000` offset, no colon -> RFC 3339 in UTC.
+fn normalize_time(value: &str) -> Option<String> {
+    let fmt = time::macros::format_description!(
+        "[year]-[month]-[day]T[hour]:[minute]:[second][optional [.[subsecond]]][offset_hour sign:mandatory][optional [:]][offset_minute]"
+    );
+    OffsetDateTime::parse(value, &fmt)
+        .ok()?
+        .to_offset(UtcOffset::UTC)
+        .format(&Rfc3339)
+        .ok()
+}
+
+/// [`flatten_with`] hook: normalizes the [`TIME_KEYS`] strings.
+fn normalize_times(key: &str, v: Value) -> Value {
+    match v {
+        Value::String(s) if TIME_KEYS.contains(&key) => {
+            Value::String(normalize_time(&s).unwrap_or(s))
+        }
+        v => v,
+    }
+}
+
+impl Parser for Suricata {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("suricata")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Suricata eve.json, flattened to dotted keys, filterable by event_type")
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        OPTIONS
+    }
+
+    fn configure(&mut self, opts: &ModuleOptions) -> Result<()> {
+        if let Some(types) = opts.list("event_types") {
+            self.event_types = types;
+        }
+        Ok(())
+    }
+
+    fn recognizes(&self, line: &str) -> bool {
+        let line = line.trim();
+        line.starts_with('{')
+            && line.contains("\"timestamp\"")
+            && serde_json::from_str::<Head>(line).is_ok()
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["src_ip".to_string(), "dest_ip".to_string()]
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let line = line.trim();
+        let Ok(head) = serde_json::from_str::<Head>(line) else {
+            return false;
+        };
+        if !self.event_types.is_empty() && !self.event_types.iter().any(|t| *t == head.event_type) {
+            return false;
+        }
+        let Ok(Value::Object(mut event)) = serde_json::from_str::<Value>(line) else {
+            return false;
+        };
+        let ts = match event.shift_remove("timestamp") {
+            Some(Value::String(s)) => Value::String(normalize_time(&s).unwrap_or(s)),
+            other => other.unwrap_or(Value::Null),
+        };
+
+        let mut flat = Map::new();
+        flat.insert("ts".to_string(), ts);
+        flatten_with("", event, &[], &mut flat, &normalize_times);
+        if serde_json::to_writer(&mut *out, &flat).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALERT: &str = r#"{"timestamp":"2023-03-01T12:00:00.123456+0100","flow_id":1,"event_type":"alert","src_ip":"10.0.0.1","src_port":4242,"dest_ip":"10.0.0.2","dest_port":80,"proto":"TCP","alert":{"action":"allowed","signature_id":2100498,"signature":"GPL ATTACK_RESPONSE id check returned root","severity":2,"metadata":{"tag":["x"]}},"flow":{"pkts_toserver":3,"start":"2023-03-01T11:59:59.000000+0100"},"http":{}}"#;
+    const DNS: &str = r#"{"timestamp":"2023-03-01T12:00:01.000000+0000","event_type":"dns","dns":{"type":"answer","rrname":"example.com","answers":[{"rdata":"93.184.216.34"}]}}"#;
+
+    fn run(p: &Suricata, line: &str) -> Option<serde_json::Value> {
+        let mut out = Vec::new();
+        p.process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn flattens_and_normalizes_timestamps() {
+        let v = run(&Suricata::default(), ALERT).unwrap();
+        assert_eq!(v["ts"], "2023-03-01T11:00:00.123456Z");
+        assert_eq!(v["flow.start"], "2023-03-01T10:59:59Z");
+        assert_eq!(v["alert.signature_id"], 2100498);
+        assert_eq!(v["alert.metadata.tag"], serde_json::json!(["x"]));
+        assert_eq!(v["flow.pkts_toserver"], 3);
+        assert_eq!(v["http"], serde_json::json!({}));
+
+        let v = run(&Suricata::default(), DNS).unwrap();
+        assert_eq!(v["dns.rrname"], "example.com");
+        assert_eq!(v["dns.answers"][0]["rdata"], "93.184.216.34");
+    }
+
+    #[test]
+    fn filters_by_event_type() {
+        let mut p = Suricata::default();
+        p.configure(&ModuleOptions::parse(&["event_types=dns,tls"]).unwrap())
+            .unwrap();
+
+        assert!(run(&p, ALERT).is_none());
+        assert!(run(&p, DNS).is_some());
+    }
+
+    #[test]
+    fn rejects_other_json() {
+        let p = Suricata::default();
+        assert!(run(&p, r#"{"msg":"hello"}"#).is_none());
+        assert!(run(&p, "not json").is_none());
+        assert!(p.recognizes(DNS));
+        assert!(!p.recognizes(r#"{"event_type":"x"}"#));
+    }
+}
@@ -0,0 +1,614 @@
+use crate::core::Parser;
+use crate::modules::winevt_xml::parse_event;
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(Sysmon)
+}
+
+/// Sysmon events as XML (the same input as `winevt-xml`), mapped by EventID
+/// to a named schema: `event_type` plus the event's `Data` fields as
+/// snake_case keys (`CommandLine` -> `command_line`), `UtcTime` as `ts`,
+/// `Hashes` split per algorithm, numbers and booleans typed. Events from
+/// other providers are rejected.
+pub struct Sysmon;
+
+const PROVIDER: &str = "Microsoft-Windows-Sysmon";
+
+/// EventID -> (`event_type`, `Data` names in schema order). Every listed
+/// field is present in the output (`null` when missing); unlisted ones
+/// from newer schemas are appended.
+const SCHEMAS: &[(u64, &str, &[&str])] = &[
+    (
+        1,
+        "process_create",
+        &[
+            "ProcessGuid",
+            "ProcessId",
+            "Image",
+            "FileVersion",
+            "Description",
+            "Product",
+            "Company",
+            "OriginalFileName",
+            "CommandLine",
+            "CurrentDirectory",
+            "User",
+            "LogonGuid",
+            "LogonId",
+            "TerminalSessionId",
+            "IntegrityLevel",
+            "Hashes",
+            "ParentProcessGuid",
+            "ParentProcessId",
+            "ParentImage",
+            "ParentCommandLine",
+            "ParentUser",
+        ],
+    ),
+    (
+        2,
+        "file_create_time",
+        &[
+            "ProcessGuid",
+            "ProcessId",
+            "Image",
+            "TargetFilename",
+            "CreationUtcTime",
+            "PreviousCreationUtcTime",
+            "User",
+        ],
+    ),
+    (
+        3,
+        "network_connect",
+        &[
+            "ProcessGuid",
+            "ProcessId",
+            "Image",
+            "User",
+            "Protocol",
+            "Initiated",
+            "SourceIsIpv6",
+            "SourceIp",
+            "SourceHostname",
+            "SourcePort",
+            "SourcePortName",
+            "DestinationIsIpv6",
+            "DestinationIp",
+            "DestinationHostname",
+            "DestinationPort",
+            "DestinationPortName",
+        ],
+    ),
+    (
+        4,
+        "service_state_change",
+        &["State", "Version", "SchemaVersion"],
+    ),
+    (
+        5,
+        "process_terminate",
+        &["ProcessGuid", "ProcessId", "Image", "User"],
+    ),
+    (
+        6,
+        "driver_load",
+        &[
+            "ImageLoaded",
+            "Hashes",
+            "Signed",
+            "Signature",
+            "SignatureStatus",
+        ],
+    ),
+    (
+        7,
+        "image_load",
+        &[
+            "ProcessGuid",
+            "ProcessId",
+            "Image",
+            "ImageLoaded",
+            "FileVersion",
+            "Description",
+            "Product",
+            "Company",
+            "OriginalFileName",
+            "Hashes",
+            "Signed",
+            "Signature",
+            "SignatureStatus",
+            "User",
+        ],
+    ),
+    (
+        8,
+        "create_remote_thread",
+        &[
+            "SourceProcessGuid",
+            "SourceProcessId",
+            "SourceImage",
+            "TargetProcessGuid",
+            "TargetProcessId",
+            "TargetImage",
+            "NewThreadId",
+            "StartAddress",
+            "StartModule",
+            "StartFunction",
+            "SourceUser",
+            "TargetUser",
+        ],
+    ),
+    (
+        9,
+        "raw_access_read",
+        &["ProcessGuid", "ProcessId", "Image", "Device", "User"],
+    ),
+    (
+        10,
+        "process_access",
+        &[
+            "SourceProcessGUID",
+            "SourceProcessId",
+            "SourceThreadId",
+            "SourceImage",
+            "TargetProcessGUID",
+            "TargetProcessId",
+            "TargetImage",
+            "GrantedAccess",
+            "CallTrace",
+            "SourceUser",
+            "TargetUser",
+        ],
+    ),
+    (
+        11,
+        "file_create",
+        &[
+            "ProcessGuid",
+            "ProcessId",
+            "Image",
+            "TargetFilename",
+            "CreationUtcTime",
+            "User",
+        ],
+    ),
+    (
+        12,
+        "registry_create_delete",
+        &[
+            "EventType",
+            "ProcessGuid",
+            "ProcessId",
+            "Image",
+            "TargetObject",
+            "User",
+        ],
+    ),
+    (
+        13,
+        "registry_value_set",
+        &[
+            "EventType",
+            "ProcessGuid",
+            "ProcessId",
+            "Image",
+            "TargetObject",
+            "Details",
+            "User",
+        ],
+    ),
+    (
+        14,
+        "registry_rename",
+        &[
+            "EventType",
+            "ProcessGuid",
+            "ProcessId",
+            "Image",
+            "TargetObject",
+            "NewName",
+            "User",
+        ],
+    ),
+    (
+        15,
+        "file_create_stream_hash",
+        &[
+            "ProcessGuid",
+            "ProcessId",
+            "Image",
+            "TargetFilename",
+            "CreationUtcTime",
+            "Hash",
+            "Contents",
+            "User",
+        ],
+    ),
+    (
+        16,
+        "config_change",
+        &["Configuration", "ConfigurationFileHash"],
+    ),
+    (
+        17,
+        "pipe_created",
+        &[
+            "EventType",
+            "ProcessGuid",
+            "ProcessId",
+            "PipeName",
+            "Image",
+            "User",
+        ],
+    ),
+    (
+        18,
+        "pipe_connected",
+        &[
+            "EventType",
+            "ProcessGuid",
+            "ProcessId",
+            "PipeName",
+            "Image",
+            "User",
+        ],
+    ),
+    (
+        19,
+        "wmi_filter",
+        &[
+            "EventType",
+            "Operation",
+            "User",
+            "EventNamespace",
+            "Name",
+            "Query",
+        ],
+    ),
+    (
+        20,
+        "wmi_consumer",
+        &[
+            "EventType",
+            "Operation",
+            "User",
+            "Name",
+            "Type",
+            "Destination",
+        ],
+    ),
+    (
+        21,
+        "wmi_binding",
+        &["EventType", "Operation", "User", "Consumer", "Filter"],
+    ),
+    (
+        22,
+        "dns_query",
+        &[
+            "ProcessGuid",
+            "ProcessId",
+            "QueryName",
+            "QueryStatus",
+            "QueryResults",
+            "Image",
+            "User",
+        ],
+    ),
+    (
+        23,
+        "file_delete",
+        &[
+            "ProcessGuid",
+            "ProcessId",
+            "User",
+            "Image",
+            "TargetFilename",
+            "Hashes",
+            "IsExecutable",
+            "Archived",
+        ],
+    ),
+    (
+        24,
+        "clipboard_change",
+        &[
+            "ProcessGuid",
+            "ProcessId",
+            "Image",
+            "Session",
+            "ClientInfo",
+            "Hashes",
+            "Archived",
+            "User",
+        ],
+    ),
+    (
+        25,
+        "process_tampering",
+        &["ProcessGuid", "ProcessId", "Image", "Type", "User"],
+    ),
+    (
+        26,
+        "file_delete_detected",
+        &[
+            "ProcessGuid",
+            "ProcessId",
+            "User",
+            "Image",
+            "TargetFilename",
+            "Hashes",
+            "IsExecutable",
+        ],
+    ),
+    (
+        27,
+        "file_block_executable",
+        &[
+            "ProcessGuid",
+            "ProcessId",
+            "User",
+            "Image",
+            "TargetFilename",
+            "Hashes",
+        ],
+    ),
+    (
+        28,
+        "file_block_shredding",
+        &[
+            "ProcessGuid",
+            "ProcessId",
+            "User",
+            "Image",
+            "TargetFilename",
+            "Hashes",
+            "IsExecutable",
+        ],
+    ),
+    (
+        29,
+        "file_executable_detected",
+        &[
+            "ProcessGuid",
+            "ProcessId",
+            "User",
+            "Image",
+            "TargetFilename",
+            "Hashes",
+        ],
+    ),
+    (255, "error", &["ID", "Description"]),
+];
+
+const INT_DATA: &[&str] = &[
+    "ProcessId",
+    "ParentProcessId",
+    "TerminalSessionId",
+    "SourcePort",
+    "DestinationPort",
+    "SourceProcessId",
+    "TargetProcessId",
+    "SourceThreadId",
+    "NewThreadId",
+];
+
+const BOOL_DATA: &[&str] = &[
+    "Initiated",
+    "SourceIsIpv6",
+    "DestinationIsIpv6",
+    "Signed",
+    "IsExecutable",
+    "Archived",
+];
+
+/// `SourceProcessGUID` -> `source_process_guid`, `SourceIsIpv6` ->
+/// `source_is_ipv6`.
+fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut out = String::with_capacity(name.len() + 4);
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_lower = chars.get(i + 1).is_some_and(|n| n.is_ascii_lowercase());
+            if prev.is_ascii_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_ascii_uppercase() && next_lower)
+            {
+                out.push('_');
+            }
+        }
+        out.push(c.to_ascii_lowercase());
+    }
+    out
+}
+
+/// Output key of a `Data` name. Sysmon's own `EventType` (`SetValue`,
+/// `CreatePipe`, ...) would clash with the schema's `event_type`.
+fn key(name: &str) -> String {
+    match name {
+        "EventType" => "event_subtype".to_string(),
+        _ => snake_case(name),
+    }
+}
+
+/// Sysmon writes `-` for empty values.
+fn typed(name: &str, value: &str) -> Value {
+    match value {
+        "" | "-" => Value::Null,
+        v if INT_DATA.contains(&name) => v.parse::<u64>().map_or_else(|_| v.into(), Into::into),
+        v if BOOL_DATA.contains(&name) => match v {
+            "true" => true.into(),
+            "false" => false.into(),
+            _ => v.into(),
+        },
+        v => v.into(),
+    }
+}
+
+/// `SHA1=AB..,MD5=CD..,SHA256=EF..,IMPHASH=01..` -> `sha1`, `md5`, ...
+fn split_hashes(hashes: &str, rec: &mut Map<String, Value>) {
+    for pair in hashes.split(',') {
+        if let Some((algo, digest)) = pair.split_once('=') {
+            rec.insert(
+                algo.trim().to_ascii_lowercase(),
+                digest.trim().to_ascii_lowercase().into(),
+            );
+        }
+    }
+}
+
+fn sysmon_event(line: &str) -> Option<Map<String, Value>> {
+    let event = parse_event(line)?;
+    if event.get("provider").and_then(Value::as_str) != Some(PROVIDER) {
+        return None;
+    }
+    let event_id = event.get("event_id")?.as_u64()?;
+    let (event_type, fields) = SCHEMAS
+        .iter()
+        .find(|(id, _, _)| *id == event_id)
+        .map_or(("unknown", &[][..]), |(_, t, f)| (*t, *f));
+    let empty = Map::new();
+    let data = match event.get("event_data") {
+        Some(Value::Object(d)) => d,
+        _ => &empty,
+    };
+    let text = |name: &str| data.get(name).and_then(Value::as_str);
+
+    // `UtcTime` is `2024-01-20 10:11:12.345` in UTC.
+    let ts = text("UtcTime")
+        .filter(|t| t.len() >= 19)
+        .map(|t| format!("{}T{}Z", &t[..10], &t[11..]))
+        .or_else(|| event.get("ts")?.as_str().map(str::to_string));
+
+    let mut rec = Map::new();
+    rec.insert("ts".into(), ts.into());
+    rec.insert("event_id".into(), event_id.into());
+    rec.insert("event_type".into(), event_type.into());
+    for key in ["computer", "record_id"] {
+        rec.insert(key.into(), event.get(key).cloned().unwrap_or(Value::Null));
+    }
+    rec.insert(
+        "rule_name".into(),
+        text("RuleName").map_or(Value::Null, |v| typed("RuleName", v)),
+    );
+    for &name in fields {
+        rec.insert(
+            key(name),
+            text(name).map_or(Value::Null, |v| typed(name, v)),
+        );
+    }
+    for (name, value) in data {
+        if fields.contains(&name.as_str()) || matches!(name.as_str(), "RuleName" | "UtcTime") {
+            continue;
+        }
+        rec.insert(key(name), typed(name, value.as_str().unwrap_or("")));
+    }
+    if let Some(hashes) = text("Hashes").or_else(|| text("Hash")) {
+        split_hashes(hashes, &mut rec);
+    }
+    Some(rec)
+}
+
+impl Parser for Sysmon {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("sysmon")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Sysmon event XML -> flattened JSONL with a schema per EventID")
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["source_ip".to_string(), "destination_ip".to_string()]
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let Some(rec) = sysmon_event(line.trim()) else {
+            return false;
+        };
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(line: &str) -> Option<Value> {
+        let mut out = Vec::new();
+        Sysmon
+            .process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    fn event(id: u32, data: &str) -> String {
+        format!(
+            "<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Microsoft-Windows-Sysmon' Guid='{{5770385F-C22A-43E0-BF4C-06F5698FFBD9}}'/><EventID>{id}</EventID><TimeCreated SystemTime='2024-01-20T10:11:12.9999999Z'/><EventRecordID>42</EventRecordID><Channel>Microsoft-Windows-Sysmon/Operational</Channel><Computer>WS042.corp.local</Computer></System><EventData>{data}</EventData></Event>"
+        )
+    }
+
+    #[test]
+    fn process_create_schema() {
+        let v = run(&event(1, "<Data Name='RuleName'>-</Data><Data Name='UtcTime'>2024-01-20 10:11:12.345</Data><Data Name='ProcessGuid'>{a1}</Data><Data Name='ProcessId'>4242</Data><Data Name='Image'>C:\\Windows\\System32\\cmd.exe</Data><Data Name='CommandLine'>cmd.exe /c whoami</Data><Data Name='User'>CORP\\alice</Data><Data Name='LogonId'>0x3e7</Data><Data Name='Hashes'>SHA1=AA11,MD5=BB22,SHA256=CC33,IMPHASH=DD44</Data><Data Name='ParentProcessId'>1000</Data><Data Name='ParentImage'>C:\\Windows\\explorer.exe</Data>")).unwrap();
+        assert_eq!(v["ts"], "2024-01-20T10:11:12.345Z");
+        assert_eq!(v["event_id"], 1);
+        assert_eq!(v["event_type"], "process_create");
+        assert_eq!(v["computer"], "WS042.corp.local");
+        assert_eq!(v["record_id"], 42);
+        assert_eq!(v["rule_name"], Value::Null);
+        assert_eq!(v["process_id"], 4242);
+        assert_eq!(v["image"], r"C:\Windows\System32\cmd.exe");
+        assert_eq!(v["command_line"], "cmd.exe /c whoami");
+        assert_eq!(v["user"], r"CORP\alice");
+        assert_eq!(v["logon_id"], "0x3e7");
+        assert_eq!(v["parent_process_id"], 1000);
+        assert_eq!(v["parent_image"], r"C:\Windows\explorer.exe");
+        assert_eq!(v["sha256"], "cc33");
+        assert_eq!(v["imphash"], "dd44");
+        // Schema fields the event lacks are still there.
+        assert_eq!(v["parent_command_line"], Value::Null);
+        assert!(v.get("utc_time").is_none());
+    }
+
+    #[test]
+    fn network_registry_and_unknown_fields() {
+        let v = run(&event(3, "<Data Name='UtcTime'>2024-01-20 10:11:12.345</Data><Data Name='ProcessId'>812</Data><Data Name='Protocol'>tcp</Data><Data Name='Initiated'>true</Data><Data Name='SourceIsIpv6'>false</Data><Data Name='SourceIp'>10.0.0.5</Data><Data Name='SourcePort'>50123</Data><Data Name='DestinationIp'>203.0.113.9</Data><Data Name='DestinationPort'>443</Data><Data Name='DestinationPortName'>https</Data>")).unwrap();
+        assert_eq!(v["event_type"], "network_connect");
+        assert_eq!(v["initiated"], true);
+        assert_eq!(v["source_is_ipv6"], false);
+        assert_eq!(v["source_ip"], "10.0.0.5");
+        assert_eq!(v["destination_port"], 443);
+        assert_eq!(v["destination_port_name"], "https");
+
+        let v = run(&event(13, "<Data Name='EventType'>SetValue</Data><Data Name='TargetObject'>HKLM\\Software\\Microsoft\\Windows\\CurrentVersion\\Run\\x</Data><Data Name='Details'>C:\\x.exe</Data><Data Name='SourceProcessGUID'>{b2}</Data>")).unwrap();
+        assert_eq!(v["event_type"], "registry_value_set");
+        assert_eq!(v["event_subtype"], "SetValue");
+        assert_eq!(
+            v["target_object"],
+            r"HKLM\Software\Microsoft\Windows\CurrentVersion\Run\x"
+        );
+        assert_eq!(v["details"], r"C:\x.exe");
+        assert_eq!(v["source_process_guid"], "{b2}");
+        assert_eq!(v["ts"], "2024-01-20T10:11:12.9999999Z");
+    }
+
+    #[test]
+    fn rejects_other_providers() {
+        let security = event(4624, "").replace(
+            "Microsoft-Windows-Sysmon'",
+            "Microsoft-Windows-Security-Auditing'",
+        );
+        assert!(run(&security).is_none());
+        assert!(run("not xml").is_none());
+        assert_eq!(snake_case("SourceProcessGUID"), "source_process_guid");
+        assert_eq!(snake_case("ID"), "id");
+    }
+}
@@ -0,0 +1,241 @@
+use crate::core::{FieldType, ModuleOptions, OptionSpec, Parser};
+use anyhow::Result;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::borrow::Cow;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(VpcFlow::new())
+}
+
+/// AWS VPC Flow Logs, default version 2 layout or a custom field list.
+pub struct VpcFlow {
+    fields: Vec<Field>,
+}
+
+/// The version 2 default format.
+const DEFAULT_FIELDS: &str = "version account-id interface-id srcaddr dstaddr srcport dstport \
+                              protocol packets bytes start end action log-status";
+
+const OPTIONS: &[OptionSpec] = &[OptionSpec {
+    key: "fields",
+    help: "Custom format field list, comma- or space-separated; ${...} is accepted \
+           (default: the version 2 default format)",
+}];
+
+/// Fields emitted as JSON numbers.
+const INT_FIELDS: &[&str] = &[
+    "version",
+    "srcport",
+    "dstport",
+    "protocol",
+    "packets",
+    "bytes",
+    "tcp-flags",
+    "traffic-path",
+    "ecs-container-count",
+];
+
+/// Epoch-second fields emitted as RFC 3339 timestamps.
+const TIME_FIELDS: &[&str] = &["start", "end"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Text,
+    Int,
+    Time,
+}
+
+struct Field {
+    /// JSON key: the AWS name with `-` turned into `_`.
+    key: String,
+    kind: Kind,
+}
+
+impl VpcFlow {
+    fn new() -> Self {
+        Self {
+            fields: parse_field_list(DEFAULT_FIELDS),
+        }
+    }
+
+    /// The S3 delivery header repeats the field names.
+    fn is_header(&self, tokens: &[&str]) -> bool {
+        tokens
+            .first()
+            .zip(self.fields.first())
+            .is_some_and(|(t, f)| t.replace('-', "_") == f.key)
+    }
+}
+
+fn parse_field_list(spec: &str) -> Vec<Field> {
+    spec.split(|c: char| c == ',' || c.is_whitespace())
+        .map(|f| f.trim().trim_start_matches("${").trim_end_matches('}'))
+        .filter(|f| !f.is_empty())
+        .map(|name| Field {
+            key: name.replace('-', "_"),
+            kind: if INT_FIELDS.contains(&name) {
+                Kind::Int
+            } else if TIME_FIELDS.contains(&name) {
+                Kind::Time
+            } else {
+                Kind::Text
+            },
+        })
+        .collect()
+}
+
+impl Parser for VpcFlow {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("vpc-flow")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses AWS VPC Flow Logs (default v2 or --opt fields=...) -> typed JSONL")
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        OPTIONS
+    }
+
+    fn configure(&mut self, opts: &ModuleOptions) -> Result<()> {
+        // `get` rather than `list`: a space-separated format string is one value.
+        if let Some(spec) = opts.get("fields") {
+            let fields = parse_field_list(spec);
+            if fields.is_empty() {
+                anyhow::bail!("fields must name at least one field");
+            }
+            self.fields = fields;
+        }
+        Ok(())
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["srcaddr".to_string(), "dstaddr".to_string()]
+    }
+
+    fn fields(&self) -> Vec<(String, FieldType)> {
+        self.fields
+            .iter()
+            .map(|f| {
+                let ty = match f.kind {
+                    Kind::Int => FieldType::Int,
+                    Kind::Text | Kind::Time => FieldType::Text,
+                };
+                (f.key.clone(), ty)
+            })
+            .collect()
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let tokens: Vec<&str> = line.split_ascii_whitespace().collect();
+        if tokens.len() != self.fields.len() || self.is_header(&tokens) {
+            return false;
+        }
+
+        let rec = Record {
+            fields: &self.fields,
+            values: &tokens,
+        };
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+struct Record<'a> {
+    fields: &'a [Field],
+    values: &'a [&'a str],
+}
+
+impl Serialize for Record<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.fields.len()))?;
+        for (f, &v) in self.fields.iter().zip(self.values) {
+            // `-` marks a field with no data (e.g. NODATA / SKIPDATA records).
+            if v == "-" {
+                map.serialize_entry(&f.key, &())?;
+                continue;
+            }
+            match f.kind {
+                Kind::Int => match v.parse::<i64>() {
+                    Ok(n) => map.serialize_entry(&f.key, &n)?,
+                    Err(_) => map.serialize_entry(&f.key, v)?,
+                },
+                Kind::Time => match epoch_to_rfc3339(v) {
+                    Some(t) => map.serialize_entry(&f.key, &t)?,
+                    None => map.serialize_entry(&f.key, v)?,
+                },
+                Kind::Text => map.serialize_entry(&f.key, v)?,
+            }
+        }
+        map.end()
+    }
+}
+
+fn epoch_to_rfc3339(value: &str) -> Option<String> {
+    OffsetDateTime::from_unix_timestamp(value.parse().ok()?)
+        .ok()?
+        .format(&Rfc3339)
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(p: &VpcFlow, line: &str) -> Option<serde_json::Value> {
+        let mut out = Vec::new();
+        p.process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn parses_default_v2_record() {
+        let v = run(
+            &VpcFlow::new(),
+            "2 123456789010 eni-1235b8ca123456789 172.31.16.139 172.31.16.21 20641 22 6 20 4249 1418530010 1418530070 ACCEPT OK",
+        )
+        .unwrap();
+
+        assert_eq!(v["version"], 2);
+        assert_eq!(v["account_id"], "123456789010");
+        assert_eq!(v["dstport"], 22);
+        assert_eq!(v["bytes"], 4249);
+        assert_eq!(v["start"], "2014-12-14T04:06:50Z");
+        assert_eq!(v["end"], "2014-12-14T04:07:50Z");
+        assert_eq!(v["action"], "ACCEPT");
+    }
+
+    #[test]
+    fn nodata_dashes_become_null() {
+        let v = run(
+            &VpcFlow::new(),
+            "2 123456789010 eni-1a2b3c4d - - - - - - - 1431280876 1431280934 - NODATA",
+        )
+        .unwrap();
+        assert!(v["srcaddr"].is_null());
+        assert!(v["packets"].is_null());
+        assert_eq!(v["log_status"], "NODATA");
+    }
+
+    #[test]
+    fn custom_fields_and_header_line() {
+        let mut p = VpcFlow::new();
+        p.configure(
+            &ModuleOptions::parse(&["fields=${vpc-id} ${srcaddr} ${dstport} ${tcp-flags}"])
+                .unwrap(),
+        )
+        .unwrap();
+
+        let v = run(&p, "vpc-abc 10.0.0.1 443 19").unwrap();
+        assert_eq!(v["vpc_id"], "vpc-abc");
+        assert_eq!(v["dstport"], 443);
+        assert_eq!(v["tcp_flags"], 19);
+
+        assert!(run(&p, "vpc-id srcaddr dstport tcp-flags").is_none());
+        assert!(run(&p, "vpc-abc 10.0.0.1 443").is_none());
+    }
+}
@@ -1,427 +1,1045 @@
-use crate::core::Parser;
-use anyhow::Result;
-use regex::Regex;
-use serde::Serialize;
-use std::borrow::Cow;
-use time::{format_description::FormatItem, OffsetDateTime, UtcOffset};
-
-/// Set `MULTIPARSE_WEB_FAST_TIME=1` to skip datetime parsing for speed.
-fn fast_time_env() -> bool {
-    std::env::var("MULTIPARSE_WEB_FAST_TIME")
-        .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
-        .unwrap_or(false)
-}
-
-pub struct WebAccess {
-    ctx: ParserCtx,
-}
-
-pub fn new() -> Box<dyn Parser> {
-    Box::new(WebAccess {
-        ctx: ParserCtx::new(fast_time_env()).expect("init web access ParserCtx"),
-    })
-}
-
-impl Parser for WebAccess {
-    fn name(&self) -> Cow<'static, str> {
-        Cow::Borrowed("web-access")
-    }
-
-    fn description(&self) -> Cow<'static, str> {
-        Cow::Borrowed("Parses Apache/Nginx access logs (common/combined/vhost) -> JSONL")
-    }
-
-    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
-        let s = trim_cr(line).trim();
-
-        if s.is_empty() {
-            return false;
-        }
-
-        // Forensic invariant: never drop a non-empty line silently.
-        // A line that does not match a known format is emitted as an
-        // `unparsed` record so collections remain auditable.
-        match self.ctx.parse_line(s) {
-            Some(rec) => {
-                if serde_json::to_writer(&mut *out, &rec).is_ok() {
-                    out.push(b'\n');
-                    return true;
-                }
-            }
-            None => {
-                let rec = Unparsed {
-                    unparsed: true,
-                    raw: s,
-                };
-                if serde_json::to_writer(&mut *out, &rec).is_ok() {
-                    out.push(b'\n');
-                    return true;
-                }
-            }
-        }
-
-        false
-    }
-}
-
-/* -------------------- Core parsing logic -------------------- */
-
-#[derive(Serialize)]
-struct Record<'a> {
-    vhost: Option<&'a str>,
-    ip: Option<&'a str>,
-    ident: Option<&'a str>,
-    user: Option<&'a str>,
-    ts: Option<String>,
-    ts_raw: Option<&'a str>,
-    method: Option<String>,
-    target: Option<String>,
-    path: Option<String>,
-    query: Option<String>,
-    protocol: Option<String>,
-    status: Option<i64>,
-    bytes: Option<i64>,
-    referer: Option<Cow<'a, str>>,
-    user_agent: Option<Cow<'a, str>>,
-    raw: &'a str,
-}
-
-/// Fallback record for lines that match no known access-log format.
-#[derive(Serialize)]
-struct Unparsed<'a> {
-    unparsed: bool,
-    raw: &'a str,
-}
-
-struct ParserCtx {
-    re: Regex,
-    fmt: &'static [FormatItem<'static>],
-    fast_time: bool,
-}
-
-impl ParserCtx {
-    fn new(fast_time: bool) -> Result<Self> {
-        // Single regex covering, in one pass:
-        //
-        // Common:
-        //   IP ident user [time] "request" status size
-        //
-        // Combined:
-        //   IP ident user [time] "request" status size "referer" "agent"
-        //
-        // Vhost-prefixed (combined or common):
-        //   vhost IP ident user [time] "request" status size [...]
-        //
-        // The referer/agent pair is optional; extra trailing fields are
-        // accepted and ignored.
-        //
-        // Quoted fields use `(?:[^"\\]|\\.)*` (not `[^"]*`) so an Apache
-        // backslash-escaped quote inside request/referer/agent does not
-        // terminate the field early. The Rust `regex` crate uses a finite
-        // automaton, so this alternation cannot cause catastrophic
-        // backtracking.
-        let re = Regex::new(
-            r#"^(?:(?P<vhost>\S+)\s+)?(?P<ip>\S+)\s+(?P<ident>\S+)\s+(?P<user>\S+)\s+\[(?P<time>[^\]]+)\]\s+"(?P<request>(?:[^"\\]|\\.)*)"\s+(?P<status>\d{3}|-)\s+(?P<size>\S+)(?:\s+"(?P<referer>(?:[^"\\]|\\.)*)"\s+"(?P<agent>(?:[^"\\]|\\.)*)")?(?:\s+.*)?$"#,
-        )?;
-
-        let fmt = time::macros::format_description!(
-            "[day]/[month repr:short]/[year]:[hour]:[minute]:[second] [offset_hour sign:mandatory][offset_minute]"
-        );
-
-        Ok(Self {
-            re,
-            fmt,
-            fast_time,
-        })
-    }
-
-    fn parse_time_iso8601_utc(&self, s: &str) -> Option<String> {
-        if self.fast_time {
-            return None;
-        }
-
-        if let Ok(dt) = OffsetDateTime::parse(s, &self.fmt) {
-            let utc = dt.to_offset(UtcOffset::UTC);
-            return Some(
-                utc.format(&time::format_description::well_known::Rfc3339)
-                    .ok()?,
-            );
-        }
-
-        None
-    }
-
-    fn parse_request(
-        &self,
-        req: &str,
-    ) -> (
-        Option<String>,
-        Option<String>,
-        Option<String>,
-        Option<String>,
-        Option<String>,
-    ) {
-        if req.is_empty() || req == "-" {
-            return (None, None, None, None, None);
-        }
-
-        // `split(' ')` (not `split_whitespace`) is intentional: it keeps
-        // empty segments, so the `join` below preserves multi-space
-        // targets verbatim, which matters for forensic fidelity.
-        let parts: Vec<&str> = req.split(' ').collect();
-        let (mut method, mut target, mut protocol) = (None, None, None);
-
-        match parts.len() {
-            n if n >= 3 => {
-                method = Some(parts[0].to_string());
-                protocol = Some(parts[n - 1].to_string());
-                target = Some(parts[1..n - 1].join(" "));
-            }
-            2 => {
-                method = Some(parts[0].to_string());
-                target = Some(parts[1].to_string());
-            }
-            1 => {
-                target = Some(parts[0].to_string());
-            }
-            _ => {}
-        }
-
-        let mut path: Option<String> = None;
-        let mut query: Option<String> = None;
-
-        if let Some(t) = &target {
-            if t == "*" {
-                path = Some("*".to_string());
-            } else if let Some(pos) = t.find('?') {
-                path = Some(t[..pos].to_string());
-
-                if pos + 1 < t.len() {
-                    query = Some(t[pos + 1..].to_string());
-                }
-            } else {
-                path = Some(t.clone());
-            }
-        }
-
-        (method, target, path, query, protocol)
-    }
-
-    fn to_int(s: Option<&str>) -> Option<i64> {
-        let s = s?;
-
-        if s == "-" {
-            return None;
-        }
-
-        s.parse::<i64>().ok()
-    }
-
-    /// Caller must pass an already-trimmed, non-empty line.
-    fn parse_line<'a>(&'a self, line: &'a str) -> Option<Record<'a>> {
-        let caps = self.re.captures(line)?;
-
-        let vhost = caps.name("vhost").map(|m| m.as_str());
-        let ip = caps.name("ip").map(|m| m.as_str());
-        let ident = caps.name("ident").map(|m| m.as_str()).filter(|&v| v != "-");
-        let user = caps.name("user").map(|m| m.as_str()).filter(|&v| v != "-");
-
-        let time_raw = caps.name("time").map(|m| m.as_str());
-        let ts = time_raw.and_then(|t| self.parse_time_iso8601_utc(t));
-
-        let request_raw = caps.name("request").map(|m| m.as_str()).unwrap_or("");
-        let request = unescape_logitem(request_raw);
-        let (method, target, path, query, protocol) = self.parse_request(&request);
-
-        let status = Self::to_int(caps.name("status").map(|m| m.as_str()));
-        let bytes = Self::to_int(caps.name("size").map(|m| m.as_str()));
-
-        let referer = caps
-            .name("referer")
-            .map(|m| m.as_str())
-            .filter(|&v| v != "-")
-            .map(unescape_logitem);
-
-        let agent = caps
-            .name("agent")
-            .map(|m| m.as_str())
-            .filter(|&v| v != "-")
-            .map(unescape_logitem);
-
-        Some(Record {
-            vhost,
-            ip,
-            ident,
-            user,
-            ts,
-            ts_raw: time_raw,
-            method,
-            target,
-            path,
-            query,
-            protocol,
-            status,
-            bytes,
-            referer,
-            user_agent: agent,
-            raw: line,
-        })
-    }
-}
-
-/* -------------------- small helpers -------------------- */
-
-fn trim_cr(s: &str) -> &str {
-    if s.as_bytes().last().copied() == Some(b'\r') {
-        &s[..s.len() - 1]
-    } else {
-        s
-    }
-}
-
-/// Reverse Apache `ap_escape_logitem` escaping inside quoted fields.
-/// Handles `\"`, `\\`, `\n`, `\r`, `\t`, `\b`, `\v`, `\f` and `\xHH`.
-/// Returns the input borrowed unchanged when it contains no backslash,
-/// so the common (unescaped) case allocates nothing.
-fn unescape_logitem(s: &str) -> Cow<'_, str> {
-    if !s.contains('\\') {
-        return Cow::Borrowed(s);
-    }
-
-    let mut out = String::with_capacity(s.len());
-    let mut it = s.chars();
-
-    while let Some(c) = it.next() {
-        if c != '\\' {
-            out.push(c);
-            continue;
-        }
-
-        match it.next() {
-            Some('n') => out.push('\n'),
-            Some('r') => out.push('\r'),
-            Some('t') => out.push('\t'),
-            Some('b') => out.push('\u{0008}'),
-            Some('v') => out.push('\u{000B}'),
-            Some('f') => out.push('\u{000C}'),
-            Some('"') => out.push('"'),
-            Some('\\') => out.push('\\'),
-            Some('x') => {
-                let a = it.next();
-                let b = it.next();
-                match (
-                    a.and_then(|c| c.to_digit(16)),
-                    b.and_then(|c| c.to_digit(16)),
-                ) {
-                    (Some(x), Some(y)) => out.push(char::from((x * 16 + y) as u8)),
-                    _ => {
-                        // Not a valid \xHH sequence: emit literally.
-                        out.push('\\');
-                        out.push('x');
-                        if let Some(a) = a {
-                            out.push(a);
-                        }
-                        if let Some(b) = b {
-                            out.push(b);
-                        }
-                    }
-                }
-            }
-            // Unknown escape: drop the backslash, keep the char.
-            Some(other) => out.push(other),
-            // Trailing lone backslash.
-            None => out.push('\\'),
-        }
-    }
-
-    Cow::Owned(out)
-}
-
-/* -------------------- tests -------------------- */
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    fn ctx() -> ParserCtx {
-        ParserCtx::new(false).unwrap()
-    }
-
-    #[test]
-    fn parses_combined() {
-        let line = r#"1.2.3.4 - alice [10/Oct/2000:13:55:36 -0700] "GET /index.html?a=1 HTTP/1.1" 200 2326 "http://ref/" "Mozilla/5.0""#;
-        let r = ctx().parse_line(line).unwrap();
-        assert_eq!(r.ip, Some("1.2.3.4"));
-        assert_eq!(r.user, Some("alice"));
-        assert_eq!(r.method.as_deref(), Some("GET"));
-        assert_eq!(r.path.as_deref(), Some("/index.html"));
-        assert_eq!(r.query.as_deref(), Some("a=1"));
-        assert_eq!(r.protocol.as_deref(), Some("HTTP/1.1"));
-        assert_eq!(r.status, Some(200));
-        assert_eq!(r.bytes, Some(2326));
-        assert_eq!(r.referer.as_deref(), Some("http://ref/"));
-        assert_eq!(r.user_agent.as_deref(), Some("Mozilla/5.0"));
-        assert_eq!(r.ts.as_deref(), Some("2000-10-10T20:55:36Z"));
-    }
-
-    #[test]
-    fn parses_common() {
-        let line = r#"1.2.3.4 - - [10/Oct/2000:13:55:36 -0700] "GET / HTTP/1.0" 404 -"#;
-        let r = ctx().parse_line(line).unwrap();
-        assert_eq!(r.status, Some(404));
-        assert_eq!(r.bytes, None);
-        assert!(r.referer.is_none());
-        assert!(r.user_agent.is_none());
-    }
-
-    #[test]
-    fn parses_vhost_prefixed() {
-        let line = r#"www.example.com 1.2.3.4 - - [10/Oct/2000:13:55:36 -0700] "GET / HTTP/1.1" 200 10 "-" "curl""#;
-        let r = ctx().parse_line(line).unwrap();
-        assert_eq!(r.vhost, Some("www.example.com"));
-        assert_eq!(r.ip, Some("1.2.3.4"));
-        assert_eq!(r.user_agent.as_deref(), Some("curl"));
-    }
-
-    #[test]
-    fn handles_escaped_quote_in_request() {
-        // The key fix: a backslash-escaped quote must not end the field.
-        let line = r#"1.2.3.4 - - [10/Oct/2000:13:55:36 -0700] "GET /a\"b HTTP/1.1" 200 5 "-" "UA""#;
-        let r = ctx().parse_line(line).unwrap();
-        assert_eq!(r.path.as_deref(), Some(r#"/a"b"#));
-        assert_eq!(r.status, Some(200));
-    }
-
-    #[test]
-    fn empty_request_is_tolerated() {
-        let line = r#"1.2.3.4 - - [10/Oct/2000:13:55:36 -0700] "-" 408 0"#;
-        let r = ctx().parse_line(line).unwrap();
-        assert!(r.method.is_none());
-        assert!(r.target.is_none());
-        assert_eq!(r.status, Some(408));
-    }
-
-    #[test]
-    fn unescape_borrows_when_no_backslash() {
-        assert!(matches!(unescape_logitem("plain text"), Cow::Borrowed(_)));
-        assert_eq!(unescape_logitem(r#"a\"b\\c\x41"#), "a\"b\\cA");
-    }
-
-    #[test]
-    fn unmatched_line_emits_unparsed_record() {
-        let p = new();
-        let mut out = Vec::new();
-        assert!(p.process_line_to_buf("this is not an access log line", &mut out));
-        let s = String::from_utf8(out).unwrap();
-        assert!(s.contains(r#""unparsed":true"#));
-        assert!(s.ends_with('\n'));
-    }
-
-    #[test]
-    fn empty_line_emits_nothing() {
-        let p = new();
-        let mut out = Vec::new();
-        assert!(!p.process_line_to_buf("   \r", &mut out));
-        assert!(out.is_empty());
-    }
-}
+use crate::core::{ModuleOptions, OptionSpec, Parser};
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::borrow::Cow;
+use time::{format_description::FormatItem, OffsetDateTime, UtcOffset};
+
+const OPTIONS: &[OptionSpec] = &[
+    OptionSpec {
+        key: "fast_time",
+        help: "Skip datetime parsing for speed (ts stays null, ts_raw is kept)",
+    },
+    OptionSpec {
+        key: "log_format",
+        help: "Apache LogFormat (%h %l %u %t \"%r\" ...) or nginx log_format ($remote_addr ...) \
+               string to parse instead of common/combined",
+    },
+];
+
+pub struct WebAccess {
+    ctx: ParserCtx,
+}
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(WebAccess {
+        ctx: ParserCtx::new(false).expect("init web access ParserCtx"),
+    })
+}
+
+impl Parser for WebAccess {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("web-access")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses Apache/Nginx access logs (common/combined/vhost) -> JSONL")
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        OPTIONS
+    }
+
+    fn configure(&mut self, opts: &ModuleOptions) -> Result<()> {
+        if let Some(format) = opts.get("log_format") {
+            self.ctx = ParserCtx::with_format(format, self.ctx.fast_time)?;
+        }
+        if let Some(fast) = opts.flag("fast_time")? {
+            self.ctx.fast_time = fast;
+        }
+        Ok(())
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["ip".to_string()]
+    }
+
+    fn recognizes(&self, line: &str) -> bool {
+        self.ctx.re.is_match(trim_cr(line).trim())
+    }
+
+    fn user_agent_fields(&self) -> Vec<String> {
+        vec!["user_agent".to_string()]
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let s = trim_cr(line).trim();
+
+        if s.is_empty() {
+            return false;
+        }
+
+        // Forensic invariant: never drop a non-empty line silently.
+        // A line that does not match a known format is emitted as an
+        // `unparsed` record so collections remain auditable.
+        match self.ctx.parse_line(s) {
+            Some(rec) => {
+                if serde_json::to_writer(&mut *out, &rec).is_ok() {
+                    out.push(b'\n');
+                    return true;
+                }
+            }
+            None => {
+                let rec = Unparsed {
+                    unparsed: true,
+                    raw: s,
+                };
+                if serde_json::to_writer(&mut *out, &rec).is_ok() {
+                    out.push(b'\n');
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+}
+
+/* -------------------- Core parsing logic -------------------- */
+
+#[derive(serde::Serialize)]
+struct Record<'a> {
+    vhost: Option<&'a str>,
+    ip: Option<&'a str>,
+    ident: Option<&'a str>,
+    user: Option<&'a str>,
+    ts: Option<String>,
+    ts_raw: Option<&'a str>,
+    method: Option<String>,
+    target: Option<String>,
+    path: Option<String>,
+    query: Option<String>,
+    protocol: Option<String>,
+    status: Option<i64>,
+    bytes: Option<i64>,
+    referer: Option<Cow<'a, str>>,
+    user_agent: Option<Cow<'a, str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration_us: Option<i64>,
+    /// `X-Forwarded-For` chain, client first.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    xff: Option<Vec<&'a str>>,
+    /// `log_format` fields without a column of their own.
+    #[serde(flatten)]
+    extra: Extra<'a>,
+    raw: &'a str,
+}
+
+/// Extra `log_format` fields, in format order; `-` becomes `null`.
+struct Extra<'a>(Vec<(&'a str, Option<Cow<'a, str>>)>);
+
+impl Serialize for Extra<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (k, v) in &self.0 {
+            map.serialize_entry(k, v)?;
+        }
+        map.end()
+    }
+}
+
+/// Fallback record for lines that match no known access-log format.
+#[derive(serde::Serialize)]
+struct Unparsed<'a> {
+    unparsed: bool,
+    raw: &'a str,
+}
+
+/// `(method, target, path, query, protocol)` split out of a request line.
+type RequestParts = (
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+);
+
+struct ParserCtx {
+    re: Regex,
+    fmt: &'static [FormatItem<'static>],
+    fast_time: bool,
+    /// `log_format` fields kept under their own name, with their capture group.
+    extras: Vec<(String, usize)>,
+}
+
+impl ParserCtx {
+    fn new(fast_time: bool) -> Result<Self> {
+        // Single regex covering, in one pass:
+        //
+        // Common:
+        //   IP ident user [time] "request" status size
+        //
+        // Combined:
+        //   IP ident user [time] "request" status size "referer" "agent"
+        //
+        // Vhost-prefixed (combined or common):
+        //   vhost IP ident user [time] "request" status size [...]
+        //
+        // The referer/agent pair is optional. Trailing fields are captured
+        // as `tail` and scanned for a request duration and forwarded-for
+        // chain (see `scan_tail`); anything else there is ignored.
+        //
+        // Quoted fields use `(?:[^"\\]|\\.)*` (not `[^"]*`) so an Apache
+        // backslash-escaped quote inside request/referer/agent does not
+        // terminate the field early. The Rust `regex` crate uses a finite
+        // automaton, so this alternation cannot cause catastrophic
+        // backtracking.
+        let re = Regex::new(
+            r#"^(?:(?P<vhost>\S+)\s+)?(?P<ip>\S+)\s+(?P<ident>\S+)\s+(?P<user>\S+)\s+\[(?P<time>[^\]]+)\]\s+"(?P<request>(?:[^"\\]|\\.)*)"\s+(?P<status>\d{3}|-)\s+(?P<size>\S+)(?:\s+"(?P<referer>(?:[^"\\]|\\.)*)"\s+"(?P<agent>(?:[^"\\]|\\.)*)")?(?:\s+(?P<tail>.*))?$"#,
+        )?;
+
+        let fmt = time::macros::format_description!(
+            "[day]/[month repr:short]/[year]:[hour]:[minute]:[second] [offset_hour sign:mandatory][offset_minute]"
+        );
+
+        Ok(Self {
+            re,
+            fmt,
+            fast_time,
+            extras: Vec::new(),
+        })
+    }
+
+    /// Compile an Apache `LogFormat` or nginx `log_format` string.
+    fn with_format(format: &str, fast_time: bool) -> Result<Self> {
+        let tokens = if format.contains('$') && !format.contains('%') {
+            nginx_tokens(format)?
+        } else {
+            apache_tokens(format)?
+        };
+        let (pattern, extra_groups) = format_regex(&tokens);
+        let re = Regex::new(&pattern)
+            .map_err(|e| anyhow::anyhow!("log_format compiles to an invalid pattern: {e}"))?;
+
+        let extras = extra_groups
+            .into_iter()
+            .filter_map(|(name, group)| {
+                let idx = re.capture_names().position(|n| n == Some(group.as_str()))?;
+                Some((name, idx))
+            })
+            .collect();
+        Ok(Self {
+            re,
+            extras,
+            ..Self::new(fast_time)?
+        })
+    }
+
+    fn parse_time_iso8601_utc(&self, s: &str) -> Option<String> {
+        if self.fast_time {
+            return None;
+        }
+
+        // nginx `$time_iso8601`.
+        if s.as_bytes().get(4) == Some(&b'-')
+            && let Ok(dt) = OffsetDateTime::parse(s, &time::format_description::well_known::Rfc3339)
+        {
+            return dt
+                .to_offset(UtcOffset::UTC)
+                .format(&time::format_description::well_known::Rfc3339)
+                .ok();
+        }
+
+        if let Ok(dt) = OffsetDateTime::parse(s, &self.fmt) {
+            let utc = dt.to_offset(UtcOffset::UTC);
+            return utc
+                .format(&time::format_description::well_known::Rfc3339)
+                .ok();
+        }
+
+        None
+    }
+
+    fn parse_request(&self, req: &str) -> RequestParts {
+        if req.is_empty() || req == "-" {
+            return (None, None, None, None, None);
+        }
+
+        // `split(' ')` (not `split_whitespace`) is intentional: it keeps
+        // empty segments, so the `join` below preserves multi-space
+        // targets verbatim, which matters for forensic fidelity.
+        let parts: Vec<&str> = req.split(' ').collect();
+        let (mut method, mut target, mut protocol) = (None, None, None);
+
+        match parts.len() {
+            n if n >= 3 => {
+                method = Some(parts[0].to_string());
+                protocol = Some(parts[n - 1].to_string());
+                target = Some(parts[1..n - 1].join(" "));
+            }
+            2 => {
+                method = Some(parts[0].to_string());
+                target = Some(parts[1].to_string());
+            }
+            1 => {
+                target = Some(parts[0].to_string());
+            }
+            _ => {}
+        }
+
+        let (path, query) = Self::split_target(target.as_deref());
+        (method, target, path, query, protocol)
+    }
+
+    fn split_target(target: Option<&str>) -> (Option<String>, Option<String>) {
+        let mut path: Option<String> = None;
+        let mut query: Option<String> = None;
+
+        if let Some(t) = target {
+            if t == "*" {
+                path = Some("*".to_string());
+            } else if let Some(pos) = t.find('?') {
+                path = Some(t[..pos].to_string());
+
+                if pos + 1 < t.len() {
+                    query = Some(t[pos + 1..].to_string());
+                }
+            } else {
+                path = Some(t.to_string());
+            }
+        }
+
+        (path, query)
+    }
+
+    /// Request parts logged separately (`%m %U%q %H`, `$request_method
+    /// $request_uri $server_protocol`, ...) instead of as a request line.
+    fn request_from_parts(&self, caps: &regex::Captures) -> RequestParts {
+        let part = |name: &str| {
+            caps.name(name)
+                .map(|m| unescape_logitem(m.as_str()).into_owned())
+                .filter(|v| !v.is_empty() && v != "-")
+        };
+        let target = part("target");
+        let (mut path, mut query) = Self::split_target(target.as_deref());
+        if let Some(p) = part("path") {
+            path = Some(p);
+        }
+        if let Some(q) = part("query") {
+            query = Some(q.strip_prefix('?').unwrap_or(&q).to_string()).filter(|q| !q.is_empty());
+        }
+        let target = target.or_else(|| match (&path, &query) {
+            (Some(p), Some(q)) => Some(format!("{p}?{q}")),
+            (p, _) => p.clone(),
+        });
+        (part("method"), target, path, query, part("protocol"))
+    }
+
+    fn to_int(s: Option<&str>) -> Option<i64> {
+        let s = s?;
+
+        if s == "-" {
+            return None;
+        }
+
+        s.parse::<i64>().ok()
+    }
+
+    /// Caller must pass an already-trimmed, non-empty line.
+    fn parse_line<'a>(&'a self, line: &'a str) -> Option<Record<'a>> {
+        let caps = self.re.captures(line)?;
+
+        let vhost = caps.name("vhost").map(|m| m.as_str());
+        let ip = caps.name("ip").map(|m| m.as_str());
+        let ident = caps.name("ident").map(|m| m.as_str()).filter(|&v| v != "-");
+        let user = caps.name("user").map(|m| m.as_str()).filter(|&v| v != "-");
+
+        let time_raw = caps.name("time").map(|m| m.as_str());
+        let ts = time_raw.and_then(|t| self.parse_time_iso8601_utc(t));
+        // `%{format}t`: kept as written.
+        let time_raw = time_raw.or_else(|| caps.name("time_raw").map(|m| m.as_str()));
+
+        let (method, target, path, query, protocol) = match caps.name("request") {
+            Some(m) => self.parse_request(&unescape_logitem(m.as_str())),
+            None => self.request_from_parts(&caps),
+        };
+
+        let status = Self::to_int(caps.name("status").map(|m| m.as_str()));
+        let bytes = Self::to_int(caps.name("size").map(|m| m.as_str()));
+
+        let referer = caps
+            .name("referer")
+            .map(|m| m.as_str())
+            .filter(|&v| v != "-")
+            .map(unescape_logitem);
+
+        let agent = caps
+            .name("agent")
+            .map(|m| m.as_str())
+            .filter(|&v| v != "-")
+            .map(unescape_logitem);
+
+        let mut duration_us = caps
+            .name("duration_us")
+            .and_then(|m| m.as_str().parse().ok())
+            .or_else(|| {
+                let ms: f64 = caps.name("duration_ms")?.as_str().parse().ok()?;
+                Some((ms * 1e3).round() as i64)
+            })
+            .or_else(|| seconds_to_us(caps.name("duration_s")?.as_str()));
+        let mut xff = caps.name("xff").map(|m| split_xff(m.as_str()));
+        if let Some(tail) = caps.name("tail") {
+            scan_tail(tail.as_str(), &mut duration_us, &mut xff);
+        }
+        let xff = xff.filter(|chain| !chain.is_empty());
+
+        let extra = self
+            .extras
+            .iter()
+            .map(|(name, idx)| {
+                let value = caps
+                    .get(*idx)
+                    .map(|m| m.as_str())
+                    .filter(|&v| v != "-")
+                    .map(unescape_logitem);
+                (name.as_str(), value)
+            })
+            .collect();
+
+        Some(Record {
+            vhost,
+            ip,
+            ident,
+            user,
+            ts,
+            ts_raw: time_raw,
+            method,
+            target,
+            path,
+            query,
+            protocol,
+            status,
+            bytes,
+            referer,
+            user_agent: agent,
+            duration_us,
+            xff,
+            extra: Extra(extra),
+            raw: line,
+        })
+    }
+}
+
+/* -------------------- Custom log formats -------------------- */
+
+/// One piece of a `log_format` string.
+#[derive(Debug, PartialEq)]
+enum Token {
+    Literal(String),
+    /// A logged value: the record column it fills (a capture group name of
+    /// the built-in regex), and the name it is kept under when it has no
+    /// column or the column is already taken.
+    Field {
+        column: Option<&'static str>,
+        name: String,
+    },
+}
+
+fn field(column: Option<&'static str>, name: impl Into<String>) -> Token {
+    Token::Field {
+        column,
+        name: name.into(),
+    }
+}
+
+/// `X-Forwarded-For` -> `x_forwarded_for`.
+fn snake(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Apache `LogFormat`: `%` directives with optional conditions (`%400,501`),
+/// `<`/`>` modifiers and `{argument}`. `\"` escapes as written in
+/// httpd.conf are accepted.
+fn apache_tokens(format: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut lit = String::new();
+    let mut chars = format.trim().chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('"') => lit.push('"'),
+                Some('t') => lit.push('\t'),
+                Some(other) => {
+                    lit.push('\\');
+                    lit.push(other);
+                }
+                None => lit.push('\\'),
+            }
+            continue;
+        }
+        if c != '%' {
+            lit.push(c);
+            continue;
+        }
+
+        while chars
+            .next_if(|c| matches!(c, '!' | ',' | '<' | '>' | '0'..='9'))
+            .is_some()
+        {}
+        let arg = chars
+            .next_if_eq(&'{')
+            .map(|_| chars.by_ref().take_while(|&c| c != '}').collect::<String>());
+        let letter = chars
+            .next()
+            .with_context(|| format!("log_format {format:?} ends with a lone %"))?;
+        if letter == '%' {
+            lit.push('%');
+            continue;
+        }
+
+        let tok = apache_field(letter, arg.as_deref())
+            .with_context(|| format!("log_format: unsupported directive %{letter}"))?;
+        if !lit.is_empty() {
+            tokens.push(Token::Literal(std::mem::take(&mut lit)));
+        }
+        // Plain `%t` brings its own brackets.
+        if letter == 't' && arg.is_none() {
+            tokens.push(Token::Literal("[".into()));
+            tokens.push(tok);
+            tokens.push(Token::Literal("]".into()));
+        } else {
+            tokens.push(tok);
+        }
+    }
+    if !lit.is_empty() {
+        tokens.push(Token::Literal(lit));
+    }
+    Ok(tokens)
+}
+
+fn apache_field(letter: char, arg: Option<&str>) -> Option<Token> {
+    Some(match (letter, arg) {
+        ('h', _) => field(Some("ip"), "remote_host"),
+        ('a', Some("c")) => field(None, "peer_ip"),
+        ('a', _) => field(Some("ip"), "client_ip"),
+        ('A', _) => field(None, "server_ip"),
+        ('b' | 'B', _) => field(Some("size"), "response_bytes"),
+        ('C', Some(c)) => field(None, format!("cookie_{}", snake(c))),
+        ('D', _) => field(Some("duration_us"), "duration_us"),
+        ('e', Some(v)) => field(None, format!("env_{}", snake(v))),
+        ('f', _) => field(None, "filename"),
+        ('H', _) => field(Some("protocol"), "protocol"),
+        ('i', Some(h)) if h.eq_ignore_ascii_case("referer") => field(Some("referer"), "referer"),
+        ('i', Some(h)) if h.eq_ignore_ascii_case("user-agent") => {
+            field(Some("agent"), "user_agent")
+        }
+        ('i', Some(h)) if h.eq_ignore_ascii_case("x-forwarded-for") => {
+            field(Some("xff"), "x_forwarded_for")
+        }
+        ('i', Some(h)) => field(None, snake(h)),
+        ('I', _) => field(None, "bytes_in"),
+        ('k', _) => field(None, "keepalive_requests"),
+        ('l', _) => field(Some("ident"), "ident"),
+        ('L', _) => field(None, "log_id"),
+        ('m', _) => field(Some("method"), "method"),
+        ('n', Some(n)) => field(None, format!("note_{}", snake(n))),
+        ('o', Some(h)) => field(None, format!("resp_{}", snake(h))),
+        ('O', _) => field(None, "bytes_out"),
+        ('p', Some("remote")) => field(None, "client_port"),
+        ('p', _) => field(None, "server_port"),
+        ('P', Some("tid" | "hextid")) => field(None, "tid"),
+        ('P', _) => field(None, "pid"),
+        ('q', _) => field(Some("query"), "query"),
+        ('r', _) => field(Some("request"), "request"),
+        ('R', _) => field(None, "handler"),
+        ('s', _) => field(Some("status"), "status"),
+        ('S', _) => field(None, "bytes_transferred"),
+        ('t', None) => field(Some("time"), "time"),
+        ('t', Some(_)) => field(Some("time_raw"), "time"),
+        ('T', Some("ms")) => field(Some("duration_ms"), "duration_ms"),
+        ('T', Some("us")) => field(Some("duration_us"), "duration_us"),
+        ('T', _) => field(Some("duration_s"), "duration_s"),
+        ('u', _) => field(Some("user"), "user"),
+        ('U', _) => field(Some("path"), "path"),
+        ('v', _) => field(Some("vhost"), "vhost"),
+        ('V', _) => field(Some("vhost"), "server_name"),
+        ('X', _) => field(None, "conn_status"),
+        _ => return None,
+    })
+}
+
+/// nginx `log_format`: `$variable` or `${variable}`; everything else is
+/// literal.
+fn nginx_tokens(format: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = format.trim();
+    while let Some(dollar) = rest.find('$') {
+        if dollar > 0 {
+            tokens.push(Token::Literal(rest[..dollar].to_string()));
+        }
+        rest = &rest[dollar + 1..];
+        let (var, after) = match rest.strip_prefix('{') {
+            Some(r) => r
+                .split_once('}')
+                .with_context(|| format!("log_format: unterminated ${{ in {format:?}"))?,
+            None => {
+                let end = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                rest.split_at(end)
+            }
+        };
+        if var.is_empty() {
+            anyhow::bail!("log_format: empty variable name in {format:?}");
+        }
+        tokens.push(nginx_field(var));
+        rest = after;
+    }
+    if !rest.is_empty() {
+        tokens.push(Token::Literal(rest.to_string()));
+    }
+    Ok(tokens)
+}
+
+fn nginx_field(var: &str) -> Token {
+    let column = match var {
+        "remote_addr" => "ip",
+        "remote_user" => "user",
+        "time_local" | "time_iso8601" => "time",
+        "request" => "request",
+        "status" => "status",
+        "body_bytes_sent" => "size",
+        "http_referer" => "referer",
+        "http_user_agent" => "agent",
+        "http_x_forwarded_for" => "xff",
+        "request_time" => "duration_s",
+        "host" | "http_host" | "server_name" => "vhost",
+        "request_method" => "method",
+        "request_uri" => "target",
+        "uri" | "document_uri" => "path",
+        "args" | "query_string" => "query",
+        "server_protocol" => "protocol",
+        _ => {
+            let name = match var.strip_prefix("sent_http_") {
+                Some(h) => format!("resp_{h}"),
+                None => var.strip_prefix("http_").unwrap_or(var).to_string(),
+            };
+            return field(None, name);
+        }
+    };
+    field(Some(column), var)
+}
+
+/// Keys of `Record` an extra field must not shadow.
+const RECORD_KEYS: &[&str] = &[
+    "vhost",
+    "ip",
+    "ident",
+    "user",
+    "ts",
+    "ts_raw",
+    "method",
+    "target",
+    "path",
+    "query",
+    "protocol",
+    "status",
+    "bytes",
+    "referer",
+    "user_agent",
+    "duration_us",
+    "xff",
+    "raw",
+];
+
+/// Anchored regex for `tokens`, and the extra fields as `(name, group)`.
+///
+/// A value runs up to the literal that follows it: quoted values honour
+/// `\"` escapes, values before a space stop at whitespace, and
+/// back-to-back values split as early as the rest of the line allows.
+fn format_regex(tokens: &[Token]) -> (String, Vec<(String, String)>) {
+    let mut pattern = String::from("^");
+    let mut columns: Vec<&str> = Vec::new();
+    // One duration per record, whatever its unit.
+    let slot = |c: &'static str| c.strip_prefix("duration_").map_or(c, |_| "duration");
+    let mut extras: Vec<(String, String)> = Vec::new();
+
+    for (i, tok) in tokens.iter().enumerate() {
+        let (column, name) = match tok {
+            Token::Literal(text) => {
+                let parts: Vec<String> = text.split(' ').map(regex::escape).collect();
+                pattern.push_str(&parts.join(r"\s+"));
+                continue;
+            }
+            Token::Field { column, name } => (column, name),
+        };
+
+        let value = match tokens.get(i + 1) {
+            None => ".*".to_string(),
+            // `%U%q`: the query string starts at the `?`.
+            Some(Token::Field { .. }) if *column == Some("path") => r"[^?\s]*".to_string(),
+            Some(Token::Field { .. }) => ".*?".to_string(),
+            Some(Token::Literal(next)) => match next.chars().next() {
+                Some('"') => r#"(?:[^"\\]|\\.)*"#.to_string(),
+                Some(' ') | None => r"\S*".to_string(),
+                Some(c) => format!("[^{}]*", regex::escape(&c.to_string())),
+            },
+        };
+
+        let group = match column {
+            Some(c) if !columns.contains(&slot(c)) => {
+                columns.push(slot(c));
+                c.to_string()
+            }
+            _ => {
+                let mut unique = name.clone();
+                let mut n = 1;
+                while RECORD_KEYS.contains(&unique.as_str())
+                    || extras.iter().any(|(e, _)| *e == unique)
+                {
+                    n += 1;
+                    unique = format!("{name}_{n}");
+                }
+                let group = format!("x{}", extras.len());
+                extras.push((unique, group.clone()));
+                group
+            }
+        };
+        pattern.push_str(&format!("(?P<{group}>{value})"));
+    }
+    pattern.push('$');
+    (pattern, extras)
+}
+
+/* -------------------- small helpers -------------------- */
+
+/// `$request_time` style seconds (`0.004`) -> microseconds.
+fn seconds_to_us(s: &str) -> Option<i64> {
+    let secs: f64 = s.parse().ok()?;
+    (secs.is_finite() && secs >= 0.0).then(|| (secs * 1e6).round() as i64)
+}
+
+/// `1.1.1.1, 2.2.2.2` -> `["1.1.1.1", "2.2.2.2"]`; `-` is an empty chain.
+fn split_xff(s: &str) -> Vec<&str> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|a| !a.is_empty() && *a != "-")
+        .collect()
+}
+
+/// Fields that extended combined layouts append after the user agent: a
+/// bare integer is `%D` (microseconds), a bare decimal `$request_time`
+/// (seconds), and a (quoted) list of addresses is an `X-Forwarded-For`
+/// chain. The first of each wins.
+fn scan_tail<'a>(tail: &'a str, duration_us: &mut Option<i64>, xff: &mut Option<Vec<&'a str>>) {
+    let mut rest = tail.trim_start();
+    while !rest.is_empty() {
+        let (value, quoted, after) = match rest.strip_prefix('"') {
+            Some(r) => {
+                let end = quoted_end(r).unwrap_or(r.len());
+                (&r[..end], true, r.get(end + 1..).unwrap_or(""))
+            }
+            None => {
+                let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                (&rest[..end], false, &rest[end..])
+            }
+        };
+        rest = after.trim_start();
+
+        if !quoted && duration_us.is_none() && value.bytes().all(|b| b.is_ascii_digit()) {
+            *duration_us = value.parse().ok();
+        } else if !quoted && duration_us.is_none() && value.contains('.') && !value.contains(',') {
+            *duration_us = seconds_to_us(value);
+        }
+        if xff.is_none() {
+            let chain = split_xff(value);
+            if !chain.is_empty() && chain.iter().all(|a| a.parse::<std::net::IpAddr>().is_ok()) {
+                // A lone bare address could be anything (`%A`, `$server_addr`).
+                if quoted || chain.len() > 1 {
+                    *xff = Some(chain);
+                }
+            }
+        }
+    }
+}
+
+/// Byte index of the quote closing a field, skipping `\"` escapes.
+fn quoted_end(s: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, b) in s.bytes().enumerate() {
+        match b {
+            _ if escaped => escaped = false,
+            b'\\' => escaped = true,
+            b'"' => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn trim_cr(s: &str) -> &str {
+    if s.as_bytes().last().copied() == Some(b'\r') {
+        &s[..s.len() - 1]
+    } else {
+        s
+    }
+}
+
+/// Reverse Apache `ap_escape_logitem` escaping inside quoted fields.
+/// Handles `\"`, `\\`, `\n`, `\r`, `\t`, `\b`, `\v`, `\f` and `\xHH`.
+/// Returns the input borrowed unchanged when it contains no backslash,
+/// so the common (unescaped) case allocates nothing.
+fn unescape_logitem(s: &str) -> Cow<'_, str> {
+    if !s.contains('\\') {
+        return Cow::Borrowed(s);
+    }
+
+    let mut out = String::with_capacity(s.len());
+    let mut it = s.chars();
+
+    while let Some(c) = it.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+
+        match it.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('b') => out.push('\u{0008}'),
+            Some('v') => out.push('\u{000B}'),
+            Some('f') => out.push('\u{000C}'),
+            Some('"') => out.push('"'),
+            Some('\\') => out.push('\\'),
+            Some('x') => {
+                let a = it.next();
+                let b = it.next();
+                match (
+                    a.and_then(|c| c.to_digit(16)),
+                    b.and_then(|c| c.to_digit(16)),
+                ) {
+                    (Some(x), Some(y)) => out.push(char::from((x * 16 + y) as u8)),
+                    _ => {
+                        // Not a valid \xHH sequence: emit literally.
+                        out.push('\\');
+                        out.push('x');
+                        if let Some(a) = a {
+                            out.push(a);
+                        }
+                        if let Some(b) = b {
+                            out.push(b);
+                        }
+                    }
+                }
+            }
+            // Unknown escape: drop the backslash, keep the char.
+            Some(other) => out.push(other),
+            // Trailing lone backslash.
+            None => out.push('\\'),
+        }
+    }
+
+    Cow::Owned(out)
+}
+
+/* -------------------- tests -------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx() -> ParserCtx {
+        ParserCtx::new(false).unwrap()
+    }
+
+    #[test]
+    fn parses_combined() {
+        let line = r#"1.2.3.4 - alice [10/Oct/2000:13:55:36 -0700] "GET /index.html?a=1 HTTP/1.1" 200 2326 "http://ref/" "Mozilla/5.0""#;
+        let c = ctx();
+        let r = c.parse_line(line).unwrap();
+        assert_eq!(r.ip, Some("1.2.3.4"));
+        assert_eq!(r.user, Some("alice"));
+        assert_eq!(r.method.as_deref(), Some("GET"));
+        assert_eq!(r.path.as_deref(), Some("/index.html"));
+        assert_eq!(r.query.as_deref(), Some("a=1"));
+        assert_eq!(r.protocol.as_deref(), Some("HTTP/1.1"));
+        assert_eq!(r.status, Some(200));
+        assert_eq!(r.bytes, Some(2326));
+        assert_eq!(r.referer.as_deref(), Some("http://ref/"));
+        assert_eq!(r.user_agent.as_deref(), Some("Mozilla/5.0"));
+        assert_eq!(r.ts.as_deref(), Some("2000-10-10T20:55:36Z"));
+    }
+
+    #[test]
+    fn parses_common() {
+        let line = r#"1.2.3.4 - - [10/Oct/2000:13:55:36 -0700] "GET / HTTP/1.0" 404 -"#;
+        let c = ctx();
+        let r = c.parse_line(line).unwrap();
+        assert_eq!(r.status, Some(404));
+        assert_eq!(r.bytes, None);
+        assert!(r.referer.is_none());
+        assert!(r.user_agent.is_none());
+    }
+
+    #[test]
+    fn parses_vhost_prefixed() {
+        let line = r#"www.example.com 1.2.3.4 - - [10/Oct/2000:13:55:36 -0700] "GET / HTTP/1.1" 200 10 "-" "curl""#;
+        let c = ctx();
+        let r = c.parse_line(line).unwrap();
+        assert_eq!(r.vhost, Some("www.example.com"));
+        assert_eq!(r.ip, Some("1.2.3.4"));
+        assert_eq!(r.user_agent.as_deref(), Some("curl"));
+    }
+
+    #[test]
+    fn handles_escaped_quote_in_request() {
+        // The key fix: a backslash-escaped quote must not end the field.
+        let line =
+            r#"1.2.3.4 - - [10/Oct/2000:13:55:36 -0700] "GET /a\"b HTTP/1.1" 200 5 "-" "UA""#;
+        let c = ctx();
+        let r = c.parse_line(line).unwrap();
+        assert_eq!(r.path.as_deref(), Some(r#"/a"b"#));
+        assert_eq!(r.status, Some(200));
+    }
+
+    #[test]
+    fn extended_combined_tail() {
+        let c = ctx();
+        let base = r#"1.2.3.4 - - [10/Oct/2000:13:55:36 -0700] "GET / HTTP/1.1" 200 10 "-" "curl""#;
+
+        // Apache combined + %D.
+        let line = format!("{base} 1234");
+        let r = c.parse_line(&line).unwrap();
+        assert_eq!(r.duration_us, Some(1234));
+        assert!(r.xff.is_none());
+
+        // nginx "main": forwarded-for chain, then $request_time.
+        let line = format!(r#"{base} "203.0.113.9, 10.0.0.2" 0.250"#);
+        let r = c.parse_line(&line).unwrap();
+        assert_eq!(r.xff, Some(vec!["203.0.113.9", "10.0.0.2"]));
+        assert_eq!(r.duration_us, Some(250_000));
+
+        // Unset header and no duration: neither field is emitted.
+        let line = format!(r#"{base} "-""#);
+        let v = serde_json::to_value(c.parse_line(&line).unwrap()).unwrap();
+        assert!(v.get("xff").is_none());
+        assert!(v.get("duration_us").is_none());
+
+        // Vhost prefix and tail together.
+        let line = format!(r#"www.example.com {base} "2001:db8::1" 87"#);
+        let r = c.parse_line(&line).unwrap();
+        assert_eq!(r.vhost, Some("www.example.com"));
+        assert_eq!(r.xff, Some(vec!["2001:db8::1"]));
+        assert_eq!(r.duration_us, Some(87));
+    }
+
+    #[test]
+    fn empty_request_is_tolerated() {
+        let line = r#"1.2.3.4 - - [10/Oct/2000:13:55:36 -0700] "-" 408 0"#;
+        let c = ctx();
+        let r = c.parse_line(line).unwrap();
+        assert!(r.method.is_none());
+        assert!(r.target.is_none());
+        assert_eq!(r.status, Some(408));
+    }
+
+    fn json(p: &dyn Parser, line: &str) -> serde_json::Value {
+        let mut out = Vec::new();
+        assert!(p.process_line_to_buf(line, &mut out));
+        serde_json::from_slice(&out).unwrap()
+    }
+
+    #[test]
+    fn apache_log_format() {
+        let mut p = WebAccess { ctx: ctx() };
+        p.configure(
+            &ModuleOptions::parse(&[
+                r#"log_format=%h %l %u %t \"%r\" %>s %b %D "%{X-Forwarded-For}i" %{Host}i"#,
+            ])
+            .unwrap(),
+        )
+        .unwrap();
+        let v = json(
+            &p,
+            r#"10.0.0.1 - bob [10/Oct/2000:13:55:36 -0700] "GET /a?b=1 HTTP/1.1" 200 512 1234 "1.1.1.1, 2.2.2.2" example.com"#,
+        );
+        assert_eq!(v["ip"], "10.0.0.1");
+        assert_eq!(v["user"], "bob");
+        assert_eq!(v["ts"], "2000-10-10T20:55:36Z");
+        assert_eq!(v["path"], "/a");
+        assert_eq!(v["status"], 200);
+        assert_eq!(v["bytes"], 512);
+        assert_eq!(v["duration_us"], 1234);
+        assert_eq!(v["xff"], serde_json::json!(["1.1.1.1", "2.2.2.2"]));
+        assert_eq!(v["host"], "example.com");
+
+        // A line in another layout is kept as unparsed.
+        assert_eq!(json(&p, "10.0.0.1 - - garbage")["unparsed"], true);
+    }
+
+    #[test]
+    fn nginx_log_format() {
+        let p = ParserCtx::with_format(
+            r#"$remote_addr - $remote_user [$time_local] "$request" $status $body_bytes_sent "$http_referer" "$http_user_agent" $request_time "$http_x_forwarded_for""#,
+            false,
+        )
+        .unwrap();
+        let line = r#"1.2.3.4 - - [10/Oct/2000:13:55:36 -0700] "POST /api HTTP/2.0" 201 7 "-" "curl/8" 0.004 "-""#;
+        let r = p.parse_line(line).unwrap();
+        assert_eq!(r.ip, Some("1.2.3.4"));
+        assert_eq!(r.method.as_deref(), Some("POST"));
+        assert_eq!(r.status, Some(201));
+        assert_eq!(r.user_agent.as_deref(), Some("curl/8"));
+        assert_eq!(r.duration_us, Some(4000));
+        assert!(r.xff.is_none());
+    }
+
+    #[test]
+    fn log_format_request_parts() {
+        let p = ParserCtx::with_format("%a %{%s}t %m %U%q %H %>s %a", false).unwrap();
+        let r = p
+            .parse_line("::1 1700000000 GET /x?y=2 HTTP/1.1 304 10.1.1.1")
+            .unwrap();
+        assert_eq!(r.ip, Some("::1"));
+        assert_eq!(r.ts, None);
+        assert_eq!(r.ts_raw, Some("1700000000"));
+        assert_eq!(r.method.as_deref(), Some("GET"));
+        assert_eq!(r.target.as_deref(), Some("/x?y=2"));
+        assert_eq!(r.path.as_deref(), Some("/x"));
+        assert_eq!(r.query.as_deref(), Some("y=2"));
+        assert_eq!(r.protocol.as_deref(), Some("HTTP/1.1"));
+        let v = serde_json::to_value(&r).unwrap();
+        assert_eq!(v["client_ip"], "10.1.1.1");
+    }
+
+    #[test]
+    fn bad_log_format_is_an_error() {
+        let err = ParserCtx::with_format("%h %Z", false).err().unwrap();
+        assert!(err.to_string().contains("%Z"), "{err}");
+        assert!(ParserCtx::with_format("%h %", false).is_err());
+        assert!(ParserCtx::with_format("$remote_addr ${oops", false).is_err());
+    }
+
+    #[test]
+    fn unescape_borrows_when_no_backslash() {
+        assert!(matches!(unescape_logitem("plain text"), Cow::Borrowed(_)));
+        assert_eq!(unescape_logitem(r#"a\"b\\c\x41"#), "a\"b\\cA");
+    }
+
+    #[test]
+    fn unmatched_line_emits_unparsed_record() {
+        let p = new();
+        let mut out = Vec::new();
+        assert!(p.process_line_to_buf("this is not an access log line", &mut out));
+        let s = String::from_utf8(out).unwrap();
+        assert!(s.contains(r#""unparsed":true"#));
+        assert!(s.ends_with('\n'));
+    }
+
+    #[test]
+    fn empty_line_emits_nothing() {
+        let p = new();
+        let mut out = Vec::new();
+        assert!(!p.process_line_to_buf("   \r", &mut out));
+        assert!(out.is_empty());
+    }
+}
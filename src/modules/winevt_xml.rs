@@ -0,0 +1,373 @@
+use crate::core::Parser;
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(WinevtXml)
+}
+
+/// Windows event log records as XML, one `<Event>` per line
+/// (`wevtutil qe <log> /f:xml`, `evtx_dump -o xml`, `Get-WinEvent | % ToXml`).
+/// `System` becomes top-level fields, `EventData` a `Name -> value` object,
+/// `UserData` an object of its leaf elements, and `RenderingInfo` (`/rd:true`)
+/// the rendered `message`.
+pub struct WinevtXml;
+
+impl Parser for WinevtXml {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("winevt-xml")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Windows event XML (wevtutil /f:xml, evtx_dump) -> flattened JSONL")
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let line = line.trim();
+        let Some(rec) = parse_event(line) else {
+            return false;
+        };
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+/* -------------------- Minimal XML tree -------------------- */
+
+/// Just enough XML for event records: elements, attributes, text, CDATA
+/// and entities. Namespace prefixes are dropped from names; comments,
+/// processing instructions and doctypes are skipped.
+#[derive(Debug, Default)]
+pub(crate) struct Element<'a> {
+    pub name: &'a str,
+    pub attrs: Vec<(&'a str, Cow<'a, str>)>,
+    pub children: Vec<Element<'a>>,
+    pub text: String,
+}
+
+impl<'a> Element<'a> {
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(k, _)| *k == name)
+            .map(|(_, v)| &**v)
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Element<'a>> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    /// Parse one document holding a single root element.
+    pub fn parse(s: &'a str) -> Option<Element<'a>> {
+        let mut stack: Vec<Element<'a>> = Vec::new();
+        let mut rest = s;
+        loop {
+            let lt = rest.find('<')?;
+            if let Some(open) = stack.last_mut() {
+                open.text.push_str(&unescape(&rest[..lt]));
+            } else if !rest[..lt].trim().is_empty() {
+                return None;
+            }
+            rest = &rest[lt..];
+
+            if let Some(r) = rest.strip_prefix("<!--") {
+                rest = &r[r.find("-->")? + 3..];
+            } else if let Some(r) = rest.strip_prefix("<![CDATA[") {
+                let end = r.find("]]>")?;
+                stack.last_mut()?.text.push_str(&r[..end]);
+                rest = &r[end + 3..];
+            } else if rest.starts_with("<?") || rest.starts_with("<!") {
+                rest = &rest[rest.find('>')? + 1..];
+            } else if let Some(r) = rest.strip_prefix("</") {
+                let end = r.find('>')?;
+                let done = stack.pop()?;
+                if local_name(r[..end].trim()) != done.name {
+                    return None;
+                }
+                rest = &r[end + 1..];
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(done),
+                    None => return Some(done),
+                }
+            } else {
+                let (el, closed, r) = open_tag(&rest[1..])?;
+                rest = r;
+                if !closed {
+                    stack.push(el);
+                    continue;
+                }
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(el),
+                    None => return Some(el),
+                }
+            }
+        }
+    }
+}
+
+/// `name a='1' b="2"` up to `>` or `/>`: the element, whether it was
+/// self-closing, and the rest of the input.
+fn open_tag(s: &str) -> Option<(Element<'_>, bool, &str)> {
+    let end = s.find(|c: char| c.is_whitespace() || c == '>' || c == '/')?;
+    let mut el = Element {
+        name: local_name(&s[..end]),
+        ..Default::default()
+    };
+    if el.name.is_empty() {
+        return None;
+    }
+    let mut rest = &s[end..];
+    loop {
+        rest = rest.trim_start();
+        if let Some(r) = rest.strip_prefix("/>") {
+            return Some((el, true, r));
+        }
+        if let Some(r) = rest.strip_prefix('>') {
+            return Some((el, false, r));
+        }
+        let (key, r) = rest.split_once('=')?;
+        let r = r.trim_start();
+        let quote = r.chars().next().filter(|&q| q == '"' || q == '\'')?;
+        let (value, r) = r[1..].split_once(quote)?;
+        let key = key.trim();
+        // Namespace declarations are noise in the output.
+        if key != "xmlns" && !key.starts_with("xmlns:") {
+            el.attrs.push((local_name(key), unescape(value)));
+        }
+        rest = r;
+    }
+}
+
+fn local_name(name: &str) -> &str {
+    name.rsplit_once(':').map_or(name, |(_, n)| n)
+}
+
+/// Decode the predefined entities and character references.
+fn unescape(s: &str) -> Cow<'_, str> {
+    if !s.contains('&') {
+        return Cow::Borrowed(s);
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest.find(';').and_then(|semi| {
+            let c = match &rest[1..semi] {
+                "lt" => '<',
+                "gt" => '>',
+                "amp" => '&',
+                "quot" => '"',
+                "apos" => '\'',
+                e => {
+                    let code = match e.strip_prefix("#x").or_else(|| e.strip_prefix("#X")) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => e.strip_prefix('#')?.parse().ok()?,
+                    };
+                    char::from_u32(code)?
+                }
+            };
+            Some((c, semi + 1))
+        });
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    Cow::Owned(out)
+}
+
+/* -------------------- Event mapping -------------------- */
+
+/// `System` children as `(element, attribute or text, output key)`.
+const SYSTEM_FIELDS: &[(&str, Option<&str>, &str)] = &[
+    ("TimeCreated", Some("SystemTime"), "ts"),
+    ("Provider", Some("Name"), "provider"),
+    ("Provider", Some("Guid"), "provider_guid"),
+    ("Provider", Some("EventSourceName"), "event_source"),
+    ("EventID", None, "event_id"),
+    ("EventID", Some("Qualifiers"), "qualifiers"),
+    ("Version", None, "version"),
+    ("Level", None, "level"),
+    ("Task", None, "task"),
+    ("Opcode", None, "opcode"),
+    ("Keywords", None, "keywords"),
+    ("EventRecordID", None, "record_id"),
+    ("Correlation", Some("ActivityID"), "activity_id"),
+    (
+        "Correlation",
+        Some("RelatedActivityID"),
+        "related_activity_id",
+    ),
+    ("Execution", Some("ProcessID"), "process_id"),
+    ("Execution", Some("ThreadID"), "thread_id"),
+    ("Channel", None, "channel"),
+    ("Computer", None, "computer"),
+    ("Security", Some("UserID"), "user_sid"),
+];
+
+const INT_FIELDS: &[&str] = &[
+    "event_id",
+    "qualifiers",
+    "version",
+    "level",
+    "task",
+    "opcode",
+    "record_id",
+    "process_id",
+    "thread_id",
+];
+
+pub(crate) fn parse_event(line: &str) -> Option<Map<String, Value>> {
+    if !line.starts_with("<Event") && !line.starts_with("<?xml") {
+        return None;
+    }
+    let event = Element::parse(line)?;
+    if event.name != "Event" {
+        return None;
+    }
+    let system = event.child("System")?;
+
+    let mut rec = Map::new();
+    for &(element, attr, key) in SYSTEM_FIELDS {
+        let Some(el) = system.child(element) else {
+            continue;
+        };
+        let value = match attr {
+            Some(a) => el.attr(a),
+            None => Some(el.text.trim()).filter(|t| !t.is_empty()),
+        };
+        let Some(value) = value else {
+            continue;
+        };
+        let value = match value.parse::<u64>() {
+            Ok(n) if INT_FIELDS.contains(&key) => n.into(),
+            _ => value.into(),
+        };
+        rec.insert(key.into(), value);
+    }
+
+    if let Some(data) = event.child("EventData") {
+        rec.insert("event_data".into(), event_data(data).into());
+    }
+    if let Some(user) = event.child("UserData") {
+        let mut fields = Map::new();
+        for c in &user.children {
+            leaves(c, &mut fields);
+        }
+        rec.insert("user_data".into(), fields.into());
+    }
+    if let Some(info) = event.child("RenderingInfo") {
+        for (element, key) in [
+            ("Message", "message"),
+            ("Level", "level_name"),
+            ("Task", "task_name"),
+            ("Opcode", "opcode_name"),
+        ] {
+            if let Some(el) = info.child(element) {
+                rec.insert(key.into(), el.text.trim().into());
+            }
+        }
+    }
+    Some(rec)
+}
+
+/// `<Data Name='X'>v</Data>` -> `X: v`. Unnamed `Data` (classic events)
+/// become `param1`, `param2`, ... in order, and `Binary` stays hex.
+fn event_data(data: &Element) -> Map<String, Value> {
+    let mut fields = Map::new();
+    let mut unnamed = 0;
+    for d in &data.children {
+        let key = match (d.name, d.attr("Name")) {
+            ("Data", Some(name)) => name.to_string(),
+            ("Data", None) => {
+                unnamed += 1;
+                format!("param{unnamed}")
+            }
+            (other, _) => other.to_string(),
+        };
+        fields.insert(key, d.text.clone().into());
+    }
+    fields
+}
+
+/// Leaf elements (and attributes) of `UserData`, by local name.
+fn leaves(el: &Element, out: &mut Map<String, Value>) {
+    for (k, v) in &el.attrs {
+        out.insert((*k).to_string(), (**v).into());
+    }
+    if el.children.is_empty() {
+        if el.attrs.is_empty() || !el.text.trim().is_empty() {
+            out.insert(el.name.to_string(), el.text.clone().into());
+        }
+        return;
+    }
+    for c in &el.children {
+        leaves(c, out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(line: &str) -> Option<serde_json::Value> {
+        let mut out = Vec::new();
+        WinevtXml
+            .process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn flattens_system_and_event_data() {
+        let v = run(r#"<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Microsoft-Windows-Security-Auditing' Guid='{54849625-5478-4994-A5BA-3E3B0328C30D}'/><EventID>4624</EventID><Version>2</Version><Level>0</Level><Task>12544</Task><Opcode>0</Opcode><Keywords>0x8020000000000000</Keywords><TimeCreated SystemTime='2024-01-02T03:04:05.1234567Z'/><EventRecordID>987</EventRecordID><Correlation ActivityID='{A1}'/><Execution ProcessID='4' ThreadID='5'/><Channel>Security</Channel><Computer>DC01.corp.local</Computer><Security/></System><EventData><Data Name='TargetUserName'>alice</Data><Data Name='LogonType'>3</Data><Data Name='IpAddress'>10.0.0.5</Data><Data Name='ProcessName'>C:\Windows\&lt;x&gt; &amp; y</Data></EventData><RenderingInfo Culture='en-US'><Message>An account was successfully logged on.</Message><Level>Information</Level></RenderingInfo></Event>"#).unwrap();
+        assert_eq!(v["ts"], "2024-01-02T03:04:05.1234567Z");
+        assert_eq!(v["provider"], "Microsoft-Windows-Security-Auditing");
+        assert_eq!(v["event_id"], 4624);
+        assert_eq!(v["record_id"], 987);
+        assert_eq!(v["keywords"], "0x8020000000000000");
+        assert_eq!(v["process_id"], 4);
+        assert_eq!(v["channel"], "Security");
+        assert_eq!(v["computer"], "DC01.corp.local");
+        assert!(v.get("user_sid").is_none());
+        assert_eq!(v["event_data"]["TargetUserName"], "alice");
+        assert_eq!(v["event_data"]["LogonType"], "3");
+        assert_eq!(v["event_data"]["ProcessName"], r"C:\Windows\<x> & y");
+        assert_eq!(v["message"], "An account was successfully logged on.");
+        assert_eq!(v["level_name"], "Information");
+    }
+
+    #[test]
+    fn classic_and_user_data_events() {
+        let v = run(r#"<Event xmlns="http://schemas.microsoft.com/win/2004/08/events/event"><System><Provider Name="Service Control Manager" EventSourceName="Service Control Manager"/><EventID Qualifiers="16384">7036</EventID><TimeCreated SystemTime="2024-01-02T03:04:05.000Z"/><Channel>System</Channel><Computer>PC</Computer><Security UserID="S-1-5-18"/></System><EventData><Data>Windows Update</Data><Data>running</Data><Binary>770075</Binary></EventData></Event>"#).unwrap();
+        assert_eq!(v["event_id"], 7036);
+        assert_eq!(v["qualifiers"], 16384);
+        assert_eq!(v["user_sid"], "S-1-5-18");
+        assert_eq!(v["event_data"]["param1"], "Windows Update");
+        assert_eq!(v["event_data"]["param2"], "running");
+        assert_eq!(v["event_data"]["Binary"], "770075");
+
+        let v = run(r#"<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><EventID>1102</EventID><Channel>Security</Channel></System><UserData><LogFileCleared xmlns='http://manifests.microsoft.com/win/2004/08/windows/eventlog'><SubjectUserSid>S-1-5-21-1</SubjectUserSid><SubjectUserName><![CDATA[admin]]></SubjectUserName></LogFileCleared></UserData></Event>"#).unwrap();
+        assert_eq!(v["event_id"], 1102);
+        assert_eq!(v["user_data"]["SubjectUserSid"], "S-1-5-21-1");
+        assert_eq!(v["user_data"]["SubjectUserName"], "admin");
+    }
+
+    #[test]
+    fn rejects_broken_xml_and_other_lines() {
+        assert!(run("<Event><System><EventID>1</EventID></Event>").is_none());
+        assert!(run("<Events>").is_none());
+        assert!(run("<Event><NoSystem/></Event>").is_none());
+        assert!(run("4624 An account was logged on").is_none());
+    }
+}
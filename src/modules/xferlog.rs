@@ -0,0 +1,271 @@
+use crate::core::Parser;
+use crate::modules::common::month_number;
+use serde::Serialize;
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(Xferlog)
+}
+
+/// FTP transfer logs: the classic `xferlog` format (wu-ftpd, ProFTPD,
+/// vsftpd with `xferlog_std_format`) and vsftpd's own `UPLOAD` / `DOWNLOAD`
+/// / `DELETE` lines. One record per file moved, for exfiltration review.
+pub struct Xferlog;
+
+#[derive(Default, Serialize)]
+struct Record<'a> {
+    /// Server local time, `2024-01-20T10:11:12`.
+    ts: String,
+    /// `incoming` (upload), `outgoing` (download) or `deleted`.
+    direction: &'static str,
+    remote_host: &'a str,
+    user: Option<&'a str>,
+    filename: &'a str,
+    bytes: Option<u64>,
+    /// Seconds the transfer took.
+    transfer_time: Option<f64>,
+    /// `complete` or `incomplete` (vsftpd `FAIL`).
+    completion: &'static str,
+    /// `ascii` or `binary`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    transfer_type: Option<&'static str>,
+    /// `compressed`, `uncompressed` or `tar` when the server converted the
+    /// file on the fly.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    special_action: Option<&'static str>,
+    /// `anonymous`, `guest` or `real`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    access_mode: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    service: Option<&'a str>,
+    /// RFC 931 (ident) user id, when the server looked it up.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ident_user: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pid: Option<u32>,
+}
+
+/// Split `s` after its first whitespace-separated token.
+fn token(s: &str) -> Option<(&str, &str)> {
+    let s = s.trim_start();
+    let end = s.find(' ').unwrap_or(s.len());
+    (end > 0).then(|| (&s[..end], &s[end..]))
+}
+
+/// `Sat Jan  5 10:11:12 2024 rest` -> (`2024-01-05T10:11:12`, `rest`).
+fn split_time(line: &str) -> Option<(String, &str)> {
+    let (_, rest) = token(line)?;
+    let (month, rest) = token(rest)?;
+    let (day, rest) = token(rest)?;
+    let (time, rest) = token(rest)?;
+    let (year, rest) = token(rest)?;
+    let month = month_number(month)?;
+    let day: u8 = day.parse().ok()?;
+    let year: u16 = year.parse().ok()?;
+    let b = time.as_bytes();
+    if b.len() != 8 || b[2] != b':' || b[5] != b':' {
+        return None;
+    }
+    Some((format!("{year:04}-{month:02}-{day:02}T{time}"), rest))
+}
+
+/// `5 192.0.2.10 1048576 /pub/a file.zip b _ o r alice ftp 0 * c`. The
+/// filename may hold spaces, so the nine trailing fields are taken from the
+/// right.
+fn standard<'a>(rec: &mut Record<'a>, rest: &'a str) -> Option<()> {
+    let (transfer_time, rest) = token(rest)?;
+    let (remote_host, rest) = token(rest)?;
+    let (bytes, rest) = token(rest)?;
+    let fields: Vec<&str> = rest.trim().rsplitn(10, ' ').collect();
+    let [completion, ident, _auth, service, user, mode, direction, action, kind, filename] =
+        fields[..]
+    else {
+        return None;
+    };
+    rec.transfer_time = Some(transfer_time.parse().ok()?);
+    rec.remote_host = remote_host;
+    rec.bytes = Some(bytes.parse().ok()?);
+    rec.filename = filename;
+    rec.transfer_type = Some(match kind {
+        "a" => "ascii",
+        "b" => "binary",
+        _ => return None,
+    });
+    rec.special_action = match action {
+        "C" => Some("compressed"),
+        "U" => Some("uncompressed"),
+        "T" => Some("tar"),
+        _ => None,
+    };
+    rec.direction = match direction {
+        "o" => "outgoing",
+        "i" => "incoming",
+        "d" => "deleted",
+        _ => return None,
+    };
+    rec.access_mode = Some(match mode {
+        "a" => "anonymous",
+        "g" => "guest",
+        "r" => "real",
+        _ => return None,
+    });
+    rec.user = Some(user);
+    rec.service = Some(service);
+    rec.ident_user = Some(ident).filter(|i| *i != "*");
+    rec.completion = match completion {
+        "c" => "complete",
+        "i" => "incomplete",
+        _ => return None,
+    };
+    Some(())
+}
+
+/// `[pid 1234] [alice] OK DOWNLOAD: Client "192.0.2.10", "/pub/file.zip",
+/// 1048576 bytes, 512.00Kbyte/sec`.
+fn vsftpd<'a>(rec: &mut Record<'a>, rest: &'a str) -> Option<()> {
+    let rest = rest.trim_start().strip_prefix("[pid ")?;
+    let (pid, rest) = rest.split_once("] ")?;
+    rec.pid = Some(pid.parse().ok()?);
+    let (user, rest) = rest.strip_prefix('[')?.split_once("] ")?;
+    rec.user = Some(user);
+    let (status, rest) = rest.split_once(' ')?;
+    rec.completion = match status {
+        "OK" => "complete",
+        "FAIL" => "incomplete",
+        _ => return None,
+    };
+    let (verb, rest) = rest.split_once(": Client \"")?;
+    rec.direction = match verb {
+        "UPLOAD" => "incoming",
+        "DOWNLOAD" => "outgoing",
+        "DELETE" => "deleted",
+        _ => return None,
+    };
+    let (host, rest) = rest.split_once('"')?;
+    rec.remote_host = host.strip_prefix("::ffff:").unwrap_or(host);
+    let (filename, rest) = rest.strip_prefix(", \"")?.split_once('"')?;
+    rec.filename = filename;
+    if let Some(rest) = rest.strip_prefix(", ") {
+        let (bytes, rest) = rest.split_once(" bytes")?;
+        let bytes: u64 = bytes.parse().ok()?;
+        rec.bytes = Some(bytes);
+        // Seconds from the size and rate, as xferlog reports them.
+        let rate = rest
+            .strip_prefix(", ")
+            .and_then(|r| r.strip_suffix("Kbyte/sec"))
+            .and_then(|r| r.parse::<f64>().ok())
+            .filter(|r| *r > 0.0);
+        rec.transfer_time = rate.map(|r| (bytes as f64 / 1024.0 / r * 1000.0).round() / 1000.0);
+    }
+    Some(())
+}
+
+impl Parser for Xferlog {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("xferlog")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses xferlog and vsftpd transfer lines -> FTP transfer JSONL")
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["remote_host".to_string()]
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let Some((ts, rest)) = split_time(line) else {
+            return false;
+        };
+        let mut rec = Record {
+            ts,
+            ..Default::default()
+        };
+        let parsed = if rest.trim_start().starts_with("[pid ") {
+            vsftpd(&mut rec, rest)
+        } else {
+            standard(&mut rec, rest)
+        };
+        if parsed.is_none() {
+            return false;
+        }
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn run(line: &str) -> Option<Value> {
+        let mut out = Vec::new();
+        Xferlog
+            .process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn parses_standard_xferlog() {
+        let v = run("Sat Jan  6 10:11:12 2024 5 192.0.2.10 1048576 /home/alice/q4 report.zip b _ o r alice ftp 0 * c").unwrap();
+        assert_eq!(v["ts"], "2024-01-06T10:11:12");
+        assert_eq!(v["transfer_time"], 5.0);
+        assert_eq!(v["remote_host"], "192.0.2.10");
+        assert_eq!(v["bytes"], 1048576);
+        assert_eq!(v["filename"], "/home/alice/q4 report.zip");
+        assert_eq!(v["transfer_type"], "binary");
+        assert!(v.get("special_action").is_none());
+        assert_eq!(v["direction"], "outgoing");
+        assert_eq!(v["access_mode"], "real");
+        assert_eq!(v["user"], "alice");
+        assert_eq!(v["service"], "ftp");
+        assert!(v.get("ident_user").is_none());
+        assert_eq!(v["completion"], "complete");
+
+        let v = run(
+            "Sat Jan 20 10:11:12 2024 0 host.example 12 /incoming/x.txt a C i a guest@ ftp 1 bob i",
+        )
+        .unwrap();
+        assert_eq!(v["direction"], "incoming");
+        assert_eq!(v["special_action"], "compressed");
+        assert_eq!(v["access_mode"], "anonymous");
+        assert_eq!(v["ident_user"], "bob");
+        assert_eq!(v["completion"], "incomplete");
+        assert!(run("Sat Jan 20 10:11:12 2024 0 host 12 /x a _ x r u ftp 0 * c").is_none());
+    }
+
+    #[test]
+    fn parses_vsftpd_log() {
+        let v = run(r#"Sat Jan 20 10:11:12 2024 [pid 1234] [alice] OK DOWNLOAD: Client "::ffff:192.0.2.10", "/pub/file.zip", 1048576 bytes, 512.00Kbyte/sec"#).unwrap();
+        assert_eq!(v["ts"], "2024-01-20T10:11:12");
+        assert_eq!(v["pid"], 1234);
+        assert_eq!(v["user"], "alice");
+        assert_eq!(v["direction"], "outgoing");
+        assert_eq!(v["remote_host"], "192.0.2.10");
+        assert_eq!(v["filename"], "/pub/file.zip");
+        assert_eq!(v["bytes"], 1048576);
+        assert_eq!(v["transfer_time"], 2.0);
+        assert_eq!(v["completion"], "complete");
+
+        let v = run(r#"Sat Jan 20 10:11:12 2024 [pid 1234] [alice] FAIL UPLOAD: Client "192.0.2.10", "/pub/up.bin", 65536 bytes, 0.00Kbyte/sec"#).unwrap();
+        assert_eq!(v["direction"], "incoming");
+        assert_eq!(v["completion"], "incomplete");
+        assert_eq!(v["bytes"], 65536);
+        assert_eq!(v["transfer_time"], Value::Null);
+        let v = run(r#"Sat Jan 20 10:11:12 2024 [pid 1234] [alice] OK DELETE: Client "192.0.2.10", "/pub/old.txt""#).unwrap();
+        assert_eq!(v["direction"], "deleted");
+        assert_eq!(v["bytes"], Value::Null);
+        assert!(run(
+            r#"Sat Jan 20 10:11:12 2024 [pid 1234] [alice] OK LOGIN: Client "192.0.2.10""#
+        )
+        .is_none());
+        assert!(
+            run(r#"Sat Jan 20 10:11:12 2024 [pid 1233] CONNECT: Client "192.0.2.10""#).is_none()
+        );
+    }
+}
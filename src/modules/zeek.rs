@@ -0,0 +1,331 @@
+use crate::core::{FieldType, Parser};
+use crate::modules::common::epoch_to_rfc3339;
+use anyhow::Result;
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+use std::borrow::Cow;
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(Zeek::default())
+}
+
+/// Zeek (Bro) TSV logs: `conn.log`, `dns.log`, `http.log`, ... The `#separator`,
+/// `#set_separator`, `#empty_field`, `#unset_field`, `#path`, `#fields` and
+/// `#types` headers at the top of each file drive the parsing.
+pub struct Zeek {
+    separator: String,
+    set_separator: String,
+    empty: String,
+    unset: String,
+    path: Option<String>,
+    fields: Vec<(String, Kind)>,
+}
+
+impl Default for Zeek {
+    fn default() -> Self {
+        Self {
+            separator: "\t".into(),
+            set_separator: ",".into(),
+            empty: "(empty)".into(),
+            unset: "-".into(),
+            path: None,
+            fields: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Kind {
+    /// Epoch seconds, emitted as RFC 3339.
+    Time,
+    Int,
+    /// `double` and `interval`.
+    Float,
+    Bool,
+    /// `string`, `addr`, `subnet`, `enum`, ...
+    Text,
+    /// `set[T]` / `vector[T]`.
+    List(Box<Kind>),
+}
+
+impl Kind {
+    fn parse(t: &str) -> Self {
+        if let Some(inner) = t
+            .strip_prefix("set[")
+            .or_else(|| t.strip_prefix("vector["))
+            .and_then(|i| i.strip_suffix(']'))
+        {
+            return Kind::List(Box::new(Kind::parse(inner)));
+        }
+        match t {
+            "time" => Kind::Time,
+            "count" | "int" | "port" => Kind::Int,
+            "double" | "interval" => Kind::Float,
+            "bool" => Kind::Bool,
+            _ => Kind::Text,
+        }
+    }
+}
+
+/// Decode `\xNN` escapes (header values and string fields).
+fn unescape(s: &str) -> Cow<'_, str> {
+    if !s.contains("\\x") {
+        return Cow::Borrowed(s);
+    }
+    let b = s.as_bytes();
+    let mut out = Vec::with_capacity(b.len());
+    let mut i = 0;
+    while i < b.len() {
+        if b[i] == b'\\'
+            && b.get(i + 1) == Some(&b'x')
+            && let Some(byte) = b
+                .get(i + 2..i + 4)
+                .and_then(|h| std::str::from_utf8(h).ok())
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+        {
+            out.push(byte);
+            i += 4;
+        } else {
+            out.push(b[i]);
+            i += 1;
+        }
+    }
+    Cow::Owned(String::from_utf8_lossy(&out).into_owned())
+}
+
+impl Parser for Zeek {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("zeek")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses Zeek TSV logs (#fields/#types headers) -> typed JSONL")
+    }
+
+    fn for_input(&self, head: &[&str]) -> Result<Option<Box<dyn Parser>>> {
+        let mut z = Zeek::default();
+        let mut types = Vec::new();
+        let mut names = Vec::new();
+
+        for line in head.iter().take_while(|l| l.starts_with('#')) {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            // `#separator` is itself space-separated, everything else uses it.
+            if let Some(sep) = line.strip_prefix("#separator ") {
+                z.separator = unescape(sep.trim()).into_owned();
+                continue;
+            }
+            let Some((directive, value)) = line.split_once(z.separator.as_str()) else {
+                continue;
+            };
+            match directive {
+                "#set_separator" => z.set_separator = unescape(value).into_owned(),
+                "#empty_field" => z.empty = value.to_string(),
+                "#unset_field" => z.unset = value.to_string(),
+                "#path" => z.path = Some(value.to_string()),
+                "#fields" => {
+                    names = value
+                        .split(z.separator.as_str())
+                        .map(String::from)
+                        .collect()
+                }
+                "#types" => types = value.split(z.separator.as_str()).map(Kind::parse).collect(),
+                _ => {}
+            }
+        }
+
+        if names.is_empty() {
+            return Ok(None);
+        }
+        types.resize(names.len(), Kind::Text);
+        z.fields = names.into_iter().zip(types).collect();
+        Ok(Some(Box::new(z)))
+    }
+
+    /// `detect` sees raw lines: the header directives, or data rows whose
+    /// first column is an epoch timestamp.
+    fn recognizes(&self, line: &str) -> bool {
+        if ["#separator", "#fields", "#types", "#path"]
+            .iter()
+            .any(|d| line.starts_with(d))
+        {
+            return true;
+        }
+        let mut cols = line.split('\t');
+        cols.next()
+            .is_some_and(|ts| ts.contains('.') && epoch_to_rfc3339(ts).is_some())
+            && cols.count() >= 2
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        vec!["id.orig_h".to_string(), "id.resp_h".to_string()]
+    }
+
+    fn fields(&self) -> Vec<(String, FieldType)> {
+        let path = self
+            .path
+            .as_ref()
+            .map(|_| ("_path".to_string(), FieldType::Text));
+        path.into_iter()
+            .chain(self.fields.iter().map(|(name, kind)| {
+                let ty = match kind {
+                    Kind::Int => FieldType::Int,
+                    Kind::Float => FieldType::Float,
+                    Kind::Bool => FieldType::Bool,
+                    Kind::Time | Kind::Text | Kind::List(_) => FieldType::Text,
+                };
+                (name.clone(), ty)
+            }))
+            .collect()
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        // Header (or `#close`) lines, and files whose header was never seen.
+        if line.starts_with('#') || self.fields.is_empty() {
+            return false;
+        }
+
+        let values: Vec<&str> = line.split(self.separator.as_str()).collect();
+        if values.len() != self.fields.len() {
+            return false;
+        }
+        let rec = Record { zeek: self, values };
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+struct Record<'a> {
+    zeek: &'a Zeek,
+    values: Vec<&'a str>,
+}
+
+struct Value<'a> {
+    zeek: &'a Zeek,
+    kind: &'a Kind,
+    raw: &'a str,
+}
+
+impl Serialize for Value<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let z = self.zeek;
+        let raw = self.raw;
+        if raw == z.unset {
+            return serializer.serialize_unit();
+        }
+
+        match self.kind {
+            Kind::List(inner) => {
+                if raw == z.empty {
+                    return serializer.serialize_seq(Some(0))?.end();
+                }
+                let mut seq = serializer.serialize_seq(None)?;
+                for item in raw.split(z.set_separator.as_str()) {
+                    seq.serialize_element(&Value {
+                        zeek: z,
+                        kind: inner,
+                        raw: item,
+                    })?;
+                }
+                seq.end()
+            }
+            _ if raw == z.empty => serializer.serialize_str(""),
+            Kind::Time => match epoch_to_rfc3339(raw) {
+                Some(ts) => serializer.serialize_str(&ts),
+                None => serializer.serialize_str(raw),
+            },
+            Kind::Int => match raw.parse::<i64>() {
+                Ok(n) => serializer.serialize_i64(n),
+                Err(_) => serializer.serialize_str(raw),
+            },
+            Kind::Float => match raw.parse::<f64>() {
+                Ok(n) => serializer.serialize_f64(n),
+                Err(_) => serializer.serialize_str(raw),
+            },
+            Kind::Bool => match raw {
+                "T" => serializer.serialize_bool(true),
+                "F" => serializer.serialize_bool(false),
+                _ => serializer.serialize_str(raw),
+            },
+            Kind::Text => serializer.serialize_str(&unescape(raw)),
+        }
+    }
+}
+
+impl Serialize for Record<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        if let Some(path) = &self.zeek.path {
+            map.serialize_entry("_path", path)?;
+        }
+        for ((name, kind), raw) in self.zeek.fields.iter().zip(&self.values) {
+            map.serialize_entry(
+                name,
+                &Value {
+                    zeek: self.zeek,
+                    kind,
+                    raw,
+                },
+            )?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEAD: &[&str] = &[
+        r"#separator \x09",
+        "#set_separator\t,",
+        "#empty_field\t(empty)",
+        "#unset_field\t-",
+        "#path\tconn",
+        "#fields\tts\tuid\tid.orig_h\tid.orig_p\tduration\tlocal_orig\ttunnel_parents\tservice",
+        "#types\ttime\tstring\taddr\tport\tinterval\tbool\tset[string]\tstring",
+    ];
+
+    fn run(p: &dyn Parser, line: &str) -> Option<serde_json::Value> {
+        let mut out = Vec::new();
+        p.process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn headers_drive_typed_output() {
+        let p = new().for_input(HEAD).unwrap().unwrap();
+        let v = run(
+            &*p,
+            "1320279566.452687\tCwQ8Hm2\t10.0.0.2\t49285\t0.000417\tT\t(empty)\t-",
+        )
+        .unwrap();
+
+        assert_eq!(v["_path"], "conn");
+        assert_eq!(v["ts"], "2011-11-03T00:19:26.452687Z");
+        assert_eq!(v["uid"], "CwQ8Hm2");
+        assert_eq!(v["id.orig_h"], "10.0.0.2");
+        assert_eq!(v["id.orig_p"], 49285);
+        assert_eq!(v["duration"], 0.000417);
+        assert_eq!(v["local_orig"], true);
+        assert_eq!(v["tunnel_parents"], serde_json::json!([]));
+        assert!(v["service"].is_null());
+
+        let v = run(&*p, "1.5\tC1\t::1\t1\t-\tF\ta,b\thttp\\x20x").unwrap();
+        assert_eq!(v["tunnel_parents"], serde_json::json!(["a", "b"]));
+        assert_eq!(v["service"], "http x");
+    }
+
+    #[test]
+    fn skips_headers_and_needs_them() {
+        let p = new().for_input(HEAD).unwrap().unwrap();
+        assert!(run(&*p, "#close\t2011-11-03-00-19-26").is_none());
+        assert!(run(&*p, "1.5\ttoo\tfew").is_none());
+
+        assert!(new().for_input(&["a\tb"]).unwrap().is_none());
+        assert!(run(&*new(), "1.5\tC1\t::1\t1\t-\tF\ta,b\thttp").is_none());
+        assert!(new().recognizes("1320279566.452687\tCwQ8Hm2\t10.0.0.2"));
+    }
+}
@@ -0,0 +1,374 @@
+use crate::core::{ModuleOptions, OptionSpec, Parser};
+use crate::modules::common::{epoch_to_rfc3339, percent_decode};
+use anyhow::Result;
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use time::{format_description::well_known::Rfc3339, PrimitiveDateTime};
+
+pub fn new() -> Box<dyn Parser> {
+    Box::new(Zscaler::with_format(DEFAULT_FORMAT).expect("default format"))
+}
+
+/// Zscaler NSS web log feeds. The feed's output format string
+/// (`%s{time}\t%s{login}\t%s{eurl}...`) gives the columns; tab and comma
+/// templates, quoted or not, are split like CSV, anything else on the
+/// literal text between fields.
+pub struct Zscaler {
+    fields: Vec<Field>,
+    layout: Layout,
+}
+
+const OPTIONS: &[OptionSpec] = &[OptionSpec {
+    key: "format",
+    help: "NSS feed output format string, as entered in the feed settings \
+           (default: a tab-separated web feed, see README)",
+}];
+
+/// Tab-separated web feed with the commonly exported fields.
+const DEFAULT_FORMAT: &str = "%s{time}\\t%s{login}\\t%s{proto}\\t%s{eurl}\\t%s{action}\\t\
+    %s{appname}\\t%s{appclass}\\t%d{reqsize}\\t%d{respsize}\\t%d{transactionsize}\\t\
+    %s{urlsupercat}\\t%s{urlcat}\\t%s{malwarecat}\\t%s{malwareclass}\\t%s{threatname}\\t\
+    %d{riskscore}\\t%s{rulelabel}\\t%s{location}\\t%s{dept}\\t%s{cip}\\t%s{sip}\\t\
+    %s{reqmethod}\\t%s{respcode}\\t%s{eua}\\t%s{ereferer}\\n";
+
+/// NSS field -> record field. The `e` variants are URL-encoded and decoded.
+const RENAMES: &[(&str, &str)] = &[
+    ("login", "user"),
+    ("elogin", "user"),
+    ("url", "url"),
+    ("eurl", "url"),
+    ("cip", "src_ip"),
+    ("sip", "dst_ip"),
+    ("ua", "user_agent"),
+    ("eua", "user_agent"),
+    ("referer", "referer"),
+    ("ereferer", "referer"),
+    ("reqmethod", "method"),
+    ("respcode", "status"),
+    ("malwarecat", "malware_category"),
+    ("malwareclass", "malware_class"),
+    ("threatname", "threat_name"),
+    ("reqsize", "bytes_out"),
+    ("respsize", "bytes_in"),
+    ("transactionsize", "bytes"),
+];
+
+/// URL-encoded variants of fields, named without the `e` unless renamed.
+const ENCODED: &[&str] = &[
+    "eurl",
+    "ereferer",
+    "eua",
+    "elogin",
+    "edept",
+    "elocation",
+    "edevicehostname",
+    "edeviceowner",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Text,
+    Encoded,
+    Int,
+    /// `%s{time}`: `Mon Jun 20 15:29:11 2016`.
+    Time,
+    /// `%d{epochtime}`.
+    Epoch,
+}
+
+struct Field {
+    key: String,
+    kind: Kind,
+}
+
+enum Layout {
+    /// Fields separated by one byte, each maybe in double quotes.
+    Delimited { delim: u8, quoted: bool },
+    /// Literal text before, between and after the fields
+    /// (`fields.len() + 1` entries).
+    Template { literals: Vec<String> },
+}
+
+impl Zscaler {
+    fn with_format(spec: &str) -> Result<Self> {
+        let (literals, fields) = parse_format(spec)?;
+        let layout = match delimiter(&literals) {
+            Some((delim, quoted)) => Layout::Delimited { delim, quoted },
+            None => Layout::Template { literals },
+        };
+        Ok(Self { fields, layout })
+    }
+
+    /// The raw value of each field, or `None` when the line doesn't fit.
+    fn split<'a>(&self, line: &'a str) -> Option<Vec<Cow<'a, str>>> {
+        let values: Vec<Cow<str>> = match &self.layout {
+            Layout::Delimited { delim, quoted } => {
+                let mut rdr = csv::ReaderBuilder::new()
+                    .has_headers(false)
+                    .flexible(true)
+                    .delimiter(*delim)
+                    .quoting(*quoted)
+                    .from_reader(line.as_bytes());
+                let row = rdr.records().next()?.ok()?;
+                row.iter().map(|v| Cow::Owned(v.to_string())).collect()
+            }
+            Layout::Template { literals } => {
+                let mut rest = line.strip_prefix(literals[0].as_str())?;
+                let mut values = Vec::with_capacity(self.fields.len());
+                for (i, lit) in literals[1..].iter().enumerate() {
+                    let last = i + 2 == literals.len();
+                    let end = if lit.is_empty() && last {
+                        rest.len()
+                    } else if last {
+                        rest.strip_suffix(lit.as_str())?.len()
+                    } else {
+                        rest.find(lit.as_str())?
+                    };
+                    values.push(Cow::Borrowed(&rest[..end]));
+                    rest = &rest[end + lit.len()..];
+                }
+                values
+            }
+        };
+        (values.len() == self.fields.len()).then_some(values)
+    }
+}
+
+/// `%s{time}\t%02d{hh}` -> the literals around the fields, and the fields.
+/// `\t`, `\n` and `\"` are unescaped; a trailing newline is dropped.
+fn parse_format(spec: &str) -> Result<(Vec<String>, Vec<Field>)> {
+    let spec = spec
+        .replace("\\t", "\t")
+        .replace("\\n", "\n")
+        .replace("\\\"", "\"");
+    let spec = spec.trim_end_matches(['\n', '\r']);
+    let mut literals = vec![String::new()];
+    let mut fields = Vec::new();
+    let mut rest = spec;
+    while let Some(pos) = rest.find('%') {
+        literals.last_mut().unwrap().push_str(&rest[..pos]);
+        rest = &rest[pos + 1..];
+        if let Some(after) = rest.strip_prefix('%') {
+            literals.last_mut().unwrap().push('%');
+            rest = after;
+            continue;
+        }
+        // `%s{...}`, `%d{...}`, `%02d{...}`, `%-5s{...}`
+        let spec_end = rest
+            .find('{')
+            .ok_or_else(|| anyhow::anyhow!("format: '%' without a {{field}}"))?;
+        let conv = rest[..spec_end].trim_start_matches(|c: char| c == '-' || c.is_ascii_digit());
+        let close = rest
+            .find('}')
+            .ok_or_else(|| anyhow::anyhow!("format: unterminated {{field}}"))?;
+        let name = &rest[spec_end + 1..close];
+        let kind = match (conv, name) {
+            (_, "time") => Kind::Time,
+            (_, "epochtime") => Kind::Epoch,
+            ("d" | "f", _) | (_, "respcode") => Kind::Int,
+            ("s", n) if ENCODED.contains(&n) => Kind::Encoded,
+            ("s", _) => Kind::Text,
+            _ => anyhow::bail!("format: unsupported conversion '%{conv}' for {name}"),
+        };
+        let key = match RENAMES.iter().find(|(f, _)| *f == name) {
+            Some((_, to)) => to.to_string(),
+            None if kind == Kind::Encoded => name[1..].to_string(),
+            None => name.to_string(),
+        };
+        fields.push(Field { key, kind });
+        literals.push(String::new());
+        rest = &rest[close + 1..];
+    }
+    literals.last_mut().unwrap().push_str(rest);
+    if fields.is_empty() {
+        anyhow::bail!("format must name at least one field");
+    }
+    Ok((literals, fields))
+}
+
+/// `(b'\t', false)` for `a\tb\tc`, `(b',', true)` for `"a","b","c"`; `None`
+/// when the literals are anything else.
+fn delimiter(literals: &[String]) -> Option<(u8, bool)> {
+    let (first, last) = (&literals[0], &literals[literals.len() - 1]);
+    let quoted = first == "\"";
+    if !(first.is_empty() || quoted) || *last != first[..] {
+        return None;
+    }
+    let inner = &literals[1..literals.len() - 1];
+    let sep = inner.first().map_or("\t", String::as_str);
+    let delim = match sep.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        Some(d) if quoted => d,
+        _ if !quoted => sep,
+        _ => return None,
+    };
+    let ok = matches!(delim, "\t" | "," | ";" | "|") && inner.iter().all(|l| l == sep);
+    ok.then_some((delim.as_bytes()[0], quoted))
+}
+
+/// `Mon Jun 20 15:29:11 2016` in the feed's time zone (GMT unless changed in
+/// the feed settings) -> RFC 3339, taken as UTC.
+fn normalize_time(value: &str) -> Option<String> {
+    let fmt = time::macros::format_description!(
+        "[weekday repr:short] [month repr:short] [day padding:none] \
+         [hour]:[minute]:[second] [year]"
+    );
+    // `Jun  5` (ctime) or `Jun 05`.
+    let value: Vec<&str> = value.split_ascii_whitespace().collect();
+    let mut value = value.join(" ");
+    if value.as_bytes().get(8) == Some(&b'0') {
+        value.remove(8);
+    }
+    PrimitiveDateTime::parse(&value, &fmt)
+        .ok()?
+        .assume_utc()
+        .format(&Rfc3339)
+        .ok()
+}
+
+fn value(kind: Kind, raw: &str) -> Option<Value> {
+    if raw.is_empty() {
+        return Some(Value::Null);
+    }
+    Some(match kind {
+        Kind::Text => raw.into(),
+        Kind::Encoded => percent_decode(raw).into(),
+        Kind::Int => raw.parse::<i64>().map_or_else(|_| raw.into(), Into::into),
+        Kind::Time => normalize_time(raw)?.into(),
+        Kind::Epoch => epoch_to_rfc3339(raw)?.into(),
+    })
+}
+
+impl Parser for Zscaler {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("zscaler")
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        Cow::Borrowed("Parses Zscaler NSS web feeds (--opt format=...) -> typed JSONL")
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        OPTIONS
+    }
+
+    fn configure(&mut self, opts: &ModuleOptions) -> Result<()> {
+        // `get` rather than `list`: the format string holds commas.
+        if let Some(spec) = opts.get("format") {
+            *self = Zscaler::with_format(spec)?;
+        }
+        Ok(())
+    }
+
+    fn ip_fields(&self) -> Vec<String> {
+        ["src_ip", "dst_ip"]
+            .iter()
+            .filter(|f| self.fields.iter().any(|k| k.key == **f))
+            .map(|f| f.to_string())
+            .collect()
+    }
+
+    fn user_agent_fields(&self) -> Vec<String> {
+        vec!["user_agent".to_string()]
+    }
+
+    fn process_line_to_buf(&self, line: &str, out: &mut Vec<u8>) -> bool {
+        let line = line.trim_end_matches(['\n', '\r']);
+        let Some(values) = self.split(line) else {
+            return false;
+        };
+        let mut rec = Map::new();
+        for (field, raw) in self.fields.iter().zip(&values) {
+            let Some(v) = value(field.kind, raw) else {
+                return false;
+            };
+            match field.kind {
+                Kind::Time | Kind::Epoch if !rec.contains_key("ts") => {
+                    rec.insert("ts".to_string(), v);
+                }
+                _ => {
+                    rec.insert(field.key.clone(), v);
+                }
+            }
+        }
+        if serde_json::to_writer(&mut *out, &rec).is_ok() {
+            out.push(b'\n');
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCKED: &str = "Mon Jun 20 15:29:11 2016\tjdoe@example.com\tHTTPS\twww.example.net/dl/setup%20v2.exe?a=1,2\tBlocked\tGeneral Browsing\tGeneral Browsing\t512\t0\t512\tInformation Technology\tWeb Host\tVirus\tZip\tWin32.Trojan.Agent\t100\tBlock Malware\tHQ\tSales\t10.1.2.3\t203.0.113.80\tGET\t403\tMozilla/5.0%20(Windows%20NT%2010.0)\tNone";
+
+    fn run(p: &Zscaler, line: &str) -> Option<Value> {
+        let mut out = Vec::new();
+        p.process_line_to_buf(line, &mut out)
+            .then(|| serde_json::from_slice(&out).unwrap())
+    }
+
+    #[test]
+    fn parses_the_default_tab_feed() {
+        let p = Zscaler::with_format(DEFAULT_FORMAT).unwrap();
+        let v = run(&p, BLOCKED).unwrap();
+        assert_eq!(v["ts"], "2016-06-20T15:29:11Z");
+        assert_eq!(v["user"], "jdoe@example.com");
+        assert_eq!(v["url"], "www.example.net/dl/setup v2.exe?a=1,2");
+        assert_eq!(v["action"], "Blocked");
+        assert_eq!(v["appclass"], "General Browsing");
+        assert_eq!(v["malware_category"], "Virus");
+        assert_eq!(v["threat_name"], "Win32.Trojan.Agent");
+        assert_eq!(v["bytes_out"], 512);
+        assert_eq!(v["bytes_in"], 0);
+        assert_eq!(v["bytes"], 512);
+        assert_eq!(v["status"], 403);
+        assert_eq!(v["src_ip"], "10.1.2.3");
+        assert_eq!(v["user_agent"], "Mozilla/5.0 (Windows NT 10.0)");
+        assert!(run(&p, "Mon Jun 20 15:29:11 2016\tjdoe").is_none());
+        assert!(run(&p, &BLOCKED.replace("Mon Jun", "Mon Foo")).is_none());
+        for day in ["Sun Jun  5 01:02:03 2016", "Sun Jun 05 01:02:03 2016"] {
+            assert_eq!(normalize_time(day).unwrap(), "2016-06-05T01:02:03Z");
+        }
+    }
+
+    #[test]
+    fn comma_and_free_form_templates() {
+        let csv = Zscaler::with_format(
+            r#"\"%s{datetime}\",\"%s{login}\",\"%s{url}\",\"%s{action}\",\"%d{epochtime}\"\n"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            csv.layout,
+            Layout::Delimited {
+                delim: b',',
+                quoted: true
+            }
+        ));
+        let v = run(
+            &csv,
+            r#""x","bob","a.example/?q=1,2","Allowed","1466436551""#,
+        )
+        .unwrap();
+        assert_eq!(v["url"], "a.example/?q=1,2");
+        assert_eq!(v["ts"], "2016-06-20T15:29:11Z");
+        assert_eq!(v["datetime"], "x");
+
+        let syslog = Zscaler::with_format(
+            "%s{mon} %02d{dd} %02d{hh}:%02d{mm}:%02d{ss} zscaler-nss: user=%s{login} action=%s{action} malware=%s{malwarecat}",
+        )
+        .unwrap();
+        let v = run(
+            &syslog,
+            "Jun 20 15:29:11 zscaler-nss: user=bob action=Allowed malware=None",
+        )
+        .unwrap();
+        assert_eq!(v["dd"], 20);
+        assert_eq!(v["user"], "bob");
+        assert_eq!(v["malware_category"], "None");
+        assert!(Zscaler::with_format("no fields").is_err());
+        assert!(Zscaler::with_format("%x{login}").is_err());
+    }
+}
@@ -0,0 +1,163 @@
+//! Live progress of a run on stderr: lines done, throughput and ETA.
+
+use std::{
+    io::{IsTerminal, Write},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// How often the bar is redrawn.
+const REDRAW: Duration = Duration::from_millis(250);
+
+/// Width of the bar itself, in characters.
+const BAR_WIDTH: usize = 30;
+
+/// Set while a bar is on screen, so log lines can clear it first.
+static DRAWN: AtomicBool = AtomicBool::new(false);
+
+/// Whether a progress bar currently occupies the last stderr line.
+pub fn is_drawn() -> bool {
+    DRAWN.load(Ordering::Relaxed)
+}
+
+/// Counters the workers bump after each batch of lines.
+#[derive(Debug)]
+pub struct Progress {
+    /// Lines in the input, when known ahead (the pre-pass count).
+    total: Option<u64>,
+    lines: AtomicU64,
+    /// Uncompressed input bytes.
+    bytes: AtomicU64,
+    start: Instant,
+}
+
+impl Progress {
+    pub fn new(total: Option<u64>) -> Arc<Self> {
+        Arc::new(Self {
+            total,
+            lines: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+            start: Instant::now(),
+        })
+    }
+
+    pub fn add(&self, lines: u64, bytes: u64) {
+        self.lines.fetch_add(lines, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Redraw the bar on stderr until the returned handle is dropped. Nothing
+    /// is drawn when stderr is not a terminal.
+    pub fn show(self: &Arc<Self>) -> ProgressBar {
+        let done = Arc::new(AtomicBool::new(false));
+        let handle = std::io::stderr().is_terminal().then(|| {
+            let (progress, done) = (self.clone(), done.clone());
+            thread::spawn(move || {
+                while !done.load(Ordering::Relaxed) {
+                    DRAWN.store(true, Ordering::Relaxed);
+                    eprint!("\r\x1b[K{}", progress.line(progress.start.elapsed()));
+                    let _ = std::io::stderr().flush();
+                    thread::park_timeout(REDRAW);
+                }
+                eprint!("\r\x1b[K");
+                DRAWN.store(false, Ordering::Relaxed);
+            })
+        });
+        ProgressBar { done, handle }
+    }
+
+    /// The status line after `elapsed`.
+    fn line(&self, elapsed: Duration) -> String {
+        let lines = self.lines.load(Ordering::Relaxed);
+        let bytes = self.bytes.load(Ordering::Relaxed);
+        let secs = elapsed.as_secs_f64().max(1e-3);
+        let rate = lines as f64 / secs;
+        let speed = format!(
+            "{} lines/s  {:.1} MB/s",
+            human(rate),
+            bytes as f64 / secs / 1e6
+        );
+
+        let Some(total) = self.total.filter(|&t| t > 0) else {
+            return format!(
+                "{} lines  {speed}  {}",
+                human(lines as f64),
+                clock(elapsed.as_secs())
+            );
+        };
+        let done = (lines as f64 / total as f64).min(1.0);
+        let filled = (done * BAR_WIDTH as f64) as usize;
+        let bar = if filled >= BAR_WIDTH {
+            "=".repeat(BAR_WIDTH)
+        } else {
+            format!(
+                "{}>{}",
+                "=".repeat(filled),
+                " ".repeat(BAR_WIDTH - filled - 1)
+            )
+        };
+        let eta = match total.saturating_sub(lines) {
+            0 => clock(0),
+            _ if rate < 1.0 => "--:--:--".to_string(),
+            left => clock((left as f64 / rate) as u64),
+        };
+        format!("{:5.1}% [{bar}] {speed}  ETA {eta}", done * 100.0)
+    }
+}
+
+/// Keeps the bar drawn; dropping it clears the line.
+pub struct ProgressBar {
+    done: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Drop for ProgressBar {
+    fn drop(&mut self) {
+        self.done.store(true, Ordering::Relaxed);
+        if let Some(h) = self.handle.take() {
+            h.thread().unpark();
+            let _ = h.join();
+        }
+    }
+}
+
+/// `1234567` as `1.23M`.
+fn human(n: f64) -> String {
+    match n {
+        n if n >= 1e9 => format!("{:.2}G", n / 1e9),
+        n if n >= 1e6 => format!("{:.2}M", n / 1e6),
+        n if n >= 1e3 => format!("{:.1}K", n / 1e3),
+        n => format!("{n:.0}"),
+    }
+}
+
+/// Seconds as `HH:MM:SS`.
+fn clock(secs: u64) -> String {
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_percentage_rate_and_eta() {
+        let p = Progress::new(Some(1_000_000));
+        p.add(250_000, 50_000_000);
+        assert_eq!(
+            p.line(Duration::from_secs(10)),
+            " 25.0% [=======>                      ] 25.0K lines/s  5.0 MB/s  ETA 00:00:30"
+        );
+
+        let p = Progress::new(None);
+        p.add(1_500_000, 3_000_000);
+        assert_eq!(
+            p.line(Duration::from_secs(3725)),
+            "1.50M lines  403 lines/s  0.0 MB/s  01:02:05"
+        );
+    }
+}
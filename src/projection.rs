@@ -0,0 +1,137 @@
+//! `--fields` / `--exclude`: trim records down to what downstream needs
+//! before they are written.
+
+use serde_json::{Map, Value};
+
+use crate::{core::FieldType, enrich::parent_mut};
+
+/// Keeps only `fields` (when any are given, in that order), then drops
+/// `exclude`. Dotted names reach into nested objects, so `parsed.status`
+/// keeps or drops just that key of `parsed`; a literal `a.b` key wins.
+#[derive(Clone, Debug, Default)]
+pub struct Projection {
+    pub fields: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl Projection {
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn apply(&self, rec: &mut Map<String, Value>) {
+        if !self.fields.is_empty() {
+            let mut kept = Map::new();
+            for path in &self.fields {
+                take(rec, path, &mut kept);
+            }
+            *rec = kept;
+        }
+        for path in &self.exclude {
+            if let Some((parent, key)) = parent_mut(rec, path) {
+                parent.shift_remove(key);
+            }
+        }
+    }
+
+    /// The declared `fields` a projected record still has, in its order.
+    pub fn apply_to_fields(&self, fields: Vec<(String, FieldType)>) -> Vec<(String, FieldType)> {
+        let kept = match self.fields.is_empty() {
+            true => fields,
+            false => self
+                .fields
+                .iter()
+                .filter_map(|f| fields.iter().find(|(name, _)| name == f).cloned())
+                .collect(),
+        };
+        kept.into_iter()
+            .filter(|(name, _)| !self.exclude.contains(name))
+            .collect()
+    }
+}
+
+/// Move the value at `path` from `rec` into the same place in `out`.
+fn take(rec: &mut Map<String, Value>, path: &str, out: &mut Map<String, Value>) {
+    if let Some(v) = rec.shift_remove(path) {
+        out.insert(path.to_string(), v);
+        return;
+    }
+    let Some((head, rest)) = path.split_once('.') else {
+        return;
+    };
+    let Some(Value::Object(inner)) = rec.get_mut(head) else {
+        return;
+    };
+    let slot = out
+        .entry(head.to_string())
+        .or_insert_with(|| Value::Object(Map::new()));
+    if let Value::Object(slot) = slot {
+        take(inner, rest, slot);
+        if slot.is_empty() {
+            out.shift_remove(head);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn project(fields: &[&str], exclude: &[&str], rec: Value) -> Value {
+        let p = Projection {
+            fields: fields.iter().map(|f| f.to_string()).collect(),
+            exclude: exclude.iter().map(|f| f.to_string()).collect(),
+        };
+        let mut rec = rec.as_object().unwrap().clone();
+        p.apply(&mut rec);
+        Value::Object(rec)
+    }
+
+    #[test]
+    fn keeps_and_drops_fields() {
+        let rec = json!({
+            "ts": "t", "ip": "1.2.3.4", "raw": "...",
+            "parsed": {"status": 200, "path": "/", "raw": "..."},
+            "id.orig_h": "10.0.0.1",
+        });
+        assert_eq!(
+            project(
+                &["status", "parsed.path", "ip", "id.orig_h", "nope.x"],
+                &[],
+                rec.clone()
+            ),
+            json!({"parsed": {"path": "/"}, "ip": "1.2.3.4", "id.orig_h": "10.0.0.1"})
+        );
+        assert_eq!(
+            project(&[], &["raw", "parsed.raw", "parsed.missing"], rec.clone()).to_string(),
+            json!({
+                "ts": "t", "ip": "1.2.3.4",
+                "parsed": {"status": 200, "path": "/"},
+                "id.orig_h": "10.0.0.1",
+            })
+            .to_string()
+        );
+        assert_eq!(
+            project(&["parsed"], &["parsed.raw"], rec),
+            json!({"parsed": {"status": 200, "path": "/"}})
+        );
+    }
+
+    #[test]
+    fn projects_declared_fields() {
+        let declared = vec![
+            ("ts".to_string(), FieldType::Text),
+            ("ip".to_string(), FieldType::Text),
+            ("status".to_string(), FieldType::Int),
+        ];
+        let p = Projection {
+            fields: vec!["status".into(), "geo".into(), "ts".into()],
+            exclude: vec!["ts".into()],
+        };
+        assert_eq!(
+            p.apply_to_fields(declared),
+            [("status".to_string(), FieldType::Int)]
+        );
+    }
+}
@@ -0,0 +1,138 @@
+//! `--report report.json`: a machine-readable summary of a run, for
+//! wrappers that would otherwise scrape the `[INFO]` lines.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::core::RunStats;
+
+/// The whole invocation: one entry per input parsed.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunReport {
+    pub version: &'static str,
+    pub module: String,
+    /// RFC 3339, UTC.
+    pub started_at: String,
+    pub duration_secs: f64,
+    /// Set when the run failed; `inputs` then holds the ones finished.
+    pub error: Option<String>,
+    pub totals: RunStats,
+    pub inputs: Vec<InputReport>,
+}
+
+/// One input file (or stdin) and what it produced.
+#[derive(Debug, Clone, Serialize)]
+pub struct InputReport {
+    pub path: PathBuf,
+    /// On-disk size; none for stdin.
+    pub size_bytes: Option<u64>,
+    pub gzip: bool,
+    /// Lines counted before the run, when the input allows it.
+    pub line_count: Option<u64>,
+    #[serde(flatten)]
+    pub stats: RunStats,
+    pub duration_secs: f64,
+    pub lines_per_sec: f64,
+    /// Input bytes read (after decompression) when the run was interrupted.
+    pub stopped_at: Option<u64>,
+    pub workers: Vec<RunStats>,
+    pub output: OutputReport,
+}
+
+/// Where the records went.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum OutputReport {
+    Stdout,
+    /// Files written: one, or the shards.
+    Files {
+        files: Vec<OutputFile>,
+    },
+    /// A cluster, collector or topic.
+    Remote {
+        target: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputFile {
+    pub path: PathBuf,
+    pub size_bytes: Option<u64>,
+}
+
+impl RunReport {
+    pub fn new(module: &str, started_at: String) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            module: module.to_string(),
+            started_at,
+            duration_secs: 0.0,
+            error: None,
+            totals: RunStats::default(),
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, input: InputReport) {
+        self.totals.add(&input.stats);
+        self.inputs.push(input);
+    }
+
+    /// Write the report as pretty-printed JSON.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+        std::fs::write(path, json).with_context(|| format!("write report {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn serializes_inputs_and_totals() {
+        let stats = RunStats {
+            lines: 10,
+            parsed: 8,
+            rejected: 1,
+            blank: 1,
+            records: 8,
+        };
+        let mut report = RunReport::new("web-access", "2024-01-02T03:04:05Z".into());
+        report.push(InputReport {
+            path: "access.log.gz".into(),
+            size_bytes: Some(120),
+            gzip: true,
+            line_count: Some(10),
+            stats,
+            duration_secs: 0.5,
+            lines_per_sec: 20.0,
+            stopped_at: None,
+            workers: vec![stats],
+            output: OutputReport::Files {
+                files: vec![OutputFile {
+                    path: "out.jsonl".into(),
+                    size_bytes: Some(800),
+                }],
+            },
+        });
+        report.duration_secs = 0.6;
+
+        let v = serde_json::to_value(&report).unwrap();
+        assert_eq!(v["totals"]["parsed"], 8);
+        assert_eq!(v["error"], json!(null));
+        assert_eq!(
+            v["inputs"][0],
+            json!({
+                "path": "access.log.gz", "size_bytes": 120, "gzip": true, "line_count": 10,
+                "lines": 10, "parsed": 8, "rejected": 1, "blank": 1, "records": 8,
+                "duration_secs": 0.5, "lines_per_sec": 20.0, "stopped_at": null,
+                "workers": [{"lines": 10, "parsed": 8, "rejected": 1, "blank": 1, "records": 8}],
+                "output": {"kind": "files", "files": [{"path": "out.jsonl", "size_bytes": 800}]},
+            })
+        );
+    }
+}
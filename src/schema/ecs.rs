@@ -0,0 +1,508 @@
+//! `--schema ecs`: Elastic Common Schema field names, so records index into
+//! Elastic without an ingest pipeline.
+
+use anyhow::{Context, Result};
+use serde_json::{Map, Value};
+
+use super::{split_chain, take_sigma, unchain, verdict};
+use crate::{
+    enrich::{Enricher, FieldTags},
+    transform::set,
+};
+
+/// The ECS version the mappings follow.
+pub const ECS_VERSION: &str = "8.11.0";
+
+/// What a module's events are, for `event.category` / `event.type`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    Web,
+    Auth,
+    Network,
+}
+
+/// How one module's fields land in ECS. A target may end in `|conversion`:
+/// `lower`, `str`, `http` (`HTTP/1.1` -> `1.1`), or `us` / `s` for a
+/// duration turned into nanoseconds.
+struct Mapping {
+    module: &'static str,
+    /// Where fields without an ECS home go, named as Elastic's own
+    /// integrations name it.
+    namespace: &'static str,
+    kind: Kind,
+    fields: &'static [(&'static str, &'static str)],
+}
+
+const MAPPINGS: &[Mapping] = &[
+    Mapping {
+        module: "web-access",
+        namespace: "web_access",
+        kind: Kind::Web,
+        fields: &[
+            ("vhost", "url.domain"),
+            ("ip", "source.ip"),
+            ("user", "user.name"),
+            ("ts", "@timestamp"),
+            ("method", "http.request.method"),
+            ("target", "url.original"),
+            ("path", "url.path"),
+            ("query", "url.query"),
+            ("protocol", "http.version|http"),
+            ("status", "http.response.status_code"),
+            ("bytes", "http.response.body.bytes"),
+            ("referer", "http.request.referrer"),
+            ("user_agent", "user_agent.original"),
+            ("duration_us", "event.duration|us"),
+            ("raw", "event.original"),
+        ],
+    },
+    Mapping {
+        module: "authlog",
+        namespace: "system.auth",
+        kind: Kind::Auth,
+        fields: &[
+            ("host", "host.hostname"),
+            ("program", "process.name"),
+            ("pid", "process.pid"),
+            ("event", "event.action"),
+            ("outcome", "event.outcome"),
+            ("user", "user.name"),
+            ("src_ip", "source.ip"),
+            ("src_port", "source.port"),
+            ("target_user", "user.target.name"),
+            ("command", "process.command_line"),
+            ("reason", "event.reason"),
+            ("message", "message"),
+        ],
+    },
+    Mapping {
+        module: "asa",
+        namespace: "cisco.asa",
+        kind: Kind::Network,
+        fields: &[
+            ("host", "observer.hostname"),
+            ("product", "observer.product"),
+            ("severity", "log.syslog.severity.code"),
+            ("message_id", "event.code|str"),
+            ("event", "event.action"),
+            ("protocol", "network.transport|lower"),
+            ("direction", "network.direction|lower"),
+            ("src_interface", "observer.ingress.interface.name"),
+            ("src_ip", "source.ip"),
+            ("src_port", "source.port"),
+            ("src_mapped_ip", "source.nat.ip"),
+            ("src_mapped_port", "source.nat.port"),
+            ("dst_interface", "observer.egress.interface.name"),
+            ("dst_ip", "destination.ip"),
+            ("dst_port", "destination.port"),
+            ("dst_mapped_ip", "destination.nat.ip"),
+            ("dst_mapped_port", "destination.nat.port"),
+            ("bytes", "network.bytes"),
+            ("user", "user.name"),
+            ("acl", "rule.name"),
+            ("reason", "event.reason"),
+            ("message", "message"),
+        ],
+    },
+    Mapping {
+        module: "fortigate",
+        namespace: "fortinet.firewall",
+        kind: Kind::Network,
+        fields: &[
+            ("ts", "@timestamp"),
+            ("devname", "observer.name"),
+            ("devid", "observer.serial_number"),
+            ("logid", "event.code"),
+            ("level", "log.level"),
+            ("srcip", "source.ip"),
+            ("srcport", "source.port"),
+            ("srcintf", "observer.ingress.interface.name"),
+            ("srcmac", "source.mac"),
+            ("dstip", "destination.ip"),
+            ("dstport", "destination.port"),
+            ("dstintf", "observer.egress.interface.name"),
+            ("transip", "source.nat.ip"),
+            ("transport", "source.nat.port"),
+            ("tranip", "destination.nat.ip"),
+            ("tranport", "destination.nat.port"),
+            ("action", "event.action"),
+            ("policyid", "rule.id|str"),
+            ("policyname", "rule.name"),
+            ("proto", "network.iana_number|str"),
+            ("app", "network.application"),
+            ("sentbyte", "source.bytes"),
+            ("rcvdbyte", "destination.bytes"),
+            ("sentpkt", "source.packets"),
+            ("rcvdpkt", "destination.packets"),
+            ("duration", "event.duration|s"),
+            ("user", "user.name"),
+            ("msg", "message"),
+        ],
+    },
+    Mapping {
+        module: "panos",
+        namespace: "panw.panos",
+        kind: Kind::Network,
+        fields: &[
+            ("ts", "@timestamp"),
+            ("serial", "observer.serial_number"),
+            ("src_ip", "source.ip"),
+            ("dst_ip", "destination.ip"),
+            ("nat_src_ip", "source.nat.ip"),
+            ("nat_dst_ip", "destination.nat.ip"),
+            ("rule", "rule.name"),
+            ("rule_uuid", "rule.uuid"),
+            ("src_user", "source.user.name"),
+            ("dst_user", "destination.user.name"),
+            ("app", "network.application"),
+            ("src_zone", "observer.ingress.zone"),
+            ("dst_zone", "observer.egress.zone"),
+            ("inbound_if", "observer.ingress.interface.name"),
+            ("outbound_if", "observer.egress.interface.name"),
+            ("src_port", "source.port"),
+            ("dst_port", "destination.port"),
+            ("nat_src_port", "source.nat.port"),
+            ("nat_dst_port", "destination.nat.port"),
+            ("proto", "network.transport|lower"),
+            ("action", "event.action"),
+            ("bytes", "network.bytes"),
+            ("bytes_sent", "source.bytes"),
+            ("bytes_received", "destination.bytes"),
+            ("packets", "network.packets"),
+            ("packets_sent", "source.packets"),
+            ("packets_received", "destination.packets"),
+            ("elapsed_time", "event.duration|s"),
+            ("device_name", "observer.hostname"),
+            ("user_agent", "user_agent.original"),
+            ("referer", "http.request.referrer"),
+            ("http_method", "http.request.method"),
+            ("file_digest", "file.hash.sha256"),
+            ("description", "message"),
+        ],
+    },
+    Mapping {
+        module: "vpc-flow",
+        namespace: "aws.vpcflow",
+        kind: Kind::Network,
+        fields: &[
+            ("account_id", "cloud.account.id"),
+            ("region", "cloud.region"),
+            ("az_id", "cloud.availability_zone"),
+            ("instance_id", "cloud.instance.id"),
+            ("srcaddr", "source.ip"),
+            ("dstaddr", "destination.ip"),
+            ("srcport", "source.port"),
+            ("dstport", "destination.port"),
+            ("protocol", "network.iana_number|str"),
+            ("packets", "network.packets"),
+            ("bytes", "network.bytes"),
+            ("start", "event.start"),
+            ("end", "event.end"),
+            ("action", "event.action|lower"),
+            ("flow_direction", "network.direction|lower"),
+        ],
+    },
+    Mapping {
+        module: "elb",
+        namespace: "aws.elb",
+        kind: Kind::Web,
+        fields: &[
+            ("ts", "@timestamp"),
+            ("elb", "aws.elb.name"),
+            ("client_ip", "source.ip"),
+            ("client_port", "source.port"),
+            ("target_ip", "destination.ip"),
+            ("target_port", "destination.port"),
+            ("backend_ip", "destination.ip"),
+            ("backend_port", "destination.port"),
+            ("elb_status_code", "http.response.status_code"),
+            ("received_bytes", "http.request.body.bytes"),
+            ("sent_bytes", "http.response.body.bytes"),
+            ("request_method", "http.request.method"),
+            ("request_url", "url.original"),
+            ("request_protocol", "http.version|http"),
+            ("user_agent", "user_agent.original"),
+            ("ssl_cipher", "tls.cipher"),
+            ("trace_id", "trace.id"),
+        ],
+    },
+];
+
+/// Modules `--schema ecs` knows.
+pub fn modules() -> impl Iterator<Item = &'static str> {
+    MAPPINGS.iter().map(|m| m.module)
+}
+
+/// The field of `module`'s records that maps to the ECS field `ecs`.
+pub(crate) fn module_field(module: &str, ecs: &str) -> Option<&'static str> {
+    let mapping = MAPPINGS.iter().find(|m| m.module == module)?;
+    mapping
+        .fields
+        .iter()
+        .find(|(_, t)| t.split('|').next() == Some(ecs))
+        .map(|(k, _)| *k)
+}
+
+/// Renames a module's fields to ECS and fills `event.*` and `ecs.version`.
+/// Fields without an ECS counterpart keep their name under the module's
+/// namespace (`cisco.asa.connection_id`); `null`s are dropped. GeoIP
+/// (`<ip field>_geo`) and `--parse-ua` results move to `<source>.geo`,
+/// `<source>.as` and `user_agent`.
+///
+/// For a chain (`docker-json,web-access`) the inner module's record is
+/// mapped and the outer one's fields go under the outer module's name.
+pub struct Ecs {
+    mapping: &'static Mapping,
+    outer: Option<String>,
+}
+
+impl Ecs {
+    pub fn for_module(name: &str) -> Result<Self> {
+        let (outer, inner) = split_chain(name);
+        let mapping = MAPPINGS
+            .iter()
+            .find(|m| m.module == inner)
+            .with_context(|| {
+                let known: Vec<_> = modules().collect();
+                format!(
+                    "no ECS mapping for module {inner} (mapped: {})",
+                    known.join(", ")
+                )
+            })?;
+        Ok(Self { mapping, outer })
+    }
+
+    fn map(&self, src: Map<String, Value>, out: &mut Map<String, Value>) {
+        let m = self.mapping;
+        let target = |key: &str| m.fields.iter().find(|(k, _)| *k == key).map(|(_, t)| *t);
+        for (key, v) in &src {
+            if v.is_null() {
+                continue;
+            }
+            if let Some(t) = target(key) {
+                let (path, conv) = t.split_once('|').unwrap_or((t, ""));
+                if let Some(v) = convert(v, conv) {
+                    set(out, path, v);
+                }
+            } else if let Some(base) = key.strip_suffix("_geo")
+                && let Some(ip) = target(base).and_then(|t| t.strip_suffix(".ip"))
+                && let Value::Object(geo) = v
+            {
+                put_geo(out, ip, geo);
+            } else if key == "ua"
+                && let Value::Object(ua) = v
+            {
+                put_ua(out, ua);
+            } else {
+                set(out, &format!("{}.{key}", m.namespace), v.clone());
+            }
+        }
+
+        let action = src
+            .get("action")
+            .or(src.get("event"))
+            .and_then(Value::as_str);
+        let (category, kind_type) = match m.kind {
+            Kind::Web => ("web", "access"),
+            Kind::Auth => ("authentication", "info"),
+            Kind::Network => ("network", "connection"),
+        };
+        let mut types = vec![Value::from(kind_type)];
+        if m.kind == Kind::Network
+            && let Some(allowed) = action.and_then(verdict)
+        {
+            types.push(if allowed { "allowed" } else { "denied" }.into());
+        }
+        let outcome = match m.kind {
+            Kind::Web => module_field(m.module, "http.response.status_code")
+                .and_then(|k| src.get(k))
+                .and_then(Value::as_i64)
+                .map(|s| if s < 400 { "success" } else { "failure" }),
+            _ => None,
+        };
+
+        set(out, "event.kind", "event".into());
+        set(out, "event.module", m.module.into());
+        set(out, "event.dataset", m.namespace.into());
+        set(out, "event.category", vec![Value::from(category)].into());
+        set(out, "event.type", types.into());
+        if let Some(outcome) = outcome {
+            set(out, "event.outcome", outcome.into());
+        }
+        if matches!(m.module, "vpc-flow" | "elb") {
+            set(out, "cloud.provider", "aws".into());
+        }
+    }
+}
+
+impl Enricher for Ecs {
+    fn enrich(&self, rec: &mut Map<String, Value>, _fields: &FieldTags) {
+        let mut src = std::mem::take(rec);
+        let sigma = take_sigma(&mut src);
+        let mut out = Map::new();
+        if let Some(outer) = &self.outer {
+            let fields = unchain(&mut src);
+            if !fields.is_empty() {
+                out.insert(outer.clone(), Value::Object(fields));
+            }
+        }
+        self.map(src, &mut out);
+        set(&mut out, "ecs.version", ECS_VERSION.into());
+
+        if let Some(ts) = out.shift_remove("@timestamp") {
+            rec.insert("@timestamp".to_string(), ts);
+        }
+        rec.extend(out);
+        rec.extend(sigma);
+    }
+}
+
+fn convert(v: &Value, conv: &str) -> Option<Value> {
+    Some(match conv {
+        "lower" => v
+            .as_str()
+            .map_or_else(|| v.clone(), |s| s.to_lowercase().into()),
+        "str" => match v {
+            Value::String(_) => v.clone(),
+            other => other.to_string().into(),
+        },
+        "http" => v.as_str().map_or_else(
+            || v.clone(),
+            |s| s.strip_prefix("HTTP/").unwrap_or(s).into(),
+        ),
+        "us" => (v.as_i64()? * 1_000).into(),
+        "s" => ((v.as_f64()? * 1e9) as i64).into(),
+        _ => v.clone(),
+    })
+}
+
+fn put_geo(out: &mut Map<String, Value>, prefix: &str, geo: &Map<String, Value>) {
+    for (from, to) in [
+        ("country_code", "geo.country_iso_code"),
+        ("country", "geo.country_name"),
+        ("region", "geo.region_name"),
+        ("city", "geo.city_name"),
+        ("lat", "geo.location.lat"),
+        ("lon", "geo.location.lon"),
+        ("asn", "as.number"),
+        ("as_org", "as.organization.name"),
+    ] {
+        if let Some(v) = geo.get(from).filter(|v| !v.is_null()) {
+            set(out, &format!("{prefix}.{to}"), v.clone());
+        }
+    }
+}
+
+fn put_ua(out: &mut Map<String, Value>, ua: &Map<String, Value>) {
+    for (from, to) in [
+        ("browser", "user_agent.name"),
+        ("browser_version", "user_agent.version"),
+        ("os", "user_agent.os.name"),
+        ("os_version", "user_agent.os.version"),
+        ("device", "user_agent.device.name"),
+    ] {
+        if let Some(v) = ua.get(from).filter(|v| !v.is_null()) {
+            set(out, to, v.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn ecs(module: &str, rec: Value) -> Value {
+        let mut rec = rec.as_object().unwrap().clone();
+        Ecs::for_module(module)
+            .unwrap()
+            .enrich(&mut rec, &FieldTags::default());
+        Value::Object(rec)
+    }
+
+    #[test]
+    fn maps_web_access() {
+        let v = ecs(
+            "web-access",
+            json!({
+                "vhost": null, "ip": "1.2.3.4", "ident": null, "user": "bob",
+                "ts": "2000-10-10T20:55:36Z", "ts_raw": "10/Oct/2000:13:55:36 -0700",
+                "method": "GET", "target": "/a?b=1", "path": "/a", "query": "b=1",
+                "protocol": "HTTP/1.1", "status": 503, "bytes": 12, "duration_us": 1500,
+                "raw": "...", "ip_geo": {"country_code": "NL", "lat": 52.1, "lon": 4.3, "asn": 1136},
+                "ua": {"browser": "curl", "browser_version": "8.4.0", "os": null, "bot": true},
+            }),
+        );
+        assert_eq!(v.as_object().unwrap().keys().next().unwrap(), "@timestamp");
+        assert_eq!(v["source"]["ip"], "1.2.3.4");
+        assert_eq!(v["source"]["geo"]["country_iso_code"], "NL");
+        assert_eq!(
+            v["source"]["geo"]["location"],
+            json!({"lat": 52.1, "lon": 4.3})
+        );
+        assert_eq!(v["source"]["as"]["number"], 1136);
+        assert_eq!(v["user"]["name"], "bob");
+        assert_eq!(v["http"]["version"], "1.1");
+        assert_eq!(v["http"]["response"]["status_code"], 503);
+        assert_eq!(v["url"]["path"], "/a");
+        assert_eq!(v["user_agent"], json!({"name": "curl", "version": "8.4.0"}));
+        assert_eq!(
+            v["web_access"],
+            json!({"ts_raw": "10/Oct/2000:13:55:36 -0700"})
+        );
+        assert_eq!(v["event"]["duration"], 1_500_000);
+        assert_eq!(v["event"]["original"], "...");
+        assert_eq!(v["event"]["category"], json!(["web"]));
+        assert_eq!(v["event"]["outcome"], "failure");
+        assert_eq!(v["ecs"]["version"], ECS_VERSION);
+        assert!(v.get("vhost").is_none() && v.get("ident").is_none());
+    }
+
+    #[test]
+    fn maps_firewalls_and_chains() {
+        let v = ecs(
+            "asa",
+            json!({"message_id": 106023, "event": "deny", "action": "deny", "protocol": "TCP",
+                   "src_ip": "203.0.113.5", "dst_port": 22, "connection_id": 7}),
+        );
+        assert_eq!(v["event"]["code"], "106023");
+        assert_eq!(v["event"]["type"], json!(["connection", "denied"]));
+        assert_eq!(v["network"]["transport"], "tcp");
+        assert_eq!(v["destination"]["port"], 22);
+        assert_eq!(
+            v["cisco"]["asa"],
+            json!({"action": "deny", "connection_id": 7})
+        );
+
+        let v = ecs(
+            "docker-json,web-access",
+            json!({"stream": "stdout", "log": "...", "parsed": {"ip": "1.2.3.4", "status": 200}}),
+        );
+        assert_eq!(v["docker_json"], json!({"stream": "stdout", "log": "..."}));
+        assert_eq!(v["source"]["ip"], "1.2.3.4");
+        assert_eq!(v["event"]["outcome"], "success");
+
+        assert!(Ecs::for_module("mactime").is_err());
+    }
+
+    #[test]
+    fn maps_elb() {
+        let v = ecs(
+            "elb",
+            json!({"type": "https", "ts": "2018-07-02T22:23:00.186641Z", "elb": "app/lb/50dc",
+                   "client_ip": "192.168.131.39", "client_port": 2817, "target_ip": null,
+                   "elb_status_code": 502, "target_status_code": null, "sent_bytes": 57,
+                   "request_method": "GET", "request_protocol": "HTTP/1.1"}),
+        );
+        assert_eq!(v["@timestamp"], "2018-07-02T22:23:00.186641Z");
+        assert_eq!(v["source"], json!({"ip": "192.168.131.39", "port": 2817}));
+        assert_eq!(v["http"]["response"]["status_code"], 502);
+        assert_eq!(v["http"]["version"], "1.1");
+        assert_eq!(
+            v["aws"]["elb"],
+            json!({"name": "app/lb/50dc", "type": "https"})
+        );
+        assert_eq!(v["event"]["outcome"], "failure");
+        assert_eq!(v["cloud"]["provider"], "aws");
+    }
+}
@@ -0,0 +1,82 @@
+//! `--schema`: rename module fields to a standard schema as the last
+//! enrichment step, so output loads into a SIEM without its own mapping.
+
+use anyhow::Result;
+use serde_json::{Map, Value};
+
+use crate::enrich::Enricher;
+
+pub mod ecs;
+pub mod ocsf;
+
+pub use ecs::Ecs;
+pub use ocsf::Ocsf;
+
+/// Target schema for `--schema`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Schema {
+    /// Elastic Common Schema.
+    Ecs,
+    /// Open Cybersecurity Schema Framework.
+    Ocsf,
+}
+
+impl Schema {
+    /// The stage mapping records of `module` (a parser name, possibly a
+    /// chain) to this schema; an error when the module has no mapping.
+    pub fn mapper(self, module: &str) -> Result<Box<dyn Enricher>> {
+        Ok(match self {
+            Schema::Ecs => Box::new(Ecs::for_module(module)?),
+            Schema::Ocsf => Box::new(Ocsf::for_module(module)?),
+        })
+    }
+}
+
+/// The inner module of a chain (`docker-json,web-access`) and the key the
+/// outer modules' own fields go under (`docker_json`).
+pub(crate) fn split_chain(module: &str) -> (Option<String>, &str) {
+    match module.rsplit_once(',') {
+        Some((outer, inner)) => (Some(outer.replace([',', '-'], "_")), inner),
+        None => (None, module),
+    }
+}
+
+/// Remove the `--sigma` tags, which stay at the top level whatever the
+/// schema so `--sigma-only` and `sigma_match` filters keep working.
+pub(crate) fn take_sigma(rec: &mut Map<String, Value>) -> Map<String, Value> {
+    ["sigma_match", "sigma"]
+        .into_iter()
+        .filter_map(|k| rec.shift_remove_entry(k))
+        .collect()
+}
+
+/// Replace a chained record by its `parsed` record, returning the outer
+/// module's non-null fields.
+pub(crate) fn unchain(rec: &mut Map<String, Value>) -> Map<String, Value> {
+    let parsed = rec.shift_remove("parsed");
+    let outer = std::mem::take(rec)
+        .into_iter()
+        .filter(|(_, v)| !v.is_null())
+        .collect();
+    if let Some(Value::Object(parsed)) = parsed {
+        *rec = parsed;
+    }
+    outer
+}
+
+/// Whether a firewall action or event (`deny`, `ACCEPT`, `permit`) let the
+/// traffic through, when it says.
+pub(crate) fn verdict(action: &str) -> Option<bool> {
+    let a = action.to_ascii_lowercase();
+    let starts = |words: &[&str]| words.iter().any(|w| a.starts_with(w));
+    if starts(&["deny", "drop", "reject", "block", "reset", "discard"]) {
+        Some(false)
+    } else if starts(&[
+        "accept", "allow", "permit", "pass", "built", "close", "start", "end",
+    ]) {
+        Some(true)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,619 @@
+//! `--schema ocsf`: Open Cybersecurity Schema Framework events, for
+//! OCSF-native stores such as Amazon Security Lake.
+
+use anyhow::{Context, Result};
+use serde_json::{json, Map, Value};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use super::{split_chain, take_sigma, unchain, verdict};
+use crate::{
+    enrich::{Enricher, FieldTags},
+    transform::set,
+};
+
+/// The OCSF version the mappings follow.
+pub const OCSF_VERSION: &str = "1.1.0";
+
+/// An OCSF event class and its activities.
+struct Class {
+    uid: u32,
+    name: &'static str,
+    category_uid: u32,
+    category_name: &'static str,
+    activities: &'static [(u32, &'static str)],
+}
+
+const HTTP_ACTIVITY: Class = Class {
+    uid: 4002,
+    name: "HTTP Activity",
+    category_uid: 4,
+    category_name: "Network Activity",
+    activities: &[
+        (1, "Connect"),
+        (2, "Delete"),
+        (3, "Get"),
+        (4, "Head"),
+        (5, "Options"),
+        (6, "Post"),
+        (7, "Put"),
+        (8, "Trace"),
+        (9, "Patch"),
+    ],
+};
+
+const NETWORK_ACTIVITY: Class = Class {
+    uid: 4001,
+    name: "Network Activity",
+    category_uid: 4,
+    category_name: "Network Activity",
+    activities: &[
+        (1, "Open"),
+        (2, "Close"),
+        (3, "Reset"),
+        (4, "Fail"),
+        (5, "Refuse"),
+        (6, "Traffic"),
+    ],
+};
+
+const AUTHENTICATION: Class = Class {
+    uid: 3002,
+    name: "Authentication",
+    category_uid: 3,
+    category_name: "Identity & Access Management",
+    activities: &[(1, "Logon"), (2, "Logoff")],
+};
+
+/// How one module's records become one OCSF class. A target may end in
+/// `|conversion`: `lower`, `str`, `http` (`HTTP/1.1` -> `1.1`), `time`
+/// (RFC 3339 -> epoch milliseconds), `list` (comma-separated -> array), or
+/// `us` / `s` for a duration turned into milliseconds.
+struct Mapping {
+    module: &'static str,
+    class: &'static Class,
+    /// `metadata.product`: name and vendor of what wrote the log.
+    product: (&'static str, &'static str),
+    /// The field naming the activity and its values' `activity_id`s; any
+    /// other value is 99 (Other).
+    activity: (&'static str, &'static [(&'static str, u32)]),
+    fields: &'static [(&'static str, &'static str)],
+}
+
+/// HTTP methods and their HTTP Activity `activity_id`s.
+const HTTP_METHODS: &[(&str, u32)] = &[
+    ("CONNECT", 1),
+    ("DELETE", 2),
+    ("GET", 3),
+    ("HEAD", 4),
+    ("OPTIONS", 5),
+    ("POST", 6),
+    ("PUT", 7),
+    ("TRACE", 8),
+    ("PATCH", 9),
+];
+
+const MAPPINGS: &[Mapping] = &[
+    Mapping {
+        module: "web-access",
+        class: &HTTP_ACTIVITY,
+        product: ("Web Server", ""),
+        activity: ("method", HTTP_METHODS),
+        fields: &[
+            ("vhost", "http_request.url.hostname"),
+            ("ip", "src_endpoint.ip"),
+            ("ts", "time|time"),
+            ("ts_raw", "metadata.original_time"),
+            ("method", "http_request.http_method"),
+            ("path", "http_request.url.path"),
+            ("query", "http_request.url.query_string"),
+            ("protocol", "http_request.version|http"),
+            ("status", "http_response.code"),
+            ("bytes", "http_response.length"),
+            ("referer", "http_request.referrer"),
+            ("user_agent", "http_request.user_agent"),
+            ("xff", "http_request.x_forwarded_for|list"),
+            ("duration_us", "duration|us"),
+            ("raw", "raw_data"),
+        ],
+    },
+    Mapping {
+        module: "authlog",
+        class: &AUTHENTICATION,
+        product: ("Linux auth log", ""),
+        activity: (
+            "event",
+            &[
+                ("ssh_login", 1),
+                ("ssh_invalid_user", 1),
+                ("auth_failure", 1),
+                ("session_opened", 1),
+                ("su", 1),
+                ("ssh_disconnect", 2),
+                ("session_closed", 2),
+            ],
+        ),
+        fields: &[
+            ("syslog_timestamp", "metadata.original_time"),
+            ("host", "dst_endpoint.hostname"),
+            ("program", "actor.process.name"),
+            ("pid", "actor.process.pid"),
+            ("user", "user.name"),
+            ("src_ip", "src_endpoint.ip"),
+            ("src_port", "src_endpoint.port"),
+            ("reason", "status_detail"),
+            ("message", "message"),
+        ],
+    },
+    Mapping {
+        module: "asa",
+        class: &NETWORK_ACTIVITY,
+        product: ("ASA", "Cisco"),
+        activity: (
+            "event",
+            &[
+                ("connection_built", 1),
+                ("connection_teardown", 2),
+                ("deny", 5),
+                ("acl", 6),
+            ],
+        ),
+        fields: &[
+            ("syslog_timestamp", "metadata.original_time"),
+            ("host", "device.hostname"),
+            ("message_id", "metadata.event_code|str"),
+            ("protocol", "connection_info.protocol_name|lower"),
+            ("direction", "connection_info.direction"),
+            ("connection_id", "connection_info.uid|str"),
+            ("src_interface", "src_endpoint.interface_name"),
+            ("src_ip", "src_endpoint.ip"),
+            ("src_port", "src_endpoint.port"),
+            ("dst_interface", "dst_endpoint.interface_name"),
+            ("dst_ip", "dst_endpoint.ip"),
+            ("dst_port", "dst_endpoint.port"),
+            ("bytes", "traffic.bytes"),
+            ("message", "message"),
+        ],
+    },
+    Mapping {
+        module: "fortigate",
+        class: &NETWORK_ACTIVITY,
+        product: ("FortiGate", "Fortinet"),
+        activity: (
+            "action",
+            &[
+                ("start", 1),
+                ("close", 2),
+                ("timeout", 2),
+                ("server-rst", 3),
+                ("client-rst", 3),
+                ("deny", 5),
+                ("accept", 6),
+            ],
+        ),
+        fields: &[
+            ("ts", "time|time"),
+            ("devname", "device.hostname"),
+            ("devid", "device.uid"),
+            ("logid", "metadata.event_code"),
+            ("sessionid", "connection_info.uid|str"),
+            ("proto", "connection_info.protocol_num"),
+            ("srcip", "src_endpoint.ip"),
+            ("srcport", "src_endpoint.port"),
+            ("srcintf", "src_endpoint.interface_name"),
+            ("srcmac", "src_endpoint.mac"),
+            ("dstip", "dst_endpoint.ip"),
+            ("dstport", "dst_endpoint.port"),
+            ("dstintf", "dst_endpoint.interface_name"),
+            ("app", "app_name"),
+            ("sentbyte", "traffic.bytes_out"),
+            ("rcvdbyte", "traffic.bytes_in"),
+            ("sentpkt", "traffic.packets_out"),
+            ("rcvdpkt", "traffic.packets_in"),
+            ("duration", "duration|s"),
+            ("msg", "message"),
+        ],
+    },
+    Mapping {
+        module: "panos",
+        class: &NETWORK_ACTIVITY,
+        product: ("PAN-OS", "Palo Alto Networks"),
+        activity: (
+            "subtype",
+            &[("start", 1), ("end", 2), ("drop", 5), ("deny", 5)],
+        ),
+        fields: &[
+            ("ts", "time|time"),
+            ("serial", "device.uid"),
+            ("device_name", "device.hostname"),
+            ("session_id", "connection_info.uid|str"),
+            ("proto", "connection_info.protocol_name|lower"),
+            ("src_ip", "src_endpoint.ip"),
+            ("src_port", "src_endpoint.port"),
+            ("src_zone", "src_endpoint.zone"),
+            ("inbound_if", "src_endpoint.interface_name"),
+            ("dst_ip", "dst_endpoint.ip"),
+            ("dst_port", "dst_endpoint.port"),
+            ("dst_zone", "dst_endpoint.zone"),
+            ("outbound_if", "dst_endpoint.interface_name"),
+            ("app", "app_name"),
+            ("bytes", "traffic.bytes"),
+            ("bytes_sent", "traffic.bytes_out"),
+            ("bytes_received", "traffic.bytes_in"),
+            ("packets", "traffic.packets"),
+            ("packets_sent", "traffic.packets_out"),
+            ("packets_received", "traffic.packets_in"),
+            ("elapsed_time", "duration|s"),
+            ("description", "message"),
+        ],
+    },
+    Mapping {
+        module: "vpc-flow",
+        class: &NETWORK_ACTIVITY,
+        product: ("Amazon VPC", "AWS"),
+        activity: ("action", &[("ACCEPT", 6), ("REJECT", 5)]),
+        fields: &[
+            ("version", "metadata.log_version|str"),
+            ("account_id", "cloud.account.uid"),
+            ("region", "cloud.region"),
+            ("az_id", "cloud.zone"),
+            ("interface_id", "src_endpoint.interface_uid"),
+            ("instance_id", "src_endpoint.instance_uid"),
+            ("vpc_id", "src_endpoint.vpc_uid"),
+            ("srcaddr", "src_endpoint.ip"),
+            ("srcport", "src_endpoint.port"),
+            ("dstaddr", "dst_endpoint.ip"),
+            ("dstport", "dst_endpoint.port"),
+            ("protocol", "connection_info.protocol_num"),
+            ("flow_direction", "connection_info.direction"),
+            ("packets", "traffic.packets"),
+            ("bytes", "traffic.bytes"),
+            ("start", "start_time|time"),
+            ("end", "end_time|time"),
+        ],
+    },
+    Mapping {
+        module: "elb",
+        class: &HTTP_ACTIVITY,
+        product: ("Elastic Load Balancing", "AWS"),
+        activity: ("request_method", HTTP_METHODS),
+        fields: &[
+            ("ts", "time|time"),
+            ("client_ip", "src_endpoint.ip"),
+            ("client_port", "src_endpoint.port"),
+            ("target_ip", "dst_endpoint.ip"),
+            ("target_port", "dst_endpoint.port"),
+            ("backend_ip", "dst_endpoint.ip"),
+            ("backend_port", "dst_endpoint.port"),
+            ("elb_status_code", "http_response.code"),
+            ("sent_bytes", "http_response.length"),
+            ("request_method", "http_request.http_method"),
+            ("request_url", "http_request.url.url_string"),
+            ("request_protocol", "http_request.version|http"),
+            ("user_agent", "http_request.user_agent"),
+            ("ssl_protocol", "tls.version"),
+            ("ssl_cipher", "tls.cipher"),
+            ("trace_id", "http_request.uid"),
+        ],
+    },
+];
+
+/// Modules `--schema ocsf` knows.
+pub fn modules() -> impl Iterator<Item = &'static str> {
+    MAPPINGS.iter().map(|m| m.module)
+}
+
+/// Turns a module's records into one OCSF class: `class_uid`,
+/// `activity_id`, `type_uid`, `severity_id`, `status_id` / `action_id` and
+/// `metadata` are filled in, fields are moved to their OCSF attributes and
+/// the rest land in `unmapped`; `null`s are dropped. `time` is epoch
+/// milliseconds, taken from the module's timestamp (or `start_time`); for
+/// year-less syslog timestamps only `metadata.original_time` is set.
+///
+/// For a chain the inner module's record is mapped and the outer one's
+/// fields go under `unmapped.<outer module>`.
+pub struct Ocsf {
+    mapping: &'static Mapping,
+    outer: Option<String>,
+}
+
+impl Ocsf {
+    pub fn for_module(name: &str) -> Result<Self> {
+        let (outer, inner) = split_chain(name);
+        let mapping = MAPPINGS
+            .iter()
+            .find(|m| m.module == inner)
+            .with_context(|| {
+                let known: Vec<_> = modules().collect();
+                format!(
+                    "no OCSF mapping for module {inner} (mapped: {})",
+                    known.join(", ")
+                )
+            })?;
+        Ok(Self { mapping, outer })
+    }
+
+    fn map(&self, src: &Map<String, Value>, out: &mut Map<String, Value>) {
+        let m = self.mapping;
+        let class = m.class;
+        let target = |key: &str| m.fields.iter().find(|(k, _)| *k == key).map(|(_, t)| *t);
+
+        let (field, values) = m.activity;
+        let activity_id = src
+            .get(field)
+            .and_then(Value::as_str)
+            .and_then(|v| values.iter().find(|(name, _)| *name == v))
+            .map_or(99, |(_, id)| *id);
+        let activity_name = class
+            .activities
+            .iter()
+            .find(|(id, _)| *id == activity_id)
+            .map_or("Other", |(_, name)| *name);
+        set(out, "activity_id", activity_id.into());
+        set(out, "activity_name", activity_name.into());
+        set(out, "category_uid", class.category_uid.into());
+        set(out, "category_name", class.category_name.into());
+        set(out, "class_uid", class.uid.into());
+        set(out, "class_name", class.name.into());
+        set(out, "type_uid", (class.uid * 100 + activity_id).into());
+        set(
+            out,
+            "type_name",
+            format!("{}: {activity_name}", class.name).into(),
+        );
+        set(out, "severity_id", 1.into());
+        set(out, "severity", "Informational".into());
+
+        let success = if class.uid == HTTP_ACTIVITY.uid {
+            m.fields
+                .iter()
+                .find(|(_, t)| *t == "http_response.code")
+                .and_then(|(k, _)| src.get(*k))
+                .and_then(Value::as_i64)
+                .map(|s| s < 400)
+        } else if class.uid == AUTHENTICATION.uid {
+            match src.get("outcome").and_then(Value::as_str) {
+                Some("success") => Some(true),
+                Some("failure") => Some(false),
+                _ => None,
+            }
+        } else {
+            None
+        };
+        if let Some(success) = success {
+            let (id, name) = if success {
+                (1, "Success")
+            } else {
+                (2, "Failure")
+            };
+            set(out, "status_id", id.into());
+            set(out, "status", name.into());
+        }
+        if class.uid == NETWORK_ACTIVITY.uid
+            && let Some(allowed) = ["action", "event"]
+                .iter()
+                .filter_map(|k| src.get(*k).and_then(Value::as_str))
+                .find_map(verdict)
+        {
+            let (id, name) = if allowed {
+                (1, "Allowed")
+            } else {
+                (2, "Denied")
+            };
+            set(out, "action_id", id.into());
+            set(out, "action", name.into());
+        }
+
+        let mut unmapped = Map::new();
+        for (key, v) in src {
+            if v.is_null() {
+                continue;
+            }
+            if let Some(t) = target(key) {
+                let (path, conv) = t.split_once('|').unwrap_or((t, ""));
+                match convert(v, conv) {
+                    Some(v) => set(out, path, v),
+                    None => {
+                        unmapped.insert(key.clone(), v.clone());
+                    }
+                }
+            } else if let Some(base) = key.strip_suffix("_geo")
+                && let Some(endpoint) = target(base).and_then(|t| t.strip_suffix(".ip"))
+                && let Value::Object(geo) = v
+            {
+                put_geo(out, endpoint, geo);
+            } else {
+                unmapped.insert(key.clone(), v.clone());
+            }
+        }
+        if !out.contains_key("time")
+            && let Some(start) = out.get("start_time").cloned()
+        {
+            set(out, "time", start);
+        }
+
+        let (product, vendor) = m.product;
+        set(out, "metadata.version", OCSF_VERSION.into());
+        set(out, "metadata.product.name", product.into());
+        if !vendor.is_empty() {
+            set(out, "metadata.product.vendor_name", vendor.into());
+        }
+        set(out, "metadata.log_name", m.module.into());
+        if matches!(m.module, "vpc-flow" | "elb") {
+            set(out, "cloud.provider", "AWS".into());
+        }
+        if !unmapped.is_empty() {
+            let slot = out
+                .entry("unmapped")
+                .or_insert_with(|| Value::Object(Map::new()));
+            if let Value::Object(slot) = slot {
+                slot.extend(unmapped);
+            }
+        }
+    }
+}
+
+impl Enricher for Ocsf {
+    fn enrich(&self, rec: &mut Map<String, Value>, _fields: &FieldTags) {
+        let mut src = std::mem::take(rec);
+        let sigma = take_sigma(&mut src);
+        if let Some(outer) = &self.outer {
+            let fields = unchain(&mut src);
+            if !fields.is_empty() {
+                set(rec, &format!("unmapped.{outer}"), Value::Object(fields));
+            }
+        }
+        self.map(&src, rec);
+        rec.extend(sigma);
+    }
+}
+
+/// `None` when the value does not convert, e.g. an unparsable timestamp.
+fn convert(v: &Value, conv: &str) -> Option<Value> {
+    Some(match conv {
+        "lower" => v
+            .as_str()
+            .map_or_else(|| v.clone(), |s| s.to_lowercase().into()),
+        "str" => match v {
+            Value::String(_) => v.clone(),
+            other => other.to_string().into(),
+        },
+        "http" => v.as_str().map_or_else(
+            || v.clone(),
+            |s| s.strip_prefix("HTTP/").unwrap_or(s).into(),
+        ),
+        "time" => {
+            let t = OffsetDateTime::parse(v.as_str()?, &Rfc3339).ok()?;
+            ((t.unix_timestamp_nanos() / 1_000_000) as i64).into()
+        }
+        "list" => v
+            .as_str()?
+            .split(',')
+            .map(|s| Value::from(s.trim()))
+            .collect(),
+        "us" => (v.as_i64()? / 1_000).into(),
+        "s" => ((v.as_f64()? * 1e3) as i64).into(),
+        _ => v.clone(),
+    })
+}
+
+fn put_geo(out: &mut Map<String, Value>, endpoint: &str, geo: &Map<String, Value>) {
+    let get = |k: &str| geo.get(k).filter(|v| !v.is_null()).cloned();
+    for (from, to) in [
+        ("country_code", "location.country"),
+        ("region", "location.region"),
+        ("city", "location.city"),
+        ("asn", "autonomous_system.number"),
+        ("as_org", "autonomous_system.name"),
+    ] {
+        if let Some(v) = get(from) {
+            set(out, &format!("{endpoint}.{to}"), v);
+        }
+    }
+    if let (Some(lat), Some(lon)) = (get("lat"), get("lon")) {
+        set(
+            out,
+            &format!("{endpoint}.location.coordinates"),
+            json!([lon, lat]),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ocsf(module: &str, rec: Value) -> Value {
+        let mut rec = rec.as_object().unwrap().clone();
+        Ocsf::for_module(module)
+            .unwrap()
+            .enrich(&mut rec, &FieldTags::default());
+        Value::Object(rec)
+    }
+
+    #[test]
+    fn maps_http_activity() {
+        let v = ocsf(
+            "web-access",
+            json!({
+                "vhost": null, "ip": "1.2.3.4", "user": "bob", "ts": "2000-10-10T20:55:36.5Z",
+                "method": "POST", "path": "/a", "protocol": "HTTP/1.1", "status": 503,
+                "xff": "10.0.0.1, 10.0.0.2", "duration_us": 1500, "raw": "...",
+                "ip_geo": {"country_code": "NL", "lat": 52.1, "lon": 4.3, "asn": 1136},
+            }),
+        );
+        assert_eq!(v["class_uid"], 4002);
+        assert_eq!(v["category_uid"], 4);
+        assert_eq!(v["activity_id"], 6);
+        assert_eq!(v["type_uid"], 400206);
+        assert_eq!(v["type_name"], "HTTP Activity: Post");
+        assert_eq!(v["status_id"], 2);
+        assert_eq!(v["time"], 971211336500i64);
+        assert_eq!(v["duration"], 1);
+        assert_eq!(v["src_endpoint"]["ip"], "1.2.3.4");
+        assert_eq!(v["src_endpoint"]["location"]["country"], "NL");
+        assert_eq!(
+            v["src_endpoint"]["location"]["coordinates"],
+            json!([4.3, 52.1])
+        );
+        assert_eq!(v["src_endpoint"]["autonomous_system"]["number"], 1136);
+        assert_eq!(v["http_request"]["version"], "1.1");
+        assert_eq!(
+            v["http_request"]["x_forwarded_for"],
+            json!(["10.0.0.1", "10.0.0.2"])
+        );
+        assert_eq!(v["http_response"]["code"], 503);
+        assert_eq!(v["raw_data"], "...");
+        assert_eq!(v["metadata"]["version"], OCSF_VERSION);
+        assert_eq!(v["unmapped"], json!({"user": "bob"}));
+
+        let v = ocsf(
+            "elb",
+            json!({"type": "h2", "ts": "2018-07-02T22:23:00.186641Z", "elb": "app/lb/50dc",
+                   "client_ip": "192.168.131.39", "backend_ip": null, "elb_status_code": 200,
+                   "request_method": "GET", "request_protocol": "HTTP/2.0"}),
+        );
+        assert_eq!(v["class_uid"], 4002);
+        assert_eq!(v["activity_name"], "Get");
+        assert_eq!(v["status"], "Success");
+        assert_eq!(v["time"], 1530570180186i64);
+        assert_eq!(v["src_endpoint"]["ip"], "192.168.131.39");
+        assert_eq!(v["http_request"]["version"], "2.0");
+        assert_eq!(v["cloud"]["provider"], "AWS");
+        assert_eq!(v["unmapped"], json!({"type": "h2", "elb": "app/lb/50dc"}));
+    }
+
+    #[test]
+    fn maps_network_and_authentication() {
+        let v = ocsf(
+            "vpc-flow",
+            json!({"srcaddr": "10.0.0.1", "dstport": 22, "protocol": 6, "action": "REJECT",
+                   "start": "2014-12-14T04:06:50Z", "log_status": "OK"}),
+        );
+        assert_eq!(v["class_uid"], 4001);
+        assert_eq!(v["activity_name"], "Refuse");
+        assert_eq!(v["action_id"], 2);
+        assert_eq!(v["time"], 1418530010000i64);
+        assert_eq!(v["dst_endpoint"]["port"], 22);
+        assert_eq!(v["cloud"]["provider"], "AWS");
+        assert_eq!(v["metadata"]["product"]["vendor_name"], "AWS");
+        assert_eq!(
+            v["unmapped"],
+            json!({"action": "REJECT", "log_status": "OK"})
+        );
+
+        let v = ocsf(
+            "docker-json,authlog",
+            json!({"stream": "stdout", "parsed": {
+                "event": "ssh_login", "outcome": "failure", "user": "root", "src_ip": "203.0.113.5",
+            }}),
+        );
+        assert_eq!(v["class_uid"], 3002);
+        assert_eq!(v["type_uid"], 300201);
+        assert_eq!(v["status"], "Failure");
+        assert_eq!(v["user"]["name"], "root");
+        assert_eq!(v["unmapped"]["docker_json"], json!({"stream": "stdout"}));
+        assert!(v.get("action_id").is_none());
+
+        assert!(Ocsf::for_module("zeek").is_err());
+    }
+}
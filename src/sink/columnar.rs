@@ -0,0 +1,609 @@
+//! `--format parquet` and `--format arrow`: records gathered into Arrow
+//! arrays and written as Parquet row groups or as an Arrow IPC stream, for
+//! DuckDB, Spark, polars or pandas to read without parsing JSON.
+
+use std::{collections::BTreeSet, io::Write, sync::Arc};
+
+use anyhow::{Context, Result};
+use arrow_array::{
+    builder::{BooleanBuilder, Float64Builder, Int64Builder, StringBuilder},
+    ArrayRef, RecordBatch,
+};
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use parquet::{
+    arrow::ArrowWriter,
+    basic::{Compression, ZstdLevel},
+    file::properties::WriterProperties,
+};
+use serde_json::{Map, Value};
+
+use super::{
+    columns::{text, ColumnType, Columns},
+    Sink,
+};
+use crate::core::FieldType;
+
+/// Records looked at to settle the schema before anything is written.
+const SAMPLE: usize = 1000;
+
+/// Rows gathered into one record batch.
+const BATCH_ROWS: usize = 8192;
+
+/// Rows per Parquet row group.
+const ROW_GROUP_ROWS: usize = 128 * 1024;
+
+/// Column holding, per row, what the fixed schema has no place for: fields
+/// first seen after it was fixed and values not fitting their column, as a
+/// JSON object (null when there are none).
+const EXTRA: &str = "_extra";
+
+/// Builds record batches of a schema fixed before the first one: the
+/// module's declared fields with their types, then the other fields of the
+/// first records (as text when the module declares fields, else typed from
+/// the values), then [`EXTRA`]. One nullable column per field, `Int64`,
+/// `Float64`, `Boolean` or `Utf8` (also for nested objects and arrays, as
+/// JSON); a column whose first records hold values of another type is
+/// widened to `Utf8`.
+pub(crate) struct Batcher {
+    schema: SchemaRef,
+    columns: Columns,
+    builders: Vec<Builder>,
+    extra: StringBuilder,
+    rows: usize,
+    /// Fields first seen after the schema was fixed; kept in [`EXTRA`].
+    late: BTreeSet<String>,
+    /// Values that did not fit their column's type; kept in [`EXTRA`].
+    mismatched: u64,
+}
+
+enum Builder {
+    Bool(BooleanBuilder),
+    Int(Int64Builder),
+    Float(Float64Builder),
+    Text(StringBuilder),
+}
+
+impl Batcher {
+    pub(crate) fn new(declared: &[(String, FieldType)], sample: &[Map<String, Value>]) -> Self {
+        let mut columns = Columns::default();
+        for (name, ty) in declared {
+            columns.add(name, ColumnType::from(*ty));
+        }
+        let typed = columns.len();
+        for rec in sample {
+            columns.observe(rec);
+        }
+        if typed > 0 {
+            for ty in &mut columns.types[typed..] {
+                *ty = ColumnType::Text;
+            }
+        }
+        let (mut fields, builders) = columns
+            .names
+            .iter()
+            .zip(&columns.types)
+            .map(|(name, ty)| {
+                let (dt, b) = match ty {
+                    ColumnType::Bool => (DataType::Boolean, Builder::Bool(BooleanBuilder::new())),
+                    ColumnType::Int => (DataType::Int64, Builder::Int(Int64Builder::new())),
+                    ColumnType::Float => (DataType::Float64, Builder::Float(Float64Builder::new())),
+                    ColumnType::Null | ColumnType::Text => {
+                        (DataType::Utf8, Builder::Text(StringBuilder::new()))
+                    }
+                };
+                (Field::new(name, dt, true), b)
+            })
+            .unzip::<_, _, Vec<_>, Vec<_>>();
+        fields.push(Field::new(EXTRA, DataType::Utf8, true));
+        Self {
+            schema: Arc::new(Schema::new(fields)),
+            columns,
+            builders,
+            extra: StringBuilder::new(),
+            rows: 0,
+            late: BTreeSet::new(),
+            mismatched: 0,
+        }
+    }
+
+    pub(crate) fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    /// Rows pushed since the last batch.
+    pub(crate) fn len(&self) -> usize {
+        self.rows
+    }
+
+    pub(crate) fn push(&mut self, rec: &Map<String, Value>) {
+        let mut extra = Map::new();
+        for (k, v) in rec {
+            if self.columns.position(k).is_none() {
+                if !self.late.contains(k) {
+                    self.late.insert(k.clone());
+                }
+                extra.insert(k.clone(), v.clone());
+            }
+        }
+        for (name, b) in self.columns.names.iter().zip(&mut self.builders) {
+            let v = rec.get(name).unwrap_or(&Value::Null);
+            let fits = match b {
+                Builder::Bool(b) => append(b, v, Value::as_bool),
+                Builder::Int(b) => append(b, v, |v| v.as_i64().or(v.as_bool().map(i64::from))),
+                Builder::Float(b) => append(b, v, |v| {
+                    v.as_f64().or(v.as_bool().map(|b| f64::from(u8::from(b))))
+                }),
+                Builder::Text(b) => append(b, v, |v| (!v.is_null()).then(|| text(v))),
+            };
+            if !fits {
+                self.mismatched += 1;
+                extra.insert(name.clone(), v.clone());
+            }
+        }
+        self.extra
+            .append_option((!extra.is_empty()).then(|| Value::Object(extra).to_string()));
+        self.rows += 1;
+    }
+
+    /// The rows pushed so far as a batch; the builders start over.
+    pub(crate) fn batch(&mut self) -> Result<RecordBatch> {
+        let mut arrays: Vec<ArrayRef> = self
+            .builders
+            .iter_mut()
+            .map(|b| -> ArrayRef {
+                match b {
+                    Builder::Bool(b) => Arc::new(b.finish()),
+                    Builder::Int(b) => Arc::new(b.finish()),
+                    Builder::Float(b) => Arc::new(b.finish()),
+                    Builder::Text(b) => Arc::new(b.finish()),
+                }
+            })
+            .collect();
+        arrays.push(Arc::new(self.extra.finish()));
+        self.rows = 0;
+        Ok(RecordBatch::try_new(self.schema.clone(), arrays)?)
+    }
+
+    /// What went to [`EXTRA`] instead of its own column, one warning each.
+    pub(crate) fn spilled(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        if !self.late.is_empty() {
+            let names: Vec<_> = self.late.iter().map(String::as_str).collect();
+            out.push(format!(
+                "fields not in the schema, kept in {EXTRA}: {}",
+                names.join(", ")
+            ));
+        }
+        if self.mismatched > 0 {
+            out.push(format!(
+                "{} values not matching their column's type, kept in {EXTRA}",
+                self.mismatched
+            ));
+        }
+        out
+    }
+}
+
+/// Append `v` converted by `conv`, or null. False when a non-null value did
+/// not convert.
+fn append<T, B>(b: &mut B, v: &Value, conv: impl Fn(&Value) -> Option<T>) -> bool
+where
+    B: Extend<Option<T>>,
+{
+    let cell = conv(v);
+    let fits = cell.is_some() || v.is_null();
+    b.extend(std::iter::once(cell));
+    fits
+}
+
+/// Files written from record batches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Columnar {
+    Parquet,
+    /// The Arrow IPC streaming format.
+    ArrowIpc,
+}
+
+impl Columnar {
+    fn label(self) -> &'static str {
+        match self {
+            Columnar::Parquet => "Parquet",
+            Columnar::ArrowIpc => "Arrow IPC",
+        }
+    }
+}
+
+enum TableWriter {
+    Parquet(ArrowWriter<Box<dyn Write + Send>>),
+    ArrowIpc(StreamWriter<Box<dyn Write + Send>>),
+}
+
+impl TableWriter {
+    fn new(kind: Columnar, out: Box<dyn Write + Send>, schema: SchemaRef) -> Result<Self> {
+        Ok(match kind {
+            Columnar::Parquet => {
+                let props = WriterProperties::builder()
+                    .set_compression(Compression::ZSTD(ZstdLevel::default()))
+                    .set_max_row_group_size(ROW_GROUP_ROWS)
+                    .build();
+                TableWriter::Parquet(ArrowWriter::try_new(out, schema, Some(props))?)
+            }
+            Columnar::ArrowIpc => TableWriter::ArrowIpc(StreamWriter::try_new(out, &schema)?),
+        })
+    }
+
+    fn write(&mut self, batch: &RecordBatch) -> Result<()> {
+        match self {
+            TableWriter::Parquet(w) => w.write(batch)?,
+            TableWriter::ArrowIpc(w) => w.write(batch)?,
+        }
+        Ok(())
+    }
+
+    /// Ends the current Parquet row group; pushes the stream out.
+    fn flush(&mut self) -> Result<()> {
+        match self {
+            TableWriter::Parquet(w) => w.flush()?,
+            TableWriter::ArrowIpc(w) => w.flush()?,
+        }
+        Ok(())
+    }
+
+    /// Writes the Parquet footer or the end-of-stream marker.
+    fn close(self) -> Result<()> {
+        let mut out = match self {
+            TableWriter::Parquet(w) => w.into_inner()?,
+            TableWriter::ArrowIpc(mut w) => {
+                w.finish()?;
+                w.into_inner()?
+            }
+        };
+        out.flush()?;
+        Ok(())
+    }
+}
+
+/// Samples the first records for the schema, then writes record batches.
+/// A Parquet footer is written by `finish`, so an interrupted run leaves an
+/// unreadable file; an IPC stream is readable up to its last batch.
+pub struct ColumnarSink {
+    kind: Columnar,
+    out: Option<Box<dyn Write + Send>>,
+    /// The module's fields, from [`Sink::declare`].
+    declared: Vec<(String, FieldType)>,
+    sample: Vec<Map<String, Value>>,
+    batcher: Option<Batcher>,
+    writer: Option<TableWriter>,
+    written: u64,
+}
+
+impl ColumnarSink {
+    pub(crate) fn new(kind: Columnar, out: Box<dyn Write + Send>) -> Self {
+        Self {
+            kind,
+            out: Some(out),
+            declared: Vec::new(),
+            sample: Vec::new(),
+            batcher: None,
+            writer: None,
+            written: 0,
+        }
+    }
+
+    fn start(&mut self) -> Result<()> {
+        let batcher = Batcher::new(&self.declared, &self.sample);
+        let out = self.out.take().expect("writer started twice");
+        self.writer = Some(
+            TableWriter::new(self.kind, out, batcher.schema())
+                .with_context(|| format!("start {} output", self.kind.label()))?,
+        );
+        self.batcher = Some(batcher);
+        for rec in std::mem::take(&mut self.sample) {
+            self.push(&rec)?;
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, rec: &Map<String, Value>) -> Result<()> {
+        let batcher = self.batcher.as_mut().expect("schema is fixed");
+        batcher.push(rec);
+        if batcher.len() >= BATCH_ROWS {
+            self.write_batch()?;
+        }
+        Ok(())
+    }
+
+    fn write_batch(&mut self) -> Result<()> {
+        let (Some(batcher), Some(writer)) = (&mut self.batcher, &mut self.writer) else {
+            return Ok(());
+        };
+        if batcher.len() == 0 {
+            return Ok(());
+        }
+        let batch = batcher.batch()?;
+        writer
+            .write(&batch)
+            .with_context(|| format!("write {} batch", self.kind.label()))?;
+        self.written += batch.num_rows() as u64;
+        Ok(())
+    }
+}
+
+impl Sink for ColumnarSink {
+    fn write_blob(&mut self, blob: &[u8]) -> Result<()> {
+        for line in blob.split(|&b| b == b'\n').filter(|l| !l.is_empty()) {
+            let rec: Map<String, Value> =
+                serde_json::from_slice(line).context("record is not a JSON object")?;
+            if self.batcher.is_some() {
+                self.push(&rec)?;
+            } else {
+                self.sample.push(rec);
+                if self.sample.len() >= SAMPLE {
+                    self.start()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.batcher.is_none() && !self.sample.is_empty() {
+            self.start()?;
+        }
+        self.write_batch()?;
+        if let Some(writer) = &mut self.writer {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        if self.batcher.is_none() {
+            // Even no records make a valid (empty) file or stream.
+            self.start()?;
+        }
+        self.write_batch()?;
+        let label = self.kind.label();
+        let writer = self.writer.take().expect("schema is fixed");
+        writer
+            .close()
+            .with_context(|| format!("close {label} output"))?;
+        let batcher = self.batcher.as_ref().expect("schema is fixed");
+        log::info!(
+            "{label}: {} rows, {} columns",
+            self.written,
+            batcher.schema.fields().len()
+        );
+        for spill in batcher.spilled() {
+            log::warn!("{label}: {spill}");
+        }
+        Ok(())
+    }
+
+    fn declare(&mut self, fields: &[(String, FieldType)]) {
+        self.declared = fields.to_vec();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{Array, BooleanArray, Float64Array, Int64Array, StringArray};
+    use arrow_ipc::reader::StreamReader;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    #[test]
+    fn writes_readable_parquet() {
+        let path = std::env::temp_dir().join(format!("turbolp-{}.parquet", std::process::id()));
+        let fh = std::fs::File::create(&path).unwrap();
+        let mut sink: Box<dyn Sink> = Box::new(ColumnarSink::new(Columnar::Parquet, Box::new(fh)));
+        sink.write_blob(
+            b"{\"ip\":\"1.2.3.4\",\"status\":200,\"bytes\":null,\"ua\":{\"bot\":true}}\n\
+              {\"ip\":\"::1\",\"status\":404,\"bytes\":12.5,\"ua\":null}\n",
+        )
+        .unwrap();
+        sink.flush().unwrap();
+        sink.write_blob(b"{\"ip\":\"5.6.7.8\",\"status\":\"-\",\"late\":1}\n")
+            .unwrap();
+        sink.finish().unwrap();
+
+        let fh = std::fs::File::open(&path).unwrap();
+        let reader = ParquetRecordBatchReaderBuilder::try_new(fh).unwrap();
+        let schema = reader.schema().clone();
+        let batches: Vec<_> = reader.build().unwrap().collect::<Result<_, _>>().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let types: Vec<_> = schema
+            .fields()
+            .iter()
+            .map(|f| (f.name().as_str(), f.data_type().clone()))
+            .collect();
+        assert_eq!(
+            types,
+            [
+                ("ip", DataType::Utf8),
+                ("status", DataType::Int64),
+                ("bytes", DataType::Float64),
+                ("ua", DataType::Utf8),
+                ("_extra", DataType::Utf8),
+            ]
+        );
+        assert_eq!(
+            values(&batches, 0, |a: &StringArray, i| a.value(i).to_string()),
+            [
+                Some("1.2.3.4".into()),
+                Some("::1".into()),
+                Some("5.6.7.8".into())
+            ]
+        );
+        assert_eq!(
+            values(&batches, 1, |a: &Int64Array, i| a.value(i)),
+            [Some(200), Some(404), None]
+        );
+        assert_eq!(
+            values(&batches, 2, |a: &Float64Array, i| a.value(i)),
+            [None, Some(12.5), None]
+        );
+        assert_eq!(
+            values(&batches, 3, |a: &StringArray, i| a.value(i).to_string()),
+            [Some("{\"bot\":true}".into()), None, None]
+        );
+        assert_eq!(
+            values(&batches, 4, |a: &StringArray, i| a.value(i).to_string()),
+            [None, None, Some(r#"{"late":1,"status":"-"}"#.into())]
+        );
+    }
+
+    #[test]
+    fn declared_fields_type_the_schema_and_nothing_is_lost() {
+        let declared = [
+            ("ts".to_string(), FieldType::Text),
+            ("status".to_string(), FieldType::Int),
+            ("bytes".to_string(), FieldType::Int),
+        ];
+        let recs: Vec<Map<String, Value>> = (0..2000)
+            .map(|i| {
+                let mut rec = serde_json::json!({"ts": "t", "status": 200, "bytes": i, "geo": 1});
+                if i == 7 {
+                    // Still in the sample: the column becomes text.
+                    rec["bytes"] = "-".into();
+                }
+                if i >= 1500 {
+                    rec["user"] = "bob".into();
+                }
+                if i == 1600 {
+                    rec["status"] = "n/a".into();
+                }
+                serde_json::from_value(rec).unwrap()
+            })
+            .collect();
+        let mut batcher = Batcher::new(&declared, &recs[..SAMPLE]);
+        for rec in &recs {
+            batcher.push(rec);
+        }
+        let batches = [batcher.batch().unwrap()];
+
+        let types: Vec<_> = batches[0]
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| (f.name().clone(), f.data_type().clone()))
+            .collect();
+        assert_eq!(
+            types,
+            [
+                ("ts".into(), DataType::Utf8),
+                ("status".into(), DataType::Int64),
+                ("bytes".into(), DataType::Utf8),
+                ("geo".into(), DataType::Utf8),
+                ("_extra".into(), DataType::Utf8),
+            ]
+        );
+        let bytes = values(&batches, 2, |a: &StringArray, i| a.value(i).to_string());
+        assert_eq!(bytes[7].as_deref(), Some("-"));
+        assert_eq!(bytes[8].as_deref(), Some("8"));
+
+        let extra = values(&batches, 4, |a: &StringArray, i| a.value(i).to_string());
+        assert_eq!(extra[1499], None);
+        assert_eq!(extra[1500].as_deref(), Some(r#"{"user":"bob"}"#));
+        assert_eq!(
+            extra[1600].as_deref(),
+            Some(r#"{"user":"bob","status":"n/a"}"#)
+        );
+        let status = values(&batches, 1, |a: &Int64Array, i| a.value(i));
+        assert_eq!(status[1600], None);
+        assert_eq!(
+            batcher.spilled(),
+            [
+                "fields not in the schema, kept in _extra: user",
+                "1 values not matching their column's type, kept in _extra"
+            ]
+        );
+    }
+
+    #[test]
+    fn streams_arrow_ipc_batches() {
+        let path = std::env::temp_dir().join(format!("turbolp-{}.arrows", std::process::id()));
+        let fh = std::fs::File::create(&path).unwrap();
+        let mut sink: Box<dyn Sink> = Box::new(ColumnarSink::new(Columnar::ArrowIpc, Box::new(fh)));
+        sink.write_blob(b"{\"ip\":\"1.2.3.4\",\"ok\":true}\n")
+            .unwrap();
+        sink.flush().unwrap();
+        sink.write_blob(b"{\"ip\":\"::1\",\"ok\":false}\n").unwrap();
+        sink.finish().unwrap();
+
+        let fh = std::fs::File::open(&path).unwrap();
+        let reader = StreamReader::try_new(fh, None).unwrap();
+        let batches: Vec<_> = reader.collect::<Result<_, _>>().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(batches.len(), 2);
+        assert_eq!(
+            values(&batches, 0, |a: &StringArray, i| a.value(i).to_string()),
+            [Some("1.2.3.4".into()), Some("::1".into())]
+        );
+        assert_eq!(
+            values(&batches, 1, |a: &BooleanArray, i| a.value(i)),
+            [Some(true), Some(false)]
+        );
+    }
+
+    #[test]
+    fn arrow_ipc_uses_the_declared_schema() {
+        let path = std::env::temp_dir().join(format!("turbolp-{}-decl.arrows", std::process::id()));
+        let fh = std::fs::File::create(&path).unwrap();
+        let mut sink: Box<dyn Sink> = Box::new(ColumnarSink::new(Columnar::ArrowIpc, Box::new(fh)));
+        sink.declare(&[
+            ("status".to_string(), FieldType::Int),
+            ("ok".to_string(), FieldType::Bool),
+        ]);
+        sink.write_blob(b"{\"status\":200,\"ok\":true}\n").unwrap();
+        sink.flush().unwrap();
+        sink.write_blob(b"{\"status\":\"-\",\"ok\":false,\"user\":\"bob\"}\n")
+            .unwrap();
+        sink.finish().unwrap();
+
+        let fh = std::fs::File::open(&path).unwrap();
+        let reader = StreamReader::try_new(fh, None).unwrap();
+        let batches: Vec<_> = reader.collect::<Result<_, _>>().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let types: Vec<_> = batches[0]
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| (f.name().clone(), f.data_type().clone()))
+            .collect();
+        assert_eq!(
+            types,
+            [
+                ("status".into(), DataType::Int64),
+                ("ok".into(), DataType::Boolean),
+                ("_extra".into(), DataType::Utf8),
+            ]
+        );
+        assert_eq!(
+            values(&batches, 0, |a: &Int64Array, i| a.value(i)),
+            [Some(200), None]
+        );
+        assert_eq!(
+            values(&batches, 2, |a: &StringArray, i| a.value(i).to_string()),
+            [None, Some(r#"{"user":"bob","status":"-"}"#.into())]
+        );
+    }
+
+    /// Column `col` across `batches`.
+    fn values<A: Array + 'static, T>(
+        batches: &[RecordBatch],
+        col: usize,
+        get: impl Fn(&A, usize) -> T,
+    ) -> Vec<Option<T>> {
+        let mut out = Vec::new();
+        for b in batches {
+            let a = b.column(col).as_any().downcast_ref::<A>().unwrap();
+            out.extend((0..a.len()).map(|i| (!a.is_null(i)).then(|| get(a, i))));
+        }
+        out
+    }
+}
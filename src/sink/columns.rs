@@ -0,0 +1,139 @@
+//! Column names and types for tabular outputs, gathered from the records
+//! themselves: modules write every field of a record (as `null` when
+//! absent), so the first records give the module's fields in its order,
+//! and enrichment or `--transform` fields join as they appear.
+
+use std::collections::HashMap;
+
+use serde_json::{Map, Value};
+
+use crate::core::FieldType;
+
+/// A column's type: the widest of the values seen so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum ColumnType {
+    /// Only `null`s so far.
+    Null,
+    Bool,
+    Int,
+    Float,
+    /// Strings, and objects or arrays as JSON text.
+    Text,
+}
+
+impl ColumnType {
+    pub(crate) fn of(v: &Value) -> Self {
+        match v {
+            Value::Null => ColumnType::Null,
+            Value::Bool(_) => ColumnType::Bool,
+            Value::Number(n) if n.is_i64() => ColumnType::Int,
+            Value::Number(_) => ColumnType::Float,
+            _ => ColumnType::Text,
+        }
+    }
+
+    /// A type holding both. Booleans and numbers mix as numbers.
+    pub(crate) fn widen(self, other: Self) -> Self {
+        let (lo, hi) = (self.min(other), self.max(other));
+        match (lo, hi) {
+            (ColumnType::Null, _) => hi,
+            _ if lo == hi => hi,
+            (ColumnType::Bool | ColumnType::Int, ColumnType::Int | ColumnType::Float) => hi,
+            _ => ColumnType::Text,
+        }
+    }
+}
+
+impl From<FieldType> for ColumnType {
+    fn from(ty: FieldType) -> Self {
+        match ty {
+            FieldType::Bool => ColumnType::Bool,
+            FieldType::Int => ColumnType::Int,
+            FieldType::Float => ColumnType::Float,
+            FieldType::Text => ColumnType::Text,
+        }
+    }
+}
+
+/// The columns seen so far, in first-seen order.
+#[derive(Debug, Default)]
+pub(crate) struct Columns {
+    pub(crate) names: Vec<String>,
+    pub(crate) types: Vec<ColumnType>,
+    index: HashMap<String, usize>,
+}
+
+impl Columns {
+    pub(crate) fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub(crate) fn position(&self, name: &str) -> Option<usize> {
+        self.index.get(name).copied()
+    }
+
+    /// Add a column of `ty` unless it exists; its position either way.
+    pub(crate) fn add(&mut self, name: &str, ty: ColumnType) -> usize {
+        if let Some(i) = self.position(name) {
+            return i;
+        }
+        self.index.insert(name.to_string(), self.names.len());
+        self.names.push(name.to_string());
+        self.types.push(ty);
+        self.names.len() - 1
+    }
+
+    /// Take in `rec`'s fields: new ones are added, and types widened.
+    /// Returns the positions of the columns added.
+    pub(crate) fn observe(&mut self, rec: &Map<String, Value>) -> Vec<usize> {
+        let mut added = Vec::new();
+        for (k, v) in rec {
+            let ty = ColumnType::of(v);
+            match self.position(k) {
+                Some(i) => self.types[i] = self.types[i].widen(ty),
+                None => added.push(self.add(k, ty)),
+            }
+        }
+        added
+    }
+}
+
+/// A value as cell text: strings as they are, objects and arrays as JSON.
+pub(crate) fn text(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn gathers_columns_and_widens_types() {
+        let mut cols = Columns::default();
+        let recs = [
+            json!({"ip": "1.2.3.4", "status": 200, "bytes": null, "ok": true}),
+            json!({"ip": "::1", "status": 404, "bytes": 12, "ok": 1, "geo": {"cc": "NL"}}),
+            json!({"status": "-", "bytes": 1.5}),
+        ];
+        let added: Vec<_> = recs
+            .iter()
+            .map(|r| cols.observe(r.as_object().unwrap()))
+            .collect();
+        assert_eq!(added, [vec![0, 1, 2, 3], vec![4], vec![]]);
+        assert_eq!(cols.names, ["ip", "status", "bytes", "ok", "geo"]);
+        assert_eq!(
+            cols.types,
+            [
+                ColumnType::Text,
+                ColumnType::Text,
+                ColumnType::Float,
+                ColumnType::Int,
+                ColumnType::Text
+            ]
+        );
+    }
+}
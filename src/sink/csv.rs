@@ -0,0 +1,225 @@
+//! `--format csv` / `--format tsv`: one row per record under a header row,
+//! for spreadsheets and SIEM imports that take no JSON.
+
+use std::collections::BTreeSet;
+
+use anyhow::{Context, Result};
+use serde_json::{Map, Value};
+
+use super::{
+    columns::{text, Columns},
+    Sink,
+};
+use crate::expr::field;
+
+/// Records looked at to settle the header when `--fields` does not give it.
+const SAMPLE: usize = 1000;
+
+/// Writes delimited rows through another sink (a file, compressed or not, or
+/// stdout). The columns are the `--fields`, in that order, or else the
+/// fields of the first records. Values holding the delimiter, a quote or a
+/// line break are quoted, quotes doubled (RFC 4180); null is an empty cell
+/// and objects or arrays are JSON.
+pub struct CsvSink {
+    inner: Box<dyn Sink>,
+    delimiter: u8,
+    label: &'static str,
+    /// Column paths, once known; dotted ones reach into nested objects.
+    columns: Option<Vec<String>>,
+    sample: Vec<Map<String, Value>>,
+    /// Fields left out because the header was already written without them.
+    dropped: BTreeSet<String>,
+    buf: Vec<u8>,
+    rows: u64,
+}
+
+impl CsvSink {
+    /// `delimiter` is `,` or `\t`; `fields` fixes the columns when not empty.
+    pub fn new(inner: Box<dyn Sink>, delimiter: u8, fields: &[String]) -> Self {
+        let mut sink = Self {
+            inner,
+            delimiter,
+            label: if delimiter == b'\t' { "TSV" } else { "CSV" },
+            columns: None,
+            sample: Vec::new(),
+            dropped: BTreeSet::new(),
+            buf: Vec::new(),
+            rows: 0,
+        };
+        if !fields.is_empty() {
+            sink.set_columns(fields.to_vec());
+        }
+        sink
+    }
+
+    /// Fix the columns and queue the header row.
+    fn set_columns(&mut self, columns: Vec<String>) {
+        for (i, name) in columns.iter().enumerate() {
+            if i > 0 {
+                self.buf.push(self.delimiter);
+            }
+            push_cell(&mut self.buf, name, self.delimiter);
+        }
+        self.buf.extend_from_slice(b"\n");
+        self.columns = Some(columns);
+    }
+
+    /// Settle the columns from the sampled records and write those out.
+    fn start(&mut self) {
+        let mut columns = Columns::default();
+        for rec in &self.sample {
+            columns.observe(rec);
+        }
+        self.set_columns(columns.names);
+        for rec in std::mem::take(&mut self.sample) {
+            self.push_row(&rec);
+        }
+    }
+
+    fn push_row(&mut self, rec: &Map<String, Value>) {
+        let columns = self.columns.as_ref().expect("columns are fixed");
+        for k in rec.keys() {
+            // A field is in a dotted column when the column names it or one of its keys.
+            let listed = columns.iter().any(|c| {
+                c == k
+                    || c.strip_prefix(k.as_str())
+                        .is_some_and(|r| r.starts_with('.'))
+            });
+            if !listed && !self.dropped.contains(k) {
+                self.dropped.insert(k.clone());
+            }
+        }
+        for (i, path) in columns.iter().enumerate() {
+            if i > 0 {
+                self.buf.push(self.delimiter);
+            }
+            match field(rec, path) {
+                None | Some(Value::Null) => {}
+                Some(v) => push_cell(&mut self.buf, &text(v), self.delimiter),
+            }
+        }
+        self.buf.extend_from_slice(b"\n");
+        self.rows += 1;
+    }
+
+    fn write_out(&mut self) -> Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        self.inner.write_blob(&self.buf)?;
+        self.buf.clear();
+        Ok(())
+    }
+}
+
+impl Sink for CsvSink {
+    fn write_blob(&mut self, blob: &[u8]) -> Result<()> {
+        for line in blob.split(|&b| b == b'\n').filter(|l| !l.is_empty()) {
+            let rec: Map<String, Value> =
+                serde_json::from_slice(line).context("record is not a JSON object")?;
+            if self.columns.is_some() {
+                self.push_row(&rec);
+            } else {
+                self.sample.push(rec);
+                if self.sample.len() >= SAMPLE {
+                    self.start();
+                }
+            }
+        }
+        self.write_out()
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.columns.is_none() && !self.sample.is_empty() {
+            self.start();
+        }
+        self.write_out()?;
+        self.inner.flush()
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        if self.columns.is_none() {
+            self.start();
+        }
+        self.write_out()?;
+        let columns = self.columns.as_ref().map_or(0, Vec::len);
+        let Self {
+            inner,
+            label,
+            rows,
+            dropped,
+            ..
+        } = *self;
+        inner.finish()?;
+        log::info!("{label}: {rows} rows, {columns} columns");
+        if !dropped.is_empty() {
+            let names: Vec<_> = dropped.iter().map(String::as_str).collect();
+            log::warn!(
+                "{label}: fields not in the header, dropped: {}",
+                names.join(", ")
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Append `s`, quoted when it holds the delimiter, a quote or a line break.
+fn push_cell(buf: &mut Vec<u8>, s: &str, delimiter: u8) {
+    let quote = s
+        .bytes()
+        .any(|b| b == delimiter || b == b'"' || b == b'\n' || b == b'\r');
+    if !quote {
+        buf.extend_from_slice(s.as_bytes());
+        return;
+    }
+    buf.push(b'"');
+    for part in s.split_inclusive('"') {
+        buf.extend_from_slice(part.as_bytes());
+        if part.ends_with('"') {
+            buf.push(b'"');
+        }
+    }
+    buf.push(b'"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::tests::Capture;
+
+    #[test]
+    fn writes_quoted_rows_under_header() {
+        let (capture, out) = Capture::new();
+        let mut sink: Box<dyn Sink> = Box::new(CsvSink::new(Box::new(capture), b',', &[]));
+        sink.write_blob(
+            b"{\"ip\":\"1.2.3.4\",\"status\":200,\"ua\":\"Mozilla/5.0 (X11, \\\"Linux\\\")\"}\n\
+              {\"ip\":\"::1\",\"status\":null,\"ua\":{\"bot\":true}}\n",
+        )
+        .unwrap();
+        sink.flush().unwrap();
+        sink.write_blob(b"{\"ip\":\"5.6.7.8\",\"late\":\"x\"}\n")
+            .unwrap();
+        sink.finish().unwrap();
+        assert_eq!(
+            String::from_utf8(out.lock().unwrap().clone()).unwrap(),
+            "ip,status,ua\n\
+             1.2.3.4,200,\"Mozilla/5.0 (X11, \"\"Linux\"\")\"\n\
+             ::1,,\"{\"\"bot\"\":true}\"\n\
+             5.6.7.8,,\n"
+        );
+    }
+
+    #[test]
+    fn takes_columns_from_fields() {
+        let (capture, out) = Capture::new();
+        let fields = ["status".to_string(), "parsed.user".into(), "ip".into()];
+        let mut sink: Box<dyn Sink> = Box::new(CsvSink::new(Box::new(capture), b'\t', &fields));
+        sink.write_blob(b"{\"ip\":\"1.2.3.4\",\"status\":200,\"parsed\":{\"user\":\"a\\tb\"}}\n")
+            .unwrap();
+        sink.finish().unwrap();
+        assert_eq!(
+            String::from_utf8(out.lock().unwrap().clone()).unwrap(),
+            "status\tparsed.user\tip\n200\t\"a\tb\"\t1.2.3.4\n"
+        );
+    }
+}
@@ -0,0 +1,397 @@
+//! Elasticsearch / OpenSearch `_bulk` output: an action line in front of
+//! every record, either written out like any JSONL or sent to a cluster in
+//! batches.
+
+use std::{borrow::Cow, thread, time::Duration};
+
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use time::OffsetDateTime;
+
+use super::{backoff, Sink, DATE_FIELDS, RETRIES};
+
+/// `_bulk` output settings.
+#[derive(Debug, Clone)]
+pub struct ElasticOptions {
+    /// Target index; `%Y`, `%m` and `%d` are filled from each record's
+    /// date (`logs-%Y.%m.%d`).
+    pub index: String,
+    /// Cluster to send to (`https://host:9200`) instead of writing the
+    /// bulk lines out.
+    pub url: Option<String>,
+    pub auth: Option<ElasticAuth>,
+    /// Request body size at which a batch is sent.
+    pub batch_bytes: usize,
+}
+
+#[derive(Debug, Clone)]
+pub enum ElasticAuth {
+    /// `user:password`.
+    Basic(String),
+    ApiKey(String),
+}
+
+impl ElasticAuth {
+    fn header(&self) -> String {
+        match self {
+            ElasticAuth::Basic(creds) => format!(
+                "Basic {}",
+                base64::engine::general_purpose::STANDARD.encode(creds)
+            ),
+            ElasticAuth::ApiKey(key) => format!("ApiKey {key}"),
+        }
+    }
+}
+
+/// Turns records into `{"create":{"_index":...}}` + record line pairs.
+/// `create` works for plain indices and data streams alike.
+struct BulkLines {
+    index: String,
+    dated: bool,
+}
+
+impl BulkLines {
+    fn new(index: &str) -> Self {
+        Self {
+            index: index.to_string(),
+            dated: ["%Y", "%m", "%d"].iter().any(|p| index.contains(p)),
+        }
+    }
+
+    fn push(&self, record: &[u8], out: &mut Vec<u8>) {
+        let action = serde_json::json!({"create": {"_index": self.index_for(record)}});
+        serde_json::to_writer(&mut *out, &action).expect("serialize bulk action");
+        out.push(b'\n');
+        out.extend_from_slice(record);
+        out.push(b'\n');
+    }
+
+    /// The index for `record`; records without a date use today's (UTC).
+    fn index_for(&self, record: &[u8]) -> Cow<'_, str> {
+        if !self.dated {
+            return Cow::Borrowed(&self.index);
+        }
+        let date = record_date(record).unwrap_or_else(|| {
+            let today = OffsetDateTime::now_utc().date();
+            format!(
+                "{:04}-{:02}-{:02}",
+                today.year(),
+                today.month() as u8,
+                today.day()
+            )
+        });
+        Cow::Owned(
+            self.index
+                .replace("%Y", &date[0..4])
+                .replace("%m", &date[5..7])
+                .replace("%d", &date[8..10]),
+        )
+    }
+}
+
+/// `YYYY-MM-DD` from the first of [`DATE_FIELDS`] holding an ISO date.
+fn record_date(record: &[u8]) -> Option<String> {
+    let rec: Map<String, Value> = serde_json::from_slice(record).ok()?;
+    DATE_FIELDS.iter().find_map(|f| {
+        let s = rec.get(*f)?.as_str()?;
+        let b = s.as_bytes();
+        let iso = b.len() >= 10
+            && b[4] == b'-'
+            && b[7] == b'-'
+            && b[..10]
+                .iter()
+                .enumerate()
+                .all(|(i, c)| i == 4 || i == 7 || c.is_ascii_digit());
+        iso.then(|| s[..10].to_string())
+    })
+}
+
+fn records(blob: &[u8]) -> impl Iterator<Item = &[u8]> {
+    blob.split(|&b| b == b'\n').filter(|l| !l.is_empty())
+}
+
+/* -------------------- Bulk lines to a file -------------------- */
+
+/// Writes the bulk lines through another sink, ready for
+/// `curl --data-binary @out.ndjson .../_bulk`.
+pub struct BulkFileSink {
+    lines: BulkLines,
+    inner: Box<dyn Sink>,
+    buf: Vec<u8>,
+}
+
+impl BulkFileSink {
+    pub fn new(inner: Box<dyn Sink>, opts: &ElasticOptions) -> Self {
+        Self {
+            lines: BulkLines::new(&opts.index),
+            inner,
+            buf: Vec::new(),
+        }
+    }
+}
+
+impl Sink for BulkFileSink {
+    fn write_blob(&mut self, blob: &[u8]) -> Result<()> {
+        self.buf.clear();
+        for record in records(blob) {
+            self.lines.push(record, &mut self.buf);
+        }
+        self.inner.write_blob(&self.buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        self.inner.finish()
+    }
+}
+
+/* -------------------- Bulk requests to a cluster -------------------- */
+
+#[derive(Deserialize)]
+struct BulkResponse {
+    #[serde(default)]
+    errors: bool,
+    #[serde(default)]
+    items: Vec<Map<String, Value>>,
+}
+
+/// What one `_bulk` request came back with.
+enum Attempt {
+    Done(BulkResponse),
+    /// Worth retrying: a connection error, 429 or a 5xx.
+    Retry(String),
+}
+
+/// Sends batches to `<url>/_bulk` from the writer thread, so a slow cluster
+/// slows the run down instead of piling records up in memory.
+pub struct ElasticSink {
+    lines: BulkLines,
+    agent: ureq::Agent,
+    endpoint: String,
+    auth: Option<String>,
+    batch_bytes: usize,
+    /// The pending request body and where each record's pair starts in it.
+    body: Vec<u8>,
+    starts: Vec<usize>,
+    indexed: u64,
+    failed: u64,
+    first_error: Option<String>,
+}
+
+impl ElasticSink {
+    pub fn new(url: &str, opts: &ElasticOptions) -> Self {
+        Self {
+            lines: BulkLines::new(&opts.index),
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(120))
+                .build(),
+            endpoint: format!(
+                "{}/_bulk?filter_path=errors,items.*.status,items.*.error.type,items.*.error.reason",
+                url.trim_end_matches('/')
+            ),
+            auth: opts.auth.as_ref().map(ElasticAuth::header),
+            batch_bytes: opts.batch_bytes.max(1),
+            body: Vec::new(),
+            starts: Vec::new(),
+            indexed: 0,
+            failed: 0,
+            first_error: None,
+        }
+    }
+
+    /// Send the pending batch, retrying the request or the documents the
+    /// cluster pushed back on.
+    fn send(&mut self) -> Result<()> {
+        let mut body = std::mem::take(&mut self.body);
+        let mut starts = std::mem::take(&mut self.starts);
+        let mut attempt = 0;
+        while !starts.is_empty() {
+            let resp = match self.post(&body)? {
+                Attempt::Done(resp) => resp,
+                Attempt::Retry(_) if attempt < RETRIES => {
+                    thread::sleep(backoff(attempt));
+                    attempt += 1;
+                    continue;
+                }
+                Attempt::Retry(why) => {
+                    bail!("Elasticsearch bulk request failed after {RETRIES} retries: {why}")
+                }
+            };
+            if !resp.errors {
+                self.indexed += starts.len() as u64;
+                break;
+            }
+
+            // Keep only the documents rejected for back-pressure.
+            let mut retry_body = Vec::new();
+            let mut retry_starts = Vec::new();
+            for (i, &start) in starts.iter().enumerate() {
+                let result = resp.items.get(i).and_then(|item| item.values().next());
+                let status = result.and_then(|r| r.get("status")?.as_u64());
+                let error = result.and_then(|r| r.get("error"));
+                if status == Some(429) && attempt < RETRIES {
+                    let end = starts.get(i + 1).copied().unwrap_or(body.len());
+                    retry_starts.push(retry_body.len());
+                    retry_body.extend_from_slice(&body[start..end]);
+                } else if let Some(error) = error {
+                    self.failed += 1;
+                    self.first_error.get_or_insert_with(|| {
+                        let field = |k: &str| error.get(k).and_then(Value::as_str).unwrap_or("?");
+                        format!("{}: {}", field("type"), field("reason"))
+                    });
+                } else {
+                    self.indexed += 1;
+                }
+            }
+            if !retry_starts.is_empty() {
+                thread::sleep(backoff(attempt));
+                attempt += 1;
+            }
+            body = retry_body;
+            starts = retry_starts;
+        }
+
+        // Reuse the allocation for the next batch.
+        body.clear();
+        self.body = body;
+        Ok(())
+    }
+
+    fn post(&self, body: &[u8]) -> Result<Attempt> {
+        let mut req = self
+            .agent
+            .post(&self.endpoint)
+            .set("Content-Type", "application/x-ndjson");
+        if let Some(auth) = &self.auth {
+            req = req.set("Authorization", auth);
+        }
+        match req.send_bytes(body) {
+            Ok(resp) => {
+                let text = resp.into_string().context("read bulk response")?;
+                let parsed = serde_json::from_str(&text).context("parse bulk response")?;
+                Ok(Attempt::Done(parsed))
+            }
+            Err(ureq::Error::Status(code, resp)) if code == 429 || code >= 500 => Ok(
+                Attempt::Retry(format!("HTTP {code} {}", resp.status_text())),
+            ),
+            Err(ureq::Error::Status(code, resp)) => {
+                let text = resp.into_string().unwrap_or_default();
+                bail!("Elasticsearch rejected the bulk request (HTTP {code}): {text}")
+            }
+            Err(ureq::Error::Transport(e)) => Ok(Attempt::Retry(e.to_string())),
+        }
+    }
+}
+
+impl Sink for ElasticSink {
+    fn write_blob(&mut self, blob: &[u8]) -> Result<()> {
+        for record in records(blob) {
+            self.starts.push(self.body.len());
+            self.lines.push(record, &mut self.body);
+            if self.body.len() >= self.batch_bytes {
+                self.send()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.send()
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.send()?;
+        log::info!("Elasticsearch: {} documents indexed", self.indexed);
+        if self.failed > 0 {
+            bail!(
+                "documents rejected by Elasticsearch: {} (first: {})",
+                self.failed,
+                self.first_error.as_deref().unwrap_or("?")
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::tests::{serve, Capture};
+
+    #[test]
+    fn formats_bulk_pairs_with_dated_index() {
+        let opts = ElasticOptions {
+            index: "logs-%Y.%m.%d".into(),
+            url: None,
+            auth: None,
+            batch_bytes: 0,
+        };
+        let (capture, out) = Capture::new();
+
+        let mut sink: Box<dyn Sink> = Box::new(BulkFileSink::new(Box::new(capture), &opts));
+        sink.write_blob(
+            b"{\"ts\":\"2024-01-02T03:04:05Z\",\"a\":1}\n{\"@timestamp\":\"2023-12-31\"}\n",
+        )
+        .unwrap();
+        sink.finish().unwrap();
+        assert_eq!(
+            String::from_utf8(out.lock().unwrap().clone()).unwrap(),
+            "{\"create\":{\"_index\":\"logs-2024.01.02\"}}\n\
+             {\"ts\":\"2024-01-02T03:04:05Z\",\"a\":1}\n\
+             {\"create\":{\"_index\":\"logs-2023.12.31\"}}\n\
+             {\"@timestamp\":\"2023-12-31\"}\n"
+        );
+
+        let plain = BulkLines::new("web");
+        assert_eq!(plain.index_for(b"{\"ts\":\"2024-01-02\"}"), "web");
+        assert_eq!(record_date(b"{\"ts\":\"Mar  1 10:00:00\"}"), None);
+        assert_eq!(
+            ElasticAuth::Basic("elastic:changeme".into()).header(),
+            "Basic ZWxhc3RpYzpjaGFuZ2VtZQ=="
+        );
+    }
+
+    #[test]
+    fn retries_requests_and_pushed_back_documents() {
+        let (url, server) = serve(vec![
+            (503, "{}"),
+            (
+                200,
+                r#"{"errors":true,"items":[{"create":{"status":201}},{"create":{"status":429}},
+                   {"create":{"status":400,"error":{"type":"mapper_parsing_exception","reason":"bad"}}}]}"#,
+            ),
+            (200, r#"{"errors":false}"#),
+        ]);
+        let opts = ElasticOptions {
+            index: "web".into(),
+            url: Some(url.clone()),
+            auth: None,
+            batch_bytes: 1 << 20,
+        };
+        let mut sink: Box<dyn Sink> = Box::new(ElasticSink::new(&url, &opts));
+        sink.write_blob(b"{\"a\":1}\n{\"a\":2}\n{\"a\":3}\n")
+            .unwrap();
+        let err = sink.finish().unwrap_err().to_string();
+        assert!(
+            err.contains("rejected by Elasticsearch: 1 (first: mapper_parsing_exception: bad)"),
+            "{err}"
+        );
+
+        let action = "{\"create\":{\"_index\":\"web\"}}\n";
+        let all = format!("{action}{{\"a\":1}}\n{action}{{\"a\":2}}\n{action}{{\"a\":3}}\n");
+        assert_eq!(
+            server
+                .join()
+                .unwrap()
+                .into_iter()
+                .map(|(_, body)| body)
+                .collect::<Vec<_>>(),
+            [all.clone(), all, format!("{action}{{\"a\":2}}\n")]
+        );
+    }
+}
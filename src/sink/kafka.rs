@@ -0,0 +1,173 @@
+//! `--sink kafka`: every record published as one message to a Kafka topic.
+
+use std::{thread, time::Duration};
+
+use anyhow::{bail, Context, Result};
+use kafka::{
+    client::{Compression, RequiredAcks},
+    producer::{Producer, Record},
+};
+use serde_json::{Map, Value};
+
+use super::{backoff, Sink, RETRIES};
+use crate::expr::field;
+
+/// Producer-side compression of message sets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KafkaCompression {
+    Gzip,
+    Snappy,
+}
+
+/// Kafka producer settings.
+#[derive(Debug, Clone)]
+pub struct KafkaOptions {
+    /// Bootstrap brokers, `host:port`.
+    pub brokers: Vec<String>,
+    pub topic: String,
+    /// Record field (dotted for nested) whose value keys the message, so
+    /// records with the same value land in the same partition.
+    pub key_field: Option<String>,
+    pub compression: Option<KafkaCompression>,
+    /// Bytes of records gathered before a batch is produced.
+    pub batch_bytes: usize,
+}
+
+/// Produces batches from the writer thread and waits for the leader's ack,
+/// so a slow cluster slows the run. A failed batch is retried whole after
+/// refreshing metadata: delivery is at least once.
+pub struct KafkaSink {
+    producer: Producer,
+    topic: String,
+    key_field: Option<String>,
+    batch_bytes: usize,
+    /// Pending `(key, value)` messages and their total size.
+    batch: Vec<(Vec<u8>, Vec<u8>)>,
+    bytes: usize,
+    sent: u64,
+}
+
+impl KafkaSink {
+    pub fn new(opts: &KafkaOptions) -> Result<Self> {
+        let compression = match opts.compression {
+            None => Compression::NONE,
+            Some(KafkaCompression::Gzip) => Compression::GZIP,
+            Some(KafkaCompression::Snappy) => Compression::SNAPPY,
+        };
+        let producer = Producer::from_hosts(opts.brokers.clone())
+            .with_compression(compression)
+            .with_required_acks(RequiredAcks::One)
+            .with_ack_timeout(Duration::from_secs(30))
+            .with_client_id("turbolp".to_string())
+            .create()
+            .with_context(|| format!("connect to Kafka at {}", opts.brokers.join(",")))?;
+        if !producer.client().topics().contains(&opts.topic) {
+            bail!("Kafka topic {} does not exist", opts.topic);
+        }
+        Ok(Self {
+            producer,
+            topic: opts.topic.clone(),
+            key_field: opts.key_field.clone(),
+            batch_bytes: opts.batch_bytes.max(1),
+            batch: Vec::new(),
+            bytes: 0,
+            sent: 0,
+        })
+    }
+
+    fn send(&mut self) -> Result<()> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+        let records: Vec<_> = self
+            .batch
+            .iter()
+            .map(|(k, v)| Record::from_key_value(&self.topic, k.as_slice(), v.as_slice()))
+            .collect();
+        let mut attempt = 0;
+        loop {
+            let why = match self.producer.send_all(&records) {
+                Ok(confirms) => match confirms
+                    .iter()
+                    .flat_map(|c| &c.partition_confirms)
+                    .find_map(|p| p.offset.err().map(|e| (p.partition, e)))
+                {
+                    None => break,
+                    Some((partition, code)) => format!("partition {partition}: {code:?}"),
+                },
+                Err(e) => e.to_string(),
+            };
+            if attempt == RETRIES {
+                bail!(
+                    "Kafka produce to {} failed after {RETRIES} retries: {why}",
+                    self.topic
+                );
+            }
+            thread::sleep(backoff(attempt));
+            attempt += 1;
+            // A leader may have moved.
+            let _ = self.producer.client_mut().load_metadata_all();
+        }
+        self.sent += self.batch.len() as u64;
+        self.batch.clear();
+        self.bytes = 0;
+        Ok(())
+    }
+}
+
+impl Sink for KafkaSink {
+    fn write_blob(&mut self, blob: &[u8]) -> Result<()> {
+        for record in blob.split(|&b| b == b'\n').filter(|l| !l.is_empty()) {
+            let key = self
+                .key_field
+                .as_deref()
+                .map(|f| record_key(record, f))
+                .unwrap_or_default();
+            self.bytes += key.len() + record.len();
+            self.batch.push((key, record.to_vec()));
+            if self.bytes >= self.batch_bytes {
+                self.send()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.send()
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.send()?;
+        log::info!("Kafka: {} messages produced to {}", self.sent, self.topic);
+        Ok(())
+    }
+}
+
+/// The message key for `record`: the field's string value, or its JSON for
+/// other values. Empty (no key) when the field is missing or null.
+fn record_key(record: &[u8], path: &str) -> Vec<u8> {
+    let Ok(rec) = serde_json::from_slice::<Map<String, Value>>(record) else {
+        return Vec::new();
+    };
+    match field(&rec, path) {
+        None | Some(Value::Null) => Vec::new(),
+        Some(Value::String(s)) => s.clone().into_bytes(),
+        Some(v) => v.to_string().into_bytes(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_messages_by_field() {
+        let rec = br#"{"ip":"1.2.3.4","status":503,"parsed":{"user":"bob"},"ident":null}"#;
+        assert_eq!(record_key(rec, "ip"), b"1.2.3.4");
+        assert_eq!(record_key(rec, "status"), b"503");
+        assert_eq!(record_key(rec, "parsed.user"), b"bob");
+        assert!(record_key(rec, "ident").is_empty());
+        assert!(record_key(rec, "missing").is_empty());
+    }
+}
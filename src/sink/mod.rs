@@ -0,0 +1,682 @@
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use flate2::write::GzEncoder;
+use memchr::memchr_iter;
+
+use crate::core::FieldType;
+
+mod columnar;
+mod columns;
+pub mod csv;
+pub mod elastic;
+pub mod kafka;
+pub mod splunk;
+pub mod sqlite;
+
+use columnar::{Columnar, ColumnarSink};
+use csv::CsvSink;
+use elastic::{BulkFileSink, ElasticSink};
+pub use elastic::{ElasticAuth, ElasticOptions};
+use kafka::KafkaSink;
+pub use kafka::{KafkaCompression, KafkaOptions};
+pub use splunk::HecOptions;
+use splunk::HecSink;
+use sqlite::SqliteSink;
+
+/// Failed requests to a cluster or collector are retried this many times,
+/// backing off from half a second and doubling each time.
+pub(crate) const RETRIES: u32 = 5;
+
+pub(crate) fn backoff(attempt: u32) -> std::time::Duration {
+    std::time::Duration::from_millis(500) * 2u32.pow(attempt)
+}
+
+/// Record fields an event date or time is taken from, in order.
+pub(crate) const DATE_FIELDS: &[&str] = &["@timestamp", "ts", "time", "start"];
+
+/* -------------------- Sink trait -------------------- */
+
+/// Destination of the writer thread. Blobs always hold complete JSONL records.
+pub trait Sink: Send {
+    fn write_blob(&mut self, blob: &[u8]) -> Result<()>;
+
+    /// Push buffered data out (used by follow mode).
+    fn flush(&mut self) -> Result<()>;
+
+    /// Write trailers and close. Called once, after the last blob.
+    fn finish(self: Box<Self>) -> Result<()>;
+
+    /// The fields the module declares (`Parser::fields`), before the first
+    /// blob. Only sinks with a fixed schema use them.
+    fn declare(&mut self, _fields: &[(String, FieldType)]) {}
+}
+
+/// A service `--sink` sends records to instead of writing them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RemoteSink {
+    /// Splunk HTTP Event Collector.
+    SplunkHec,
+    /// A Kafka topic.
+    Kafka,
+}
+
+/// Settings of the `--sink` service.
+#[derive(Debug, Clone)]
+pub enum RemoteOptions {
+    SplunkHec(HecOptions),
+    Kafka(KafkaOptions),
+}
+
+/// How the writer thread should lay out its output.
+#[derive(Debug, Clone, Default)]
+pub struct SinkOptions {
+    pub compress: Option<OutputCompression>,
+    pub shard: ShardLimits,
+    /// Write Elasticsearch `_bulk` lines, or send them to a cluster.
+    pub elastic: Option<ElasticOptions>,
+    /// Send records to a service instead of writing them.
+    pub remote: Option<RemoteOptions>,
+    pub format: OutputFormat,
+    /// Table records go to with `--format sqlite`; `records` when unset.
+    pub table: Option<String>,
+    /// Columns of `--format csv` / `tsv`, in order; from the records when empty.
+    pub columns: Vec<String>,
+    /// Add to an existing JSONL output file instead of replacing it.
+    pub append: bool,
+}
+
+impl SinkOptions {
+    /// The service records are sent to, when they are not written out.
+    pub fn remote_name(&self) -> Option<String> {
+        match (&self.remote, &self.elastic) {
+            (Some(RemoteOptions::SplunkHec(hec)), _) => Some(hec.url.clone()),
+            (Some(RemoteOptions::Kafka(k)), _) => Some(format!(
+                "Kafka topic {} at {}",
+                k.topic,
+                k.brokers.join(",")
+            )),
+            (None, Some(es)) => es.url.clone(),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Open the sink for `output` (stdout when `None`), creating parent directories.
+pub fn open_sink(output: Option<&Path>, opts: &SinkOptions) -> Result<Box<dyn Sink>> {
+    if let Some(remote) = &opts.remote {
+        if output.is_some() || opts.compress.is_some() || opts.shard.is_enabled() {
+            anyhow::bail!("--sink sends records to a service; drop --output and its options");
+        }
+        if opts.elastic.is_some() {
+            anyhow::bail!("--sink cannot be combined with --es-index");
+        }
+        return Ok(match remote {
+            RemoteOptions::SplunkHec(hec) => Box::new(HecSink::new(hec)),
+            RemoteOptions::Kafka(kafka) => Box::new(KafkaSink::new(kafka)?),
+        });
+    }
+    if let Some(delimiter) = opts.format.delimiter() {
+        if opts.shard.is_enabled() || opts.elastic.is_some() {
+            anyhow::bail!(
+                "--format {} cannot be combined with sharding or --es-index",
+                opts.format.name()
+            );
+        }
+        let inner = open_jsonl_sink(output, opts)?;
+        return Ok(Box::new(CsvSink::new(inner, delimiter, &opts.columns)));
+    }
+    if opts.format != OutputFormat::Jsonl {
+        let name = opts.format.name();
+        if opts.compress.is_some() || opts.shard.is_enabled() || opts.elastic.is_some() {
+            anyhow::bail!(
+                "--format {name} cannot be combined with --output-compress, sharding or --es-index"
+            );
+        }
+        if let Some(path) = output {
+            create_parent(path)?;
+        }
+        let create = |path: &Path| -> Result<Box<dyn Write + Send>> {
+            let fh = File::create(path).with_context(|| format!("create {}", path.display()))?;
+            Ok(Box::new(BufWriter::new(fh)))
+        };
+        return Ok(match (opts.format, output) {
+            (OutputFormat::Sqlite, Some(path)) => {
+                let table = opts.table.as_deref().unwrap_or("records");
+                Box::new(SqliteSink::open(path, table)?)
+            }
+            (OutputFormat::Parquet, Some(path)) => {
+                Box::new(ColumnarSink::new(Columnar::Parquet, create(path)?))
+            }
+            (OutputFormat::Arrow, Some(path)) => {
+                Box::new(ColumnarSink::new(Columnar::ArrowIpc, create(path)?))
+            }
+            (OutputFormat::Arrow, None) => Box::new(ColumnarSink::new(
+                Columnar::ArrowIpc,
+                Box::new(BufWriter::new(std::io::stdout())),
+            )),
+            (_, None) => anyhow::bail!("--format {name} requires --output"),
+            (_, Some(_)) => unreachable!(),
+        });
+    }
+    let Some(es) = &opts.elastic else {
+        return open_jsonl_sink(output, opts);
+    };
+    if opts.shard.is_enabled() {
+        anyhow::bail!("Elasticsearch bulk output cannot be sharded");
+    }
+    match &es.url {
+        Some(url) if output.is_some() || opts.compress.is_some() => {
+            anyhow::bail!(
+                "--es-url {url} sends records to the cluster; drop --output and --output-compress"
+            )
+        }
+        Some(url) => Ok(Box::new(ElasticSink::new(url, es))),
+        None => Ok(Box::new(BulkFileSink::new(
+            open_jsonl_sink(output, opts)?,
+            es,
+        ))),
+    }
+}
+
+fn open_jsonl_sink(output: Option<&Path>, opts: &SinkOptions) -> Result<Box<dyn Sink>> {
+    let Some(path) = output else {
+        if opts.shard.is_enabled() {
+            anyhow::bail!("output sharding requires --output");
+        }
+        return Ok(Box::new(WriterSink::new(
+            Box::new(std::io::stdout()),
+            opts.compress,
+        )?));
+    };
+
+    create_parent(path)?;
+
+    if opts.shard.is_enabled() {
+        return Ok(Box::new(ShardedFileSink::new(
+            path,
+            opts.compress,
+            opts.shard,
+        )));
+    }
+
+    let fh = File::options()
+        .write(true)
+        .create(true)
+        .append(opts.append)
+        .truncate(!opts.append)
+        .open(path)
+        .with_context(|| format!("create {}", path.display()))?;
+    Ok(Box::new(WriterSink::new(Box::new(fh), opts.compress)?))
+}
+
+fn create_parent(path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("create output directory {}", parent.display()))?;
+    }
+    Ok(())
+}
+
+/// Files actually written for `output`: the file itself, or its shards.
+pub fn output_files(output: &Path, opts: &SinkOptions) -> Vec<PathBuf> {
+    if !opts.shard.is_enabled() {
+        return vec![output.to_path_buf()];
+    }
+
+    (1..)
+        .map(|i| shard_path(output, i))
+        .take_while(|p| p.exists())
+        .collect()
+}
+
+/* -------------------- Compression -------------------- */
+
+const WRITER_BUF: usize = 32 << 20; // 32 MiB
+
+/// Layout of the records written to `--output`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// One JSON object per line.
+    #[default]
+    Jsonl,
+    /// Rows of a table in a SQLite database.
+    Sqlite,
+    /// A Parquet file, zstd-compressed.
+    Parquet,
+    /// An Arrow IPC stream, to stdout without `--output`.
+    Arrow,
+    /// Comma-separated values under a header row.
+    Csv,
+    /// Tab-separated values under a header row.
+    Tsv,
+}
+
+impl OutputFormat {
+    /// The `--format` value, also the extension of files mirrored from `--input-dir`.
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputFormat::Jsonl => "jsonl",
+            OutputFormat::Sqlite => "sqlite",
+            OutputFormat::Parquet => "parquet",
+            OutputFormat::Arrow => "arrow",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Tsv => "tsv",
+        }
+    }
+
+    /// The cell separator of the delimited formats.
+    fn delimiter(self) -> Option<u8> {
+        match self {
+            OutputFormat::Csv => Some(b','),
+            OutputFormat::Tsv => Some(b'\t'),
+            _ => None,
+        }
+    }
+}
+
+/// Inline compression applied by the writer thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputCompression {
+    Gzip,
+    Zstd,
+}
+
+impl OutputCompression {
+    /// Conventional filename suffix, without the dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputCompression::Gzip => "gz",
+            OutputCompression::Zstd => "zst",
+        }
+    }
+}
+
+/// Output stream that may compress; `finish` must be called to write trailers.
+enum OutputWriter<W: Write> {
+    Plain(W),
+    Gzip(GzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> OutputWriter<W> {
+    fn new(inner: W, compression: Option<OutputCompression>) -> Result<Self> {
+        Ok(match compression {
+            None => OutputWriter::Plain(inner),
+            Some(OutputCompression::Gzip) => {
+                OutputWriter::Gzip(GzEncoder::new(inner, flate2::Compression::default()))
+            }
+            Some(OutputCompression::Zstd) => {
+                OutputWriter::Zstd(zstd::Encoder::new(inner, 0).context("init zstd encoder")?)
+            }
+        })
+    }
+
+    fn finish(self) -> Result<W> {
+        Ok(match self {
+            OutputWriter::Plain(w) => w,
+            OutputWriter::Gzip(gz) => gz.finish()?,
+            OutputWriter::Zstd(zs) => zs.finish()?,
+        })
+    }
+}
+
+impl<W: Write> Write for OutputWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            OutputWriter::Plain(w) => w.write(buf),
+            OutputWriter::Gzip(w) => w.write(buf),
+            OutputWriter::Zstd(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            OutputWriter::Plain(w) => w.flush(),
+            OutputWriter::Gzip(w) => w.flush(),
+            OutputWriter::Zstd(w) => w.flush(),
+        }
+    }
+}
+
+/// Buffered, optionally compressed stream.
+/// The buffer sits in front of the compressor so it sees large writes.
+struct Stream<W: Write> {
+    w: BufWriter<OutputWriter<W>>,
+}
+
+impl<W: Write> Stream<W> {
+    fn new(inner: W, compression: Option<OutputCompression>) -> Result<Self> {
+        Ok(Self {
+            w: BufWriter::with_capacity(WRITER_BUF, OutputWriter::new(inner, compression)?),
+        })
+    }
+
+    fn close(self) -> Result<()> {
+        let mut inner = self
+            .w
+            .into_inner()
+            .map_err(|e| anyhow::anyhow!("flush output: {}", e.error()))?
+            .finish()?;
+        inner.flush()?;
+        Ok(())
+    }
+}
+
+/* -------------------- Single-stream sink -------------------- */
+
+/// Writes everything to one stream (file, stdout, in-memory buffer...).
+pub struct WriterSink {
+    stream: Stream<Box<dyn Write + Send>>,
+}
+
+impl WriterSink {
+    pub fn new(
+        writer: Box<dyn Write + Send>,
+        compression: Option<OutputCompression>,
+    ) -> Result<Self> {
+        Ok(Self {
+            stream: Stream::new(writer, compression)?,
+        })
+    }
+}
+
+impl Sink for WriterSink {
+    fn write_blob(&mut self, blob: &[u8]) -> Result<()> {
+        self.stream.w.write_all(blob)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.stream.w.flush()?;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        self.stream.close()
+    }
+}
+
+/* -------------------- Sharded file sink -------------------- */
+
+/// When to roll to the next shard. Limits are checked on record boundaries,
+/// so a shard never splits a line. Sizes count uncompressed JSONL bytes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ShardLimits {
+    pub max_bytes: Option<u64>,
+    pub max_records: Option<u64>,
+}
+
+impl ShardLimits {
+    pub fn is_enabled(&self) -> bool {
+        self.max_bytes.is_some() || self.max_records.is_some()
+    }
+}
+
+/// Rolls `out.jsonl` into `out.0001.jsonl`, `out.0002.jsonl`, ...
+pub struct ShardedFileSink {
+    base: PathBuf,
+    compression: Option<OutputCompression>,
+    limits: ShardLimits,
+    index: usize,
+    current: Option<Stream<File>>,
+    bytes: u64,
+    records: u64,
+}
+
+impl ShardedFileSink {
+    pub fn new(base: &Path, compression: Option<OutputCompression>, limits: ShardLimits) -> Self {
+        Self {
+            base: base.to_path_buf(),
+            compression,
+            limits,
+            index: 0,
+            current: None,
+            bytes: 0,
+            records: 0,
+        }
+    }
+
+    fn open_next(&mut self) -> Result<()> {
+        self.index += 1;
+        let path = shard_path(&self.base, self.index);
+        let fh = File::create(&path).with_context(|| format!("create {}", path.display()))?;
+
+        self.current = Some(Stream::new(fh, self.compression)?);
+        self.bytes = 0;
+        self.records = 0;
+        Ok(())
+    }
+
+    fn is_full(&self) -> bool {
+        self.limits.max_bytes.is_some_and(|m| self.bytes >= m)
+            || self.limits.max_records.is_some_and(|m| self.records >= m)
+    }
+}
+
+impl Sink for ShardedFileSink {
+    fn write_blob(&mut self, blob: &[u8]) -> Result<()> {
+        let mut start = 0;
+        let mut ends = memchr_iter(b'\n', blob);
+
+        while start < blob.len() {
+            if self.current.is_none() || self.is_full() {
+                if let Some(done) = self.current.take() {
+                    done.close()?;
+                }
+                self.open_next()?;
+            }
+
+            // Take as many whole records as fit in the current shard.
+            let mut end = start;
+            for nl in ends.by_ref() {
+                self.bytes += (nl + 1 - end) as u64;
+                self.records += 1;
+                end = nl + 1;
+                if self.is_full() {
+                    break;
+                }
+            }
+            if end == start {
+                // Trailing bytes without a newline.
+                end = blob.len();
+            }
+
+            let stream = self.current.as_mut().expect("shard opened above");
+            stream.w.write_all(&blob[start..end])?;
+            start = end;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if let Some(s) = self.current.as_mut() {
+            s.w.flush()?;
+        }
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        if let Some(s) = self.current {
+            s.close()?;
+        }
+        Ok(())
+    }
+}
+
+/// `dir/out.jsonl.gz` + 3 => `dir/out.0003.jsonl.gz`.
+///
+/// A compression suffix and the final extension are kept at the end so
+/// shards stay recognizable by tools that look at extensions.
+pub fn shard_path(base: &Path, index: usize) -> PathBuf {
+    let name = base
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let mut stem = name.as_str();
+    let mut tail = String::new();
+
+    for ext in ["gz", "zst"] {
+        if let Some(s) = stem.strip_suffix(&format!(".{ext}")) {
+            tail = format!(".{ext}");
+            stem = s;
+            break;
+        }
+    }
+    if let Some(dot) = stem.rfind('.').filter(|&d| d > 0) {
+        tail = format!("{}{}", &stem[dot..], tail);
+        stem = &stem[..dot];
+    }
+
+    let mut shard = OsString::from(stem);
+    shard.push(format!(".{index:04}{tail}"));
+    base.with_file_name(shard)
+}
+
+/// Parse a human size such as `500000`, `64K`, `512MiB`, `1G` (binary units).
+pub fn parse_size(s: &str) -> Result<u64> {
+    let t = s.trim();
+    let digits_end = t.find(|c: char| !c.is_ascii_digit()).unwrap_or(t.len());
+    let (num, unit) = t.split_at(digits_end);
+
+    let n: u64 = num.parse().with_context(|| format!("invalid size '{s}'"))?;
+    let mult: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        "t" | "tb" | "tib" => 1 << 40,
+        other => anyhow::bail!("invalid size unit '{other}' in '{s}'"),
+    };
+
+    n.checked_mul(mult)
+        .with_context(|| format!("size '{s}' overflows"))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Keeps what is written to it, for sinks that write through another.
+    pub(crate) struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl Capture {
+        pub(crate) fn new() -> (Self, Arc<Mutex<Vec<u8>>>) {
+            let out = Arc::new(Mutex::new(Vec::new()));
+            (Self(out.clone()), out)
+        }
+    }
+
+    impl Sink for Capture {
+        fn write_blob(&mut self, blob: &[u8]) -> Result<()> {
+            self.0.lock().unwrap().extend_from_slice(blob);
+            Ok(())
+        }
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+        fn finish(self: Box<Self>) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Answer one request per entry of `responses` on a local port; the
+    /// handle returns each request's head and body.
+    pub(crate) fn serve(
+        responses: Vec<(u16, &'static str)>,
+    ) -> (String, std::thread::JoinHandle<Vec<(String, String)>>) {
+        use std::io::{BufRead, BufReader, Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let mut bodies = Vec::new();
+            for (status, reply) in responses {
+                let (mut conn, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(conn.try_clone().unwrap());
+                let mut head = String::new();
+                let mut len = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    head.push_str(&line);
+                    if let Some(v) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                        len = v.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; len];
+                reader.read_exact(&mut body).unwrap();
+                bodies.push((head, String::from_utf8(body).unwrap()));
+                write!(
+                    conn,
+                    "HTTP/1.1 {status} X\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{reply}",
+                    reply.len()
+                )
+                .unwrap();
+            }
+            bodies
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn shard_path_keeps_extension_and_compression_suffix() {
+        assert_eq!(
+            shard_path(Path::new("/out/web.jsonl"), 1),
+            PathBuf::from("/out/web.0001.jsonl")
+        );
+        assert_eq!(
+            shard_path(Path::new("/out/web.jsonl.gz"), 12),
+            PathBuf::from("/out/web.0012.jsonl.gz")
+        );
+        assert_eq!(shard_path(Path::new("out"), 2), PathBuf::from("out.0002"));
+    }
+
+    #[test]
+    fn parse_size_accepts_binary_units() {
+        assert_eq!(parse_size("123").unwrap(), 123);
+        assert_eq!(parse_size("64K").unwrap(), 64 << 10);
+        assert_eq!(parse_size("1GiB").unwrap(), 1 << 30);
+        assert!(parse_size("12X").is_err());
+    }
+
+    #[test]
+    fn sharded_sink_rolls_on_record_count() {
+        let dir = std::env::temp_dir().join(format!("turbolp-shard-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let base = dir.join("out.jsonl");
+
+        let limits = ShardLimits {
+            max_bytes: None,
+            max_records: Some(2),
+        };
+        let mut sink: Box<dyn Sink> = Box::new(ShardedFileSink::new(&base, None, limits));
+        sink.write_blob(b"{\"a\":1}\n{\"a\":2}\n{\"a\":3}\n")
+            .unwrap();
+        sink.write_blob(b"{\"a\":4}\n{\"a\":5}\n").unwrap();
+        sink.finish().unwrap();
+
+        let read = |i| std::fs::read_to_string(shard_path(&base, i)).unwrap();
+        assert_eq!(read(1), "{\"a\":1}\n{\"a\":2}\n");
+        assert_eq!(read(2), "{\"a\":3}\n{\"a\":4}\n");
+        assert_eq!(read(3), "{\"a\":5}\n");
+        assert!(!shard_path(&base, 4).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,265 @@
+//! `--format sqlite`: records inserted into a table of a SQLite database,
+//! ready for `sqlite3 logs.db 'SELECT ...'`.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use rusqlite::{params_from_iter, types::Value as Cell, Connection};
+use serde_json::{Map, Value};
+
+use super::{
+    columns::{text, ColumnType, Columns},
+    Sink,
+};
+
+/// Records looked at to settle the column types before the table is created.
+const SAMPLE: usize = 1000;
+
+/// Rows per transaction.
+const TRANSACTION_ROWS: usize = 50_000;
+
+/// Creates the table from the first records' fields (one column each, typed
+/// from their values, nested objects as JSON text) and inserts in large
+/// transactions. Fields first seen later are added as columns. An existing
+/// table is appended to, so several inputs can share one database.
+pub struct SqliteSink {
+    conn: Connection,
+    table: String,
+    columns: Columns,
+    /// Whether the table exists yet.
+    created: bool,
+    sample: Vec<Map<String, Value>>,
+    /// The `INSERT` for the current columns.
+    insert_sql: String,
+    in_transaction: usize,
+    inserted: u64,
+}
+
+impl SqliteSink {
+    pub fn open(path: &Path, table: &str) -> Result<Self> {
+        let conn =
+            Connection::open(path).with_context(|| format!("open database {}", path.display()))?;
+        conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;")?;
+
+        let mut columns = Columns::default();
+        let mut stmt = conn.prepare("SELECT name, type FROM pragma_table_info(?1)")?;
+        let existing = stmt
+            .query_map([table], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        drop(stmt);
+        for (name, ty) in &existing {
+            let ty = match ty.as_str() {
+                "INTEGER" => ColumnType::Int,
+                "REAL" => ColumnType::Float,
+                _ => ColumnType::Text,
+            };
+            columns.add(name, ty);
+        }
+
+        Ok(Self {
+            conn,
+            table: table.to_string(),
+            columns,
+            created: !existing.is_empty(),
+            sample: Vec::new(),
+            insert_sql: String::new(),
+            in_transaction: 0,
+            inserted: 0,
+        })
+    }
+
+    fn create_table(&mut self) -> Result<()> {
+        for rec in &self.sample {
+            self.columns.observe(rec);
+        }
+        let defs: Vec<_> = (0..self.columns.len())
+            .map(|i| self.column_def(i))
+            .collect();
+        self.conn
+            .execute_batch(&format!(
+                "CREATE TABLE {} ({})",
+                quote(&self.table),
+                defs.join(", ")
+            ))
+            .with_context(|| format!("create table {}", self.table))?;
+        self.created = true;
+        for rec in std::mem::take(&mut self.sample) {
+            self.insert(&rec)?;
+        }
+        Ok(())
+    }
+
+    fn column_def(&self, i: usize) -> String {
+        let ty = match self.columns.types[i] {
+            ColumnType::Bool | ColumnType::Int => "INTEGER",
+            ColumnType::Float => "REAL",
+            ColumnType::Null | ColumnType::Text => "TEXT",
+        };
+        format!("{} {ty}", quote(&self.columns.names[i]))
+    }
+
+    fn insert(&mut self, rec: &Map<String, Value>) -> Result<()> {
+        for i in self.columns.observe(rec) {
+            self.conn.execute_batch(&format!(
+                "ALTER TABLE {} ADD COLUMN {}",
+                quote(&self.table),
+                self.column_def(i)
+            ))?;
+            self.insert_sql.clear();
+        }
+        if self.insert_sql.is_empty() {
+            let names: Vec<_> = self.columns.names.iter().map(|n| quote(n)).collect();
+            self.insert_sql = format!(
+                "INSERT INTO {} ({}) VALUES ({})",
+                quote(&self.table),
+                names.join(", "),
+                vec!["?"; names.len()].join(", ")
+            );
+        }
+        if self.in_transaction == 0 {
+            self.conn.execute_batch("BEGIN")?;
+        }
+
+        let mut row = vec![Cell::Null; self.columns.len()];
+        for (k, v) in rec {
+            if let Some(i) = self.columns.position(k) {
+                row[i] = cell(v);
+            }
+        }
+        self.conn
+            .prepare_cached(&self.insert_sql)?
+            .execute(params_from_iter(row))?;
+
+        self.inserted += 1;
+        self.in_transaction += 1;
+        if self.in_transaction >= TRANSACTION_ROWS {
+            self.commit()?;
+        }
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<()> {
+        if self.in_transaction > 0 {
+            self.conn.execute_batch("COMMIT")?;
+            self.in_transaction = 0;
+        }
+        Ok(())
+    }
+}
+
+impl Sink for SqliteSink {
+    fn write_blob(&mut self, blob: &[u8]) -> Result<()> {
+        for line in blob.split(|&b| b == b'\n').filter(|l| !l.is_empty()) {
+            let rec: Map<String, Value> =
+                serde_json::from_slice(line).context("record is not a JSON object")?;
+            if self.created {
+                self.insert(&rec)?;
+            } else {
+                self.sample.push(rec);
+                if self.sample.len() >= SAMPLE {
+                    self.create_table()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if !self.created && !self.sample.is_empty() {
+            self.create_table()?;
+        }
+        self.commit()
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.flush()?;
+        log::info!("SQLite: {} rows into table {}", self.inserted, self.table);
+        Ok(())
+    }
+}
+
+/// `"name"`, with embedded quotes doubled.
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn cell(v: &Value) -> Cell {
+    match v {
+        Value::Null => Cell::Null,
+        Value::Bool(b) => Cell::Integer(*b as i64),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => Cell::Integer(i),
+            None => Cell::Real(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        other => Cell::Text(text(other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn creates_and_extends_table() {
+        let dir = std::env::temp_dir().join(format!("turbolp-sqlite-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = dir.join("logs.db");
+
+        let mut sink: Box<dyn Sink> = Box::new(SqliteSink::open(&db, "web_access").unwrap());
+        sink.write_blob(
+            b"{\"ip\":\"1.2.3.4\",\"status\":200,\"bytes\":null,\"ua\":{\"bot\":true}}\n\
+              {\"ip\":\"::1\",\"status\":404,\"bytes\":12.5,\"ua\":null}\n",
+        )
+        .unwrap();
+        sink.finish().unwrap();
+
+        // A second input appends, adding the field it brings.
+        let mut sink: Box<dyn Sink> = Box::new(SqliteSink::open(&db, "web_access").unwrap());
+        sink.write_blob(b"{\"ip\":\"5.6.7.8\",\"status\":500,\"ioc_match\":true}\n")
+            .unwrap();
+        sink.finish().unwrap();
+
+        let conn = Connection::open(&db).unwrap();
+        let schema: String = conn
+            .query_row(
+                "SELECT sql FROM sqlite_master WHERE name = 'web_access'",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(
+            schema,
+            "CREATE TABLE \"web_access\" (\"ip\" TEXT, \"status\" INTEGER, \"bytes\" REAL, \
+             \"ua\" TEXT, \"ioc_match\" INTEGER)"
+        );
+        type Row = (String, i64, Option<f64>, Option<String>, Option<i64>);
+        let rows: Vec<Row> = conn
+            .prepare("SELECT * FROM web_access")
+            .unwrap()
+            .query_map([], |r| {
+                Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?))
+            })
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            [
+                (
+                    "1.2.3.4".into(),
+                    200,
+                    None,
+                    Some("{\"bot\":true}".into()),
+                    None
+                ),
+                ("::1".into(), 404, Some(12.5), None, None),
+                ("5.6.7.8".into(), 500, None, None, Some(1)),
+            ]
+        );
+
+        drop(conn);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,420 @@
+//! `stats`: counts and numeric summaries over a module's records, for the
+//! triage questions ("top talkers", "5xx per hour") that need no export.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write as _,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{bail, Context, Result};
+use serde_json::{Map, Value};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{
+    core::{run_streaming_parallel, Parser, RunOptions, RunStats},
+    expr::field,
+    sink::{Sink, DATE_FIELDS},
+};
+
+/// What to compute.
+#[derive(Debug, Clone, Default)]
+pub struct StatsSpec {
+    /// Fields (dotted for nested) whose value combinations are counted.
+    pub by: Vec<String>,
+    /// Groups shown, most frequent first.
+    pub top: usize,
+    /// Width in seconds of the time buckets counted, if any.
+    pub bucket: Option<i64>,
+    /// Field holding the record time; the usual date fields when unset.
+    pub time_field: Option<String>,
+    /// Numeric fields summarized with min, max and average.
+    pub numeric: Vec<String>,
+}
+
+/// Count, sum and range of one numeric field.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Summary {
+    pub count: u64,
+    pub sum: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Summary {
+    fn add(&mut self, x: f64) {
+        if self.count == 0 || x < self.min {
+            self.min = x;
+        }
+        if self.count == 0 || x > self.max {
+            self.max = x;
+        }
+        self.count += 1;
+        self.sum += x;
+    }
+
+    pub fn avg(&self) -> f64 {
+        self.sum / self.count.max(1) as f64
+    }
+}
+
+/// The counts gathered over a run.
+#[derive(Debug, Default)]
+pub struct Aggregate {
+    pub records: u64,
+    /// Records per combination of `by` values (`null` for a missing one).
+    pub groups: HashMap<Vec<String>, u64>,
+    /// Records per bucket, keyed by the bucket's start in epoch seconds.
+    pub buckets: BTreeMap<i64, u64>,
+    /// Records without a usable time, when bucketing.
+    pub untimed: u64,
+    /// One per `numeric` field.
+    pub numeric: Vec<Summary>,
+}
+
+impl Aggregate {
+    fn add(&mut self, spec: &StatsSpec, rec: &Map<String, Value>) {
+        self.records += 1;
+        if !spec.by.is_empty() {
+            let key = spec.by.iter().map(|f| key_text(field(rec, f))).collect();
+            *self.groups.entry(key).or_default() += 1;
+        }
+        if let Some(width) = spec.bucket {
+            match record_time(rec, spec.time_field.as_deref()) {
+                Some(t) => *self.buckets.entry(t - t.rem_euclid(width)).or_default() += 1,
+                None => self.untimed += 1,
+            }
+        }
+        if self.numeric.len() < spec.numeric.len() {
+            self.numeric.resize(spec.numeric.len(), Summary::default());
+        }
+        for (f, summary) in spec.numeric.iter().zip(&mut self.numeric) {
+            if let Some(x) = field(rec, f).and_then(number) {
+                summary.add(x);
+            }
+        }
+    }
+
+    /// The `top` most frequent groups, ties by value.
+    pub fn top(&self, top: usize) -> Vec<(&[String], u64)> {
+        let mut groups: Vec<_> = self
+            .groups
+            .iter()
+            .map(|(k, n)| (k.as_slice(), *n))
+            .collect();
+        groups.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        groups.truncate(top);
+        groups
+    }
+
+    /// The tables printed by `stats`.
+    pub fn report(&self, spec: &StatsSpec) -> String {
+        let mut out = format!("Records: {}\n", self.records);
+        let pct = |n: u64| n as f64 * 100.0 / self.records.max(1) as f64;
+
+        if !spec.by.is_empty() {
+            let top = self.top(spec.top);
+            let _ = writeln!(
+                out,
+                "\nTop {} by {} (of {} groups):",
+                top.len(),
+                spec.by.join(", "),
+                self.groups.len()
+            );
+            let mut widths: Vec<_> = spec.by.iter().map(String::len).collect();
+            for (key, _) in &top {
+                for (w, v) in widths.iter_mut().zip(key.iter()) {
+                    *w = (*w).max(v.chars().count());
+                }
+            }
+            let row = |cells: &[&str]| {
+                let padded: Vec<_> = cells
+                    .iter()
+                    .zip(&widths)
+                    .map(|(c, w)| format!("{c:<w$}"))
+                    .collect();
+                padded.join("  ").trim_end().to_string()
+            };
+            let names: Vec<_> = spec.by.iter().map(String::as_str).collect();
+            let _ = writeln!(out, "  {:>10}  {:>7}  {}", "count", "%", row(&names));
+            for (key, n) in &top {
+                let cells: Vec<_> = key.iter().map(String::as_str).collect();
+                let _ = writeln!(out, "  {n:>10}  {:>6.2}%  {}", pct(*n), row(&cells));
+            }
+        }
+
+        if let Some(width) = spec.bucket {
+            let _ = write!(
+                out,
+                "\nPer {} of {}",
+                format_width(width),
+                spec.time_field.as_deref().unwrap_or("record time")
+            );
+            if self.untimed > 0 {
+                let _ = write!(out, " ({} records without one)", self.untimed);
+            }
+            out.push_str(":\n");
+            for (start, n) in &self.buckets {
+                let label = OffsetDateTime::from_unix_timestamp(*start)
+                    .ok()
+                    .and_then(|t| t.format(&Rfc3339).ok())
+                    .unwrap_or_else(|| start.to_string());
+                let _ = writeln!(out, "  {label:<25} {n:>10}");
+            }
+        }
+
+        if !spec.numeric.is_empty() {
+            let w = spec
+                .numeric
+                .iter()
+                .map(String::len)
+                .max()
+                .unwrap_or(0)
+                .max(5);
+            let _ = writeln!(
+                out,
+                "\n  {:<w$}  {:>10}  {:>14}  {:>14}  {:>14}",
+                "field", "count", "min", "max", "avg"
+            );
+            for (i, f) in spec.numeric.iter().enumerate() {
+                match self.numeric.get(i).filter(|s| s.count > 0) {
+                    Some(s) => {
+                        let _ = writeln!(
+                            out,
+                            "  {f:<w$}  {:>10}  {:>14}  {:>14}  {:>14.2}",
+                            s.count,
+                            s.min,
+                            s.max,
+                            s.avg()
+                        );
+                    }
+                    None => {
+                        let _ = writeln!(out, "  {f:<w$}  {:>10}", 0);
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Run `parser` over `input` and aggregate its records instead of writing them.
+pub fn collect(
+    parser: &dyn Parser,
+    input: &Path,
+    opts: &RunOptions,
+    spec: &StatsSpec,
+) -> Result<(RunStats, Aggregate)> {
+    let agg = Arc::new(Mutex::new(Aggregate::default()));
+    let sink = StatsSink {
+        spec: spec.clone(),
+        agg: agg.clone(),
+    };
+    let opts = RunOptions {
+        follow: false,
+        ..opts.clone()
+    };
+    let stats = run_streaming_parallel(parser, input, Box::new(sink), &opts)?;
+    let agg = std::mem::take(
+        &mut *agg
+            .lock()
+            .map_err(|_| anyhow::anyhow!("stats aggregate poisoned"))?,
+    );
+    Ok((stats, agg))
+}
+
+struct StatsSink {
+    spec: StatsSpec,
+    agg: Arc<Mutex<Aggregate>>,
+}
+
+impl Sink for StatsSink {
+    fn write_blob(&mut self, blob: &[u8]) -> Result<()> {
+        let mut agg = self
+            .agg
+            .lock()
+            .map_err(|_| anyhow::anyhow!("stats aggregate poisoned"))?;
+        for line in blob.split(|&b| b == b'\n').filter(|l| !l.is_empty()) {
+            let rec: Map<String, Value> =
+                serde_json::from_slice(line).context("record is not a JSON object")?;
+            agg.add(&self.spec, &rec);
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A bucket width such as `30s`, `5m`, `1h` or `1d` (plain numbers are
+/// seconds), in seconds.
+pub fn parse_bucket(s: &str) -> Result<i64> {
+    let s = s.trim();
+    let (num, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let n: i64 = num
+        .parse()
+        .with_context(|| format!("invalid bucket width {s:?}"))?;
+    let unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => bail!("invalid bucket unit in {s:?} (use s, m, h or d)"),
+    };
+    if n <= 0 {
+        bail!("bucket width must be positive: {s:?}");
+    }
+    Ok(n * unit)
+}
+
+fn format_width(secs: i64) -> String {
+    for (unit, size) in [("d", 86400), ("h", 3600), ("m", 60)] {
+        if secs % size == 0 {
+            return format!("{}{unit}", secs / size);
+        }
+    }
+    format!("{secs}s")
+}
+
+/// A group value as shown: strings as they are, missing as `null`.
+fn key_text(v: Option<&Value>) -> String {
+    match v {
+        None => "null".to_string(),
+        Some(Value::String(s)) => s.clone(),
+        Some(v) => v.to_string(),
+    }
+}
+
+/// Numbers, and strings holding one (`"1024"`).
+fn number(v: &Value) -> Option<f64> {
+    match v {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Epoch seconds of the record: an RFC 3339 string, or epoch seconds or
+/// milliseconds, in `time_field` or the first of [`DATE_FIELDS`] present.
+fn record_time(rec: &Map<String, Value>, time_field: Option<&str>) -> Option<i64> {
+    let v = match time_field {
+        Some(f) => field(rec, f)?,
+        None => DATE_FIELDS
+            .iter()
+            .find_map(|f| rec.get(*f).filter(|v| !v.is_null()))?,
+    };
+    match v {
+        Value::String(s) => OffsetDateTime::parse(s, &Rfc3339)
+            .ok()
+            .map(|t| t.unix_timestamp()),
+        Value::Number(n) => {
+            let x = n.as_f64()?;
+            // Past 5138 CE in seconds: milliseconds.
+            Some(if x.abs() >= 1e11 { x / 1000.0 } else { x }.floor() as i64)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn counts_groups_buckets_and_numbers() {
+        let spec = StatsSpec {
+            by: vec!["ip".into(), "status".into()],
+            top: 2,
+            bucket: Some(parse_bucket("1h").unwrap()),
+            time_field: None,
+            numeric: vec!["bytes".into()],
+        };
+        let mut agg = Aggregate::default();
+        for rec in [
+            json!({"ip": "1.2.3.4", "status": 200, "bytes": 10, "ts": "2024-01-02T03:04:05Z"}),
+            json!({"ip": "1.2.3.4", "status": 200, "bytes": "30", "ts": "2024-01-02T03:59:59Z"}),
+            json!({"ip": "::1", "status": 503, "bytes": null, "ts": "2024-01-02T05:00:00Z"}),
+            json!({"ip": "::1", "bytes": 2, "ts": "-"}),
+        ] {
+            agg.add(&spec, rec.as_object().unwrap());
+        }
+
+        assert_eq!(
+            agg.top(2),
+            [
+                (&["1.2.3.4".to_string(), "200".into()][..], 2),
+                (&["::1".to_string(), "503".into()][..], 1),
+            ]
+        );
+        assert_eq!(agg.groups.len(), 3);
+        assert_eq!(
+            agg.buckets.into_iter().collect::<Vec<_>>(),
+            [(1704164400, 2), (1704171600, 1)]
+        );
+        assert_eq!(agg.untimed, 1);
+        assert_eq!(
+            agg.numeric,
+            [Summary {
+                count: 3,
+                sum: 42.0,
+                min: 2.0,
+                max: 30.0
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_tables() {
+        let spec = StatsSpec {
+            by: vec!["ip".into()],
+            top: 10,
+            bucket: Some(3600),
+            time_field: Some("ts".into()),
+            numeric: vec!["bytes".into()],
+        };
+        let mut agg = Aggregate::default();
+        for rec in [
+            json!({"ip": "1.2.3.4", "ts": 1704164645, "bytes": 10}),
+            json!({"ip": "1.2.3.4", "ts": 1704164645000_i64, "bytes": 20}),
+            json!({"ip": "::1", "ts": null}),
+        ] {
+            agg.add(&spec, rec.as_object().unwrap());
+        }
+        assert_eq!(
+            agg.report(&spec),
+            "Records: 3\n\
+             \n\
+             Top 2 by ip (of 2 groups):\n\
+             \x20      count        %  ip\n\
+             \x20          2   66.67%  1.2.3.4\n\
+             \x20          1   33.33%  ::1\n\
+             \n\
+             Per 1h of ts (1 records without one):\n\
+             \x20 2024-01-02T03:00:00Z               2\n\
+             \n\
+             \x20 field       count             min             max             avg\n\
+             \x20 bytes           2              10              20           15.00\n"
+        );
+    }
+
+    #[test]
+    fn parses_bucket_widths() {
+        assert_eq!(parse_bucket("30s").unwrap(), 30);
+        assert_eq!(parse_bucket("5m").unwrap(), 300);
+        assert_eq!(parse_bucket("1d").unwrap(), 86400);
+        assert_eq!(parse_bucket("90").unwrap(), 90);
+        assert!(parse_bucket("0h").is_err());
+        assert!(parse_bucket("1w").is_err());
+        assert_eq!(format_width(5400), "90m");
+    }
+}
@@ -0,0 +1,191 @@
+//! `--transform`: reshape records with a few statements, run in order on
+//! each record, separated by `;`:
+//!
+//! ```text
+//! status_class = status / 100; method = lower(method)
+//! rename ip -> client.ip; del ident, raw
+//! ```
+//!
+//! `field = expr` sets a field (dotted names create nested objects),
+//! `rename old -> new` moves one and `del a, b` removes fields. Expressions
+//! are those of [`crate::expr`].
+
+use anyhow::{bail, Context, Result};
+use serde_json::{Map, Value};
+
+use crate::{
+    enrich::parent_mut,
+    expr::{Expr, ExprParser, Token},
+};
+
+/// Compiled `--transform` statements.
+#[derive(Debug, Default)]
+pub struct Transform {
+    steps: Vec<Step>,
+}
+
+#[derive(Debug)]
+enum Step {
+    Set(String, Expr),
+    Rename(String, String),
+    Delete(Vec<String>),
+}
+
+impl Transform {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut p = ExprParser::new(text)?;
+        let mut steps = Vec::new();
+        loop {
+            while p.eat(";", None) {}
+            if p.at_end() {
+                break;
+            }
+            steps.push(statement(&mut p)?);
+            if !p.at_end() {
+                p.expect(";")?;
+            }
+        }
+        Ok(Self { steps })
+    }
+
+    /// One transform out of several `--transform` texts, run in the given
+    /// order.
+    pub fn parse_all(texts: &[String]) -> Result<Self> {
+        let mut all = Self::default();
+        for text in texts {
+            let t = Self::parse(text).with_context(|| format!("--transform {text:?}"))?;
+            all.steps.extend(t.steps);
+        }
+        Ok(all)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn apply(&self, rec: &mut Map<String, Value>) {
+        for step in &self.steps {
+            match step {
+                Step::Set(path, expr) => {
+                    let v = expr.eval(rec);
+                    set(rec, path, v);
+                }
+                Step::Rename(from, to) => {
+                    if let Some((parent, key)) = parent_mut(rec, from)
+                        && let Some(v) = parent.shift_remove(key)
+                    {
+                        set(rec, to, v);
+                    }
+                }
+                Step::Delete(paths) => {
+                    for path in paths {
+                        if let Some((parent, key)) = parent_mut(rec, path) {
+                            parent.shift_remove(key);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn statement(p: &mut ExprParser) -> Result<Step> {
+    let keyword = match (p.peek(), p.peek_at(1)) {
+        (Some(Token::Ident(w)), next) if next != Some(&Token::Op("=")) => Some(w.clone()),
+        _ => None,
+    };
+    match keyword.as_deref() {
+        Some("del") => {
+            p.field_name()?;
+            let mut paths = vec![p.field_name()?];
+            while p.eat(",", None) {
+                paths.push(p.field_name()?);
+            }
+            Ok(Step::Delete(paths))
+        }
+        Some("rename") => {
+            p.field_name()?;
+            let from = p.field_name()?;
+            p.expect("->")?;
+            Ok(Step::Rename(from, p.field_name()?))
+        }
+        _ => {
+            let target = p.field_name()?;
+            if !p.eat("=", None) {
+                bail!(
+                    "expected `field = expr`, `rename a -> b` or `del a` {}",
+                    p.position()
+                );
+            }
+            Ok(Step::Set(target, p.expr()?))
+        }
+    }
+}
+
+/// Set `path`, creating the objects a dotted name goes through. An existing
+/// literal `a.b` key, or a non-object on the way, takes the value as is.
+pub(crate) fn set(rec: &mut Map<String, Value>, path: &str, v: Value) {
+    if rec.contains_key(path) {
+        rec.insert(path.to_string(), v);
+        return;
+    }
+    if let Some((head, rest)) = path.split_once('.') {
+        let slot = rec
+            .entry(head.to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+        if let Value::Object(inner) = slot {
+            set(inner, rest, v);
+            return;
+        }
+    }
+    rec.insert(path.to_string(), v);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn run(texts: &[&str], rec: Value) -> Value {
+        let texts: Vec<String> = texts.iter().map(|t| t.to_string()).collect();
+        let t = Transform::parse_all(&texts).unwrap_or_else(|e| panic!("{e:#}"));
+        let mut rec = rec.as_object().unwrap().clone();
+        t.apply(&mut rec);
+        Value::Object(rec)
+    }
+
+    #[test]
+    fn runs_statements_in_order() {
+        let rec = json!({"ip": "1.2.3.4", "status": 503, "method": "GET", "raw": "...", "id.orig_h": "x"});
+        let out = run(
+            &[
+                "status_class = status / 100; method = lower(method);",
+                "rename ip -> client.ip; del raw, missing; client.port = 443",
+                "rename `id.orig_h` -> src; del = 1",
+            ],
+            rec,
+        );
+        assert_eq!(
+            out.to_string(),
+            json!({
+                "status": 503, "method": "get", "status_class": 5,
+                "client": {"ip": "1.2.3.4", "port": 443}, "src": "x", "del": 1,
+            })
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn reports_bad_statements() {
+        for (text, msg) in [
+            ("status", "expected `field = expr`"),
+            ("a = 1 b = 2", "expected \";\""),
+            ("rename a b", "expected \"->\""),
+            ("del", "expected a field name"),
+            ("1 = a", "expected a field name"),
+        ] {
+            let err = format!("{:#}", Transform::parse(text).unwrap_err());
+            assert!(err.contains(msg), "{text}: {err}");
+        }
+    }
+}